@@ -10,6 +10,7 @@ use syn::{
 struct SimMain {
     id: Ident,
     inputs_path: String,
+    render_config_path: Option<String>,
 }
 
 impl Parse for SimMain {
@@ -18,15 +19,40 @@ impl Parse for SimMain {
         let _comma: Comma = input.parse()?;
         let id = input.parse()?;
 
+        let render_config_path = if input.peek(Comma) {
+            let _comma: Comma = input.parse()?;
+            let path: LitStr = input.parse()?;
+            Some(path.value())
+        } else {
+            None
+        };
+
         Ok(Self {
             id,
             inputs_path: inputs_path.value(),
+            render_config_path,
         })
     }
 }
 
+fn render_config(render_config_path: Option<String>) -> Option<TokenStream> {
+    render_config_path.map(|path| {
+        quote! {
+            let render_config_src = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), #path));
+            aftgraphs::render::RenderConfig::set_default(Some(
+                aftgraphs::render::RenderConfig::from_toml(render_config_src).unwrap(),
+            ));
+        }
+    })
+}
+
 fn sim_main_impl(input: TokenStream) -> TokenStream {
-    let SimMain { id, inputs_path } = parse2(input).expect("did not encounter Ident");
+    let SimMain {
+        id,
+        inputs_path,
+        render_config_path,
+    } = parse2(input).expect("did not encounter Ident");
+    let render_config = render_config(render_config_path);
 
     quote! {
         #[cfg(target_arch = "wasm32")]
@@ -37,6 +63,7 @@ fn sim_main_impl(input: TokenStream) -> TokenStream {
         pub fn sim_main() {
             let inputs_src = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), #inputs_path));
             let inputs = aftgraphs::input::Inputs::new(inputs_src).unwrap();
+            #render_config
             aftgraphs::sim_main::<#id>(
                 inputs,
             );
@@ -46,6 +73,10 @@ fn sim_main_impl(input: TokenStream) -> TokenStream {
         pub fn sim_main() {
             let inputs_src = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), #inputs_path));
             let inputs = aftgraphs::input::Inputs::new(inputs_src).unwrap();
+            // Only debug builds know where their sources are, for --watch
+            #[cfg(debug_assertions)]
+            let inputs = inputs.with_source(concat!(env!("CARGO_MANIFEST_DIR"), #inputs_path));
+            #render_config
             aftgraphs::sim_main::<#id>(
                 inputs,
             );
@@ -53,10 +84,54 @@ fn sim_main_impl(input: TokenStream) -> TokenStream {
     }
 }
 
+fn sim_plugin_impl(input: TokenStream) -> TokenStream {
+    let SimMain {
+        id,
+        inputs_path,
+        render_config_path,
+    } = parse2(input).expect("did not encounter Ident");
+    let render_config = render_config(render_config_path);
+    let name = format!("{id}\0");
+
+    quote! {
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe extern "C" fn aftgraphs_plugin_run(
+            argc: usize,
+            argv: *const *const ::std::ffi::c_char,
+        ) {
+            let args = aftgraphs::plugin::plugin_args(argc, argv);
+            let inputs_src = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), #inputs_path));
+            let inputs = aftgraphs::input::Inputs::new(inputs_src).unwrap();
+            // Only debug builds know where their sources are, for --watch
+            #[cfg(debug_assertions)]
+            let inputs = inputs.with_source(concat!(env!("CARGO_MANIFEST_DIR"), #inputs_path));
+            #render_config
+            aftgraphs::sim_main_with_args::<#id>(inputs, args);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        #[no_mangle]
+        pub static AFTGRAPHS_PLUGIN: aftgraphs::plugin::PluginDeclaration =
+            aftgraphs::plugin::PluginDeclaration {
+                abi_version: aftgraphs::plugin::PLUGIN_ABI_VERSION,
+                aftgraphs_version: aftgraphs::plugin::AFTGRAPHS_VERSION.as_ptr(),
+                name: #name.as_ptr().cast(),
+                run: aftgraphs_plugin_run,
+            };
+    }
+}
+
 // Macro parameters:
 //   str literal containing path to simulation TOML (concat'd to CARGO_MANIFEST_DIR)
 //   identifier literal which is the name of the simulation struct type
+//   optional str literal containing path to a RenderConfig TOML (concat'd to CARGO_MANIFEST_DIR)
 #[proc_macro]
 pub fn sim_main(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     sim_main_impl(input.into()).into()
 }
+
+// Exports the simulation from a cdylib for aftgraphs::plugin::host_main, same parameters as sim_main!
+#[proc_macro]
+pub fn sim_plugin(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    sim_plugin_impl(input.into()).into()
+}
@@ -0,0 +1,212 @@
+use aftgraphs::{
+    headless,
+    input::{InputState, InputValue},
+    prelude::*,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::collections::HashMap;
+
+const SIZE: (u32, u32) = (512, 512);
+const INSTANCE_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// Headless device for the GPU benchmarks
+/// Returns None if no adapter is available, skipping the benchmark
+fn fixture() -> Option<Renderer<'static, ()>> {
+    match pollster::block_on(headless::init(SIZE)) {
+        Ok(renderer) => Some(renderer),
+        Err(e) => {
+            eprintln!("aftgraphs benches: skipping GPU benchmark: {e}");
+            None
+        }
+    }
+}
+
+fn uniform(c: &mut Criterion) {
+    let Some(renderer) = fixture() else {
+        return;
+    };
+
+    let layout = BindGroupLayoutBuilder::new()
+        .with_label(Some("benches::uniform"))
+        .with_entry(BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX,
+            ty: BINDING_UNIFORM_BUFFER,
+            count: None,
+        })
+        .build(&renderer);
+    let mut uniform: Uniform<[f32; 4]> = UniformBuilder::new()
+        .with_label(Some("benches::uniform"))
+        .with_bind_group_layout(layout)
+        .with_zero_data()
+        .build(&renderer);
+
+    let mut group = c.benchmark_group("uniform");
+
+    let mut value = 0.0;
+    group.bench_function("update", |b| {
+        b.iter(|| {
+            value += 1.0;
+            uniform.update(&renderer, [value; 4]);
+        })
+    });
+
+    group.bench_function("update_unchanged", |b| {
+        b.iter(|| uniform.update(&renderer, [value; 4]))
+    });
+
+    group.bench_function("modify", |b| {
+        b.iter(|| {
+            let mut guard = uniform.modify(&renderer);
+            guard[0] += 1.0;
+        })
+    });
+
+    group.finish();
+}
+
+fn instance_buffer(c: &mut Criterion) {
+    let Some(renderer) = fixture() else {
+        return;
+    };
+
+    let build = |count: usize| {
+        InstanceBufferBuilder::<[f32; 2], [f32; 4]>::new()
+            .with_initial_vertices(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+            .with_initial_instances_owned(vec![[0.0; 4]; count])
+            .build(&renderer)
+    };
+
+    let mut group = c.benchmark_group("instance_buffer");
+    for count in INSTANCE_COUNTS {
+        group.throughput(Throughput::Elements(count as u64));
+
+        // Same length, buffered with a queue write
+        let mut buffer = build(count);
+        group.bench_with_input(BenchmarkId::new("write", count), &count, |b, _| {
+            b.iter(|| {
+                let mut guard = buffer.modify(&renderer);
+                guard.instances_mut()[0][0] += 1.0;
+            })
+        });
+
+        // Changed length, the buffer is recreated
+        group.bench_with_input(BenchmarkId::new("grow", count), &count, |b, &count| {
+            b.iter_batched(
+                || build(count),
+                |mut buffer| {
+                    let mut guard = buffer.modify(&renderer);
+                    guard.instances_push([1.0; 4]);
+                    drop(guard);
+                    buffer
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn input_map(c: &mut Criterion) {
+    let values: HashMap<String, InputValue> = (0..64)
+        .map(|idx| {
+            (
+                format!("inputs.slider {idx}"),
+                InputValue::SLIDER(idx as f64),
+            )
+        })
+        .collect();
+
+    let state = InputState::default();
+    pollster::block_on(async {
+        state.lock().await.as_mut().extend(values.clone());
+    });
+
+    let mut group = c.benchmark_group("input_map");
+
+    group.bench_function("hash_map_get", |b| {
+        b.iter(|| values.get("inputs.slider 42").cloned())
+    });
+
+    group.bench_function("input_state_lock_get", |b| {
+        b.iter(|| {
+            pollster::block_on(async {
+                let guard = state.lock().await;
+                guard.get("inputs.slider 42").cloned()
+            })
+        })
+    });
+
+    group.finish();
+}
+
+/// Draws a fixed number of markers every frame
+struct MarkerSimulation {
+    markers: MarkerBuffer,
+}
+
+impl Simulation for MarkerSimulation {
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        _inputs: &mut HashMap<String, InputValue>,
+    ) {
+        self.markers.draw(renderer, &mut render_pass);
+    }
+
+    async fn on_input(&mut self, _event: InputEvent) {}
+
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let markers = (0..10_000)
+            .map(|idx| {
+                let t = idx as f32 / 10_000.0;
+                Marker::new(
+                    [t * 2.0 - 1.0, (t * 50.0).sin()],
+                    4.0,
+                    MarkerShape::Circle,
+                    [1.0, t, 0.0, 1.0],
+                )
+            })
+            .collect();
+
+        Self {
+            markers: MarkerBuffer::with_vec(
+                renderer,
+                markers,
+                MarkerSizing::Pixels,
+                Some("benches::MarkerSimulation"),
+            ),
+        }
+    }
+}
+
+fn headless_frame(c: &mut Criterion) {
+    let Some(renderer) = fixture() else {
+        return;
+    };
+
+    let simulation = Arc::new(Mutex::new(pollster::block_on(MarkerSimulation::new(
+        &renderer,
+    ))));
+    let mut inputs = HashMap::new();
+    let mut out_img = vec![];
+
+    let mut group = c.benchmark_group("headless");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("frame", |b| {
+        b.iter(|| {
+            pollster::block_on(async {
+                renderer.render(simulation.clone(), &mut inputs).await;
+                renderer
+                    .render_headless_finish(&mut out_img)
+                    .await
+                    .expect("aftgraphs benches: headless frame failed");
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, uniform, instance_buffer, input_map, headless_frame);
+criterion_main!(benches);
@@ -0,0 +1,140 @@
+use aftgraphs::prelude::*;
+use rand::{distributions::Uniform, prelude::*};
+
+/// Boids see neighbors within this distance
+const VISION: f32 = 0.08;
+/// Boids steer away from neighbors closer than this
+const PERSONAL_SPACE: f32 = 0.025;
+
+pub const MIN_SPEED: f32 = 0.15;
+pub const MAX_SPEED: f32 = 0.4;
+
+/// Steering weights of the three boid rules
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+}
+
+/// Positions and velocities of the boids in world space
+/// World space is clip space with y divided by the aspect ratio, so distances are
+/// the same in every direction. The world wraps around at the edges of the window.
+pub struct Flock {
+    pub positions: Vec<[f32; 2]>,
+    pub velocities: Vec<[f32; 2]>,
+    hash: SpatialHash,
+    neighbors: Vec<usize>,
+    rng: ThreadRng,
+}
+
+impl Flock {
+    pub fn new() -> Self {
+        Self {
+            positions: vec![],
+            velocities: vec![],
+            hash: SpatialHash::new(VISION),
+            neighbors: vec![],
+            rng: thread_rng(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Add or remove boids until there are count, new boids start anywhere in the world
+    pub fn resize(&mut self, count: usize, aspect_ratio: f32) {
+        self.positions.truncate(count);
+        self.velocities.truncate(count);
+
+        let x = Uniform::new_inclusive(-1.0, 1.0);
+        let y = Uniform::new_inclusive(-1.0 / aspect_ratio, 1.0 / aspect_ratio);
+        let speed = Uniform::new_inclusive(MIN_SPEED, MAX_SPEED);
+        let angle = Uniform::new(0.0, std::f32::consts::TAU);
+
+        while self.positions.len() < count {
+            self.positions
+                .push([self.rng.sample(x), self.rng.sample(y)]);
+
+            let (speed, angle) = (self.rng.sample(speed), self.rng.sample(angle));
+            self.velocities
+                .push([speed * angle.cos(), speed * angle.sin()]);
+        }
+    }
+
+    /// Apply the boid rules and move every boid by dt
+    pub fn step(&mut self, dt: f32, weights: Weights, aspect_ratio: f32) {
+        self.hash.build(self.positions.iter().copied());
+
+        let mut accelerations = Vec::with_capacity(self.len());
+        for (idx, &position) in self.positions.iter().enumerate() {
+            self.neighbors.clear();
+            self.hash.query_into(position, VISION, &mut self.neighbors);
+
+            let mut separation = [0.0; 2];
+            let mut mean_velocity = [0.0; 2];
+            let mut center = [0.0; 2];
+            let mut count = 0.0;
+
+            for &neighbor in self.neighbors.iter().filter(|&&neighbor| neighbor != idx) {
+                let other = self.positions[neighbor];
+                let offset = [position[0] - other[0], position[1] - other[1]];
+                let distance_sq = offset[0] * offset[0] + offset[1] * offset[1];
+
+                // Push apart harder the closer the neighbor is
+                if distance_sq < PERSONAL_SPACE * PERSONAL_SPACE && distance_sq > 0.0 {
+                    separation[0] += offset[0] / distance_sq * PERSONAL_SPACE;
+                    separation[1] += offset[1] / distance_sq * PERSONAL_SPACE;
+                }
+
+                let velocity = self.velocities[neighbor];
+                mean_velocity[0] += velocity[0];
+                mean_velocity[1] += velocity[1];
+                center[0] += other[0];
+                center[1] += other[1];
+                count += 1.0;
+            }
+
+            let mut acceleration = [
+                weights.separation * separation[0],
+                weights.separation * separation[1],
+            ];
+            if count > 0.0 {
+                let velocity = self.velocities[idx];
+                for axis in 0..2 {
+                    acceleration[axis] += weights.alignment
+                        * (mean_velocity[axis] / count - velocity[axis])
+                        + weights.cohesion * (center[axis] / count - position[axis]) * 10.0;
+                }
+            }
+            accelerations.push(acceleration);
+        }
+
+        let half_height = 1.0 / aspect_ratio;
+        for ((position, velocity), acceleration) in self
+            .positions
+            .iter_mut()
+            .zip(self.velocities.iter_mut())
+            .zip(accelerations)
+        {
+            velocity[0] += acceleration[0] * dt;
+            velocity[1] += acceleration[1] * dt;
+
+            let speed = velocity[0].hypot(velocity[1]);
+            if speed > 0.0 {
+                let clamped = speed.clamp(MIN_SPEED, MAX_SPEED);
+                velocity[0] *= clamped / speed;
+                velocity[1] *= clamped / speed;
+            }
+
+            position[0] = wrap(position[0] + velocity[0] * dt, 1.0);
+            position[1] = wrap(position[1] + velocity[1] * dt, half_height);
+        }
+    }
+}
+
+/// Wrap a coordinate into [-half, half)
+fn wrap(coordinate: f32, half: f32) -> f32 {
+    (coordinate + half).rem_euclid(2.0 * half) - half
+}
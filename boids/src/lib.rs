@@ -0,0 +1,108 @@
+use aftgraphs::prelude::*;
+use aftgraphs_macros::sim_main;
+use std::collections::HashMap;
+
+mod flock;
+use flock::{Flock, Weights, MAX_SPEED, MIN_SPEED};
+
+const COUNT: usize = 1000;
+const WEIGHTS: Weights = Weights {
+    separation: 1.5,
+    alignment: 1.0,
+    cohesion: 1.0,
+};
+
+/// Boid diameter in pixels
+const BOID_SIZE: f32 = 8.0;
+
+/// Longest time step, so a stalled frame does not scatter the flock
+const MAX_DT: f64 = 1.0 / 30.0;
+
+struct Boids {
+    flock: Flock,
+    markers: MarkerBuffer,
+    inputs_initialized: bool,
+}
+
+impl Simulation for Boids {
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let markers = MarkerBuffer::new(
+            renderer,
+            MarkerSizing::Pixels,
+            Some("aftgraphs::boids::Boids::markers"),
+        );
+
+        Self {
+            flock: Flock::new(),
+            markers,
+            inputs_initialized: false,
+        }
+    }
+
+    async fn on_input(&mut self, _event: InputEvent) {}
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        // Sliders start at their lower bound
+        if !self.inputs_initialized {
+            self.inputs_initialized = true;
+            for (name, value) in [
+                ("flock.count", COUNT as f64),
+                ("flock.separation", WEIGHTS.separation as f64),
+                ("flock.alignment", WEIGHTS.alignment as f64),
+                ("flock.cohesion", WEIGHTS.cohesion as f64),
+            ] {
+                inputs
+                    .entry(name.to_owned())
+                    .or_insert(InputValue::SLIDER(value));
+            }
+        }
+
+        let slider = |name: &str, default: f32| match inputs.get(name) {
+            Some(&InputValue::SLIDER(val)) => val as f32,
+            _ => default,
+        };
+        let count = slider("flock.count", COUNT as f32) as usize;
+        let weights = Weights {
+            separation: slider("flock.separation", WEIGHTS.separation),
+            alignment: slider("flock.alignment", WEIGHTS.alignment),
+            cohesion: slider("flock.cohesion", WEIGHTS.cohesion),
+        };
+
+        let aspect_ratio = renderer.aspect_ratio as f32;
+        self.flock.resize(count, aspect_ratio);
+        self.flock.step(
+            renderer.clock.delta_time().min(MAX_DT) as f32,
+            weights,
+            aspect_ratio,
+        );
+
+        {
+            let mut markers = self.markers.modify(renderer);
+            let markers = markers.markers_vec();
+            markers.clear();
+            markers.extend(self.flock.positions.iter().zip(&self.flock.velocities).map(
+                |(position, velocity)| {
+                    // Faster boids are brighter
+                    let speed = velocity[0].hypot(velocity[1]);
+                    let [r, g, b] =
+                        Colormap::Viridis.sample((speed - MIN_SPEED) / (MAX_SPEED - MIN_SPEED));
+                    Marker::new(
+                        [position[0], position[1] * aspect_ratio],
+                        BOID_SIZE,
+                        MarkerShape::Triangle,
+                        [r, g, b, 1.0],
+                    )
+                },
+            ));
+        }
+
+        self.markers.draw(renderer, &mut render_pass);
+    }
+}
+
+sim_main! { "/res/boids.toml", Boids }
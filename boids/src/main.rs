@@ -0,0 +1,5 @@
+use boids::sim_main;
+
+fn main() {
+    sim_main();
+}
@@ -17,6 +17,10 @@ for dir in *; do
   [ -d "$dir" ] || continue
   # Don't try to compile the imgui submodules
   [[ "$dir" =~ ^imgui.*$ ]] && continue
+  # The template is not a crate until it is generated
+  [[ "$dir" == "template" ]] && continue
+  # Build tooling only runs natively
+  [[ "$dir" == "xtask" ]] && continue
   # Only try to compile directories with Cargo.toml
   [[ -f "$dir/Cargo.toml" ]] || continue
   cd "$dir" || exit
@@ -0,0 +1,12 @@
+// Copy the dye into the R32Float texture of the ScalarField
+@group(0) @binding(0) var dye: texture_2d<f32>;
+@group(0) @binding(1) var field: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8)
+fn copy_dye(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dye);
+    if (any(id.xy >= size)) {
+        return;
+    }
+    textureStore(field, id.xy, vec4<f32>(textureLoad(dye, id.xy, 0).x, 0.0, 0.0, 0.0));
+}
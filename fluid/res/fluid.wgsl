@@ -0,0 +1,137 @@
+// Stable fluids (Stam 1999) on a grid of texels
+// Velocities are in texels per second, with y pointing down the texture
+
+struct Params {
+    dt: f32,
+    viscosity: f32,
+    dissipation: f32,
+    splat_radius: f32,
+    splat_position: vec2<f32>, // texels
+    splat_force: vec2<f32>, // texels per second
+    splat_dye: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+
+// Every kernel reads src and aux and writes dst
+@group(1) @binding(0) var src: texture_2d<f32>;
+@group(1) @binding(1) var aux: texture_2d<f32>;
+@group(1) @binding(2) var dst: texture_storage_2d<rgba32float, write>;
+
+fn load_src(coord: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(src));
+    return textureLoad(src, clamp(coord, vec2<i32>(0), size - 1), 0);
+}
+
+fn load_aux(coord: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(aux));
+    return textureLoad(aux, clamp(coord, vec2<i32>(0), size - 1), 0);
+}
+
+// Bilinear sample of src at a position in texels, 32 bit float textures are not filterable
+fn sample_src(position: vec2<f32>) -> vec4<f32> {
+    let p = position - 0.5;
+    let base = floor(p);
+    let f = p - base;
+    let coord = vec2<i32>(base);
+
+    let top = mix(load_src(coord), load_src(coord + vec2<i32>(1, 0)), f.x);
+    let bottom = mix(load_src(coord + vec2<i32>(0, 1)), load_src(coord + vec2<i32>(1, 1)), f.x);
+    return mix(top, bottom, f.y);
+}
+
+fn splat(coord: vec2<u32>) -> f32 {
+    let offset = vec2<f32>(coord) + 0.5 - params.splat_position;
+    return exp(-dot(offset, offset) / (params.splat_radius * params.splat_radius));
+}
+
+fn in_bounds(id: vec3<u32>) -> bool {
+    return all(id.xy < textureDimensions(dst));
+}
+
+// src: velocity
+@compute @workgroup_size(8, 8)
+fn advect_velocity(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id)) {
+        return;
+    }
+    let velocity = load_src(vec2<i32>(id.xy)).xy;
+    let back = vec2<f32>(id.xy) + 0.5 - params.dt * velocity;
+    let advected = sample_src(back).xy + params.splat_force * splat(id.xy);
+    textureStore(dst, id.xy, vec4<f32>(advected, 0.0, 0.0));
+}
+
+// src: dye, aux: velocity
+@compute @workgroup_size(8, 8)
+fn advect_dye(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id)) {
+        return;
+    }
+    let velocity = load_aux(vec2<i32>(id.xy)).xy;
+    let back = vec2<f32>(id.xy) + 0.5 - params.dt * velocity;
+    let dye = sample_src(back).x * params.dissipation + params.splat_dye * splat(id.xy);
+    textureStore(dst, id.xy, vec4<f32>(dye, 0.0, 0.0, 0.0));
+}
+
+// One Jacobi iteration of the implicit viscous diffusion
+// src: current estimate, aux: velocity before diffusion
+@compute @workgroup_size(8, 8)
+fn diffuse(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id)) {
+        return;
+    }
+    let coord = vec2<i32>(id.xy);
+    let alpha = params.viscosity * params.dt;
+    let neighbors = load_src(coord + vec2<i32>(1, 0)) + load_src(coord - vec2<i32>(1, 0))
+        + load_src(coord + vec2<i32>(0, 1)) + load_src(coord - vec2<i32>(0, 1));
+    let diffused = (load_aux(coord) + alpha * neighbors) / (1.0 + 4.0 * alpha);
+    textureStore(dst, id.xy, diffused);
+}
+
+// src: velocity
+@compute @workgroup_size(8, 8)
+fn divergence(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id)) {
+        return;
+    }
+    let coord = vec2<i32>(id.xy);
+    let dx = load_src(coord + vec2<i32>(1, 0)).x - load_src(coord - vec2<i32>(1, 0)).x;
+    let dy = load_src(coord + vec2<i32>(0, 1)).y - load_src(coord - vec2<i32>(0, 1)).y;
+    textureStore(dst, id.xy, vec4<f32>(0.5 * (dx + dy), 0.0, 0.0, 0.0));
+}
+
+// One Jacobi iteration of the pressure Poisson equation
+// src: current pressure, aux: divergence
+@compute @workgroup_size(8, 8)
+fn pressure(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id)) {
+        return;
+    }
+    let coord = vec2<i32>(id.xy);
+    let neighbors = load_src(coord + vec2<i32>(1, 0)).x + load_src(coord - vec2<i32>(1, 0)).x
+        + load_src(coord + vec2<i32>(0, 1)).x + load_src(coord - vec2<i32>(0, 1)).x;
+    let pressure = (neighbors - load_aux(coord).x) * 0.25;
+    textureStore(dst, id.xy, vec4<f32>(pressure, 0.0, 0.0, 0.0));
+}
+
+// Subtract the pressure gradient to make the velocity divergence free
+// src: velocity, aux: pressure
+@compute @workgroup_size(8, 8)
+fn project(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst);
+    if (!in_bounds(id)) {
+        return;
+    }
+    let coord = vec2<i32>(id.xy);
+    let gradient = 0.5 * vec2<f32>(
+        load_aux(coord + vec2<i32>(1, 0)).x - load_aux(coord - vec2<i32>(1, 0)).x,
+        load_aux(coord + vec2<i32>(0, 1)).x - load_aux(coord - vec2<i32>(0, 1)).x,
+    );
+    var velocity = load_src(coord).xy - gradient;
+
+    // No flow through the walls
+    if (id.x == 0u || id.y == 0u || id.x == size.x - 1u || id.y == size.y - 1u) {
+        velocity = vec2<f32>(0.0);
+    }
+    textureStore(dst, id.xy, vec4<f32>(velocity, 0.0, 0.0));
+}
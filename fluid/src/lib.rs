@@ -0,0 +1,156 @@
+use aftgraphs::prelude::*;
+use aftgraphs_macros::sim_main;
+use std::collections::{HashMap, VecDeque};
+
+mod solver;
+use solver::{FluidSolver, Params, GRID};
+
+/// Longest time step the solver takes, so a stalled frame does not sweep the dye away
+const MAX_DT: f64 = 1.0 / 30.0;
+
+const SPLAT_RADIUS: f32 = 8.0;
+const SPLAT_DYE: f32 = 1.0;
+/// Texels per second of velocity added per clip space unit dragged
+const FORCE_SCALE: f32 = 400.0;
+
+const DISSIPATION: f32 = 0.995;
+const PRESSURE_ITERATIONS: usize = 40;
+
+/// A pending injection of dye and momentum
+struct Splat {
+    /// Position in texels
+    position: [f32; 2],
+    /// Velocity added at the center, in texels per second
+    force: [f32; 2],
+    dye: f32,
+}
+
+struct FluidSimulation {
+    field: ScalarField,
+    solver: Option<FluidSolver>,
+    /// Where the left button was pressed, in [-1, 1] space
+    drag_start: Option<(f64, f64)>,
+    splats: VecDeque<Splat>,
+    inputs_initialized: bool,
+}
+
+/// Convert a position in [-1, 1] space, y up, to texels of the grid, y down
+fn to_texels((x, y): (f64, f64)) -> [f32; 2] {
+    [
+        (x as f32 + 1.0) / 2.0 * GRID as f32,
+        (1.0 - y as f32) / 2.0 * GRID as f32,
+    ]
+}
+
+impl Simulation for FluidSimulation {
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let field = ScalarFieldBuilder::new(GRID, GRID)
+            .with_label(Some("aftgraphs::fluid::FluidSimulation::dye"))
+            .with_colormap(Colormap::Inferno)
+            .with_range(0.0, 1.0)
+            .build(renderer);
+
+        let solver = FluidSolver::new(renderer, &field);
+        if solver.is_none() {
+            log::error!(
+                "aftgraphs::fluid::FluidSimulation::new: Adapter does not support compute shaders"
+            );
+        }
+
+        Self {
+            field,
+            solver,
+            drag_start: None,
+            splats: VecDeque::new(),
+            inputs_initialized: false,
+        }
+    }
+
+    /// Pressing the left button drops in dye, releasing it pushes the fluid along the drag
+    async fn on_input(&mut self, event: InputEvent) {
+        let InputEvent::Mouse(state, MouseButton::Left, position) = event else {
+            return;
+        };
+
+        match state {
+            ElementState::Pressed => {
+                self.drag_start = Some(position);
+                self.splats.push_back(Splat {
+                    position: to_texels(position),
+                    force: [0.0; 2],
+                    dye: SPLAT_DYE,
+                });
+            }
+            ElementState::Released => {
+                let Some(start) = self.drag_start.take() else {
+                    return;
+                };
+                let drag = [
+                    (position.0 - start.0) as f32,
+                    // Texels are y down
+                    (start.1 - position.1) as f32,
+                ];
+                self.splats.push_back(Splat {
+                    position: to_texels(start),
+                    force: drag.map(|d| d * FORCE_SCALE),
+                    dye: SPLAT_DYE,
+                });
+            }
+        }
+    }
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        // Sliders start at their lower bound
+        if !self.inputs_initialized {
+            self.inputs_initialized = true;
+            inputs
+                .entry("controls.dissipation".to_owned())
+                .or_insert(InputValue::SLIDER(DISSIPATION as f64));
+            inputs
+                .entry("controls.pressure_iterations".to_owned())
+                .or_insert(InputValue::SLIDER(PRESSURE_ITERATIONS as f64));
+        }
+
+        let slider = |name: &str, default: f64| match inputs.get(name) {
+            Some(&InputValue::SLIDER(val)) => val,
+            _ => default,
+        };
+        let viscosity = slider("controls.viscosity", 0.0) as f32;
+        let dissipation = slider("controls.dissipation", DISSIPATION as f64) as f32;
+        let pressure_iterations =
+            slider("controls.pressure_iterations", PRESSURE_ITERATIONS as f64) as usize;
+
+        if let Some(solver) = self.solver.as_mut() {
+            let dt = renderer.clock.delta_time().min(MAX_DT) as f32;
+            let params = Params {
+                dt,
+                viscosity,
+                dissipation,
+                splat_radius: SPLAT_RADIUS,
+                ..Default::default()
+            };
+
+            // One splat per step, the rest wait for the next frames
+            let params = match self.splats.pop_front() {
+                Some(splat) => Params {
+                    splat_position: splat.position,
+                    splat_force: splat.force,
+                    splat_dye: splat.dye,
+                    ..params
+                },
+                None => params,
+            };
+
+            solver.step(renderer, params, pressure_iterations);
+        }
+
+        self.field.draw(&mut render_pass);
+    }
+}
+
+sim_main! { "/res/fluid.toml", FluidSimulation }
@@ -0,0 +1,5 @@
+use fluid::sim_main;
+
+fn main() {
+    sim_main();
+}
@@ -0,0 +1,341 @@
+use aftgraphs::prelude::*;
+use std::collections::HashMap;
+
+/// Side of the square simulation grid in texels
+pub const GRID: u32 = 256;
+
+const WORKGROUP: u32 = 8;
+/// Must be even, see FluidSolver::step
+const DIFFUSE_ITERATIONS: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C)]
+pub struct Params {
+    pub dt: f32,
+    pub viscosity: f32,
+    pub dissipation: f32,
+    pub splat_radius: f32,
+    pub splat_position: [f32; 2],
+    pub splat_force: [f32; 2],
+    pub splat_dye: f32,
+    pub _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for Params {}
+unsafe impl bytemuck::NoUninit for Params {}
+
+/// Grid textures, all Rgba32Float
+/// The velocity starts and ends each step in Velocity2
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Grid {
+    Velocity0,
+    Velocity1,
+    Velocity2,
+    Pressure0,
+    Pressure1,
+    Divergence,
+    Dye0,
+    Dye1,
+}
+
+const GRIDS: usize = 8;
+
+/// Advection, diffusion, and projection compute passes of a stable fluids solver
+/// The dye is copied into a ScalarField at the end of every step for display.
+pub struct FluidSolver {
+    _textures: Vec<wgpu::Texture>,
+    views: Vec<wgpu::TextureView>,
+    kernel_layout: BindGroupLayout,
+    /// Kernel bind groups by (src, aux, dst), created as they are first used
+    bind_groups: HashMap<Bindings, wgpu::BindGroup>,
+    params: Uniform<Params>,
+    advect_velocity: wgpu::ComputePipeline,
+    advect_dye: wgpu::ComputePipeline,
+    diffuse: wgpu::ComputePipeline,
+    divergence: wgpu::ComputePipeline,
+    pressure: wgpu::ComputePipeline,
+    project: wgpu::ComputePipeline,
+    copy_pipeline: wgpu::ComputePipeline,
+    /// Copy bind groups from Dye0 and Dye1
+    copy_bind_groups: [wgpu::BindGroup; 2],
+    /// Index of the dye texture holding the current dye
+    dye: usize,
+}
+
+fn unfilterable_texture(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn storage_texture(binding: u32, format: wgpu::TextureFormat) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+/// Key of a kernel bind group
+type Bindings = (Grid, Grid, Grid);
+
+fn kernel_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    views: &[wgpu::TextureView],
+    (src, aux, dst): Bindings,
+) -> wgpu::BindGroup {
+    let entries: Vec<_> = [src, aux, dst]
+        .into_iter()
+        .enumerate()
+        .map(|(binding, grid)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: wgpu::BindingResource::TextureView(&views[grid as usize]),
+        })
+        .collect();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("aftgraphs::fluid::FluidSolver::kernel"),
+        layout,
+        entries: &entries,
+    })
+}
+
+impl FluidSolver {
+    /// None if the adapter does not support compute shaders
+    pub fn new<P: UiPlatform>(renderer: &Renderer<'_, P>, field: &ScalarField) -> Option<Self> {
+        if !renderer
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return None;
+        }
+
+        let device = &renderer.device;
+
+        let textures: Vec<_> = (0..GRIDS)
+            .map(|_| {
+                device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("aftgraphs::fluid::FluidSolver::grid"),
+                    size: wgpu::Extent3d {
+                        width: GRID,
+                        height: GRID,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba32Float,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::STORAGE_BINDING,
+                    view_formats: &[],
+                })
+            })
+            .collect();
+        let views: Vec<_> = textures
+            .iter()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+
+        let params_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::fluid::FluidSolver::params"))
+            .with_entry(BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .build(renderer);
+        let params = UniformBuilder::new()
+            .with_label(Some("aftgraphs::fluid::FluidSolver::params"))
+            .with_bind_group_layout(params_layout)
+            .with_data(Params::default())
+            .build(renderer);
+
+        let kernel_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::fluid::FluidSolver::kernel"))
+            .with_entries(vec![
+                unfilterable_texture(0),
+                unfilterable_texture(1),
+                storage_texture(2, wgpu::TextureFormat::Rgba32Float),
+            ])
+            .build(renderer);
+        let kernel_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("aftgraphs::fluid::FluidSolver::kernel"),
+                bind_group_layouts: &[params.bind_group_layout(), &kernel_layout],
+                push_constant_ranges: &[],
+            });
+
+        let module = device.create_shader_module(include_wgsl!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/res/fluid.wgsl"
+        )));
+        let kernel = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&kernel_pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        let copy_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::fluid::FluidSolver::copy"))
+            .with_entries(vec![
+                unfilterable_texture(0),
+                storage_texture(1, wgpu::TextureFormat::R32Float),
+            ])
+            .build(renderer);
+        let copy_module = device.create_shader_module(include_wgsl!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/res/copy.wgsl"
+        )));
+        let copy_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("aftgraphs::fluid::FluidSolver::copy"),
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("aftgraphs::fluid::FluidSolver::copy"),
+                    bind_group_layouts: &[&copy_layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            module: &copy_module,
+            entry_point: Some("copy_dye"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let copy_bind_groups = [Grid::Dye0, Grid::Dye1].map(|dye| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::fluid::FluidSolver::copy"),
+                layout: &copy_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[dye as usize]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(field.view()),
+                    },
+                ],
+            })
+        });
+
+        Some(Self {
+            advect_velocity: kernel("advect_velocity"),
+            advect_dye: kernel("advect_dye"),
+            diffuse: kernel("diffuse"),
+            divergence: kernel("divergence"),
+            pressure: kernel("pressure"),
+            project: kernel("project"),
+            _textures: textures,
+            views,
+            kernel_layout,
+            bind_groups: HashMap::new(),
+            params,
+            copy_pipeline,
+            copy_bind_groups,
+            dye: 0,
+        })
+    }
+
+    /// Advance the fluid by params.dt and copy the dye into the field
+    /// The passes are submitted immediately, ahead of the frame's render pass.
+    pub fn step<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        params: Params,
+        pressure_iterations: usize,
+    ) {
+        use Grid::*;
+
+        self.params.update(renderer, params);
+
+        let device = &renderer.device;
+        let (dye_src, dye_dst) = if self.dye == 0 {
+            (Dye0, Dye1)
+        } else {
+            (Dye1, Dye0)
+        };
+
+        let mut passes = vec![(&self.advect_velocity, (Velocity2, Velocity2, Velocity1))];
+        // Velocity1 is the right hand side of the diffusion, iterate between Velocity2 and
+        // Velocity0, ending in Velocity0 after an even number of iterations
+        for iteration in 0..DIFFUSE_ITERATIONS {
+            let (src, dst) = match iteration {
+                0 => (Velocity1, Velocity2),
+                _ if iteration % 2 == 0 => (Velocity0, Velocity2),
+                _ => (Velocity2, Velocity0),
+            };
+            passes.push((&self.diffuse, (src, Velocity1, dst)));
+        }
+        passes.push((&self.divergence, (Velocity0, Velocity0, Divergence)));
+        // Warm started from the last step's pressure, ending in Pressure0
+        for iteration in 0..pressure_iterations.next_multiple_of(2) {
+            let (src, dst) = if iteration % 2 == 0 {
+                (Pressure0, Pressure1)
+            } else {
+                (Pressure1, Pressure0)
+            };
+            passes.push((&self.pressure, (src, Divergence, dst)));
+        }
+        passes.push((&self.project, (Velocity0, Pressure0, Velocity2)));
+        passes.push((&self.advect_dye, (dye_src, Velocity2, dye_dst)));
+
+        for &(_, bindings) in &passes {
+            self.bind_groups.entry(bindings).or_insert_with(|| {
+                kernel_bind_group(device, &self.kernel_layout, &self.views, bindings)
+            });
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("aftgraphs::fluid::FluidSolver::step"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("aftgraphs::fluid::FluidSolver::step"),
+                timestamp_writes: None,
+            });
+            let workgroups = GRID.div_ceil(WORKGROUP);
+
+            pass.set_bind_group(0, self.params.bind_group(), &[]);
+            for (pipeline, bindings) in passes {
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(1, &self.bind_groups[&bindings], &[]);
+                pass.dispatch_workgroups(workgroups, workgroups, 1);
+            }
+
+            pass.set_pipeline(&self.copy_pipeline);
+            pass.set_bind_group(
+                0,
+                &self.copy_bind_groups[dye_dst as usize - Dye0 as usize],
+                &[],
+            );
+            pass.dispatch_workgroups(workgroups, workgroups, 1);
+        }
+        renderer.queue.submit(Some(encoder.finish()));
+
+        self.dye = 1 - self.dye;
+    }
+}
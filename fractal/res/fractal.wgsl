@@ -0,0 +1,121 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) screen: vec2<f32>, // (-1, 1), y up
+}
+
+struct Params {
+    // The view center split into high and low f32 parts, center = hi + lo
+    center_hi: vec2<f32>,
+    center_lo: vec2<f32>,
+    half_extent: vec2<f32>,
+    julia: vec2<f32>,
+    iterations: u32,
+    // Bit 0: Julia set instead of Mandelbrot, bit 1: emulated double precision
+    flags: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.screen = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.screen, 0.0, 1.0);
+    return out;
+}
+
+// Double-float arithmetic, a value is hi + lo with |lo| <= ulp(hi) / 2
+fn two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    let v = s - a;
+    let e = (a - (s - v)) + (b - v);
+    return vec2<f32>(s, e);
+}
+
+fn quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    return vec2<f32>(s, b - (s - a));
+}
+
+fn two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = a * b;
+    return vec2<f32>(p, fma(a, b, -p));
+}
+
+fn df_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = two_sum(a.x, b.x);
+    let t = two_sum(a.y, b.y);
+    let u = quick_two_sum(s.x, s.y + t.x);
+    return quick_two_sum(u.x, u.y + t.y);
+}
+
+fn df_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = two_prod(a.x, b.x);
+    return quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+// Iterations until escape, smoothed, or -1 if the point did not escape
+fn escape_single(z_in: vec2<f32>, c: vec2<f32>) -> f32 {
+    var z = z_in;
+    for (var i = 0u; i < params.iterations; i++) {
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        let r2 = dot(z, z);
+        if (r2 > 256.0) {
+            return f32(i) + 1.0 - log2(log2(r2) * 0.5);
+        }
+    }
+    return -1.0;
+}
+
+// Same as escape_single, with every coordinate a double-float
+fn escape_double(zx_in: vec2<f32>, zy_in: vec2<f32>, cx: vec2<f32>, cy: vec2<f32>) -> f32 {
+    var zx = zx_in;
+    var zy = zy_in;
+    for (var i = 0u; i < params.iterations; i++) {
+        let x2 = df_mul(zx, zx);
+        let y2 = df_mul(zy, zy);
+        let xy = df_mul(zx, zy);
+        zx = df_add(df_add(x2, -y2), cx);
+        zy = df_add(df_add(xy, xy), cy);
+        let r2 = zx.x * zx.x + zy.x * zy.x;
+        if (r2 > 256.0) {
+            return f32(i) + 1.0 - log2(log2(r2) * 0.5);
+        }
+    }
+    return -1.0;
+}
+
+fn palette(t: f32) -> vec3<f32> {
+    return 0.5 + 0.5 * cos(6.28318 * (t + vec3<f32>(0.0, 0.1, 0.2)));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let offset = in.screen * params.half_extent;
+    let julia = (params.flags & 1u) != 0u;
+
+    var escape: f32;
+    if ((params.flags & 2u) != 0u) {
+        let x = df_add(vec2<f32>(params.center_hi.x, params.center_lo.x), two_sum(offset.x, 0.0));
+        let y = df_add(vec2<f32>(params.center_hi.y, params.center_lo.y), two_sum(offset.y, 0.0));
+        if (julia) {
+            escape = escape_double(x, y, vec2<f32>(params.julia.x, 0.0), vec2<f32>(params.julia.y, 0.0));
+        } else {
+            escape = escape_double(vec2<f32>(0.0), vec2<f32>(0.0), x, y);
+        }
+    } else {
+        let point = params.center_hi + params.center_lo + offset;
+        if (julia) {
+            escape = escape_single(point, params.julia);
+        } else {
+            escape = escape_single(vec2<f32>(0.0), point);
+        }
+    }
+
+    if (escape < 0.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return vec4<f32>(palette(escape * 0.02), 1.0);
+}
@@ -0,0 +1,47 @@
+/// Pan and zoom over the complex plane, kept in f64 so deep zooms do not lose the center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    pub center: [f64; 2],
+    /// Half the height of the view in world units
+    pub half_height: f64,
+}
+
+impl Camera2D {
+    pub fn new(center: [f64; 2], half_height: f64) -> Self {
+        Self {
+            center,
+            half_height,
+        }
+    }
+
+    /// Half the width and height of the view in world units
+    pub fn half_extent(&self, aspect_ratio: f64) -> [f64; 2] {
+        [self.half_height * aspect_ratio, self.half_height]
+    }
+
+    /// World position of a point in [-1, 1] screen space
+    pub fn world_position(&self, (x, y): (f64, f64), aspect_ratio: f64) -> [f64; 2] {
+        let [half_width, half_height] = self.half_extent(aspect_ratio);
+        [
+            self.center[0] + x * half_width,
+            self.center[1] + y * half_height,
+        ]
+    }
+
+    /// Move the view by a fraction of its size
+    pub fn pan(&mut self, dx: f64, dy: f64, aspect_ratio: f64) {
+        let [half_width, half_height] = self.half_extent(aspect_ratio);
+        self.center[0] += dx * half_width;
+        self.center[1] += dy * half_height;
+    }
+
+    /// Zoom in by factor (out if below 1), keeping the world position under point fixed
+    pub fn zoom_at(&mut self, point: (f64, f64), factor: f64, aspect_ratio: f64) {
+        let anchor = self.world_position(point, aspect_ratio);
+        self.half_height /= factor;
+
+        let moved = self.world_position(point, aspect_ratio);
+        self.center[0] += anchor[0] - moved[0];
+        self.center[1] += anchor[1] - moved[1];
+    }
+}
@@ -0,0 +1,192 @@
+use aftgraphs::prelude::*;
+use aftgraphs_macros::sim_main;
+use std::collections::HashMap;
+
+mod camera;
+use camera::Camera2D;
+
+const ITERATIONS: u32 = 256;
+const ZOOM_STEP: f64 = 2.0;
+/// Fraction of the view an arrow key press pans by
+const PAN_STEP: f64 = 0.25;
+
+const FLAG_JULIA: u32 = 1;
+const FLAG_DOUBLE: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+struct Params {
+    center_hi: [f32; 2],
+    center_lo: [f32; 2],
+    half_extent: [f32; 2],
+    julia: [f32; 2],
+    iterations: u32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for Params {}
+unsafe impl bytemuck::NoUninit for Params {}
+
+/// Split an f64 into f32 parts whose sum is the f64 to about 48 bits
+fn split(value: f64) -> (f32, f32) {
+    let hi = value as f32;
+    (hi, (value - hi as f64) as f32)
+}
+
+/// How to move the camera on the next frame, the aspect ratio is only known when rendering
+enum CameraMove {
+    Zoom((f64, f64), f64),
+    Pan(f64, f64),
+}
+
+struct Fractal {
+    pipeline: RenderPipeline,
+    params: Uniform<Params>,
+    camera: Camera2D,
+    moves: Vec<CameraMove>,
+    inputs_initialized: bool,
+}
+
+impl Simulation for Fractal {
+    // The camera only moves on input events
+    const STATELESS: bool = true;
+
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let module = include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/fractal.wgsl"));
+
+        let params_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::fractal::Fractal::params"))
+            .with_entry(BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .build(renderer);
+        let params = UniformBuilder::new()
+            .with_label(Some("aftgraphs::fractal::Fractal::params"))
+            .with_bind_group_layout(params_layout)
+            .with_data(Params {
+                center_hi: [0.0; 2],
+                center_lo: [0.0; 2],
+                half_extent: [1.0; 2],
+                julia: [0.0; 2],
+                iterations: ITERATIONS,
+                flags: 0,
+                _padding: [0; 2],
+            })
+            .build(renderer);
+
+        let shader = ShaderBuilder::new()
+            .with_module(module)
+            .with_default_fs_entrypoint()
+            .build(renderer);
+
+        let pipeline = RenderPipelineBuilder::new()
+            .with_pipeline_label(Some("aftgraphs::fractal::Fractal::pipeline"))
+            .with_vertex_shader(shader)
+            .with_bind_group_layout(params.bind_group_layout())
+            .build(renderer)
+            .expect("aftgraphs::fractal::Fractal::pipeline failed to build");
+
+        Self {
+            pipeline,
+            params,
+            camera: Camera2D::new([-0.5, 0.0], 1.25),
+            moves: vec![],
+            inputs_initialized: false,
+        }
+    }
+
+    /// Left click zooms in at the cursor, right click zooms out, arrow keys pan
+    async fn on_input(&mut self, event: InputEvent) {
+        let camera_move = match event {
+            InputEvent::Mouse(ElementState::Pressed, MouseButton::Left, position) => {
+                CameraMove::Zoom(position, ZOOM_STEP)
+            }
+            InputEvent::Mouse(ElementState::Pressed, MouseButton::Right, position) => {
+                CameraMove::Zoom(position, 1.0 / ZOOM_STEP)
+            }
+            InputEvent::Keyboard(RawKeyEvent {
+                physical_key: PhysicalKey::Code(code),
+                state: ElementState::Pressed,
+            }) => match code {
+                KeyCode::ArrowLeft => CameraMove::Pan(-PAN_STEP, 0.0),
+                KeyCode::ArrowRight => CameraMove::Pan(PAN_STEP, 0.0),
+                KeyCode::ArrowUp => CameraMove::Pan(0.0, PAN_STEP),
+                KeyCode::ArrowDown => CameraMove::Pan(0.0, -PAN_STEP),
+                _ => return,
+            },
+            _ => return,
+        };
+
+        self.moves.push(camera_move);
+    }
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        // Sliders start at their lower bound
+        if !self.inputs_initialized {
+            self.inputs_initialized = true;
+            inputs
+                .entry("fractal.iterations".to_owned())
+                .or_insert(InputValue::SLIDER(ITERATIONS as f64));
+        }
+
+        let aspect_ratio = renderer.aspect_ratio;
+        for camera_move in self.moves.drain(..) {
+            match camera_move {
+                CameraMove::Zoom(point, factor) => self.camera.zoom_at(point, factor, aspect_ratio),
+                CameraMove::Pan(dx, dy) => self.camera.pan(dx, dy, aspect_ratio),
+            }
+        }
+
+        let slider = |name: &str| match inputs.get(name) {
+            Some(&InputValue::SLIDER(val)) => Some(val),
+            _ => None,
+        };
+        let checkbox = |name: &str| matches!(inputs.get(name), Some(&InputValue::CHECKBOX(true)));
+
+        let mut flags = 0;
+        if checkbox("fractal.julia") {
+            flags |= FLAG_JULIA;
+        }
+        if checkbox("fractal.double") {
+            flags |= FLAG_DOUBLE;
+        }
+
+        let (x_hi, x_lo) = split(self.camera.center[0]);
+        let (y_hi, y_lo) = split(self.camera.center[1]);
+        let [half_width, half_height] = self.camera.half_extent(aspect_ratio);
+        self.params.update(
+            renderer,
+            Params {
+                center_hi: [x_hi, y_hi],
+                center_lo: [x_lo, y_lo],
+                half_extent: [half_width as f32, half_height as f32],
+                julia: [
+                    slider("fractal.julia_re").unwrap_or(0.0) as f32,
+                    slider("fractal.julia_im").unwrap_or(0.0) as f32,
+                ],
+                iterations: slider("fractal.iterations").map_or(ITERATIONS, |val| val as u32),
+                flags,
+                _padding: [0; 2],
+            },
+        );
+
+        render_pass.set_pipeline(&self.pipeline);
+        self.params.bind(&mut render_pass, 0);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+sim_main! { "/res/fractal.toml", Fractal }
@@ -0,0 +1,5 @@
+use fractal::sim_main;
+
+fn main() {
+    sim_main();
+}
@@ -14,18 +14,18 @@ struct VertexOutput {
     @location(1) quad_pos: vec2<f32>, // (-1, 1)
 }
 
-struct Float {
-    @align(16) f: f32,
+struct Projection {
+    projection: mat4x4<f32>,
 }
 
-@group(0) @binding(0) var<uniform> aspect_ratio: Float;
+@group(0) @binding(0) var<uniform> projection: Projection;
 
 @vertex
 fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
     out.color = instance.color;
-    let quad_pos = vec2<f32>(vertex.quad_pos.x * instance.radius, vertex.quad_pos.y * instance.radius * aspect_ratio.f);
-    out.clip_position = vec4<f32>(quad_pos + instance.position, 1.0, 1.0);
+    let quad_pos = vertex.quad_pos * instance.radius;
+    out.clip_position = projection.projection * vec4<f32>(quad_pos + instance.position, 1.0, 1.0);
     out.quad_pos = vertex.quad_pos;
     return out;
 }
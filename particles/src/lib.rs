@@ -1,6 +1,6 @@
 use aftgraphs::prelude::*;
 use aftgraphs_macros::sim_main;
-use std::{cmp::Ordering, collections::HashMap, num::NonZeroU64};
+use std::{cmp::Ordering, collections::HashMap};
 
 mod physics;
 use physics::Physics;
@@ -19,19 +19,12 @@ struct Instance {
     color: [f32; 3],
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-#[repr(C, align(16))]
-struct Float(f32);
-
 unsafe impl bytemuck::Zeroable for Vertex {}
 unsafe impl bytemuck::NoUninit for Vertex {}
 
 unsafe impl bytemuck::Zeroable for Instance {}
 unsafe impl bytemuck::NoUninit for Instance {}
 
-unsafe impl bytemuck::Zeroable for Float {}
-unsafe impl bytemuck::NoUninit for Float {}
-
 const RADIUS: f32 = 0.0625;
 
 const QUAD: [Vertex; 4] = [
@@ -53,12 +46,20 @@ const INDICES: [u16; 6] = [2, 1, 0, 2, 1, 3];
 
 const MAX_VELOCITY: f32 = 0.5;
 
+/// Particles live in a square world, letterboxed to fit the window
+const ASPECT_POLICY: AspectPolicy = AspectPolicy::Letterbox(WorldRect::NDC);
+
 struct Particles {
     pipeline: RenderPipeline,
     instances: InstanceBuffer<Vertex, Instance>,
     indices: IndexBuffer<u16>,
-    aspect_ratio: Uniform<Float>,
+    projection: Uniform<ProjectionParams>,
+    /// Aspect ratio of the last frame, to map the cursor into the world
+    aspect_ratio: f64,
     physics: Physics,
+    inputs_initialized: bool,
+    /// Set when particles were added or removed with the mouse
+    count_changed: bool,
 }
 
 impl Simulation for Particles {
@@ -74,8 +75,7 @@ impl Simulation for Particles {
         let instances = InstanceBufferBuilder::new()
             .with_initial_vertices(QUAD.as_slice())
             .with_initial_instances_owned(initial_instances)
-            .with_vertex_label(Some("aftgraphs::particles::Particles::vertices"))
-            .with_instance_label(Some("aftgraphs::particles::Particles::instances"))
+            .with_label(Some("aftgraphs::particles::Particles"))
             .with_vertex_attributes_owned(vec![VertexAttribute {
                 offset: 0,
                 shader_location: 0,
@@ -107,24 +107,8 @@ impl Simulation for Particles {
             Some("aftgraphs::particles::Particles::indices"),
         );
 
-        let aspect_ratio_layout = BindGroupLayoutBuilder::new()
-            .with_label(Some("aftgraphs::particles::Particles::aspect_ratio"))
-            .with_entry(BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(16u64),
-                },
-                count: None,
-            })
-            .build(renderer);
-        let aspect_ratio = UniformBuilder::new()
-            .with_label(Some("aftgraphs::particles::Particles::aspect_ratio"))
-            .with_bind_group_layout(aspect_ratio_layout)
-            .with_data(Float(renderer.aspect_ratio as f32))
-            .build(renderer);
+        renderer.set_aspect_policy(ASPECT_POLICY);
+        let projection = renderer.projection_uniform();
 
         let shader = ShaderBuilder::new()
             .with_module(module)
@@ -134,11 +118,13 @@ impl Simulation for Particles {
             .build(renderer);
 
         let pipeline = RenderPipelineBuilder::new()
+            .with_pipeline_label(Some("aftgraphs::particles::Particles::pipeline"))
             .with_vertex_shader(shader)
-            .with_bind_group_layout(aspect_ratio.bind_group_layout())
-            .build(renderer);
+            .with_bind_group_layout(projection.bind_group_layout())
+            .build(renderer)
+            .expect("aftgraphs::particles::Particles::pipeline failed to build");
 
-        let mut physics = Physics::new(renderer.surface.is_some(), 0.0, RADIUS, aspect_ratio.0)
+        let mut physics = Physics::new(renderer.surface.is_some(), 0.0, RADIUS, 1.0)
             .await
             .expect("aftgraphs::particles::Particles::physics failed to create");
 
@@ -150,12 +136,27 @@ impl Simulation for Particles {
             pipeline,
             instances,
             indices,
-            aspect_ratio,
+            projection,
+            aspect_ratio: renderer.aspect_ratio,
             physics,
+            inputs_initialized: false,
+            count_changed: false,
         }
     }
 
-    async fn on_input(&mut self, _event: InputEvent) {}
+    /// Left click spawns a particle at the cursor, right click removes the nearest particle
+    async fn on_input(&mut self, event: InputEvent) {
+        let InputEvent::Mouse(ElementState::Pressed, button, position) = event else {
+            return;
+        };
+        let position = ASPECT_POLICY.ndc_to_world(position, self.aspect_ratio);
+
+        self.count_changed |= match button {
+            MouseButton::Left => self.physics.spawn_at(position).await,
+            MouseButton::Right => self.physics.remove_nearest(position).await,
+            _ => false,
+        };
+    }
 
     async fn render<P: UiPlatform>(
         &mut self,
@@ -163,12 +164,53 @@ impl Simulation for Particles {
         mut render_pass: RenderPass<'_>,
         inputs: &mut HashMap<String, InputValue>,
     ) {
+        self.aspect_ratio = renderer.viewport_aspect_ratio();
+        self.projection
+            .update(renderer, renderer.projection_params());
+
+        // Sliders start at their lower bound, start with perfectly elastic collisions
+        // and the default radius
+        if !self.inputs_initialized {
+            self.inputs_initialized = true;
+            inputs
+                .entry("controls.restitution".to_owned())
+                .or_insert(InputValue::SLIDER(1.0));
+            inputs
+                .entry("controls.radius".to_owned())
+                .or_insert(InputValue::SLIDER(RADIUS as f64));
+        }
+
+        let radius = match inputs.get("controls.radius") {
+            Some(&InputValue::SLIDER(val)) => val as f32,
+            _ => RADIUS,
+        };
+        self.physics.set_radius(radius).await;
+
+        let color_by_speed = matches!(
+            inputs.get("controls.color_by_speed"),
+            Some(&InputValue::CHECKBOX(true))
+        );
         self.physics
-            .update_aspect_ratio(renderer.aspect_ratio as f32)
+            .set_speed_colormap(color_by_speed.then_some(Colormap::Viridis));
+
+        let collisions = matches!(
+            inputs.get("controls.collision"),
+            Some(&InputValue::CHECKBOX(true))
+        );
+        let restitution = match inputs.get("controls.restitution") {
+            Some(&InputValue::SLIDER(val)) => val as f32,
+            _ => 1.0,
+        };
+        self.physics
+            .set_collisions(collisions.then_some(restitution))
             .await;
 
-        self.aspect_ratio
-            .update(renderer, Float(renderer.aspect_ratio as f32));
+        if std::mem::take(&mut self.count_changed) {
+            inputs.insert(
+                "controls.count".to_owned(),
+                InputValue::SLIDER(self.physics.len() as f64),
+            );
+        }
 
         if let Some(inp) = inputs.get_mut("controls.count") {
             let physics_len = self.physics.len();
@@ -182,10 +224,8 @@ impl Simulation for Particles {
 
             match val.cmp(&physics_len) {
                 Ordering::Less => {
+                    // The instances are replaced by the state of the physics below
                     self.physics.pop(physics_len - val).await;
-
-                    let mut instances = self.instances.modify(renderer);
-                    instances.instances_drain(val..);
                 }
                 Ordering::Greater => {
                     self.physics.spawn(val - physics_len).await;
@@ -200,13 +240,13 @@ impl Simulation for Particles {
 
         {
             let mut instances = self.instances.modify(renderer);
-            *instances.instances_vec() = self.physics.get_state(renderer.time as f32).await;
+            *instances.instances_vec() = self.physics.get_state(renderer.clock.time() as f32).await;
         }
 
         render_pass.set_pipeline(&self.pipeline);
         self.instances.bind(&mut render_pass, 0, 1);
         self.indices.bind(&mut render_pass);
-        self.aspect_ratio.bind(&mut render_pass, 0);
+        self.projection.bind(&mut render_pass, 0);
         render_pass.draw_indexed(self.indices.range(), 0, self.instances.range_instance());
     }
 }
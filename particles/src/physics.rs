@@ -1,282 +1,322 @@
 use crate::{Instance, MAX_VELOCITY};
-use aftgraphs::{block_on, spawn, Handle};
-use async_std::{
-    channel::{bounded, Receiver, Sender, TryRecvError},
-    sync::Mutex,
+use aftgraphs::{
+    dynamics::{
+        bacon_sci::{ivp::UserError, prelude::*},
+        DynamicalSystem, DynamicsError, IntegratorWorker, State, WorkerSettings,
+    },
+    field::Colormap,
+    spatial::SpatialHash,
 };
-use bacon_sci::{
-    ivp::{Derivative, Euler, IVPError, IVPSolver, UserError},
-    prelude::*,
-};
-use crossbeam::{
-    deque::Injector,
-    sync::{Parker, Unparker},
-};
-use rand::{distributions::Uniform, prelude::*, thread_rng};
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use rand::{distributions::Uniform, prelude::*, rngs::ThreadRng, thread_rng};
+use std::{cell::RefCell, rc::Rc};
 
 pub struct Physics {
-    deque: Arc<Injector<(f32, BVector<f32, Dyn>)>>,
-    scratchpad: Vec<(f32, BVector<f32, Dyn>)>,
-    time: f32,
+    worker: IntegratorWorker<ParticleSystem>,
     radius: f32,
     aspect_ratio: f32,
     num_particles: usize,
-    reset_tx: Sender<PhysicsMessage>,
-    response: Receiver<bool>,
-    request: Unparker,
-    lock: Arc<Mutex<bool>>,
-    _handle: Handle,
+    restitution: Option<f32>,
+    /// Colormap to color the particles by speed with, None for white particles
+    speed_colormap: Option<Colormap>,
+    /// Time and state of the last frame, for estimating velocities
+    last_state: Option<(f32, State)>,
+    speeds: Vec<f32>,
 }
 
 #[derive(Clone)]
 struct PhysicsData {
     radius: f32,
     aspect_ratio: f32,
-    velocities: Rc<RefCell<BVector<f32, Dyn>>>,
+    velocities: Rc<RefCell<State>>,
+    /// Restitution coefficient of particle-particle collisions, None to disable them
+    restitution: Option<f32>,
+    hash: Rc<RefCell<SpatialHash>>,
 }
 
 enum PhysicsMessage {
-    /// time, radius, aspect_ratio
-    Reset(f32, f32, f32),
+    /// radius, aspect_ratio
+    Reset(f32, f32),
     Spawn(usize),
     Pop(usize),
+    Collisions(Option<f32>),
+    /// Spawn one particle at the position, if it fits
+    SpawnAt([f32; 2]),
+    /// Remove the particle nearest to the position, if one is within PICK_RADIUS radii
+    RemoveNearest([f32; 2]),
 }
 
-struct PhysicsThread {
-    display: bool,
-    deque: Arc<Injector<(f32, BVector<f32, Dyn>)>>,
-    time: f32,
+/// How far from a particle, in radii, a click still picks it
+const PICK_RADIUS: f32 = 2.0;
+
+struct ParticleSystem {
     radius: f32,
     aspect_ratio: f32,
-    reset: Receiver<PhysicsMessage>,
-    response: Sender<bool>,
-    request: Parker,
-    lock: Arc<Mutex<bool>>,
+    velocities: Rc<RefCell<State>>,
+    restitution: Option<f32>,
+    hash: Rc<RefCell<SpatialHash>>,
+    rng: ThreadRng,
 }
 
-fn particle_derivative(
-    _t: f32,
-    y: &[f32],
-    data: &mut PhysicsData,
-) -> Result<BVector<f32, Dyn>, UserError> {
-    let mut derivative = BVector::from_element_generic(Dyn(y.len()), U1, 0f32);
-    let mut velocities = data.velocities.borrow_mut();
+impl ParticleSystem {
+    fn new(radius: f32, aspect_ratio: f32) -> Self {
+        Self {
+            radius,
+            aspect_ratio,
+            velocities: Rc::new(RefCell::new(BVector::from_element_generic(Dyn(0), U1, 0.0))),
+            restitution: None,
+            hash: Rc::new(RefCell::new(SpatialHash::new(2.0 * radius))),
+            rng: thread_rng(),
+        }
+    }
+
+    fn spawn(&mut self, num: usize, state: &mut State) -> bool {
+        let distribution = Uniform::new_inclusive(-1.0, 1.0);
+
+        let mut new_particles = Vec::with_capacity(num);
+        let mut failed_circles = 0;
+
+        while new_particles.len() < num && failed_circles < 50 {
+            let x = self.rng.sample(distribution);
+            let y = self.rng.sample(distribution);
 
-    for (particle_idx, state) in y.chunks_exact(2).enumerate() {
-        let velocity = &mut velocities.as_mut_slice()[particle_idx * 2..(particle_idx + 1) * 2];
+            let new_velocity = self.random_velocity();
 
-        if state[0] <= -1.0 + data.radius || state[0] >= 1.0 - data.radius {
-            velocity[0] *= -1.0;
+            if !self.fits(state, &new_particles, [x, y]) {
+                failed_circles += 1;
+                continue;
+            }
+
+            new_particles.push(([x, y], new_velocity));
+            failed_circles = 0;
         }
 
-        if state[1] <= -1.0 + data.radius * data.aspect_ratio
-            || state[1] >= 1.0 - data.radius * data.aspect_ratio
-        {
-            velocity[1] *= -1.0;
+        if failed_circles == 50 {
+            return false;
         }
 
-        derivative[particle_idx * 2] = velocity[0];
-        derivative[particle_idx * 2 + 1] = velocity[1];
+        self.push(state, &new_particles);
+        true
     }
-    Ok(derivative)
-}
 
-impl PhysicsThread {
-    pub async fn create(self) -> Handle {
-        spawn(move || {
-            let mut thread = self;
-            block_on(async move {
-                thread
-                    .thread()
-                    .await
-                    .expect("aftgraphs::particles::physics::PhysicsThread: failed");
-            });
-        })
-        .await
-        .expect("aftgraphs::particles::physics::PhysicsThread: failed to spawn")
+    fn random_velocity(&mut self) -> (f32, f32) {
+        let velocity_distribution = Uniform::new_inclusive(0.0, MAX_VELOCITY);
+        let angle_distribution = Uniform::new(0.0, std::f32::consts::TAU);
+
+        let new_velocity = self.rng.sample(velocity_distribution);
+        let angle = self.rng.sample(angle_distribution);
+        (new_velocity * angle.cos(), new_velocity * angle.sin())
     }
 
-    pub async fn thread(&mut self) -> Result<(), IVPError> {
-        let mut num_particles = 0;
-        let velocities = Rc::new(RefCell::new(BVector::from_element_generic(
-            Dyn(num_particles * 2),
-            U1::name(),
-            0.0,
-        )));
+    /// Whether a particle at position is inside the walls and does not overlap any other
+    fn fits(
+        &self,
+        state: &State,
+        new_particles: &[([f32; 2], (f32, f32))],
+        position: [f32; 2],
+    ) -> bool {
+        let [x, y] = position;
+
+        if x <= -1.0 + self.radius || x >= 1.0 - self.radius {
+            return false;
+        }
 
-        let mut rng = thread_rng();
-        let distribution = Uniform::new_inclusive(-1.0, 1.0);
-        let velocity_distribution = Uniform::new_inclusive(0.0, MAX_VELOCITY);
-        let angle_distribution = Uniform::new(0.0, std::f32::consts::TAU);
-        let mut new_particles = vec![];
+        if y <= -1.0 + self.radius * self.aspect_ratio || y >= 1.0 - self.radius * self.aspect_ratio
+        {
+            return false;
+        }
 
-        let data = PhysicsData {
-            radius: self.radius,
-            aspect_ratio: self.aspect_ratio,
-            velocities: velocities.clone(),
-        };
+        !state
+            .as_slice()
+            .chunks_exact(2)
+            .chain(new_particles.iter().map(|(c, _)| c.as_slice()))
+            .any(|circle| {
+                (circle[0] - x).powi(2) + ((circle[1] - y) / self.aspect_ratio).powi(2)
+                    <= 4.0 * self.radius.powi(2)
+            })
+    }
 
-        let mut solver = Euler::new_dyn(num_particles * 2)?
-            .with_tolerance(1e-2)?
-            .with_minimum_dt(0.1)?
-            .with_maximum_dt(0.1)?
-            .with_initial_time(self.time)?
-            .with_ending_time(f32::INFINITY)?
-            .with_initial_conditions(BVector::from_element_generic(Dyn(0), U1::name(), 0.0))?
-            .with_derivative(
-                Box::new(particle_derivative) as Box<dyn Derivative<f32, Dyn, PhysicsData>>
-            )
-            .solve(data)?;
-
-        loop {
-            let len = {
-                let mut lock = self.lock.lock().await;
-                if *lock {
-                    *lock = false;
-                    continue;
-                }
+    fn spawn_at(&mut self, position: [f32; 2], state: &mut State) -> bool {
+        if !self.fits(state, &[], position) {
+            return false;
+        }
+
+        let new_velocity = self.random_velocity();
+        self.push(state, &[(position, new_velocity)]);
+        true
+    }
+
+    fn push(&mut self, state: &mut State, new_particles: &[([f32; 2], (f32, f32))]) {
+        let num_particles = state.len() / 2 + new_particles.len();
+
+        let mut v = self.velocities.borrow_mut();
+        let iter = v
+            .iter()
+            .cloned()
+            .chain(new_particles.iter().flat_map(|&(_, v)| [v.0, v.1]));
+        *v = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+
+        let iter = state
+            .iter()
+            .cloned()
+            .chain(new_particles.iter().flat_map(|&(c, _)| c));
+        *state = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+    }
 
-                let next = solver.next().ok_or(IVPError::TimeEndOOB)??;
-                self.time = next.0;
-                self.deque.push(next);
-                self.deque.len()
+    /// Remove the particle nearest to position, measured in the space where particles are circles
+    fn remove_nearest(&mut self, position: [f32; 2], state: &mut State) -> bool {
+        let nearest = {
+            let mut hash = self.hash.borrow_mut();
+            hash.build(
+                state
+                    .as_slice()
+                    .chunks_exact(2)
+                    .map(|particle| [particle[0], particle[1] / self.aspect_ratio]),
+            );
+
+            let point = [position[0], position[1] / self.aspect_ratio];
+            let distance = |idx: &usize| {
+                let particle = hash.positions()[*idx];
+                (particle[0] - point[0]).hypot(particle[1] - point[1])
             };
+            hash.query(point, PICK_RADIUS * self.radius)
+                .into_iter()
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        };
+        let Some(nearest) = nearest else {
+            return false;
+        };
 
-            match self.reset.try_recv() {
-                Ok(reset) => {
-                    let (start_time, mut start_state) = {
-                        let s = self.deque.steal();
-                        if let Some(s) = s.success() {
-                            s
-                        } else {
-                            (
-                                self.time,
-                                BVector::from_element_generic(Dyn(num_particles * 2), U1, 0f32),
-                            )
-                        }
-                    };
-
-                    match reset {
-                        PhysicsMessage::Reset(time, radius, aspect_ratio) => {
-                            self.time = time;
-                            self.radius = radius;
-                            self.aspect_ratio = aspect_ratio;
-                        }
-                        PhysicsMessage::Pop(num) => {
-                            if num > num_particles {
-                                num_particles = 0;
-                            } else {
-                                num_particles -= num;
-                            }
-
-                            let v = velocities.as_ref();
-                            let mut v = v.borrow_mut();
-                            let iter = v.into_iter().cloned().take(num_particles * 2);
-                            *v = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
-
-                            let iter = start_state.into_iter().cloned().take(num_particles * 2);
-                            start_state =
-                                BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
-                        }
-                        PhysicsMessage::Spawn(num) => {
-                            let mut idx = 0;
-                            let mut failed_circles = 0;
-                            new_particles.clear();
-
-                            while idx < num && failed_circles < 50 {
-                                let x = rng.sample(distribution);
-                                let y = rng.sample(distribution);
-
-                                let new_velocity = rng.sample(velocity_distribution);
-                                let angle = rng.sample(angle_distribution);
-                                let new_velocity =
-                                    (new_velocity * angle.cos(), new_velocity * angle.sin());
-
-                                if x <= -1.0 + self.radius || x >= 1.0 - self.radius {
-                                    failed_circles += 1;
-                                    continue;
-                                }
-
-                                if y <= -1.0 + self.radius * self.aspect_ratio
-                                    || y >= 1.0 - self.radius * self.aspect_ratio
-                                {
-                                    failed_circles += 1;
-                                    continue;
-                                }
-
-                                for circle in start_state.as_slice().chunks(2) {
-                                    if (circle[0] - x).powi(2)
-                                        + ((circle[1] - y) / self.aspect_ratio).powi(2)
-                                        <= 4.0 * self.radius.powi(2)
-                                    {
-                                        failed_circles += 1;
-                                        continue;
-                                    }
-                                }
-
-                                new_particles.push(((x, y), new_velocity));
-                                failed_circles = 0;
-                                idx += 1;
-                            }
-
-                            if failed_circles == 50 {
-                                self.response.send(false).await.expect("aftgraphs::particles::physics::PhysicsThread: Failed to send response");
-                                continue;
-                            }
-
-                            num_particles += num;
-                            let v = velocities.as_ref();
-                            let mut v = v.borrow_mut();
-                            let iter = v.into_iter().cloned().chain(
-                                new_particles
-                                    .iter()
-                                    .flat_map(|&(_, v)| [v.0, v.1].into_iter()),
-                            );
-                            *v = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
-
-                            let iter = start_state.into_iter().cloned().chain(
-                                new_particles
-                                    .iter()
-                                    .flat_map(|&(c, _)| [c.0, c.1].into_iter()),
-                            );
-                            start_state =
-                                BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
-                        }
-                    }
-
-                    self.time = start_time;
-
-                    while self.deque.steal().success().is_some() {}
-
-                    let data = PhysicsData {
-                        radius: self.radius,
-                        aspect_ratio: self.aspect_ratio,
-                        velocities: velocities.clone(),
-                    };
-
-                    self.deque.push((self.time, start_state.clone()));
-                    solver = Euler::new_dyn(num_particles * 2)?
-                        .with_tolerance(1e-2)?
-                        .with_minimum_dt(0.1)?
-                        .with_maximum_dt(0.1)?
-                        .with_initial_time(self.time)?
-                        .with_ending_time(f32::INFINITY)?
-                        .with_initial_conditions(start_state)?
-                        .with_derivative(Box::new(particle_derivative)
-                            as Box<dyn Derivative<f32, Dyn, PhysicsData>>)
-                        .solve(data)?;
-
-                    self.response.send(true).await.expect(
-                        "aftgraphs::particles::physics::PhysicsThread: failed to send response",
-                    );
-                }
-                Err(TryRecvError::Closed) => break Ok(()),
-                _ => (),
+        let keep = |(idx, _): &(usize, &f32)| idx / 2 != nearest;
+        let num_particles = state.len() / 2 - 1;
+
+        let mut v = self.velocities.borrow_mut();
+        let iter = v
+            .iter()
+            .enumerate()
+            .filter(keep)
+            .map(|(_, &v)| v)
+            .collect::<Vec<_>>();
+        *v = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+
+        let iter = state
+            .iter()
+            .enumerate()
+            .filter(keep)
+            .map(|(_, &c)| c)
+            .collect::<Vec<_>>();
+        *state = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+
+        true
+    }
+
+    fn pop(&mut self, num: usize, state: &mut State) {
+        let num_particles = (state.len() / 2).saturating_sub(num);
+
+        let mut v = self.velocities.borrow_mut();
+        let iter = v.iter().cloned().take(num_particles * 2);
+        *v = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+
+        let iter = state.iter().cloned().take(num_particles * 2);
+        *state = BVector::from_iterator_generic(Dyn(num_particles * 2), U1, iter);
+    }
+}
+
+/// Resolve elastic collisions between overlapping particles that are moving towards each other
+/// Positions and velocities are scaled to a space where y is not stretched by the aspect ratio,
+/// so that particles are circles. All particles have the same mass.
+fn collide(y: &[f32], velocities: &mut [f32], data: &PhysicsData) {
+    let Some(restitution) = data.restitution else {
+        return;
+    };
+
+    let mut hash = data.hash.borrow_mut();
+    hash.build(
+        y.chunks_exact(2)
+            .map(|position| [position[0], position[1] / data.aspect_ratio]),
+    );
+
+    for (i, j) in hash.pairs(2.0 * data.radius) {
+        let (pi, pj) = (hash.positions()[i], hash.positions()[j]);
+        let normal = [pj[0] - pi[0], pj[1] - pi[1]];
+        let distance = normal[0].hypot(normal[1]);
+        if distance <= f32::EPSILON {
+            continue;
+        }
+        let normal = [normal[0] / distance, normal[1] / distance];
+
+        let relative = [
+            velocities[j * 2] - velocities[i * 2],
+            (velocities[j * 2 + 1] - velocities[i * 2 + 1]) / data.aspect_ratio,
+        ];
+        let approach = relative[0] * normal[0] + relative[1] * normal[1];
+        if approach >= 0.0 {
+            continue;
+        }
+
+        let impulse = 0.5 * (1.0 + restitution) * approach;
+        velocities[i * 2] += impulse * normal[0];
+        velocities[i * 2 + 1] += impulse * normal[1] * data.aspect_ratio;
+        velocities[j * 2] -= impulse * normal[0];
+        velocities[j * 2 + 1] -= impulse * normal[1] * data.aspect_ratio;
+    }
+}
+
+impl DynamicalSystem for ParticleSystem {
+    type Data = PhysicsData;
+    type Message = PhysicsMessage;
+
+    fn derivative(_t: f32, y: &[f32], data: &mut PhysicsData) -> Result<State, UserError> {
+        let mut derivative = BVector::from_element_generic(Dyn(y.len()), U1, 0f32);
+        let mut velocities = data.velocities.borrow_mut();
+        collide(y, velocities.as_mut_slice(), data);
+
+        for (particle_idx, state) in y.chunks_exact(2).enumerate() {
+            let velocity = &mut velocities.as_mut_slice()[particle_idx * 2..(particle_idx + 1) * 2];
+
+            if state[0] <= -1.0 + data.radius || state[0] >= 1.0 - data.radius {
+                velocity[0] *= -1.0;
             }
 
-            if len > 2 && !self.display || len > 100 && self.display {
-                self.request.park();
+            if state[1] <= -1.0 + data.radius * data.aspect_ratio
+                || state[1] >= 1.0 - data.radius * data.aspect_ratio
+            {
+                velocity[1] *= -1.0;
+            }
+
+            derivative[particle_idx * 2] = velocity[0];
+            derivative[particle_idx * 2 + 1] = velocity[1];
+        }
+        Ok(derivative)
+    }
+
+    fn data(&self) -> PhysicsData {
+        PhysicsData {
+            radius: self.radius,
+            aspect_ratio: self.aspect_ratio,
+            velocities: self.velocities.clone(),
+            restitution: self.restitution,
+            hash: self.hash.clone(),
+        }
+    }
+
+    fn handle_message(&mut self, message: PhysicsMessage, _time: f32, state: &mut State) -> bool {
+        match message {
+            PhysicsMessage::Reset(radius, aspect_ratio) => {
+                self.radius = radius;
+                self.aspect_ratio = aspect_ratio;
+                self.hash.borrow_mut().set_cell_size(2.0 * radius);
+                true
+            }
+            PhysicsMessage::Collisions(restitution) => {
+                self.restitution = restitution;
+                true
+            }
+            PhysicsMessage::Spawn(num) => self.spawn(num, state),
+            PhysicsMessage::SpawnAt(position) => self.spawn_at(position, state),
+            PhysicsMessage::RemoveNearest(position) => self.remove_nearest(position, state),
+            PhysicsMessage::Pop(num) => {
+                self.pop(num, state);
+                true
             }
         }
     }
@@ -288,43 +328,24 @@ impl Physics {
         time: f32,
         radius: f32,
         aspect_ratio: f32,
-    ) -> Result<Self, IVPError> {
-        let (tx, rx) = bounded(1);
-        let (response_tx, response_rx) = bounded(1);
-
-        let request = Parker::new();
-        let request_unpark = request.unparker().clone();
-        request_unpark.unpark();
-
-        let reset = Arc::new(Mutex::new(false));
-
-        let deque = Arc::new(Injector::new());
-
-        let thread = PhysicsThread {
-            display,
-            deque: deque.clone(),
+    ) -> Result<Self, DynamicsError> {
+        let worker = IntegratorWorker::spawn(
+            WorkerSettings::new(display),
             time,
-            radius,
-            aspect_ratio,
-            reset: rx,
-            response: response_tx,
-            request,
-            lock: reset.clone(),
-        };
-        let handle = thread.create().await;
+            BVector::from_element_generic(Dyn(0), U1, 0.0),
+            move || ParticleSystem::new(radius, aspect_ratio),
+        )
+        .await?;
 
         Ok(Self {
-            deque,
-            scratchpad: vec![],
-            time,
+            worker,
             radius,
             aspect_ratio,
             num_particles: 0,
-            lock: reset,
-            reset_tx: tx,
-            response: response_rx,
-            request: request_unpark,
-            _handle: handle,
+            restitution: None,
+            speed_colormap: None,
+            last_state: None,
+            speeds: vec![],
         })
     }
 
@@ -332,60 +353,71 @@ impl Physics {
         self.num_particles
     }
 
-    pub async fn update_aspect_ratio(&mut self, aspect_ratio: f32) {
-        if aspect_ratio != self.aspect_ratio {
-            self.aspect_ratio = aspect_ratio;
+    async fn reset(&mut self) {
+        self.worker
+            .send(PhysicsMessage::Reset(self.radius, self.aspect_ratio))
+            .await
+            .expect("aftgraphs::particles::Physics: failed to send reset message");
+    }
+
+    pub async fn set_radius(&mut self, radius: f32) {
+        if radius != self.radius {
+            self.radius = radius;
             self.reset().await;
         }
     }
 
-    async fn reset(&mut self) {
-        let mut lock = self.lock.lock().await;
-        *lock = true;
-
-        let mut state = BVector::from_element_generic(Dyn(self.num_particles * 2), U1, 0f32);
-        let mut state_time = 0.0;
-        loop {
-            let s = self.deque.steal();
-            if s.is_empty() {
-                break;
-            }
+    /// Color particles by their speed, from zero to MAX_VELOCITY, or white if None
+    pub fn set_speed_colormap(&mut self, colormap: Option<Colormap>) {
+        self.speed_colormap = colormap;
+    }
 
-            if let Some(s) = s.success() {
-                if s.0 <= self.time {
-                    state = s.1;
-                    state_time = s.0;
+    /// Turn particle-particle collisions on with the given restitution coefficient, or off
+    pub async fn set_collisions(&mut self, restitution: Option<f32>) {
+        if restitution != self.restitution {
+            self.restitution = restitution;
+            self.worker
+                .send(PhysicsMessage::Collisions(restitution))
+                .await
+                .expect("aftgraphs::particles::Physics: failed to send collisions message");
+        }
+    }
+
+    /// Estimate the speed of each particle from the interpolated states of this and the last frame
+    /// Particles that did not exist last frame keep a speed of zero until the next frame.
+    fn update_speeds(&mut self, t: f32, state: &State) {
+        self.speeds.resize(state.len() / 2, 0.0);
+
+        if let Some((last_t, last_state)) = self.last_state.as_ref() {
+            let dt = t - last_t;
+            // Particles were added or removed, the indices may not line up
+            if dt > 0.0 && last_state.len() == state.len() {
+                let positions = state.as_slice().chunks_exact(2);
+                let last_positions = last_state.as_slice().chunks_exact(2);
+                for ((speed, position), last_position) in
+                    self.speeds.iter_mut().zip(positions).zip(last_positions)
+                {
+                    *speed =
+                        (position[0] - last_position[0]).hypot(position[1] - last_position[1]) / dt;
                 }
             }
         }
 
-        self.deque.push((state_time, state));
-        self.time = state_time;
-        drop(lock);
-
-        self.reset_tx
-            .send(PhysicsMessage::Reset(
-                self.time,
-                self.radius,
-                self.aspect_ratio,
-            ))
-            .await
-            .expect("aftgraphs::particles::Physics: failed to send reset message");
-        self.request.unpark();
-        self.response
-            .recv()
-            .await
-            .expect("aftgraphs::particles::Physics: failed to receive response");
+        self.last_state = Some((t, state.clone()));
     }
 
-    fn instances(&self, state: BVector<f32, Dyn>) -> Vec<Instance> {
+    fn instances(&self, state: State) -> Vec<Instance> {
         let mut instances = Vec::with_capacity(state.len() / 2);
 
-        for particle in state.as_slice().chunks_exact(2) {
+        for (particle, &speed) in state.as_slice().chunks_exact(2).zip(&self.speeds) {
+            let color = self
+                .speed_colormap
+                .map_or([1.0; 3], |colormap| colormap.sample(speed / MAX_VELOCITY));
+
             instances.push(Instance {
                 position: [particle[0], particle[1]],
                 radius: self.radius,
-                color: [1.0; 3],
+                color,
             });
         }
 
@@ -393,69 +425,64 @@ impl Physics {
     }
 
     pub async fn get_state(&mut self, t: f32) -> Vec<Instance> {
-        self.scratchpad.clear();
-        self.request.unpark();
-
-        let (time_after, state_after) = loop {
-            let s = self.deque.steal();
-
-            if s.is_empty() {
-                log::warn!("queue is empty");
-                self.request.unpark();
-            } else if let Some(s) = s.success() {
-                if s.0 < t {
-                    self.scratchpad.push(s);
-                    continue;
-                } else {
-                    break s;
-                }
+        match self.worker.state_at(t).await {
+            Ok(state) => {
+                self.update_speeds(t, &state);
+                self.instances(state)
+            }
+            Err(e) => {
+                log::error!("aftgraphs::particles::Physics::get_state: {e}");
+                vec![]
             }
-        };
-
-        if self.scratchpad.len() > 10 {
-            log::warn!("too many old states: {}", self.scratchpad.len());
-        }
-
-        if self.scratchpad.is_empty() {
-            self.instances(state_after)
-        } else {
-            let (time_before, ref state_before) = self.scratchpad[self.scratchpad.len() - 1];
-            let time_bracket = time_after - time_before;
-            let interp_t = (t - time_before) / time_bracket;
-            self.instances(state_before * interp_t + state_after * (1.0 - interp_t))
         }
     }
 
     pub async fn spawn(&mut self, num: usize) -> bool {
-        self.reset_tx
+        let spawned = self
+            .worker
             .send(PhysicsMessage::Spawn(num))
             .await
-            .expect("aftgraphs::particles::Physics::push_circle: failed to send reset message");
+            .expect("aftgraphs::particles::Physics::spawn: failed to send spawn message");
 
-        self.request.unpark();
-
-        if self.response.recv().await.unwrap_or(false) {
+        if spawned {
             self.num_particles += num;
-            true
-        } else {
-            false
         }
+        spawned
     }
 
-    pub async fn pop(&mut self, num: usize) {
-        self.reset_tx
-            .send(PhysicsMessage::Pop(num))
+    /// Spawn a particle at the position, returning false if it would overlap a wall or particle
+    pub async fn spawn_at(&mut self, position: [f32; 2]) -> bool {
+        let spawned = self
+            .worker
+            .send(PhysicsMessage::SpawnAt(position))
             .await
-            .expect("aftgraphs::particles::Physics::pop: failed to send reset message");
-        self.request.unpark();
-        self.response
-            .recv()
+            .expect("aftgraphs::particles::Physics::spawn_at: failed to send spawn message");
+
+        if spawned {
+            self.num_particles += 1;
+        }
+        spawned
+    }
+
+    /// Remove the particle nearest to the position, returning false if none was close enough
+    pub async fn remove_nearest(&mut self, position: [f32; 2]) -> bool {
+        let removed = self
+            .worker
+            .send(PhysicsMessage::RemoveNearest(position))
             .await
-            .expect("aftgraphs::particles::Physics::pop: failed to receive response");
-        if self.num_particles > num {
-            self.num_particles -= num;
-        } else {
-            self.num_particles = 0;
+            .expect("aftgraphs::particles::Physics::remove_nearest: failed to send remove message");
+
+        if removed {
+            self.num_particles -= 1;
         }
+        removed
+    }
+
+    pub async fn pop(&mut self, num: usize) {
+        self.worker
+            .send(PhysicsMessage::Pop(num))
+            .await
+            .expect("aftgraphs::particles::Physics::pop: failed to send pop message");
+        self.num_particles = self.num_particles.saturating_sub(num);
     }
 }
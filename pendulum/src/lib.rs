@@ -0,0 +1,250 @@
+use aftgraphs::prelude::*;
+use aftgraphs_macros::sim_main;
+use std::{collections::HashMap, f32::consts::PI};
+
+mod physics;
+use physics::Pendulum;
+
+/// Length of each arm in the clip space of the pendulum viewport
+const ARM: f32 = 0.45;
+
+/// Angular velocities mapped to the top and bottom of the phase plot
+const MAX_OMEGA: f32 = 10.0;
+
+const GRAVITY: f32 = 9.5;
+const TRAIL: usize = 1000;
+const INITIAL_ANGLES: (f32, f32) = (2.0, 2.5);
+
+/// Label the rods pipeline is compiled under in Simulation::prepare
+const RODS_PIPELINE: &str = "aftgraphs::pendulum::DoublePendulumSimulation::rods_pipeline";
+/// The rods are a line strip of [f32; 2] positions
+const ROD_ATTRIBUTES: [VertexAttribute; 1] = [VertexAttribute {
+    offset: 0,
+    shader_location: 0,
+    format: VertexFormat::Float32x2,
+}];
+
+const BOB_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const INNER_COLOR: [f32; 3] = [0.231, 0.510, 0.965];
+const OUTER_COLOR: [f32; 3] = [0.976, 0.451, 0.086];
+
+struct DoublePendulumSimulation {
+    rods: VertexBuffer<[f32; 2]>,
+    rods_pipeline: RenderPipeline,
+    bobs: MarkerBuffer,
+    phase: MarkerBuffer,
+    /// Phase space points (theta, omega) of the inner and outer arm, oldest first
+    trail: Vec<([f32; 2], [f32; 2])>,
+    pendulum: Pendulum,
+    inputs_initialized: bool,
+    /// Window size in pixels as of the last frame, to map clicks into the pendulum viewport
+    window_size: [f32; 2],
+    reset: Option<(f32, f32)>,
+}
+
+/// Square viewports [x, y, width, height] for the pendulum on the left
+/// and the phase plot on the right, centered in their halves of the window
+fn viewports([width, height]: [f32; 2]) -> ([f32; 4], [f32; 4]) {
+    let side = (width / 2.0).min(height);
+    let x = (width / 2.0 - side) / 2.0;
+    let y = (height - side) / 2.0;
+
+    ([x, y, side, side], [width / 2.0 + x, y, side, side])
+}
+
+/// Wrap an angle to [-pi, pi)
+fn wrap(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+impl DoublePendulumSimulation {
+    fn update_trail(&mut self, state: [f32; 4], length: usize) {
+        let [theta1, theta2, omega1, omega2] = state;
+        self.trail.push(([theta1, omega1], [theta2, omega2]));
+
+        let excess = self.trail.len().saturating_sub(length);
+        self.trail.drain(..excess);
+    }
+
+    fn phase_markers(&self) -> Vec<Marker> {
+        let len = self.trail.len() as f32;
+        let point = |[theta, omega]: [f32; 2]| [wrap(theta) / PI, omega / MAX_OMEGA];
+
+        self.trail
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, &(inner, outer))| {
+                // Fade out towards the oldest points
+                let alpha = (idx + 1) as f32 / len;
+                let [r, g, b] = INNER_COLOR;
+                let inner = Marker::new(point(inner), 3.0, MarkerShape::Circle, [r, g, b, alpha]);
+                let [r, g, b] = OUTER_COLOR;
+                let outer = Marker::new(point(outer), 3.0, MarkerShape::Circle, [r, g, b, alpha]);
+                [inner, outer]
+            })
+            .collect()
+    }
+}
+
+impl Simulation for DoublePendulumSimulation {
+    fn prepare<P: UiPlatform>(warmup: &mut Warmup<P>) {
+        warmup.pipeline(RODS_PIPELINE, |renderer| {
+            let module = include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/rods.wgsl"));
+            let shader = ShaderBuilder::new()
+                .with_module(module)
+                .with_default_fs_entrypoint()
+                .with_buffer(wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 2]>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &ROD_ATTRIBUTES,
+                })
+                .build(renderer);
+
+            RenderPipelineBuilder::new()
+                .with_pipeline_label(Some(RODS_PIPELINE))
+                .with_vertex_shader(shader)
+                .with_primitive_state(wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineStrip,
+                    ..Default::default()
+                })
+                .build(renderer)
+        });
+    }
+
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let rods = VertexBufferBuilder::new()
+            .with_initial_vertices(&[[0.0; 2]; 3])
+            .with_label(Some("aftgraphs::pendulum::DoublePendulumSimulation::rods"))
+            .with_attributes_owned(ROD_ATTRIBUTES.to_vec())
+            .build(renderer);
+
+        let rods_pipeline = renderer
+            .take_pipeline(RODS_PIPELINE)
+            .expect("aftgraphs::pendulum::DoublePendulumSimulation::rods_pipeline failed to build");
+
+        let bobs = MarkerBuffer::new(
+            renderer,
+            MarkerSizing::Clip,
+            Some("aftgraphs::pendulum::DoublePendulumSimulation::bobs"),
+        );
+        let phase = MarkerBuffer::new(
+            renderer,
+            MarkerSizing::Pixels,
+            Some("aftgraphs::pendulum::DoublePendulumSimulation::phase"),
+        );
+
+        let pendulum = Pendulum::new(renderer.surface.is_some(), 0.0, GRAVITY, INITIAL_ANGLES)
+            .await
+            .expect("aftgraphs::pendulum::DoublePendulumSimulation::pendulum failed to create");
+
+        Self {
+            rods,
+            rods_pipeline,
+            bobs,
+            phase,
+            trail: Vec::with_capacity(TRAIL),
+            pendulum,
+            inputs_initialized: false,
+            window_size: [1.0; 2],
+            reset: None,
+        }
+    }
+
+    /// Left click in the pendulum viewport drops the pendulum from rest, pointing at the cursor
+    async fn on_input(&mut self, event: InputEvent) {
+        let InputEvent::Mouse(ElementState::Pressed, MouseButton::Left, (x, y)) = event else {
+            return;
+        };
+
+        let [width, height] = self.window_size;
+        let ([vx, vy, side, _], _) = viewports(self.window_size);
+        let pixel = [
+            (x as f32 + 1.0) / 2.0 * width,
+            (1.0 - y as f32) / 2.0 * height,
+        ];
+        let local = [
+            (pixel[0] - vx) / side * 2.0 - 1.0,
+            1.0 - (pixel[1] - vy) / side * 2.0,
+        ];
+        if local.iter().any(|coord| coord.abs() > 1.0) {
+            return;
+        }
+
+        let angle = local[0].atan2(-local[1]);
+        self.reset = Some((angle, angle));
+    }
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        // Sliders start at their lower bound
+        if !self.inputs_initialized {
+            self.inputs_initialized = true;
+            inputs
+                .entry("controls.gravity".to_owned())
+                .or_insert(InputValue::SLIDER(GRAVITY as f64));
+            inputs
+                .entry("controls.trail".to_owned())
+                .or_insert(InputValue::SLIDER(TRAIL as f64));
+        }
+
+        if let Some(&InputValue::SLIDER(gravity)) = inputs.get("controls.gravity") {
+            self.pendulum.set_gravity(gravity as f32).await;
+        }
+        let trail = match inputs.get("controls.trail") {
+            Some(&InputValue::SLIDER(trail)) => trail as usize,
+            _ => TRAIL,
+        };
+
+        if let Some((theta1, theta2)) = self.reset.take() {
+            self.pendulum.reset(theta1, theta2).await;
+            self.trail.clear();
+        }
+
+        let Some(state) = self.pendulum.get_state(renderer.clock.time() as f32).await else {
+            return;
+        };
+        self.update_trail(state, trail);
+
+        let [theta1, theta2, _, _] = state;
+        let inner = [ARM * theta1.sin(), -ARM * theta1.cos()];
+        let outer = [inner[0] + ARM * theta2.sin(), inner[1] - ARM * theta2.cos()];
+
+        {
+            let mut rods = self.rods.modify(renderer);
+            rods.copy_from_slice(&[[0.0; 2], inner, outer]);
+        }
+        {
+            let mut bobs = self.bobs.modify(renderer);
+            let bobs = bobs.markers_vec();
+            bobs.clear();
+            bobs.push(Marker::new([0.0; 2], 0.04, MarkerShape::Square, BOB_COLOR));
+            bobs.push(Marker::new(inner, 0.1, MarkerShape::Circle, BOB_COLOR));
+            bobs.push(Marker::new(outer, 0.1, MarkerShape::Circle, BOB_COLOR));
+        }
+        {
+            let markers = self.phase_markers();
+            *self.phase.modify(renderer).markers_vec() = markers;
+        }
+
+        let [width, height] = renderer.viewport_size();
+        self.window_size = [width as f32, height as f32];
+        let (pendulum_viewport, phase_viewport) = viewports(self.window_size);
+
+        let [x, y, side, _] = pendulum_viewport;
+        render_pass.set_viewport(x, y, side, side, 0.0, 1.0);
+        render_pass.set_pipeline(&self.rods_pipeline);
+        self.rods.bind(&mut render_pass, 0);
+        render_pass.draw(self.rods.range(), 0..1);
+        self.bobs
+            .draw_in_viewport(renderer, &mut render_pass, pendulum_viewport);
+
+        self.phase
+            .draw_in_viewport(renderer, &mut render_pass, phase_viewport);
+    }
+}
+
+sim_main! { "/res/pendulum.toml", DoublePendulumSimulation }
@@ -0,0 +1,5 @@
+use pendulum::sim_main;
+
+fn main() {
+    sim_main();
+}
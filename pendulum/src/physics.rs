@@ -0,0 +1,112 @@
+use aftgraphs::dynamics::{
+    bacon_sci::{ivp::UserError, prelude::*},
+    DynamicalSystem, DynamicsError, Integrator, IntegratorWorker, State, WorkerSettings,
+};
+
+/// Both arms have unit mass and unit length
+/// The state is [theta1, theta2, omega1, omega2], angles measured from straight down
+pub struct Pendulum {
+    worker: IntegratorWorker<DoublePendulum>,
+    gravity: f32,
+}
+
+enum PendulumMessage {
+    Gravity(f32),
+    /// Restart at rest from the given angles
+    Reset(f32, f32),
+}
+
+struct DoublePendulum {
+    gravity: f32,
+}
+
+impl DynamicalSystem for DoublePendulum {
+    type Data = f32;
+    type Message = PendulumMessage;
+
+    fn derivative(_t: f32, y: &[f32], gravity: &mut f32) -> Result<State, UserError> {
+        let g = *gravity;
+        let [theta1, theta2, omega1, omega2] = [y[0], y[1], y[2], y[3]];
+        let delta = theta1 - theta2;
+        let denominator = 3.0 - (2.0 * delta).cos();
+
+        let alpha1 = (-3.0 * g * theta1.sin()
+            - g * (theta1 - 2.0 * theta2).sin()
+            - 2.0 * delta.sin() * (omega2.powi(2) + omega1.powi(2) * delta.cos()))
+            / denominator;
+        let alpha2 = 2.0
+            * delta.sin()
+            * (2.0 * omega1.powi(2) + 2.0 * g * theta1.cos() + omega2.powi(2) * delta.cos())
+            / denominator;
+
+        Ok(BVector::from_column_slice_generic(
+            Dyn(4),
+            U1,
+            &[omega1, omega2, alpha1, alpha2],
+        ))
+    }
+
+    fn data(&self) -> f32 {
+        self.gravity
+    }
+
+    fn handle_message(&mut self, message: PendulumMessage, _time: f32, state: &mut State) -> bool {
+        match message {
+            PendulumMessage::Gravity(gravity) => self.gravity = gravity,
+            PendulumMessage::Reset(theta1, theta2) => {
+                *state = BVector::from_column_slice_generic(Dyn(4), U1, &[theta1, theta2, 0.0, 0.0])
+            }
+        }
+        true
+    }
+}
+
+impl Pendulum {
+    pub async fn new(
+        display: bool,
+        time: f32,
+        gravity: f32,
+        angles: (f32, f32),
+    ) -> Result<Self, DynamicsError> {
+        let settings =
+            WorkerSettings::new(display).with_integrator(Integrator::RungeKutta4 { dt: 0.002 });
+        let state = BVector::from_column_slice_generic(Dyn(4), U1, &[angles.0, angles.1, 0.0, 0.0]);
+
+        let worker =
+            IntegratorWorker::spawn(settings, time, state, move || DoublePendulum { gravity })
+                .await?;
+
+        Ok(Self { worker, gravity })
+    }
+
+    pub async fn set_gravity(&mut self, gravity: f32) {
+        if gravity != self.gravity {
+            self.gravity = gravity;
+            self.worker
+                .send(PendulumMessage::Gravity(gravity))
+                .await
+                .expect(
+                    "aftgraphs::pendulum::Pendulum::set_gravity: failed to send gravity message",
+                );
+        }
+    }
+
+    /// Drop the pendulum from rest at the given angles
+    pub async fn reset(&mut self, theta1: f32, theta2: f32) {
+        self.worker
+            .send(PendulumMessage::Reset(theta1, theta2))
+            .await
+            .expect("aftgraphs::pendulum::Pendulum::reset: failed to send reset message");
+    }
+
+    /// [theta1, theta2, omega1, omega2] at time t
+    pub async fn get_state(&mut self, t: f32) -> Option<[f32; 4]> {
+        match self.worker.state_at(t).await {
+            Ok(state) => Some([state[0], state[1], state[2], state[3]]),
+            Err(e) => {
+                log::error!("aftgraphs::pendulum::Pendulum::get_state: {e}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+@group(0) @binding(0) var frame: texture_2d<f32>;
+
+// The frame and the sum are the size of the render target, so fragments map one to one onto texels
+// Sub-frames are weighted through the blend constant when summed
+@fragment
+fn fs_main(in: PostVertex) -> @location(0) vec4<f32> {
+    return textureLoad(frame, vec2<i32>(in.position.xy), 0);
+}
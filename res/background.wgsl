@@ -0,0 +1,36 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>, // (-1, 1), y up
+}
+
+struct BackgroundParams {
+    inverse_view_projection: mat4x4<f32>,
+    // Image uv per unit of screen uv, centered on the middle of the screen
+    scale: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: BackgroundParams;
+@group(1) @binding(0) var image: texture_2d<f32>;
+@group(1) @binding(1) var image_sampler: sampler;
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let screen_uv = vec2<f32>(in.ndc.x, -in.ndc.y) * 0.5 + 0.5;
+    let uv = (screen_uv - 0.5) * params.scale + 0.5;
+    let color = textureSample(image, image_sampler, uv);
+    // Letterbox outside of the image
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return vec4<f32>(0.0);
+    }
+    return color;
+}
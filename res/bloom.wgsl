@@ -0,0 +1,59 @@
+struct Bloom {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+}
+
+@group(1) @binding(0) var<uniform> bloom: Bloom;
+// The blurred glow, only bound for fs_composite
+@group(2) @binding(0) var glow: texture_2d<f32>;
+
+// The frame of post.wgsl is the step's source, either the frame or the previous mip
+fn sample_offset(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSample(frame, frame_sampler, uv + texel * vec2<f32>(x, y)).rgb;
+}
+
+// Four bilinear taps averaging a 4x4 block of source texels
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(frame));
+    return (sample_offset(uv, texel, -1.0, -1.0) + sample_offset(uv, texel, 1.0, -1.0)
+        + sample_offset(uv, texel, -1.0, 1.0) + sample_offset(uv, texel, 1.0, 1.0)) * 0.25;
+}
+
+// Keep the part of color above the threshold, easing in over the knee
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = bloom.threshold * bloom.knee + 1e-5;
+    var soft = clamp(brightness - bloom.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    return color * max(soft, brightness - bloom.threshold) / max(brightness, 1e-5);
+}
+
+@fragment
+fn fs_prefilter(in: PostVertex) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: PostVertex) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, added onto the larger mip by the blend state
+@fragment
+fn fs_upsample(in: PostVertex) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(frame));
+    var sum = sample_offset(in.uv, texel, 0.0, 0.0) * 4.0;
+    sum += (sample_offset(in.uv, texel, -1.0, 0.0) + sample_offset(in.uv, texel, 1.0, 0.0)
+        + sample_offset(in.uv, texel, 0.0, -1.0) + sample_offset(in.uv, texel, 0.0, 1.0)) * 2.0;
+    sum += sample_offset(in.uv, texel, -1.0, -1.0) + sample_offset(in.uv, texel, 1.0, -1.0)
+        + sample_offset(in.uv, texel, -1.0, 1.0) + sample_offset(in.uv, texel, 1.0, 1.0);
+    return vec4<f32>(sum / 16.0, 0.0);
+}
+
+@fragment
+fn fs_composite(in: PostVertex) -> @location(0) vec4<f32> {
+    let color = textureSample(frame, frame_sampler, in.uv);
+    let blurred = textureSample(glow, frame_sampler, in.uv).rgb;
+    return vec4<f32>(color.rgb + blurred * bloom.intensity, color.a);
+}
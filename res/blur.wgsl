@@ -0,0 +1,13 @@
+// 3x3 gaussian of bilinear taps between pixels, covering 5x5 pixels
+@fragment
+fn fs_main(in: PostVertex) -> @location(0) vec4<f32> {
+    var sum = vec4<f32>(0.0);
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let weight = f32((2 - abs(x)) * (2 - abs(y))) / 16.0;
+            let offset = vec2<f32>(f32(x), f32(y)) * 1.5 * post.texel;
+            sum += weight * textureSampleLevel(frame, frame_sampler, in.uv + offset, 0.0);
+        }
+    }
+    return sum;
+}
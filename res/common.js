@@ -17,3 +17,19 @@ export async function createWorker(memory, ptr) {
   await wait
   return worker
 }
+
+export async function fetchText(url) {
+  const response = await fetch(url)
+  if (!response.ok) {
+    throw new Error(`${response.status} ${response.statusText}`)
+  }
+  return await response.text()
+}
+
+export async function fetchBytes(url) {
+  const response = await fetch(url)
+  if (!response.ok) {
+    throw new Error(`${response.status} ${response.statusText}`)
+  }
+  return new Uint8Array(await response.arrayBuffer())
+}
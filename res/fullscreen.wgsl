@@ -0,0 +1,12 @@
+// Fullscreen triangle of every pass drawn over a whole target, prepended to its source
+struct PostVertex {
+    @builtin(position) position: vec4<f32>,
+    // Texture coordinates of the target, from the top left
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> PostVertex {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return PostVertex(vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0), uv);
+}
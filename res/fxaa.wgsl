@@ -0,0 +1,35 @@
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+// Blurs along the edges found from the luma of the corners
+@fragment
+fn fs_main(in: PostVertex) -> @location(0) vec4<f32> {
+    let center = textureSample(frame, frame_sampler, in.uv);
+    let nw = luma(textureSample(frame, frame_sampler, in.uv + vec2<f32>(-1.0, -1.0) * post.texel).rgb);
+    let ne = luma(textureSample(frame, frame_sampler, in.uv + vec2<f32>(1.0, -1.0) * post.texel).rgb);
+    let sw = luma(textureSample(frame, frame_sampler, in.uv + vec2<f32>(-1.0, 1.0) * post.texel).rgb);
+    let se = luma(textureSample(frame, frame_sampler, in.uv + vec2<f32>(1.0, 1.0) * post.texel).rgb);
+    let m = luma(center.rgb);
+    let luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    let luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    var dir = vec2<f32>(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    let reduce = max((nw + ne + sw + se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * post.texel;
+
+    let inner = 0.5 * (textureSample(frame, frame_sampler, in.uv + dir * (1.0 / 3.0 - 0.5)).rgb
+        + textureSample(frame, frame_sampler, in.uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    let outer = inner * 0.5 + 0.25 * (textureSample(frame, frame_sampler, in.uv - dir * 0.5).rgb
+        + textureSample(frame, frame_sampler, in.uv + dir * 0.5).rgb);
+
+    // The wider blur crossed another edge if it left the range of the neighborhood
+    let luma_outer = luma(outer);
+    let crossed = luma_outer < luma_min || luma_outer > luma_max;
+    return vec4<f32>(select(outer, inner, crossed), center.a);
+}
@@ -0,0 +1,49 @@
+struct Grade {
+    domain_min: vec4<f32>,
+    domain_max: vec4<f32>,
+    // 1 if the frame is in an sRGB format, so loading it decoded the colors
+    srgb: u32,
+    lut_size: f32,
+}
+
+// Sampled with the frame_sampler of post.wgsl
+@group(1) @binding(0) var lut: texture_3d<f32>;
+@group(1) @binding(1) var<uniform> grade: Grade;
+
+fn encode_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn decode_srgb(encoded: vec3<f32>) -> vec3<f32> {
+    let low = encoded / 12.92;
+    let high = pow((encoded + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, encoded <= vec3<f32>(0.04045));
+}
+
+// The frame is the size of the render target, so fragments map one to one onto texels
+// LUTs map display colors, so sRGB targets are graded in their encoded values
+@fragment
+fn fs_main(in: PostVertex) -> @location(0) vec4<f32> {
+    let texel = textureLoad(frame, vec2<i32>(in.position.xy), 0);
+
+    var color = max(texel.rgb, vec3<f32>(0.0));
+    if grade.srgb != 0u {
+        color = encode_srgb(color);
+    }
+
+    // Sample at the texel centers so the domain maps onto the first and last entries
+    let coords = clamp(
+        (color - grade.domain_min.xyz) / (grade.domain_max.xyz - grade.domain_min.xyz),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0),
+    );
+    let uvw = (coords * (grade.lut_size - 1.0) + 0.5) / grade.lut_size;
+    var graded = max(textureSampleLevel(lut, frame_sampler, uvw, 0.0).rgb, vec3<f32>(0.0));
+
+    if grade.srgb != 0u {
+        graded = decode_srgb(graded);
+    }
+    return vec4<f32>(graded, texel.a);
+}
@@ -0,0 +1,15 @@
+@group(0) @binding(0) var layer: texture_2d<f32>;
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// Layers are the size of the render target, so fragments map one to one onto texels
+// The texels hold premultiplied alpha, the blend state of the pipeline does the compositing
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(layer, vec2<i32>(position.xy), 0);
+}
@@ -0,0 +1,95 @@
+struct VertexInput {
+    @location(0) quad_pos: vec2<f32>, // (-1, 1)
+}
+
+struct InstanceInput {
+    @location(1) position: vec2<f32>,
+    @location(2) size: f32,
+    @location(3) shape: u32,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) quad_pos: vec2<f32>, // (-1, 1)
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) shape: u32,
+}
+
+struct MarkerParams {
+    viewport: vec2<f32>,
+    sizing: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: MarkerParams;
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    var offset: vec2<f32>;
+    if (params.sizing == 0u) {
+        // size is the diameter in pixels
+        offset = vertex.quad_pos * instance.size / params.viewport;
+    } else {
+        // size is the diameter in clip space x units
+        offset = vertex.quad_pos * instance.size * 0.5;
+        offset.y *= params.viewport.x / params.viewport.y;
+    }
+
+    out.clip_position = vec4<f32>(instance.position + offset, 0.0, 1.0);
+    out.quad_pos = vertex.quad_pos;
+    out.color = instance.color;
+    out.shape = instance.shape;
+    return out;
+}
+
+fn sd_circle(p: vec2<f32>) -> f32 {
+    return length(p) - 1.0;
+}
+
+fn sd_square(p: vec2<f32>) -> f32 {
+    let d = abs(p) - vec2<f32>(0.8);
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+}
+
+fn sd_cross(p: vec2<f32>) -> f32 {
+    let q = abs(p);
+    let horizontal = max(q.x - 1.0, q.y - 0.2);
+    let vertical = max(q.y - 1.0, q.x - 0.2);
+    return min(horizontal, vertical);
+}
+
+// Equilateral triangle inscribed in the unit circle, pointing up
+fn sd_triangle(p_in: vec2<f32>) -> f32 {
+    let k = sqrt(3.0);
+    var p = vec2<f32>(abs(p_in.x) - k * 0.5, p_in.y + 0.5);
+    if (p.x + k * p.y > 0.0) {
+        p = vec2<f32>(p.x - k * p.y, -k * p.x - p.y) * 0.5;
+    }
+    p.x -= clamp(p.x, -k, 0.0);
+    return -length(p) * sign(p.y);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var distance: f32;
+    switch in.shape {
+        case 1u: {
+            distance = sd_cross(in.quad_pos);
+        }
+        case 2u: {
+            distance = sd_triangle(in.quad_pos);
+        }
+        case 3u: {
+            distance = sd_square(in.quad_pos);
+        }
+        default: {
+            distance = sd_circle(in.quad_pos);
+        }
+    }
+
+    // Antialias over about one pixel
+    let coverage = clamp(0.5 - distance / max(fwidth(distance), 1e-6), 0.0, 1.0);
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
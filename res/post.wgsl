@@ -0,0 +1,12 @@
+// Declarations shared by every post-processing step, prepended to its source
+struct Post {
+    // Size of the frame in pixels and of one pixel in texture coordinates
+    size: vec2<f32>,
+    texel: vec2<f32>,
+    // Simulation time in seconds
+    time: f32,
+}
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var frame_sampler: sampler;
+@group(0) @binding(2) var<uniform> post: Post;
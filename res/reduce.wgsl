@@ -0,0 +1,159 @@
+const WORKGROUP_SIZE: u32 = 256u;
+const MAX_BINS: u32 = 256u;
+const LARGEST: f32 = 3.40282347e38;
+
+struct Params {
+    count: u32,
+    // Width of the source texture, texels are numbered row by row
+    width: u32,
+    // 0 for no histogram
+    bins: u32,
+    // 0 to 3 a channel of the texels, 4 their luminance, 5 the log2 of their luminance
+    value: u32,
+    histogram_min: f32,
+    histogram_max: f32,
+    // Partials written by the reduce pass
+    workgroups: u32,
+    _padding: u32,
+}
+
+struct Partial {
+    min: f32,
+    max: f32,
+    sum: f32,
+    _padding: f32,
+}
+
+struct Totals {
+    min: f32,
+    max: f32,
+    sum: f32,
+    _padding: f32,
+    bins: array<atomic<u32>, MAX_BINS>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> partials: array<Partial>;
+@group(0) @binding(2) var<storage, read_write> totals: Totals;
+
+// Only one of the sources is bound, depending on the entry point
+@group(1) @binding(0) var<storage, read> values: array<f32>;
+@group(1) @binding(1) var source: texture_2d<f32>;
+
+var<workgroup> shared_partials: array<Partial, WORKGROUP_SIZE>;
+var<workgroup> shared_bins: array<atomic<u32>, MAX_BINS>;
+
+fn empty() -> Partial {
+    return Partial(LARGEST, -LARGEST, 0.0, 0.0);
+}
+
+fn merge(a: Partial, b: Partial) -> Partial {
+    return Partial(min(a.min, b.min), max(a.max, b.max), a.sum + b.sum, 0.0);
+}
+
+// Values outside the histogram's range are counted in the first or last bin
+fn accumulate(partial: Partial, value: f32) -> Partial {
+    if params.bins > 0u {
+        let range = params.histogram_max - params.histogram_min;
+        let position = (value - params.histogram_min) / range * f32(params.bins);
+        let bin = u32(clamp(position, 0.0, f32(params.bins - 1u)));
+        atomicAdd(&shared_bins[bin], 1u);
+    }
+    return Partial(min(partial.min, value), max(partial.max, value), partial.sum + value, 0.0);
+}
+
+fn begin(local: u32) {
+    atomicStore(&shared_bins[local], 0u);
+    workgroupBarrier();
+}
+
+// Tree reduction of the invocations' partials, the first invocation holds the result
+fn reduce_workgroup(local: u32, partial: Partial) -> Partial {
+    shared_partials[local] = partial;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if local < stride {
+            shared_partials[local] = merge(shared_partials[local], shared_partials[local + stride]);
+        }
+        workgroupBarrier();
+    }
+    return shared_partials[0];
+}
+
+fn finish(local: u32, group: u32, partial: Partial) {
+    let reduced = reduce_workgroup(local, partial);
+    if local == 0u {
+        partials[group] = reduced;
+    }
+
+    let count = atomicLoad(&shared_bins[local]);
+    if local < params.bins && count > 0u {
+        atomicAdd(&totals.bins[local], count);
+    }
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn texel_value(texel: vec4<f32>) -> f32 {
+    switch params.value {
+        case 4u: {
+            return luminance(texel.rgb);
+        }
+        case 5u: {
+            return log2(max(luminance(texel.rgb), 1e-5));
+        }
+        default: {
+            var channels = texel;
+            return channels[min(params.value, 3u)];
+        }
+    }
+}
+
+// Each invocation loops over the items in strides of every invocation dispatched
+@compute @workgroup_size(256)
+fn reduce_buffer(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    begin(local);
+    var partial = empty();
+    let stride = groups.x * WORKGROUP_SIZE;
+    for (var index = group.x * WORKGROUP_SIZE + local; index < params.count; index += stride) {
+        partial = accumulate(partial, values[index]);
+    }
+    finish(local, group.x, partial);
+}
+
+@compute @workgroup_size(256)
+fn reduce_texture(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    begin(local);
+    var partial = empty();
+    let stride = groups.x * WORKGROUP_SIZE;
+    for (var index = group.x * WORKGROUP_SIZE + local; index < params.count; index += stride) {
+        let texel = textureLoad(source, vec2<u32>(index % params.width, index / params.width), 0);
+        partial = accumulate(partial, texel_value(texel));
+    }
+    finish(local, group.x, partial);
+}
+
+// Dispatched as a single workgroup after the reduce pass
+@compute @workgroup_size(256)
+fn combine(@builtin(local_invocation_index) local: u32) {
+    var partial = empty();
+    for (var index = local; index < params.workgroups; index += WORKGROUP_SIZE) {
+        partial = merge(partial, partials[index]);
+    }
+    let reduced = reduce_workgroup(local, partial);
+    if local == 0u {
+        totals.min = reduced.min;
+        totals.max = reduced.max;
+        totals.sum = reduced.sum;
+    }
+}
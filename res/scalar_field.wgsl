@@ -0,0 +1,93 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>, // (0, 1), y down
+}
+
+struct FieldParams {
+    range: vec2<f32>,
+    colormap: u32,
+    contours: u32,
+    contour_width: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: FieldParams;
+@group(1) @binding(0) var field: texture_2d<f32>;
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Polynomial fits of the matplotlib colormaps
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+    let c1 = vec3<f32>(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+    let c2 = vec3<f32>(11.60249308247187, -3.972853965665698, -15.9423941062914);
+    let c3 = vec3<f32>(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+    let c4 = vec3<f32>(77.162935699427, -33.40235894210092, -81.80730925738993);
+    let c5 = vec3<f32>(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+    let c6 = vec3<f32>(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn coolwarm(t: f32) -> vec3<f32> {
+    let cool = vec3<f32>(0.230, 0.299, 0.754);
+    let white = vec3<f32>(0.865, 0.865, 0.865);
+    let warm = vec3<f32>(0.706, 0.016, 0.150);
+    if (t < 0.5) {
+        return mix(cool, white, t * 2.0);
+    }
+    return mix(white, warm, t * 2.0 - 1.0);
+}
+
+fn colormap(t: f32) -> vec3<f32> {
+    switch params.colormap {
+        case 0u: {
+            return viridis(t);
+        }
+        case 1u: {
+            return inferno(t);
+        }
+        case 2u: {
+            return coolwarm(t);
+        }
+        default: {
+            return vec3<f32>(t);
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(field));
+    let texel = vec2<i32>(clamp(in.uv * size, vec2<f32>(0.0), size - 1.0));
+    let value = textureLoad(field, texel, 0).r;
+
+    let t = clamp((value - params.range.x) / (params.range.y - params.range.x), 0.0, 1.0);
+    var color = colormap(t);
+
+    // Contour lines at evenly spaced levels of the normalized value
+    let level = t * f32(params.contours);
+    let distance = abs(fract(level - 0.5) - 0.5) / max(fwidth(level), 1e-6);
+    if (params.contours > 0u && distance < params.contour_width) {
+        color = vec3<f32>(0.0);
+    }
+
+    return vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
@@ -0,0 +1,32 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>, // (-1, 1), y up
+}
+
+struct BackgroundParams {
+    inverse_view_projection: mat4x4<f32>,
+    scale: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: BackgroundParams;
+@group(1) @binding(0) var skybox: texture_cube<f32>;
+@group(1) @binding(1) var skybox_sampler: sampler;
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The view ray through the fragment, from the near to the far plane
+    let near = params.inverse_view_projection * vec4<f32>(in.ndc, 0.0, 1.0);
+    let far = params.inverse_view_projection * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = far.xyz / far.w - near.xyz / near.w;
+    return textureSample(skybox, skybox_sampler, direction);
+}
@@ -0,0 +1,75 @@
+// Counting sort of the points of a GpuSpatialHash by bucket, res/spatial_hash.wgsl is prepended
+const WORKGROUP_SIZE: u32 = 256u;
+
+struct Params {
+    cell_size: f32,
+    count: u32,
+    // A power of two, cell_start holds table_size + 1 entries
+    table_size: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// The points of each bucket after count, the first entry of each bucket after scan
+@group(0) @binding(1) var<storage, read_write> cell_start: array<atomic<u32>>;
+@group(0) @binding(2) var<storage, read_write> entries: array<u32>;
+// Index of each point among the points of its bucket
+@group(0) @binding(3) var<storage, read_write> offsets: array<u32>;
+
+@group(1) @binding(0) var<storage, read> positions: array<vec2<f32>>;
+
+var<workgroup> sums: array<u32, WORKGROUP_SIZE>;
+
+fn bucket_of(index: u32) -> u32 {
+    return spatial_bucket(spatial_cell(positions[index], params.cell_size), params.table_size);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn count(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+    offsets[id.x] = atomicAdd(&cell_start[bucket_of(id.x)], 1u);
+}
+
+// Exclusive prefix sum of the counts in a single workgroup, each invocation takes a run of them
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scan(@builtin(local_invocation_index) local: u32) {
+    let len = params.table_size + 1u;
+    let run = (len + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let start = min(local * run, len);
+    let end = min(start + run, len);
+
+    var sum = 0u;
+    for (var i = start; i < end; i++) {
+        sum += atomicLoad(&cell_start[i]);
+    }
+    sums[local] = sum;
+    workgroupBarrier();
+
+    // Inclusive scan of the sums of the runs
+    for (var stride = 1u; stride < WORKGROUP_SIZE; stride *= 2u) {
+        var value = sums[local];
+        if local >= stride {
+            value += sums[local - stride];
+        }
+        workgroupBarrier();
+        sums[local] = value;
+        workgroupBarrier();
+    }
+
+    var offset = sums[local] - sum;
+    for (var i = start; i < end; i++) {
+        let points = atomicLoad(&cell_start[i]);
+        atomicStore(&cell_start[i], offset);
+        offset += points;
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scatter(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+    entries[atomicLoad(&cell_start[bucket_of(id.x)]) + offsets[id.x]] = id.x;
+}
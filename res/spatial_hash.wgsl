@@ -0,0 +1,12 @@
+// Cells and buckets of aftgraphs::spatial::SpatialHash, shared by its GPU build and queries
+
+// The cell of position, in squares of side cell_size
+fn spatial_cell(position: vec2<f32>, cell_size: f32) -> vec2<i32> {
+    return vec2<i32>(floor(position / cell_size));
+}
+
+// The bucket of cell in a table of table_size buckets, a power of two
+fn spatial_bucket(cell: vec2<i32>, table_size: u32) -> u32 {
+    let hash = (bitcast<u32>(cell.x) * 73856093u) ^ (bitcast<u32>(cell.y) * 19349663u);
+    return hash & (table_size - 1u);
+}
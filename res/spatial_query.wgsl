@@ -0,0 +1,27 @@
+// A GpuSpatialHash, prepended to shaders by GpuSpatialHash::query_header
+// The points near a position are found bucket by bucket, checking their distance,
+// since distinct cells can share a bucket:
+//
+//     let cell = spatial_cell(position, spatial.cell_size);
+//     for (var x = cell.x - 1; x <= cell.x + 1; x++) {
+//         for (var y = cell.y - 1; y <= cell.y + 1; y++) {
+//             let bucket = spatial_bucket(vec2<i32>(x, y), spatial.table_size);
+//             for (var i = spatial_cell_start[bucket]; i < spatial_cell_start[bucket + 1u]; i++) {
+//                 let index = spatial_entries[i];
+//             }
+//         }
+//     }
+struct SpatialParams {
+    cell_size: f32,
+    // Points the hash was built from
+    count: u32,
+    // A power of two, spatial_cell_start holds table_size + 1 entries
+    table_size: u32,
+    _padding: u32,
+}
+
+@group({group}) @binding(0) var<uniform> spatial: SpatialParams;
+// Bucket i holds spatial_entries[spatial_cell_start[i]..spatial_cell_start[i + 1]]
+@group({group}) @binding(1) var<storage, read> spatial_cell_start: array<u32>;
+// Indices into the positions the hash was built from
+@group({group}) @binding(2) var<storage, read> spatial_entries: array<u32>;
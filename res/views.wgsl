@@ -0,0 +1,21 @@
+// Views of a multiview pipeline, prepended to shaders by ShaderBuilder::with_views
+// Index them with the @builtin(view_index) of the vertex stage, see Renderer::set_views
+struct View {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    // xyz is the position of the camera in world space, w is 1
+    position: vec4<f32>,
+}
+
+struct Views {
+    views: array<View, 6>,
+    count: u32,
+}
+
+@group({group}) @binding(0) var<uniform> aftgraphs_views: Views;
+
+// The view of index, the last view set if there are fewer views
+fn view_at(index: u32) -> View {
+    return aftgraphs_views.views[min(index, max(aftgraphs_views.count, 1u) - 1u)];
+}
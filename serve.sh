@@ -5,12 +5,10 @@ if [[ "$#" != "1" ]]; then
   exit 1
 fi
 
-./buildwasm.sh "$SERVE_DEBUG"
-
-if ! [[ -d "target/web/$1" ]]; then
-  echo "target/web/$1 not a directory" >&2
-  exit 1
+if [[ "$SERVE_DEBUG" == "debug" ]]; then
+  cargo xtask web "$1" --debug --out "target/web/$1" || exit
+else
+  cargo xtask web "$1" --out "target/web/$1" || exit
 fi
 
-cp serve.json "target/web/$1"
 yarn exec serve "target/web/$1"
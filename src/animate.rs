@@ -0,0 +1,265 @@
+//! Frame-rate independent animation of values over time
+//! Step animations every frame with Clock::delta_time, which advances by the same
+//! amounts in headless renders as it does live, so both animate alike.
+
+/// Longest step springs integrate at once, longer frames are split into steps this long
+const MAX_SPRING_STEP: f64 = 1.0 / 240.0;
+
+/// Shape of a Tween over its duration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly and accelerates, cubic
+    EaseIn,
+    /// Starts quickly and decelerates, cubic
+    EaseOut,
+    /// Accelerates then decelerates, cubic
+    #[default]
+    EaseInOut,
+    /// Hermite smoothstep, gentler than EaseInOut
+    SmoothStep,
+    /// Overshoots the end a little before settling on it
+    BackOut,
+}
+
+impl Easing {
+    /// Eased progress at t, the fraction of the duration elapsed in [0, 1]
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::BackOut => {
+                const OVERSHOOT: f64 = 1.70158;
+                let t = t - 1.0;
+                1.0 + (OVERSHOOT + 1.0) * t * t * t + OVERSHOOT * t * t
+            }
+        }
+    }
+}
+
+/// Moves a value from one end to another over a fixed duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    from: f64,
+    to: f64,
+    duration: f64,
+    elapsed: f64,
+    easing: Easing,
+}
+
+impl Tween {
+    /// Tween from from to to over duration seconds
+    pub fn new(from: f64, to: f64, duration: f64, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            easing,
+        }
+    }
+
+    /// Restart towards to from the current value, keeping the duration and easing
+    pub fn retarget(&mut self, to: f64) {
+        self.from = self.value();
+        self.to = to;
+        self.elapsed = 0.0;
+    }
+
+    /// Advance by delta_time seconds, returning the new value
+    pub fn update(&mut self, delta_time: f64) -> f64 {
+        self.elapsed = (self.elapsed + delta_time.max(0.0)).min(self.duration);
+        self.value()
+    }
+
+    pub fn value(&self) -> f64 {
+        self.from + (self.to - self.from) * self.easing.apply(self.progress())
+    }
+
+    /// Fraction of the duration elapsed, in [0, 1]
+    pub fn progress(&self) -> f64 {
+        if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        }
+    }
+
+    pub fn target(&self) -> f64 {
+        self.to
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// A damped spring pulling a value towards a target, which may change at any time
+/// Underdamped springs overshoot and bounce, see CriticallyDamped for smoothing that does not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spring {
+    pub value: f64,
+    pub velocity: f64,
+    pub target: f64,
+    /// Angular frequency of the undamped spring in radians per second
+    angular_frequency: f64,
+    /// 1 for critical damping, below 1 bounces
+    damping_ratio: f64,
+}
+
+impl Spring {
+    /// A spring at rest on value, oscillating frequency times a second when undamped
+    pub fn new(value: f64, frequency: f64, damping_ratio: f64) -> Self {
+        Self {
+            value,
+            velocity: 0.0,
+            target: value,
+            angular_frequency: std::f64::consts::TAU * frequency.max(0.0),
+            damping_ratio: damping_ratio.max(0.0),
+        }
+    }
+
+    /// Advance by delta_time seconds towards the target, returning the new value
+    /// Long frames are integrated in short steps so the spring stays stable at any frame rate.
+    pub fn update(&mut self, delta_time: f64) -> f64 {
+        let steps = (delta_time.max(0.0) / MAX_SPRING_STEP).ceil().max(1.0);
+        let step = delta_time.max(0.0) / steps;
+        let omega = self.angular_frequency;
+        for _ in 0..steps as u32 {
+            // Semi-implicit Euler, updating the velocity first
+            let acceleration = omega * omega * (self.target - self.value)
+                - 2.0 * self.damping_ratio * omega * self.velocity;
+            self.velocity += acceleration * step;
+            self.value += self.velocity * step;
+        }
+        self.value
+    }
+
+    /// If the spring is within epsilon of its target and nearly still
+    pub fn is_settled(&self, epsilon: f64) -> bool {
+        (self.target - self.value).abs() <= epsilon && self.velocity.abs() <= epsilon
+    }
+}
+
+/// Critically damped smoothing of a value towards a changing target,
+/// reaching it about smooth_time seconds after it stops moving without overshooting
+/// Steps exactly for any delta_time, so it moves alike at any frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CriticallyDamped {
+    pub value: f64,
+    pub velocity: f64,
+    pub smooth_time: f64,
+}
+
+impl CriticallyDamped {
+    pub fn new(value: f64, smooth_time: f64) -> Self {
+        Self {
+            value,
+            velocity: 0.0,
+            smooth_time,
+        }
+    }
+
+    /// Advance by delta_time seconds towards target, returning the new value
+    pub fn update(&mut self, target: f64, delta_time: f64) -> f64 {
+        if self.smooth_time <= 0.0 {
+            self.value = target;
+            self.velocity = 0.0;
+            return self.value;
+        }
+
+        // Closed form of a critically damped spring with omega = 2 / smooth_time
+        let omega = 2.0 / self.smooth_time;
+        let offset = self.value - target;
+        let decay = (-omega * delta_time.max(0.0)).exp();
+        let temp = (self.velocity + omega * offset) * delta_time.max(0.0);
+        self.velocity = (self.velocity - omega * temp) * decay;
+        self.value = target + (offset + temp) * decay;
+        self.value
+    }
+}
+
+/// The angle in degrees equal to to modulo 360 that is nearest from
+/// Tweening to it turns the short way around the circle.
+pub fn nearest_angle_degrees(from: f64, to: f64) -> f64 {
+    from + (to - from + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn easings_span_zero_to_one() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::SmoothStep,
+            Easing::BackOut,
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-12, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-12, "{easing:?}");
+        }
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!(Easing::BackOut.apply(0.8) > 1.0);
+    }
+
+    #[test]
+    fn tween_reaches_its_target() {
+        let mut tween = Tween::new(10.0, 20.0, 1.0, Easing::Linear);
+        assert_eq!(15.0, tween.update(0.5));
+        assert!(!tween.is_done());
+        assert_eq!(20.0, tween.update(2.0));
+        assert!(tween.is_done());
+
+        tween.retarget(0.0);
+        assert_eq!(20.0, tween.value());
+        assert_eq!(10.0, tween.update(0.5));
+    }
+
+    /// Animations stepped at different frame rates must agree over the same time
+    fn at_frame_rate(fps: u32, mut update: impl FnMut(f64) -> f64) -> f64 {
+        let mut value = 0.0;
+        for _ in 0..fps / 2 {
+            value = update(1.0 / fps as f64);
+        }
+        value
+    }
+
+    #[test]
+    fn smoothing_is_frame_rate_independent() {
+        let mut slow = CriticallyDamped::new(0.0, 0.2);
+        let mut fast = slow;
+        let slow = at_frame_rate(30, |dt| slow.update(1.0, dt));
+        let fast = at_frame_rate(240, |dt| fast.update(1.0, dt));
+        assert!((slow - fast).abs() < 1e-9, "{slow} != {fast}");
+        assert!(slow > 0.95 && slow <= 1.0);
+
+        let mut slow = Spring::new(0.0, 2.0, 0.3);
+        slow.target = 1.0;
+        let mut fast = slow;
+        let slow = at_frame_rate(30, |dt| slow.update(dt));
+        let fast = at_frame_rate(240, |dt| fast.update(dt));
+        assert!((slow - fast).abs() < 1e-2, "{slow} != {fast}");
+    }
+
+    #[test]
+    fn angles_turn_the_short_way() {
+        assert_eq!(370.0, nearest_angle_degrees(350.0, 10.0));
+        assert_eq!(-20.0, nearest_angle_degrees(10.0, 340.0));
+        assert_eq!(90.0, nearest_angle_degrees(0.0, 90.0));
+    }
+}
@@ -1,8 +1,10 @@
 use crate::{
+    coords::ScreenSpace,
     input::{InputState, Inputs},
     prelude::InputEvent,
-    render::Renderer,
-    simulation::Simulation,
+    render::{CursorRequest, Renderer},
+    replay::ReplayBuffer,
+    simulation::{Simulation, TimedInput},
     ui::{UiPlatform, UiWinitPlatform},
 };
 use async_std::sync::Mutex;
@@ -13,8 +15,7 @@ use winit::{
     application::ApplicationHandler,
     dpi::{PhysicalPosition, PhysicalSize},
     event::{
-        ElementState, Event, KeyEvent, MouseButton, RawKeyEvent, StartCause, Touch, TouchPhase,
-        WindowEvent,
+        ElementState, Event, KeyEvent, MouseButton, RawKeyEvent, Touch, TouchPhase, WindowEvent,
     },
     event_loop::ActiveEventLoop,
     keyboard::{Key, NamedKey},
@@ -29,229 +30,332 @@ use crate::wasm::block_on;
 struct AppWindow<P: UiPlatform> {
     window: Arc<Window>,
     renderer: Renderer<'static, P>,
+    replay: ReplayBuffer,
+    /// Files restarting the simulation when they change, with --watch
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<crate::watch::Watcher>,
 }
 
 type AsyncWindow<P> = Rc<Mutex<AppWindow<P>>>;
 
-struct AppData {
+/// State only touched synchronously by the event handlers
+struct AppState {
     cursor_position: PhysicalPosition<f64>,
-    inputs: Inputs,
-    input_values: InputState,
     last_frame: Instant,
     recieved_resize: bool,
     start_time: Instant,
-    window_size: PhysicalSize<f64>,
+    screen: ScreenSpace,
+    /// Inputs for the next frame when Simulation::QUEUE_INPUTS is set
+    queued_inputs: Vec<TimedInput>,
+    /// Cursor requested by the simulation through the renderer
+    cursor: Option<Arc<CursorRequest>>,
 }
 
-impl AppData {
-    fn new(inputs: Inputs) -> Self {
+impl AppState {
+    fn new() -> Self {
         let now = Instant::now();
         Self {
             cursor_position: PhysicalPosition::new(0.0, 0.0),
             last_frame: now,
-            inputs,
-            input_values: InputState::default(),
             recieved_resize: false,
             start_time: now,
-            window_size: PhysicalSize::new(0.0, 0.0),
+            screen: ScreenSpace::default(),
+            queued_inputs: vec![],
+            cursor: None,
         }
     }
+
+    /// Seconds since the start, the time base of Clock::wall_time
+    fn time(&self) -> f64 {
+        self.start_time.elapsed().as_secs_f64()
+    }
+
+    /// Convert a position in the window to [-1, 1] screen space
+    fn to_screen_space(&self, position: PhysicalPosition<f64>) -> (f64, f64) {
+        self.screen.physical_to_ndc((position.x, position.y))
+    }
 }
 
-// Lock in alphabetical order, except simulation must be last
+// Only the pieces used by async code are behind locks
+// Lock in the order window, input values, simulation
 pub struct App<T: Simulation> {
+    inputs: Rc<Inputs>,
+    input_values: InputState,
     simulation: Option<Arc<Mutex<T>>>,
-    data: Arc<Mutex<AppData>>,
+    state: AppState,
     window: Option<AsyncWindow<UiWinitPlatform>>,
 }
 
+/// Run f on the window immediately if no pending future holds it,
+/// otherwise queue it behind the pending futures
+fn with_window<P: UiPlatform + 'static>(
+    window: &AsyncWindow<P>,
+    f: impl FnOnce(&mut AppWindow<P>) + 'static,
+) {
+    if let Some(mut app_window) = window.try_lock() {
+        f(&mut app_window);
+        return;
+    }
+
+    let window = window.clone();
+    block_on(async move {
+        f(&mut *window.lock().await);
+    });
+}
+
 impl<T: Simulation> App<T> {
     pub fn new(inputs: Inputs) -> Self {
         Self {
+            inputs: Rc::new(inputs),
+            input_values: InputState::default(),
             simulation: None,
-            data: Arc::new(Mutex::new(AppData::new(inputs))),
+            state: AppState::new(),
             window: None,
         }
     }
 
     async fn on_resumed(
         window: Window,
-        data: &mut AppData,
-    ) -> (AsyncWindow<UiWinitPlatform>, Arc<Mutex<T>>) {
+        inputs: Rc<Inputs>,
+        input_values: InputState,
+    ) -> Option<(AsyncWindow<UiWinitPlatform>, Arc<Mutex<T>>)> {
         let window = Arc::new(window);
 
-        window.set_title(data.inputs.simulation.name.as_str());
+        let options = &inputs.simulation.window;
+        let mut renderer = match crate::display::init(window.clone(), options).await {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                crate::error::report("aftgraphs::app::App::on_resumed", e);
+                return None;
+            }
+        };
+        if let Some(config) = crate::render::RenderConfig::startup().await {
+            renderer.apply_render_config(&config);
+        }
+        if options.transparent {
+            renderer.clear_color.a = 0.0;
+        }
+        renderer.set_palette(crate::render::Palette::startup().await);
+        renderer.configure_inputs(&inputs);
+        renderer.set_script(crate::script::Script::startup().await);
+        renderer.set_audio_input(crate::audio::AudioInput::startup(false).await);
+        #[cfg(not(target_arch = "wasm32"))]
+        renderer.set_timeline(crate::timeline::Timeline::startup().await);
+        if let Some(snapshot) = crate::snapshot::Snapshot::startup().await {
+            snapshot.apply(&mut renderer, input_values.lock().await.as_mut());
+        }
 
-        let PhysicalSize { width, height } = window.inner_size();
-        data.window_size = PhysicalSize::new(width.into(), height.into());
-        let renderer = crate::display::init(window.clone())
-            .await
-            .expect("failed to create renderer");
-
-        let simulation = Arc::new(Mutex::new(T::new(&renderer).await));
-        (
-            Rc::new(Mutex::new(AppWindow { window, renderer })),
+        #[cfg(not(target_arch = "wasm32"))]
+        let watcher = crate::watch::Watcher::startup(&inputs).await;
+
+        let simulation = Arc::new(Mutex::new(
+            crate::simulation::create::<T, _>(&mut renderer, Some(&window)).await,
+        ));
+        Some((
+            Rc::new(Mutex::new(AppWindow {
+                window,
+                renderer,
+                replay: ReplayBuffer::default(),
+                #[cfg(not(target_arch = "wasm32"))]
+                watcher,
+            })),
             simulation,
-        )
+        ))
     }
 
-    async fn on_window_event(
-        window_id: WindowId,
-        event: WindowEvent,
-        app_window: &mut AppWindow<UiWinitPlatform>,
-        simulation: Arc<Mutex<T>>,
-        data: &mut AppData,
-    ) -> bool {
-        match event.clone() {
-            WindowEvent::RedrawRequested => {
-                log::debug!("aftgraphs::app::App::on_window_event: window redraw requested");
+    fn on_redraw(&mut self, app_window: AsyncWindow<UiWinitPlatform>, simulation: Arc<Mutex<T>>) {
+        log::debug!("aftgraphs::app::App::on_redraw: window redraw requested");
 
-                if cfg!(target_arch = "wasm32") && !data.recieved_resize {
-                    return false;
-                }
+        if cfg!(target_arch = "wasm32") && !self.state.recieved_resize {
+            return;
+        }
+
+        let inputs = self.inputs.clone();
+        let input_values = self.input_values.clone();
+        let queued_inputs = std::mem::take(&mut self.state.queued_inputs);
+
+        let now = Instant::now();
+        let delta_time = now - self.state.last_frame;
+        self.state.last_frame = now;
+        let time = now.duration_since(self.state.start_time).as_secs_f64();
 
-                {
-                    log::debug!("aftgraphs::app::App::on_window_event: Rendering simulation");
-                    let mut input_values = data.input_values.lock().await;
-                    app_window
-                        .renderer
-                        .render(simulation, input_values.as_mut())
-                        .await;
+        block_on(async move {
+            let mut app_window = app_window.lock().await;
+            let AppWindow {
+                window,
+                renderer,
+                replay,
+                ..
+            } = &mut *app_window;
+            renderer.update_clock(time, delta_time);
+            renderer.queue_inputs(queued_inputs);
+            renderer.apply_frame_step();
+
+            {
+                let mut simulation = simulation.lock().await;
+                if let Some(simulation) = simulation.serializable() {
+                    if replay.before_render(simulation, renderer.clock.wall_delta()) {
+                        // Shown states are only drawn, not stepped
+                        renderer.clock.hold();
+                    }
                 }
+            }
 
-                log::debug!("aftgraphs::app::App::on_window_event: Updating input values");
-                if let Err(e) = app_window
-                    .renderer
-                    .draw_ui(
-                        Some(&app_window.window),
-                        &data.inputs,
-                        data.input_values.clone(),
-                    )
-                    .await
-                {
-                    log::warn!("aftgraphs::app::App::on_window_event: {e}");
+            {
+                log::debug!("aftgraphs::app::App::on_redraw: Rendering simulation");
+                let mut input_values = input_values.lock().await;
+                #[cfg(not(target_arch = "wasm32"))]
+                for TimedInput { event, time } in renderer.advance_timeline(input_values.as_mut()) {
+                    crate::simulation::send_input(renderer, &simulation, event, time).await;
                 }
+                renderer
+                    .render(simulation.clone(), input_values.as_mut())
+                    .await;
             }
-            WindowEvent::Resized(PhysicalSize { width, height }) => {
-                log::info!("aftgraphs::app::App::on_window_event: Handling window resize event");
 
-                data.recieved_resize = true;
-                data.window_size = PhysicalSize::new(width.into(), height.into());
+            if let Some(simulation) = simulation.lock().await.serializable() {
+                replay.after_render(simulation, renderer.clock.time());
+            }
 
-                if width > 0 && height > 0 {
-                    if let Some(config) = app_window.renderer.config.as_mut() {
-                        config.width = width;
-                        config.height = height;
-                    } else {
-                        log::warn!("aftgraphs::app::App::on_window_event: Error handling window resize: No surface configuration");
-                        return false;
-                    }
+            log::debug!("aftgraphs::app::App::on_redraw: Updating input values");
+            if let Err(e) = renderer.draw_ui(Some(window), &inputs, input_values).await {
+                log::warn!("aftgraphs::app::App::on_redraw: {e}");
+            }
 
-                    if let (Some(surface), Some(config)) = (
-                        app_window.renderer.surface.as_ref(),
-                        app_window.renderer.config.as_ref(),
-                    ) {
-                        surface.configure(&app_window.renderer.device, config);
-                    } else {
-                        log::warn!("aftgraphs::app::App::on_window_event: Error handling window resize: No surface");
-                        return false;
-                    }
+            profiling::finish_frame!();
+        });
+    }
 
-                    app_window.renderer.aspect_ratio = width as f64 / height as f64;
-                }
+    fn on_resized(
+        &mut self,
+        app_window: &AsyncWindow<UiWinitPlatform>,
+        window_id: WindowId,
+        event: WindowEvent,
+        size: PhysicalSize<u32>,
+    ) {
+        log::info!("aftgraphs::app::App::on_resized: Handling window resize event");
 
-                app_window.window.request_redraw();
-            }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        logical_key: Key::Named(NamedKey::Escape),
-                        state: ElementState::Pressed,
-                        ..
-                    },
-                ..
-            }
-            | WindowEvent::CloseRequested => {
-                log::info!("aftgraphs::app::App::on_window_event: Exit requested");
-                return true;
-            }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key,
-                        state,
-                        ..
-                    },
-                ..
-            } => {
-                log::debug!("aftgraphs::app::App::run: KeyboardEvent event found on window");
+        self.state.recieved_resize = true;
+        self.state.screen.size = (size.width, size.height);
 
-                simulation
-                    .lock()
-                    .await
-                    .on_input(InputEvent::Keyboard(RawKeyEvent {
-                        physical_key,
-                        state,
-                    }))
-                    .await;
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                log::debug!(
-                    "aftgraphs::app::App::on_window_event: CursorMoved event found on window"
-                );
-                data.cursor_position = position;
-            }
-            WindowEvent::MouseInput { state, button, .. } => {
-                log::debug!(
-                    "aftgraphs::app::App::on_window_event: MouseInput event found on window"
-                );
+        with_window(app_window, move |app_window| {
+            app_window.renderer.resize_surface(size.width, size.height);
 
-                // Convert mouse coordinates to screen space
-                let position = (
-                    data.cursor_position.x / data.window_size.width,
-                    data.cursor_position.y / data.window_size.height,
-                );
-                let position = (position.0 * 2.0 - 1.0, 1.0 - position.1 * 2.0);
+            app_window.window.request_redraw();
 
-                simulation
-                    .lock()
-                    .await
-                    .on_input(InputEvent::Mouse(state, button, position))
-                    .await;
-            }
-            WindowEvent::Touch(Touch {
-                phase, location, ..
-            }) => {
-                log::debug!("aftgraphs::app::App::on_window_event: Touch event found on window");
+            profiling::scope!("input polling");
+            let AppWindow {
+                window, renderer, ..
+            } = app_window;
+            renderer.handle_event(
+                window,
+                &Event::<InputEvent>::WindowEvent { window_id, event },
+            );
+        });
+    }
 
-                let state = match phase {
-                    TouchPhase::Started => ElementState::Pressed,
-                    TouchPhase::Moved => return false,
-                    TouchPhase::Ended | TouchPhase::Cancelled => ElementState::Released,
-                };
+    /// Recreate the simulation in the open window after a watched file changed,
+    /// rereading the inputs TOML if it was one of them
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restart(
+        &mut self,
+        app_window: AsyncWindow<UiWinitPlatform>,
+        changes: crate::watch::Changes,
+    ) {
+        for path in &changes.paths {
+            log::info!("aftgraphs::app::App::restart: {} changed", path.display());
+        }
 
-                let position = (
-                    location.x / data.window_size.width,
-                    location.y / data.window_size.height,
-                );
+        let mut reload_inputs = false;
+        if let (true, Some(source)) = (changes.inputs, self.inputs.source.clone()) {
+            match Inputs::from_file(&source) {
+                Ok(inputs) => {
+                    self.inputs = Rc::new(inputs);
+                    reload_inputs = true;
+                }
+                Err(e) => log::error!(
+                    "aftgraphs::app::App::restart: keeping the old inputs, failed to load {}: {e}",
+                    source.display()
+                ),
+            }
+        }
 
-                let position = (position.0 * 2.0 - 1.0, 1.0 - position.1 * 2.0);
+        let Some(simulation) = self.simulation.clone() else {
+            return;
+        };
+        self.state.queued_inputs.clear();
 
-                simulation
-                    .lock()
-                    .await
-                    .on_input(InputEvent::Mouse(state, MouseButton::Left, position))
-                    .await;
+        let inputs = self.inputs.clone();
+        let input_values = self.input_values.clone();
+        block_on(async move {
+            let mut app_window = app_window.lock().await;
+            let AppWindow {
+                window,
+                renderer,
+                replay,
+                ..
+            } = &mut *app_window;
+            if reload_inputs {
+                // Values are filled in again from the new inputs
+                input_values.lock().await.as_mut().clear();
             }
-            _ => (),
+            renderer.reset_simulation(&inputs);
+            // Keeps the clear color of a transparent window
+            let clear_color = renderer.clear_color;
+            if let Some(config) = crate::render::RenderConfig::startup().await {
+                renderer.apply_render_config(&config);
+            }
+            renderer.clear_color = clear_color;
+            *replay = ReplayBuffer::default();
+
+            let restarted =
+                crate::simulation::create::<T, _>(renderer, Some(window.as_ref())).await;
+            *simulation.lock().await = restarted;
+            log::info!("aftgraphs::app::App::restart: restarted the simulation");
+        });
+    }
+
+    /// Send an input to the simulation, then forward the event to the ui
+    fn on_simulation_input(
+        &mut self,
+        app_window: AsyncWindow<UiWinitPlatform>,
+        window_id: WindowId,
+        event: WindowEvent,
+        input: InputEvent,
+    ) {
+        let simulation = self.simulation.as_ref().unwrap().clone();
+        let time = self.state.time();
+
+        if T::QUEUE_INPUTS {
+            self.state
+                .queued_inputs
+                .push(TimedInput { event: input, time });
+            with_window(&app_window, move |app_window| {
+                profiling::scope!("input polling");
+                let AppWindow {
+                    window, renderer, ..
+                } = app_window;
+                renderer.handle_event(
+                    window,
+                    &Event::<InputEvent>::WindowEvent { window_id, event },
+                );
+            });
+            return;
         }
 
-        app_window.renderer.handle_event(
-            &app_window.window,
-            &Event::<InputEvent>::WindowEvent { window_id, event },
-        );
+        block_on(async move {
+            simulation.lock().await.on_input_at(input, time).await;
 
-        false
+            profiling::scope!("input polling");
+            let mut app_window = app_window.lock().await;
+            let AppWindow {
+                window, renderer, ..
+            } = &mut *app_window;
+            renderer.handle_event(
+                window,
+                &Event::<InputEvent>::WindowEvent { window_id, event },
+            );
+        });
     }
 }
 
@@ -271,32 +375,61 @@ fn make_window_attributes() -> WindowAttributes {
 
 impl<T: Simulation> ApplicationHandler<InputEvent> for App<T> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let attributes = make_window_attributes();
-        let window = event_loop
-            .create_window(attributes)
-            .expect("Failed to create winit window");
-        let data = self.data.clone();
+        let options = &self.inputs.simulation.window;
+        let attributes = options
+            .apply(make_window_attributes())
+            .with_title(self.inputs.simulation.name.as_str());
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => window,
+            Err(e) => {
+                crate::error::report("aftgraphs::app::App::resumed", e);
+                event_loop.exit();
+                return;
+            }
+        };
+        options.apply_to_window(&window);
+
+        let PhysicalSize { width, height } = window.inner_size();
+        self.state.screen = ScreenSpace::new((width, height), window.scale_factor());
 
         #[cfg(target_arch = "wasm32")]
         {
             use winit::platform::web::WindowExtWebSys;
-            let canvas = window.canvas().expect("Failed to get window canvas");
+            let Some(canvas) = window.canvas() else {
+                crate::error::report("aftgraphs::app::App::resumed", "the window has no canvas");
+                event_loop.exit();
+                return;
+            };
             canvas.set_id(crate::CANVAS_ID);
-            let style = &canvas.style();
-            style
-                .set_property("margin", "50px")
-                .expect("Failed to set canvas style");
+            if let Err(e) = canvas.style().set_property("margin", "50px") {
+                log::warn!("aftgraphs::app::App::resumed: failed to set the canvas style: {e:?}");
+            }
         }
 
         let (send, recv) = bounded(1);
+        let inputs = self.inputs.clone();
+        let input_values = self.input_values.clone();
         block_on(async move {
-            let mut data = data.lock().await;
-
-            let app_window = Self::on_resumed(window, &mut data).await;
-            send.send(app_window).expect("Failed to send AppWindow");
+            let app_window = Self::on_resumed(window, inputs, input_values).await;
+            // The receiver outlives block_on
+            let _ = send.send(app_window);
         });
 
-        let (app_window, simulation) = recv.recv().expect("Failed to recieve AppWindow");
+        let (app_window, simulation) = match recv.recv() {
+            Ok(Some(app_window)) => app_window,
+            Ok(None) => {
+                event_loop.exit();
+                return;
+            }
+            Err(e) => {
+                crate::error::report("aftgraphs::app::App::resumed", e);
+                event_loop.exit();
+                return;
+            }
+        };
+        self.state.cursor = app_window
+            .try_lock()
+            .map(|app_window| app_window.renderer.cursor.clone());
         self.window = Some(app_window);
         self.simulation = Some(simulation);
     }
@@ -310,25 +443,229 @@ impl<T: Simulation> ApplicationHandler<InputEvent> for App<T> {
         let Some(app_window) = self.window.as_ref().map(Clone::clone) else {
             return;
         };
-        let data = self.data.clone();
-        let simulation = self.simulation.as_ref().unwrap().clone();
 
-        let (send, recv) = bounded(1);
-        block_on(async move {
-            let mut app_window = app_window.lock().await;
-            let mut data = data.lock().await;
+        match event {
+            WindowEvent::RedrawRequested => {
+                let simulation = self.simulation.as_ref().unwrap().clone();
+                self.on_redraw(app_window.clone(), simulation);
+            }
+            WindowEvent::Resized(size) => {
+                self.on_resized(&app_window, window_id, event, size);
+                return;
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.state.screen.scale_factor = scale_factor;
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Escape),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            }
+            | WindowEvent::CloseRequested => {
+                log::info!("aftgraphs::app::App::window_event: Exiting application");
+                event_loop.exit();
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F1),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling about window");
+                with_window(&app_window, |app_window| {
+                    app_window.renderer.show_about = !app_window.renderer.show_about;
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F2),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling high-contrast palette");
+                with_window(&app_window, |app_window| {
+                    let palette = app_window.renderer.palette().toggled();
+                    app_window.renderer.set_palette(palette);
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F3),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling renderer statistics");
+                with_window(&app_window, |app_window| {
+                    app_window.renderer.show_stats = !app_window.renderer.show_stats;
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F4),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling resource inspector");
+                with_window(&app_window, |app_window| {
+                    app_window.renderer.show_inspector = !app_window.renderer.show_inspector;
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F6),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling pause");
+                with_window(&app_window, |app_window| {
+                    let renderer = &app_window.renderer;
+                    renderer.set_paused(!renderer.is_paused());
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F7),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Stepping one frame");
+                with_window(&app_window, |app_window| app_window.renderer.step_frame());
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F5),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Toggling slow-motion replay");
+                with_window(&app_window, |app_window| app_window.replay.toggle());
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F8),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Sharing a snapshot");
+                let input_values = self.input_values.clone();
+                block_on(async move {
+                    let app_window = app_window.lock().await;
+                    let input_values = input_values.lock().await;
+                    crate::snapshot::Snapshot::capture(&app_window.renderer, input_values.as_ref())
+                        .share();
+                });
+            }
+            #[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F12),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!("aftgraphs::app::App::window_event: Requesting RenderDoc capture");
+                crate::capture::capture_next_frame();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key,
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                log::debug!(
+                    "aftgraphs::app::App::window_event: KeyboardEvent event found on window"
+                );
 
-            let exit =
-                Self::on_window_event(window_id, event, &mut app_window, simulation, &mut data)
-                    .await;
+                let input = InputEvent::Keyboard(RawKeyEvent {
+                    physical_key,
+                    state,
+                });
+                self.on_simulation_input(app_window, window_id, event, input);
+                return;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                log::debug!("aftgraphs::app::App::window_event: CursorMoved event found on window");
+                self.state.cursor_position = position;
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                log::debug!("aftgraphs::app::App::window_event: MouseInput event found on window");
 
-            send.send(exit).expect("Failed to send window_event result");
-        });
+                let position = self.state.to_screen_space(self.state.cursor_position);
+                let input = InputEvent::Mouse(state, button, position);
+                self.on_simulation_input(app_window, window_id, event, input);
+                return;
+            }
+            WindowEvent::Touch(Touch {
+                phase, location, ..
+            }) => {
+                log::debug!("aftgraphs::app::App::window_event: Touch event found on window");
+
+                let state = match phase {
+                    TouchPhase::Started => ElementState::Pressed,
+                    TouchPhase::Moved => return,
+                    TouchPhase::Ended | TouchPhase::Cancelled => ElementState::Released,
+                };
 
-        if recv.recv().expect("Failed to recieve window_event result") {
-            log::info!("aftgraphs::app::App::window_event: Exiting application");
-            event_loop.exit();
+                let position = self.state.to_screen_space(location);
+                let input = InputEvent::Mouse(state, MouseButton::Left, position);
+                self.on_simulation_input(app_window, window_id, event, input);
+                return;
+            }
+            _ => (),
         }
+
+        with_window(&app_window, move |app_window| {
+            profiling::scope!("input polling");
+            let AppWindow {
+                window, renderer, ..
+            } = app_window;
+            renderer.handle_event(
+                window,
+                &Event::<InputEvent>::WindowEvent { window_id, event },
+            );
+        });
     }
 
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: InputEvent) {
@@ -337,12 +674,35 @@ impl<T: Simulation> ApplicationHandler<InputEvent> for App<T> {
             return;
         };
         let simulation = self.simulation.as_ref().unwrap().clone();
+        let time = self.state.time();
+
+        if T::QUEUE_INPUTS {
+            self.state.queued_inputs.push(TimedInput {
+                event: event.clone(),
+                time,
+            });
+            with_window(&app_window, move |app_window| {
+                profiling::scope!("input polling");
+                let AppWindow {
+                    window, renderer, ..
+                } = app_window;
+                renderer.handle_event(window, &Event::UserEvent(event));
+            });
+            return;
+        }
 
         block_on(async move {
-            let mut app_window = app_window.lock().await;
-            let AppWindow { window, renderer } = &mut *app_window;
+            profiling::scope!("input polling");
+            simulation
+                .lock()
+                .await
+                .on_input_at(event.clone(), time)
+                .await;
 
-            simulation.lock().await.on_input(event.clone()).await;
+            let mut app_window = app_window.lock().await;
+            let AppWindow {
+                window, renderer, ..
+            } = &mut *app_window;
             renderer.handle_event(window, &Event::UserEvent(event));
         });
     }
@@ -354,14 +714,15 @@ impl<T: Simulation> ApplicationHandler<InputEvent> for App<T> {
         event: winit::event::DeviceEvent,
     ) {
         log::debug!("aftgraphs::app::App::device_event: DeviceEvent event found on window");
-        let Some(app_window) = self.window.as_ref().map(Clone::clone) else {
+        let Some(app_window) = self.window.as_ref() else {
             return;
         };
 
-        block_on(async move {
-            let mut app_window = app_window.lock().await;
-            let AppWindow { window, renderer } = &mut *app_window;
-
+        with_window(app_window, move |app_window| {
+            profiling::scope!("input polling");
+            let AppWindow {
+                window, renderer, ..
+            } = app_window;
             renderer.handle_event(
                 window,
                 &Event::<InputEvent>::DeviceEvent { device_id, event },
@@ -369,38 +730,43 @@ impl<T: Simulation> ApplicationHandler<InputEvent> for App<T> {
         });
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         log::debug!("aftgraphs::app::App::about_to_wait: Window about to wait");
-        let Some(app_window) = self.window.as_ref().map(Clone::clone) else {
-            return;
-        };
-
-        block_on(async move {
-            let mut app_window = app_window.lock().await;
-            let AppWindow { window, renderer } = &mut *app_window;
-            renderer.prepare_ui(window).await;
-            renderer.handle_event(window, &Event::<InputEvent>::AboutToWait);
-            app_window.window.request_redraw();
-        });
-    }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(app_window) = self.window.clone() {
+            // Skipped while pending futures hold the window, the next poll sees the changes
+            let changes = app_window.try_lock().and_then(|mut app_window| {
+                let watcher = app_window.watcher.as_mut()?;
+                Some(watcher.poll())
+            });
+            if let Some(changes) = changes.filter(|changes| !changes.is_empty()) {
+                self.restart(app_window, changes);
+            }
+        }
 
-    fn new_events(&mut self, _event_loop: &ActiveEventLoop, _cause: StartCause) {
-        log::debug!("aftgraphs::app::App::new_events: New events found on window");
-        let Some(app_window) = self.window.as_ref().map(Clone::clone) else {
+        let Some(app_window) = self.window.as_ref() else {
             return;
         };
-        let data = self.data.clone();
 
-        block_on(async move {
-            let mut app_window = app_window.lock().await;
-            let mut data = data.lock().await;
-
-            let now = Instant::now();
-            let delta_time = now - data.last_frame;
-            data.last_frame = now;
-
-            app_window.renderer.update_delta_time(delta_time);
-            app_window.renderer.time = now.duration_since(data.start_time).as_secs_f64();
+        // Custom cursors are created on the event loop, so the change is resolved here
+        let cursor = self
+            .state
+            .cursor
+            .as_ref()
+            .and_then(|cursor| cursor.take_change())
+            .map(|style| style.to_winit(event_loop));
+
+        with_window(app_window, move |app_window| {
+            let AppWindow {
+                window, renderer, ..
+            } = app_window;
+            if let Some((cursor, visible)) = cursor {
+                window.set_cursor(cursor);
+                window.set_cursor_visible(visible);
+            }
+            renderer.prepare_ui(window);
+            renderer.handle_event(window, &Event::<InputEvent>::AboutToWait);
+            window.request_redraw();
         });
     }
 }
@@ -0,0 +1,365 @@
+use crate::render::{Allocation, FrameCounters, MemoryBudget, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use crossbeam::channel::{self, TryRecvError};
+use std::any::Any;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use thiserror::Error;
+
+mod builtin;
+#[cfg(not(target_arch = "wasm32"))]
+mod linux;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use builtin::{MeshAsset, MeshVertex, ShaderAsset};
+
+#[cfg(not(target_arch = "wasm32"))]
+use linux::read;
+#[cfg(target_arch = "wasm32")]
+use wasm::read;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AssetError {
+    #[error("failed to read asset {path}: {message}")]
+    Read { path: String, message: String },
+    #[error("failed to decode asset {path}: {message}")]
+    Decode { path: String, message: String },
+    #[error("asset {0} stopped loading before it finished")]
+    Disconnected(String),
+}
+
+/// A resource an AssetLoader loads from a file natively or a url on WASM
+/// decode runs away from the render thread, on a loader thread natively or after
+/// the fetch on WASM, upload creates the GPU resources during AssetLoader::poll.
+pub trait Asset: Sized + 'static {
+    type Decoded: Send + 'static;
+
+    fn decode(bytes: Vec<u8>) -> Result<Self::Decoded, String>;
+
+    fn upload(decoded: Self::Decoded, context: &UploadContext<'_>, label: &str) -> Self;
+}
+
+/// The device and queue an Asset is uploaded with
+pub struct UploadContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    memory: &'a Arc<MemoryBudget>,
+    stats: &'a Arc<FrameCounters>,
+}
+
+impl<'a> UploadContext<'a> {
+    pub(crate) fn new<P: UiPlatform>(renderer: &'a Renderer<P>) -> Self {
+        Self {
+            device: &renderer.device,
+            queue: &renderer.queue,
+            memory: &renderer.memory,
+            stats: &renderer.stats,
+        }
+    }
+
+    pub(crate) fn track_memory(
+        &self,
+        kind: ResourceKind,
+        label: Option<&str>,
+        bytes: u64,
+    ) -> Allocation {
+        self.memory.allocate(kind, label, bytes)
+    }
+
+    pub(crate) fn record_upload(&self, bytes: usize) {
+        self.stats.record_upload(bytes);
+    }
+}
+
+/// Bytes and assets loaded so far, shared with the ui
+#[derive(Debug, Default)]
+pub(crate) struct LoadProgress {
+    bytes_loaded: AtomicU64,
+    bytes_total: AtomicU64,
+    loaded: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl LoadProgress {
+    pub(crate) fn add_bytes(&self, loaded: u64, total: u64) {
+        self.bytes_loaded.fetch_add(loaded, Ordering::Relaxed);
+        self.bytes_total.fetch_add(total, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> AssetProgress {
+        AssetProgress {
+            bytes_loaded: self.bytes_loaded.load(Ordering::Relaxed),
+            bytes_total: self.bytes_total.load(Ordering::Relaxed),
+            loaded: self.loaded.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Progress of the assets of an AssetLoader
+/// Byte counts only include assets whose size is known, which on WASM is once they are fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AssetProgress {
+    pub bytes_loaded: u64,
+    pub bytes_total: u64,
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl AssetProgress {
+    pub fn is_done(&self) -> bool {
+        self.loaded == self.total
+    }
+
+    /// Progress in [0, 1], averaging the bytes read and the assets uploaded
+    pub fn fraction(&self) -> f32 {
+        let uploaded = self.loaded as f32 / self.total as f32;
+        if self.is_done() {
+            1.0
+        } else if self.bytes_total > 0 {
+            (self.bytes_loaded as f32 / self.bytes_total as f32 + uploaded) / 2.0
+        } else {
+            uploaded
+        }
+    }
+}
+
+impl std::ops::AddAssign for AssetProgress {
+    fn add_assign(&mut self, rhs: Self) {
+        self.bytes_loaded += rhs.bytes_loaded;
+        self.bytes_total += rhs.bytes_total;
+        self.loaded += rhs.loaded;
+        self.total += rhs.total;
+    }
+}
+
+/// Progress bar of the assets still loading, in the corner of the window
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn draw_progress(frame: &imgui::Ui, progress: AssetProgress) {
+    let [width, height] = frame.io().display_size;
+    frame
+        .window("Loading assets")
+        .position([width - 10.0, height - 10.0], imgui::Condition::Always)
+        .position_pivot([1.0, 1.0])
+        .size([width / 4.0, 0.0], imgui::Condition::Always)
+        .no_decoration()
+        .movable(false)
+        .build(|| {
+            frame.text(format!(
+                "Loading assets {}/{}",
+                progress.loaded, progress.total
+            ));
+            imgui::ProgressBar::new(progress.fraction())
+                .size([-1.0, 0.0])
+                .build(frame);
+        });
+}
+
+enum Slot<A> {
+    Loading,
+    Ready(Arc<A>),
+    Failed(AssetError),
+}
+
+/// An asset being loaded by an AssetLoader, ready after the AssetLoader::poll that uploads it
+pub struct AssetHandle<A> {
+    path: String,
+    slot: Arc<Mutex<Slot<A>>>,
+}
+
+impl<A> Clone for AssetHandle<A> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<A> AssetHandle<A> {
+    fn slot(&self) -> std::sync::MutexGuard<'_, Slot<A>> {
+        self.slot
+            .lock()
+            .expect("aftgraphs::asset::AssetHandle: poisoned lock")
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The asset, once it is loaded
+    pub fn get(&self) -> Option<Arc<A>> {
+        match *self.slot() {
+            Slot::Ready(ref asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<AssetError> {
+        match *self.slot() {
+            Slot::Failed(ref error) => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(*self.slot(), Slot::Loading)
+    }
+}
+
+type Decoded = Box<dyn Any + Send>;
+type Finish = Box<dyn FnOnce(Result<Decoded, AssetError>, &UploadContext<'_>)>;
+
+struct Job {
+    path: String,
+    recv: channel::Receiver<Result<Decoded, AssetError>>,
+    finish: Finish,
+}
+
+/// Loads assets in the background so large files do not hold up Simulation::new
+/// or the first frame. Files are read on a few loader threads natively and fetched on WASM,
+/// the ui shows the progress while assets load. Call AssetLoader::poll every frame
+/// to upload finished assets, which makes their handles ready and runs their callbacks.
+pub struct AssetLoader {
+    jobs: Vec<Job>,
+    progress: Arc<LoadProgress>,
+}
+
+impl AssetLoader {
+    pub fn new<P: UiPlatform>(renderer: &Renderer<P>) -> Self {
+        let progress = Arc::new(LoadProgress::default());
+        renderer.track_asset_progress(&progress);
+        Self {
+            jobs: vec![],
+            progress,
+        }
+    }
+
+    /// Start loading the asset at path, a file path natively and a url on WASM
+    pub fn load<A: Asset>(&mut self, path: &str) -> AssetHandle<A> {
+        self.load_with(path, |_| {})
+    }
+
+    /// AssetLoader::load, calling callback from AssetLoader::poll once the asset is uploaded or failed
+    pub fn load_with<A: Asset>(
+        &mut self,
+        path: &str,
+        callback: impl FnOnce(Result<Arc<A>, &AssetError>) + 'static,
+    ) -> AssetHandle<A> {
+        log::debug!("aftgraphs::asset::AssetLoader::load: loading {path}");
+        self.progress.total.fetch_add(1, Ordering::Relaxed);
+
+        let (send, recv) = channel::bounded(1);
+        {
+            let path = path.to_owned();
+            read(path.clone(), self.progress.clone(), move |bytes| {
+                let decoded = bytes.and_then(|bytes| {
+                    A::decode(bytes)
+                        .map(|decoded| Box::new(decoded) as Decoded)
+                        .map_err(|message| AssetError::Decode { path, message })
+                });
+                // The loader may have been dropped in the meantime
+                let _ = send.send(decoded);
+            });
+        }
+
+        let handle = AssetHandle {
+            path: path.to_owned(),
+            slot: Arc::new(Mutex::new(Slot::Loading)),
+        };
+        let slot = handle.slot.clone();
+        let label = path.to_owned();
+        let finish: Finish = Box::new(move |decoded, context| {
+            let asset = decoded.map(|decoded| {
+                let decoded = *decoded
+                    .downcast::<A::Decoded>()
+                    .expect("aftgraphs::asset::AssetLoader::poll: decoded asset of the wrong type");
+                Arc::new(A::upload(decoded, context, &label))
+            });
+            let mut slot = slot
+                .lock()
+                .expect("aftgraphs::asset::AssetLoader::poll: poisoned lock");
+            match asset {
+                Ok(asset) => {
+                    *slot = Slot::Ready(asset.clone());
+                    drop(slot);
+                    callback(Ok(asset));
+                }
+                Err(error) => {
+                    log::error!("aftgraphs::asset::AssetLoader::poll: {error}");
+                    callback(Err(&error));
+                    *slot = Slot::Failed(error);
+                }
+            }
+        });
+
+        self.jobs.push(Job {
+            path: path.to_owned(),
+            recv,
+            finish,
+        });
+        handle
+    }
+
+    /// Upload the assets decoded since the last poll, returning how many finished
+    pub fn poll<P: UiPlatform>(&mut self, renderer: &Renderer<P>) -> usize {
+        let context = UploadContext::new(renderer);
+
+        let mut finished = 0;
+        let mut idx = 0;
+        while idx < self.jobs.len() {
+            let decoded = match self.jobs[idx].recv.try_recv() {
+                Ok(decoded) => decoded,
+                Err(TryRecvError::Empty) => {
+                    idx += 1;
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    Err(AssetError::Disconnected(self.jobs[idx].path.clone()))
+                }
+            };
+
+            let job = self.jobs.remove(idx);
+            (job.finish)(decoded, &context);
+            self.progress.loaded.fetch_add(1, Ordering::Relaxed);
+            finished += 1;
+        }
+        finished
+    }
+
+    pub fn progress(&self) -> AssetProgress {
+        self.progress.get()
+    }
+
+    /// If every asset finished loading
+    pub fn is_done(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn progress_fraction() {
+        let mut progress = AssetProgress {
+            total: 2,
+            ..Default::default()
+        };
+        assert_eq!(0.0, progress.fraction());
+
+        progress.bytes_total = 100;
+        progress.bytes_loaded = 50;
+        assert_eq!(0.25, progress.fraction());
+
+        progress.loaded = 1;
+        assert_eq!(0.5, progress.fraction());
+        progress.loaded = 2;
+        assert!(progress.is_done());
+        assert_eq!(1.0, progress.fraction());
+    }
+}
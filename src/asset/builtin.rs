@@ -0,0 +1,171 @@
+use super::{Asset, UploadContext};
+use crate::render::{Allocation, ResourceKind};
+use crate::texture::{Texture, TextureBuilder};
+use wgpu::util::DeviceExt;
+
+/// A WGSL shader module
+pub struct ShaderAsset {
+    pub module: wgpu::ShaderModule,
+}
+
+impl Asset for ShaderAsset {
+    type Decoded = String;
+
+    fn decode(bytes: Vec<u8>) -> Result<Self::Decoded, String> {
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }
+
+    fn upload(source: String, context: &UploadContext<'_>, label: &str) -> Self {
+        let module = context
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        Self { module }
+    }
+}
+
+/// An Rgba8UnormSrgb Texture decoded from a PNG or JPEG, with the defaults of TextureBuilder
+impl Asset for Texture {
+    type Decoded = (u32, u32, Vec<u8>);
+
+    fn decode(bytes: Vec<u8>) -> Result<Self::Decoded, String> {
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| e.to_string())?
+            .into_rgba8();
+        Ok((image.width(), image.height(), image.into_raw()))
+    }
+
+    fn upload(
+        (width, height, rgba): Self::Decoded,
+        context: &UploadContext<'_>,
+        label: &str,
+    ) -> Self {
+        TextureBuilder::new()
+            .with_label(Some(label))
+            .with_format(wgpu::TextureFormat::Rgba8UnormSrgb)
+            .with_data(width, height, rgba)
+            .upload(context)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[repr(C)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for MeshVertex {}
+unsafe impl bytemuck::NoUninit for MeshVertex {}
+
+impl MeshVertex {
+    pub const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// An indexed triangle mesh from a Wavefront OBJ file, read with tobj
+/// Only positions, normals and faces are read, the objects of the file are merged
+/// into one mesh. Vertices without a normal get a zero normal.
+pub struct MeshAsset {
+    pub vertices: wgpu::Buffer,
+    pub indices: wgpu::Buffer,
+    pub index_count: u32,
+    _allocation: Allocation,
+}
+
+impl Asset for MeshAsset {
+    type Decoded = (Vec<MeshVertex>, Vec<u32>);
+
+    fn decode(bytes: Vec<u8>) -> Result<Self::Decoded, String> {
+        parse_obj(&bytes)
+    }
+
+    fn upload(
+        (vertices, indices): Self::Decoded,
+        context: &UploadContext<'_>,
+        label: &str,
+    ) -> Self {
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        let vertex_buffer = context
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: vertex_bytes,
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let index_buffer = context
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: index_bytes,
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        let bytes = vertex_bytes.len() + index_bytes.len();
+        context.record_upload(bytes);
+        let allocation = context.track_memory(ResourceKind::Buffer, Some(label), bytes as u64);
+        Self {
+            vertices: vertex_buffer,
+            indices: index_buffer,
+            index_count: indices.len() as u32,
+            _allocation: allocation,
+        }
+    }
+}
+
+fn parse_obj(mut source: &[u8]) -> Result<(Vec<MeshVertex>, Vec<u32>), String> {
+    // Materials are not used, so mtllib files are not read
+    let (models, _) = tobj::load_obj_buf(&mut source, &tobj::GPU_LOAD_OPTIONS, |_| {
+        Err(tobj::LoadError::OpenFileFailed)
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for tobj::Model { mesh, .. } in models {
+        let base = vertices.len() as u32;
+        vertices.extend(
+            mesh.positions
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(idx, position)| MeshVertex {
+                    position: [position[0], position[1], position[2]],
+                    normal: mesh
+                        .normals
+                        .get(idx * 3..idx * 3 + 3)
+                        .map_or([0.0; 3], |normal| [normal[0], normal[1], normal[2]]),
+                }),
+        );
+        indices.extend(mesh.indices.iter().map(|index| base + index));
+    }
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_obj_quads() {
+        let source =
+            b"# quad\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 -1//1\n";
+        let (vertices, indices) = parse_obj(source).unwrap();
+        assert_eq!(4, vertices.len());
+        assert_eq!(vec![0, 1, 2, 0, 2, 3], indices);
+        assert_eq!([0.0, 1.0, 0.0], vertices[3].position);
+        assert_eq!([0.0, 0.0, 1.0], vertices[3].normal);
+
+        assert!(parse_obj(b"v 0 0 0\nf 1 2 3\n").is_err());
+    }
+}
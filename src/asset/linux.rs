@@ -0,0 +1,56 @@
+use super::{AssetError, LoadProgress};
+use std::{
+    fs::File,
+    io::Read,
+    sync::{Arc, OnceLock},
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Assets read and decoded at once, the others wait for a free thread
+const LOADER_THREADS: usize = 4;
+
+/// Threads shared by every AssetLoader, started with the first asset
+fn pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(LOADER_THREADS)
+            .thread_name(|idx| format!("asset loader {idx}"))
+            .start_handler(|_| profiling::register_thread!("asset loader"))
+            .build()
+            .expect("aftgraphs::asset::linux::pool: failed to start the loader threads")
+    })
+}
+
+/// Read the file at path on a loader thread, calling done with its bytes on that thread
+pub(super) fn read(
+    path: String,
+    progress: Arc<LoadProgress>,
+    done: impl FnOnce(Result<Vec<u8>, AssetError>) + Send + 'static,
+) {
+    pool().spawn(move || {
+        let bytes = read_file(&path, &progress).map_err(|e| AssetError::Read {
+            path,
+            message: e.to_string(),
+        });
+        done(bytes);
+    });
+}
+
+fn read_file(path: &str, progress: &LoadProgress) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    progress.add_bytes(0, len);
+
+    let mut bytes = Vec::with_capacity(len as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let count = file.read(&mut chunk)?;
+        if count == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..count]);
+        progress.add_bytes(count as u64, 0);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,17 @@
+use super::{AssetError, LoadProgress};
+use std::sync::Arc;
+
+/// Fetch the url at path, calling done with its bytes once the response arrives
+pub(super) fn read(
+    path: String,
+    progress: Arc<LoadProgress>,
+    done: impl FnOnce(Result<Vec<u8>, AssetError>) + 'static,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let bytes = crate::wasm::fetch_bytes(&path).await;
+        if let Ok(ref bytes) = bytes {
+            progress.add_bytes(bytes.len() as u64, bytes.len() as u64);
+        }
+        done(bytes.map_err(|message| AssetError::Read { path, message }));
+    });
+}
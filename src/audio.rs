@@ -0,0 +1,344 @@
+//! Audio reactivity: the loudness of frequency bands, the overall level and beats of a
+//! microphone or a WAV file, written every frame to the read-only "audio.*" inputs.
+//! "audio.band0" to "audio.band7" and "audio.level" are sliders in [0, 1], "audio.beat"
+//! is a checkbox set on the frames a beat starts. WAV files are analyzed at the simulation
+//! time, so headless renders react to them exactly as the display does.
+//! Natively the microphone needs the audio feature, on WASM it is read through WebAudio.
+
+use crate::input::InputValue;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+mod microphone;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+mod wav;
+pub use wav::Wav;
+
+/// Samples analyzed each frame, about 43 ms at 48 kHz
+pub const FFT_SIZE: usize = 2048;
+/// Number of frequency bands, spaced logarithmically from 20 Hz up to 16 kHz
+pub const BANDS: usize = 8;
+
+const LOWEST_FREQUENCY: f32 = 20.0;
+const HIGHEST_FREQUENCY: f32 = 16_000.0;
+/// Frequencies beats are detected in
+const BASS_FREQUENCY: f32 = 150.0;
+/// Quietest level in decibels, mapped to 0
+const FLOOR_DB: f32 = -60.0;
+/// Seconds for bands to fall to about a third after the sound stops
+const RELEASE: f64 = 0.2;
+
+#[derive(Error, Debug)]
+pub enum AudioError {
+    #[error("failed to read audio: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unsupported or malformed WAV file: {0}")]
+    Wav(#[from] hound::Error),
+    #[error("no audio input device available")]
+    NoDevice,
+    #[error("audio input failed: {0}")]
+    Device(String),
+    #[error("microphone input needs the audio feature")]
+    Unsupported,
+}
+
+/// An amplitude in [0, 1] mapped through decibels onto [0, 1]
+fn loudness(amplitude: f32) -> f32 {
+    let db = 20.0 * amplitude.max(1e-9).log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+/// Analysis of the latest audio, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AudioAnalysis {
+    pub bands: [f32; BANDS],
+    pub level: f32,
+    pub beat: bool,
+}
+
+/// Finds beats as bass energy well above its average over the last second
+#[derive(Debug, Clone)]
+pub struct BeatDetector {
+    /// How many times the average energy a beat needs
+    pub sensitivity: f32,
+    /// Shortest time between beats in seconds
+    pub min_interval: f64,
+    history: VecDeque<(f64, f32)>,
+    last_beat: Option<f64>,
+}
+
+impl Default for BeatDetector {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.5,
+            min_interval: 0.15,
+            history: VecDeque::new(),
+            last_beat: None,
+        }
+    }
+}
+
+impl BeatDetector {
+    /// Add the energy at time, returning if a beat starts
+    pub fn update(&mut self, time: f64, energy: f32) -> bool {
+        while self
+            .history
+            .front()
+            .is_some_and(|&(past, _)| past < time - 1.0 || past > time)
+        {
+            self.history.pop_front();
+        }
+
+        let average = if self.history.is_empty() {
+            f32::INFINITY
+        } else {
+            self.history.iter().map(|&(_, energy)| energy).sum::<f32>() / self.history.len() as f32
+        };
+        self.history.push_back((time, energy));
+
+        let rested = self
+            .last_beat
+            .is_none_or(|last| time - last >= self.min_interval || time < last);
+        let beat = rested && energy > 1e-6 && energy > self.sensitivity * average;
+        if beat {
+            self.last_beat = Some(time);
+        }
+        beat
+    }
+}
+
+struct Analyzer {
+    /// Hann window, and the sum of its weights
+    window: Vec<f32>,
+    window_sum: f32,
+    fft: Arc<dyn Fft<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    beats: BeatDetector,
+    last: AudioAnalysis,
+    last_time: Option<f64>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / FFT_SIZE as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        Self {
+            window_sum: window.iter().sum(),
+            window,
+            spectrum: vec![Complex::default(); FFT_SIZE],
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            fft,
+            beats: BeatDetector::default(),
+            last: AudioAnalysis::default(),
+            last_time: None,
+        }
+    }
+}
+
+impl Analyzer {
+    /// Analyze the FFT_SIZE samples ending at time
+    fn analyze(&mut self, samples: &[f32], sample_rate: f32, time: f64) -> AudioAnalysis {
+        for (i, value) in self.spectrum.iter_mut().enumerate() {
+            *value = Complex::new(samples.get(i).copied().unwrap_or(0.0) * self.window[i], 0.0);
+        }
+        self.fft
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let highest = HIGHEST_FREQUENCY.min(sample_rate / 2.0);
+        let mut peaks = [0.0f32; BANDS];
+        let (mut bass, mut bass_bins) = (0.0, 0);
+        for bin in 1..FFT_SIZE / 2 {
+            let frequency = bin as f32 * bin_width;
+            if !(LOWEST_FREQUENCY..highest).contains(&frequency) {
+                continue;
+            }
+            // Amplitude of a sine, the window halving it
+            let amplitude = 2.0 * self.spectrum[bin].norm() / self.window_sum;
+            let band = ((frequency / LOWEST_FREQUENCY).ln() / (highest / LOWEST_FREQUENCY).ln()
+                * BANDS as f32) as usize;
+            let band = band.min(BANDS - 1);
+            peaks[band] = peaks[band].max(amplitude);
+            if frequency < BASS_FREQUENCY {
+                bass += amplitude * amplitude;
+                bass_bins += 1;
+            }
+        }
+        let bass = bass / bass_bins.max(1) as f32;
+
+        let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>()
+            / samples.len().max(1) as f32)
+            .sqrt();
+
+        // Bands rise at once and fall smoothly
+        let release = match self.last_time {
+            Some(last) if time > last => (-(time - last) / RELEASE).exp() as f32,
+            _ => 0.0,
+        };
+        self.last_time = Some(time);
+        let mut bands = [0.0; BANDS];
+        for (band, peak) in bands.iter_mut().zip(peaks) {
+            *band = loudness(peak);
+        }
+        for (band, last) in bands.iter_mut().zip(self.last.bands) {
+            *band = band.max(last * release);
+        }
+
+        self.last = AudioAnalysis {
+            bands,
+            level: loudness(rms * std::f32::consts::SQRT_2).max(self.last.level * release),
+            beat: self.beats.update(time, bass),
+        };
+        self.last
+    }
+}
+
+enum Source {
+    Wav(Wav),
+    #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+    Microphone(microphone::Microphone),
+    #[cfg(target_arch = "wasm32")]
+    Microphone(wasm::Microphone),
+}
+
+/// An audio source and its analysis, set on a Renderer with Renderer::set_audio_input
+pub struct AudioInput {
+    source: Source,
+    analyzer: Analyzer,
+    samples: Vec<f32>,
+}
+
+impl AudioInput {
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            analyzer: Analyzer::default(),
+            samples: vec![0.0; FFT_SIZE],
+        }
+    }
+
+    /// Analyze wav at the simulation time
+    pub fn from_wav(wav: Wav) -> Self {
+        Self::new(Source::Wav(wav))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_wav(path: impl AsRef<std::path::Path>) -> Result<Self, AudioError> {
+        Ok(Self::from_wav(Wav::parse(&std::fs::read(path)?)?))
+    }
+
+    /// Analyze the default microphone, asking the user for permission on WASM
+    pub async fn microphone() -> Result<Self, AudioError> {
+        #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+        return Ok(Self::new(Source::Microphone(
+            microphone::Microphone::open()?
+        )));
+        #[cfg(target_arch = "wasm32")]
+        return Ok(Self::new(Source::Microphone(
+            wasm::Microphone::open().await?,
+        )));
+        #[cfg(not(any(feature = "audio", target_arch = "wasm32")))]
+        Err(AudioError::Unsupported)
+    }
+
+    /// Analysis of the last frame
+    pub fn analysis(&self) -> AudioAnalysis {
+        self.analyzer.last
+    }
+
+    /// Analyze the audio at time and write it to the "audio.*" inputs
+    pub(crate) fn apply(&mut self, values: &mut HashMap<String, InputValue>, time: f64) {
+        let sample_rate = match self.source {
+            Source::Wav(ref wav) => {
+                wav.window(time, &mut self.samples);
+                wav.sample_rate as f32
+            }
+            #[cfg(any(feature = "audio", target_arch = "wasm32"))]
+            Source::Microphone(ref microphone) => {
+                microphone.samples(&mut self.samples);
+                microphone.sample_rate()
+            }
+        };
+        let analysis = self.analyzer.analyze(&self.samples, sample_rate, time);
+
+        for (idx, band) in analysis.bands.iter().enumerate() {
+            values.insert(format!("audio.band{idx}"), InputValue::SLIDER(*band as f64));
+        }
+        values.insert(
+            "audio.level".to_owned(),
+            InputValue::SLIDER(analysis.level as f64),
+        );
+        values.insert("audio.beat".to_owned(), InputValue::CHECKBOX(analysis.beat));
+    }
+
+    /// The audio given on the command line, if any
+    /// Headless renders only take WAV files, the microphone is read live.
+    pub(crate) async fn startup(headless: bool) -> Option<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let source = crate::cli::ARGUMENTS.read().await.audio.clone()?;
+            let input = if source == "mic" {
+                if headless {
+                    log::warn!("aftgraphs::audio::AudioInput::startup: headless renders can not read the microphone");
+                    return None;
+                }
+                Self::microphone().await
+            } else {
+                Self::open_wav(&source)
+            };
+            match input {
+                Ok(input) => return Some(input),
+                Err(e) => {
+                    log::error!(
+                        "aftgraphs::audio::AudioInput::startup: failed to open {source}: {e}"
+                    )
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = headless;
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sines_light_their_band() {
+        let sample_rate = 48_000.0;
+        let samples: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let analysis = Analyzer::default().analyze(&samples, sample_rate, 0.0);
+
+        let loudest = (0..BANDS)
+            .max_by(|&a, &b| analysis.bands[a].total_cmp(&analysis.bands[b]))
+            .unwrap();
+        assert_eq!(4, loudest);
+        assert!(analysis.bands[loudest] > 0.9);
+        assert!(analysis.level > 0.9);
+    }
+
+    #[test]
+    fn beats_stand_out_from_the_average() {
+        let mut beats = BeatDetector::default();
+        let frame = 1.0 / 60.0;
+        assert!(!(0..60).any(|i| beats.update(i as f64 * frame, 0.01)));
+        assert!(beats.update(1.0, 0.1));
+        // Too soon after the last beat
+        assert!(!beats.update(1.0 + frame, 0.2));
+        assert!(!beats.update(1.0 + 10.0 * frame, 0.01));
+    }
+}
@@ -0,0 +1,153 @@
+use super::{AudioError, FFT_SIZE};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+/// Samples kept from the microphone, older ones are dropped
+const CAPACITY: usize = 4 * FFT_SIZE;
+
+type Ring = Arc<Mutex<VecDeque<f32>>>;
+
+/// The default input device, mixed to mono
+/// The stream is not Send on every host, so it lives on its own thread until dropped.
+pub(super) struct Microphone {
+    ring: Ring,
+    sample_rate: f32,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    ring: Ring,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mut ring = ring
+                .lock()
+                .expect("aftgraphs::audio::Microphone::stream: poisoned lock");
+            for frame in data.chunks_exact(channels) {
+                let sample = frame
+                    .iter()
+                    .map(|sample| cpal::Sample::to_sample::<f32>(*sample))
+                    .sum::<f32>()
+                    / channels as f32;
+                if ring.len() == CAPACITY {
+                    ring.pop_front();
+                }
+                ring.push_back(sample);
+            }
+        },
+        |e| log::error!("aftgraphs::audio::Microphone::stream: {e}"),
+        None,
+    )
+}
+
+fn run(ring: Ring, stop: &AtomicBool, opened: mpsc::Sender<Result<f32, AudioError>>) {
+    let stream = (|| {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or(AudioError::NoDevice)?;
+        let supported = device
+            .default_input_config()
+            .map_err(|e| AudioError::Device(e.to_string()))?;
+        let format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+        let stream = match format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, ring),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, ring),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, ring),
+            format => return Err(AudioError::Device(format!("unsupported format {format:?}"))),
+        }
+        .map_err(|e| AudioError::Device(e.to_string()))?;
+        stream
+            .play()
+            .map_err(|e| AudioError::Device(e.to_string()))?;
+        Ok((stream, config.sample_rate.0 as f32))
+    })();
+
+    let _stream = match stream {
+        Ok((stream, sample_rate)) => {
+            let _ = opened.send(Ok(sample_rate));
+            stream
+        }
+        Err(e) => {
+            let _ = opened.send(Err(e));
+            return;
+        }
+    };
+    while !stop.load(Ordering::Acquire) {
+        std::thread::park();
+    }
+}
+
+impl Microphone {
+    pub(super) fn open() -> Result<Self, AudioError> {
+        let ring = Arc::new(Mutex::new(VecDeque::with_capacity(CAPACITY)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (opened, opening) = mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name("aftgraphs::audio::Microphone".to_owned())
+            .spawn({
+                let ring = ring.clone();
+                let stop = stop.clone();
+                move || run(ring, &stop, opened)
+            })?;
+        let sample_rate = opening
+            .recv()
+            .map_err(|_| AudioError::Device("audio thread exited".to_owned()))??;
+
+        Ok(Self {
+            ring,
+            sample_rate,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Fill out with the latest samples, silence before the first ones
+    pub(super) fn samples(&self, out: &mut [f32]) {
+        let ring = self
+            .ring
+            .lock()
+            .expect("aftgraphs::audio::Microphone::samples: poisoned lock");
+        let skip = out.len().saturating_sub(ring.len());
+        out[..skip].fill(0.0);
+        for (sample, latest) in out[skip..]
+            .iter_mut()
+            .zip(ring.range(ring.len() + skip - out.len()..))
+        {
+            *sample = *latest;
+        }
+    }
+
+    pub(super) fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            if thread.join().is_err() {
+                log::error!("aftgraphs::audio::Microphone::drop: audio thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use super::{AudioError, FFT_SIZE};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// The user's microphone, read through a WebAudio AnalyserNode
+pub(super) struct Microphone {
+    context: web_sys::AudioContext,
+    analyser: web_sys::AnalyserNode,
+    _source: web_sys::MediaStreamAudioSourceNode,
+}
+
+fn device_error(e: wasm_bindgen::JsValue) -> AudioError {
+    AudioError::Device(format!("{e:?}"))
+}
+
+impl Microphone {
+    /// Asks the user for permission, failing if it is refused
+    pub(super) async fn open() -> Result<Self, AudioError> {
+        let devices = web_sys::window()
+            .ok_or(AudioError::NoDevice)?
+            .navigator()
+            .media_devices()
+            .map_err(|_| AudioError::NoDevice)?;
+
+        let constraints = web_sys::MediaStreamConstraints::new();
+        constraints.set_audio(&true.into());
+        let stream = JsFuture::from(
+            devices
+                .get_user_media_with_constraints(&constraints)
+                .map_err(device_error)?,
+        )
+        .await
+        .map_err(device_error)?
+        .dyn_into::<web_sys::MediaStream>()
+        .map_err(device_error)?;
+
+        let context = web_sys::AudioContext::new().map_err(device_error)?;
+        let source = context
+            .create_media_stream_source(&stream)
+            .map_err(device_error)?;
+        let analyser = context.create_analyser().map_err(device_error)?;
+        analyser.set_fft_size(FFT_SIZE as u32);
+        source
+            .connect_with_audio_node(&analyser)
+            .map_err(device_error)?;
+
+        Ok(Self {
+            context,
+            analyser,
+            _source: source,
+        })
+    }
+
+    /// Fill out with the latest samples
+    pub(super) fn samples(&self, out: &mut [f32]) {
+        self.analyser.get_float_time_domain_data(out);
+    }
+
+    pub(super) fn sample_rate(&self) -> f32 {
+        self.context.sample_rate()
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        let _ = self.context.close();
+    }
+}
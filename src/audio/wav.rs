@@ -0,0 +1,123 @@
+use super::AudioError;
+use hound::{SampleFormat, WavReader, WavSpec};
+
+/// Mono samples decoded from a WAV file with hound, channels mixed down
+/// Reads 8, 16, 24 and 32 bit integer PCM and 32 bit float samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Wav {
+    pub fn parse(bytes: &[u8]) -> Result<Self, AudioError> {
+        let mut reader = WavReader::new(bytes)?;
+        let WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        } = reader.spec();
+
+        // Integer samples are read as signed, 8 bit ones included
+        let samples: Vec<f32> = match sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let samples = samples
+            .chunks_exact(channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        Ok(Self {
+            sample_rate,
+            samples,
+        })
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+
+    /// Fill out with the samples ending at time, silence outside of the file
+    pub fn window(&self, time: f64, out: &mut [f32]) {
+        let end = (time * self.sample_rate as f64).round() as i64;
+        let start = end - out.len() as i64;
+        for (i, sample) in out.iter_mut().enumerate() {
+            *sample = usize::try_from(start + i as i64)
+                .ok()
+                .and_then(|idx| self.samples.get(idx))
+                .copied()
+                .unwrap_or(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FORMAT_PCM: u16 = 1;
+    const FORMAT_FLOAT: u16 = 3;
+
+    fn encode(tag: u16, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        let block = channels as u32 * bits as u32 / 8;
+        bytes.extend_from_slice(&(8000 * block).to_le_bytes());
+        bytes.extend_from_slice(&(block as u16).to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn mixes_channels_down() {
+        let data: Vec<u8> = [16384i16, -16384, 32767, 32767]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let wav = Wav::parse(&encode(FORMAT_PCM, 2, 16, &data)).unwrap();
+        assert_eq!(8000, wav.sample_rate);
+        assert_eq!(2, wav.samples.len());
+        assert_eq!(0.0, wav.samples[0]);
+        assert!((wav.samples[1] - 1.0).abs() < 1e-4);
+
+        let data: Vec<u8> = [0.5f32, -0.25]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let wav = Wav::parse(&encode(FORMAT_FLOAT, 1, 32, &data)).unwrap();
+        assert_eq!(vec![0.5, -0.25], wav.samples);
+
+        assert!(Wav::parse(b"RIFF\0\0\0\0WAVE").is_err());
+    }
+
+    #[test]
+    fn windows_pad_with_silence() {
+        let wav = Wav {
+            sample_rate: 4,
+            samples: vec![1.0, 2.0, 3.0, 4.0],
+        };
+        let mut out = [0.0; 3];
+        wav.window(0.5, &mut out);
+        assert_eq!([0.0, 1.0, 2.0], out);
+        wav.window(2.0, &mut out);
+        assert_eq!([0.0; 3], out);
+    }
+}
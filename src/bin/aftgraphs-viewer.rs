@@ -0,0 +1,6 @@
+fn main() {
+    if let Err(err) = aftgraphs::plugin::host_main() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
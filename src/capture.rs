@@ -0,0 +1,111 @@
+use renderdoc::{InputButton, RenderDoc, V141};
+use std::{
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// No frame number requested
+const NO_FRAME: u64 = u64::MAX;
+
+static RENDERDOC: Mutex<Option<RenderDoc<V141>>> = Mutex::new(None);
+static FRAME: AtomicU64 = AtomicU64::new(0);
+static CAPTURE_FRAME: AtomicU64 = AtomicU64::new(NO_FRAME);
+static CAPTURE_NEXT: AtomicBool = AtomicBool::new(false);
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Connect to the RenderDoc in-application API
+/// Must run before the renderer creates its device. Only succeeds when the
+/// process was launched from RenderDoc or librenderdoc is otherwise loadable.
+pub fn init() -> bool {
+    let mut renderdoc = match RenderDoc::<V141>::new() {
+        Ok(renderdoc) => renderdoc,
+        Err(e) => {
+            log::warn!("aftgraphs::capture::init: RenderDoc is not available: {e}");
+            return false;
+        }
+    };
+
+    // Captures are triggered by the crate so they span the whole frame, ui included
+    renderdoc.set_capture_keys::<InputButton>(&[]);
+
+    let (major, minor, patch) = renderdoc.get_api_version();
+    log::info!("aftgraphs::capture::init: Connected to RenderDoc API {major}.{minor}.{patch}");
+
+    *RENDERDOC
+        .lock()
+        .expect("aftgraphs::capture::init: poisoned lock") = Some(renderdoc);
+    true
+}
+
+/// Capture the next frame the renderer starts
+pub fn capture_next_frame() {
+    CAPTURE_NEXT.store(true, Ordering::Relaxed);
+}
+
+/// Capture the frame with the given index, counting from 0
+pub fn capture_frame(frame: u64) {
+    CAPTURE_FRAME.store(frame, Ordering::Relaxed);
+}
+
+/// Index of the frame in progress
+pub fn frame_index() -> u64 {
+    FRAME.load(Ordering::Relaxed)
+}
+
+/// Start a capture if one was requested for the frame in progress
+pub(crate) fn begin_frame() {
+    if CAPTURING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let frame = FRAME.load(Ordering::Relaxed);
+    // Clear both requests, they may name the same frame
+    let requested = CAPTURE_NEXT.swap(false, Ordering::Relaxed)
+        | CAPTURE_FRAME
+            .compare_exchange(frame, NO_FRAME, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok();
+    if !requested {
+        return;
+    }
+
+    let mut renderdoc = RENDERDOC
+        .lock()
+        .expect("aftgraphs::capture::begin_frame: poisoned lock");
+    let Some(renderdoc) = renderdoc.as_mut() else {
+        log::warn!("aftgraphs::capture::begin_frame: Capture of frame {frame} requested, but RenderDoc is not connected");
+        return;
+    };
+
+    log::info!("aftgraphs::capture::begin_frame: Capturing frame {frame}");
+    renderdoc.start_frame_capture(ptr::null(), ptr::null());
+    CAPTURING.store(true, Ordering::Relaxed);
+}
+
+/// Finish the capture of the frame in progress, if any, and advance the frame index
+pub(crate) fn end_frame() {
+    let frame = FRAME.fetch_add(1, Ordering::Relaxed);
+    if !CAPTURING.swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    let mut renderdoc = RENDERDOC
+        .lock()
+        .expect("aftgraphs::capture::end_frame: poisoned lock");
+    if let Some(renderdoc) = renderdoc.as_mut() {
+        renderdoc.end_frame_capture(ptr::null(), ptr::null());
+
+        let captures = renderdoc.get_num_captures();
+        if let Some((path, _)) = captures
+            .checked_sub(1)
+            .and_then(|idx| renderdoc.get_capture(idx))
+        {
+            log::info!(
+                "aftgraphs::capture::end_frame: Saved capture of frame {frame} to {}",
+                path.display()
+            );
+        }
+    }
+}
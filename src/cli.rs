@@ -1,8 +1,12 @@
-use crate::block_on;
+use crate::{
+    block_on,
+    headless::{Crop, CropError},
+    simulation::Config,
+};
 use async_std::sync::RwLock;
-use clap::{crate_version, Args, Command};
+use clap::{crate_version, Arg, Args, Command};
 use lazy_static::lazy_static;
-use std::{num::NonZeroU32, path::PathBuf};
+use std::{ffi::OsString, num::NonZeroU32, path::PathBuf};
 
 lazy_static! {
     pub static ref ARGUMENTS: RwLock<Arguments> = RwLock::new(Arguments::default());
@@ -19,6 +23,44 @@ pub struct HeadlessArgs {
 pub struct Arguments {
     pub headless: Option<HeadlessArgs>,
     pub render_imgui: bool,
+    /// Report the time spent in each stage of the headless pipeline
+    pub timings: bool,
+    /// RenderConfig TOML applied to the renderer at startup
+    pub render_config: Option<PathBuf>,
+    /// Seed of the random streams
+    pub seed: Option<u64>,
+    /// Name headless frames are shared with other applications under
+    pub share: Option<String>,
+    /// rtmp:// url headless frames are streamed to
+    pub rtmp: Option<String>,
+    /// Encoding bitrate in kilobits per second
+    pub bitrate: Option<u32>,
+    /// Rhai script driving uniforms and draws, see crate::script
+    pub script: Option<PathBuf>,
+    /// Headless input file played back in display mode, see crate::timeline
+    pub timeline: Option<PathBuf>,
+    /// Number of GPUs to split headless frames across
+    pub gpus: Option<usize>,
+    /// Render the last frame as a PNG in tiles of this many pixels
+    pub tile: Option<u32>,
+    /// Render 360° video from cubemap faces of this many pixels square
+    pub cubemap: Option<u32>,
+    /// Part of the headless frames encoded, parsed with Crop::from_str
+    pub crop: Option<String>,
+    /// Sub-frames averaged into every headless frame
+    pub accumulate: Option<u32>,
+    /// WAV file, or `mic` for the microphone, analyzed into the audio.* inputs
+    pub audio: Option<String>,
+    /// Clear to a transparent background and write RGBA PNGs instead of video
+    pub transparent: bool,
+    /// Snapshot applied at startup, see crate::snapshot
+    pub snapshot: Option<String>,
+    /// Start with the high-contrast, colorblind-safe Palette
+    pub high_contrast: bool,
+    /// Values of the arguments of Simulation::args
+    pub config: Config,
+    /// Restart the simulation when its files change, with the asset directory to watch
+    pub watch: Option<Option<PathBuf>>,
 }
 
 #[derive(Args)]
@@ -26,6 +68,17 @@ pub struct Arguments {
 struct MyArgs {
     #[clap(long, action, name = "render-imgui")]
     render_imgui: bool,
+    /// Enable wgpu validation, even in release builds
+    #[clap(long, action)]
+    validation: bool,
+    /// Keep compiled pipelines in this directory, so later runs start faster.
+    /// Only used on adapters supporting pipeline caches, such as Vulkan ones
+    #[clap(long, name = "pipeline-cache")]
+    pipeline_cache: Option<PathBuf>,
+    /// Trigger a RenderDoc capture of the given frame, counting from 0
+    #[cfg(feature = "renderdoc")]
+    #[clap(long, name = "capture-frame")]
+    capture_frame: Option<u64>,
     #[clap(long, short, requires = "output")]
     render: Option<PathBuf>,
     #[clap(long, short, requires = "render")]
@@ -34,11 +87,91 @@ struct MyArgs {
     width: Option<NonZeroU32>,
     #[clap(long, short = 'H', requires = "render")]
     height: Option<NonZeroU32>,
+    /// Report per-frame timings of each headless pipeline stage and a summary at the end
+    #[clap(long, action, requires = "render")]
+    timings: bool,
+    /// Load the rendering setup from a render configuration TOML
+    #[clap(long, name = "render-config")]
+    render_config: Option<PathBuf>,
+    /// Seed the random streams to reproduce an earlier run, the seed of every run is logged
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Also share rendered frames with other applications under this name
+    #[clap(long, requires = "render")]
+    share: Option<String>,
+    /// Also stream the encoded video live to rtmp://host[:port]/app/stream-key
+    #[clap(long, requires = "render")]
+    rtmp: Option<String>,
+    /// Encode at this average bitrate in kilobits per second instead of constant quality
+    #[clap(long, requires = "render")]
+    bitrate: Option<NonZeroU32>,
+    /// Drive uniforms and draws with a Rhai script, reloaded when the file changes
+    /// Needs the rhai feature
+    #[clap(long)]
+    script: Option<PathBuf>,
+    /// Play the cues of a headless input file back while running interactively
+    #[clap(long, conflicts_with = "render")]
+    timeline: Option<PathBuf>,
+    /// Split frames across up to this many GPUs, for simulations with Simulation::STATELESS
+    #[clap(long, requires = "render")]
+    gpus: Option<NonZeroU32>,
+    /// Render only the last frame, in tiles of at most this many pixels square,
+    /// and write it as one PNG. Allows sizes beyond the GPU texture limit
+    #[clap(long, requires = "render")]
+    tile: Option<NonZeroU32>,
+    /// Render every frame as a cubemap with faces of this many pixels square, converted to
+    /// an equirectangular 360° video of the render size, twice as wide as high for VR players.
+    /// The simulation draws each face from Renderer::cube_face
+    #[clap(long, requires = "render", conflicts_with = "tile")]
+    cubemap: Option<NonZeroU32>,
+    /// Encode only this part of the frames, as x,y,width,height in pixels or
+    /// world:min_x,min_y,max_x,max_y in world units, placed by the camera of the first frame.
+    /// Video crops are shrunk to an even size. Overrides the crop of the input file
+    #[clap(long, requires = "render", conflicts_with_all = ["tile", "cubemap"], value_parser = parse_crop)]
+    crop: Option<String>,
+    /// Average this many sub-frames, drawn over the interval of each frame, into every
+    /// frame for motion blur and less noise in stochastic simulations.
+    /// Renders of ensemble input files can not be accumulated
+    #[clap(long, requires = "render", conflicts_with_all = ["tile", "cubemap"])]
+    accumulate: Option<NonZeroU32>,
+    /// Analyze a WAV file, or `mic` for the default microphone, into the audio.* inputs.
+    /// Headless renders take only WAV files, analyzed at the simulation time
+    #[clap(long)]
+    audio: Option<String>,
+    /// Clear to a transparent background and write an RGBA PNG sequence next to the
+    /// output instead of video, to composite the simulation over other footage.
+    /// With the 'ffmpeg' feature a .mov output is encoded as ProRes 4444 instead
+    #[clap(long, action, requires = "render")]
+    transparent: bool,
+    /// Start from a snapshot string, as copied by Snapshot::share
+    #[clap(long, conflicts_with = "render")]
+    snapshot: Option<String>,
+    /// Use a high-contrast ui and colorblind-safe default colors
+    #[clap(long, action, name = "high-contrast")]
+    high_contrast: bool,
+    /// Restart the simulation in place when the inputs TOML, the shaders next to it
+    /// or the files under the given asset directory change
+    #[clap(long, conflicts_with = "render", value_name = "ASSETS")]
+    watch: Option<Option<PathBuf>>,
 }
 
-pub fn parse_cli(name: &str, description: Option<&str>, author: Option<&str>) {
-    let cmd = command(name, description, author);
-    let matches = cmd.get_matches();
+fn parse_crop(crop: &str) -> Result<String, CropError> {
+    crop.parse::<Crop>().map(|_| crop.to_owned())
+}
+
+pub fn parse_cli(
+    args: impl IntoIterator<Item = OsString>,
+    name: &str,
+    description: Option<&str>,
+    author: Option<&str>,
+    simulation_args: Vec<Arg>,
+) {
+    let ids: Vec<String> = simulation_args
+        .iter()
+        .map(|arg| arg.get_id().to_string())
+        .collect();
+    let cmd = command(name, description, author).args(simulation_args);
+    let matches = cmd.get_matches_from(args);
 
     let in_file: Option<PathBuf> = matches.get_one("render").cloned();
     let out_file: Option<PathBuf> = matches.get_one("output").cloned();
@@ -63,11 +196,65 @@ pub fn parse_cli(name: &str, description: Option<&str>, author: Option<&str>) {
         false
     };
 
+    let timings = matches.get_flag("timings");
+    let render_config: Option<PathBuf> = matches.get_one("render-config").cloned();
+    let seed: Option<u64> = matches.get_one("seed").copied();
+    let share: Option<String> = matches.get_one("share").cloned();
+    let rtmp: Option<String> = matches.get_one("rtmp").cloned();
+    let bitrate: Option<NonZeroU32> = matches.get_one("bitrate").copied();
+    let script: Option<PathBuf> = matches.get_one("script").cloned();
+    let timeline: Option<PathBuf> = matches.get_one("timeline").cloned();
+    let gpus: Option<NonZeroU32> = matches.get_one("gpus").copied();
+    let tile: Option<NonZeroU32> = matches.get_one("tile").copied();
+    let cubemap: Option<NonZeroU32> = matches.get_one("cubemap").copied();
+    let crop: Option<String> = matches.get_one("crop").cloned();
+    let accumulate: Option<NonZeroU32> = matches.get_one("accumulate").copied();
+    let audio: Option<String> = matches.get_one("audio").cloned();
+    let transparent = matches.get_flag("transparent");
+    let snapshot: Option<String> = matches.get_one("snapshot").cloned();
+    let high_contrast = matches.get_flag("high-contrast");
+    let config = Config::from_matches(&matches, ids.iter().map(String::as_str));
+    let watch: Option<Option<PathBuf>> = matches
+        .contains_id("watch")
+        .then(|| matches.get_one("watch").cloned());
+
+    if matches.get_flag("validation") {
+        crate::render::set_validation(Some(true));
+    }
+
+    if let Some(dir) = matches.get_one::<PathBuf>("pipeline-cache") {
+        crate::render::set_pipeline_cache_dir(Some(dir.clone()));
+    }
+
+    #[cfg(feature = "renderdoc")]
+    if let Some(&frame) = matches.get_one::<u64>("capture-frame") {
+        crate::capture::capture_frame(frame);
+    }
+
     block_on(async move {
         let mut args = ARGUMENTS.write().await;
         *args = Arguments {
             headless,
             render_imgui,
+            timings,
+            render_config,
+            seed,
+            share,
+            rtmp,
+            bitrate: bitrate.map(Into::<u32>::into),
+            script,
+            timeline,
+            gpus: gpus.map(|gpus| u32::from(gpus) as usize),
+            tile: tile.map(Into::<u32>::into),
+            cubemap: cubemap.map(Into::<u32>::into),
+            crop,
+            accumulate: accumulate.map(Into::<u32>::into),
+            audio,
+            transparent,
+            snapshot,
+            high_contrast,
+            config,
+            watch,
         };
     });
 }
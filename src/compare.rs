@@ -0,0 +1,248 @@
+use crate::headless;
+use crate::input::InputValue;
+use crate::simulation::{Simulation, SimulationRunError};
+use async_std::sync::Mutex;
+use std::{collections::HashMap, sync::Arc};
+use web_time::Duration;
+
+/// Side length of the windows SSIM is computed over
+const SSIM_WINDOW: usize = 8;
+
+/// Per-pixel comparison of two RGBA8 frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameComparison {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 image of the absolute per-channel difference, opaque
+    pub diff: Vec<u8>,
+    /// Largest difference of any color channel
+    pub max_error: u8,
+    /// Mean squared error over the color channels
+    pub mse: f64,
+    /// Peak signal to noise ratio in dB, infinite for identical frames
+    pub psnr: f64,
+    /// Mean structural similarity of the luma, 1 for identical frames
+    pub ssim: f64,
+}
+
+/// Comparison of one frame rendered by both simulations
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationComparison {
+    pub time: f64,
+    pub frame: FrameComparison,
+}
+
+fn luma(pixel: &[u8]) -> f64 {
+    0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+}
+
+fn ssim(width: usize, height: usize, lhs: &[u8], rhs: &[u8]) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    for y0 in (0..height).step_by(SSIM_WINDOW) {
+        for x0 in (0..width).step_by(SSIM_WINDOW) {
+            let (mut sum_l, mut sum_r, mut sum_ll, mut sum_rr, mut sum_lr) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            let mut n = 0.0;
+
+            for y in y0..(y0 + SSIM_WINDOW).min(height) {
+                for x in x0..(x0 + SSIM_WINDOW).min(width) {
+                    let idx = (y * width + x) * 4;
+                    let l = luma(&lhs[idx..idx + 4]);
+                    let r = luma(&rhs[idx..idx + 4]);
+                    sum_l += l;
+                    sum_r += r;
+                    sum_ll += l * l;
+                    sum_rr += r * r;
+                    sum_lr += l * r;
+                    n += 1.0;
+                }
+            }
+
+            let mean_l = sum_l / n;
+            let mean_r = sum_r / n;
+            let var_l = sum_ll / n - mean_l * mean_l;
+            let var_r = sum_rr / n - mean_r * mean_r;
+            let covariance = sum_lr / n - mean_l * mean_r;
+
+            total += ((2.0 * mean_l * mean_r + C1) * (2.0 * covariance + C2))
+                / ((mean_l * mean_l + mean_r * mean_r + C1) * (var_l + var_r + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.0
+    } else {
+        total / windows as f64
+    }
+}
+
+/// Compare two tightly packed RGBA8 frames of the same size
+/// The alpha channel is ignored
+/// Panics if either frame is not width * height * 4 bytes
+pub fn compare_frames(width: u32, height: u32, lhs: &[u8], rhs: &[u8]) -> FrameComparison {
+    let len = width as usize * height as usize * 4;
+    assert!(
+        lhs.len() == len && rhs.len() == len,
+        "aftgraphs::compare::compare_frames: frames must be width * height RGBA8 pixels"
+    );
+
+    let mut diff = Vec::with_capacity(len);
+    let mut max_error = 0;
+    let mut squared_error = 0.0;
+
+    for (lhs, rhs) in lhs.chunks_exact(4).zip(rhs.chunks_exact(4)) {
+        for channel in 0..3 {
+            let error = lhs[channel].abs_diff(rhs[channel]);
+            max_error = max_error.max(error);
+            squared_error += (error as f64).powi(2);
+            diff.push(error);
+        }
+        diff.push(u8::MAX);
+    }
+
+    let samples = (width as usize * height as usize * 3).max(1) as f64;
+    let mse = squared_error / samples;
+    let psnr = if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    };
+
+    FrameComparison {
+        width,
+        height,
+        diff,
+        max_error,
+        mse,
+        psnr,
+        ssim: ssim(width as usize, height as usize, lhs, rhs),
+    }
+}
+
+/// Strip the row padding of a frame read back with Renderer::render_headless_finish
+pub(crate) fn unpad(width: u32, height: u32, frame: &[u8]) -> Vec<u8> {
+    let row = width as usize * 4;
+    let stride = frame.len() / height.max(1) as usize;
+
+    frame
+        .chunks_exact(stride)
+        .take(height as usize)
+        .flat_map(|padded| &padded[..row])
+        .copied()
+        .collect()
+}
+
+/// Render two simulations headlessly in lockstep and compare every frame
+///
+/// Meant for validating refactors that should not change the output,
+/// such as moving a simulation's physics from the CPU to a compute shader:
+/// implement both versions as separate Simulations and compare them here.
+/// Both simulations get their own renderer of the given size and are rendered
+/// at times 0, delta_t, ..., (frames - 1) * delta_t with the same inputs.
+pub async fn compare_simulations<A: Simulation, B: Simulation>(
+    size: (u32, u32),
+    delta_t: f64,
+    frames: usize,
+    inputs: HashMap<String, InputValue>,
+) -> Result<Vec<SimulationComparison>, SimulationRunError> {
+    log::debug!("aftgraphs::compare::compare_simulations: Initializing renderers");
+
+    let mut lhs_renderer = headless::init(size).await?;
+    let mut rhs_renderer = headless::init(size).await?;
+
+    let lhs = Arc::new(Mutex::new(A::new(&lhs_renderer).await));
+    let rhs = Arc::new(Mutex::new(B::new(&rhs_renderer).await));
+
+    let (width, height) = (size.0.max(1), size.1.max(1));
+    let mut lhs_inputs = inputs.clone();
+    let mut rhs_inputs = inputs;
+    let mut lhs_img = vec![];
+    let mut rhs_img = vec![];
+
+    let delta_duration = Duration::from_secs_f64(delta_t);
+    let mut comparisons = Vec::with_capacity(frames);
+
+    for frame in 0..frames {
+        let time = frame as f64 * delta_t;
+
+        for renderer in [&mut lhs_renderer, &mut rhs_renderer] {
+            renderer.update_clock(time, delta_duration);
+        }
+
+        lhs_renderer.render(lhs.clone(), &mut lhs_inputs).await;
+        lhs_renderer.render_headless_finish(&mut lhs_img).await?;
+        rhs_renderer.render(rhs.clone(), &mut rhs_inputs).await;
+        rhs_renderer.render_headless_finish(&mut rhs_img).await?;
+
+        let comparison = compare_frames(
+            width,
+            height,
+            &unpad(width, height, &lhs_img),
+            &unpad(width, height, &rhs_img),
+        );
+        log::debug!(
+            "aftgraphs::compare::compare_simulations: frame {frame}: PSNR {:.2} dB, SSIM {:.4}",
+            comparison.psnr,
+            comparison.ssim
+        );
+
+        comparisons.push(SimulationComparison {
+            time,
+            frame: comparison,
+        });
+    }
+
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient(width: u32, height: u32, offset: u8) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|idx| {
+                let value = (idx % 256) as u8;
+                [value.saturating_add(offset), value, 255 - value, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_frames() {
+        let frame = gradient(16, 16, 0);
+        let comparison = compare_frames(16, 16, &frame, &frame);
+
+        assert_eq!(0, comparison.max_error);
+        assert_eq!(0.0, comparison.mse);
+        assert!(comparison.psnr.is_infinite());
+        assert!((comparison.ssim - 1.0).abs() < 1e-9);
+        assert!(comparison
+            .diff
+            .chunks_exact(4)
+            .all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn different_frames() {
+        let lhs = gradient(16, 16, 0);
+        let rhs = gradient(16, 16, 10);
+        let comparison = compare_frames(16, 16, &lhs, &rhs);
+
+        assert_eq!(10, comparison.max_error);
+        assert!(comparison.psnr.is_finite());
+        assert!(comparison.ssim < 1.0);
+    }
+
+    #[test]
+    fn unpad_rows() {
+        let padded = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], unpad(1, 2, &padded));
+    }
+}
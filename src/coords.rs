@@ -0,0 +1,133 @@
+//! Conversions between the coordinate spaces of a render target:
+//! physical pixels and logical pixels from the top left corner,
+//! normalized device coordinates (NDC) in [-1, 1] with +y up,
+//! aspect space, NDC scaled so that units are square and the shorter axis spans [-1, 1],
+//! and world space through a camera's view-projection matrix.
+
+use crate::uniform::Mat4;
+
+/// Size and DPI scale of a render target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenSpace {
+    /// Size in physical pixels
+    pub size: (u32, u32),
+    /// Physical pixels per logical pixel
+    pub scale_factor: f64,
+}
+
+impl Default for ScreenSpace {
+    fn default() -> Self {
+        Self {
+            size: (1, 1),
+            scale_factor: 1.0,
+        }
+    }
+}
+
+impl ScreenSpace {
+    pub fn new(size: (u32, u32), scale_factor: f64) -> Self {
+        Self { size, scale_factor }
+    }
+
+    /// Width over height, the aspect ratio of Renderer::aspect_ratio
+    pub fn aspect_ratio(&self) -> f64 {
+        self.size.0.max(1) as f64 / self.size.1.max(1) as f64
+    }
+
+    pub fn logical_size(&self) -> (f64, f64) {
+        (
+            self.size.0 as f64 / self.scale_factor,
+            self.size.1 as f64 / self.scale_factor,
+        )
+    }
+
+    pub fn physical_to_logical(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x / self.scale_factor, y / self.scale_factor)
+    }
+
+    pub fn logical_to_physical(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x * self.scale_factor, y * self.scale_factor)
+    }
+
+    pub fn physical_to_ndc(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (width, height) = (self.size.0.max(1) as f64, self.size.1.max(1) as f64);
+        (x / width * 2.0 - 1.0, 1.0 - y / height * 2.0)
+    }
+
+    pub fn ndc_to_physical(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (width, height) = (self.size.0 as f64, self.size.1 as f64);
+        ((x + 1.0) / 2.0 * width, (1.0 - y) / 2.0 * height)
+    }
+
+    pub fn logical_to_ndc(&self, position: (f64, f64)) -> (f64, f64) {
+        self.physical_to_ndc(self.logical_to_physical(position))
+    }
+
+    pub fn ndc_to_logical(&self, position: (f64, f64)) -> (f64, f64) {
+        self.physical_to_logical(self.ndc_to_physical(position))
+    }
+
+    /// Scale NDC so that one unit is the same length along both axes
+    pub fn ndc_to_aspect(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let aspect_ratio = self.aspect_ratio();
+        if aspect_ratio >= 1.0 {
+            (x * aspect_ratio, y)
+        } else {
+            (x, y / aspect_ratio)
+        }
+    }
+
+    pub fn aspect_to_ndc(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let aspect_ratio = self.aspect_ratio();
+        if aspect_ratio >= 1.0 {
+            (x / aspect_ratio, y)
+        } else {
+            (x, y * aspect_ratio)
+        }
+    }
+}
+
+/// Counterclockwise angle of position from +x in degrees, in [0, 360)
+pub fn angle_degrees((x, y): (f64, f64)) -> f64 {
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// The world space point at NDC position and depth in [0, 1],
+/// through the inverse of a column major view-projection matrix
+pub fn ndc_to_world((x, y): (f64, f64), depth: f32, inverse_view_projection: &Mat4) -> [f32; 3] {
+    let [x, y, z, w] = transform(inverse_view_projection, [x as f32, y as f32, depth, 1.0]);
+    [x / w, y / w, z / w]
+}
+
+/// NDC position and depth of a world space point, None if it is behind the camera
+pub fn world_to_ndc(point: [f32; 3], view_projection: &Mat4) -> Option<((f64, f64), f32)> {
+    let [x, y, z, w] = transform(view_projection, [point[0], point[1], point[2], 1.0]);
+    if w <= 0.0 {
+        return None;
+    }
+    Some((((x / w) as f64, (y / w) as f64), z / w))
+}
+
+fn transform(matrix: &Mat4, vector: [f32; 4]) -> [f32; 4] {
+    std::array::from_fn(|row| (0..4).map(|col| matrix[col][row] * vector[col]).sum())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions_round_trip_across_dpi_and_aspect() {
+        let screen = ScreenSpace::new((800, 400), 2.0);
+        assert_eq!((400.0, 200.0), screen.logical_size());
+        assert_eq!((0.0, 0.0), screen.logical_to_ndc((200.0, 100.0)));
+        assert_eq!((-1.0, 1.0), screen.physical_to_ndc((0.0, 0.0)));
+        assert_eq!((800.0, 400.0), screen.ndc_to_physical((1.0, -1.0)));
+        assert_eq!((2.0, 1.0), screen.ndc_to_aspect((1.0, 1.0)));
+        assert_eq!((0.5, -1.0), screen.aspect_to_ndc((1.0, -1.0)));
+
+        let tall = ScreenSpace::new((200, 400), 1.0);
+        assert_eq!((1.0, 2.0), tall.ndc_to_aspect((1.0, 1.0)));
+        assert_eq!(270.0, angle_degrees((0.0, -1.0)));
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+mod csv;
+mod series;
+pub use series::TimeSeries;
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("failed to read data: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse JSON data: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CSV row {row} has {found} fields, expected {expected}")]
+    Csv {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("JSON data must be an array of objects or an object of arrays")]
+    JsonShape,
+    #[error("unknown data format of {0}, expected .csv, .json or .ndjson")]
+    UnknownFormat(String),
+    #[error("failed to fetch data: {0}")]
+    Fetch(String),
+}
+
+/// Text encoding of a Table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// An array of objects, one per row, or an object of column arrays
+    Json,
+    /// One JSON object per line
+    NdJson,
+}
+
+impl DataFormat {
+    /// The format of a file path or url, from its extension
+    pub fn from_path(path: &str) -> Result<Self, DataError> {
+        let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Ok(DataFormat::Csv),
+            Some("json") => Ok(DataFormat::Json),
+            Some("ndjson" | "jsonl") => Ok(DataFormat::NdJson),
+            _ => Err(DataError::UnknownFormat(path.to_owned())),
+        }
+    }
+}
+
+/// A column of a Table
+/// Columns where every present value is a number are Float, missing values are NaN.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Float(Vec<f64>),
+    Text(Vec<String>),
+}
+
+impl Column {
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Float(values) => values.len(),
+            Column::Text(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A value of a row before its column is typed
+enum Cell {
+    Missing,
+    Number(f64),
+    /// Text that may still parse as a number, as in CSV
+    Text(String),
+}
+
+impl Cell {
+    fn number(&self) -> Option<f64> {
+        match self {
+            Cell::Missing => Some(f64::NAN),
+            Cell::Number(value) => Some(*value),
+            Cell::Text(text) => text.trim().parse().ok(),
+        }
+    }
+
+    fn into_text(self) -> String {
+        match self {
+            Cell::Missing => String::new(),
+            Cell::Number(value) => value.to_string(),
+            Cell::Text(text) => text,
+        }
+    }
+}
+
+impl From<serde_json::Value> for Cell {
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+        match value {
+            Value::Null => Cell::Missing,
+            Value::Bool(value) => Cell::Number(value as u8 as f64),
+            Value::Number(value) => value.as_f64().map_or(Cell::Missing, Cell::Number),
+            Value::String(text) => Cell::Text(text),
+            value => Cell::Text(value.to_string()),
+        }
+    }
+}
+
+/// Columnar data loaded from CSV, JSON or NDJSON
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+    names: Vec<String>,
+    columns: Vec<Column>,
+}
+
+impl Table {
+    pub fn parse(src: &str, format: DataFormat) -> Result<Self, DataError> {
+        let table = match format {
+            DataFormat::Csv => Table::from_csv(src),
+            DataFormat::Json => Table::from_json(src),
+            DataFormat::NdJson => Table::from_ndjson(src),
+        };
+        table.inspect_err(|err| log::error!("aftgraphs::data::Table::parse: {err}"))
+    }
+
+    pub fn from_csv(src: &str) -> Result<Self, DataError> {
+        let mut records = csv::records(src).into_iter();
+        let Some(names) = records.next() else {
+            return Ok(Table::default());
+        };
+
+        let mut cells: Vec<Vec<Cell>> = names.iter().map(|_| vec![]).collect();
+        for (row, record) in records.enumerate() {
+            if record.len() != names.len() {
+                return Err(DataError::Csv {
+                    row: row + 1,
+                    expected: names.len(),
+                    found: record.len(),
+                });
+            }
+
+            for (column, field) in cells.iter_mut().zip(record) {
+                column.push(if field.is_empty() {
+                    Cell::Missing
+                } else {
+                    Cell::Text(field)
+                });
+            }
+        }
+
+        Ok(Table::from_cells(names, cells))
+    }
+
+    pub fn from_json(src: &str) -> Result<Self, DataError> {
+        use serde_json::Value;
+        match serde_json::from_str(src)? {
+            Value::Array(rows) => Table::from_rows(rows),
+            Value::Object(columns) => {
+                let mut names = vec![];
+                let mut cells = vec![];
+                for (name, column) in columns {
+                    let Value::Array(column) = column else {
+                        return Err(DataError::JsonShape);
+                    };
+                    names.push(name);
+                    cells.push(column.into_iter().map(Cell::from).collect());
+                }
+                Ok(Table::from_cells(names, cells))
+            }
+            _ => Err(DataError::JsonShape),
+        }
+    }
+
+    pub fn from_ndjson(src: &str) -> Result<Self, DataError> {
+        let rows = src
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Table::from_rows(rows)
+    }
+
+    /// Read a table from a file, in the format of its extension
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn load(path: impl AsRef<std::path::Path>) -> Result<Self, DataError> {
+        let path = path.as_ref();
+        let format = DataFormat::from_path(&path.to_string_lossy())?;
+        let src = async_std::fs::read_to_string(path)
+            .await
+            .inspect_err(|err| {
+                log::error!(
+                    "aftgraphs::data::Table::load: failed to read {}: {err}",
+                    path.display()
+                )
+            })?;
+        Table::parse(src.as_str(), format)
+    }
+
+    /// Fetch a table from a url, in the format of its extension
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch(url: &str) -> Result<Self, DataError> {
+        let format = DataFormat::from_path(url.split(['?', '#']).next().unwrap_or(url))?;
+        let src = crate::wasm::fetch_text(url).await.map_err(|err| {
+            log::error!("aftgraphs::data::Table::fetch: failed to fetch {url}: {err}");
+            DataError::Fetch(err)
+        })?;
+        Table::parse(src.as_str(), format)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, Column::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        let idx = self.names.iter().position(|column| column == name)?;
+        Some(&self.columns[idx])
+    }
+
+    pub fn floats(&self, name: &str) -> Option<&[f64]> {
+        match self.column(name)? {
+            Column::Float(values) => Some(values),
+            Column::Text(_) => None,
+        }
+    }
+
+    pub fn texts(&self, name: &str) -> Option<&[String]> {
+        match self.column(name)? {
+            Column::Text(values) => Some(values),
+            Column::Float(_) => None,
+        }
+    }
+
+    /// The value column sampled at the time column, None if either is missing or not numeric
+    pub fn time_series(&self, time: &str, value: &str) -> Option<TimeSeries> {
+        Some(TimeSeries::new(self.floats(time)?, self.floats(value)?))
+    }
+
+    /// Build a table from JSON objects, columns in order of first appearance
+    fn from_rows(rows: Vec<serde_json::Value>) -> Result<Self, DataError> {
+        let mut names: Vec<String> = vec![];
+        let mut indices = HashMap::new();
+        let mut cells: Vec<Vec<Cell>> = vec![];
+
+        for (row, value) in rows.into_iter().enumerate() {
+            let serde_json::Value::Object(object) = value else {
+                return Err(DataError::JsonShape);
+            };
+
+            for (name, value) in object {
+                let idx = *indices.entry(name.clone()).or_insert_with(|| {
+                    names.push(name);
+                    cells.push(vec![]);
+                    cells.len() - 1
+                });
+                let column = &mut cells[idx];
+                column.resize_with(row, || Cell::Missing);
+                column.push(value.into());
+            }
+        }
+
+        let len = cells.iter().map(Vec::len).max().unwrap_or(0);
+        for column in &mut cells {
+            column.resize_with(len, || Cell::Missing);
+        }
+        Ok(Table::from_cells(names, cells))
+    }
+
+    fn from_cells(names: Vec<String>, cells: Vec<Vec<Cell>>) -> Self {
+        let columns = cells
+            .into_iter()
+            .map(|column| {
+                let numbers: Option<Vec<f64>> = column.iter().map(Cell::number).collect();
+                match numbers {
+                    Some(numbers) => Column::Float(numbers),
+                    None => Column::Text(column.into_iter().map(Cell::into_text).collect()),
+                }
+            })
+            .collect();
+
+        Self { names, columns }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_agree() {
+        let csv = Table::from_csv("time,value,label\n0,1.5,a\n1,,b\n").unwrap();
+        let json = Table::from_json(
+            r#"[{"time": 0, "value": 1.5, "label": "a"}, {"time": 1, "label": "b"}]"#,
+        )
+        .unwrap();
+        let ndjson = Table::from_ndjson(
+            "{\"time\": 0, \"value\": 1.5, \"label\": \"a\"}\n{\"time\": 1, \"label\": \"b\", \"value\": null}\n",
+        )
+        .unwrap();
+
+        assert_eq!(2, csv.len());
+        assert_eq!(Some(&[0.0, 1.0][..]), csv.floats("time"));
+        assert!(csv.floats("value").unwrap()[1].is_nan());
+        assert_eq!(
+            Some(&["a".to_owned(), "b".to_owned()][..]),
+            csv.texts("label")
+        );
+        for table in [json, ndjson] {
+            assert_eq!(csv.names(), table.names());
+            assert_eq!(csv.texts("label"), table.texts("label"));
+            assert_eq!(csv.floats("time"), table.floats("time"));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::mem::take;
+
+/// Split CSV source into records of fields
+/// Fields may be quoted to hold commas, newlines and "" escaped quotes, blank lines are skipped.
+pub(super) fn records(src: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(take(&mut field)),
+            (false, '\r') => (),
+            (false, '\n') => {
+                record.push(take(&mut field));
+                push_record(&mut records, take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        push_record(&mut records, record);
+    }
+
+    records
+}
+
+fn push_record(records: &mut Vec<Vec<String>>, record: Vec<String>) {
+    let blank = record.len() == 1 && record[0].trim().is_empty();
+    if !blank {
+        records.push(record);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        let src = "time,label\r\n0,\"a, \"\"quoted\"\"\nvalue\"\n\n1,b";
+        assert_eq!(
+            vec![
+                vec!["time", "label"],
+                vec!["0", "a, \"quoted\"\nvalue"],
+                vec!["1", "b"],
+            ],
+            records(src)
+        );
+    }
+}
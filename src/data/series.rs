@@ -0,0 +1,100 @@
+/// Values sampled at increasing times, linearly interpolated in between
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TimeSeries {
+    times: Vec<f64>,
+    values: Vec<f64>,
+}
+
+impl TimeSeries {
+    /// Pairs with a NaN time or value are dropped, the rest are sorted by time
+    pub fn new(times: &[f64], values: &[f64]) -> Self {
+        let mut samples: Vec<_> = times
+            .iter()
+            .copied()
+            .zip(values.iter().copied())
+            .filter(|(time, value)| !time.is_nan() && !value.is_nan())
+            .collect();
+        samples.sort_by(|lhs, rhs| lhs.0.total_cmp(&rhs.0));
+
+        let (times, values) = samples.into_iter().unzip();
+        Self { times, values }
+    }
+
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// First and last time, None if empty
+    pub fn span(&self) -> Option<(f64, f64)> {
+        Some((*self.times.first()?, *self.times.last()?))
+    }
+
+    /// Value at time, held constant before the first and after the last sample
+    pub fn sample(&self, time: f64) -> Option<f64> {
+        let idx = self.times.partition_point(|&t| t <= time);
+        if idx == 0 {
+            return self.values.first().copied();
+        }
+        if idx == self.times.len() {
+            return self.values.last().copied();
+        }
+
+        let (t0, t1) = (self.times[idx - 1], self.times[idx]);
+        let (v0, v1) = (self.values[idx - 1], self.values[idx]);
+        let t = (time - t0) / (t1 - t0);
+        Some(v0 + (v1 - v0) * t)
+    }
+
+    /// count values sampled every step from start
+    pub fn resample(&self, start: f64, step: f64, count: usize) -> Vec<f64> {
+        (0..count)
+            .filter_map(|idx| self.sample(start + step * idx as f64))
+            .collect()
+    }
+
+    /// The series sampled every step over its span, e.g. every headless delta_t
+    pub fn resample_uniform(&self, step: f64) -> TimeSeries {
+        let Some((start, end)) = self.span() else {
+            return TimeSeries::default();
+        };
+        if step <= 0.0 {
+            log::warn!(
+                "aftgraphs::data::TimeSeries::resample_uniform: step {step} is not positive"
+            );
+            return self.clone();
+        }
+
+        let count = ((end - start) / step).floor() as usize + 1;
+        let times: Vec<_> = (0..count).map(|idx| start + step * idx as f64).collect();
+        let values = self.resample(start, step, count);
+        TimeSeries { times, values }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resample_interpolates() {
+        let series = TimeSeries::new(&[2.0, 0.0, 1.0], &[0.0, 1.0, f64::NAN]);
+        assert_eq!(&[0.0, 2.0], series.times());
+        assert_eq!(
+            vec![1.0, 1.0, 0.75, 0.5, 0.25, 0.0, 0.0],
+            series.resample(-0.5, 0.5, 7)
+        );
+        assert_eq!(5, series.resample_uniform(0.5).len());
+    }
+}
@@ -1,26 +1,190 @@
 use crate::{
     render::Renderer,
-    ui::{Ui, UiWinitPlatform},
+    ui::{Ui, UiPlatform, UiWinitPlatform},
     GraphicsInitError,
 };
 use async_std::sync::Mutex;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
 use wgpu;
-use winit::window::Window;
+use winit::window::{Icon, Window, WindowAttributes, WindowLevel};
+
+/// PNG image shown as the window and taskbar icon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WindowIcon {
+    /// Path of a PNG file, read when the window is created
+    Path(PathBuf),
+    /// A PNG embedded in the binary, e.g. with include_bytes!
+    #[serde(skip)]
+    Embedded(&'static [u8]),
+}
+
+impl WindowIcon {
+    fn load(&self) -> Option<Icon> {
+        let png = match self {
+            WindowIcon::Path(path) => match std::fs::read(path) {
+                Ok(png) => Cow::Owned(png),
+                Err(e) => {
+                    log::warn!(
+                        "aftgraphs::display::WindowIcon::load: failed to read {}: {e}",
+                        path.display()
+                    );
+                    return None;
+                }
+            },
+            WindowIcon::Embedded(png) => Cow::Borrowed(*png),
+        };
+
+        let icon = match crate::png::read(&png) {
+            Ok((width, height, rgba)) => {
+                Icon::from_rgba(rgba, width, height).map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        match icon {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                log::warn!("aftgraphs::display::WindowIcon::load: invalid icon: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Options of the display mode window, to run a simulation as a desktop overlay
+/// or to polish a distributed binary. Set in the [simulation.window] table of the
+/// input TOML, or on Inputs before calling sim_main.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct WindowOptions {
+    /// Show the desktop through pixels with an alpha below 1, the clear color
+    /// is made fully transparent
+    pub transparent: bool,
+    /// Keep the window above other windows
+    pub always_on_top: bool,
+    /// Pass mouse events through the window to the windows below it
+    /// Not supported on every platform, a warning is logged where it is not.
+    pub click_through: bool,
+    /// Window and taskbar icon
+    pub icon: Option<WindowIcon>,
+    /// Application id the desktop groups windows by, the Wayland app_id and X11
+    /// WM_CLASS. Should match the name of the .desktop file of the simulation
+    pub app_id: Option<String>,
+}
+
+impl WindowOptions {
+    /// Apply the options set when the window is created
+    pub fn apply(&self, attributes: WindowAttributes) -> WindowAttributes {
+        let level = if self.always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        };
+        let icon = self.icon.as_ref().and_then(WindowIcon::load);
+        #[allow(unused_mut)]
+        let mut attributes = attributes
+            .with_transparent(self.transparent)
+            .with_window_level(level)
+            .with_window_icon(icon.clone());
+
+        #[cfg(target_os = "linux")]
+        if let Some(ref app_id) = self.app_id {
+            use winit::platform::wayland::WindowAttributesExtWayland;
+            attributes = attributes.with_name(app_id, app_id);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            use winit::platform::windows::WindowAttributesExtWindows;
+            attributes = attributes.with_taskbar_icon(icon);
+        }
+        attributes
+    }
+
+    /// Apply the options that can only be set on a created window
+    pub fn apply_to_window(&self, window: &Window) {
+        if self.click_through {
+            if let Err(e) = window.set_cursor_hittest(false) {
+                log::warn!("aftgraphs::display::WindowOptions::apply_to_window: click through is not supported: {e}");
+            }
+        }
+    }
+}
 
 pub async fn init(
     window: Arc<Window>,
+    options: &WindowOptions,
 ) -> Result<Renderer<'static, UiWinitPlatform>, GraphicsInitError> {
     log::debug!("aftgraphs::display::init: Initializing display");
 
-    let mut size = window.inner_size();
+    let size = window.inner_size();
+    let instance = create_instance();
+    let surface = instance.create_surface(window.clone())?;
+    init_with_surface(
+        instance,
+        surface,
+        (size.width, size.height),
+        options.transparent,
+        |device, queue, format| Ui::new(&window, device, queue, format),
+    )
+    .await
+}
+
+/// Initialize a renderer drawing to a window owned by the host application
+/// The host drives the event loop, see embed::Embedded. size is the window's size in pixels.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn init_external(
+    target: impl Into<wgpu::SurfaceTarget<'static>>,
+    size: (u32, u32),
+) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    log::debug!("aftgraphs::display::init_external: Initializing display");
+
+    let instance = create_instance();
+    let surface = instance.create_surface(target)?;
+    init_with_surface(instance, surface, size, false, |device, queue, format| {
+        Ui::new_headless(size, device, queue, format)
+    })
+    .await
+}
+
+/// Initialize a renderer drawing to a raw window handle owned by the host application
+/// # Safety
+/// The window and display handles of target must stay valid until the Renderer drops
+#[cfg(not(target_arch = "wasm32"))]
+pub async unsafe fn init_raw(
+    target: wgpu::SurfaceTargetUnsafe,
+    size: (u32, u32),
+) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    log::debug!("aftgraphs::display::init_raw: Initializing display");
+
+    let instance = create_instance();
+    let surface = instance.create_surface_unsafe(target)?;
+    init_with_surface(instance, surface, size, false, |device, queue, format| {
+        Ui::new_headless(size, device, queue, format)
+    })
+    .await
+}
+
+fn create_instance() -> wgpu::Instance {
+    let flags = crate::render::instance_flags();
+    log::debug!("aftgraphs::display::init: Creating instance with flags {flags:?}");
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        flags,
+        ..Default::default()
+    })
+}
+
+async fn init_with_surface<P: UiPlatform>(
+    instance: wgpu::Instance,
+    surface: wgpu::Surface<'static>,
+    (width, height): (u32, u32),
+    transparent: bool,
+    new_ui: impl FnOnce(&wgpu::Device, &wgpu::Queue, wgpu::TextureFormat) -> (Ui, P),
+) -> Result<Renderer<'static, P>, GraphicsInitError> {
     // wgpu minimum surface size is 4x4
-    size.width = size.width.max(4);
-    size.height = size.height.max(4);
+    let width = width.max(4);
+    let height = height.max(4);
 
-    log::debug!("aftgraphs::display::init: Creating surface");
-    let instance = wgpu::Instance::default();
-    let surface = instance.create_surface(window.clone())?;
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::default(),
@@ -35,9 +199,8 @@ pub async fn init(
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                    .using_resolution(adapter.limits()),
+                required_features: crate::render::required_features(&adapter),
+                required_limits: crate::render::required_limits(&adapter),
                 ..Default::default()
             },
             None,
@@ -52,14 +215,29 @@ pub async fn init(
 
     let swapchain_capabilities = surface.get_capabilities(&adapter);
     let swapchain_format = swapchain_capabilities.formats[0];
+    let alpha_mode = if transparent {
+        [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            wgpu::CompositeAlphaMode::Inherit,
+        ]
+        .into_iter()
+        .find(|mode| swapchain_capabilities.alpha_modes.contains(mode))
+        .unwrap_or_else(|| {
+            log::warn!("aftgraphs::display::init: surface does not support transparency");
+            swapchain_capabilities.alpha_modes[0]
+        })
+    } else {
+        swapchain_capabilities.alpha_modes[0]
+    };
 
     let config = wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         format: swapchain_format,
-        width: size.width,
-        height: size.height,
+        width,
+        height,
         present_mode: wgpu::PresentMode::Fifo,
-        alpha_mode: swapchain_capabilities.alpha_modes[0],
+        alpha_mode,
         view_formats: vec![],
         desired_maximum_frame_latency: 2,
     };
@@ -69,9 +247,10 @@ pub async fn init(
 
     log::info!("surface configured");
 
-    let aspect_ratio = size.width as f64 / size.height as f64;
+    let aspect_ratio = width as f64 / height as f64;
 
-    let (ui, platform) = Ui::new(&window, &device, &queue, swapchain_format);
+    let pipeline_cache = crate::render::PipelineCache::open(&device, &adapter);
+    let (ui, platform) = new_ui(&device, &queue, swapchain_format);
     Ok(Renderer {
         headless: false,
         instance,
@@ -87,7 +266,43 @@ pub async fn init(
         platform,
         ui,
         aspect_ratio,
-        time: 0.0,
-        delta_time: 0.0,
+        clock: Default::default(),
+        show_stats: false,
+        show_inspector: false,
+        show_about: false,
+        palette: Default::default(),
+        clear_color: wgpu::Color::BLACK,
+        stats: Default::default(),
+        memory: Default::default(),
+        resources: Default::default(),
+        pipeline_cache,
+        #[cfg(not(target_arch = "wasm32"))]
+        hot_reloads: Default::default(),
+        viewport: Default::default(),
+        tile: Default::default(),
+        cube_face: Default::default(),
+        layers: Default::default(),
+        background: Default::default(),
+        accumulation: Default::default(),
+        msaa: Default::default(),
+        post: Default::default(),
+        views: Default::default(),
+        seed: crate::rand::startup_seed().await,
+        aspect_policy: Default::default(),
+        world_scale: Default::default(),
+        input_queue: Default::default(),
+        prepared: Default::default(),
+        input_modulation: Default::default(),
+        input_smoothing: Default::default(),
+        uniform_bindings: Default::default(),
+        script: Default::default(),
+        audio: Default::default(),
+        #[cfg(not(target_arch = "wasm32"))]
+        timeline: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ui_layout: Default::default(),
+        frame_step: Default::default(),
+        cursor: Default::default(),
+        asset_progress: Default::default(),
     })
 }
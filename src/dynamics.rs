@@ -0,0 +1,194 @@
+use bacon_sci::{
+    ivp::{rk::RungeKutta45, Euler, IVPError, IVPIterator, IVPSolver, IVPStepper, UserError},
+    prelude::*,
+};
+use thiserror::Error;
+
+pub mod interpolator;
+pub mod worker;
+pub use interpolator::{Lerp, StateInterpolator};
+pub use worker::{BufferPolicy, IntegratorWorker, StateBuffer, WorkerSettings};
+
+pub use bacon_sci;
+
+/// State vector of a system being integrated
+pub type State = BVector<f32, Dyn>;
+
+/// Derivative function used by the solvers: (time, state, user data) -> dy/dt
+pub type DerivativeFn<D> = fn(f32, &[f32], &mut D) -> Result<State, UserError>;
+
+/// A system of ODEs integrated on a background IntegratorWorker
+///
+/// The system is created on the worker thread (see IntegratorWorker::spawn),
+/// so it does not need to be Send. Messages sent from the simulation are
+/// applied to the system between solver steps.
+pub trait DynamicalSystem: 'static {
+    /// Data handed to the derivative function by the solver
+    /// The solver is rebuilt with a fresh copy after every message
+    type Data: Clone + 'static;
+    /// Messages sent from the simulation to change the system
+    type Message: Send + 'static;
+
+    /// Compute the derivative of the state vector at time t
+    fn derivative(t: f32, y: &[f32], data: &mut Self::Data) -> Result<State, UserError>;
+
+    /// Create the data passed to the derivative function
+    fn data(&self) -> Self::Data;
+
+    /// Apply a message to the system
+    /// state is the state of the system at time, and may be resized or modified.
+    /// Return false if the message could not be applied, in which case
+    /// the state passed in must be left unchanged.
+    fn handle_message(&mut self, message: Self::Message, time: f32, state: &mut State) -> bool;
+}
+
+/// Integration method used by the IntegratorWorker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    /// Forward Euler with a fixed time step
+    Euler { dt: f32 },
+    /// Fourth order Runge-Kutta with a fixed time step
+    RungeKutta4 { dt: f32 },
+    /// Adaptive Runge-Kutta-Fehlberg 4(5)
+    Adaptive {
+        tolerance: f32,
+        min_dt: f32,
+        max_dt: f32,
+    },
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Self::Euler { dt: 0.1 }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DynamicsError {
+    #[error("failed to build the IVP solver: {0}")]
+    SolverError(#[from] IVPError),
+    #[error("failed to spawn the integrator worker")]
+    SpawnFailed,
+    #[error("the integrator worker has stopped")]
+    WorkerStopped,
+}
+
+/// Iterator over (time, state) pairs produced by a solver
+pub(crate) type Solution = Box<dyn Iterator<Item = Result<(f32, State), IVPError>>>;
+
+fn into_solution<T>(iter: IVPIterator<Dyn, T>) -> Solution
+where
+    T: IVPStepper<Dyn, Error = IVPError, Field = f32, RealField = f32> + 'static,
+{
+    Box::new(iter)
+}
+
+type EulerSolver<D> = Euler<'static, f32, Dyn, D, DerivativeFn<D>>;
+type RungeKuttaSolver<D> = RungeKutta45<'static, f32, Dyn, D, DerivativeFn<D>>;
+
+impl Integrator {
+    /// Build a solver starting at time from the initial state
+    pub(crate) fn solve<S: DynamicalSystem>(
+        self,
+        time: f32,
+        state: State,
+        data: S::Data,
+    ) -> Result<Solution, IVPError> {
+        let derivative = S::derivative as DerivativeFn<S::Data>;
+        let dim = state.len();
+
+        match self {
+            Self::Euler { dt } => EulerSolver::new_dyn(dim)?
+                .with_maximum_dt(dt)?
+                .with_initial_time(time)?
+                .with_ending_time(f32::INFINITY)?
+                .with_initial_conditions(state)?
+                .with_derivative(derivative)
+                .solve(data)
+                .map(into_solution),
+            Self::RungeKutta4 { dt } => {
+                if dt <= 0.0 {
+                    return Err(IVPError::TimeDeltaOOB);
+                }
+                Ok(runge_kutta4(derivative, data, dt, time, state))
+            }
+            Self::Adaptive {
+                tolerance,
+                min_dt,
+                max_dt,
+            } => RungeKuttaSolver::new_dyn(dim)?
+                .with_tolerance(tolerance)?
+                .with_minimum_dt(min_dt)?
+                .with_maximum_dt(max_dt)?
+                .with_initial_time(time)?
+                .with_ending_time(f32::INFINITY)?
+                .with_initial_conditions(state)?
+                .with_derivative(derivative)
+                .solve(data)
+                .map(into_solution),
+        }
+    }
+}
+
+/// Classic fourth order Runge-Kutta with a fixed time step, yielding the state after each step
+/// The solution ends after the first error of the derivative function.
+fn runge_kutta4<D: 'static>(
+    derivative: DerivativeFn<D>,
+    mut data: D,
+    dt: f32,
+    start: f32,
+    mut state: State,
+) -> Solution {
+    let mut steps = 0u32;
+    let mut failed = false;
+    Box::new(std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let time = start + steps as f32 * dt;
+        match runge_kutta4_step(derivative, &mut data, dt, time, &state) {
+            Ok(next) => {
+                steps += 1;
+                state = next;
+                Some(Ok((start + steps as f32 * dt, state.clone())))
+            }
+            Err(e) => {
+                failed = true;
+                Some(Err(e.into()))
+            }
+        }
+    }))
+}
+
+fn runge_kutta4_step<D>(
+    derivative: DerivativeFn<D>,
+    data: &mut D,
+    dt: f32,
+    t: f32,
+    y: &State,
+) -> Result<State, UserError> {
+    let half = dt / 2.0;
+    let k1 = derivative(t, y.as_slice(), data)?;
+    let k2 = derivative(t + half, (y + &k1 * half).as_slice(), data)?;
+    let k3 = derivative(t + half, (y + &k2 * half).as_slice(), data)?;
+    let k4 = derivative(t + dt, (y + &k3 * dt).as_slice(), data)?;
+    Ok(y + (k1 + k2 * 2.0 + k3 * 2.0 + k4) * (dt / 6.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn growth(_t: f32, y: &[f32], _data: &mut ()) -> Result<State, UserError> {
+        Ok(State::from_column_slice(y))
+    }
+
+    #[test]
+    fn runge_kutta4_is_fourth_order() {
+        // y' = y from y(0) = 1 is e^t, forward Euler would reach 2.594 at t = 1
+        let solution = runge_kutta4(growth, (), 0.1, 0.0, State::from_vec(vec![1.0]));
+        let (time, state) = solution.take(10).last().unwrap().unwrap();
+        assert!((time - 1.0).abs() < 1e-6);
+        assert!((state[0] - std::f32::consts::E).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,221 @@
+use super::State;
+use std::collections::VecDeque;
+
+/// Values that can be blended linearly, s = 0 gives self and s = 1 gives other
+pub trait Lerp {
+    fn lerp(&self, other: &Self, s: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, s: f32) -> Self {
+        self + (other - self) * s
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, other: &Self, s: f32) -> Self {
+        self + (other - self) * s as f64
+    }
+}
+
+impl<T: Lerp, const N: usize> Lerp for [T; N] {
+    fn lerp(&self, other: &Self, s: f32) -> Self {
+        std::array::from_fn(|i| self[i].lerp(&other[i], s))
+    }
+}
+
+/// Vectors of different lengths, e.g. after particles were added, snap to other
+impl<T: Lerp + Clone> Lerp for Vec<T> {
+    fn lerp(&self, other: &Self, s: f32) -> Self {
+        if self.len() != other.len() {
+            return other.clone();
+        }
+        self.iter().zip(other).map(|(a, b)| a.lerp(b, s)).collect()
+    }
+}
+
+/// States of different lengths snap to other
+impl Lerp for State {
+    fn lerp(&self, other: &Self, s: f32) -> Self {
+        if self.len() != other.len() {
+            return other.clone();
+        }
+        self * (1.0 - s) + other * s
+    }
+}
+
+/// Ring buffer of timestamped states produced by a physics thread, sampled at
+/// the render time. Decouples the rate physics steps at from the frame rate
+/// without visible stutter, IntegratorWorker::state_at samples one for DynamicalSystems.
+///
+/// Sampling between two states blends them, sampling past the newest state holds it,
+/// and warns once when the newest state is more than the max staleness behind.
+#[derive(Debug, Clone)]
+pub struct StateInterpolator<T> {
+    states: VecDeque<(f32, T)>,
+    capacity: usize,
+    max_staleness: Option<f32>,
+    warned: bool,
+}
+
+impl<T: Lerp + Clone> StateInterpolator<T> {
+    /// Keep the newest capacity states, at least 2
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2);
+        Self {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+            max_staleness: None,
+            warned: false,
+        }
+    }
+
+    /// Warn when sampling more than seconds past the newest state
+    pub fn with_max_staleness(mut self, seconds: f32) -> Self {
+        self.max_staleness = Some(seconds);
+        self
+    }
+
+    /// Add the state at time, dropping the oldest state when full
+    /// States newer than time are dropped, so a physics thread that rewinds
+    /// can push its new history.
+    pub fn push(&mut self, time: f32, state: T) {
+        while self
+            .states
+            .back()
+            .is_some_and(|&(newest, _)| newest >= time)
+        {
+            self.states.pop_back();
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back((time, state));
+        self.warned = false;
+    }
+
+    /// The state at time t, None before any state is pushed
+    pub fn sample(&mut self, t: f32) -> Option<T> {
+        let idx = self.states.partition_point(|&(time, _)| time <= t);
+        if idx == 0 {
+            return self.states.front().map(|(_, state)| state.clone());
+        }
+        if idx == self.states.len() {
+            self.warn_if_stale(t);
+            return self.states.back().map(|(_, state)| state.clone());
+        }
+
+        let (before, ref first) = self.states[idx - 1];
+        let (after, ref second) = self.states[idx];
+        Some(first.lerp(second, (t - before) / (after - before)))
+    }
+
+    /// Warn once until the next push if sampling at t would hold a state older
+    /// than the max staleness, e.g. before waiting for the physics thread to catch up
+    pub fn warn_if_stale(&mut self, t: f32) {
+        let Some(newest) = self.newest_time() else {
+            return;
+        };
+        if self.is_stale(t) && !self.warned {
+            log::warn!(
+                "aftgraphs::dynamics::interpolator::StateInterpolator::warn_if_stale: newest state is {:.3}s old",
+                t - newest
+            );
+            self.warned = true;
+        }
+    }
+
+    /// If sampling at t would hold a state older than the max staleness
+    pub fn is_stale(&self, t: f32) -> bool {
+        match (self.max_staleness, self.newest_time()) {
+            (Some(max_staleness), Some(newest)) => t - newest > max_staleness,
+            _ => false,
+        }
+    }
+
+    pub fn newest_time(&self) -> Option<f32> {
+        self.states.back().map(|&(time, _)| time)
+    }
+
+    pub fn oldest_time(&self) -> Option<f32> {
+        self.states.front().map(|&(time, _)| time)
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.warned = false;
+    }
+
+    /// Take every state, oldest first
+    pub fn drain(&mut self) -> impl Iterator<Item = (f32, T)> + '_ {
+        self.warned = false;
+        self.states.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bacon_sci::prelude::*;
+
+    #[test]
+    fn samples_between_states() {
+        let mut interpolator = StateInterpolator::new(4).with_max_staleness(0.5);
+        assert_eq!(None, interpolator.sample(0.0));
+
+        interpolator.push(0.0, [0.0f32, 10.0]);
+        interpolator.push(1.0, [1.0, 20.0]);
+        assert_eq!(Some([0.0, 10.0]), interpolator.sample(-1.0));
+        assert_eq!(Some([0.25, 12.5]), interpolator.sample(0.25));
+        assert_eq!(Some([1.0, 20.0]), interpolator.sample(1.0));
+
+        assert!(!interpolator.is_stale(1.5));
+        assert_eq!(Some([1.0, 20.0]), interpolator.sample(2.0));
+        assert!(interpolator.is_stale(2.0));
+    }
+
+    #[test]
+    fn keeps_newest_states() {
+        let mut interpolator = StateInterpolator::new(3);
+        for time in 0..5 {
+            interpolator.push(time as f32, time as f64);
+        }
+        assert_eq!(
+            (3, Some(2.0)),
+            (interpolator.len(), interpolator.oldest_time())
+        );
+
+        // Rewinding drops the states after the new one
+        interpolator.push(2.5, 10.0);
+        assert_eq!(Some(2.5), interpolator.newest_time());
+        assert_eq!(Some(6.0), interpolator.sample(2.25));
+        assert_eq!(
+            vec![(2.0, 2.0), (2.5, 10.0)],
+            interpolator.drain().collect::<Vec<_>>()
+        );
+        assert!(interpolator.is_empty());
+    }
+
+    fn state(values: &[f32]) -> State {
+        State::from_iterator_generic(Dyn(values.len()), U1, values.iter().copied())
+    }
+
+    #[test]
+    fn lerps_states() {
+        let a = state(&[0.0, 2.0]);
+        let b = state(&[2.0, 4.0]);
+        assert_eq!(state(&[1.0, 3.0]), a.lerp(&b, 0.5));
+
+        let c = state(&[1.0]);
+        assert_eq!(c, a.lerp(&c, 0.5));
+        assert_eq!(vec![1.0f32], vec![0.0f32].lerp(&vec![2.0], 0.5));
+    }
+}
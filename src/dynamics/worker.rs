@@ -0,0 +1,461 @@
+use super::{DynamicalSystem, DynamicsError, Integrator, Solution, State, StateInterpolator};
+use crate::{block_on, spawn, Handle};
+use async_std::{
+    channel::{bounded, Receiver, Sender, TryRecvError},
+    sync::Mutex,
+};
+use bacon_sci::ivp::IVPError;
+use crossbeam::{
+    deque::{Injector, Steal},
+    sync::{Parker, Unparker},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+type Buffer = Arc<StateBuffer<(f32, State)>>;
+
+/// What a StateBuffer does when a producer pushes into it while it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferPolicy {
+    /// Keep every state and tell the producer to wait until the consumer catches up
+    #[default]
+    BlockProducer,
+    /// Drop the oldest state to make room, the producer keeps running
+    DropOldest,
+    /// Keep only the newest state, the producer keeps running
+    LatestOnly,
+}
+
+/// Bounded lock-free queue of states between a producer thread (web worker on WASM)
+/// and the render thread. Producers push with StateBuffer::push and park while it
+/// returns true, the consumer unparks them after taking states. Only
+/// BufferPolicy::BlockProducer asks the producer to wait, the other policies drop states
+/// instead. The buffer never blocks on its own, so the render thread is never stalled
+/// by a slow producer.
+#[derive(Debug)]
+pub struct StateBuffer<T> {
+    queue: Injector<T>,
+    capacity: usize,
+    policy: BufferPolicy,
+    dropped: AtomicUsize,
+}
+
+impl<T> StateBuffer<T> {
+    /// Buffer holding up to capacity states, at least 1
+    pub fn new(capacity: usize, policy: BufferPolicy) -> Self {
+        let capacity = match policy {
+            BufferPolicy::LatestOnly => 1,
+            _ => capacity.max(1),
+        };
+        Self {
+            queue: Injector::new(),
+            capacity,
+            policy,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Push a state, returning whether the buffer is full and the producer should wait
+    pub fn push(&self, item: T) -> bool {
+        match self.policy {
+            BufferPolicy::BlockProducer => {
+                self.queue.push(item);
+                self.queue.len() >= self.capacity
+            }
+            BufferPolicy::DropOldest | BufferPolicy::LatestOnly => {
+                // The consumer may take states meanwhile, so stop once the buffer is empty
+                while self.queue.len() >= self.capacity && self.pop().is_some() {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                self.queue.push(item);
+                false
+            }
+        }
+    }
+
+    /// Take the oldest state
+    pub fn pop(&self) -> Option<T> {
+        std::iter::repeat_with(|| self.queue.steal())
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+    }
+
+    /// Take every state, oldest first
+    pub fn drain(&self) -> Vec<T> {
+        std::iter::from_fn(|| self.pop()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> BufferPolicy {
+        self.policy
+    }
+
+    /// Count states a consumer evicted after the render time passed them
+    pub fn evicted(&self, count: usize) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Number of states dropped before they were interpolated so far
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Settings for an IntegratorWorker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkerSettings {
+    pub integrator: Integrator,
+    /// Number of states the worker computes ahead
+    pub max_buffered: usize,
+    /// What the worker does once max_buffered states are ahead
+    /// With BufferPolicy::BlockProducer the worker parks until states are queried,
+    /// with the other policies it keeps integrating and the oldest states are dropped.
+    pub policy: BufferPolicy,
+    /// Seconds the queried time may get ahead of the newest state before a warning is logged
+    pub max_staleness: Option<f32>,
+}
+
+impl WorkerSettings {
+    /// Default settings for display or headless rendering
+    /// Display rendering buffers far more states since frame times are not fixed
+    pub fn new(display: bool) -> Self {
+        Self {
+            integrator: Integrator::default(),
+            max_buffered: if display { 100 } else { 2 },
+            policy: BufferPolicy::default(),
+            // Headless renders wait for every state
+            max_staleness: display.then_some(0.5),
+        }
+    }
+
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: BufferPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_max_staleness(mut self, max_staleness: Option<f32>) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+}
+
+/// Integrates a DynamicalSystem on a background thread (web worker on WASM)
+///
+/// The worker pushes timestamped states into a StateBuffer ahead of the render time.
+/// Simulations query the state at the render time with IntegratorWorker::state_at,
+/// which interpolates between the bracketing states with a StateInterpolator,
+/// waiting for the worker when it fell behind, and change the system with
+/// IntegratorWorker::send, which rewinds the worker to the last queried time.
+pub struct IntegratorWorker<S: DynamicalSystem> {
+    buffer: Buffer,
+    /// The states taken from the buffer around the last queried time
+    states: StateInterpolator<State>,
+    time: f32,
+    messages: Sender<S::Message>,
+    response: Receiver<bool>,
+    /// Signalled when the worker pushes a state, so waiting for one does not spin
+    pushed: Receiver<()>,
+    request: Unparker,
+    lock: Arc<Mutex<bool>>,
+    _handle: Handle,
+}
+
+struct WorkerThread<S: DynamicalSystem> {
+    settings: WorkerSettings,
+    buffer: Buffer,
+    time: f32,
+    state: State,
+    messages: Receiver<S::Message>,
+    response: Sender<bool>,
+    pushed: Sender<()>,
+    request: Parker,
+    lock: Arc<Mutex<bool>>,
+}
+
+impl<S: DynamicalSystem> WorkerThread<S> {
+    async fn run(&mut self, mut system: S) -> Result<(), IVPError> {
+        let mut solver: Solution =
+            self.settings
+                .integrator
+                .solve::<S>(self.time, self.state.clone(), system.data())?;
+
+        loop {
+            let wait = {
+                let mut lock = self.lock.lock().await;
+                if *lock {
+                    *lock = false;
+                    continue;
+                }
+
+                let next = solver.next().ok_or(IVPError::TimeEndOOB)??;
+                self.time = next.0;
+                self.state = next.1.clone();
+                let wait = self.buffer.push(next);
+                // A full channel already has a signal waiting
+                let _ = self.pushed.try_send(());
+                wait
+            };
+
+            match self.messages.try_recv() {
+                Ok(message) => {
+                    let (start_time, mut start_state) = self
+                        .buffer
+                        .pop()
+                        .unwrap_or_else(|| (self.time, self.state.clone()));
+
+                    let applied = system.handle_message(message, start_time, &mut start_state);
+
+                    self.buffer.drain();
+
+                    self.time = start_time;
+                    self.state = start_state.clone();
+                    self.buffer.push((start_time, start_state.clone()));
+                    let _ = self.pushed.try_send(());
+                    solver = self.settings.integrator.solve::<S>(
+                        start_time,
+                        start_state,
+                        system.data(),
+                    )?;
+
+                    if self.response.send(applied).await.is_err() {
+                        break Ok(());
+                    }
+                }
+                Err(TryRecvError::Closed) => break Ok(()),
+                Err(TryRecvError::Empty) => (),
+            }
+
+            if wait {
+                self.request.park();
+            }
+        }
+    }
+}
+
+impl<S: DynamicalSystem> IntegratorWorker<S> {
+    /// Spawn a worker integrating from the initial state at time
+    /// The system is created on the worker by calling factory
+    pub async fn spawn(
+        settings: WorkerSettings,
+        time: f32,
+        state: State,
+        factory: impl FnOnce() -> S + Send + 'static,
+    ) -> Result<Self, DynamicsError> {
+        let (messages_tx, messages_rx) = bounded(1);
+        let (response_tx, response_rx) = bounded(1);
+        let (pushed_tx, pushed_rx) = bounded(1);
+
+        let request = Parker::new();
+        let request_unpark = request.unparker().clone();
+        request_unpark.unpark();
+
+        let lock = Arc::new(Mutex::new(false));
+        let buffer = Arc::new(StateBuffer::new(settings.max_buffered, settings.policy));
+        let mut states = StateInterpolator::new(2);
+        if let Some(max_staleness) = settings.max_staleness {
+            states = states.with_max_staleness(max_staleness);
+        }
+
+        let mut thread = WorkerThread::<S> {
+            settings,
+            buffer: buffer.clone(),
+            time,
+            state,
+            messages: messages_rx,
+            response: response_tx,
+            pushed: pushed_tx,
+            request,
+            lock: lock.clone(),
+        };
+
+        let handle = spawn(move || {
+            block_on(async move {
+                let system = factory();
+                if let Err(e) = thread.run(system).await {
+                    log::error!("aftgraphs::dynamics::worker::WorkerThread: solver failed: {e}");
+                }
+            });
+        })
+        .await
+        .map_err(|_| {
+            log::error!(
+                "aftgraphs::dynamics::worker::IntegratorWorker::spawn: {}",
+                DynamicsError::SpawnFailed
+            );
+            DynamicsError::SpawnFailed
+        })?;
+
+        Ok(Self {
+            buffer,
+            states,
+            time,
+            messages: messages_tx,
+            response: response_rx,
+            pushed: pushed_rx,
+            request: request_unpark,
+            lock,
+            _handle: handle,
+        })
+    }
+
+    /// The last time the state was queried at
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Number of states computed ahead of the last queried time
+    pub fn buffered(&self) -> usize {
+        let next = self
+            .states
+            .newest_time()
+            .is_some_and(|newest| newest >= self.time);
+        self.buffer.len() + usize::from(next)
+    }
+
+    /// Number of states the render time passed before they were interpolated
+    pub fn dropped(&self) -> usize {
+        self.buffer.dropped()
+    }
+
+    async fn next_state(&mut self) -> Result<(f32, State), DynamicsError> {
+        loop {
+            if let Some(s) = self.buffer.pop() {
+                return Ok(s);
+            }
+
+            self.request.unpark();
+            if self.pushed.recv().await.is_err() {
+                log::error!(
+                    "aftgraphs::dynamics::worker::IntegratorWorker: {}",
+                    DynamicsError::WorkerStopped
+                );
+                return Err(DynamicsError::WorkerStopped);
+            }
+        }
+    }
+
+    /// Get the state of the system at time t, interpolating
+    /// between the computed states around t
+    pub async fn state_at(&mut self, t: f32) -> Result<State, DynamicsError> {
+        self.time = t;
+
+        let mut skipped = 0;
+        while self.states.newest_time().is_none_or(|newest| newest < t) {
+            if self.buffer.is_empty() {
+                self.states.warn_if_stale(t);
+            }
+            let (time, state) = self.next_state().await?;
+            skipped += usize::from(time < t);
+            self.states.push(time, state);
+        }
+
+        // States the render time passed were dropped, except the one interpolated from
+        self.buffer.evicted(skipped.saturating_sub(1));
+        // Taking states made room for the worker
+        self.request.unpark();
+        if skipped > 10 && self.buffer.policy() == BufferPolicy::BlockProducer {
+            log::warn!(
+                "aftgraphs::dynamics::worker::IntegratorWorker::state_at: skipped {skipped} old states"
+            );
+        }
+
+        // A state at or after t was just taken
+        Ok(self
+            .states
+            .sample(t)
+            .expect("aftgraphs::dynamics::worker::IntegratorWorker::state_at: no state taken"))
+    }
+
+    /// Send a message to the system
+    /// The worker is rewound to the last state at or before the last queried time,
+    /// the message is applied to that state, and integration restarts from there.
+    /// Returns whether the system applied the message
+    pub async fn send(&mut self, message: S::Message) -> Result<bool, DynamicsError> {
+        {
+            let mut lock = self.lock.lock().await;
+            *lock = true;
+
+            // States are in time order: the interpolated ones, then the buffer
+            let mut states: Vec<_> = self.states.drain().collect();
+            states.extend(self.buffer.drain());
+
+            let index = states
+                .iter()
+                .rposition(|&(time, _)| time <= self.time)
+                .unwrap_or(0);
+            if index < states.len() {
+                self.buffer.push(states.swap_remove(index));
+            }
+        }
+
+        self.messages.send(message).await.map_err(|_| {
+            log::error!(
+                "aftgraphs::dynamics::worker::IntegratorWorker::send: {}",
+                DynamicsError::WorkerStopped
+            );
+            DynamicsError::WorkerStopped
+        })?;
+        self.request.unpark();
+
+        self.response.recv().await.map_err(|_| {
+            log::error!(
+                "aftgraphs::dynamics::worker::IntegratorWorker::send: {}",
+                DynamicsError::WorkerStopped
+            );
+            DynamicsError::WorkerStopped
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffer_policies() {
+        let blocking = StateBuffer::new(2, BufferPolicy::BlockProducer);
+        assert!(!blocking.push(0));
+        assert!(blocking.push(1));
+        assert!(blocking.push(2));
+        assert_eq!((3, 0), (blocking.len(), blocking.dropped()));
+        assert_eq!(Some(0), blocking.pop());
+
+        let oldest = StateBuffer::new(2, BufferPolicy::DropOldest);
+        assert!(!oldest.push(0));
+        assert!(!oldest.push(1));
+        assert!(!oldest.push(2));
+        assert_eq!(1, oldest.dropped());
+        assert_eq!(vec![1, 2], oldest.drain());
+        oldest.evicted(3);
+        assert_eq!(4, oldest.dropped());
+
+        let latest = StateBuffer::new(10, BufferPolicy::LatestOnly);
+        assert!(!latest.push(1));
+        assert!(!latest.push(2));
+        assert_eq!(vec![2], latest.drain());
+        assert!(latest.is_empty());
+    }
+}
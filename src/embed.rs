@@ -0,0 +1,138 @@
+use crate::{
+    coords::ScreenSpace,
+    input::{InputState, Inputs},
+    render::Renderer,
+    simulation::{ElementState, InputEvent, MouseButton, Simulation},
+};
+use async_std::sync::Mutex;
+use std::sync::Arc;
+use web_time::Duration;
+
+/// A simulation drawn into a window owned by the host application
+/// The host forwards its events and calls frame once per redraw instead of aftgraphs running a winit event loop.
+/// Create the renderer with display::init_external or display::init_raw.
+pub struct Embedded<T: Simulation> {
+    renderer: Renderer<'static, ()>,
+    simulation: Arc<Mutex<T>>,
+    inputs: Inputs,
+    input_values: InputState,
+    screen: ScreenSpace,
+}
+
+impl<T: Simulation> Embedded<T> {
+    /// Create the simulation, size is the window's size in pixels as passed to display::init_external
+    pub async fn new(
+        mut renderer: Renderer<'static, ()>,
+        inputs: Inputs,
+        size: (u32, u32),
+    ) -> Self {
+        renderer.configure_inputs(&inputs);
+        crate::stream::collect_pending();
+        let simulation = Arc::new(Mutex::new(
+            crate::simulation::create::<T, _>(&mut renderer, None).await,
+        ));
+        Self {
+            renderer,
+            simulation,
+            inputs,
+            input_values: InputState::default(),
+            screen: ScreenSpace::new(size, 1.0),
+        }
+    }
+
+    pub fn renderer(&self) -> &Renderer<'static, ()> {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut Renderer<'static, ()> {
+        &mut self.renderer
+    }
+
+    pub fn simulation(&self) -> Arc<Mutex<T>> {
+        self.simulation.clone()
+    }
+
+    pub fn input_values(&self) -> &InputState {
+        &self.input_values
+    }
+
+    /// Call when the host window resizes, size is in pixels
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.screen.size = (width, height);
+        self.renderer.resize_surface(width, height);
+        self.renderer.ui.context_mut().io_mut().display_size = [width as f32, height as f32];
+    }
+
+    /// Call when the host window moves to a display with a different DPI scale
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.screen.scale_factor = scale_factor;
+    }
+
+    pub fn screen(&self) -> &ScreenSpace {
+        &self.screen
+    }
+
+    /// Convert a cursor position in pixels from the window's top left to [-1, 1] screen space
+    pub fn to_screen_space(&self, position: (f64, f64)) -> (f64, f64) {
+        self.screen.physical_to_ndc(position)
+    }
+
+    /// Send an input from the host to the simulation at the time of the last frame
+    pub async fn input(&mut self, event: InputEvent) {
+        let time = self.renderer.clock.wall_time();
+        self.input_at(event, time).await;
+    }
+
+    /// Send an input from the host that happened at time, in the time base of Embedded::frame
+    /// Mouse events also reach the ui.
+    pub async fn input_at(&mut self, event: InputEvent, time: f64) {
+        if let InputEvent::Mouse(state, button, position) = event {
+            let io = self.renderer.ui.context_mut().io_mut();
+            let (x, y) = self.screen.ndc_to_physical(position);
+            io.add_mouse_pos_event([x as f32, y as f32]);
+
+            let button = match button {
+                MouseButton::Left => Some(imgui::MouseButton::Left),
+                MouseButton::Right => Some(imgui::MouseButton::Right),
+                MouseButton::Middle => Some(imgui::MouseButton::Middle),
+                _ => None,
+            };
+            if let Some(button) = button {
+                io.add_mouse_button_event(button, state == ElementState::Pressed);
+            }
+        }
+
+        crate::simulation::send_input(&self.renderer, &self.simulation, event, time).await;
+    }
+
+    /// Step and draw one frame, time is seconds since the simulation started
+    pub async fn frame(&mut self, time: f64, delta_time: Duration) {
+        self.renderer.update_clock(time, delta_time);
+        self.renderer.apply_frame_step();
+
+        for event in crate::stream::take_pending() {
+            crate::simulation::send_input(&self.renderer, &self.simulation, event, time).await;
+        }
+
+        {
+            let mut input_values = self.input_values.lock().await;
+            self.renderer
+                .render(self.simulation.clone(), input_values.as_mut())
+                .await;
+        }
+
+        if let Err(err) = self
+            .renderer
+            .draw_ui(None, &self.inputs, self.input_values.clone())
+            .await
+        {
+            log::warn!("aftgraphs::embed::Embedded::frame: {err}");
+        }
+
+        profiling::finish_frame!();
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// A library-level failure that would otherwise abort the process
+#[derive(Error, Debug, Clone)]
+#[error("{context}: {message}")]
+pub struct LibraryError {
+    /// Path of the failing function, e.g. aftgraphs::app::App::resumed
+    pub context: &'static str,
+    pub message: String,
+}
+
+/// How library-level failures are handled.
+/// Every failure is logged, the policy decides what happens next
+#[derive(Clone, Default)]
+pub enum ErrorPolicy {
+    /// Panic with the error message, the default
+    #[default]
+    Panic,
+    /// Only log the error, the failing operation is skipped
+    Log,
+    /// Pass the error to a callback, the failing operation is skipped
+    Callback(Arc<dyn Fn(&LibraryError) + Send + Sync>),
+}
+
+static POLICY: Mutex<ErrorPolicy> = Mutex::new(ErrorPolicy::Panic);
+
+/// Sets the crate-wide error policy. Embedders and plugin hosts should call
+/// this before creating a Renderer to avoid aborting the host process
+pub fn set_error_policy(policy: ErrorPolicy) {
+    *POLICY
+        .lock()
+        .expect("aftgraphs::error::set_error_policy: poisoned lock") = policy;
+}
+
+pub fn error_policy() -> ErrorPolicy {
+    POLICY
+        .lock()
+        .expect("aftgraphs::error::error_policy: poisoned lock")
+        .clone()
+}
+
+/// Route an error through the error policy.
+/// Only returns if the policy does not panic, callers must then recover
+pub(crate) fn report(context: &'static str, error: impl Display) {
+    report_with(&error_policy(), context, error);
+}
+
+fn report_with(policy: &ErrorPolicy, context: &'static str, error: impl Display) {
+    let error = LibraryError {
+        context,
+        message: error.to_string(),
+    };
+    log::error!("{error}");
+
+    match policy {
+        ErrorPolicy::Panic => panic!("{error}"),
+        ErrorPolicy::Log => (),
+        ErrorPolicy::Callback(callback) => callback(&error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn callback_policy_receives_errors() {
+        // The global policy is left alone, other tests may report concurrently
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let policy = ErrorPolicy::Callback(Arc::new(move |error| {
+            assert_eq!("aftgraphs::test: failed", error.to_string());
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        report_with(&policy, "aftgraphs::test", "failed");
+        report_with(&ErrorPolicy::Log, "aftgraphs::test", "failed");
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+    }
+}
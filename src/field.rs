@@ -0,0 +1,371 @@
+use crate::render::{
+    Allocation, BindGroupLayout, RenderPass, RenderPipeline, Renderer, ResourceKind,
+};
+use crate::ui::UiPlatform;
+use crate::uniform::Uniform;
+use std::ops::{Deref, DerefMut};
+
+mod builder;
+pub use builder::ScalarFieldBuilder;
+
+/// Colormap used to render a ScalarField
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Inferno,
+    /// Diverging blue-white-red
+    Coolwarm,
+    Grayscale,
+}
+
+/// Polynomial fits of the matplotlib colormaps, lowest order first
+/// Kept in sync with res/scalar_field.wgsl
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_6, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_03],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_44, 3.932_712_4],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_398, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_325],
+];
+
+impl Colormap {
+    /// Color of the normalized value t on the CPU, matching the colors of a ScalarField
+    /// t is clamped to [0, 1]
+    pub fn sample(self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let mix =
+            |a: [f32; 3], b: [f32; 3], t: f32| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+        let polynomial = |coefficients: &[[f32; 3]; 7]| {
+            coefficients.iter().rev().fold([0.0; 3], |acc, c| {
+                std::array::from_fn(|i| c[i] + t * acc[i])
+            })
+        };
+
+        let color = match self {
+            Self::Viridis => polynomial(&VIRIDIS),
+            Self::Inferno => polynomial(&INFERNO),
+            Self::Coolwarm => {
+                let cool = [0.230, 0.299, 0.754];
+                let white = [0.865, 0.865, 0.865];
+                let warm = [0.706, 0.016, 0.150];
+                if t < 0.5 {
+                    mix(cool, white, t * 2.0)
+                } else {
+                    mix(white, warm, t * 2.0 - 1.0)
+                }
+            }
+            Self::Grayscale => [t; 3],
+        };
+
+        color.map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+struct FieldParams {
+    range: [f32; 2],
+    colormap: u32,
+    contours: u32,
+    contour_width: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for FieldParams {}
+unsafe impl bytemuck::NoUninit for FieldParams {}
+
+/// A 2D grid of f32 values rendered as a heatmap over the whole viewport
+///
+/// The values live in an R32Float texture. Write them from the CPU with
+/// ScalarField::modify or ScalarField::upload, or from a compute shader by
+/// binding ScalarField::view as a write-only storage texture (when the adapter
+/// supports compute shaders). Values are mapped through the range to [0, 1]
+/// and colored with the Colormap, with optional evenly spaced contour lines.
+pub struct ScalarField {
+    width: u32,
+    height: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    texture_layout: BindGroupLayout,
+    texture_bind_group: wgpu::BindGroup,
+    params: Uniform<FieldParams>,
+    pipeline: RenderPipeline,
+    data: Vec<f32>,
+    label: Option<String>,
+    allocation: Allocation,
+}
+
+pub struct ScalarFieldGuard<'a, 'b, P: UiPlatform> {
+    field: &'a mut ScalarField,
+    renderer: &'a Renderer<'b, P>,
+    changed: bool,
+}
+
+impl ScalarField {
+    fn create_texture<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        width: u32,
+        height: u32,
+        label: Option<&str>,
+        layout: &wgpu::BindGroupLayout,
+    ) -> (
+        wgpu::Texture,
+        wgpu::TextureView,
+        wgpu::BindGroup,
+        Allocation,
+    ) {
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if renderer
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            usage |= wgpu::TextureUsages::STORAGE_BINDING;
+        }
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            });
+
+        let allocation = renderer.track_memory(
+            ResourceKind::Texture,
+            label,
+            (width * height) as u64 * std::mem::size_of::<f32>() as u64,
+        );
+
+        (texture, view, bind_group, allocation)
+    }
+
+    fn write<P: UiPlatform>(&self, renderer: &Renderer<P>) {
+        renderer.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(self.data.as_slice()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.width * std::mem::size_of::<f32>() as u32),
+                rows_per_image: Some(self.height),
+            },
+            self.texture.size(),
+        );
+        renderer.record_upload(std::mem::size_of_val(self.data.as_slice()));
+    }
+
+    /// Create a guard to modify the values of the field, in row-major order
+    /// When the guard drops, it will buffer the data to the GPU
+    pub fn modify<'a, 'b, P: UiPlatform>(
+        &'a mut self,
+        renderer: &'a Renderer<'b, P>,
+    ) -> ScalarFieldGuard<'a, 'b, P> {
+        ScalarFieldGuard {
+            field: self,
+            renderer,
+            changed: false,
+        }
+    }
+
+    /// Replace the values of the field, in row-major order
+    /// Will immediately buffer the data to the GPU
+    /// Panics if values is not width * height long
+    pub fn upload<P: UiPlatform>(&mut self, renderer: &Renderer<P>, values: &[f32]) {
+        assert_eq!(
+            values.len(),
+            self.data.len(),
+            "aftgraphs::field::ScalarField::upload: expected width * height values"
+        );
+        self.data.copy_from_slice(values);
+        self.write(renderer);
+    }
+
+    /// Resize the field, recreating the texture
+    /// The values are reset to zero
+    pub fn resize<P: UiPlatform>(&mut self, renderer: &Renderer<P>, width: u32, height: u32) {
+        let (texture, view, bind_group, allocation) = Self::create_texture(
+            renderer,
+            width,
+            height,
+            self.label.as_deref(),
+            &self.texture_layout,
+        );
+
+        self.width = width;
+        self.height = height;
+        self.texture = texture;
+        self.view = view;
+        self.texture_bind_group = bind_group;
+        self.allocation = allocation;
+        self.data = vec![0.0; (width * height) as usize];
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The CPU copy of the values
+    /// Does not reflect writes made by compute shaders
+    pub fn as_slice(&self) -> &[f32] {
+        self.data.as_slice()
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The R32Float texture view, for binding in compute shaders
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn colormap(&self) -> Colormap {
+        match self.params.colormap {
+            0 => Colormap::Viridis,
+            1 => Colormap::Inferno,
+            2 => Colormap::Coolwarm,
+            _ => Colormap::Grayscale,
+        }
+    }
+
+    pub fn set_colormap<P: UiPlatform>(&mut self, renderer: &Renderer<P>, colormap: Colormap) {
+        let mut params = *self.params;
+        params.colormap = colormap as u32;
+        self.params.update(renderer, params);
+    }
+
+    /// The values mapped to the ends of the colormap
+    pub fn range(&self) -> (f32, f32) {
+        (self.params.range[0], self.params.range[1])
+    }
+
+    pub fn set_range<P: UiPlatform>(&mut self, renderer: &Renderer<P>, min: f32, max: f32) {
+        let mut params = *self.params;
+        params.range = [min, max];
+        self.params.update(renderer, params);
+    }
+
+    /// Set the number of contour lines and their width in pixels
+    /// Zero contours disables the lines
+    pub fn set_contours<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        contours: u32,
+        width: f32,
+    ) {
+        let mut params = *self.params;
+        params.contours = contours;
+        params.contour_width = width;
+        self.params.update(renderer, params);
+    }
+
+    /// Draw the field over the whole viewport
+    pub fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.params.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl AsRef<[f32]> for ScalarField {
+    fn as_ref(&self) -> &[f32] {
+        self.as_slice()
+    }
+}
+
+impl<P: UiPlatform> AsRef<[f32]> for ScalarFieldGuard<'_, '_, P> {
+    fn as_ref(&self) -> &[f32] {
+        self.field.as_slice()
+    }
+}
+
+impl<P: UiPlatform> Deref for ScalarFieldGuard<'_, '_, P> {
+    type Target = [f32];
+
+    fn deref(&self) -> &Self::Target {
+        self.field.as_slice()
+    }
+}
+
+/// Using this will make the data be sent to the GPU on drop
+impl<P: UiPlatform> AsMut<[f32]> for ScalarFieldGuard<'_, '_, P> {
+    fn as_mut(&mut self) -> &mut [f32] {
+        self.changed = true;
+        self.field.data.as_mut_slice()
+    }
+}
+
+/// Using this will make the data be sent to the GPU on drop
+impl<P: UiPlatform> DerefMut for ScalarFieldGuard<'_, '_, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut()
+    }
+}
+
+/// Buffers data to GPU if changed
+impl<P: UiPlatform> Drop for ScalarFieldGuard<'_, '_, P> {
+    fn drop(&mut self) {
+        if self.changed {
+            self.field.write(self.renderer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colormap_endpoints() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.02);
+
+        assert!(close([0.267, 0.005, 0.329], Colormap::Viridis.sample(0.0)));
+        assert!(close([0.993, 0.906, 0.144], Colormap::Viridis.sample(1.0)));
+        assert!(close([0.988, 1.0, 0.645], Colormap::Inferno.sample(2.0)));
+        assert_eq!([0.865; 3], Colormap::Coolwarm.sample(0.5));
+        assert_eq!([0.25; 3], Colormap::Grayscale.sample(0.25));
+    }
+}
@@ -0,0 +1,159 @@
+use super::{Colormap, FieldParams, ScalarField};
+use crate::render::{
+    BindGroupLayoutBuilder, RenderPipelineBuilder, Renderer, ShaderBuilder, BINDING_UNIFORM_BUFFER,
+};
+use crate::ui::UiPlatform;
+use crate::uniform::UniformBuilder;
+
+/// Builder struct for a ScalarField of a given size
+/// Defaults to the colormap of the Renderer's Palette over the range [0, 1] without contours
+pub struct ScalarFieldBuilder<'a> {
+    width: u32,
+    height: u32,
+    colormap: Option<Colormap>,
+    range: [f32; 2],
+    contours: u32,
+    contour_width: f32,
+    label: Option<&'a str>,
+    data: Option<Vec<f32>>,
+}
+
+impl<'a> ScalarFieldBuilder<'a> {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            colormap: None,
+            range: [0.0, 1.0],
+            contours: 0,
+            contour_width: 1.0,
+            label: None,
+            data: None,
+        }
+    }
+
+    /// Add a label to the field
+    /// The label will be applied to the texture, uniform, and pipeline
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn with_colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = Some(colormap);
+        self
+    }
+
+    /// Set the values mapped to the ends of the colormap
+    pub fn with_range(mut self, min: f32, max: f32) -> Self {
+        self.range = [min, max];
+        self
+    }
+
+    /// Draw contours evenly spaced contour lines over the range
+    /// width is the width of the lines in pixels
+    pub fn with_contours(mut self, contours: u32, width: f32) -> Self {
+        self.contours = contours;
+        self.contour_width = width;
+        self
+    }
+
+    /// Sets the initial values of the field, in row-major order
+    /// Defaults to all zeros
+    pub fn with_initial_values(mut self, values: Vec<f32>) -> Self {
+        self.data = Some(values);
+        self
+    }
+
+    /// Creates the ScalarField
+    /// This includes calls to the GPU
+    /// Panics if the initial values are not width * height long
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> ScalarField {
+        let Self {
+            width,
+            height,
+            colormap,
+            range,
+            contours,
+            contour_width,
+            label,
+            data,
+        } = self;
+
+        let data = data.unwrap_or_else(|| vec![0.0; (width * height) as usize]);
+        assert_eq!(
+            data.len(),
+            (width * height) as usize,
+            "aftgraphs::field::ScalarFieldBuilder::build: expected width * height initial values"
+        );
+
+        let params_layout = BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let params = UniformBuilder::new()
+            .with_label(label)
+            .with_bind_group_layout(params_layout)
+            .with_data(FieldParams {
+                range,
+                colormap: colormap.unwrap_or_else(|| renderer.palette().colormap()) as u32,
+                contours,
+                contour_width,
+                _padding: [0.0; 3],
+            })
+            .build(renderer);
+
+        let texture_layout = BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .build(renderer);
+        let (texture, view, texture_bind_group, allocation) =
+            ScalarField::create_texture(renderer, width, height, label, &texture_layout);
+
+        let shader = ShaderBuilder::new()
+            .with_module(wgpu::include_wgsl!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/res/scalar_field.wgsl"
+            )))
+            .with_default_fs_entrypoint()
+            .build(renderer);
+        let pipeline = RenderPipelineBuilder::new()
+            .with_vertex_shader(shader)
+            .with_bind_group_layout(params.bind_group_layout())
+            .with_bind_group_layout(&texture_layout)
+            .with_layout_label(label)
+            .with_pipeline_label(label)
+            .build(renderer)
+            .expect("aftgraphs::field::ScalarFieldBuilder::build: pipeline without push constants failed to build");
+
+        let field = ScalarField {
+            width,
+            height,
+            texture,
+            view,
+            texture_layout,
+            texture_bind_group,
+            params,
+            pipeline,
+            data,
+            label: label.map(String::from),
+            allocation,
+        };
+        field.write(renderer);
+        field
+    }
+}
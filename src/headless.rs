@@ -1,10 +1,17 @@
-use crate::render::Renderer;
+use crate::render::{MemoryBudget, Renderer};
 use crate::ui::Ui;
 use crate::GraphicsInitError;
 use crate::{input::InputValue, simulation::InputEvent};
 use async_std::sync::Mutex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+mod crop;
+pub use crop::{copy_rect, even, Crop, CropError};
 
 /// Event at a certain time
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -17,6 +24,8 @@ pub enum HeadlessEvent {
     MOUSEUP((f64, f64), winit::event::MouseButton),
     /// Keyboard event
     KEYEVENT(winit::event::RawKeyEvent),
+    /// (stream, message) - a DataStream message, to replay recorded live data
+    DATA(String, serde_json::Value),
 }
 
 impl From<HeadlessEvent> for InputEvent {
@@ -29,6 +38,7 @@ impl From<HeadlessEvent> for InputEvent {
             }
             HeadlessEvent::MOUSEUP(pos, button) => Self::Mouse(ElementState::Released, button, pos),
             HeadlessEvent::KEYEVENT(key_event) => Self::Keyboard(key_event),
+            HeadlessEvent::DATA(stream, message) => Self::Data(stream, message),
         }
     }
 }
@@ -36,6 +46,9 @@ impl From<HeadlessEvent> for InputEvent {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct HeadlessInputBlock {
     pub time: f64,
+    /// Title of a chapter starting at this block
+    #[serde(default)]
+    pub label: Option<String>,
     #[serde(default)]
     pub events: Vec<HeadlessEvent>,
     #[serde(flatten)]
@@ -47,6 +60,8 @@ pub struct HeadlessMetadata {
     pub duration: f64,
     pub size: Option<[u32; 2]>,
     pub delta_t: f64,
+    /// Part of the frames encoded, --crop takes precedence
+    pub crop: Option<Crop>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -55,11 +70,62 @@ pub struct HeadlessInitialInputs {
     pub inputs: HashMap<String, InputValue>,
 }
 
+/// Runs of the same headless input with different seeds, from an [ensemble] table
+/// Shows the variability of stochastic simulations, see SimulationContext::run_ensemble
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct HeadlessEnsemble {
+    /// Number of runs
+    pub runs: usize,
+    /// Seeds of the first runs, the other runs count up from the seed of the render
+    #[serde(default)]
+    pub seeds: Vec<u64>,
+    /// Tile the runs into one video instead of writing a video per run
+    #[serde(default)]
+    pub grid: bool,
+}
+
+impl HeadlessEnsemble {
+    /// Seed of every run, base being the seed from --seed or the current time
+    pub fn seeds(&self, base: u64) -> Vec<u64> {
+        (0..self.runs.max(self.seeds.len()))
+            .map(|idx| {
+                self.seeds
+                    .get(idx)
+                    .copied()
+                    .unwrap_or_else(|| base.wrapping_add(idx as u64))
+            })
+            .collect()
+    }
+
+    /// Columns and rows of a grid of runs, as close to square as possible
+    pub fn grid_size(runs: usize) -> (usize, usize) {
+        let columns = ((runs as f64).sqrt().ceil() as usize).max(1);
+        (columns, runs.div_ceil(columns))
+    }
+
+    /// File the run with seed is written to, next to out_file
+    pub fn run_file(out_file: &Path, seed: u64) -> PathBuf {
+        let stem = out_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut name = format!("{stem}-seed{seed}");
+        if let Some(extension) = out_file.extension() {
+            name.push('.');
+            name.push_str(&extension.to_string_lossy());
+        }
+        out_file.with_file_name(name)
+    }
+}
+
 /// Input file for headless rendering
 /// Input is in TOML
 /// simulation TOML block defines total duration, size of render, and time step to use
 /// Optional [initial-inputs] definies initial inputs
-/// Each [[block]] defines a change in input at a specific time
+/// Optional [ensemble] renders several seeds of the input, see HeadlessEnsemble
+/// Each [[block]] defines a change in input at a specific time, a block with a label
+/// starts a chapter. Chapters are muxed into MP4, MKV and MOV videos built with the 'ffmpeg'
+/// feature, otherwise they are written next to the video in FFmpeg metadata format
 /// Each input is the full input key from the spec file, with spaces
 /// replaced by '_' and periods replaced by '-' (e.g. block_name-group_name-input_name)
 /// as the key mapped to an InputValue
@@ -70,36 +136,196 @@ pub struct HeadlessInput {
     pub initial_inputs: Option<HeadlessInitialInputs>,
     #[serde(rename = "block", default)]
     pub blocks: Vec<HeadlessInputBlock>,
+    #[serde(default)]
+    pub ensemble: Option<HeadlessEnsemble>,
+}
+
+/// Full input name of a key in a headless input file, see HeadlessInput
+pub fn input_name(key: &str) -> String {
+    key.replace('_', " ").replace('-', ".")
+}
+
+/// A named section of a headless video, times are in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl HeadlessInput {
+    /// Chapters started by the labeled blocks, each lasting until the next one
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut labeled: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| Some((block.time, block.label.clone()?)))
+            .collect();
+        labeled.sort_by(|lhs, rhs| lhs.0.total_cmp(&rhs.0));
+
+        let ends = labeled
+            .iter()
+            .skip(1)
+            .map(|(time, _)| *time)
+            .chain(std::iter::once(self.simulation.duration));
+        labeled
+            .iter()
+            .zip(ends)
+            .map(|((start, title), end)| Chapter {
+                title: title.clone(),
+                start: *start,
+                end,
+            })
+            .collect()
+    }
+}
+
+/// Chapters in the FFmpeg metadata format, for videos they could not be muxed into, e.g. with
+/// ffmpeg -i video.h264 -i video.ffmetadata -map_metadata 1 -c copy video.mp4
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let escape = |title: &str| {
+        title.chars().fold(String::new(), |mut escaped, c| {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    };
+    let ms = |time: f64| (time * 1e3).round() as u64;
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            ms(chapter.start),
+            ms(chapter.end),
+            escape(&chapter.title)
+        ));
+    }
+    metadata
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub async fn init(mut size: (u32, u32)) -> Result<Renderer<'static, ()>, GraphicsInitError> {
-    use GraphicsInitError as HIE;
+pub async fn init(size: (u32, u32)) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    init_with_adapter(size, false).await
+}
 
-    log::debug!("aftgraphs::headless::init: Initializing renderer");
+/// Initialize a headless renderer on the fallback adapter
+/// The fallback adapter is a software renderer such as llvmpipe or WARP,
+/// so rendering works on machines without a GPU.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn init_fallback(size: (u32, u32)) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    init_with_adapter(size, true).await
+}
 
-    size.0 = size.0.max(1);
-    size.1 = size.1.max(1);
+/// Initialize a headless renderer on each of up to count GPUs, to split frames across.
+/// Falls back to a single renderer from init on machines with one GPU
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn init_all(
+    size: (u32, u32),
+    count: usize,
+) -> Result<Vec<Renderer<'static, ()>>, GraphicsInitError> {
+    let adapters = distinct_adapters(&create_instance()).len().min(count);
+    if adapters < 2 {
+        return Ok(vec![init(size).await?]);
+    }
+
+    let mut renderers = Vec::with_capacity(adapters);
+    for idx in 0..adapters {
+        // Every renderer owns its instance, enumeration order is stable between them
+        let instance = create_instance();
+        let adapter = distinct_adapters(&instance)
+            .into_iter()
+            .nth(idx)
+            .ok_or(GraphicsInitError::NoAdapter)?;
+        log::info!(
+            "aftgraphs::headless::init_all: Rendering on {}",
+            adapter.get_info().name
+        );
+        renderers.push(init_on_adapter(size, instance, adapter).await?);
+    }
+    Ok(renderers)
+}
+
+/// Hardware adapters of instance, each GPU once although most are listed for several backends
+#[cfg(not(target_arch = "wasm32"))]
+fn distinct_adapters(instance: &wgpu::Instance) -> Vec<wgpu::Adapter> {
+    let adapters: Vec<_> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .into_iter()
+        .filter(|adapter| adapter.get_info().device_type != wgpu::DeviceType::Cpu)
+        .collect();
+    let backends: Vec<_> = adapters
+        .iter()
+        .map(|adapter| adapter.get_info().backend)
+        .collect();
+    let backend = widest_backend(&backends);
+    adapters
+        .into_iter()
+        .filter(|adapter| Some(adapter.get_info().backend) == backend)
+        .collect()
+}
+
+/// The backend listing the most of the adapters with backends, the first on a tie
+/// A backend lists every GPU it drives once, so its adapters are distinct GPUs even
+/// when they are the same model, which vendor and device ids can not tell apart.
+#[cfg(not(target_arch = "wasm32"))]
+fn widest_backend(backends: &[wgpu::Backend]) -> Option<wgpu::Backend> {
+    let count = |backend| backends.iter().filter(|&&other| other == backend).count();
+    backends
+        .iter()
+        .copied()
+        .rev()
+        .max_by_key(|&backend| count(backend))
+}
 
-    log::debug!("aftgraphs::headless::init: Creating surface");
-    let instance = wgpu::Instance::default();
+#[cfg(not(target_arch = "wasm32"))]
+fn create_instance() -> wgpu::Instance {
+    let flags = crate::render::instance_flags();
+    log::debug!("aftgraphs::headless::init: Creating instance with flags {flags:?}");
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        flags,
+        ..Default::default()
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn init_with_adapter(
+    size: (u32, u32),
+    force_fallback_adapter: bool,
+) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    log::debug!("aftgraphs::headless::init: Initializing renderer");
+
+    let instance = create_instance();
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,
+            force_fallback_adapter,
             compatible_surface: None,
         })
         .await
-        .ok_or(HIE::NoAdapter)?;
+        .ok_or(GraphicsInitError::NoAdapter)?;
+
+    init_on_adapter(size, instance, adapter).await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn init_on_adapter(
+    mut size: (u32, u32),
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+) -> Result<Renderer<'static, ()>, GraphicsInitError> {
+    size.0 = size.0.max(1);
+    size.1 = size.1.max(1);
 
     log::debug!("aftgraphs::headless::init: Requesting rendering device");
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                    .using_resolution(adapter.limits()),
+                required_features: crate::render::required_features(&adapter),
+                required_limits: crate::render::required_limits(&adapter),
                 ..Default::default()
             },
             None,
@@ -125,17 +351,15 @@ pub async fn init(mut size: (u32, u32)) -> Result<Renderer<'static, ()>, Graphic
         dimension: wgpu::TextureDimension::D2,
         format: wgpu::TextureFormat::Rgba8UnormSrgb,
         label: None,
-        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage: wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
     };
     let texture = device.create_texture(&texture_desc);
     let texture_view = texture.create_view(&Default::default());
 
-    let u32_size = std::mem::size_of::<u32>() as u32;
-    let bytes_per_row = u32_size * size.0;
-    let missing_bytes =
-        wgpu::COPY_BYTES_PER_ROW_ALIGNMENT - (bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
-    let bytes_per_row = bytes_per_row + missing_bytes;
+    let bytes_per_row = crate::render::padded_bytes_per_row(size.0);
     let buffer_size = (bytes_per_row * size.1) as wgpu::BufferAddress;
     let buffer_desc = wgpu::BufferDescriptor {
         size: buffer_size,
@@ -145,6 +369,7 @@ pub async fn init(mut size: (u32, u32)) -> Result<Renderer<'static, ()>, Graphic
     };
 
     let buffer = device.create_buffer(&buffer_desc);
+    let pipeline_cache = crate::render::PipelineCache::open(&device, &adapter);
 
     let (ui, platform) =
         Ui::new_headless(size, &device, &queue, wgpu::TextureFormat::Rgba8UnormSrgb);
@@ -163,7 +388,114 @@ pub async fn init(mut size: (u32, u32)) -> Result<Renderer<'static, ()>, Graphic
         platform,
         ui,
         aspect_ratio,
-        time: 0.0,
-        delta_time: 0.0,
+        clock: Default::default(),
+        show_stats: false,
+        show_inspector: false,
+        show_about: false,
+        palette: Default::default(),
+        clear_color: wgpu::Color::BLACK,
+        stats: Default::default(),
+        memory: Arc::new(MemoryBudget::with_reserved(
+            size.0 as u64 * size.1 as u64 * 4 + buffer_size,
+        )),
+        resources: Default::default(),
+        pipeline_cache,
+        hot_reloads: Default::default(),
+        viewport: Default::default(),
+        tile: Default::default(),
+        cube_face: Default::default(),
+        layers: Default::default(),
+        background: Default::default(),
+        accumulation: Default::default(),
+        msaa: Default::default(),
+        post: Default::default(),
+        views: Default::default(),
+        seed: crate::rand::startup_seed().await,
+        aspect_policy: Default::default(),
+        world_scale: Default::default(),
+        input_queue: Default::default(),
+        prepared: Default::default(),
+        input_modulation: Default::default(),
+        input_smoothing: Default::default(),
+        uniform_bindings: Default::default(),
+        script: Default::default(),
+        audio: Default::default(),
+        timeline: None,
+        ui_layout: Default::default(),
+        frame_step: Default::default(),
+        cursor: Default::default(),
+        asset_progress: Default::default(),
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chapters_end_at_next_label() {
+        let block = |time: f64, label: Option<&str>| HeadlessInputBlock {
+            time,
+            label: label.map(String::from),
+            ..Default::default()
+        };
+        let input = HeadlessInput {
+            simulation: HeadlessMetadata {
+                duration: 10.0,
+                ..Default::default()
+            },
+            blocks: vec![
+                block(4.0, Some("b=1")),
+                block(2.0, None),
+                block(1.0, Some("a")),
+            ],
+            ..Default::default()
+        };
+
+        let chapters = input.chapters();
+        assert_eq!(
+            vec![
+                Chapter {
+                    title: "a".to_owned(),
+                    start: 1.0,
+                    end: 4.0,
+                },
+                Chapter {
+                    title: "b=1".to_owned(),
+                    start: 4.0,
+                    end: 10.0,
+                },
+            ],
+            chapters
+        );
+        assert!(ffmetadata(&chapters).ends_with("START=4000\nEND=10000\ntitle=b\\=1\n"));
+    }
+
+    #[test]
+    fn identical_gpus_stay_distinct() {
+        use wgpu::Backend::{Dx12, Gl, Vulkan};
+
+        // Two identical GPUs, both listed for Vulkan and one for GL
+        assert_eq!(Some(Vulkan), widest_backend(&[Vulkan, Gl, Vulkan]));
+        assert_eq!(Some(Dx12), widest_backend(&[Dx12, Vulkan]));
+        assert_eq!(None, widest_backend(&[]));
+    }
+
+    #[test]
+    fn ensemble_runs() {
+        let input: HeadlessInput = toml::from_str(
+            "[simulation]\nduration = 1.0\ndelta_t = 0.1\n[ensemble]\nruns = 5\nseeds = [7, 3]\n",
+        )
+        .unwrap();
+        let ensemble = input.ensemble.unwrap();
+        assert_eq!(vec![7, 3, 12, 13, 14], ensemble.seeds(10));
+        assert!(!ensemble.grid);
+
+        assert_eq!((3, 2), HeadlessEnsemble::grid_size(5));
+        assert_eq!((1, 1), HeadlessEnsemble::grid_size(1));
+        assert_eq!(
+            PathBuf::from("out/video-seed7.h264"),
+            HeadlessEnsemble::run_file(Path::new("out/video.h264"), 7)
+        );
+    }
+}
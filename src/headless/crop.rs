@@ -0,0 +1,150 @@
+use crate::render::WorldRect;
+use crate::uniform::Mat4;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CropError {
+    #[error("expected x,y,width,height or world:min_x,min_y,max_x,max_y, got {0:?}")]
+    Syntax(String),
+    #[error("the crop rectangle does not overlap the {0}x{1} frame")]
+    OutsideFrame(u32, u32),
+    #[error("the {0}x{1} crop is too small to encode, video needs at least 2x2 pixels")]
+    TooSmall(u32, u32),
+    #[error("frames of {0} renders can not be cropped")]
+    Unsupported(&'static str),
+}
+
+/// Part of the frames kept in a headless video, from [simulation.crop] or --crop
+/// Lets a detail of a large render be exported without changing the simulation's camera.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Crop {
+    /// x, y, width and height in pixels, from the top left corner of the frame
+    Pixels([u32; 4]),
+    /// A rectangle of world space, mapped to pixels with Renderer::projection_params
+    /// once before the first frame, so the crop stays where the startup camera put it
+    World(WorldRect),
+}
+
+impl FromStr for Crop {
+    type Err = CropError;
+
+    /// x,y,width,height in pixels, or world:min_x,min_y,max_x,max_y in world units
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn numbers<T: FromStr>(s: &str) -> Option<[T; 4]> {
+            let numbers: Vec<T> = s
+                .split(',')
+                .map(|number| number.trim().parse().ok())
+                .collect::<Option<_>>()?;
+            numbers.try_into().ok()
+        }
+        let syntax = || CropError::Syntax(s.to_owned());
+
+        match s.strip_prefix("world:") {
+            Some(rect) => {
+                let [min_x, min_y, max_x, max_y] = numbers(rect).ok_or_else(syntax)?;
+                Ok(Crop::World(WorldRect::new([min_x, min_y], [max_x, max_y])))
+            }
+            None => numbers(s).map(Crop::Pixels).ok_or_else(syntax),
+        }
+    }
+}
+
+impl Crop {
+    /// The rectangle x, y, width, height in pixels of a frame of size, clamped to the frame
+    /// projection maps world space to NDC, as in Renderer::projection_params
+    pub fn pixels(&self, size: [u32; 2], projection: Mat4) -> Result<[u32; 4], CropError> {
+        let [min, max] = match *self {
+            Crop::Pixels([x, y, width, height]) => {
+                [[x, y], [x.saturating_add(width), y.saturating_add(height)]]
+            }
+            Crop::World(rect) => {
+                // Pixel rows go down, NDC up
+                let pixel = |[x, y]: [f32; 2]| {
+                    let ndc_x = projection[0][0] * x + projection[1][0] * y + projection[3][0];
+                    let ndc_y = projection[0][1] * x + projection[1][1] * y + projection[3][1];
+                    [
+                        (ndc_x + 1.0) / 2.0 * size[0] as f32,
+                        (1.0 - ndc_y) / 2.0 * size[1] as f32,
+                    ]
+                };
+                let [a, b] = [pixel(rect.min), pixel(rect.max)];
+                let bound = |start: f32, end: f32, size: u32| {
+                    let clamp = |pixel: f32| pixel.clamp(0.0, size as f32) as u32;
+                    [clamp(start.min(end).floor()), clamp(start.max(end).ceil())]
+                };
+                let [min_x, max_x] = bound(a[0], b[0], size[0]);
+                let [min_y, max_y] = bound(a[1], b[1], size[1]);
+                [[min_x, min_y], [max_x, max_y]]
+            }
+        };
+
+        let min = [min[0].min(size[0]), min[1].min(size[1])];
+        let max = [max[0].min(size[0]), max[1].min(size[1])];
+        if max[0] <= min[0] || max[1] <= min[1] {
+            return Err(CropError::OutsideFrame(size[0], size[1]));
+        }
+        Ok([min[0], min[1], max[0] - min[0], max[1] - min[1]])
+    }
+}
+
+/// Shrink rect x, y, width, height to an even width and height
+/// Video is encoded as YUV 4:2:0, which halves the chroma planes in both directions.
+pub fn even([x, y, width, height]: [u32; 4]) -> Result<[u32; 4], CropError> {
+    if width < 2 || height < 2 {
+        return Err(CropError::TooSmall(width, height));
+    }
+    Ok([x, y, width & !1, height & !1])
+}
+
+/// Copy the pixels of rect x, y, width, height out of frame into out, 4 bytes per pixel
+/// Frame rows are frame_stride bytes apart, out rows out_stride
+pub fn copy_rect(
+    [x, y, width, height]: [u32; 4],
+    frame: &[u8],
+    frame_stride: usize,
+    out: &mut [u8],
+    out_stride: usize,
+) {
+    let row_len = width as usize * 4;
+    for row in 0..height as usize {
+        let src = (y as usize + row) * frame_stride + x as usize * 4;
+        let dst = row * out_stride;
+        out[dst..dst + row_len].copy_from_slice(&frame[src..src + row_len]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crops_parse_and_clamp_to_the_frame() {
+        let identity = Mat4::IDENTITY;
+        let size = [200, 100];
+
+        let crop: Crop = "150, 20, 100, 40".parse().unwrap();
+        assert_eq!(Crop::Pixels([150, 20, 100, 40]), crop);
+        assert_eq!(Ok([150, 20, 50, 40]), crop.pixels(size, identity));
+        assert_eq!(
+            Err(CropError::OutsideFrame(200, 100)),
+            Crop::Pixels([200, 0, 10, 10]).pixels(size, identity)
+        );
+        assert!("1,2,3".parse::<Crop>().is_err());
+
+        // The top right quarter of NDC
+        let crop: Crop = "world:0,0,1,1".parse().unwrap();
+        assert_eq!(Ok([100, 0, 100, 50]), crop.pixels(size, identity));
+
+        assert_eq!(Ok([150, 20, 50, 40]), even([150, 20, 51, 41]));
+        assert_eq!(Err(CropError::TooSmall(1, 40)), even([150, 20, 1, 40]));
+
+        let frame: Vec<u8> = (0..4 * 4 * 3).collect();
+        let mut out = vec![0; 2 * 4 * 2];
+        copy_rect([1, 1, 2, 2], &frame, 4 * 4, &mut out, 2 * 4);
+        assert_eq!(&frame[20..28], &out[..8]);
+        assert_eq!(&frame[36..44], &out[8..]);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use std::convert::{AsMut, AsRef};
 use std::fs::read_to_string;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -16,6 +16,20 @@ pub enum Input {
     CHECKBOX,
     #[serde(untagged)]
     GROUP(HashMap<String, Input>),
+    /// An input whose value is written to a registered uniform every frame,
+    /// e.g. `{ SLIDER = [0.0, 360.0], bind = "u_rotation", transform = "radians" }`
+    #[serde(untagged)]
+    BOUND(BoundInput),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoundInput {
+    #[serde(flatten)]
+    pub input: Box<Input>,
+    /// Name the uniform is registered under, see Renderer::register_uniform
+    pub bind: String,
+    #[serde(default)]
+    pub transform: Transform,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -70,6 +84,18 @@ pub struct InputBlock {
     pub name: Option<String>,
     #[serde(rename = "_size")]
     pub size: Option<[f32; 2]>,
+    /// Position of the window on its first run, later runs restore where the user left it
+    #[serde(rename = "_position")]
+    pub position: Option<[f32; 2]>,
+    /// Start with the window collapsed
+    #[serde(rename = "_collapsed", default)]
+    pub collapsed: bool,
+    /// Smoothing time constants in seconds of sliders in the block, see InputSmoothing
+    #[serde(rename = "_smoothing", default)]
+    pub smoothing: HashMap<String, f64>,
+    /// Generators driving inputs in the block, see InputModulation
+    #[serde(rename = "_modulation", default)]
+    pub modulation: HashMap<String, Modulation>,
     #[serde(flatten)]
     pub inputs: HashMap<String, Input>,
 }
@@ -79,6 +105,9 @@ pub struct InputMetadata {
     pub name: String,
     pub description: Option<String>,
     pub author: Option<String>,
+    /// Options of the display mode window, from [simulation.window]
+    #[serde(default)]
+    pub window: crate::display::WindowOptions,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -86,6 +115,9 @@ pub struct Inputs {
     pub simulation: InputMetadata,
     #[serde(rename = "block", default)]
     pub blocks: Vec<InputBlock>,
+    /// File the inputs were read from, reloaded by --watch
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 #[derive(Error, Debug)]
@@ -102,15 +134,32 @@ impl Inputs {
     }
 
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, InputsError> {
-        let data = read_to_string(path)?;
-        Self::new(data)
+        let data = read_to_string(path.as_ref())?;
+        Ok(Self::new(data)?.with_source(path.as_ref()))
+    }
+
+    /// Record the file the inputs were parsed from
+    pub fn with_source(mut self, path: impl Into<PathBuf>) -> Self {
+        self.source = Some(path.into());
+        self
     }
 }
 
+mod binding;
+#[cfg(not(target_arch = "wasm32"))]
+mod layout;
 #[cfg(not(target_arch = "wasm32"))]
 mod linux;
+mod modulation;
+mod smoothing;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
+pub(crate) use binding::BoundInputs;
+pub use binding::{InputBinding, Transform, UniformBindings};
+#[cfg(not(target_arch = "wasm32"))]
+pub use layout::{UiLayout, WindowLayout};
+pub use modulation::{InputModulation, Modulation, Modulator};
+pub use smoothing::InputSmoothing;
 
 #[cfg(test)]
 mod test {
@@ -136,8 +185,10 @@ mod test {
                     author: None,
                     name: "test".to_owned(),
                     description: None,
+                    window: Default::default(),
                 },
                 blocks: vec![],
+                source: None,
             },
             result
         );
@@ -159,8 +210,10 @@ mod test {
                     name: "test".to_owned(),
                     author: None,
                     description: Some("testing".to_owned()),
+                    window: Default::default(),
                 },
                 blocks: vec![],
+                source: None,
             },
             result
         );
@@ -187,6 +240,7 @@ mod test {
             name: "test".to_owned(),
             author: None,
             description: None,
+            window: Default::default(),
         };
 
         let inner_block_map: HashMap<String, Input> = [
@@ -211,6 +265,7 @@ mod test {
                     inputs: block_map,
                     ..Default::default()
                 }],
+                source: None,
             },
             result
         );
@@ -239,6 +294,7 @@ mod test {
             name: "test".to_owned(),
             description: None,
             author: None,
+            window: Default::default(),
         };
 
         let inner_block_map: HashMap<String, Input> = [
@@ -262,10 +318,42 @@ mod test {
                 blocks: vec![InputBlock {
                     name: Some("test block".to_owned()),
                     size: Some([400.0, 400.0]),
+                    position: None,
+                    collapsed: false,
+                    smoothing: HashMap::new(),
+                    modulation: HashMap::new(),
                     inputs: block_map
                 }],
+                source: None,
             },
             result
         );
     }
+
+    #[test]
+    fn window_options() {
+        let document = r#"
+            [simulation]
+            name = "test"
+
+            [simulation.window]
+            transparent = true
+            always_on_top = true
+            icon = "res/icon.png"
+            app_id = "org.example.test"
+        "#;
+
+        let result = Inputs::new(document).unwrap();
+
+        assert_eq!(
+            crate::display::WindowOptions {
+                transparent: true,
+                always_on_top: true,
+                click_through: false,
+                icon: Some(crate::display::WindowIcon::Path("res/icon.png".into())),
+                app_id: Some("org.example.test".to_owned()),
+            },
+            result.simulation.window
+        );
+    }
 }
@@ -0,0 +1,228 @@
+use super::{Input, InputValue, Inputs};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Conversion of a slider value before it is written to its bound uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Transform {
+    #[default]
+    Identity,
+    /// Degrees to radians
+    Radians,
+    /// Radians to degrees
+    Degrees,
+}
+
+impl Transform {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            Self::Identity => value,
+            Self::Radians => value.to_radians(),
+            Self::Degrees => value.to_degrees(),
+        }
+    }
+}
+
+/// An input declared with `bind = "uniform name"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputBinding {
+    /// Full name of the input, as in the input values
+    pub input: String,
+    pub uniform: String,
+    pub transform: Transform,
+}
+
+/// Encode an input value as written to a uniform: sliders as f32, checkboxes as u32
+fn encode(value: &InputValue, transform: Transform) -> [u8; 4] {
+    match *value {
+        InputValue::SLIDER(value) => (transform.apply(value) as f32).to_ne_bytes(),
+        InputValue::CHECKBOX(checked) => (checked as u32).to_ne_bytes(),
+    }
+}
+
+/// The latest values written to a uniform by UniformBindings, by byte offset in its buffer
+/// Shared with the uniform, which copies them into its own data so that its writes keep them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BoundInputs(Arc<Mutex<Vec<(usize, [u8; 4])>>>);
+
+impl BoundInputs {
+    fn set(&self, offset: usize, data: [u8; 4]) {
+        let mut values = self
+            .0
+            .lock()
+            .expect("aftgraphs::input::binding::BoundInputs::set: poisoned lock");
+        match values.iter_mut().find(|(bound, _)| *bound == offset) {
+            Some((_, value)) => *value = data,
+            None => values.push((offset, data)),
+        }
+    }
+
+    /// Copy the bound values into data, the bytes of the uniform
+    pub(crate) fn copy_into(&self, data: &mut [u8]) {
+        let values = self
+            .0
+            .lock()
+            .expect("aftgraphs::input::binding::BoundInputs::copy_into: poisoned lock");
+        for &(offset, value) in values.iter() {
+            if let Some(bytes) = data.get_mut(offset..offset + value.len()) {
+                bytes.copy_from_slice(&value);
+            }
+        }
+    }
+}
+
+/// A uniform registered for bindings: its buffer, the byte offset in it and its bound values
+#[derive(Debug)]
+struct Target {
+    buffer: Arc<wgpu::Buffer>,
+    offset: u64,
+    bound: BoundInputs,
+}
+
+impl Target {
+    fn write(&self, queue: &wgpu::Queue, data: [u8; 4]) {
+        self.bound.set(self.offset as usize, data);
+        queue.write_buffer(&self.buffer, self.offset, &data);
+    }
+}
+
+/// Input bindings and the uniforms registered for them,
+/// see Renderer::register_uniform
+#[derive(Debug, Default)]
+pub struct UniformBindings {
+    bindings: Vec<InputBinding>,
+    targets: HashMap<String, Target>,
+}
+
+impl UniformBindings {
+    pub fn new(inputs: &Inputs) -> Self {
+        fn collect(scope: &str, inputs: &HashMap<String, Input>, out: &mut Vec<InputBinding>) {
+            for (name, input) in inputs {
+                let name = format!("{scope}.{name}");
+                match input {
+                    Input::GROUP(inputs) => collect(&name, inputs, out),
+                    Input::BOUND(bound) => out.push(InputBinding {
+                        input: name,
+                        uniform: bound.bind.clone(),
+                        transform: bound.transform,
+                    }),
+                    _ => (),
+                }
+            }
+        }
+
+        let mut bindings = vec![];
+        for (idx, block) in inputs.blocks.iter().enumerate() {
+            let scope = block.name.clone().unwrap_or_else(|| idx.to_string());
+            collect(&scope, &block.inputs, &mut bindings);
+        }
+
+        Self {
+            bindings,
+            ..Default::default()
+        }
+    }
+
+    pub fn bindings(&self) -> &[InputBinding] {
+        &self.bindings
+    }
+
+    /// Write input values to uniform at offset in buffer from now on,
+    /// and to bound, the values the uniform keeps in its own data
+    pub(crate) fn register(
+        &mut self,
+        uniform: &str,
+        buffer: Arc<wgpu::Buffer>,
+        offset: u64,
+        bound: BoundInputs,
+    ) {
+        let target = Target {
+            buffer,
+            offset,
+            bound,
+        };
+        self.targets.insert(uniform.to_owned(), target);
+    }
+
+    /// Write value to the uniform registered under name, returns false if there is none
+    pub fn write(&self, queue: &wgpu::Queue, uniform: &str, value: f32) -> bool {
+        let Some(target) = self.targets.get(uniform) else {
+            return false;
+        };
+        target.write(queue, value.to_ne_bytes());
+        true
+    }
+
+    /// Write the bound inputs to their uniforms, returns the bytes written
+    /// Every value is written on every call, the simulation may have written over it since.
+    pub fn apply(&self, queue: &wgpu::Queue, values: &HashMap<String, InputValue>) -> usize {
+        let mut bytes = 0;
+        for binding in &self.bindings {
+            let (Some(value), Some(target)) = (
+                values.get(&binding.input),
+                self.targets.get(&binding.uniform),
+            ) else {
+                continue;
+            };
+
+            let data = encode(value, binding.transform);
+            target.write(queue, data);
+            bytes += data.len();
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bound_inputs_are_collected() {
+        let inputs = Inputs::new(
+            r#"
+            [simulation]
+            name = "test"
+
+            [[block]]
+            _name = "triangle"
+            rotation = { SLIDER = [0.0, 360.0], bind = "u_rotation", transform = "radians" }
+            color = { SLIDER = [0.0, 1.0] }
+            "#,
+        )
+        .unwrap();
+
+        let bindings = UniformBindings::new(&inputs);
+        assert_eq!(
+            [InputBinding {
+                input: "triangle.rotation".to_owned(),
+                uniform: "u_rotation".to_owned(),
+                transform: Transform::Radians,
+            }],
+            bindings.bindings()
+        );
+        assert_eq!(
+            (180f64.to_radians() as f32).to_ne_bytes(),
+            encode(&InputValue::SLIDER(180.0), Transform::Radians)
+        );
+    }
+
+    #[test]
+    fn bound_values_are_copied_into_uniform_data() {
+        let bound = BoundInputs::default();
+        bound.set(4, 1.5f32.to_ne_bytes());
+        bound.set(0, 2u32.to_ne_bytes());
+        bound.set(4, 2.5f32.to_ne_bytes());
+        // Out of range values are skipped
+        bound.set(8, [1; 4]);
+
+        let mut data = [0; 8];
+        bound.copy_into(&mut data);
+        assert_eq!(2u32.to_ne_bytes(), data[..4]);
+        assert_eq!(2.5f32.to_ne_bytes(), data[4..]);
+    }
+}
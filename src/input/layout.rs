@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Where the user left an input block window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub struct WindowLayout {
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// Input block windows moved by the user, by window title.
+/// Saved to `$XDG_CONFIG_HOME/aftgraphs/<simulation>.layout.toml` and restored
+/// over the _position and _collapsed of the block on the next run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UiLayout {
+    #[serde(flatten)]
+    windows: HashMap<String, WindowLayout>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl UiLayout {
+    /// Layout file of the simulation, None without a home directory
+    pub fn path(simulation: &str) -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+        let name: String = simulation
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        Some(config.join("aftgraphs").join(format!("{name}.layout.toml")))
+    }
+
+    /// The saved layout of the simulation, empty if there is none
+    pub fn load(simulation: &str) -> Self {
+        let Some(path) = Self::path(simulation) else {
+            return Self::default();
+        };
+        let mut layout = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                log::warn!(
+                    "aftgraphs::input::UiLayout::load: ignoring {}: {e}",
+                    path.display()
+                );
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        layout.path = Some(path);
+        layout
+    }
+
+    pub fn get(&self, window: &str) -> Option<&WindowLayout> {
+        self.windows.get(window)
+    }
+
+    /// Record the layout of window as drawn this frame
+    pub fn update(&mut self, window: &str, layout: WindowLayout) {
+        if self.windows.get(window) != Some(&layout) {
+            self.windows.insert(window.to_owned(), layout);
+            self.dirty = true;
+        }
+    }
+
+    /// Write the layout back if it changed since it was loaded or last saved
+    pub fn save(&mut self) {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else {
+            return;
+        };
+        self.dirty = false;
+
+        let result = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(path, contents).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!(
+                "aftgraphs::input::UiLayout::save: failed to save {}: {e}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_changes_mark_the_layout_for_saving() {
+        let mut layout: UiLayout = toml::from_str(
+            r#"
+            "Input block 0" = { position = [10.0, 20.0] }
+            "#,
+        )
+        .unwrap();
+        let window = WindowLayout {
+            position: [10.0, 20.0],
+            collapsed: false,
+        };
+        assert_eq!(Some(&window), layout.get("Input block 0"));
+
+        layout.update("Input block 0", window);
+        assert!(!layout.dirty);
+        layout.update(
+            "Input block 0",
+            WindowLayout {
+                collapsed: true,
+                ..window
+            },
+        );
+        assert!(layout.dirty);
+    }
+}
@@ -53,6 +53,9 @@ impl Inputs {
                     }
                 }
             }
+            Input::BOUND(bound) => {
+                Self::render_input(ui, (name, &bound.input), scope, map)?;
+            }
             Input::GROUP(inputs) => {
                 let scope = input_name;
 
@@ -71,7 +74,8 @@ impl Inputs {
         Some(())
     }
 
-    pub async fn render(&self, ui: &mut imgui::Ui, values: InputState) {
+    /// Draw a window per block, restoring and recording window positions in layout
+    pub async fn render(&self, ui: &mut imgui::Ui, values: InputState, layout: &mut UiLayout) {
         let mut values = values.lock().await;
 
         for (idx, block) in self.blocks.iter().enumerate() {
@@ -91,6 +95,12 @@ impl Inputs {
             if let Some(size) = block.size {
                 ui_window = ui_window.size(size, Condition::Always);
             }
+            let saved = layout.get(window_title).copied();
+            if let Some(position) = saved.map(|saved| saved.position).or(block.position) {
+                ui_window = ui_window.position(position, Condition::FirstUseEver);
+            }
+            let collapsed = saved.map_or(block.collapsed, |saved| saved.collapsed);
+            ui_window = ui_window.collapsed(collapsed, Condition::FirstUseEver);
 
             let mut run = false;
             ui_window = ui_window.opened(&mut run).movable(true).resizable(true);
@@ -102,13 +112,51 @@ impl Inputs {
                 .collect();
             inputs.sort_by_key(|&(name, _)| name);
 
-            ui_window.build(|| {
+            let drawn = ui_window.build(|| {
                 for input in inputs {
                     if Self::render_input(ui, input, scope.as_str(), values.as_mut()).is_none() {
                         log::error!("aftgraphs::input::render failed to render inputs");
                     }
                 }
+                ui.window_pos()
             });
+
+            // Collapsed windows are not built, they keep their last position
+            let window_layout = match drawn {
+                Some(position) => WindowLayout {
+                    position,
+                    collapsed: false,
+                },
+                None => WindowLayout {
+                    collapsed: true,
+                    ..saved.unwrap_or_default()
+                },
+            };
+            layout.update(window_title, window_layout);
         }
     }
 }
+
+impl InputMetadata {
+    /// Draw the name, description and author of the simulation, centered in the window
+    pub fn render_about(&self, ui: &Ui) {
+        let [width, height] = ui.io().display_size;
+        ui.window("About")
+            .position([width / 2.0, height / 2.0], Condition::Appearing)
+            .position_pivot([0.5, 0.5])
+            .size([width / 3.0, 0.0], Condition::Appearing)
+            .always_auto_resize(true)
+            .build(|| {
+                ui.text(&self.name);
+                if let Some(ref author) = self.author {
+                    ui.text_disabled(format!("by {author}"));
+                }
+                if let Some(ref description) = self.description {
+                    ui.separator();
+                    ui.text_wrapped(description);
+                }
+                ui.separator();
+                ui.text_disabled(concat!("aftgraphs ", env!("CARGO_PKG_VERSION")));
+            });
+    }
+}
@@ -0,0 +1,148 @@
+use super::{InputValue, Inputs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+/// Shape of a time-based generator, periods are in simulated seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum Modulator {
+    /// Sine LFO starting at its midpoint
+    Sine {
+        period: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Rises and falls linearly once per period
+    Triangle {
+        period: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Rises linearly, then jumps back at the end of each period
+    Ramp {
+        period: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Steps through values, each held for period, looping
+    Steps { period: f64, values: Vec<f64> },
+}
+
+impl Modulator {
+    /// Value at time, in [0, 1] except for Modulator::Steps
+    pub fn sample(&self, time: f64) -> f64 {
+        let cycle = |period: f64, phase: f64| (time / period + phase).rem_euclid(1.0);
+        match *self {
+            Self::Sine { period, phase } => 0.5 + 0.5 * (cycle(period, phase) * TAU).sin(),
+            Self::Triangle { period, phase } => 1.0 - (2.0 * cycle(period, phase) - 1.0).abs(),
+            Self::Ramp { period, phase } => cycle(period, phase),
+            Self::Steps { period, ref values } => {
+                if values.is_empty() {
+                    return 0.0;
+                }
+                let step = (time / period).floor().rem_euclid(values.len() as f64);
+                values[step as usize]
+            }
+        }
+    }
+}
+
+/// A Modulator driving an input between min and max.
+/// Declared per block with `_modulation = { name = { shape = "sine", period = 4.0, max = 360.0 } }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Modulation {
+    #[serde(flatten)]
+    pub modulator: Modulator,
+    #[serde(default)]
+    pub min: f64,
+    #[serde(default = "max_default")]
+    pub max: f64,
+}
+
+fn max_default() -> f64 {
+    1.0
+}
+
+impl Modulation {
+    /// Value at time, Modulator::Steps values are used as is
+    pub fn sample(&self, time: f64) -> f64 {
+        let value = self.modulator.sample(time);
+        match self.modulator {
+            Modulator::Steps { .. } => value,
+            _ => self.min + (self.max - self.min) * value,
+        }
+    }
+}
+
+/// Inputs driven by modulations instead of the ui
+#[derive(Debug, Clone, Default)]
+pub struct InputModulation {
+    modulations: HashMap<String, Modulation>,
+}
+
+impl InputModulation {
+    pub fn new(inputs: &Inputs) -> Self {
+        let modulations = inputs
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, block)| {
+                let scope = block.name.clone().unwrap_or_else(|| idx.to_string());
+                block
+                    .modulation
+                    .iter()
+                    .map(move |(name, modulation)| (format!("{scope}.{name}"), modulation.clone()))
+            })
+            .collect();
+
+        Self { modulations }
+    }
+
+    /// Drive the input with the full name input, or stop driving it with None
+    pub fn set(&mut self, input: &str, modulation: Option<Modulation>) {
+        match modulation {
+            Some(modulation) => self.modulations.insert(input.to_owned(), modulation),
+            None => self.modulations.remove(input),
+        };
+    }
+
+    /// Write every modulated input at simulated time, checkboxes are set above one half
+    pub fn apply(&self, values: &mut HashMap<String, InputValue>, time: f64) {
+        for (name, modulation) in &self.modulations {
+            let sample = modulation.sample(time);
+            let value = match values.get(name) {
+                Some(InputValue::CHECKBOX(_)) => InputValue::CHECKBOX(sample > 0.5),
+                _ => InputValue::SLIDER(sample),
+            };
+            values.insert(name.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modulations_sample_their_shape() {
+        let inputs = Inputs::new(
+            r#"
+            [simulation]
+            name = "test"
+
+            [[block]]
+            _name = "demo"
+            _modulation = { angle = { shape = "triangle", period = 4.0, max = 360.0 }, pattern = { shape = "steps", period = 1.0, values = [1.0, 5.0] } }
+            angle = { SLIDER = [0.0, 360.0] }
+            pattern = { SLIDER = [0.0, 5.0] }
+            "#,
+        )
+        .unwrap();
+
+        let mut values = HashMap::new();
+        InputModulation::new(&inputs).apply(&mut values, 1.0);
+        assert_eq!(Some(&InputValue::SLIDER(180.0)), values.get("demo.angle"));
+        assert_eq!(Some(&InputValue::SLIDER(5.0)), values.get("demo.pattern"));
+    }
+}
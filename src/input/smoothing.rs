@@ -0,0 +1,103 @@
+use super::{InputValue, Inputs};
+use std::collections::HashMap;
+
+/// Exponential moving average of slider values, configured per block with
+/// `_smoothing = { name = time constant in seconds }`.
+/// The values set by the ui or headless events are the targets the smoothed values decay to,
+/// only the copy of the values handed to the simulation is smoothed.
+#[derive(Debug, Clone, Default)]
+pub struct InputSmoothing {
+    time_constants: HashMap<String, f64>,
+    /// Smoothed value of each input
+    smoothed: HashMap<String, f64>,
+}
+
+impl InputSmoothing {
+    pub fn new(inputs: &Inputs) -> Self {
+        let time_constants = inputs
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, block)| {
+                let scope = block.name.clone().unwrap_or_else(|| idx.to_string());
+                block
+                    .smoothing
+                    .iter()
+                    .map(move |(name, &tau)| (format!("{scope}.{name}"), tau))
+            })
+            .collect();
+
+        Self {
+            time_constants,
+            smoothed: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time_constants.is_empty()
+    }
+
+    /// Move every smoothed slider towards its value in values by delta_time seconds,
+    /// replacing the value with the smoothed one. Pass a copy of the values the ui shows.
+    pub fn apply(&mut self, values: &mut HashMap<String, InputValue>, delta_time: f64) {
+        for (name, &tau) in &self.time_constants {
+            let Some(InputValue::SLIDER(value)) = values.get_mut(name) else {
+                continue;
+            };
+            let Some(smoothed) = self.smoothed.get_mut(name) else {
+                self.smoothed.insert(name.clone(), *value);
+                continue;
+            };
+
+            let alpha = if tau > 0.0 {
+                1.0 - (-delta_time / tau).exp()
+            } else {
+                1.0
+            };
+            *smoothed += (*value - *smoothed) * alpha;
+            *value = *smoothed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn smoothed_slider_decays_to_target() {
+        let inputs = Inputs::new(
+            r#"
+            [simulation]
+            name = "test"
+
+            [[block]]
+            _name = "controls"
+            _smoothing = { speed = 1.0 }
+            speed = { SLIDER = [0.0, 1.0] }
+            "#,
+        )
+        .unwrap();
+        let mut smoothing = InputSmoothing::new(&inputs);
+        let mut values = HashMap::new();
+        values.insert("controls.speed".to_owned(), InputValue::SLIDER(0.0));
+        smoothing.apply(&mut values, 0.0);
+
+        let slider = |values: &HashMap<String, InputValue>| match values.get("controls.speed") {
+            Some(&InputValue::SLIDER(value)) => value,
+            _ => unreachable!(),
+        };
+
+        // The ui's values stay the targets, each frame smooths a fresh copy
+        let mut raw = values.clone();
+        raw.insert("controls.speed".to_owned(), InputValue::SLIDER(1.0));
+        let mut values = raw.clone();
+        smoothing.apply(&mut values, 1.0);
+        assert!((slider(&values) - (1.0 - (-1.0f64).exp())).abs() < 1e-9);
+        assert_eq!(1.0, slider(&raw));
+
+        let mut values = raw.clone();
+        smoothing.apply(&mut values, 100.0);
+        assert!((slider(&values) - 1.0).abs() < 1e-9);
+    }
+}
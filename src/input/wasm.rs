@@ -2,7 +2,7 @@ use super::*;
 use crate::ui::{Ui, UiFrame};
 use lazy_static::lazy_static;
 use std::collections::hash_map::Entry;
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
     self, Element, HtmlFieldSetElement, HtmlFormElement, HtmlInputElement, HtmlLabelElement,
     HtmlLegendElement, Node,
@@ -12,66 +12,125 @@ lazy_static! {
     static ref INPUT_STATE: Mutex<HashMap<String, InputValue>> = Mutex::new(HashMap::new());
 }
 
+/// Visible keyboard focus for the generated inputs, the high-contrast Palette
+/// and no transitions with prefers-reduced-motion
+const INPUT_STYLE: &str = "
+.aftgraphs-inputs input:focus-visible {
+  outline: 3px solid #1a73e8;
+  outline-offset: 2px;
+}
+.aftgraphs-inputs output {
+  margin-left: 0.5em;
+  font-variant-numeric: tabular-nums;
+}
+[data-palette=\"high-contrast\"] .aftgraphs-inputs {
+  background: #000;
+  color: #fff;
+  accent-color: #ffd900;
+}
+[data-palette=\"high-contrast\"] .aftgraphs-inputs fieldset {
+  border: 2px solid #fff;
+}
+[data-palette=\"high-contrast\"] .aftgraphs-inputs input:focus-visible {
+  outline-color: #ffd900;
+}
+@media (prefers-reduced-motion: reduce) {
+  .aftgraphs-inputs * {
+    transition: none !important;
+    animation: none !important;
+  }
+}
+";
+
+/// Slider values with at most 3 decimals, without trailing zeros
+fn format_value(value: f64) -> String {
+    let formatted = format!("{value:.3}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+fn set_slider_value_text(
+    slider: &HtmlInputElement,
+    output: &Element,
+    value: f64,
+) -> Result<(), JsValue> {
+    let text = format_value(value);
+    slider.set_attribute("aria-valuetext", &text)?;
+    output.set_text_content(Some(&text));
+    Ok(())
+}
+
 impl Inputs {
-    fn create_input((name, input): (&str, &Input), scope: &str, ui: &mut Ui) -> Element {
+    fn create_input(
+        (name, input): (&str, &Input),
+        scope: &str,
+        ui: &mut Ui,
+    ) -> Result<Element, JsValue> {
         let input_name = format!("{}-{}", scope, name);
         let sanitized_name = input_name.replace(' ', "_");
 
-        match input {
+        Ok(match input {
             Input::CHECKBOX => {
-                let label_elem = ui.document.create_element("label").unwrap();
-                let label_elem: HtmlLabelElement = label_elem.dyn_into().unwrap();
+                let label_elem = ui.document.create_element("label")?;
+                let label_elem: HtmlLabelElement = label_elem.dyn_into()?;
                 label_elem.set_html_for(sanitized_name.as_str());
                 label_elem.set_inner_text(name);
 
-                let input_elem = ui.document.create_element("input").unwrap();
-                let input_elem: HtmlInputElement = input_elem.dyn_into().unwrap();
+                let input_elem = ui.document.create_element("input")?;
+                let input_elem: HtmlInputElement = input_elem.dyn_into()?;
                 input_elem.set_id(sanitized_name.as_str());
                 input_elem.set_type("checkbox");
 
-                let div = ui.document.create_element("div").unwrap();
+                let div = ui.document.create_element("div")?;
                 div.set_class_name("inputset");
 
-                div.append_child(&input_elem).unwrap();
-                div.append_child(&label_elem).unwrap();
-                div.append_child(&ui.document.create_element("br").unwrap())
-                    .unwrap();
+                div.append_child(&input_elem)?;
+                div.append_child(&label_elem)?;
+                div.append_child(&ui.document.create_element("br")?)?;
 
                 div
             }
             Input::SLIDER(lower, upper, step) => {
-                let label_elem = ui.document.create_element("label").unwrap();
-                let label_elem: HtmlLabelElement = label_elem.dyn_into().unwrap();
+                let label_elem = ui.document.create_element("label")?;
+                let label_elem: HtmlLabelElement = label_elem.dyn_into()?;
                 label_elem.set_html_for(sanitized_name.as_str());
                 label_elem.set_inner_text(name);
 
-                let input_elem = ui.document.create_element("input").unwrap();
-                let input_elem: HtmlInputElement = input_elem.dyn_into().unwrap();
+                let input_elem = ui.document.create_element("input")?;
+                let input_elem: HtmlInputElement = input_elem.dyn_into()?;
                 input_elem.set_id(sanitized_name.as_str());
                 input_elem.set_type("range");
-                input_elem
-                    .set_attribute("min", &ToString::to_string(&lower))
-                    .unwrap();
-                input_elem
-                    .set_attribute("max", &ToString::to_string(&upper))
-                    .unwrap();
+                input_elem.set_attribute("min", &ToString::to_string(&lower))?;
+                input_elem.set_attribute("max", &ToString::to_string(&upper))?;
                 input_elem.set_value_as_number(*lower);
                 if let Some(step) = step {
-                    input_elem.set_attribute("step", &step.to_string()).unwrap();
+                    input_elem.set_attribute("step", &step.to_string())?;
                 } else {
-                    input_elem.set_attribute("step", "any").unwrap();
+                    input_elem.set_attribute("step", "any")?;
                 }
 
-                let div = ui.document.create_element("div").unwrap();
+                // Screen readers announce the value shown next to the slider as it changes
+                let output_name = format!("{sanitized_name}-value");
+                let output_elem = ui.document.create_element("output")?;
+                output_elem.set_id(output_name.as_str());
+                output_elem.set_attribute("for", sanitized_name.as_str())?;
+                output_elem.set_attribute("aria-live", "polite")?;
+                input_elem.set_attribute("aria-describedby", output_name.as_str())?;
+                set_slider_value_text(&input_elem, &output_elem, *lower)?;
+
+                let div = ui.document.create_element("div")?;
                 div.set_class_name("inputset");
 
-                div.append_child(&input_elem).unwrap();
-                div.append_child(&label_elem).unwrap();
-                div.append_child(&ui.document.create_element("br").unwrap())
-                    .unwrap();
+                div.append_child(&input_elem)?;
+                div.append_child(&label_elem)?;
+                div.append_child(&output_elem)?;
+                div.append_child(&ui.document.create_element("br")?)?;
 
                 div
             }
+            Input::BOUND(bound) => Self::create_input((name, &bound.input), scope, ui)?,
             Input::GROUP(inputs) => {
                 let scope = sanitized_name;
 
@@ -81,29 +140,35 @@ impl Inputs {
                     .collect();
                 inputs.sort_by_key(|&(name, _)| name);
 
-                let fieldset_elem = ui.document.create_element("fieldset").unwrap();
-                let fieldset_elem: HtmlFieldSetElement = fieldset_elem.dyn_into().unwrap();
+                let fieldset_elem = ui.document.create_element("fieldset")?;
+                let fieldset_elem: HtmlFieldSetElement = fieldset_elem.dyn_into()?;
                 fieldset_elem.set_id(scope.as_str());
                 fieldset_elem.set_name(scope.as_str());
 
-                let legend_elem = ui.document.create_element("legend").unwrap();
-                let legend_elem: HtmlLegendElement = legend_elem.dyn_into().unwrap();
+                let legend_elem = ui.document.create_element("legend")?;
+                let legend_elem: HtmlLegendElement = legend_elem.dyn_into()?;
                 legend_elem.set_inner_text(name);
-                fieldset_elem.append_child(&legend_elem).unwrap();
+                fieldset_elem.append_child(&legend_elem)?;
 
                 for input in inputs {
-                    let child = Self::create_input(input, scope.as_str(), ui);
-                    fieldset_elem.append_child(&child).unwrap();
+                    let child = Self::create_input(input, scope.as_str(), ui)?;
+                    fieldset_elem.append_child(&child)?;
                 }
 
-                fieldset_elem.dyn_into().unwrap()
+                fieldset_elem.dyn_into()?
             }
-        }
+        })
     }
 
-    fn create_inputs(&self, ui: &mut Ui) {
-        let form_elem = ui.document.create_element("form").unwrap();
-        let form_elem: HtmlFormElement = form_elem.dyn_into().unwrap();
+    fn create_inputs(&self, ui: &mut Ui) -> Result<(), JsValue> {
+        let style_elem = ui.document.create_element("style")?;
+        style_elem.set_text_content(Some(INPUT_STYLE));
+        ui.body.append_child(&style_elem)?;
+
+        let form_elem = ui.document.create_element("form")?;
+        let form_elem: HtmlFormElement = form_elem.dyn_into()?;
+        form_elem.set_class_name("aftgraphs-inputs");
+        form_elem.set_attribute("aria-label", "Simulation inputs")?;
 
         for (idx, block) in self.blocks.iter().enumerate() {
             let default_block_title = format!("Input block {}", idx);
@@ -119,14 +184,14 @@ impl Inputs {
             };
             let scope = scope.replace(' ', "_");
 
-            let block_fieldset = ui.document.create_element("fieldset").unwrap();
-            let block_fieldset: HtmlFieldSetElement = block_fieldset.dyn_into().unwrap();
+            let block_fieldset = ui.document.create_element("fieldset")?;
+            let block_fieldset: HtmlFieldSetElement = block_fieldset.dyn_into()?;
             block_fieldset.set_id(scope.as_str());
 
-            let block_legend = ui.document.create_element("legend").unwrap();
-            let block_legend: HtmlLegendElement = block_legend.dyn_into().unwrap();
+            let block_legend = ui.document.create_element("legend")?;
+            let block_legend: HtmlLegendElement = block_legend.dyn_into()?;
             block_legend.set_inner_text(block_title);
-            block_fieldset.append_child(&block_legend).unwrap();
+            block_fieldset.append_child(&block_legend)?;
 
             let mut inputs: Vec<_> = block
                 .inputs
@@ -136,20 +201,18 @@ impl Inputs {
             inputs.sort_by_key(|&(name, _)| name);
 
             for input in inputs {
-                let child = Self::create_input(input, scope.as_ref(), ui);
-                block_fieldset.append_child(&child).unwrap();
+                let child = Self::create_input(input, scope.as_ref(), ui)?;
+                block_fieldset.append_child(&child)?;
             }
 
-            form_elem.append_child(&block_fieldset).unwrap();
+            form_elem.append_child(&block_fieldset)?;
         }
 
-        let canvas_list = ui.document.get_elements_by_name("canvas");
+        // Inputs come before the canvas in the keyboard navigation order
+        let canvas = ui.document.get_element_by_id(crate::CANVAS_ID);
         let body_node: &Node = &ui.body;
-        body_node
-            .insert_before(&form_elem, canvas_list.get(0).as_ref())
-            .unwrap();
-
-        ui.input_forms_created = true;
+        body_node.insert_before(&form_elem, canvas.as_deref())?;
+        Ok(())
     }
 
     pub fn get_input(
@@ -222,6 +285,9 @@ impl Inputs {
 
                 let val = range.value_as_number();
                 let key = sanitized_name.replace('_', " ").replace('-', ".");
+                let output = ui
+                    .document
+                    .get_element_by_id(&format!("{sanitized_name}-value"));
 
                 let old_entry = old_state.entry(key.clone());
                 let state_val = state.insert(key.clone(), InputValue::SLIDER(val));
@@ -230,15 +296,26 @@ impl Inputs {
                         Entry::Occupied(old_entry) => {
                             if *old_entry.get() != InputValue::SLIDER(state_val) {
                                 range.set_value_as_number(state_val);
-                                state.insert(key, InputValue::SLIDER(state_val));
+                                state.insert(key.clone(), InputValue::SLIDER(state_val));
                             }
                         }
                         Entry::Vacant(_) => {
                             range.set_value_as_number(state_val);
-                            state.insert(key, InputValue::SLIDER(state_val));
+                            state.insert(key.clone(), InputValue::SLIDER(state_val));
                         }
                     }
                 }
+
+                if let (Some(output), Some(&InputValue::SLIDER(val))) = (output, state.get(&key)) {
+                    if output.text_content().as_deref() != Some(format_value(val).as_str()) {
+                        if let Err(e) = set_slider_value_text(&range, &output, val) {
+                            log::warn!("aftgraphs::input::Inputs::get_input: failed to describe {key}: {e:?}");
+                        }
+                    }
+                }
+            }
+            Input::BOUND(bound) => {
+                Self::get_input((name, &bound.input), scope, ui, state, old_state);
             }
             Input::GROUP(inputs) => {
                 let scope = sanitized_name;
@@ -271,7 +348,13 @@ impl Inputs {
 
     pub async fn render<'a>(&'a self, ui: UiFrame<'a>, state: InputState) {
         if !ui.input_forms_created {
-            self.create_inputs(ui);
+            if let Err(e) = self.create_inputs(ui) {
+                crate::error::report(
+                    "aftgraphs::input::Inputs::render",
+                    format!("failed to create the input forms: {e:?}"),
+                );
+            }
+            ui.input_forms_created = true;
         }
 
         let mut values = state.lock().await;
@@ -1,16 +1,55 @@
 use thiserror::Error;
 
+pub mod animate;
 mod app;
+pub mod asset;
+pub mod audio;
+#[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+pub mod capture;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compare;
+pub mod coords;
+pub mod data;
 pub mod display;
+pub mod dynamics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod embed;
+pub mod error;
+pub mod field;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod headless;
 pub mod input;
+pub mod marker;
+mod parallel;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
+pub mod png;
 pub mod primitives;
+#[cfg(all(feature = "profile-with-puffin", not(target_arch = "wasm32")))]
+pub mod profiler;
+pub mod rand;
 pub mod render;
+mod replay;
+pub mod script;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod share;
 pub mod simulation;
+pub mod snapshot;
+pub mod spatial;
+pub mod stereo;
+pub mod storage;
+pub mod stream;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod texture;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timeline;
 pub mod ui;
 pub mod uniform;
+pub mod units;
 pub mod vertex;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
 
 #[derive(Clone, Debug, Error)]
 pub enum GraphicsInitError {
@@ -30,16 +69,44 @@ pub(crate) use crate::app::App;
 mod cli;
 
 pub mod prelude {
+    pub use crate::animate::{CriticallyDamped, Easing, Spring, Tween};
+    pub use crate::asset::{AssetHandle, AssetLoader};
+    pub use crate::audio::{AudioAnalysis, AudioInput};
+    pub use crate::coords::ScreenSpace;
+    pub use crate::error::{set_error_policy, ErrorPolicy, LibraryError};
+    pub use crate::field::{Colormap, ScalarField, ScalarFieldBuilder};
     pub use crate::input::{InputState, InputValue};
+    pub use crate::marker::{Marker, MarkerBuffer, MarkerShape, MarkerSizing};
+    pub use crate::rand::{RandomStream, RngCore, SeedableRng};
     pub use crate::render::{
-        BindGroupLayoutBuilder, RenderPass, RenderPipeline, RenderPipelineBuilder, Renderer,
-        ShaderBuilder, BINDING_UNIFORM_BUFFER,
+        AspectPolicy, AutoExposure, BackgroundFit, BindGroupBuilder, BindGroupLayout,
+        BindGroupLayoutBuilder, BlendMode, BloomSettings, BufferHandle, Clock, ComputePass,
+        ComputePipeline, ComputePipelineBuilder, CubeFace, CursorStyle, Frame, Layer, Lut3d,
+        Palette, PostEffect, ProjectionParams, Reduction, RenderGraph, RenderPass, RenderPipeline,
+        RenderPipelineBuilder, Renderer, RendererStats, ShaderBuilder, ShaderReflection,
+        TextureHandle, TransientTexture, ViewParams, Warmup, WorldRect,
+        BINDING_READ_ONLY_STORAGE_BUFFER, BINDING_STORAGE_BUFFER, BINDING_UNIFORM_BUFFER,
     };
+    pub use crate::script::{Script, ScriptDraw};
     pub use crate::simulation::{
-        ElementState, InputEvent, MouseButton, RawKeyEvent, Simulation, SimulationContext,
+        CompositeSimulation, Config, ConfigValue, ElementState, InputEvent, KeyCode, MouseButton,
+        PhysicalKey, RawKeyEvent, SerializableSimulation, Simulation, SimulationContext,
+        SimulationSet, TimedInput, Viewport,
     };
+    pub use crate::snapshot::Snapshot;
+    pub use crate::spatial::{GpuSpatialHash, SpatialHash};
+    pub use crate::stereo::{Eye, StereoCamera, StereoTarget};
+    pub use crate::storage::{StorageBuffer, StorageBufferBuilder};
+    pub use crate::stream::{DataSource, DataStream};
+    pub use crate::texture::{RenderTarget, Sampler, Texture, TextureBuilder};
+    #[cfg(any(feature = "ffmpeg", target_arch = "wasm32"))]
+    pub use crate::texture::{VideoError, VideoTexture};
     pub use crate::ui::{Ui, UiFrame, UiPlatform};
-    pub use crate::uniform::{Uniform, UniformBuilder};
+    pub use crate::uniform::{
+        Color, Float, Mat4, Uniform, UniformBuilder, UniformField, UniformSet, UniformSetBuilder,
+        Vec2, Vec3, Vec4,
+    };
+    pub use crate::units::WorldScale;
     pub use crate::vertex::{
         IndexBuffer, InstanceBuffer, InstanceBufferBuilder, VertexBuffer, VertexBufferBuilder,
         PRIMITIVE_POINTS,
@@ -64,3 +131,7 @@ mod wasm;
 pub use linux::*;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
+
+/// For Simulation::args
+#[cfg(not(target_arch = "wasm32"))]
+pub use clap;
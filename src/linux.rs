@@ -7,7 +7,10 @@ use async_std::{
     future::{pending, timeout},
     sync::Mutex,
 };
-use std::{fs::File, future::Future, io::read_to_string, sync::Arc, time::Duration};
+use std::{
+    ffi::OsString, fs::File, future::Future, io::read_to_string, path::PathBuf, sync::Arc,
+    time::Duration,
+};
 
 fn init_platform() {
     env_logger::init();
@@ -31,26 +34,43 @@ pub async fn spawn(f: impl FnOnce() + Send + 'static) -> Result<Handle, SpawnErr
 }
 
 pub fn sim_main<T: Simulation>(inputs: Inputs) {
+    sim_main_with_args::<T>(inputs, std::env::args_os());
+}
+
+/// sim_main with the command line arguments given, the first being the program name
+pub fn sim_main_with_args<T: Simulation>(inputs: Inputs, args: impl IntoIterator<Item = OsString>) {
     init_platform();
+    #[cfg(feature = "profile-with-puffin")]
+    crate::profiler::init();
+    #[cfg(feature = "renderdoc")]
+    crate::capture::init();
 
     parse_cli(
+        args,
         inputs.simulation.name.as_str(),
         inputs.simulation.description.as_deref(),
         inputs.simulation.author.as_deref(),
+        T::args(),
     );
 
     block_on(async move {
         log::debug!("aftgraphs::sim_main: running simulation");
-        let is_headless = {
+        let (is_headless, tile, cubemap) = {
             let args = ARGUMENTS.read().await;
-            args.headless.clone().map(|args| (args.in_file, args.size))
+            (
+                args.headless.clone().map(|args| (args.in_file, args.size)),
+                args.tile,
+                args.cubemap,
+            )
         };
         if let Some((in_file, arg_size)) = is_headless {
-            let input_file = File::open(in_file).expect("Failed to open headless input file");
-            let input_file =
-                read_to_string(input_file).expect("Failed to read headless input file");
-            let headless_input: HeadlessInput = toml::from_str(input_file.as_str())
-                .expect("Failed to parse headless input file TOML");
+            let headless_input = match read_headless_input(in_file) {
+                Ok(headless_input) => headless_input,
+                Err(e) => {
+                    crate::error::report("aftgraphs::sim_main", e);
+                    return;
+                }
+            };
 
             let mut size = (
                 arg_size.0.unwrap_or_else(|| {
@@ -72,20 +92,69 @@ pub fn sim_main<T: Simulation>(inputs: Inputs) {
             size.0 = size.0.max(4);
             size.1 = size.1.max(4);
 
+            if headless_input.ensemble.is_some() {
+                if let Err(e) = SimulationContext::<T, _>::new_headless(size)
+                    .run_ensemble(inputs, headless_input)
+                    .await
+                {
+                    crate::error::report(
+                        "aftgraphs::sim_main",
+                        format!("ensemble rendering failed: {e}"),
+                    );
+                }
+                return;
+            }
+
+            if let Some(tile) = tile {
+                if let Err(e) = SimulationContext::<T, _>::new_headless(size)
+                    .run_tiled(inputs, headless_input, tile)
+                    .await
+                {
+                    crate::error::report(
+                        "aftgraphs::sim_main",
+                        format!("tiled rendering failed: {e}"),
+                    );
+                }
+                return;
+            }
+
+            if let Some(face_size) = cubemap {
+                if let Err(e) = SimulationContext::<T, _>::new_headless(size)
+                    .run_cubemap(inputs, headless_input, face_size)
+                    .await
+                {
+                    crate::error::report(
+                        "aftgraphs::sim_main",
+                        format!("cubemap rendering failed: {e}"),
+                    );
+                }
+                return;
+            }
+
             let out_img = Arc::new(Mutex::new(vec![]));
             if let Err(e) = SimulationContext::<T, _>::new_headless(size)
                 .run_headless(inputs, headless_input, out_img)
                 .await
             {
-                log::error!("aftgraphs::sim_main: headless rendering failed: {e}");
-                panic!("aftgraphs::sim_main: headless rendering failed:  {e}");
+                crate::error::report(
+                    "aftgraphs::sim_main",
+                    format!("headless rendering failed: {e}"),
+                );
             }
         } else if let Err(e) = SimulationContext::<T, UiWinitPlatform>::new()
             .run_display(inputs)
             .await
         {
-            log::error!("aftgraphs::sim_main: simulation failed: {e}");
-            panic!("aftgraphs::sim_main: simulation failed: {e}");
+            crate::error::report("aftgraphs::sim_main", format!("simulation failed: {e}"));
         };
     });
 }
+
+fn read_headless_input(in_file: PathBuf) -> Result<HeadlessInput, String> {
+    let input_file =
+        File::open(in_file).map_err(|e| format!("failed to open headless input file: {e}"))?;
+    let input_file = read_to_string(input_file)
+        .map_err(|e| format!("failed to read headless input file: {e}"))?;
+    toml::from_str(input_file.as_str())
+        .map_err(|e| format!("failed to parse headless input file TOML: {e}"))
+}
@@ -0,0 +1,305 @@
+use crate::render::{
+    BindGroupLayoutBuilder, RenderPass, RenderPipeline, RenderPipelineBuilder, Renderer,
+    ShaderBuilder, BINDING_UNIFORM_BUFFER,
+};
+use crate::ui::UiPlatform;
+use crate::uniform::{Uniform, UniformBuilder};
+use crate::vertex::{IndexBuffer, InstanceBuffer, InstanceBufferBuilder, InstanceBufferGuard};
+use std::ops::{Deref, DerefMut};
+
+const QUAD: [[f32; 2]; 4] = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+const INDICES: [u16; 6] = [0, 1, 2, 2, 1, 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkerShape {
+    #[default]
+    Circle,
+    Cross,
+    Triangle,
+    Square,
+}
+
+/// How the size of a Marker is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkerSizing {
+    /// Diameter in pixels, constant as the window resizes
+    #[default]
+    Pixels,
+    /// Diameter in clip space x units, scaled with the aspect ratio to stay round
+    Clip,
+}
+
+/// A single point of a MarkerBuffer
+/// position is in clip space
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct Marker {
+    pub position: [f32; 2],
+    pub size: f32,
+    shape: u32,
+    pub color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for Marker {}
+unsafe impl bytemuck::NoUninit for Marker {}
+
+impl Marker {
+    pub fn new(position: [f32; 2], size: f32, shape: MarkerShape, color: [f32; 4]) -> Self {
+        Self {
+            position,
+            size,
+            shape: shape as u32,
+            color,
+        }
+    }
+
+    pub fn shape(&self) -> MarkerShape {
+        match self.shape {
+            1 => MarkerShape::Cross,
+            2 => MarkerShape::Triangle,
+            3 => MarkerShape::Square,
+            _ => MarkerShape::Circle,
+        }
+    }
+
+    pub fn set_shape(&mut self, shape: MarkerShape) {
+        self.shape = shape as u32;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+struct MarkerParams {
+    viewport: [f32; 2],
+    sizing: u32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Zeroable for MarkerParams {}
+unsafe impl bytemuck::NoUninit for MarkerParams {}
+
+/// Instanced point markers for scatter plots
+/// Every marker is one instance of an antialiased quad, so large
+/// point datasets draw in a single call.
+pub struct MarkerBuffer {
+    instances: InstanceBuffer<[f32; 2], Marker>,
+    indices: IndexBuffer<u16>,
+    params: Uniform<MarkerParams>,
+    pipeline: RenderPipeline,
+}
+
+pub struct MarkerBufferGuard<'a, 'b, P: UiPlatform> {
+    guard: InstanceBufferGuard<'a, 'b, [f32; 2], Marker, P>,
+}
+
+impl MarkerBuffer {
+    pub fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        sizing: MarkerSizing,
+        label: Option<&str>,
+    ) -> Self {
+        Self::with_vec(renderer, vec![], sizing, label)
+    }
+
+    pub fn with_vec<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        markers: Vec<Marker>,
+        sizing: MarkerSizing,
+        label: Option<&str>,
+    ) -> Self {
+        let instances = InstanceBufferBuilder::new()
+            .with_initial_vertices(QUAD.as_slice())
+            .with_initial_instances_owned(markers)
+            .with_vertex_label(label)
+            .with_instance_label(label)
+            .with_vertex_attributes_owned(vec![wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            }])
+            .with_instance_attributes_owned(vec![
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ])
+            .build(renderer);
+
+        let indices = IndexBuffer::new(renderer, &INDICES, wgpu::IndexFormat::Uint16, label);
+
+        let params_layout = BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let [width, height] = renderer.viewport_size();
+        let params = UniformBuilder::new()
+            .with_label(label)
+            .with_bind_group_layout(params_layout)
+            .with_data(MarkerParams {
+                viewport: [width as f32, height as f32],
+                sizing: sizing as u32,
+                _padding: 0,
+            })
+            .build(renderer);
+
+        let shader = ShaderBuilder::new()
+            .with_module(wgpu::include_wgsl!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/res/marker.wgsl"
+            )))
+            .with_default_fs_entrypoint()
+            .with_buffer(instances.vertex_layout())
+            .with_buffer(instances.instance_layout())
+            .build(renderer);
+        let pipeline = RenderPipelineBuilder::new()
+            .with_vertex_shader(shader)
+            .with_bind_group_layout(params.bind_group_layout())
+            .with_layout_label(label)
+            .with_pipeline_label(label)
+            .build(renderer)
+            .expect("aftgraphs::marker::MarkerBuffer::with_vec: pipeline without push constants failed to build");
+
+        Self {
+            instances,
+            indices,
+            params,
+            pipeline,
+        }
+    }
+
+    /// Create a guard to modify the markers
+    /// When the guard drops, it will buffer the data to the GPU
+    pub fn modify<'a, 'b, P: UiPlatform>(
+        &'a mut self,
+        renderer: &'a Renderer<'b, P>,
+    ) -> MarkerBufferGuard<'a, 'b, P> {
+        MarkerBufferGuard {
+            guard: self.instances.modify(renderer),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Marker] {
+        self.instances.as_instance_slice()
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    pub fn sizing(&self) -> MarkerSizing {
+        if self.params.sizing == MarkerSizing::Clip as u32 {
+            MarkerSizing::Clip
+        } else {
+            MarkerSizing::Pixels
+        }
+    }
+
+    pub fn set_sizing<P: UiPlatform>(&mut self, renderer: &Renderer<P>, sizing: MarkerSizing) {
+        let mut params = *self.params;
+        params.sizing = sizing as u32;
+        self.params.update(renderer, params);
+    }
+
+    /// Draw all markers
+    /// Updates the viewport size used for pixel sizing if the render target resized
+    pub fn draw<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        render_pass: &mut RenderPass<'_>,
+    ) {
+        let [width, height] = renderer.viewport_size();
+        self.draw_sized(renderer, render_pass, [width as f32, height as f32]);
+    }
+
+    /// Draw all markers into a rectangle of the render target, given as [x, y, width, height]
+    /// in pixels from the top left. Sets the viewport of the render pass to the rectangle,
+    /// marker positions are in the clip space of the rectangle and sizes are relative to it.
+    pub fn draw_in_viewport<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        render_pass: &mut RenderPass<'_>,
+        viewport: [f32; 4],
+    ) {
+        let [x, y, width, height] = viewport;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        self.draw_sized(renderer, render_pass, [width, height]);
+    }
+
+    fn draw_sized<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        render_pass: &mut RenderPass<'_>,
+        viewport: [f32; 2],
+    ) {
+        let mut params = *self.params;
+        params.viewport = viewport;
+        self.params.update(renderer, params);
+
+        if self.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.params.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.instances.as_vertex_buffer());
+        render_pass.set_vertex_buffer(1, self.instances.as_instance_buffer());
+        render_pass.set_index_buffer(self.indices.as_index_buffer(), self.indices.format());
+        render_pass.draw_indexed(self.indices.range(), 0, self.instances.range_instance());
+    }
+}
+
+impl AsRef<[Marker]> for MarkerBuffer {
+    fn as_ref(&self) -> &[Marker] {
+        self.as_slice()
+    }
+}
+
+impl<P: UiPlatform> MarkerBufferGuard<'_, '_, P> {
+    pub fn push(&mut self, marker: Marker) {
+        self.guard.instances_push(marker);
+    }
+
+    pub fn markers_vec(&mut self) -> &mut Vec<Marker> {
+        self.guard.instances_vec()
+    }
+}
+
+impl<P: UiPlatform> Deref for MarkerBufferGuard<'_, '_, P> {
+    type Target = [Marker];
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_instance_slice()
+    }
+}
+
+/// Using this will make the data be sent to the GPU on drop
+impl<P: UiPlatform> DerefMut for MarkerBufferGuard<'_, '_, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.instances_mut()
+    }
+}
@@ -0,0 +1,143 @@
+/// Replace the contents of out with f(0), f(1), ..., f(count - 1), computed in parallel
+/// Takes the same owned f as on WASM, where it is sent to web workers.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn fill<I, F>(out: &mut Vec<I>, count: usize, f: F)
+where
+    I: Send + 'static,
+    F: Fn(usize) -> I + Send + Sync + 'static,
+{
+    use rayon::prelude::*;
+
+    (0..count).into_par_iter().map(&f).collect_into_vec(out);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm::fill;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use crossbeam::channel::{unbounded, Sender};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    };
+
+    /// Number of web workers in the pool
+    const WORKERS: usize = 4;
+
+    /// Fill sequentially below this many elements, the workers are not worth waking
+    const MIN_PARALLEL: usize = 4096;
+
+    type Job = Box<dyn FnOnce() + Send + 'static>;
+
+    struct Pool {
+        jobs: Sender<Job>,
+        workers: usize,
+    }
+
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    static STARTING: AtomicBool = AtomicBool::new(false);
+
+    /// The worker pool, if it has started
+    /// The first call starts spawning the workers in the background.
+    fn pool() -> Option<&'static Pool> {
+        if let Some(pool) = POOL.get() {
+            return Some(pool);
+        }
+
+        if !STARTING.swap(true, Ordering::Relaxed) {
+            let (jobs, receiver) = unbounded::<Job>();
+            crate::block_on(async move {
+                let mut workers = 0;
+                for _ in 0..WORKERS {
+                    let receiver = receiver.clone();
+                    let worker = crate::spawn(move || {
+                        while let Ok(job) = receiver.recv() {
+                            job();
+                        }
+                    })
+                    .await;
+
+                    match worker {
+                        Ok(_) => workers += 1,
+                        Err(e) => {
+                            log::warn!("aftgraphs::parallel::pool: Failed to spawn worker: {e:?}")
+                        }
+                    }
+                }
+
+                log::debug!("aftgraphs::parallel::pool: Started {workers} workers");
+                if workers > 0 {
+                    let _ = POOL.set(Pool { jobs, workers });
+                }
+            });
+        }
+
+        None
+    }
+
+    /// Replace the contents of out with f(0), f(1), ..., f(count - 1), computed in parallel
+    /// Runs sequentially until the worker pool has started. Jobs own f and the values they
+    /// compute, so nothing they use is borrowed if the future is dropped before they finish.
+    /// The main thread of a browser is not allowed to block, so it awaits the jobs instead.
+    pub(crate) async fn fill<I, F>(out: &mut Vec<I>, count: usize, f: F)
+    where
+        I: Send + 'static,
+        F: Fn(usize) -> I + Send + Sync + 'static,
+    {
+        out.clear();
+
+        let Some(pool) = pool().filter(|_| count >= MIN_PARALLEL) else {
+            out.extend((0..count).map(f));
+            return;
+        };
+
+        let f = Arc::new(f);
+        let chunk_len = count.div_ceil(pool.workers + 1);
+        let (results, received) = async_std::channel::unbounded();
+        let mut jobs = 0;
+        for start in (chunk_len..count).step_by(chunk_len) {
+            let end = (start + chunk_len).min(count);
+            let (job_idx, f, results) = (jobs, f.clone(), results.clone());
+            let job: Job = Box::new(move || {
+                let values: Vec<I> = (start..end).map(|idx| f(idx)).collect();
+                // Nobody is waiting for the values if fill was dropped
+                let _ = results.try_send((job_idx, values));
+            });
+            jobs += 1;
+
+            if let Err(e) = pool.jobs.send(job) {
+                // Not sent, run it here instead
+                (e.into_inner())();
+            }
+        }
+        // Receiving fails once every job is gone, even those that panicked
+        drop(results);
+
+        out.reserve(count);
+        out.extend((0..chunk_len.min(count)).map(|idx| f(idx)));
+
+        let mut chunks: Vec<Option<Vec<I>>> = (0..jobs).map(|_| None).collect();
+        for _ in 0..jobs {
+            match received.recv().await {
+                Ok((job_idx, values)) => chunks[job_idx] = Some(values),
+                Err(_) => panic!("aftgraphs::parallel::fill: a worker panicked"),
+            }
+        }
+        out.extend(chunks.into_iter().flatten().flatten());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fill_replaces_contents() {
+        let mut out = vec![7; 3];
+        async_std::task::block_on(fill(&mut out, 10_000, |idx| idx * 2));
+
+        assert_eq!(10_000, out.len());
+        assert!(out.iter().enumerate().all(|(idx, &value)| value == idx * 2));
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    ffi::{c_char, CStr, CString, OsString},
+    path::Path,
+};
+use thiserror::Error;
+
+/// Version of PluginDeclaration, bumped whenever its layout changes
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol sim_plugin! exports the PluginDeclaration under
+pub const PLUGIN_SYMBOL: &[u8] = b"AFTGRAPHS_PLUGIN\0";
+
+/// aftgraphs version a plugin was built against, NUL terminated
+pub const AFTGRAPHS_VERSION: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+        Ok(version) => version,
+        Err(_) => panic!("aftgraphs::plugin: version contains a NUL byte"),
+    };
+
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error("failed to load plugin: {0}")]
+    Load(#[from] libloading::Error),
+    #[error("plugin ABI version {plugin} does not match the host's {host}")]
+    AbiMismatch { plugin: u32, host: u32 },
+    #[error("plugin built against aftgraphs {plugin}, the host is aftgraphs {host}")]
+    VersionMismatch { plugin: String, host: String },
+    #[error("usage: {0} <plugin> [simulation arguments...]")]
+    Usage(String),
+}
+
+/// Entry point of a simulation plugin, exported by sim_plugin!
+/// Only C types cross the library boundary, the plugin runs with its own copy of aftgraphs.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub aftgraphs_version: *const c_char,
+    /// Name of the simulation
+    pub name: *const c_char,
+    /// Run the simulation with argc NUL terminated command line arguments
+    pub run: unsafe extern "C" fn(argc: usize, argv: *const *const c_char),
+}
+
+// The pointers are to 'static strings
+unsafe impl Sync for PluginDeclaration {}
+
+/// Convert the arguments passed to PluginDeclaration::run
+/// # Safety
+/// argv must point to argc valid NUL terminated strings
+pub unsafe fn plugin_args(argc: usize, argv: *const *const c_char) -> Vec<OsString> {
+    (0..argc)
+        .map(|idx| {
+            let arg = CStr::from_ptr(*argv.add(idx));
+            OsString::from(arg.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+/// A loaded simulation plugin
+pub struct Plugin {
+    declaration: &'static PluginDeclaration,
+    // Keeps declaration valid
+    _library: libloading::Library,
+}
+
+impl Plugin {
+    /// Load the plugin at path, checking it was built for this host
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let path = path.as_ref();
+        Plugin::load_inner(path).inspect_err(|err| {
+            log::error!("aftgraphs::plugin::Plugin::load: {}: {err}", path.display())
+        })
+    }
+
+    fn load_inner(path: &Path) -> Result<Self, PluginError> {
+        // Safety: loading runs the library's initializers, plugins are trusted code
+        let library = unsafe { libloading::Library::new(path)? };
+        let declaration: &'static PluginDeclaration = unsafe {
+            let symbol = library.get::<*const PluginDeclaration>(PLUGIN_SYMBOL)?;
+            &**symbol
+        };
+
+        if declaration.abi_version != PLUGIN_ABI_VERSION {
+            return Err(PluginError::AbiMismatch {
+                plugin: declaration.abi_version,
+                host: PLUGIN_ABI_VERSION,
+            });
+        }
+
+        let version = unsafe { CStr::from_ptr(declaration.aftgraphs_version) };
+        if version != AFTGRAPHS_VERSION {
+            return Err(PluginError::VersionMismatch {
+                plugin: version.to_string_lossy().into_owned(),
+                host: AFTGRAPHS_VERSION.to_string_lossy().into_owned(),
+            });
+        }
+
+        let plugin = Self {
+            declaration,
+            _library: library,
+        };
+        log::info!(
+            "aftgraphs::plugin::Plugin::load: Loaded {} from {}",
+            plugin.name(),
+            path.display()
+        );
+        Ok(plugin)
+    }
+
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(self.declaration.name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Run the simulation, args start with the program name like std::env::args
+    pub fn run(&self, args: impl IntoIterator<Item = OsString>) {
+        let args: Vec<CString> = args
+            .into_iter()
+            .map(|arg| CString::new(arg.to_string_lossy().into_owned()).unwrap_or_default())
+            .collect();
+        let argv: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        unsafe { (self.declaration.run)(argv.len(), argv.as_ptr()) };
+    }
+}
+
+/// Main of a viewer running plugins: `viewer <plugin> [simulation arguments...]`
+pub fn host_main() -> Result<(), PluginError> {
+    let mut args = std::env::args_os();
+    let program = args
+        .next()
+        .unwrap_or_else(|| OsString::from("aftgraphs-viewer"));
+    let Some(path) = args.next() else {
+        return Err(PluginError::Usage(program.to_string_lossy().into_owned()));
+    };
+
+    let plugin = Plugin::load(&path)?;
+    let name = OsString::from(plugin.name());
+    plugin.run(std::iter::once(name).chain(args));
+    Ok(())
+}
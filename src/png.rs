@@ -0,0 +1,98 @@
+//! Reading and writing PNG images as 8 bit RGBA, with the png crate.
+
+use std::io::{self, Write};
+
+/// Write a PNG of width x height pixels to out.
+/// Row y of the image is row(y) in RGBA, alpha is dropped unless alpha is set
+pub fn write<'a>(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    alpha: bool,
+    row: impl Fn(u32) -> &'a [u8],
+) -> io::Result<()> {
+    let mut encoder = ::png::Encoder::new(out, width, height);
+    encoder.set_color(if alpha {
+        ::png::ColorType::Rgba
+    } else {
+        ::png::ColorType::Rgb
+    });
+    encoder.set_depth(::png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    // Rows are compressed as they come, the image is never held twice
+    let mut stream = writer.stream_writer()?;
+
+    let row_len = width as usize * 4;
+    let mut rgb = Vec::with_capacity(width as usize * 3);
+    for y in 0..height {
+        let data = &row(y)[..row_len];
+        if alpha {
+            stream.write_all(data)?;
+        } else {
+            rgb.clear();
+            for pixel in data.chunks_exact(4) {
+                rgb.extend_from_slice(&pixel[..3]);
+            }
+            stream.write_all(&rgb)?;
+        }
+    }
+    stream.finish()?;
+    Ok(())
+}
+
+/// Read a PNG, returning its width, height and RGBA pixels
+/// Grayscale and palette images are expanded to RGBA, opaque unless they have alpha,
+/// and 16 bit channels are cut to 8 bits.
+pub fn read(data: &[u8]) -> io::Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = ::png::Decoder::new(data);
+    decoder.set_transformations(::png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    buf.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        ::png::ColorType::Rgba => buf,
+        ::png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        ::png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        ::png::ColorType::Grayscale => buf
+            .iter()
+            .flat_map(|&gray| [gray, gray, gray, 255])
+            .collect(),
+        ::png::ColorType::Indexed => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "palette was not expanded",
+            ))
+        }
+    };
+    Ok((info.width, info.height, rgba))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_written_images() {
+        let pixels = [255u8, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 9, 9, 9, 9];
+        let mut png = vec![];
+        write(&mut png, 2, 2, true, |y| &pixels[y as usize * 8..]).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!((2, 2, pixels.to_vec()), read(&png).unwrap());
+
+        let mut png = vec![];
+        write(&mut png, 2, 2, false, |y| &pixels[y as usize * 8..]).unwrap();
+        let opaque = [
+            255u8, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 9, 9, 9, 255,
+        ];
+        assert_eq!((2, 2, opaque.to_vec()), read(&png).unwrap());
+        assert!(read(b"GIF89a").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use imgui::{TreeNodeFlags, Ui};
+use lazy_static::lazy_static;
+use puffin::{GlobalFrameView, MergeScope, ScopeCollection, ThreadInfo};
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Number of most recent frames merged by the viewer
+const MERGED_FRAMES: usize = 60;
+
+lazy_static! {
+    static ref FRAME_VIEW: GlobalFrameView = GlobalFrameView::default();
+}
+
+/// Turn on puffin scopes and start collecting frames for the viewer
+pub fn init() {
+    log::info!("aftgraphs::profiler::init: Enabling puffin profiling scopes");
+    puffin::set_scopes_on(true);
+    lazy_static::initialize(&FRAME_VIEW);
+}
+
+fn ms(ns: puffin::NanoSecond) -> f64 {
+    ns as f64 * 1e-6
+}
+
+fn draw_scope(ui: &Ui, scopes: &ScopeCollection, scope: &MergeScope<'_>) {
+    let name = scopes
+        .fetch_by_id(&scope.id)
+        .map_or("<unknown>", |details| details.name().as_ref());
+    let text = format!(
+        "{name}: {:.3} ms/frame (max {:.3} ms, {}x)",
+        ms(scope.duration_per_frame_ns),
+        ms(scope.max_duration_ns),
+        scope.num_pieces,
+    );
+
+    if scope.children.is_empty() {
+        ui.bullet_text(text);
+        return;
+    }
+
+    // Keep the node id stable while the timings in the label change
+    if let Some(_node) = ui.tree_node(format!("{text}###{name}")) {
+        for child in &scope.children {
+            draw_scope(ui, scopes, child);
+        }
+    }
+}
+
+/// Draw the profiler window, merging the scopes of the most recent frames per thread
+/// Scopes are shown as ms per frame, so CPU bound stages stand out against
+/// the time spent waiting on the GPU in queue submit and readback.
+pub fn draw_window(ui: &Ui) {
+    let view = FRAME_VIEW.lock();
+
+    let frames: Vec<_> = view
+        .latest_frames(MERGED_FRAMES)
+        .filter_map(|frame| frame.unpacked().ok())
+        .collect::<Vec<Arc<_>>>();
+    let threads: BTreeSet<&ThreadInfo> = frames
+        .iter()
+        .flat_map(|frame| frame.thread_streams.keys())
+        .collect();
+
+    ui.window("Profiler").build(|| {
+        if let Some(frame) = frames.last() {
+            ui.text(format!(
+                "Frame {}: {:.3} ms",
+                frame.frame_index(),
+                ms(frame.duration_ns())
+            ));
+        } else {
+            ui.text("No profiled frames yet");
+            return;
+        }
+        ui.separator();
+
+        for thread in threads {
+            let scopes =
+                match puffin::merge_scopes_for_thread(view.scope_collection(), &frames, thread) {
+                    Ok(scopes) => scopes,
+                    Err(e) => {
+                        log::warn!(
+                            "aftgraphs::profiler::draw_window: Failed to merge scopes: {e:?}"
+                        );
+                        continue;
+                    }
+                };
+
+            if ui.collapsing_header(&thread.name, TreeNodeFlags::DEFAULT_OPEN) {
+                for scope in &scopes {
+                    draw_scope(ui, view.scope_collection(), scope);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,46 @@
+pub use rand_chacha::ChaCha8Rng as RandomStream;
+pub use rand_core::{RngCore, SeedableRng};
+
+/// The random stream of a subsystem name, fully determined by seed and name
+/// Rendering with the same seed, from --seed or Renderer::seed, reproduces every stream.
+pub fn stream(seed: u64, name: &str) -> RandomStream {
+    let mut rng = RandomStream::seed_from_u64(seed);
+    rng.set_stream(stream_id(name));
+    rng
+}
+
+/// FNV-1a hash of name, stable across platforms and builds
+fn stream_id(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The seed of a new Renderer, from --seed or the current time
+pub(crate) async fn startup_seed() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    let seed = crate::cli::ARGUMENTS.read().await.seed;
+    #[cfg(target_arch = "wasm32")]
+    let seed = None;
+
+    let seed = seed.unwrap_or_else(|| {
+        web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    });
+    log::info!("aftgraphs::rand::startup_seed: Using random seed {seed}");
+    seed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn streams_are_reproducible() {
+        let first = stream(42, "spawn").next_u64();
+        assert_eq!(first, stream(42, "spawn").next_u64());
+        assert_ne!(first, stream(42, "jitter").next_u64());
+        assert_ne!(first, stream(43, "spawn").next_u64());
+    }
+}
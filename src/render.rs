@@ -1,10 +1,21 @@
-use crate::input::{InputState, InputValue, Inputs};
-use crate::simulation::Simulation;
+use crate::asset::{AssetProgress, LoadProgress};
+use crate::audio::{AudioAnalysis, AudioInput};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::input::UiLayout;
+use crate::input::{
+    InputModulation, InputSmoothing, InputState, InputValue, Inputs, Modulation, UniformBindings,
+};
+use crate::script::{Script, ScriptDraw};
+use crate::simulation::{Simulation, TimedInput};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::timeline::Timeline;
 use crate::ui::{Ui, UiDrawError, UiPlatform};
+use crate::uniform::{Mat4, Uniform, UniformBuilder, UniformField, UniformSet};
+use crate::units::WorldScale;
 use async_std::sync::Mutex;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use thiserror::Error;
 use winit::window::Window;
 
@@ -13,113 +24,1285 @@ mod linux;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
+mod accumulate;
+mod aspect;
+mod background;
+mod bind_group;
+mod bloom;
 pub mod builder;
-pub use builder::{BindGroupLayoutBuilder, RenderPipelineBuilder, ShaderBuilder};
-pub use wgpu::RenderPass;
+mod clock;
+mod compute;
+mod config;
+mod cubemap;
+mod cursor;
+mod frame;
+mod grade;
+mod graph;
+mod handle;
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+mod inspector;
+mod layer;
+mod limits;
+mod memory;
+mod msaa;
+mod palette;
+mod pipeline_cache;
+mod post;
+mod push_constants;
+mod reduce;
+mod reflect;
+mod stats;
+mod tile;
+mod timing;
+mod validation;
+mod views;
+mod warmup;
+use accumulate::Accumulation;
+pub use aspect::{AspectPolicy, ProjectionParams, WorldRect};
+use background::Background;
+pub use background::BackgroundFit;
+pub use bind_group::{BindGroupBuilder, BindGroupError, BindGroupLayout};
+pub use bloom::BloomSettings;
+pub use builder::{
+    BindGroupLayoutBuilder, ComputePipelineBuilder, PipelineError, RenderPipelineBuilder,
+    ShaderBuilder,
+};
+pub use clock::Clock;
+pub use compute::{workgroups, ComputePass, ComputePipeline};
+pub use config::{LayerConfig, RenderConfig, RenderConfigError};
+pub use cubemap::CubeFace;
+pub(crate) use cubemap::Equirectangular;
+pub(crate) use cursor::CursorRequest;
+pub use cursor::{CursorImage, CursorStyle};
+pub use frame::Frame;
+pub use grade::{CubeError, Lut3d};
+pub use graph::{
+    GraphPassBuilder, GraphResources, RenderGraph, RenderGraphError, TextureSize, TransientTexture,
+};
+pub(crate) use handle::ResourceManager;
+pub use handle::{BufferHandle, LiveHandle, ResourceScope, TextureHandle};
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use hot_reload::HotReloads;
+pub use layer::{BlendMode, Layer};
+use layer::{LayerPass, LayerStack};
+pub(crate) use limits::{required_features, required_limits};
+pub(crate) use memory::{Allocation, MemoryBudget};
+pub use memory::{MemoryUsage, ResourceInfo, ResourceKind};
+use msaa::Msaa;
+pub use palette::Palette;
+pub use pipeline_cache::set_pipeline_cache_dir;
+pub(crate) use pipeline_cache::PipelineCache;
+pub use post::PostEffect;
+use post::{PostChain, PostPass};
+pub use push_constants::PushConstantError;
+pub use reduce::{
+    AutoExposure, Histogram, Reduction, ReductionResult, TextureValue, MAX_HISTOGRAM_BINS,
+};
+pub use reflect::{LayoutMismatch, ReflectionError, ShaderReflection, VertexInput};
+pub(crate) use stats::FrameCounters;
+pub use stats::{RenderPass, RendererStats};
+pub use tile::Tile;
+pub use timing::{FrameTimes, FRAME_TIME_WINDOW, JANK_FACTOR};
+pub use validation::{instance_flags, set_validation, CapturedErrors};
+use views::Views;
+pub use views::{ViewParams, MAX_VIEWS};
+pub use warmup::Warmup;
+
+pub(crate) use inspector::FrameStep;
+
+pub static BINDING_UNIFORM_BUFFER: wgpu::BindingType = wgpu::BindingType::Buffer {
+    ty: wgpu::BufferBindingType::Uniform,
+    has_dynamic_offset: false,
+    min_binding_size: None,
+};
+
+/// Storage buffer shaders may write to, see crate::storage::StorageBuffer
+pub static BINDING_STORAGE_BUFFER: wgpu::BindingType = wgpu::BindingType::Buffer {
+    ty: wgpu::BufferBindingType::Storage { read_only: false },
+    has_dynamic_offset: false,
+    min_binding_size: None,
+};
+
+pub static BINDING_READ_ONLY_STORAGE_BUFFER: wgpu::BindingType = wgpu::BindingType::Buffer {
+    ty: wgpu::BufferBindingType::Storage { read_only: true },
+    has_dynamic_offset: false,
+    min_binding_size: None,
+};
+
+/// Bytes between the rows of a headless frame of width pixels read back from the GPU,
+/// 4 bytes per pixel padded to wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+pub struct RendererPass {
+    pub encoder: wgpu::CommandEncoder,
+    pub frame: Option<wgpu::SurfaceTexture>,
+    pub view: Option<wgpu::TextureView>,
+}
+
+/// A headless frame being copied to the readback buffer, see Renderer::render_headless_submit
+pub(crate) struct PendingReadback {
+    mapped: futures_intrusive::channel::shared::OneshotReceiver<Result<(), wgpu::BufferAsyncError>>,
+    gpu_start: web_time::Instant,
+}
+
+pub struct Shader<'a> {
+    shader: wgpu::ShaderModule,
+    vs_entry: &'a str,
+    fs_entry: Option<&'a str>,
+    buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    targets: Vec<Option<wgpu::ColorTargetState>>,
+    /// File the module was read from, see ShaderBuilder::with_path
+    #[cfg(not(target_arch = "wasm32"))]
+    path: Option<std::path::PathBuf>,
+    /// Group of the views uniform, see ShaderBuilder::with_views
+    views_group: Option<u32>,
+}
+
+pub struct RenderPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    pub layout: wgpu::PipelineLayout,
+    /// Checked by RenderPass::set_push_constants_checked
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
+    /// Group of the views uniform, bound by Renderer::bind_views
+    views_group: Option<u32>,
+    /// Rebuilt by the renderer when the shaders read with ShaderBuilder::with_path change
+    #[cfg(not(target_arch = "wasm32"))]
+    hot_reload: Option<Arc<hot_reload::Reloadable>>,
+    /// Lists the pipeline in the inspector
+    _allocation: Allocation,
+}
+
+impl RenderPipeline {
+    pub fn push_constant_ranges(&self) -> &[wgpu::PushConstantRange] {
+        &self.push_constant_ranges
+    }
+
+    /// The group of the views uniform if the shaders were built with ShaderBuilder::with_views
+    pub fn views_group(&self) -> Option<u32> {
+        self.views_group
+    }
+
+    /// Take the pipeline the renderer rebuilt after a shader file it was built from with
+    /// ShaderBuilder::with_path changed, returning if it was replaced.
+    /// RenderPass::set_pipeline already draws with the rebuilt pipeline, this only matters
+    /// to code using the wgpu::RenderPipeline directly. The layout is kept, so bind groups,
+    /// buffers and uniforms stay valid. Sources that fail to compile are logged and skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(&mut self) -> bool {
+        let Some(ref hot_reload) = self.hot_reload else {
+            return false;
+        };
+        match hot_reload
+            .pipeline
+            .lock()
+            .expect("aftgraphs::render::RenderPipeline::reload: poisoned lock")
+            .take()
+        {
+            Some(pipeline) => {
+                self.pipeline = pipeline;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A pipeline RenderPass::set_pipeline can bind
+pub trait BindPipeline {
+    fn bind_pipeline(&self, pass: &mut wgpu::RenderPass<'_>);
+
+    /// Checked by RenderPass::set_push_constants_checked, unknown for a wgpu::RenderPipeline
+    fn push_constant_ranges(&self) -> &[wgpu::PushConstantRange] {
+        &[]
+    }
+}
+
+impl BindPipeline for wgpu::RenderPipeline {
+    fn bind_pipeline(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(self);
+    }
+}
+
+/// Binds the pipeline last rebuilt from changed shader files, if there is one
+impl BindPipeline for RenderPipeline {
+    fn bind_pipeline(&self, pass: &mut wgpu::RenderPass<'_>) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref hot_reload) = self.hot_reload {
+            let rebuilt = hot_reload
+                .pipeline
+                .lock()
+                .expect("aftgraphs::render::RenderPipeline::bind_pipeline: poisoned lock");
+            if let Some(ref pipeline) = *rebuilt {
+                pass.set_pipeline(pipeline);
+                return;
+            }
+        }
+        pass.set_pipeline(&self.pipeline);
+    }
+
+    fn push_constant_ranges(&self) -> &[wgpu::PushConstantRange] {
+        &self.push_constant_ranges
+    }
+}
+
+impl AsRef<wgpu::RenderPipeline> for RenderPipeline {
+    fn as_ref(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+}
+
+impl AsRef<wgpu::PipelineLayout> for RenderPipeline {
+    fn as_ref(&self) -> &wgpu::PipelineLayout {
+        &self.layout
+    }
+}
+
+impl AsMut<wgpu::RenderPipeline> for RenderPipeline {
+    fn as_mut(&mut self) -> &mut wgpu::RenderPipeline {
+        &mut self.pipeline
+    }
+}
+
+impl AsMut<wgpu::PipelineLayout> for RenderPipeline {
+    fn as_mut(&mut self) -> &mut wgpu::PipelineLayout {
+        &mut self.layout
+    }
+}
+
+impl Deref for RenderPipeline {
+    type Target = wgpu::RenderPipeline;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+impl DerefMut for RenderPipeline {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pipeline
+    }
+}
+
+pub struct Renderer<'a, P: UiPlatform> {
+    pub headless: bool,
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub render_pass: Mutex<Option<RendererPass>>,
+    pub surface: Option<wgpu::Surface<'a>>,
+    pub queue: wgpu::Queue,
+    pub config: Option<wgpu::SurfaceConfiguration>,
+    pub texture: Option<wgpu::Texture>,
+    pub texture_view: Option<wgpu::TextureView>,
+    pub buffer: Option<wgpu::Buffer>,
+    pub platform: P,
+    pub ui: Ui,
+    pub aspect_ratio: f64,
+    /// Wall and simulation time of the current frame
+    pub clock: Clock,
+    /// Draw the renderer statistics HUD with the ui
+    pub show_stats: bool,
+    /// Draw the resource inspector with the ui
+    pub show_inspector: bool,
+    /// Draw the about window of the simulation with the ui
+    pub show_about: bool,
+    pub(crate) palette: Palette,
+    /// Color the render target is cleared to before drawing
+    /// Its alpha is kept in the frames of headless renders with --transparent
+    pub clear_color: wgpu::Color,
+    pub(crate) stats: Arc<FrameCounters>,
+    pub(crate) memory: Arc<MemoryBudget>,
+    pub(crate) resources: Arc<ResourceManager>,
+    /// Compiled pipelines kept between runs, see set_pipeline_cache_dir
+    pub(crate) pipeline_cache: Option<PipelineCache>,
+    /// Pipelines rebuilt every frame their shader files changed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) hot_reloads: HotReloads,
+    /// Size in pixels of the CompositeSimulation viewport being drawn to
+    pub(crate) viewport: std::sync::Mutex<Option<[u32; 2]>>,
+    /// Part of a larger image being drawn by a tiled render
+    pub(crate) tile: std::sync::Mutex<Option<Tile>>,
+    /// Face of the cubemap being drawn by a cubemap capture
+    pub(crate) cube_face: std::sync::Mutex<Option<CubeFace>>,
+    pub(crate) layers: std::sync::Mutex<LayerStack>,
+    pub(crate) background: std::sync::Mutex<Option<Arc<Background>>>,
+    /// Averages the sub-frames of headless frames, see Renderer::accumulate_headless
+    pub(crate) accumulation: std::sync::Mutex<Accumulation>,
+    pub(crate) msaa: std::sync::Mutex<Msaa>,
+    pub(crate) post: std::sync::Mutex<PostChain>,
+    /// Views of multiview pipelines, see Renderer::set_views
+    pub(crate) views: std::sync::Mutex<Views>,
+    /// Seed of every random stream, logged at startup
+    pub(crate) seed: u64,
+    pub(crate) aspect_policy: std::sync::Mutex<AspectPolicy>,
+    /// Physical units the ui labels the axes in, see Renderer::set_world_scale
+    pub(crate) world_scale: std::sync::Mutex<Option<WorldScale>>,
+    /// Inputs of simulations with Simulation::QUEUE_INPUTS, oldest first
+    pub(crate) input_queue: std::sync::Mutex<Vec<TimedInput>>,
+    pub(crate) frame_step: FrameStep,
+    /// Cursor requested with Renderer::set_cursor, applied by the event loop
+    pub(crate) cursor: Arc<CursorRequest>,
+    pub(crate) asset_progress: std::sync::Mutex<Vec<Weak<LoadProgress>>>,
+    /// Pipelines compiled by Warmup, by label
+    pub(crate) prepared: std::sync::Mutex<HashMap<String, RenderPipeline>>,
+    pub(crate) input_modulation: std::sync::Mutex<InputModulation>,
+    pub(crate) input_smoothing: std::sync::Mutex<InputSmoothing>,
+    pub(crate) uniform_bindings: std::sync::Mutex<UniformBindings>,
+    pub(crate) script: std::sync::Mutex<Option<Script>>,
+    /// Writes the "audio.*" inputs every frame
+    pub(crate) audio: std::sync::Mutex<Option<AudioInput>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) timeline: Option<Timeline>,
+    /// Positions of the input block windows, saved between runs
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) ui_layout: std::sync::Mutex<UiLayout>,
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum RenderError {
+    #[error("WGPU surface dropped frame: {0}")]
+    DrawFrameDropped(#[from] wgpu::SurfaceError),
+    #[error("draw_ui called without an active rendering pass active or an active WGPU surface")]
+    DrawUiMissingRenderPass,
+    #[error("drawing Ui failed: {0}")]
+    DrawUiError(#[from] UiDrawError),
+    #[error("attempted headless rendering without an active WGPU texture_view")]
+    HeadlessWithoutTextureView,
+    #[error("attempted to finish headless rendering pass without an active WGPU texture")]
+    HeadlessWithoutTexture,
+    #[error("render_headless_finished called without an active WGPU buffer")]
+    HeadlessWithoutBuffer,
+    #[error("render operation used without an active rendering pass")]
+    MissingRenderPass,
+    #[error("failed to map WGPU buffer to CPU slice")]
+    FailedBufferMap,
+}
+
+impl<'a, P: UiPlatform> Renderer<'a, P> {
+    /// Size of the render target in pixels, or of the viewport when drawn inside a CompositeSimulation
+    pub fn viewport_size(&self) -> [u32; 2] {
+        if let Some(size) = *self
+            .viewport
+            .lock()
+            .expect("aftgraphs::render::Renderer::viewport_size: poisoned lock")
+        {
+            size
+        } else if let Some(ref config) = self.config {
+            [config.width, config.height]
+        } else if let Some(ref texture) = self.texture {
+            let size = texture.size();
+            [size.width, size.height]
+        } else {
+            [1, 1]
+        }
+    }
+
+    /// Width over height of the viewport being drawn to
+    /// The same as aspect_ratio outside of a CompositeSimulation.
+    pub fn viewport_aspect_ratio(&self) -> f64 {
+        if self
+            .viewport
+            .lock()
+            .expect("aftgraphs::render::Renderer::viewport_aspect_ratio: poisoned lock")
+            .is_none()
+        {
+            return self.aspect_ratio;
+        }
+
+        let [width, height] = self.viewport_size();
+        width as f64 / height.max(1) as f64
+    }
+
+    pub(crate) fn set_viewport(&self, size: Option<[u32; 2]>) {
+        *self
+            .viewport
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_viewport: poisoned lock") = size;
+    }
+
+    /// Draw layer into its own target, composited onto the layers below it with blend
+    /// Once any layer is added the Simulation layer gets a target as well, blended with
+    /// BlendMode::Alpha unless added with another mode. Simulation::render draws the
+    /// Simulation layer, Simulation::render_layer draws the others.
+    pub fn add_layer(&self, layer: Layer, blend: BlendMode) {
+        self.layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::add_layer: poisoned lock")
+            .add(layer, blend);
+    }
+
+    pub fn remove_layer(&self, layer: Layer) {
+        self.layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::remove_layer: poisoned lock")
+            .remove(layer);
+    }
+
+    /// Show or hide an added layer, a "layers.<name>" checkbox input overrides this
+    /// Hidden layers are still drawn, so simulations keep stepping, but not composited.
+    pub fn set_layer_visible(&self, layer: Layer, visible: bool) {
+        self.layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_layer_visible: poisoned lock")
+            .set_visible(layer, visible);
+    }
+
+    /// Take the inputs queued since the last call, oldest first
+    /// Only queued for simulations setting Simulation::QUEUE_INPUTS.
+    pub fn drain_inputs(&self) -> Vec<TimedInput> {
+        std::mem::take(
+            &mut *self
+                .input_queue
+                .lock()
+                .expect("aftgraphs::render::Renderer::drain_inputs: poisoned lock"),
+        )
+    }
+
+    pub(crate) fn queue_inputs(&self, inputs: impl IntoIterator<Item = TimedInput>) {
+        self.input_queue
+            .lock()
+            .expect("aftgraphs::render::Renderer::queue_inputs: poisoned lock")
+            .extend(inputs);
+    }
+
+    /// Seconds into the step of this render at which an input at wall time happened,
+    /// None if it happened after Clock::wall_time and belongs to a later step
+    /// The step covers the wall_delta before wall_time, earlier inputs are at its start.
+    pub fn step_offset(&self, time: f64) -> Option<f64> {
+        let wall_time = self.clock.wall_time();
+        if time > wall_time {
+            return None;
+        }
+        let offset =
+            (time - (wall_time - self.clock.wall_delta())).clamp(0.0, self.clock.wall_delta());
+        Some(offset.min(self.clock.delta_time()))
+    }
+
+    /// Fit world space to the render target with policy, see Renderer::projection_uniform
+    pub fn set_aspect_policy(&self, policy: AspectPolicy) {
+        *self
+            .aspect_policy
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_aspect_policy: poisoned lock") = policy;
+    }
+
+    pub fn aspect_policy(&self) -> AspectPolicy {
+        *self
+            .aspect_policy
+            .lock()
+            .expect("aftgraphs::render::Renderer::aspect_policy: poisoned lock")
+    }
+
+    /// Label the edges of the window with ticks in the physical units of scale, along the
+    /// world space the aspect policy fits to it, or stop labeling them with None
+    pub fn set_world_scale(&self, scale: Option<WorldScale>) {
+        *self
+            .world_scale
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_world_scale: poisoned lock") = scale;
+    }
+
+    pub fn world_scale(&self) -> Option<WorldScale> {
+        *self
+            .world_scale
+            .lock()
+            .expect("aftgraphs::render::Renderer::world_scale: poisoned lock")
+    }
+
+    /// The world to NDC projection of the aspect policy for the viewport being drawn to,
+    /// cropped to the tile being drawn in a tiled render
+    pub fn projection_params(&self) -> ProjectionParams {
+        let projection = self
+            .aspect_policy()
+            .projection(self.viewport_aspect_ratio());
+        ProjectionParams {
+            projection: match self.tile() {
+                Some(tile) => tile.crop(projection),
+                None => projection,
+            },
+        }
+    }
+
+    /// The part of the image being drawn in a tiled render.
+    /// Simulations with their own projection apply Tile::crop to it
+    pub fn tile(&self) -> Option<Tile> {
+        *self
+            .tile
+            .lock()
+            .expect("aftgraphs::render::Renderer::tile: poisoned lock")
+    }
+
+    pub(crate) fn set_tile(&self, tile: Option<Tile>) {
+        *self
+            .tile
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_tile: poisoned lock") = tile;
+    }
+
+    /// The face being drawn in a cubemap capture, see --cubemap.
+    /// Simulations draw it from CubeFace::view_params instead of their own camera
+    pub fn cube_face(&self) -> Option<CubeFace> {
+        *self
+            .cube_face
+            .lock()
+            .expect("aftgraphs::render::Renderer::cube_face: poisoned lock")
+    }
+
+    pub(crate) fn set_cube_face(&self, face: Option<CubeFace>) {
+        *self
+            .cube_face
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_cube_face: poisoned lock") = face;
+    }
+
+    /// A uniform holding ProjectionParams, visible to every shader stage
+    /// Keep it current with Uniform::update(renderer, renderer.projection_params()).
+    pub fn projection_uniform(&self) -> Uniform<ProjectionParams> {
+        let label = Some("aftgraphs::render::Renderer::projection_uniform");
+        let layout = BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(self);
+        UniformBuilder::new()
+            .with_label(label)
+            .with_bind_group_layout(layout)
+            .with_data(self.projection_params())
+            .build(self)
+    }
+
+    /// Restrict the simulation to the world rect of a letterboxing aspect policy
+    fn apply_aspect_scissor(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let scissor = match self.tile() {
+            Some(tile) => self
+                .aspect_policy()
+                .scissor(tile.image)
+                .map(|scissor| tile.scissor(scissor)),
+            None => self.aspect_policy().scissor(self.viewport_size()),
+        };
+        if let Some([x, y, width, height]) = scissor {
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+    }
+
+    /// If pipelines can draw to several array layers in one pass, see stereo::StereoTarget
+    pub fn supports_multiview(&self) -> bool {
+        self.device.features().contains(wgpu::Features::MULTIVIEW)
+    }
+
+    /// Seed of the random streams, pass it to --seed to reproduce a run
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The random stream of a subsystem, the same for every call with name
+    pub fn random_stream(&self, name: &str) -> crate::rand::RandomStream {
+        crate::rand::stream(self.seed, name)
+    }
+
+    /// Reconfigure the display surface after its window resized, zero sizes are ignored
+    pub fn resize_surface(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let Some(config) = self.config.as_mut() else {
+            log::warn!("aftgraphs::render::Renderer::resize_surface: No surface configuration");
+            return;
+        };
+        config.width = width;
+        config.height = height;
+
+        let Some(surface) = self.surface.as_ref() else {
+            log::warn!("aftgraphs::render::Renderer::resize_surface: No surface");
+            return;
+        };
+        surface.configure(&self.device, config);
+        self.msaa
+            .get_mut()
+            .expect("aftgraphs::render::Renderer::resize_surface: poisoned lock")
+            .resized();
+
+        self.aspect_ratio = width as f64 / height as f64;
+    }
+
+    /// Samples per pixel the simulation is drawn with, 1 without MSAA
+    pub fn sample_count(&self) -> u32 {
+        self.msaa
+            .lock()
+            .expect("aftgraphs::render::Renderer::sample_count: poisoned lock")
+            .sample_count()
+    }
+
+    /// Draw the simulation with count samples per pixel, resolved onto the render target,
+    /// or without MSAA with a count of 1. Returns the sample count used, the highest the
+    /// render target supports up to count.
+    /// RenderPipelineBuilder uses the sample count of the renderer when the pipeline is built,
+    /// so set it before building pipelines, e.g. with the msaa_samples of a RenderConfig.
+    pub fn set_sample_count(&self, count: u32) -> u32 {
+        self.msaa
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_sample_count: poisoned lock")
+            .set_sample_count(self, count)
+    }
+
+    /// Multisample state of pipelines drawing the simulation
+    pub fn multisample_state(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count(),
+            ..Default::default()
+        }
+    }
+
+    /// Present modes the display surface supports, empty when rendering headless
+    pub fn present_modes(&self) -> Vec<wgpu::PresentMode> {
+        self.surface
+            .as_ref()
+            .map(|surface| surface.get_capabilities(&self.adapter).present_modes)
+            .unwrap_or_default()
+    }
+
+    /// Present mode of the display surface, None when rendering headless
+    pub fn present_mode(&self) -> Option<wgpu::PresentMode> {
+        self.config.as_ref().map(|config| config.present_mode)
+    }
+
+    /// Reconfigure the display surface to present with mode, e.g. Fifo for vsync or
+    /// Immediate for the lowest latency with tearing. Returns false, leaving the surface
+    /// as it was, if the surface does not support mode.
+    /// Must not be called while a frame is being drawn.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        let auto = matches!(
+            mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        );
+        if !auto && !self.present_modes().contains(&mode) {
+            log::warn!(
+                "aftgraphs::render::Renderer::set_present_mode: {mode:?} is not supported by the surface"
+            );
+            return false;
+        }
+
+        let (Some(config), Some(surface)) = (self.config.as_mut(), self.surface.as_ref()) else {
+            log::warn!("aftgraphs::render::Renderer::set_present_mode: No surface");
+            return false;
+        };
+        config.present_mode = mode;
+        surface.configure(&self.device, config);
+        log::info!("aftgraphs::render::Renderer::set_present_mode: presenting with {mode:?}");
+        true
+    }
+
+    /// The current rendering setup, to save as a preset
+    pub fn render_config(&self) -> RenderConfig {
+        let wgpu::Color { r, g, b, a } = self.clear_color;
+        RenderConfig {
+            clear_color: [r, g, b, a],
+            show_stats: self.show_stats,
+            msaa_samples: self.sample_count(),
+            bloom: self.bloom(),
+            lut: self
+                .post
+                .lock()
+                .expect("aftgraphs::render::Renderer::render_config: poisoned lock")
+                .grade
+                .source
+                .clone(),
+            layers: self
+                .layers
+                .lock()
+                .expect("aftgraphs::render::Renderer::render_config: poisoned lock")
+                .configs(),
+        }
+    }
+
+    pub fn apply_render_config(&mut self, config: &RenderConfig) {
+        self.clear_color = config.clear_color();
+        self.show_stats = config.show_stats;
+        if config.msaa_samples != self.sample_count() {
+            self.set_sample_count(config.msaa_samples);
+        }
+        self.set_bloom(config.bloom);
+        self.layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::apply_render_config: poisoned lock")
+            .set_configs(&config.layers);
+
+        let source = self
+            .post
+            .lock()
+            .expect("aftgraphs::render::Renderer::apply_render_config: poisoned lock")
+            .grade
+            .source
+            .clone();
+        match config.lut {
+            // Keep a LUT set from code when the preset has none
+            None if source.is_none() => (),
+            None => self.set_color_lut(None),
+            Some(ref path) if source.as_ref() == Some(path) => (),
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(ref path) => {
+                if let Err(err) = self.load_color_lut(path) {
+                    log::error!(
+                        "aftgraphs::render::Renderer::apply_render_config: failed to load LUT {}: {err}",
+                        path.display()
+                    );
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            Some(_) => log::warn!(
+                "aftgraphs::render::Renderer::apply_render_config: LUT files can not be loaded on WASM, use Renderer::set_color_lut"
+            ),
+        }
+    }
+
+    /// Draw an image behind the simulation, rgba holds width * height Rgba8UnormSrgb pixels
+    pub fn set_background_image(&self, width: u32, height: u32, rgba: &[u8], fit: BackgroundFit) {
+        let background = Background::image(self, width, height, rgba, fit);
+        *self
+            .background
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_background_image: poisoned lock") =
+            Some(Arc::new(background));
+    }
+
+    /// Draw a cubemap behind the simulation, seen through the matrix set with
+    /// Renderer::set_skybox_view. Every face holds size * size Rgba8UnormSrgb pixels,
+    /// in the order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn set_skybox(&self, size: u32, faces: [&[u8]; 6]) {
+        let background = Background::skybox(self, size, faces);
+        *self
+            .background
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_skybox: poisoned lock") =
+            Some(Arc::new(background));
+    }
+
+    /// Set the inverse of the column major view-projection matrix of the 3D camera
+    pub fn set_skybox_view(&self, inverse_view_projection: Mat4) {
+        if let Some(ref background) = *self
+            .background
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_skybox_view: poisoned lock")
+        {
+            background.set_view(self, inverse_view_projection);
+        }
+    }
+
+    pub fn clear_background(&self) {
+        *self
+            .background
+            .lock()
+            .expect("aftgraphs::render::Renderer::clear_background: poisoned lock") = None;
+    }
+
+    /// Grade finished frames through lut, or stop grading with None
+    /// Applied after the layers are composited, to display and headless frames alike.
+    pub fn set_color_lut(&self, lut: Option<&Lut3d>) {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_color_lut: poisoned lock")
+            .grade
+            .set_lut(self, lut);
+    }
+
+    /// Grade finished frames through the LUT of a .cube file, see Renderer::set_color_lut
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_color_lut(&self, path: impl AsRef<std::path::Path>) -> Result<(), CubeError> {
+        let path = path.as_ref();
+        let lut = Lut3d::load(path)?;
+        let mut post = self
+            .post
+            .lock()
+            .expect("aftgraphs::render::Renderer::load_color_lut: poisoned lock");
+        post.grade.set_lut(self, Some(&lut));
+        post.grade.source = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Make bright parts of finished frames glow, or turn bloom off with None
+    /// Applied after the layers are composited and before color grading.
+    pub fn set_bloom(&self, settings: Option<BloomSettings>) {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_bloom: poisoned lock")
+            .bloom
+            .settings = settings;
+    }
+
+    pub fn bloom(&self) -> Option<BloomSettings> {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::bloom: poisoned lock")
+            .bloom
+            .settings
+    }
+
+    /// Apply effect to finished frames after the effects added before it, replacing an effect
+    /// of the same name. Effects run after bloom and before color grading, the ui is drawn
+    /// over their result. A "post.<name>" checkbox input toggles the effect.
+    pub fn add_post_effect(&self, effect: PostEffect) {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::add_post_effect: poisoned lock")
+            .add(effect);
+    }
+
+    pub fn remove_post_effect(&self, name: &str) {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::remove_post_effect: poisoned lock")
+            .remove(name);
+    }
+
+    /// Names of the post-processing effects, in the order they are applied
+    pub fn post_effects(&self) -> Vec<String> {
+        self.post
+            .lock()
+            .expect("aftgraphs::render::Renderer::post_effects: poisoned lock")
+            .effects
+            .iter()
+            .map(|effect| effect.name().to_owned())
+            .collect()
+    }
+
+    /// Statistics of the last finished frame
+    pub fn stats(&self) -> RendererStats {
+        self.stats.last()
+    }
+
+    /// Statistics recorded so far for the frame in progress
+    pub fn current_stats(&self) -> RendererStats {
+        self.stats.current()
+    }
+
+    /// Frame times of the most recent FRAME_TIME_WINDOW frames
+    pub fn frame_times(&self) -> FrameTimes {
+        self.stats.frame_times()
+    }
+
+    /// Frame time at or below which percentile percent of the recent frames fall
+    pub fn frame_time_percentile(&self, percentile: f64) -> web_time::Duration {
+        self.stats.frame_times().percentile(percentile)
+    }
+
+    /// Frame time the simulation aims for, 1/60 s by default
+    pub fn target_frame_time(&self) -> web_time::Duration {
+        self.stats.target_frame_time()
+    }
+
+    pub fn set_target_frame_time(&self, target: web_time::Duration) {
+        self.stats.set_target_frame_time(target);
+    }
+
+    /// Run callback with the frame time of every frame that takes longer
+    /// than JANK_FACTOR target frame times
+    /// Runs on the rendering thread right after the frame is submitted.
+    pub fn on_jank(&self, callback: impl Fn(web_time::Duration) + Send + Sync + 'static) {
+        self.stats.set_jank_callback(Box::new(callback));
+    }
+
+    /// Record a buffer or texture write for the renderer statistics
+    pub fn record_upload(&self, bytes: usize) {
+        self.stats.record_upload(bytes);
+    }
+
+    /// Draw into target with a render pass, submitted right away so that the passes
+    /// of the frame being recorded can sample the result
+    /// Pipelines drawn in the pass must match target, see RenderTarget::color_target.
+    /// Passes drawn every frame are better begun on the frame's encoder in
+    /// Simulation::encode, see Frame::begin_pass.
+    pub fn render_to(
+        &self,
+        target: &crate::texture::RenderTarget,
+        load: wgpu::LoadOp<wgpu::Color>,
+        draw: impl FnOnce(RenderPass<'_>),
+    ) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("aftgraphs::render::Renderer::render_to"),
+            });
+        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("aftgraphs::render::Renderer::render_to"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        draw(RenderPass::new(render_pass, self.stats.clone()));
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Track the memory of a crate-managed resource until the Allocation drops
+    pub(crate) fn track_memory(
+        &self,
+        kind: ResourceKind,
+        label: Option<&str>,
+        bytes: u64,
+    ) -> Allocation {
+        self.memory.allocate(kind, label, bytes)
+    }
+
+    /// Show style as the mouse cursor over the simulation in display mode
+    /// The ui keeps its own cursors over its windows, headless renders ignore the cursor.
+    pub fn set_cursor(&self, style: CursorStyle) {
+        self.cursor.set(style);
+    }
+
+    pub fn cursor(&self) -> CursorStyle {
+        self.cursor.style()
+    }
+
+    /// Colors the user asked for, simulations should pick their colors from it
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    /// Switch the palette, restyling the ui
+    /// Only ScalarFields built afterwards pick up its colormap.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        #[cfg(not(target_arch = "wasm32"))]
+        palette.apply_style(self.ui.context_mut().style_mut());
+        #[cfg(target_arch = "wasm32")]
+        {
+            let name = match palette {
+                Palette::Standard => "standard",
+                Palette::HighContrast => "high-contrast",
+            };
+            if let Err(e) = self.ui.body.set_attribute("data-palette", name) {
+                log::warn!(
+                    "aftgraphs::render::Renderer::set_palette: failed to restyle the inputs: {e:?}"
+                );
+            }
+        }
+    }
+
+    pub(crate) fn track_asset_progress(&self, progress: &Arc<LoadProgress>) {
+        self.asset_progress
+            .lock()
+            .expect("aftgraphs::render::Renderer::track_asset_progress: poisoned lock")
+            .push(Arc::downgrade(progress));
+    }
+
+    /// Combined progress of the live AssetLoaders
+    pub fn asset_progress(&self) -> AssetProgress {
+        let mut loaders = self
+            .asset_progress
+            .lock()
+            .expect("aftgraphs::render::Renderer::asset_progress: poisoned lock");
+        loaders.retain(|progress| progress.strong_count() > 0);
+
+        let mut total = AssetProgress::default();
+        for progress in loaders.iter().filter_map(Weak::upgrade) {
+            total += progress.get();
+        }
+        total
+    }
+
+    /// Stop advancing the simulation, it is rendered with a zero Clock::delta_time until resumed
+    pub fn set_paused(&self, paused: bool) {
+        self.frame_step.set_paused(paused);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.frame_step.is_paused()
+    }
+
+    /// Play timeline back from the start, firing its cues as simulated time passes them
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_timeline(&mut self, timeline: Option<Timeline>) {
+        self.timeline = timeline;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn timeline_mut(&mut self) -> Option<&mut Timeline> {
+        self.timeline.as_mut()
+    }
+
+    /// Apply a requested seek and the inputs of the cues passed this frame, returning their events.
+    /// Pauses at the end of the timeline
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn advance_timeline(
+        &mut self,
+        values: &mut HashMap<String, InputValue>,
+    ) -> Vec<TimedInput> {
+        let Some(timeline) = self.timeline.as_mut() else {
+            return vec![];
+        };
+        if let Some(time) = timeline.take_seek() {
+            self.clock.seek(time);
+            timeline.seek(time, values);
+        }
+
+        let time = self.clock.time();
+        let events = timeline.advance(time, values);
+        if time > timeline.duration() && time - self.clock.delta_time() <= timeline.duration() {
+            self.frame_step.set_paused(true);
+        }
+        events
+    }
+
+    /// Pause and advance the simulation by one frame
+    pub fn step_frame(&self) {
+        self.frame_step.step();
+    }
+
+    /// Hold the clock for the coming frame if paused without a requested step
+    pub(crate) fn apply_frame_step(&mut self) {
+        if !self.frame_step.advance() {
+            self.clock.hold();
+        }
+    }
+
+    /// Set up the per-frame processing of input values declared in inputs, like smoothing
+    pub fn configure_inputs(&self, inputs: &Inputs) {
+        *self
+            .input_modulation
+            .lock()
+            .expect("aftgraphs::render::Renderer::configure_inputs: poisoned lock") =
+            InputModulation::new(inputs);
+        *self
+            .input_smoothing
+            .lock()
+            .expect("aftgraphs::render::Renderer::configure_inputs: poisoned lock") =
+            InputSmoothing::new(inputs);
+        *self
+            .uniform_bindings
+            .lock()
+            .expect("aftgraphs::render::Renderer::configure_inputs: poisoned lock") =
+            UniformBindings::new(inputs);
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.headless {
+            *self
+                .ui_layout
+                .lock()
+                .expect("aftgraphs::render::Renderer::configure_inputs: poisoned lock") =
+                UiLayout::load(&inputs.simulation.name);
+        }
+    }
+
+    /// Drop what the last simulation set up on the renderer before creating another in its
+    /// place: registered uniforms, modulations, layers, the background, post effects, bloom,
+    /// the color LUT, views and prepared pipelines. Apply the RenderConfig again afterwards.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn reset_simulation(&self, inputs: &Inputs) {
+        self.configure_inputs(inputs);
+        *self
+            .layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::reset_simulation: poisoned lock") =
+            Default::default();
+        self.clear_background();
+        *self
+            .post
+            .lock()
+            .expect("aftgraphs::render::Renderer::reset_simulation: poisoned lock") =
+            Default::default();
+        *self
+            .views
+            .lock()
+            .expect("aftgraphs::render::Renderer::reset_simulation: poisoned lock") =
+            Default::default();
+        self.prepared
+            .lock()
+            .expect("aftgraphs::render::Renderer::reset_simulation: poisoned lock")
+            .clear();
+        self.input_queue
+            .lock()
+            .expect("aftgraphs::render::Renderer::reset_simulation: poisoned lock")
+            .clear();
+    }
+
+    /// Drive the input with the full name input by modulation from now on, or stop with None.
+    /// Modulated inputs follow simulated time and override the ui
+    pub fn set_modulation(&self, input: &str, modulation: Option<Modulation>) {
+        self.input_modulation
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_modulation: poisoned lock")
+            .set(input, modulation);
+    }
+
+    /// Write the input declared with `bind = name` to uniform before every render.
+    /// Sliders are written as f32, checkboxes as u32, to the start of the uniform.
+    /// The Uniform keeps the value in its own data from the next update or bind,
+    /// so updating the rest of the uniform does not overwrite it
+    pub fn register_uniform<T: bytemuck::Pod>(&self, name: &str, uniform: &Uniform<T>) {
+        let bound = uniform.bound_inputs().clone();
+        self.uniform_bindings
+            .lock()
+            .expect("aftgraphs::render::Renderer::register_uniform: poisoned lock")
+            .register(name, uniform.buffer().clone(), 0, bound);
+    }
+
+    /// Run script before every render, writing to registered uniforms
+    pub fn set_script(&self, script: Option<Script>) {
+        *self
+            .script
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_script: poisoned lock") = script;
+    }
+
+    /// The draws the script spawned for this frame, issued by Simulation::render with the
+    /// pipelines they name, see crate::script
+    pub fn script_draws(&self) -> Vec<ScriptDraw> {
+        self.script
+            .lock()
+            .expect("aftgraphs::render::Renderer::script_draws: poisoned lock")
+            .as_ref()
+            .map_or_else(Vec::new, |script| script.draws().to_vec())
+    }
+
+    /// Analyze audio every frame into the "audio.*" inputs, or stop with None
+    pub fn set_audio_input(&self, audio: Option<AudioInput>) {
+        *self
+            .audio
+            .lock()
+            .expect("aftgraphs::render::Renderer::set_audio_input: poisoned lock") = audio;
+    }
+
+    /// Analysis of the audio in the last frame, if there is an audio input
+    pub fn audio_analysis(&self) -> Option<AudioAnalysis> {
+        self.audio
+            .lock()
+            .expect("aftgraphs::render::Renderer::audio_analysis: poisoned lock")
+            .as_ref()
+            .map(AudioInput::analysis)
+    }
+
+    /// Renderer::register_uniform for one uniform of a UniformSet
+    pub fn register_uniform_field<T: bytemuck::NoUninit>(
+        &self,
+        name: &str,
+        set: &UniformSet,
+        field: UniformField<T>,
+    ) {
+        self.uniform_bindings
+            .lock()
+            .expect("aftgraphs::render::Renderer::register_uniform_field: poisoned lock")
+            .register(
+                name,
+                set.buffer().clone(),
+                set.offset(field),
+                set.bound_inputs().clone(),
+            );
+    }
 
-pub static BINDING_UNIFORM_BUFFER: wgpu::BindingType = wgpu::BindingType::Buffer {
-    ty: wgpu::BufferBindingType::Uniform,
-    has_dynamic_offset: false,
-    min_binding_size: None,
-};
+    /// Write bound inputs and the outputs of the script to their uniforms
+    fn apply_bindings(&self, input_values: &HashMap<String, InputValue>) {
+        let bindings = self
+            .uniform_bindings
+            .lock()
+            .expect("aftgraphs::render::Renderer::apply_bindings: poisoned lock");
+        let mut uploaded = bindings.apply(&self.queue, input_values);
 
-pub struct RendererPass {
-    pub encoder: wgpu::CommandEncoder,
-    pub frame: Option<wgpu::SurfaceTexture>,
-    pub view: Option<wgpu::TextureView>,
-}
+        let mut script = self
+            .script
+            .lock()
+            .expect("aftgraphs::render::Renderer::apply_bindings: poisoned lock");
+        if let Some(script) = script.as_mut() {
+            #[cfg(not(target_arch = "wasm32"))]
+            script.reload_if_changed();
+            for (uniform, value) in script.run_frame(input_values, &self.clock) {
+                if bindings.write(&self.queue, &uniform, value) {
+                    uploaded += std::mem::size_of::<f32>();
+                }
+            }
+        }
 
-pub struct Shader<'a> {
-    shader: wgpu::ShaderModule,
-    vs_entry: &'a str,
-    fs_entry: Option<&'a str>,
-    buffers: Vec<wgpu::VertexBufferLayout<'a>>,
-    targets: Vec<Option<wgpu::ColorTargetState>>,
-}
+        if uploaded > 0 {
+            self.record_upload(uploaded);
+        }
+    }
 
-pub struct RenderPipeline {
-    pub pipeline: wgpu::RenderPipeline,
-    pub layout: wgpu::PipelineLayout,
-}
+    /// Take a pipeline compiled from Simulation::prepare, None if it was not declared
+    pub fn take_pipeline(&self, label: &str) -> Option<RenderPipeline> {
+        self.prepared
+            .lock()
+            .expect("aftgraphs::render::Renderer::take_pipeline: poisoned lock")
+            .remove(label)
+    }
 
-impl AsRef<wgpu::RenderPipeline> for RenderPipeline {
-    fn as_ref(&self) -> &wgpu::RenderPipeline {
-        &self.pipeline
+    /// The live crate-managed resources, largest first
+    pub fn resources(&self) -> Vec<ResourceInfo> {
+        self.memory.resources()
     }
-}
 
-impl AsRef<wgpu::PipelineLayout> for RenderPipeline {
-    fn as_ref(&self) -> &wgpu::PipelineLayout {
-        &self.layout
+    /// Approximate GPU memory used by the buffers and textures created by the crate
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory.usage()
     }
-}
 
-impl AsMut<wgpu::RenderPipeline> for RenderPipeline {
-    fn as_mut(&mut self) -> &mut wgpu::RenderPipeline {
-        &mut self.pipeline
+    /// Set the GPU memory budget in bytes, None for no budget
+    /// Exceeding the budget logs a warning and runs the callback set with
+    /// Renderer::on_memory_budget_exceeded, it does not stop allocations.
+    pub fn set_memory_budget(&self, budget: Option<u64>) {
+        self.memory.set_budget(budget);
     }
-}
 
-impl AsMut<wgpu::PipelineLayout> for RenderPipeline {
-    fn as_mut(&mut self) -> &mut wgpu::PipelineLayout {
-        &mut self.layout
+    /// Lower the MSAA sample count when a new simulation and its multisampled target do not
+    /// fit the memory budget, rather than letting allocations fail. The simulation is then
+    /// created again, as its pipelines are built for the sample count.
+    pub fn set_memory_downscale(&self, downscale: bool) {
+        self.memory.set_downscale(downscale);
     }
-}
 
-impl Deref for RenderPipeline {
-    type Target = wgpu::RenderPipeline;
+    /// Halve the sample count if memory downscaling is on and the budget would be exceeded
+    /// Returns whether it did, pipelines built for the old sample count must be built again.
+    pub(crate) fn downscale_for_memory(&self) -> bool {
+        if !self.memory.downscales() {
+            return false;
+        }
 
-    fn deref(&self) -> &Self::Target {
-        &self.pipeline
+        let mut msaa = self
+            .msaa
+            .lock()
+            .expect("aftgraphs::render::Renderer::downscale_for_memory: poisoned lock");
+        let count = msaa.sample_count();
+        if count == 1 || self.memory.fits(msaa.missing_bytes(self)) {
+            return false;
+        }
+        let lower = msaa.set_sample_count(self, count / 2);
+        log::warn!(
+            "aftgraphs::render::Renderer::downscale_for_memory: Over the GPU memory budget, lowering MSAA from {count} to {lower} samples"
+        );
+        true
     }
-}
 
-impl DerefMut for RenderPipeline {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.pipeline
+    /// Run callback whenever the memory usage goes over the budget
+    /// Use it to scale down resolutions or instance counts before allocations fail.
+    /// It may use the renderer, e.g. to allocate smaller resources.
+    pub fn on_memory_budget_exceeded(
+        &self,
+        callback: impl Fn(MemoryUsage) + Send + Sync + 'static,
+    ) {
+        self.memory.set_callback(Arc::new(callback));
     }
-}
 
-pub struct Renderer<'a, P: UiPlatform> {
-    pub headless: bool,
-    pub instance: wgpu::Instance,
-    pub adapter: wgpu::Adapter,
-    pub device: wgpu::Device,
-    pub render_pass: Mutex<Option<RendererPass>>,
-    pub surface: Option<wgpu::Surface<'a>>,
-    pub queue: wgpu::Queue,
-    pub config: Option<wgpu::SurfaceConfiguration>,
-    pub texture: Option<wgpu::Texture>,
-    pub texture_view: Option<wgpu::TextureView>,
-    pub buffer: Option<wgpu::Buffer>,
-    pub platform: P,
-    pub ui: Ui,
-    pub aspect_ratio: f64,
-    pub time: f64,
-    pub delta_time: f64,
-}
+    pub fn is_over_memory_budget(&self) -> bool {
+        self.memory.is_over_budget()
+    }
 
-#[derive(Error, Clone, Debug)]
-pub enum RenderError {
-    #[error("WGPU surface dropped frame: {0}")]
-    DrawFrameDropped(#[from] wgpu::SurfaceError),
-    #[error("draw_ui called without an active rendering pass active or an active WGPU surface")]
-    DrawUiMissingRenderPass,
-    #[error("drawing Ui failed: {0}")]
-    DrawUiError(#[from] UiDrawError),
-    #[error("attempted headless rendering without an active WGPU texture_view")]
-    HeadlessWithoutTextureView,
-    #[error("attempted to finish headless rendering pass without an active WGPU texture")]
-    HeadlessWithoutTexture,
-    #[error("render_headless_finished called without an active WGPU buffer")]
-    HeadlessWithoutBuffer,
-    #[error("render operation used without an active rendering pass")]
-    MissingRenderPass,
-    #[error("failed to map WGPU buffer to CPU slice")]
-    FailedBufferMap,
-}
+    /// If allocating bytes more would stay within the memory budget
+    pub fn fits_memory_budget(&self, bytes: u64) -> bool {
+        self.memory.fits(bytes)
+    }
 
-impl<'a, P: UiPlatform> Renderer<'a, P> {
     async fn render_display<T: Simulation>(
         &self,
         surface: &wgpu::Surface<'_>,
@@ -143,13 +1326,133 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
                 label: Some("aftgraphs::render::Renderer::render_display"),
             });
 
-        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("aftgraphs::render::Renderer::render_display"),
+        self.record_simulation(
+            &mut encoder,
+            &view,
+            simulation,
+            input_values,
+            "aftgraphs::render::Renderer::render_display",
+        )
+        .await;
+
+        *pass = Some(RendererPass {
+            encoder,
+            frame: Some(frame),
+            view: Some(view),
+        });
+    }
+
+    /// Record the simulation into view, through the layer targets if any layer was added.
+    /// Each pass is wrapped in a debug group named after the simulation type and layer
+    async fn record_simulation<T: Simulation>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        simulation: Arc<Mutex<T>>,
+        input_values: &mut HashMap<String, InputValue>,
+        label: &str,
+    ) {
+        let layers = self
+            .layers
+            .lock()
+            .expect("aftgraphs::render::Renderer::record_simulation: poisoned lock")
+            .prepare(self, input_values);
+        let background = self
+            .background
+            .lock()
+            .expect("aftgraphs::render::Renderer::record_simulation: poisoned lock")
+            .clone();
+        // The simulation is drawn multisampled, then resolved onto view or its layer's target
+        let msaa = self
+            .msaa
+            .lock()
+            .expect("aftgraphs::render::Renderer::record_simulation: poisoned lock")
+            .prepare(self);
+        let sample_count = msaa.as_ref().map_or(1, |msaa| msaa.sample_count);
+        let post = self
+            .post
+            .lock()
+            .expect("aftgraphs::render::Renderer::record_simulation: poisoned lock")
+            .prepare(self, input_values);
+        // The frame is drawn into the first target of the post-processing steps, the last
+        // step writing it to view
+        let (target, view) = (view, post.as_ref().map_or(view, PostPass::view));
+        let mut simulation = simulation.lock().await;
+        let name = std::any::type_name::<T>();
+
+        encoder.push_debug_group(&format!("aftgraphs: {name} passes"));
+        simulation
+            .encode(
+                self,
+                &mut Frame::new(encoder, self.stats.clone()),
+                input_values,
+            )
+            .await;
+        encoder.pop_debug_group();
+
+        if layers.is_empty() {
+            encoder.push_debug_group(&format!("aftgraphs: {name}"));
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(msaa::color_attachment(
+                    msaa.as_deref(),
+                    view,
+                    wgpu::LoadOp::Clear(self.clear_color),
+                ))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if let Some(ref background) = background {
+                background.draw(self, &mut render_pass, sample_count);
+            }
+            self.apply_aspect_scissor(&mut render_pass);
+            simulation
+                .render(
+                    self,
+                    RenderPass::new(render_pass, self.stats.clone()),
+                    input_values,
+                )
+                .await;
+            encoder.pop_debug_group();
+            self.record_post_processing(encoder, post.as_ref(), target);
+            return;
+        }
+
+        for LayerPass { layer, target, .. } in &layers {
+            encoder.push_debug_group(&format!("aftgraphs: {name} layer {}", layer.name()));
+            let pass_label = format!("{label} {}", layer.name());
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&pass_label),
+                color_attachments: &[Some(msaa::color_attachment(
+                    msaa.as_deref(),
+                    &target.view,
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                ))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.apply_aspect_scissor(&mut render_pass);
+            let render_pass = RenderPass::new(render_pass, self.stats.clone());
+            if *layer == Layer::Simulation {
+                simulation.render(self, render_pass, input_values).await;
+            } else {
+                simulation
+                    .render_layer(self, *layer, render_pass, input_values)
+                    .await;
+            }
+            encoder.pop_debug_group();
+        }
+
+        encoder.push_debug_group(&format!("aftgraphs: {name} layer composite"));
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    load: wgpu::LoadOp::Clear(self.clear_color),
                     store: wgpu::StoreOp::Store,
                 },
             })],
@@ -157,17 +1460,39 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-        simulation
-            .lock()
-            .await
-            .render(self, render_pass, input_values)
-            .await;
+        if let Some(ref background) = background {
+            background.draw(self, &mut render_pass, 1);
+        }
+        for LayerPass {
+            target, pipeline, ..
+        } in &layers
+        {
+            let Some(pipeline) = pipeline else {
+                continue;
+            };
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &target.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        drop(render_pass);
+        encoder.pop_debug_group();
 
-        *pass = Some(RendererPass {
-            encoder,
-            frame: Some(frame),
-            view: Some(view),
-        });
+        self.record_post_processing(encoder, post.as_ref(), target);
+    }
+
+    /// Apply the post-processing steps of the finished frame, the last writing to view
+    fn record_post_processing(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        post: Option<&PostPass>,
+        view: &wgpu::TextureView,
+    ) {
+        let Some(post) = post else {
+            return;
+        };
+        encoder.push_debug_group("aftgraphs: post-processing");
+        post.record(encoder, view);
+        encoder.pop_debug_group();
     }
 
     #[cfg(target_arch = "wasm32")]
@@ -199,25 +1524,14 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
                 label: Some("aftgraphs::render::Renderer::render_headless"),
             });
 
-        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("aftgraphs::render::Renderer::render_headless"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        simulation
-            .lock()
-            .await
-            .render(self, render_pass, input_values)
-            .await;
+        self.record_simulation(
+            &mut encoder,
+            view,
+            simulation,
+            input_values,
+            "aftgraphs::render::Renderer::render_headless",
+        )
+        .await;
 
         *pass = Some(RendererPass {
             encoder,
@@ -231,17 +1545,118 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
         simulation: Arc<Mutex<T>>,
         input_values: &mut HashMap<String, InputValue>,
     ) {
+        profiling::scope!("simulation render");
+        #[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+        crate::capture::begin_frame();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.hot_reloads.poll(self);
+        self.input_modulation
+            .lock()
+            .expect("aftgraphs::render::Renderer::render: poisoned lock")
+            .apply(input_values, self.clock.time());
+        if let Some(audio) = self
+            .audio
+            .lock()
+            .expect("aftgraphs::render::Renderer::render: poisoned lock")
+            .as_mut()
+        {
+            audio.apply(input_values, self.clock.time());
+        }
+
+        // Only the simulation sees smoothed values, the ui keeps showing the ones it set
+        let smoothed = {
+            let mut smoothing = self
+                .input_smoothing
+                .lock()
+                .expect("aftgraphs::render::Renderer::render: poisoned lock");
+            (!smoothing.is_empty()).then(|| {
+                let mut values = input_values.clone();
+                smoothing.apply(&mut values, self.clock.wall_delta());
+                values
+            })
+        };
+        match smoothed {
+            Some(mut values) => {
+                let given = values.clone();
+                self.render_simulation(simulation, &mut values).await;
+                // Keep the values the simulation set, as it would without smoothing
+                for (name, value) in values {
+                    if given.get(&name) != Some(&value) {
+                        input_values.insert(name, value);
+                    }
+                }
+            }
+            None => self.render_simulation(simulation, input_values).await,
+        }
+    }
+
+    /// Renderer::render after the input values are prepared for the simulation
+    async fn render_simulation<T: Simulation>(
+        &self,
+        simulation: Arc<Mutex<T>>,
+        input_values: &mut HashMap<String, InputValue>,
+    ) {
+        self.apply_bindings(input_values);
+
         if let Some(surface) = self.surface.as_ref() {
-            self.render_display(surface, simulation, input_values).await;
+            self.render_display(surface, simulation.clone(), input_values)
+                .await;
+        } else {
+            self.render_headless(simulation.clone(), input_values).await;
+        }
+
+        let inputs = self.drain_inputs();
+        if !inputs.is_empty() {
+            let mut simulation = simulation.lock().await;
+            for TimedInput { event, time } in inputs {
+                simulation.on_input_at(event, time).await;
+            }
+        }
+    }
+
+    /// Add the headless frame just drawn as sub_frame of sub_frames averaged into the next frame
+    /// Submits the sub-frame unless it is the last, which resolves the average onto the
+    /// render target for Renderer::render_headless_finish.
+    pub(crate) async fn accumulate_headless(&self, sub_frame: u32, sub_frames: u32) {
+        let mut pass = self.render_pass.lock().await;
+        let Some(mut frame) = pass.take() else {
+            log::warn!(
+                "aftgraphs::render::Renderer::accumulate_headless: {}",
+                RenderError::MissingRenderPass
+            );
+            return;
+        };
+
+        self.accumulation
+            .lock()
+            .expect("aftgraphs::render::Renderer::accumulate_headless: poisoned lock")
+            .record(self, &mut frame.encoder, sub_frame, sub_frames);
+        if sub_frame + 1 < sub_frames {
+            self.queue.submit(Some(frame.encoder.finish()));
         } else {
-            self.render_headless(simulation, input_values).await;
+            *pass = Some(frame);
         }
     }
 
     pub async fn render_headless_finish(&self, out_img: &mut Vec<u8>) -> Result<(), RenderError> {
+        self.render_headless_finish_timed(out_img).await.map(|_| ())
+    }
+
+    /// Renderer::render_headless_finish, returning the time spent waiting
+    /// on the GPU and the time spent copying the frame off the mapped buffer
+    pub(crate) async fn render_headless_finish_timed(
+        &self,
+        out_img: &mut Vec<u8>,
+    ) -> Result<(web_time::Duration, web_time::Duration), RenderError> {
+        let readback = self.render_headless_submit().await?;
+        self.render_headless_read(readback, out_img).await
+    }
+
+    /// Submit the recorded frame and start copying it to the readback buffer,
+    /// so several renderers can work on their frames at once
+    pub(crate) async fn render_headless_submit(&self) -> Result<PendingReadback, RenderError> {
         use RenderError as RE;
 
-        let u32_size = std::mem::size_of::<u32>() as u32;
         let texture = self.texture.as_ref().ok_or_else(|| {
             log::error!(
                 "aftgraphs::render::Renderer::render_headless_finish: {}",
@@ -268,10 +1683,7 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
             RE::HeadlessWithoutBuffer
         })?;
 
-        let bytes_per_row = u32_size * texture_size.width;
-        let missing_bytes = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
-            - (bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
-        let bytes_per_row = bytes_per_row + missing_bytes;
+        let bytes_per_row = padded_bytes_per_row(texture_size.width);
 
         pass.encoder.copy_texture_to_buffer(
             wgpu::ImageCopyTexture {
@@ -291,20 +1703,49 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
             texture_size,
         );
 
-        self.queue.submit(Some(pass.encoder.finish()));
+        let gpu_start = web_time::Instant::now();
+        {
+            profiling::scope!("queue submit");
+            self.queue.submit(Some(pass.encoder.finish()));
+        }
+        self.stats.end_frame();
+        #[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+        crate::capture::end_frame();
+
+        let (tx, mapped) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).expect("aftgraphs::render::Renderer::render_headless_finish: map_async closure failed to send");
+        });
+        Ok(PendingReadback { mapped, gpu_start })
+    }
+
+    /// Wait for a frame submitted with Renderer::render_headless_submit and copy it to out_img
+    pub(crate) async fn render_headless_read(
+        &self,
+        readback: PendingReadback,
+        out_img: &mut Vec<u8>,
+    ) -> Result<(web_time::Duration, web_time::Duration), RenderError> {
+        use web_time::Instant;
+        use RenderError as RE;
 
+        let buffer = self.buffer.as_ref().ok_or_else(|| {
+            log::error!(
+                "aftgraphs::render::Renderer::render_headless_finish: {}",
+                RE::HeadlessWithoutBuffer
+            );
+            RE::HeadlessWithoutBuffer
+        })?;
         if out_img.len() != buffer.size() as usize {
             out_img.resize(buffer.size() as usize, 0);
         }
 
+        let (gpu_wait, readback_time);
         {
-            let buffer_slice = buffer.slice(..);
-            let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
-            buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-                tx.send(result).expect("aftgraphs::render::Renderer::render_headless_finish: map_async closure failed to send");
-            });
+            profiling::scope!("readback");
             self.device.poll(wgpu::Maintain::Wait);
-            rx.receive()
+            readback
+                .mapped
+                .receive()
                 .await
                 .ok_or_else(|| {
                     log::error!(
@@ -320,13 +1761,16 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
                     );
                     RE::FailedBufferMap
                 })?;
+            gpu_wait = readback.gpu_start.elapsed();
 
-            let data = buffer_slice.get_mapped_range();
+            let readback_start = Instant::now();
+            let data = buffer.slice(..).get_mapped_range();
             out_img.clone_from_slice(&data[..]);
+            readback_time = readback_start.elapsed();
         }
 
         buffer.unmap();
-        Ok(())
+        Ok((gpu_wait, readback_time))
     }
 
     pub async fn draw_ui(
@@ -335,12 +1779,119 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
         inputs: &Inputs,
         state: InputState,
     ) -> Result<(), RenderError> {
-        use RenderError as RE;
-
+        profiling::scope!("ui draw");
+        #[cfg(not(target_arch = "wasm32"))]
+        let (present_mode, present_modes) = if self.show_stats {
+            (self.present_mode(), self.present_modes())
+        } else {
+            (None, vec![])
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut requested_present_mode = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        let axes = self
+            .world_scale()
+            .map(|scale| (scale, self.aspect_policy()));
         let ui = self.ui.context_mut();
 
         let frame = ui.new_frame();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.cursor.set_over_ui(frame.io().want_capture_mouse);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let layout = self
+                .ui_layout
+                .get_mut()
+                .expect("aftgraphs::render::Renderer::draw_ui: poisoned lock");
+            inputs.render(frame, state, layout).await;
+            // Save once the user lets go of a dragged window
+            if !frame.io().mouse_down.iter().any(|&down| down) {
+                layout.save();
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
         inputs.render(frame, state).await;
+        #[cfg(all(feature = "profile-with-puffin", not(target_arch = "wasm32")))]
+        crate::profiler::draw_window(frame);
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.show_inspector {
+            inspector::draw_inspector(
+                frame,
+                &self.frame_step,
+                self.memory.usage(),
+                &self.memory.resources(),
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeline) = self.timeline.as_mut().filter(|_| !self.headless) {
+            crate::timeline::draw_timeline(frame, timeline, &self.frame_step, self.clock.time());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some((scale, policy)) = axes {
+            crate::units::draw_axes(frame, &scale, policy);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.show_about {
+            inputs.simulation.render_about(frame);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.show_stats {
+            requested_present_mode = stats::draw_hud(
+                frame,
+                &self.stats.last(),
+                &self.stats.frame_times(),
+                self.stats.target_frame_time(),
+                present_mode,
+                &present_modes,
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let progress = self.asset_progress();
+            if !progress.is_done() {
+                crate::asset::draw_progress(frame, progress);
+            }
+        }
+
+        self.submit_ui(window, wgpu::LoadOp::Load).await?;
+
+        // The frame has been presented, so the surface can be reconfigured
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(mode) = requested_present_mode {
+            self.set_present_mode(mode);
+        }
+        Ok(())
+    }
+
+    /// Draw a loading screen with a progress bar in [0, 1] to window.
+    /// Does nothing when rendering headless
+    pub async fn draw_loading(
+        &mut self,
+        window: Option<&Window>,
+        label: &str,
+        progress: f32,
+    ) -> Result<(), RenderError> {
+        let Some(window) = window.filter(|_| self.surface.is_some()) else {
+            return Ok(());
+        };
+
+        self.prepare_ui(window);
+        #[cfg(not(target_arch = "wasm32"))]
+        warmup::draw_progress(self.ui.context_mut().new_frame(), label, progress);
+        #[cfg(target_arch = "wasm32")]
+        let _ = (label, progress);
+
+        self.submit_ui(Some(window), wgpu::LoadOp::Clear(self.clear_color))
+            .await
+    }
+
+    /// Draw the current ui frame, over the simulation if load is LoadOp::Load
+    async fn submit_ui(
+        &mut self,
+        window: Option<&Window>,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> Result<(), RenderError> {
+        use RenderError as RE;
 
         let mut pass = self.render_pass.lock().await;
         if pass.is_none() {
@@ -372,7 +1923,8 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
         {
             let pass = unsafe { pass.as_mut().unwrap_unchecked() };
             if let Some(window) = window {
-                self.platform.prepare_render(frame, window);
+                self.platform
+                    .prepare_render(self.ui.context_mut().frame(), window);
             }
 
             let view = pass
@@ -387,13 +1939,14 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
                     RE::HeadlessWithoutTextureView
                 })?;
 
+            pass.encoder.push_debug_group("aftgraphs: ui");
             let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("aftgraphs::render::Renderer::draw_ui"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
+                        load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -403,12 +1956,21 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
             });
 
             self.ui.draw(&mut render_pass, &self.queue, &self.device)?;
+            drop(render_pass);
+            pass.encoder.pop_debug_group();
         }
 
         if !self.headless {
             let pass = unsafe { pass.take().unwrap_unchecked() };
-            self.queue.submit(Some(pass.encoder.finish()));
+            {
+                profiling::scope!("queue submit");
+                self.queue.submit(Some(pass.encoder.finish()));
+            }
+            self.stats.end_frame();
+            #[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+            crate::capture::end_frame();
             if let Some(frame) = pass.frame {
+                profiling::scope!("present");
                 frame.present();
             }
         }
@@ -0,0 +1,256 @@
+use super::post::{fullscreen_module, fullscreen_pipeline};
+use super::{Allocation, BindGroupLayout, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+
+/// Format of the sum, blendable on every backend and precise enough for hundreds of sub-frames
+const SUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Float texture the sub-frames of a headless frame are summed into
+struct AccumulationTarget {
+    size: [u32; 2],
+    view: wgpu::TextureView,
+    /// Samples the render target, to add the sub-frame to the sum
+    frame_bind_group: wgpu::BindGroup,
+    /// Samples the sum, to resolve it onto the render target
+    sum_bind_group: wgpu::BindGroup,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+struct AccumulationPipelines {
+    format: wgpu::TextureFormat,
+    layout: BindGroupLayout,
+    /// Adds the render target to the sum, weighted by the blend constant
+    accumulate: wgpu::RenderPipeline,
+    /// Copies the sum onto the render target
+    resolve: wgpu::RenderPipeline,
+    _allocation: Allocation,
+}
+
+/// Averages several sub-frames, drawn over the interval of a headless frame, into the frame
+/// Gives motion blur to moving simulations and less noise to stochastic ones.
+#[derive(Default)]
+pub(crate) struct Accumulation {
+    target: Option<AccumulationTarget>,
+    pipelines: Option<AccumulationPipelines>,
+}
+
+impl Accumulation {
+    /// Add the sub-frame just drawn to the render target into the sum, the first sub-frame
+    /// restarting it. The last sub-frame resolves the average back onto the render target.
+    pub(crate) fn record<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        encoder: &mut wgpu::CommandEncoder,
+        sub_frame: u32,
+        sub_frames: u32,
+    ) {
+        let (Some(texture), Some(frame)) =
+            (renderer.texture.as_ref(), renderer.texture_view.as_ref())
+        else {
+            log::warn!("aftgraphs::render::accumulate::Accumulation::record: No target texture");
+            return;
+        };
+        let size = [texture.width(), texture.height()];
+        let format = texture.format();
+
+        if self
+            .pipelines
+            .as_ref()
+            .is_none_or(|pipelines| pipelines.format != format)
+        {
+            self.pipelines = Some(Accumulation::create_pipelines(renderer, format));
+            self.target = None;
+        }
+        let pipelines = self.pipelines.as_ref().expect(
+            "aftgraphs::render::accumulate::Accumulation::record: pipelines were just created",
+        );
+        if self
+            .target
+            .as_ref()
+            .is_none_or(|target| target.size != size)
+        {
+            self.target = Some(Accumulation::create_target(
+                renderer,
+                size,
+                &pipelines.layout,
+                frame,
+            ));
+        }
+        let target = self
+            .target
+            .as_ref()
+            .expect("aftgraphs::render::accumulate::Accumulation::record: target was just created");
+
+        let load = if sub_frame == 0 {
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("aftgraphs::render::accumulate::Accumulation::accumulate"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            let weight = 1.0 / sub_frames.max(1) as f64;
+            pass.set_pipeline(&pipelines.accumulate);
+            pass.set_blend_constant(wgpu::Color {
+                r: weight,
+                g: weight,
+                b: weight,
+                a: weight,
+            });
+            pass.set_bind_group(0, &target.frame_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        if sub_frame + 1 < sub_frames {
+            return;
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("aftgraphs::render::accumulate::Accumulation::resolve"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipelines.resolve);
+        pass.set_bind_group(0, &target.sum_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn create_bind_group<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::accumulate::AccumulationTarget"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }],
+            })
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        size: [u32; 2],
+        layout: &wgpu::BindGroupLayout,
+        frame: &wgpu::TextureView,
+    ) -> AccumulationTarget {
+        log::debug!(
+            "aftgraphs::render::accumulate::Accumulation::create_target: Creating {}x{} target",
+            size[0],
+            size[1]
+        );
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::render::accumulate::AccumulationTarget"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SUM_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bytes = SUM_FORMAT.block_copy_size(None).unwrap_or(8) as u64;
+
+        AccumulationTarget {
+            size,
+            frame_bind_group: Accumulation::create_bind_group(renderer, layout, frame),
+            sum_bind_group: Accumulation::create_bind_group(renderer, layout, &view),
+            view,
+            _texture: texture,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::accumulate::AccumulationTarget"),
+                size[0] as u64 * size[1] as u64 * bytes,
+            ),
+        }
+    }
+
+    fn create_pipelines<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        format: wgpu::TextureFormat,
+    ) -> AccumulationPipelines {
+        let label = "aftgraphs::render::accumulate::Accumulation::pipeline";
+        let layout = super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::accumulate::Accumulation::layout"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .build(renderer);
+        let module = fullscreen_module(
+            renderer,
+            label,
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/accumulate.wgsl")),
+        );
+        let create = |format, blend| {
+            fullscreen_pipeline(
+                renderer,
+                label,
+                &module,
+                "fs_main",
+                &[&layout],
+                wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                },
+            )
+        };
+        let weighted = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        AccumulationPipelines {
+            format,
+            accumulate: create(
+                SUM_FORMAT,
+                Some(wgpu::BlendState {
+                    color: weighted,
+                    alpha: weighted,
+                }),
+            ),
+            resolve: create(format, None),
+            layout,
+            _allocation: renderer.track_memory(ResourceKind::Pipeline, Some(label), 0),
+        }
+    }
+}
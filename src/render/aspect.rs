@@ -0,0 +1,153 @@
+use crate::uniform::Mat4;
+use serde::{Deserialize, Serialize};
+
+/// An axis aligned rectangle of world space
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WorldRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl WorldRect {
+    /// [-1, 1] on both axes
+    pub const NDC: WorldRect = WorldRect {
+        min: [-1.0, -1.0],
+        max: [1.0, 1.0],
+    };
+
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self { min, max }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max[0] - self.min[0]
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max[1] - self.min[1]
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+        ]
+    }
+}
+
+/// How world space is fit to render targets of any aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AspectPolicy {
+    /// Map the rect onto the whole target, units stretch with the target
+    Stretch(WorldRect),
+    /// Show all of the rect with square units, the bars around it are scissored away
+    Letterbox(WorldRect),
+    /// Fill the target with square units, cutting off the rect's longer sides
+    Crop(WorldRect),
+}
+
+impl Default for AspectPolicy {
+    /// World space is NDC, the behaviour without a policy
+    fn default() -> Self {
+        AspectPolicy::Stretch(WorldRect::NDC)
+    }
+}
+
+/// The projection of Renderer::projection_uniform
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct ProjectionParams {
+    /// World to NDC, column major
+    pub projection: Mat4,
+}
+
+unsafe impl bytemuck::Zeroable for ProjectionParams {}
+unsafe impl bytemuck::Pod for ProjectionParams {}
+
+impl AspectPolicy {
+    pub fn rect(&self) -> WorldRect {
+        match *self {
+            AspectPolicy::Stretch(rect)
+            | AspectPolicy::Letterbox(rect)
+            | AspectPolicy::Crop(rect) => rect,
+        }
+    }
+
+    /// Width and height of the world space visible on a target of aspect_ratio
+    pub fn visible_size(&self, aspect_ratio: f64) -> [f32; 2] {
+        let rect = self.rect();
+        let (width, height) = (rect.width(), rect.height());
+        let aspect_ratio = aspect_ratio as f32;
+        let wider = aspect_ratio > width / height;
+
+        match self {
+            AspectPolicy::Stretch(_) => [width, height],
+            AspectPolicy::Letterbox(_) if wider => [height * aspect_ratio, height],
+            AspectPolicy::Letterbox(_) => [width, width / aspect_ratio],
+            AspectPolicy::Crop(_) if wider => [width, width / aspect_ratio],
+            AspectPolicy::Crop(_) => [height * aspect_ratio, height],
+        }
+    }
+
+    /// Orthographic world to NDC matrix for a target of aspect_ratio
+    pub fn projection(&self, aspect_ratio: f64) -> Mat4 {
+        let [width, height] = self.visible_size(aspect_ratio);
+        let [x, y] = self.rect().center();
+        let scale = [2.0 / width, 2.0 / height];
+        Mat4([
+            [scale[0], 0.0, 0.0, 0.0],
+            [0.0, scale[1], 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [-x * scale[0], -y * scale[1], 0.0, 1.0],
+        ])
+    }
+
+    /// The world space position under a NDC position, e.g. of InputEvent::Mouse
+    pub fn ndc_to_world(&self, (x, y): (f64, f64), aspect_ratio: f64) -> [f32; 2] {
+        let [width, height] = self.visible_size(aspect_ratio);
+        let [center_x, center_y] = self.rect().center();
+        [
+            center_x + x as f32 * width / 2.0,
+            center_y + y as f32 * height / 2.0,
+        ]
+    }
+
+    /// Scissor rect x, y, width, height in pixels of a target of size, if anything is cut away
+    pub fn scissor(&self, [width, height]: [u32; 2]) -> Option<[u32; 4]> {
+        let AspectPolicy::Letterbox(rect) = self else {
+            return None;
+        };
+
+        let [visible_width, visible_height] =
+            self.visible_size(width as f64 / height.max(1) as f64);
+        let scissor_width = (width as f32 * rect.width() / visible_width).round() as u32;
+        let scissor_height = (height as f32 * rect.height() / visible_height).round() as u32;
+        let scissor_width = scissor_width.clamp(1, width.max(1));
+        let scissor_height = scissor_height.clamp(1, height.max(1));
+        Some([
+            (width - scissor_width.min(width)) / 2,
+            (height - scissor_height.min(height)) / 2,
+            scissor_width,
+            scissor_height,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn letterbox_and_crop_keep_units_square() {
+        let letterbox = AspectPolicy::Letterbox(WorldRect::NDC);
+        assert_eq!([4.0, 2.0], letterbox.visible_size(2.0));
+        assert_eq!([2.0, 4.0], letterbox.visible_size(0.5));
+        assert_eq!(Some([100, 0, 100, 100]), letterbox.scissor([300, 100]));
+        assert_eq!([-1.0, 0.5], letterbox.ndc_to_world((-0.5, 0.5), 2.0));
+
+        let crop = AspectPolicy::Crop(WorldRect::new([0.0, 0.0], [4.0, 2.0]));
+        assert_eq!([4.0, 1.0], crop.visible_size(4.0));
+        assert_eq!(None, crop.scissor([400, 100]));
+        assert_eq!(Mat4::IDENTITY, AspectPolicy::default().projection(3.0));
+    }
+}
@@ -0,0 +1,330 @@
+use super::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use crate::uniform::Mat4;
+use wgpu::util::DeviceExt;
+
+/// How a background image is scaled to the render target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundFit {
+    /// Fill the render target, ignoring the aspect ratio of the image
+    Stretch,
+    /// Show the whole image, letterboxed
+    #[default]
+    Contain,
+    /// Fill the render target, cropping the image
+    Cover,
+}
+
+impl BackgroundFit {
+    /// Image uv per unit of screen uv for an image and a render target of the given aspect ratios
+    fn scale(self, image_aspect: f64, target_aspect: f64) -> [f32; 2] {
+        let ratio = (target_aspect / image_aspect) as f32;
+        match self {
+            BackgroundFit::Stretch => [1.0, 1.0],
+            BackgroundFit::Contain if ratio > 1.0 => [ratio, 1.0],
+            BackgroundFit::Contain => [1.0, 1.0 / ratio],
+            BackgroundFit::Cover if ratio > 1.0 => [1.0, 1.0 / ratio],
+            BackgroundFit::Cover => [ratio, 1.0],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct BackgroundParams {
+    inverse_view_projection: Mat4,
+    scale: [f32; 2],
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for BackgroundParams {}
+unsafe impl bytemuck::NoUninit for BackgroundParams {}
+
+/// Offset of BackgroundParams::scale
+const SCALE_OFFSET: wgpu::BufferAddress = std::mem::size_of::<Mat4>() as u64;
+
+/// A 2D image or a cubemap the Renderer draws before the simulation
+pub(crate) struct Background {
+    pipeline: wgpu::RenderPipeline,
+    /// Pipeline for drawing in the multisampled simulation pass, and its sample count
+    multisampled: Option<(u32, wgpu::RenderPipeline)>,
+    params: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+    texture_bind_group: wgpu::BindGroup,
+    /// Aspect ratio and fit of a 2D image, None for a cubemap
+    fit: Option<(f64, BackgroundFit)>,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+impl Background {
+    pub(crate) fn image<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        fit: BackgroundFit,
+    ) -> Self {
+        let texture = Background::create_texture(renderer, [width, height], &[rgba]);
+        let mut background =
+            Background::new(renderer, texture, wgpu::TextureViewDimension::D2, || {
+                wgpu::include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/background.wgsl"))
+            });
+        background.fit = Some((width as f64 / height.max(1) as f64, fit));
+        background
+    }
+
+    /// Faces are in the order +X, -X, +Y, -Y, +Z, -Z
+    pub(crate) fn skybox<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        size: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let texture = Background::create_texture(renderer, [size, size], &faces);
+        Background::new(renderer, texture, wgpu::TextureViewDimension::Cube, || {
+            wgpu::include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/skybox.wgsl"))
+        })
+    }
+
+    /// Set the inverse view-projection matrix the skybox is seen through, column major
+    pub(crate) fn set_view<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        inverse_view_projection: Mat4,
+    ) {
+        let data = bytemuck::bytes_of(&inverse_view_projection);
+        renderer.queue.write_buffer(&self.params, 0, data);
+        renderer.record_upload(data.len());
+    }
+
+    /// Draw in a render pass with sample_count samples per pixel
+    pub(crate) fn draw<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        sample_count: u32,
+    ) {
+        let pipeline = match self.multisampled {
+            _ if sample_count == 1 => &self.pipeline,
+            Some((count, ref pipeline)) if count == sample_count => pipeline,
+            _ => {
+                log::warn!(
+                    "aftgraphs::render::background::Background::draw: background was set before the sample count changed to {sample_count}, set it again"
+                );
+                return;
+            }
+        };
+
+        if let Some((image_aspect, fit)) = self.fit {
+            let scale = fit.scale(image_aspect, renderer.aspect_ratio);
+            renderer
+                .queue
+                .write_buffer(&self.params, SCALE_OFFSET, bytemuck::cast_slice(&scale));
+        }
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.params_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        (texture, allocation): (wgpu::Texture, Allocation),
+        dimension: wgpu::TextureViewDimension,
+        module: fn() -> wgpu::ShaderModuleDescriptor<'static>,
+    ) -> Self {
+        let label = Some("aftgraphs::render::background::Background");
+
+        let params = renderer
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: bytemuck::bytes_of(&BackgroundParams {
+                    inverse_view_projection: Mat4::IDENTITY,
+                    scale: [1.0, 1.0],
+                    _padding: [0.0; 2],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let params_layout = super::BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let params_bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout: &params_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                }],
+            });
+
+        let texture_layout = super::BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: dimension,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            })
+            .build(renderer);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        });
+        let sampler = renderer.device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let texture_bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout: &texture_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+        let create_pipeline = |count| {
+            let shader = super::ShaderBuilder::new()
+                .with_module(module())
+                .with_default_fs_entrypoint()
+                .build(renderer);
+            super::RenderPipelineBuilder::new()
+                .with_vertex_shader(shader)
+                .with_bind_group_layout(&params_layout)
+                .with_bind_group_layout(&texture_layout)
+                .with_multisample(wgpu::MultisampleState {
+                    count,
+                    ..Default::default()
+                })
+                .with_layout_label(label)
+                .with_pipeline_label(label)
+                .build(renderer)
+                .expect("aftgraphs::render::background::Background::new: pipeline without push constants failed to build")
+                .pipeline
+        };
+        // Layers are composited in a pass that is not multisampled
+        let sample_count = renderer.sample_count();
+        let multisampled =
+            (sample_count > 1).then(|| (sample_count, create_pipeline(sample_count)));
+
+        Self {
+            pipeline: create_pipeline(1),
+            multisampled,
+            params,
+            params_bind_group,
+            texture_bind_group,
+            fit: None,
+            _texture: texture,
+            _allocation: allocation,
+        }
+    }
+
+    /// Create an Rgba8UnormSrgb texture with a layer per slice of pixels
+    fn create_texture<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        [width, height]: [u32; 2],
+        layers: &[&[u8]],
+    ) -> (wgpu::Texture, Allocation) {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers.len() as u32,
+        };
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::render::background::Background"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let layer_bytes = width as usize * height as usize * 4;
+        for (idx, layer) in layers.iter().enumerate() {
+            if layer.len() != layer_bytes {
+                log::error!(
+                    "aftgraphs::render::background::Background::create_texture: layer {idx} has {} bytes, expected {layer_bytes}",
+                    layer.len()
+                );
+                continue;
+            }
+
+            renderer.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: idx as u32,
+                    },
+                },
+                layer,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * 4),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+            renderer.record_upload(layer_bytes);
+        }
+
+        let allocation = renderer.track_memory(
+            ResourceKind::Texture,
+            Some("aftgraphs::render::background::Background"),
+            (layer_bytes * layers.len()) as u64,
+        );
+        (texture, allocation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fit_scale() {
+        // A square image on a target twice as wide as tall
+        assert_eq!([2.0, 1.0], BackgroundFit::Contain.scale(1.0, 2.0));
+        assert_eq!([1.0, 0.5], BackgroundFit::Cover.scale(1.0, 2.0));
+        assert_eq!([1.0, 2.0], BackgroundFit::Contain.scale(1.0, 0.5));
+        assert_eq!([1.0, 1.0], BackgroundFit::Stretch.scale(1.0, 2.0));
+    }
+}
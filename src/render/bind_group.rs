@@ -0,0 +1,429 @@
+use super::Renderer;
+use crate::texture::{Sampler, Texture};
+use crate::ui::UiPlatform;
+use std::num::NonZeroU64;
+use std::ops::Deref;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum BindGroupError {
+    #[error("binding {0} is not in the layout")]
+    NotInLayout(u32),
+    #[error("binding {0} was given more than once")]
+    Duplicate(u32),
+    #[error("binding {0} of the layout was not given a resource")]
+    Missing(u32),
+    #[error("binding {binding} expects {expected} but was given {given}")]
+    WrongKind {
+        binding: u32,
+        expected: &'static str,
+        given: &'static str,
+    },
+    #[error("buffer at binding {binding} lacks the {usage:?} usage")]
+    MissingUsage {
+        binding: u32,
+        usage: wgpu::BufferUsages,
+    },
+    #[error("buffer at binding {binding} binds {size} bytes, the layout needs at least {min}")]
+    BufferTooSmall { binding: u32, size: u64, min: u64 },
+    #[error("texture at binding {binding} samples as {given:?}, the layout expects {expected:?}")]
+    SampleType {
+        binding: u32,
+        expected: wgpu::TextureSampleType,
+        given: Option<wgpu::TextureSampleType>,
+    },
+    #[error("storage texture at binding {binding} is {given:?}, the layout expects {expected:?}")]
+    StorageFormat {
+        binding: u32,
+        expected: wgpu::TextureFormat,
+        given: wgpu::TextureFormat,
+    },
+    #[error("sampler at binding {binding} is {given:?}, the layout expects {expected:?}")]
+    SamplerType {
+        binding: u32,
+        expected: wgpu::SamplerBindingType,
+        given: wgpu::SamplerBindingType,
+    },
+}
+
+enum Resource<'a> {
+    Buffer {
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: Option<NonZeroU64>,
+    },
+    TextureView {
+        view: &'a wgpu::TextureView,
+        /// Known for crate Textures, checked against the layout
+        format: Option<wgpu::TextureFormat>,
+    },
+    Sampler {
+        sampler: &'a wgpu::Sampler,
+        ty: wgpu::SamplerBindingType,
+    },
+}
+
+/// What validation knows of a resource
+#[derive(Debug, Clone, Copy)]
+enum Summary {
+    Buffer {
+        usage: wgpu::BufferUsages,
+        size: u64,
+    },
+    Texture(Option<wgpu::TextureFormat>),
+    Sampler(wgpu::SamplerBindingType),
+}
+
+impl Summary {
+    fn name(self) -> &'static str {
+        match self {
+            Summary::Buffer { .. } => "a buffer",
+            Summary::Texture(_) => "a texture",
+            Summary::Sampler(_) => "a sampler",
+        }
+    }
+}
+
+impl Resource<'_> {
+    fn summary(&self) -> Summary {
+        match *self {
+            Resource::Buffer {
+                buffer,
+                offset,
+                size,
+            } => Summary::Buffer {
+                usage: buffer.usage(),
+                size: size.map_or(buffer.size().saturating_sub(offset), NonZeroU64::get),
+            },
+            Resource::TextureView { format, .. } => Summary::Texture(format),
+            Resource::Sampler { ty, .. } => Summary::Sampler(ty),
+        }
+    }
+
+    fn binding(&self) -> wgpu::BindingResource<'_> {
+        match *self {
+            Resource::Buffer {
+                buffer,
+                offset,
+                size,
+            } => wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset,
+                size,
+            }),
+            Resource::TextureView { view, .. } => wgpu::BindingResource::TextureView(view),
+            Resource::Sampler { sampler, .. } => wgpu::BindingResource::Sampler(sampler),
+        }
+    }
+}
+
+/// If a texture sampling as given can be bound where the layout expects expected
+fn sample_type_matches(expected: wgpu::TextureSampleType, given: wgpu::TextureSampleType) -> bool {
+    use wgpu::TextureSampleType as T;
+    match (expected, given) {
+        (T::Float { filterable: true }, T::Float { filterable }) => filterable,
+        // Depth textures may be read as unfilterable floats
+        (T::Float { filterable: false }, T::Float { .. } | T::Depth) => true,
+        (expected, given) => expected == given,
+    }
+}
+
+/// Check a resource against the layout entry of its binding
+fn validate(entry: &wgpu::BindGroupLayoutEntry, given: Summary) -> Result<(), BindGroupError> {
+    let binding = entry.binding;
+    let wrong_kind = |expected| BindGroupError::WrongKind {
+        binding,
+        expected,
+        given: given.name(),
+    };
+
+    match (entry.ty, given) {
+        (
+            wgpu::BindingType::Buffer {
+                ty,
+                min_binding_size,
+                ..
+            },
+            Summary::Buffer { usage, size },
+        ) => {
+            let needed = match ty {
+                wgpu::BufferBindingType::Uniform => wgpu::BufferUsages::UNIFORM,
+                wgpu::BufferBindingType::Storage { .. } => wgpu::BufferUsages::STORAGE,
+            };
+            if !usage.contains(needed) {
+                return Err(BindGroupError::MissingUsage {
+                    binding,
+                    usage: needed,
+                });
+            }
+            let min = min_binding_size.map_or(1, NonZeroU64::get);
+            if size < min {
+                return Err(BindGroupError::BufferTooSmall { binding, size, min });
+            }
+            Ok(())
+        }
+        (wgpu::BindingType::Buffer { .. }, _) => Err(wrong_kind("a buffer")),
+        (wgpu::BindingType::Texture { sample_type, .. }, Summary::Texture(format)) => {
+            let Some(format) = format else {
+                return Ok(());
+            };
+            let given = format.sample_type(None, None);
+            if given.is_some_and(|given| sample_type_matches(sample_type, given)) {
+                Ok(())
+            } else {
+                Err(BindGroupError::SampleType {
+                    binding,
+                    expected: sample_type,
+                    given,
+                })
+            }
+        }
+        (wgpu::BindingType::StorageTexture { format, .. }, Summary::Texture(given)) => {
+            match given {
+                Some(given) if given != format => Err(BindGroupError::StorageFormat {
+                    binding,
+                    expected: format,
+                    given,
+                }),
+                _ => Ok(()),
+            }
+        }
+        (wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. }, _) => {
+            Err(wrong_kind("a texture"))
+        }
+        (wgpu::BindingType::Sampler(expected), Summary::Sampler(given)) => {
+            use wgpu::SamplerBindingType as S;
+            match (expected, given) {
+                (S::Filtering, S::Filtering | S::NonFiltering)
+                | (S::NonFiltering, S::NonFiltering)
+                | (S::Comparison, S::Comparison) => Ok(()),
+                _ => Err(BindGroupError::SamplerType {
+                    binding,
+                    expected,
+                    given,
+                }),
+            }
+        }
+        (wgpu::BindingType::Sampler(_), _) => Err(wrong_kind("a sampler")),
+        _ => Err(wrong_kind("an unsupported binding type")),
+    }
+}
+
+/// A wgpu::BindGroupLayout built by BindGroupLayoutBuilder, along with its entries
+/// Derefs to the wrapped layout, for pipeline layouts and wgpu bind groups.
+#[derive(Debug)]
+pub struct BindGroupLayout {
+    pub(crate) layout: wgpu::BindGroupLayout,
+    pub(crate) entries: Vec<wgpu::BindGroupLayoutEntry>,
+}
+
+impl BindGroupLayout {
+    pub fn entries(&self) -> &[wgpu::BindGroupLayoutEntry] {
+        &self.entries
+    }
+
+    /// Start a bind group of this layout
+    pub fn bind_group(&self) -> BindGroupBuilder<'_> {
+        BindGroupBuilder::new(self)
+    }
+}
+
+impl From<BindGroupLayout> for wgpu::BindGroupLayout {
+    fn from(layout: BindGroupLayout) -> Self {
+        layout.layout
+    }
+}
+
+impl Deref for BindGroupLayout {
+    type Target = wgpu::BindGroupLayout;
+
+    fn deref(&self) -> &Self::Target {
+        &self.layout
+    }
+}
+
+/// Builder for a BindGroup, checked against the entries its layout was built from
+/// Mismatches are returned as errors at build instead of surfacing as wgpu validation errors.
+pub struct BindGroupBuilder<'a> {
+    label: Option<&'a str>,
+    layout: &'a BindGroupLayout,
+    resources: Vec<(u32, Resource<'a>)>,
+}
+
+impl<'a> BindGroupBuilder<'a> {
+    pub fn new(layout: &'a BindGroupLayout) -> Self {
+        Self {
+            label: None,
+            layout,
+            resources: vec![],
+        }
+    }
+
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Binds all of buffer
+    pub fn with_buffer(self, binding: u32, buffer: &'a wgpu::Buffer) -> Self {
+        self.with_buffer_range(binding, buffer, 0, None)
+    }
+
+    /// Binds size bytes of buffer from offset, or the rest of it with None
+    pub fn with_buffer_range(
+        mut self,
+        binding: u32,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: Option<NonZeroU64>,
+    ) -> Self {
+        self.resources.push((
+            binding,
+            Resource::Buffer {
+                buffer,
+                offset,
+                size,
+            },
+        ));
+        self
+    }
+
+    /// Binds the view of texture, checking its format against the layout
+    pub fn with_texture(mut self, binding: u32, texture: &'a Texture) -> Self {
+        self.resources.push((
+            binding,
+            Resource::TextureView {
+                view: texture.view(),
+                format: Some(texture.format()),
+            },
+        ));
+        self
+    }
+
+    /// Binds a texture view, whose format can not be checked
+    pub fn with_texture_view(mut self, binding: u32, view: &'a wgpu::TextureView) -> Self {
+        self.resources
+            .push((binding, Resource::TextureView { view, format: None }));
+        self
+    }
+
+    pub fn with_sampler(mut self, binding: u32, sampler: &'a Sampler) -> Self {
+        self.resources.push((
+            binding,
+            Resource::Sampler {
+                sampler,
+                ty: sampler.binding_type(),
+            },
+        ));
+        self
+    }
+
+    /// Check every resource against the layout entries and create the BindGroup
+    pub fn build<P: UiPlatform>(
+        self,
+        renderer: &Renderer<P>,
+    ) -> Result<wgpu::BindGroup, BindGroupError> {
+        for (idx, (binding, resource)) in self.resources.iter().enumerate() {
+            if self.resources[..idx]
+                .iter()
+                .any(|(other, _)| other == binding)
+            {
+                return Err(BindGroupError::Duplicate(*binding));
+            }
+            let entry = self
+                .layout
+                .entries
+                .iter()
+                .find(|entry| entry.binding == *binding)
+                .ok_or(BindGroupError::NotInLayout(*binding))?;
+            validate(entry, resource.summary())?;
+        }
+        if let Some(entry) = self.layout.entries.iter().find(|entry| {
+            !self
+                .resources
+                .iter()
+                .any(|(binding, _)| *binding == entry.binding)
+        }) {
+            return Err(BindGroupError::Missing(entry.binding));
+        }
+
+        let entries: Vec<_> = self
+            .resources
+            .iter()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: resource.binding(),
+            })
+            .collect();
+        Ok(renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: self.label,
+                layout: &self.layout.layout,
+                entries: &entries,
+            }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(binding: u32, ty: wgpu::BindingType) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty,
+            count: None,
+        }
+    }
+
+    #[test]
+    fn resources_match_their_layout() {
+        let uniform = entry(0, super::super::BINDING_UNIFORM_BUFFER);
+        let buffer = |usage| Summary::Buffer { usage, size: 16 };
+        assert!(validate(&uniform, buffer(wgpu::BufferUsages::UNIFORM)).is_ok());
+        assert_eq!(
+            Err(BindGroupError::MissingUsage {
+                binding: 0,
+                usage: wgpu::BufferUsages::UNIFORM
+            }),
+            validate(&uniform, buffer(wgpu::BufferUsages::STORAGE))
+        );
+        assert!(matches!(
+            validate(
+                &uniform,
+                Summary::Sampler(wgpu::SamplerBindingType::Filtering)
+            ),
+            Err(BindGroupError::WrongKind { .. })
+        ));
+
+        let texture = entry(
+            1,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+        );
+        let format = |format| Summary::Texture(Some(format));
+        assert!(validate(&texture, format(wgpu::TextureFormat::Rgba8UnormSrgb)).is_ok());
+        assert!(validate(&texture, format(wgpu::TextureFormat::R32Uint)).is_err());
+        assert!(validate(&texture, Summary::Texture(None)).is_ok());
+
+        let sampler = entry(
+            2,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+        );
+        assert!(validate(
+            &sampler,
+            Summary::Sampler(wgpu::SamplerBindingType::Filtering)
+        )
+        .is_err());
+        assert!(validate(
+            &sampler,
+            Summary::Sampler(wgpu::SamplerBindingType::NonFiltering)
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,393 @@
+use super::post::{draw_fullscreen, fullscreen_pipeline, post_module, PostResources};
+use super::{Allocation, BindGroupLayout, Renderer, ResourceKind};
+use crate::input::InputValue;
+use crate::ui::UiPlatform;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Format of the blurred mip chain, bright pixels can go above 1
+const MIP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Parameters of the bloom post-process, see Renderer::set_bloom
+/// "bloom.enabled" checkbox and "bloom.threshold", "bloom.knee", and "bloom.intensity"
+/// slider inputs override them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BloomSettings {
+    /// Brightness of the linear color above which pixels glow
+    pub threshold: f32,
+    /// Width of the soft transition into glowing, as a fraction of the threshold
+    pub knee: f32,
+    /// Strength of the glow added back onto the frame
+    pub intensity: f32,
+    /// Half resolution blur steps, more spread the glow further
+    pub levels: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            knee: 0.5,
+            intensity: 0.6,
+            levels: 5,
+        }
+    }
+}
+
+impl BloomSettings {
+    /// The settings overridden by the "bloom.<name>" inputs, None if disabled by them
+    pub fn with_inputs(mut self, inputs: &HashMap<String, InputValue>) -> Option<Self> {
+        if let Some(&InputValue::CHECKBOX(false)) = inputs.get("bloom.enabled") {
+            return None;
+        }
+
+        for (name, value) in [
+            ("bloom.threshold", &mut self.threshold),
+            ("bloom.knee", &mut self.knee),
+            ("bloom.intensity", &mut self.intensity),
+        ] {
+            if let Some(&InputValue::SLIDER(input)) = inputs.get(name) {
+                *value = input as f32;
+            }
+        }
+        Some(self)
+    }
+
+    /// Blur steps used for a size[0] x size[1] frame, halving it down to one pixel at most
+    fn levels_for(&self, size: [u32; 2]) -> u32 {
+        let max_levels = size[0].min(size[1]).max(2).ilog2();
+        self.levels.clamp(1, max_levels)
+    }
+}
+
+/// Uniforms of res/bloom.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for BloomUniform {}
+unsafe impl bytemuck::Pod for BloomUniform {}
+
+struct BloomPipelines {
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+    _allocation: Allocation,
+}
+
+struct BloomMip {
+    view: wgpu::TextureView,
+    /// Reads this mip as the frame of res/post.wgsl
+    bind_group: wgpu::BindGroup,
+    _texture: wgpu::Texture,
+}
+
+/// The mip chain the frame is blurred through
+struct BloomTarget {
+    size: [u32; 2],
+    /// Binds the uniform at group 1
+    bind_group: wgpu::BindGroup,
+    mips: Vec<BloomMip>,
+    _allocation: Allocation,
+}
+
+/// The bloom step of one frame
+pub(crate) struct BloomPass {
+    target: Arc<BloomTarget>,
+    pipelines: Arc<BloomPipelines>,
+}
+
+impl BloomPass {
+    /// Blur the bright parts of the frame source binds and add them back onto it in view
+    pub(crate) fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::BindGroup,
+        view: &wgpu::TextureView,
+    ) {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+        let settings = &self.target.bind_group;
+        let mips = &self.target.mips;
+
+        draw_fullscreen(
+            encoder,
+            "aftgraphs::render::bloom::prefilter",
+            &mips[0].view,
+            clear,
+            &self.pipelines.prefilter,
+            &[source, settings],
+        );
+        for pair in mips.windows(2) {
+            draw_fullscreen(
+                encoder,
+                "aftgraphs::render::bloom::downsample",
+                &pair[1].view,
+                clear,
+                &self.pipelines.downsample,
+                &[&pair[0].bind_group, settings],
+            );
+        }
+        // Each mip adds its blur onto the larger mip above it
+        for pair in mips.windows(2).rev() {
+            draw_fullscreen(
+                encoder,
+                "aftgraphs::render::bloom::upsample",
+                &pair[0].view,
+                wgpu::LoadOp::Load,
+                &self.pipelines.upsample,
+                &[&pair[1].bind_group, settings],
+            );
+        }
+        draw_fullscreen(
+            encoder,
+            "aftgraphs::render::bloom::composite",
+            view,
+            clear,
+            &self.pipelines.composite,
+            &[source, settings, &mips[0].bind_group],
+        );
+    }
+}
+
+/// The bloom settings of a PostChain and the resources to apply them
+#[derive(Default)]
+pub(crate) struct Bloom {
+    pub(crate) settings: Option<BloomSettings>,
+    uniform: Option<wgpu::Buffer>,
+    /// Last values written to the uniform buffer
+    written: Option<BloomUniform>,
+    layout: Option<BindGroupLayout>,
+    target: Option<Arc<BloomTarget>>,
+    pipelines: HashMap<wgpu::TextureFormat, Arc<BloomPipelines>>,
+}
+
+impl Bloom {
+    /// The bloom step of this frame with settings, already overridden by the inputs
+    /// (Re)creates the mip chain if the render target resized.
+    pub(crate) fn prepare<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+        settings: BloomSettings,
+    ) -> BloomPass {
+        let size = resources.size;
+        let levels = settings.levels_for(size) as usize;
+
+        let uniform = &*self.uniform.get_or_insert_with(|| {
+            renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("aftgraphs::render::bloom::Bloom::uniform"),
+                size: std::mem::size_of::<BloomUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let values = BloomUniform {
+            threshold: settings.threshold,
+            knee: settings.knee,
+            intensity: settings.intensity,
+            _padding: 0.0,
+        };
+        if self.written != Some(values) {
+            renderer
+                .queue
+                .write_buffer(uniform, 0, bytemuck::bytes_of(&values));
+            renderer.record_upload(std::mem::size_of::<BloomUniform>());
+            self.written = Some(values);
+        }
+
+        let layout = &*self
+            .layout
+            .get_or_insert_with(|| Bloom::create_layout(renderer));
+        let target = match self.target {
+            Some(ref target) if target.size == size && target.mips.len() == levels => {
+                target.clone()
+            }
+            _ => {
+                let target = Arc::new(Bloom::create_target(
+                    renderer, resources, levels, layout, uniform,
+                ));
+                self.target = Some(target.clone());
+                target
+            }
+        };
+        let pipelines = self
+            .pipelines
+            .entry(resources.format)
+            .or_insert_with(|| Arc::new(Bloom::create_pipelines(renderer, resources, layout)))
+            .clone();
+
+        BloomPass { target, pipelines }
+    }
+
+    fn create_layout<P: UiPlatform>(renderer: &Renderer<P>) -> BindGroupLayout {
+        super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::bloom::Bloom::layout"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer)
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+        levels: usize,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+    ) -> BloomTarget {
+        let size = resources.size;
+        log::debug!(
+            "aftgraphs::render::bloom::Bloom::create_target: Creating {}x{} target with {levels} levels",
+            size[0],
+            size[1]
+        );
+
+        let mut bytes = 0;
+        let mips: Vec<BloomMip> = (1..=levels)
+            .map(|level| {
+                let size = size.map(|length| (length >> level).max(1));
+                let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("aftgraphs::render::bloom::BloomTarget"),
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: MIP_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let bind_group =
+                    resources.bind_group(renderer, "aftgraphs::render::bloom::BloomTarget", &view);
+                bytes += size[0] as u64 * size[1] as u64 * 8;
+
+                BloomMip {
+                    view,
+                    bind_group,
+                    _texture: texture,
+                }
+            })
+            .collect();
+
+        BloomTarget {
+            size,
+            bind_group: renderer
+                .device
+                .create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("aftgraphs::render::bloom::BloomTarget"),
+                    layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform.as_entire_binding(),
+                    }],
+                }),
+            mips,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::bloom::BloomTarget"),
+                bytes,
+            ),
+        }
+    }
+
+    fn create_pipelines<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+        layout: &wgpu::BindGroupLayout,
+    ) -> BloomPipelines {
+        let label = "aftgraphs::render::bloom::Bloom::pipeline";
+        let module = post_module(
+            renderer,
+            label,
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/bloom.wgsl")),
+        );
+        // The composite step also reads the largest mip at group 2
+        let layouts = [resources.layout, layout, resources.layout];
+
+        let create = |entry_point, format, blend, layouts: &[&wgpu::BindGroupLayout]| {
+            fullscreen_pipeline(
+                renderer,
+                label,
+                &module,
+                entry_point,
+                layouts,
+                wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                },
+            )
+        };
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        BloomPipelines {
+            prefilter: create("fs_prefilter", MIP_FORMAT, None, &layouts[..2]),
+            downsample: create("fs_downsample", MIP_FORMAT, None, &layouts[..2]),
+            upsample: create(
+                "fs_upsample",
+                MIP_FORMAT,
+                Some(wgpu::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+                &layouts[..2],
+            ),
+            composite: create("fs_composite", resources.format, None, &layouts),
+            _allocation: renderer.track_memory(ResourceKind::Pipeline, Some(label), 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inputs_override_settings() {
+        let settings = BloomSettings::default();
+        assert_eq!(Some(settings), settings.with_inputs(&HashMap::new()));
+
+        let inputs = HashMap::from([
+            ("bloom.intensity".to_owned(), InputValue::SLIDER(2.0)),
+            ("bloom.enabled".to_owned(), InputValue::CHECKBOX(true)),
+        ]);
+        assert_eq!(
+            Some(2.0),
+            settings.with_inputs(&inputs).map(|bloom| bloom.intensity)
+        );
+
+        let inputs = HashMap::from([("bloom.enabled".to_owned(), InputValue::CHECKBOX(false))]);
+        assert_eq!(None, settings.with_inputs(&inputs));
+    }
+
+    #[test]
+    fn levels_stop_above_one_pixel() {
+        let settings = BloomSettings {
+            levels: 8,
+            ..Default::default()
+        };
+        assert_eq!(8, settings.levels_for([1920, 1080]));
+        assert_eq!(4, settings.levels_for([16, 64]));
+        assert_eq!(1, settings.levels_for([1, 1]));
+    }
+}
@@ -1,6 +1,25 @@
-use super::{RenderPipeline, Renderer, Shader};
+use super::{BindGroupLayout, ComputePipeline, RenderPipeline, Renderer, ResourceKind, Shader};
 use crate::{ui::UiPlatform, GraphicsInitError};
 use std::{marker::PhantomData, num::NonZeroU32};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PipelineError {
+    #[error("{0}: push constants are not supported by the adapter")]
+    PushConstantsUnsupported(String),
+    #[error("{label}: the vertex shader declares the views at group {vertex}, the fragment shader at {fragment}")]
+    ViewsGroupMismatch {
+        label: String,
+        vertex: u32,
+        fragment: u32,
+    },
+    #[error("{label}: the shaders declare the views at group {group:?}, but groups {free:?} were left free for them")]
+    ViewsGroupNotFree {
+        label: String,
+        group: Option<u32>,
+        free: Vec<u32>,
+    },
+}
 
 mod sealed {
     pub trait Sealed {}
@@ -20,6 +39,11 @@ pub struct ShaderBuilder<'a, S: BuilderState> {
     fs_entry: Option<&'a str>,
     buffers: Vec<wgpu::VertexBufferLayout<'a>>,
     targets: Vec<Option<wgpu::ColorTargetState>>,
+    /// File the module was read from, see ShaderBuilder::with_path
+    #[cfg(not(target_arch = "wasm32"))]
+    path: Option<std::path::PathBuf>,
+    /// Group the views header is declared at, see ShaderBuilder::with_views
+    views_group: Option<u32>,
     state: PhantomData<S>,
 }
 
@@ -37,11 +61,12 @@ pub struct RenderPipelineBuilder<'a, S: BuilderState> {
     fragment_use_vertex_shader: bool,
     pipeline_layout_label: Option<&'a str>,
     pipeline_label: Option<&'a str>,
-    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    /// None is the group left free for the views, see RenderPipelineBuilder::with_views_group
+    bind_group_layouts: Vec<Option<&'a wgpu::BindGroupLayout>>,
     push_constant_ranges: Vec<wgpu::PushConstantRange>,
     primitive: wgpu::PrimitiveState,
     depth_stencil: Option<wgpu::DepthStencilState>,
-    multisample: wgpu::MultisampleState,
+    multisample: Option<wgpu::MultisampleState>,
     multiview: Option<NonZeroU32>,
     state: PhantomData<S>,
 }
@@ -66,6 +91,9 @@ impl<'a> ShaderBuilder<'a, BuilderInit> {
             fs_entry: None,
             buffers: vec![],
             targets: vec![],
+            #[cfg(not(target_arch = "wasm32"))]
+            path: None,
+            views_group: None,
             state: PhantomData,
         }
     }
@@ -80,9 +108,31 @@ impl<'a> ShaderBuilder<'a, BuilderInit> {
             fs_entry: self.fs_entry,
             buffers: self.buffers,
             targets: self.targets,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: None,
+            views_group: self.views_group,
             state: PhantomData,
         }
     }
+
+    /// Read the WGSL module from a file
+    /// The renderer rebuilds pipelines using the shader when the file changes,
+    /// see RenderPipeline::reload.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_path(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<ShaderBuilder<'a, BuilderComplete>> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        Ok(ShaderBuilder {
+            path: Some(path.to_owned()),
+            ..self.with_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+        })
+    }
 }
 
 impl<'a> ShaderBuilder<'a, BuilderComplete> {
@@ -97,9 +147,31 @@ impl<'a> ShaderBuilder<'a, BuilderComplete> {
             fs_entry,
             buffers,
             mut targets,
+            #[cfg(not(target_arch = "wasm32"))]
+            path,
+            views_group,
             state: _,
         } = self;
-        let module = unsafe { module.unwrap_unchecked() };
+        let mut module = unsafe { module.unwrap_unchecked() };
+
+        if let Some(group) = views_group {
+            if let wgpu::ShaderSource::Wgsl(ref source) = module.source {
+                let source = format!("{}\n{source}", super::views::views_header(group));
+                module.source = wgpu::ShaderSource::Wgsl(source.into());
+            } else {
+                log::error!(
+                    "aftgraphs::render::ShaderBuilder::build: the views header can only be prepended to WGSL"
+                );
+            }
+        }
+
+        // wgpu does not compare vertex formats or offsets against the shader
+        #[cfg(debug_assertions)]
+        if !buffers.is_empty() {
+            if let Ok(reflection) = super::ShaderReflection::from_descriptor(&module) {
+                reflection.validate_vertex_layouts(vs_entry, &buffers);
+            }
+        }
 
         let shader = renderer.device.create_shader_module(module);
 
@@ -141,11 +213,23 @@ impl<'a> ShaderBuilder<'a, BuilderComplete> {
             fs_entry,
             buffers,
             targets,
+            #[cfg(not(target_arch = "wasm32"))]
+            path,
+            views_group,
         }
     }
 }
 
 impl<'a, S: BuilderState> ShaderBuilder<'a, S> {
+    /// Prepend the declarations of res/views.wgsl, the views of Renderer::set_views at group
+    /// Pipelines built with the shader must leave group free with
+    /// RenderPipelineBuilder::with_views_group. Bind them with Renderer::bind_views,
+    /// and index them with view_at(view_index) in a multiview pipeline.
+    pub fn with_views(mut self, group: u32) -> Self {
+        self.views_group = Some(group);
+        self
+    }
+
     pub fn with_vs_entrypoint(mut self, entrypoint: &'a str) -> Self {
         self.vs_entry = entrypoint;
         self
@@ -267,13 +351,17 @@ impl<'a> BindGroupLayoutBuilder<'a> {
         self
     }
 
-    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> wgpu::BindGroupLayout {
-        renderer
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> BindGroupLayout {
+        let layout = renderer
             .device
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: self.label,
                 entries: self.entries.as_slice(),
-            })
+            });
+        BindGroupLayout {
+            layout,
+            entries: self.entries,
+        }
     }
 }
 
@@ -295,7 +383,7 @@ impl<'a> RenderPipelineBuilder<'a, BuilderInit> {
             push_constant_ranges: vec![],
             primitive: wgpu::PrimitiveState::default(),
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: None,
             multiview: None,
             state: PhantomData,
         }
@@ -390,7 +478,10 @@ impl<'a> RenderPipelineBuilder<'a, BuilderInit> {
 impl RenderPipelineBuilder<'_, BuilderComplete> {
     /// Use a Renderer to build the completed pipeline.
     /// This pipeline is used when calling Renderer::render
-    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> RenderPipeline {
+    pub fn build<P: UiPlatform>(
+        self,
+        renderer: &Renderer<P>,
+    ) -> Result<RenderPipeline, PipelineError> {
         let Self {
             vertex_shader,
             fragment_shader,
@@ -431,14 +522,57 @@ impl RenderPipelineBuilder<'_, BuilderComplete> {
             })
         };
 
-        let layout = renderer
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: pipeline_layout_label,
-                bind_group_layouts: bind_group_layouts.as_slice(),
-                push_constant_ranges: push_constant_ranges.as_slice(),
+        // Labels propagate between the pipeline and its layout when only one is set
+        let pipeline_layout_label = pipeline_layout_label
+            .map(str::to_owned)
+            .or_else(|| pipeline_label.map(|label| format!("{label}::layout")));
+        let pipeline_label = pipeline_label.or(pipeline_layout_label.as_deref());
+
+        if !push_constant_ranges.is_empty() && !renderer.supports_push_constants() {
+            return Err(PipelineError::PushConstantsUnsupported(
+                pipeline_label.unwrap_or("<unlabeled>").to_owned(),
+            ));
+        }
+
+        // The views take the one group left free with with_views_group
+        let fragment_views = match fragment_shader {
+            Some(ref shader) if !fragment_use_vertex_shader => shader.views_group,
+            _ => None,
+        };
+        if let (Some(vertex), Some(fragment)) = (vertex_shader.views_group, fragment_views) {
+            if vertex != fragment {
+                return Err(PipelineError::ViewsGroupMismatch {
+                    label: pipeline_label.unwrap_or("<unlabeled>").to_owned(),
+                    vertex,
+                    fragment,
+                });
+            }
+        }
+        let views_group = vertex_shader.views_group.or(fragment_views);
+        let free: Vec<u32> = (0..bind_group_layouts.len() as u32)
+            .filter(|&group| bind_group_layouts[group as usize].is_none())
+            .collect();
+        if free != Vec::from_iter(views_group) {
+            return Err(PipelineError::ViewsGroupNotFree {
+                label: pipeline_label.unwrap_or("<unlabeled>").to_owned(),
+                group: views_group,
+                free,
             });
+        }
+        let views = views_group.map(|_| renderer.view_resources());
+        let bind_group_layouts: Vec<&wgpu::BindGroupLayout> = bind_group_layouts
+            .into_iter()
+            .filter_map(|layout| layout.or_else(|| views.as_deref().map(|views| &*views.layout)))
+            .collect();
+
+        let layout_descriptor = wgpu::PipelineLayoutDescriptor {
+            label: pipeline_layout_label.as_deref(),
+            bind_group_layouts: bind_group_layouts.as_slice(),
+            push_constant_ranges: push_constant_ranges.as_slice(),
+        };
+        let layout = renderer.device.create_pipeline_layout(&layout_descriptor);
 
+        let multisample = multisample.unwrap_or_else(|| renderer.multisample_state());
         let pipeline = renderer
             .device
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -447,13 +581,40 @@ impl RenderPipelineBuilder<'_, BuilderComplete> {
                 vertex: vertex_state,
                 fragment: fragment_state,
                 primitive,
-                depth_stencil,
+                depth_stencil: depth_stencil.clone(),
                 multisample,
                 multiview,
-                cache: None,
+                cache: renderer.pipeline_cache(),
             });
 
-        RenderPipeline { layout, pipeline }
+        #[cfg(not(target_arch = "wasm32"))]
+        let hot_reload = super::hot_reload::HotReload::new(
+            pipeline_label,
+            vertex_shader,
+            fragment_shader,
+            fragment_use_vertex_shader,
+            primitive,
+            depth_stencil,
+            multisample,
+            multiview,
+        )
+        .map(|hot_reload| {
+            let layout = renderer.device.create_pipeline_layout(&layout_descriptor);
+            let reloadable =
+                std::sync::Arc::new(super::hot_reload::Reloadable::new(hot_reload, layout));
+            renderer.hot_reloads.register(&reloadable);
+            reloadable
+        });
+
+        Ok(RenderPipeline {
+            layout,
+            pipeline,
+            push_constant_ranges,
+            views_group,
+            #[cfg(not(target_arch = "wasm32"))]
+            hot_reload,
+            _allocation: renderer.track_memory(ResourceKind::Pipeline, pipeline_label, 0),
+        })
     }
 }
 
@@ -496,6 +657,7 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
         self
     }
 
+    /// Sets the label of the pipeline, the layout is labelled after it unless set separately
     pub fn with_pipeline_label(mut self, label: Option<&'a str>) -> Self {
         self.pipeline_label = label;
         self
@@ -503,19 +665,27 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
 
     /// Append a BindGroupLayout to the pipeline
     pub fn with_bind_group_layout(mut self, layout: &'a wgpu::BindGroupLayout) -> Self {
-        self.bind_group_layouts.push(layout);
+        self.bind_group_layouts.push(Some(layout));
+        self
+    }
+
+    /// Leave the next group free for the views of shaders built with ShaderBuilder::with_views
+    /// The shaders must declare the views at this group, see Renderer::bind_views.
+    pub fn with_views_group(mut self) -> Self {
+        self.bind_group_layouts.push(None);
         self
     }
 
     /// Set the pipeline's bind_group_layouts to the passed vec
     pub fn with_bind_group_layouts(mut self, layouts: Vec<&'a wgpu::BindGroupLayout>) -> Self {
-        self.bind_group_layouts = layouts;
+        self.bind_group_layouts = layouts.into_iter().map(Some).collect();
         self
     }
 
     /// Append the slice of BindGroupLayout's to the pipeline's bind_group_layouts
     pub fn with_bind_group_layouts_slice(mut self, layouts: &[&'a wgpu::BindGroupLayout]) -> Self {
-        self.bind_group_layouts.extend_from_slice(layouts);
+        self.bind_group_layouts
+            .extend(layouts.iter().copied().map(Some));
         self
     }
 
@@ -524,7 +694,8 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
         mut self,
         layouts: impl IntoIterator<Item = &'a wgpu::BindGroupLayout>,
     ) -> Self {
-        self.bind_group_layouts.extend(layouts);
+        self.bind_group_layouts
+            .extend(layouts.into_iter().map(Some));
         self
     }
 
@@ -534,6 +705,19 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
         self
     }
 
+    /// Append a PushConstantRange for a T at offset, set with RenderPass::set_push_constants_checked
+    /// Needs Renderer::supports_push_constants.
+    pub fn with_push_constants<T: bytemuck::NoUninit>(
+        self,
+        stages: wgpu::ShaderStages,
+        offset: u32,
+    ) -> Self {
+        self.with_push_constant_range(wgpu::PushConstantRange {
+            stages,
+            range: offset..offset + std::mem::size_of::<T>() as u32,
+        })
+    }
+
     /// Set the pipeline's push_constant_ranges to the passed vec
     pub fn with_push_constant_ranges(
         mut self,
@@ -571,8 +755,11 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
         self
     }
 
+    /// Sets the multisample state of the pipeline
+    /// Defaults to Renderer::multisample_state when built, for drawing the simulation.
+    /// Pipelines drawing to other targets, like a StereoTarget, need the sample count of that target.
     pub fn with_multisample(mut self, multisample: wgpu::MultisampleState) -> Self {
-        self.multisample = multisample;
+        self.multisample = Some(multisample);
         self
     }
 
@@ -581,3 +768,140 @@ impl<'a, S: BuilderState> RenderPipelineBuilder<'a, S> {
         self
     }
 }
+
+// Builder struct for a compute pipeline
+// By default, the entry point is "cs_main".
+// Must add a shader module descriptor to build.
+pub struct ComputePipelineBuilder<'a, S: BuilderState> {
+    module: Option<wgpu::ShaderModuleDescriptor<'a>>,
+    entry_point: &'a str,
+    pipeline_layout_label: Option<&'a str>,
+    pipeline_label: Option<&'a str>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
+    state: PhantomData<S>,
+}
+
+impl Default for ComputePipelineBuilder<'_, BuilderInit> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ComputePipelineBuilder<'a, BuilderInit> {
+    pub fn new() -> Self {
+        Self {
+            module: None,
+            entry_point: "cs_main",
+            pipeline_layout_label: None,
+            pipeline_label: None,
+            bind_group_layouts: vec![],
+            push_constant_ranges: vec![],
+            state: PhantomData,
+        }
+    }
+
+    /// Adds the shader module holding the compute entry point
+    pub fn with_module(
+        self,
+        module: wgpu::ShaderModuleDescriptor<'a>,
+    ) -> ComputePipelineBuilder<'a, BuilderComplete> {
+        ComputePipelineBuilder {
+            module: Some(module),
+            entry_point: self.entry_point,
+            pipeline_layout_label: self.pipeline_layout_label,
+            pipeline_label: self.pipeline_label,
+            bind_group_layouts: self.bind_group_layouts,
+            push_constant_ranges: self.push_constant_ranges,
+            state: PhantomData,
+        }
+    }
+}
+
+impl ComputePipelineBuilder<'_, BuilderComplete> {
+    /// Use a Renderer to build the completed pipeline.
+    /// Dispatch it in Simulation::encode
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> ComputePipeline {
+        let Self {
+            module,
+            entry_point,
+            pipeline_layout_label,
+            pipeline_label,
+            bind_group_layouts,
+            push_constant_ranges,
+            state: _,
+        } = self;
+        let module = unsafe { module.unwrap_unchecked() };
+        let module = renderer.device.create_shader_module(module);
+
+        // Labels propagate between the pipeline and its layout when only one is set
+        let pipeline_layout_label = pipeline_layout_label
+            .map(str::to_owned)
+            .or_else(|| pipeline_label.map(|label| format!("{label}::layout")));
+        let pipeline_label = pipeline_label.or(pipeline_layout_label.as_deref());
+
+        let layout_descriptor = wgpu::PipelineLayoutDescriptor {
+            label: pipeline_layout_label.as_deref(),
+            bind_group_layouts: bind_group_layouts.as_slice(),
+            push_constant_ranges: push_constant_ranges.as_slice(),
+        };
+        let layout = renderer.device.create_pipeline_layout(&layout_descriptor);
+
+        let pipeline = renderer
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: pipeline_label,
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: renderer.pipeline_cache(),
+            });
+
+        ComputePipeline {
+            layout,
+            pipeline,
+            _allocation: renderer.track_memory(ResourceKind::Pipeline, pipeline_label, 0),
+        }
+    }
+}
+
+impl<'a, S: BuilderState> ComputePipelineBuilder<'a, S> {
+    /// Sets the compute entry point, defaults to "cs_main"
+    pub fn with_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    /// Sets the label of the pipeline, the layout is labelled after it unless set separately
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.pipeline_label = label;
+        self
+    }
+
+    pub fn with_layout_label(mut self, label: Option<&'a str>) -> Self {
+        self.pipeline_layout_label = label;
+        self
+    }
+
+    /// Append a BindGroupLayout to the pipeline
+    pub fn with_bind_group_layout(mut self, layout: &'a wgpu::BindGroupLayout) -> Self {
+        self.bind_group_layouts.push(layout);
+        self
+    }
+
+    /// Append the iterator of BindGroupLayout's to the pipeline's bind_group_layouts
+    pub fn with_bind_group_layouts_iter(
+        mut self,
+        layouts: impl IntoIterator<Item = &'a wgpu::BindGroupLayout>,
+    ) -> Self {
+        self.bind_group_layouts.extend(layouts);
+        self
+    }
+
+    /// Append a PushConstantRange to the pipeline
+    pub fn with_push_constant_range(mut self, constant_range: wgpu::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(constant_range);
+        self
+    }
+}
@@ -0,0 +1,166 @@
+use web_time::Duration;
+
+/// Frame timing of a Renderer.
+/// Wall time follows the event loop (or the headless timeline), simulation time
+/// only advances while the simulation is stepped, so it stops while paused or replaying.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clock {
+    wall_time: f64,
+    wall_delta: f64,
+    time: f64,
+    delta_time: f64,
+    ticks: u64,
+}
+
+impl Clock {
+    /// Seconds since the start of the event loop, or the headless timeline
+    pub fn wall_time(&self) -> f64 {
+        self.wall_time
+    }
+
+    /// Wall seconds since the previous frame
+    pub fn wall_delta(&self) -> f64 {
+        self.wall_delta
+    }
+
+    /// Simulated seconds, the end of the current step
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Simulated seconds stepped this frame, zero while paused
+    pub fn delta_time(&self) -> f64 {
+        self.delta_time
+    }
+
+    /// Index of the current frame, counting from zero
+    /// Headless renders keep it at the index of the output frame, however many times
+    /// or on however many renderers the frame is drawn.
+    pub fn frame(&self) -> u64 {
+        self.ticks.saturating_sub(1)
+    }
+
+    /// Jump the simulation time, e.g. after restoring a saved state
+    pub fn seek(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Make frame the index of the next frame started, e.g. when renderers take turns
+    /// drawing the frames of a headless render. Frame 0 does not step
+    pub fn seek_frame(&mut self, frame: u64) {
+        self.ticks = frame;
+    }
+
+    /// Step the simulation to time, delta seconds after the previous step, within the current frame
+    /// For frames drawn in several steps, like sub-frames or the faces of a cubemap
+    pub(crate) fn step_to(&mut self, time: f64, delta: f64) {
+        self.time = time;
+        self.delta_time = delta;
+    }
+
+    /// Step to sub_frame of sub_frames splitting the delta seconds after start evenly,
+    /// within the current frame, for frames averaged from several sub-frames
+    pub(crate) fn sub_frame(&mut self, start: f64, delta: f64, sub_frame: u32, sub_frames: u32) {
+        let step = delta / sub_frames as f64;
+        // Only the first step of the first frame stands still
+        let delta = if self.ticks <= 1 && sub_frame == 0 {
+            0.0
+        } else {
+            step
+        };
+        self.step_to(start + step * sub_frame as f64, delta);
+    }
+
+    /// Start a new frame at wall_time, delta after the previous one.
+    /// The first frame does not step, so simulation time starts at zero
+    pub(crate) fn tick(&mut self, wall_time: f64, delta: Duration) {
+        let delta = delta.as_secs_f64();
+        self.wall_time = wall_time;
+        self.wall_delta = delta;
+        self.delta_time = if self.ticks == 0 { 0.0 } else { delta };
+        self.time += self.delta_time;
+        self.ticks += 1;
+    }
+
+    /// Do not step the simulation this frame
+    pub(crate) fn hold(&mut self) {
+        self.time -= self.delta_time;
+        self.delta_time = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn held_frames_do_not_advance_simulation_time() {
+        let mut clock = Clock::default();
+        let delta = Duration::from_millis(500);
+
+        clock.tick(0.0, delta);
+        assert_eq!(
+            (0, 0.0, 0.0),
+            (clock.frame(), clock.time(), clock.delta_time())
+        );
+
+        clock.tick(0.5, delta);
+        clock.tick(1.0, delta);
+        clock.hold();
+        assert_eq!(
+            (2, 0.5, 0.0),
+            (clock.frame(), clock.time(), clock.delta_time())
+        );
+        assert_eq!((1.0, 0.5), (clock.wall_time(), clock.wall_delta()));
+
+        clock.tick(1.5, delta);
+        assert_eq!(1.0, clock.time());
+    }
+
+    #[test]
+    fn headless_frames_keep_their_index() {
+        let mut clock = Clock::default();
+        let delta = Duration::from_millis(500);
+
+        // A renderer drawing every third frame
+        clock.seek_frame(3);
+        clock.tick(1.5, delta);
+        clock.seek(1.5);
+        assert_eq!(
+            (3, 1.5, 0.5),
+            (clock.frame(), clock.time(), clock.delta_time())
+        );
+
+        // Drawing the same moment again stays in the frame
+        clock.step_to(clock.time(), 0.0);
+        assert_eq!(
+            (3, 1.5, 0.0),
+            (clock.frame(), clock.time(), clock.delta_time())
+        );
+        assert_eq!((1.5, 0.5), (clock.wall_time(), clock.wall_delta()));
+    }
+
+    #[test]
+    fn sub_frames_stay_in_their_frame() {
+        let mut clock = Clock::default();
+        let delta = Duration::from_millis(500);
+
+        for frame in 0..2 {
+            let start = frame as f64 * 0.5;
+            clock.tick(start, delta);
+            for sub_frame in 0..4 {
+                clock.sub_frame(start, 0.5, sub_frame, 4);
+            }
+        }
+        assert_eq!(
+            (1, 0.875, 0.125),
+            (clock.frame(), clock.time(), clock.delta_time())
+        );
+        assert_eq!(0.5, clock.wall_delta());
+
+        let mut clock = Clock::default();
+        clock.tick(0.0, delta);
+        clock.sub_frame(0.0, 0.5, 0, 4);
+        assert_eq!((0, 0.0), (clock.frame(), clock.delta_time()));
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Allocation, FrameCounters};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+pub struct ComputePipeline {
+    pub pipeline: wgpu::ComputePipeline,
+    pub layout: wgpu::PipelineLayout,
+    /// Lists the pipeline in the inspector
+    pub(crate) _allocation: Allocation,
+}
+
+impl AsRef<wgpu::ComputePipeline> for ComputePipeline {
+    fn as_ref(&self) -> &wgpu::ComputePipeline {
+        &self.pipeline
+    }
+}
+
+impl Deref for ComputePipeline {
+    type Target = wgpu::ComputePipeline;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+/// Workgroups of workgroup_size needed to cover items, one invocation per item
+pub fn workgroups(items: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    [0, 1, 2].map(|axis| items[axis].div_ceil(workgroup_size[axis].max(1)))
+}
+
+/// A wgpu::ComputePass with helpers for binding and dispatching
+/// Everything else is forwarded to the wrapped pass through Deref.
+pub struct ComputePass<'a> {
+    pass: wgpu::ComputePass<'a>,
+    counters: Arc<FrameCounters>,
+}
+
+impl<'a> ComputePass<'a> {
+    pub(crate) fn new(pass: wgpu::ComputePass<'a>, counters: Arc<FrameCounters>) -> Self {
+        Self { pass, counters }
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: &wgpu::ComputePipeline) {
+        self.counters.record_pipeline_switch();
+        self.pass.set_pipeline(pipeline);
+    }
+
+    pub fn bind(&mut self, index: u32, bind_group: &wgpu::BindGroup) {
+        self.pass.set_bind_group(index, bind_group, &[]);
+    }
+
+    pub fn dispatch(&mut self, workgroups: [u32; 3]) {
+        let [x, y, z] = workgroups;
+        self.pass.dispatch_workgroups(x, y, z);
+    }
+
+    /// Dispatch enough workgroups of workgroup_size, the @workgroup_size of the shader,
+    /// for one invocation per item. Shaders must skip invocations past the items.
+    pub fn dispatch_items(&mut self, items: [u32; 3], workgroup_size: [u32; 3]) {
+        self.dispatch(workgroups(items, workgroup_size));
+    }
+}
+
+impl<'a> Deref for ComputePass<'a> {
+    type Target = wgpu::ComputePass<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pass
+    }
+}
+
+impl DerefMut for ComputePass<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pass
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn covers_items() {
+        assert_eq!([4, 1, 1], workgroups([256, 1, 1], [64, 1, 1]));
+        assert_eq!([5, 2, 1], workgroups([257, 9, 1], [64, 8, 1]));
+        assert_eq!([0, 1, 1], workgroups([0, 1, 1], [64, 1, 1]));
+    }
+}
@@ -0,0 +1,171 @@
+use super::{BlendMode, BloomSettings, Layer};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Rendering preset loaded at startup before Simulation::new is called
+static DEFAULT_CONFIG: Mutex<Option<RenderConfig>> = Mutex::new(None);
+
+#[derive(Error, Debug)]
+pub enum RenderConfigError {
+    #[error("failed to read or write render configuration: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse render configuration TOML: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("failed to serialize render configuration TOML: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// An added layer of a RenderConfig
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct LayerConfig {
+    pub layer: Layer,
+    #[serde(default)]
+    pub blend: BlendMode,
+    #[serde(default = "visible_default")]
+    pub visible: bool,
+}
+
+fn visible_default() -> bool {
+    true
+}
+
+/// The rendering setup of a Renderer, independent of the Simulation's code
+/// Stored as TOML so presets can be shared, missing keys keep their defaults.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// Linear RGBA the render target is cleared to
+    pub clear_color: [f64; 4],
+    pub show_stats: bool,
+    /// Samples per pixel the simulation is drawn with, 1 disables MSAA
+    pub msaa_samples: u32,
+    /// A .cube file to grade frames with, see Renderer::load_color_lut
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lut: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bloom: Option<BloomSettings>,
+    pub layers: Vec<LayerConfig>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            show_stats: false,
+            msaa_samples: 1,
+            lut: None,
+            bloom: None,
+            layers: vec![],
+        }
+    }
+}
+
+impl RenderConfig {
+    pub fn from_toml(src: &str) -> Result<Self, RenderConfigError> {
+        toml::from_str(src).map_err(|err| {
+            log::error!("aftgraphs::render::config::RenderConfig::from_toml: {err}");
+            err.into()
+        })
+    }
+
+    pub fn to_toml(&self) -> Result<String, RenderConfigError> {
+        toml::to_string_pretty(self).map_err(|err| {
+            log::error!("aftgraphs::render::config::RenderConfig::to_toml: {err}");
+            err.into()
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, RenderConfigError> {
+        let src = std::fs::read_to_string(path)?;
+        RenderConfig::from_toml(src.as_str())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), RenderConfigError> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// Use config for every Renderer created from now on, before Simulation::new is called
+    /// The sim_main! macro sets it from its optional render configuration path,
+    /// the --render-config command line option takes precedence.
+    pub fn set_default(config: Option<RenderConfig>) {
+        *DEFAULT_CONFIG
+            .lock()
+            .expect("aftgraphs::render::config::RenderConfig::set_default: poisoned lock") = config;
+    }
+
+    pub fn default_config() -> Option<RenderConfig> {
+        DEFAULT_CONFIG
+            .lock()
+            .expect("aftgraphs::render::config::RenderConfig::default_config: poisoned lock")
+            .clone()
+    }
+
+    /// The configuration to apply to a new Renderer, from --render-config or the default
+    pub(crate) async fn startup() -> Option<RenderConfig> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = crate::cli::ARGUMENTS.read().await.render_config.clone();
+            if let Some(path) = path {
+                match RenderConfig::load(&path) {
+                    Ok(config) => return Some(config),
+                    Err(err) => log::error!(
+                        "aftgraphs::render::config::RenderConfig::startup: failed to load {}: {err}",
+                        path.display()
+                    ),
+                }
+            }
+        }
+
+        RenderConfig::default_config()
+    }
+
+    pub(crate) fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.clear_color;
+        wgpu::Color { r, g, b, a }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toml_round_trip() {
+        let config = RenderConfig::from_toml(
+            r#"
+            clear_color = [0.1, 0.2, 0.3, 1.0]
+            lut = "film.cube"
+
+            [bloom]
+            intensity = 1.5
+
+            [[layers]]
+            layer = "overlay"
+            blend = "additive"
+            "#,
+        )
+        .unwrap();
+
+        assert!(!config.show_stats);
+        assert_eq!(1, config.msaa_samples);
+        assert_eq!(Some(PathBuf::from("film.cube")), config.lut);
+        assert_eq!(Some(1.5), config.bloom.map(|bloom| bloom.intensity));
+        assert_eq!(
+            vec![LayerConfig {
+                layer: Layer::Overlay,
+                blend: BlendMode::Additive,
+                visible: true,
+            }],
+            config.layers
+        );
+        assert_eq!(
+            config,
+            RenderConfig::from_toml(config.to_toml().unwrap().as_str()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use super::ViewParams;
+use crate::stereo::{cross, dot};
+use crate::uniform::Mat4;
+
+/// Faces of a cubemap, in the order of the layers of a cube texture
+/// During a cubemap capture Renderer::cube_face is the face being drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PosX = 0,
+    NegX = 1,
+    PosY = 2,
+    NegY = 3,
+    PosZ = 4,
+    NegZ = 5,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    /// Direction the face looks toward and its up, with -Z ahead and +Y up like StereoCamera
+    fn basis(self) -> ([f32; 3], [f32; 3]) {
+        match self {
+            CubeFace::PosX => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            CubeFace::NegX => ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            CubeFace::PosY => ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+            CubeFace::NegY => ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+            CubeFace::PosZ => ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            CubeFace::NegZ => ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        }
+    }
+
+    /// World to view space of a camera at position looking through the face
+    pub fn view(self, position: [f32; 3]) -> Mat4 {
+        let (forward, up) = self.basis();
+        let right = cross(forward, up);
+        Mat4([
+            [right[0], up[0], -forward[0], 0.0],
+            [right[1], up[1], -forward[1], 0.0],
+            [right[2], up[2], -forward[2], 0.0],
+            [
+                -dot(right, position),
+                -dot(up, position),
+                dot(forward, position),
+                1.0,
+            ],
+        ])
+    }
+
+    /// Square perspective projection with a 90 degree field of view, depth in [0, 1]
+    pub fn projection(near: f32, far: f32) -> Mat4 {
+        let depth = near - far;
+        Mat4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, far / depth, -1.0],
+            [0.0, 0.0, near * far / depth, 0.0],
+        ])
+    }
+
+    /// The face's view for Renderer::set_views
+    pub fn view_params(self, position: [f32; 3], near: f32, far: f32) -> ViewParams {
+        ViewParams::new(self.view(position), Self::projection(near, far), position)
+    }
+
+    /// The face a direction leaves the cube through, and where in NDC of the face
+    fn locate(direction: [f32; 3]) -> (CubeFace, [f32; 2]) {
+        let [x, y, z] = direction.map(f32::abs);
+        let face = if x >= y && x >= z {
+            match direction[0] >= 0.0 {
+                true => CubeFace::PosX,
+                false => CubeFace::NegX,
+            }
+        } else if y >= z {
+            match direction[1] >= 0.0 {
+                true => CubeFace::PosY,
+                false => CubeFace::NegY,
+            }
+        } else {
+            match direction[2] >= 0.0 {
+                true => CubeFace::PosZ,
+                false => CubeFace::NegZ,
+            }
+        };
+
+        let (forward, up) = face.basis();
+        let right = cross(forward, up);
+        let depth = dot(direction, forward);
+        (
+            face,
+            [dot(direction, right) / depth, dot(direction, up) / depth],
+        )
+    }
+}
+
+/// Converts the faces of a cubemap to an equirectangular image, the projection of 360° video.
+/// Longitude runs from behind (+Z) on the left through ahead (-Z) in the center,
+/// latitude from straight up on the top row to straight down on the bottom one
+pub struct Equirectangular {
+    face_size: u32,
+    size: [u32; 2],
+    /// Sine and cosine of the longitude of each column
+    columns: Vec<(f32, f32)>,
+    /// Sine and cosine of the latitude of each row
+    rows: Vec<(f32, f32)>,
+}
+
+impl Equirectangular {
+    /// Conversion of faces of face_size pixels square to an image of size
+    pub fn new(face_size: u32, size: [u32; 2]) -> Self {
+        use std::f32::consts::{PI, TAU};
+
+        let columns = (0..size[0])
+            .map(|column| ((column as f32 + 0.5) / size[0] as f32 * TAU - PI).sin_cos())
+            .collect();
+        let rows = (0..size[1])
+            .map(|row| (PI / 2.0 - (row as f32 + 0.5) / size[1] as f32 * PI).sin_cos())
+            .collect();
+        Self {
+            face_size,
+            size,
+            columns,
+            rows,
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Bilinear sample of a face at NDC x, y, 4 bytes per pixel
+    fn sample(&self, face: &[u8], face_stride: usize, [x, y]: [f32; 2]) -> [u8; 4] {
+        let last = self.face_size.max(1) - 1;
+        let size = self.face_size as f32;
+        // Pixel rows go down, NDC up
+        let px = ((x + 1.0) / 2.0 * size - 0.5).clamp(0.0, last as f32);
+        let py = ((1.0 - y) / 2.0 * size - 0.5).clamp(0.0, last as f32);
+        let (x0, y0) = (px.floor() as u32, py.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(last), (y0 + 1).min(last));
+        let (fx, fy) = (px.fract(), py.fract());
+
+        let texel = |x: u32, y: u32, channel: usize| {
+            face[y as usize * face_stride + x as usize * 4 + channel] as f32
+        };
+        std::array::from_fn(|channel| {
+            let top = texel(x0, y0, channel) * (1.0 - fx) + texel(x1, y0, channel) * fx;
+            let bottom = texel(x0, y1, channel) * (1.0 - fx) + texel(x1, y1, channel) * fx;
+            (top * (1.0 - fy) + bottom * fy).round() as u8
+        })
+    }
+
+    /// Write the image of faces, indexed by CubeFace, into out.
+    /// Face rows are face_stride bytes apart and image rows stride bytes, 4 bytes per pixel
+    pub fn convert(&self, faces: &[Vec<u8>], face_stride: usize, out: &mut [u8], stride: usize) {
+        for (row, &(sin_lat, cos_lat)) in self.rows.iter().enumerate() {
+            let out_row = &mut out[row * stride..];
+            for (column, &(sin_lon, cos_lon)) in self.columns.iter().enumerate() {
+                let direction = [cos_lat * sin_lon, sin_lat, -cos_lat * cos_lon];
+                let (face, ndc) = CubeFace::locate(direction);
+                let texel = self.sample(&faces[face as usize], face_stride, ndc);
+                out_row[column * 4..column * 4 + 4].copy_from_slice(&texel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transform(matrix: Mat4, point: [f32; 3]) -> [f32; 4] {
+        std::array::from_fn(|row| {
+            matrix[0][row] * point[0]
+                + matrix[1][row] * point[1]
+                + matrix[2][row] * point[2]
+                + matrix[3][row]
+        })
+    }
+
+    #[test]
+    fn faces_match_their_views_and_unwrap_around_the_camera() {
+        let directions = [
+            [0.3, -0.2, -0.9],
+            [0.8, 0.5, 0.1],
+            [-0.6, 0.9, 0.4],
+            [0.1, -0.7, 0.2],
+            [-0.9, 0.3, -0.5],
+            [0.2, 0.1, 0.95],
+        ];
+        let position = [1.0, 2.0, 3.0];
+        for direction in directions {
+            let (face, [x, y]) = CubeFace::locate(direction);
+            let point = std::array::from_fn(|idx| position[idx] + direction[idx]);
+            let view_projection = face.view_params(position, 0.1, 10.0).view_projection;
+            let clip = transform(view_projection, point);
+            assert!((clip[0] / clip[3] - x).abs() < 1e-5);
+            assert!((clip[1] / clip[3] - y).abs() < 1e-5);
+            assert!((0.0..=1.0).contains(&(clip[2] / clip[3])));
+        }
+
+        // Faces filled with their index show where the image samples them
+        let face_size = 4;
+        let faces: Vec<Vec<u8>> = CubeFace::ALL
+            .iter()
+            .map(|&face| vec![face as u8; face_size * face_size * 4])
+            .collect();
+        let equirect = Equirectangular::new(face_size as u32, [16, 8]);
+        let stride = 16 * 4;
+        let mut out = vec![0; stride * 8];
+        equirect.convert(&faces, face_size * 4, &mut out, stride);
+        let at = |column: usize, row: usize| out[row * stride + column * 4];
+        assert_eq!(CubeFace::PosZ as u8, at(0, 4));
+        assert_eq!(CubeFace::NegX as u8, at(4, 4));
+        assert_eq!(CubeFace::NegZ as u8, at(8, 4));
+        assert_eq!(CubeFace::PosX as u8, at(12, 4));
+        assert_eq!(CubeFace::PosY as u8, at(8, 0));
+        assert_eq!(CubeFace::NegY as u8, at(3, 7));
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+use winit::{
+    event_loop::ActiveEventLoop,
+    window::{Cursor, CursorIcon, CustomCursor},
+};
+
+/// Mouse cursor shown over the simulation in display mode, see Renderer::set_cursor
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CursorStyle {
+    /// The system's default arrow
+    #[default]
+    Default,
+    /// No cursor, for simulations drawing their own indicator
+    Hidden,
+    /// A system cursor icon
+    Icon(CursorIcon),
+    /// A custom image
+    Image(CursorImage),
+}
+
+/// Rgba8 cursor image of width * height pixels, hotspot is the clicked pixel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorImage {
+    pub width: u16,
+    pub height: u16,
+    pub hotspot: [u16; 2],
+    pub rgba: Arc<[u8]>,
+}
+
+impl CursorStyle {
+    /// The winit cursor to set and whether the cursor is visible
+    pub(crate) fn to_winit(&self, event_loop: &ActiveEventLoop) -> (Cursor, bool) {
+        match self {
+            CursorStyle::Default => (CursorIcon::Default.into(), true),
+            CursorStyle::Hidden => (CursorIcon::Default.into(), false),
+            CursorStyle::Icon(icon) => ((*icon).into(), true),
+            CursorStyle::Image(image) => {
+                let source = CustomCursor::from_rgba(
+                    image.rgba.to_vec(),
+                    image.width,
+                    image.height,
+                    image.hotspot[0],
+                    image.hotspot[1],
+                );
+                match source {
+                    Ok(source) => (event_loop.create_custom_cursor(source).into(), true),
+                    Err(e) => {
+                        log::warn!("aftgraphs::render::cursor::CursorStyle::to_winit: invalid cursor image: {e}");
+                        (CursorIcon::Default.into(), true)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CursorState {
+    style: CursorStyle,
+    /// The window cursor does not show style yet
+    dirty: bool,
+    /// The mouse is over a ui window, which sets its own cursors
+    over_ui: bool,
+}
+
+/// The cursor requested by the simulation, applied to the window by the event loop
+#[derive(Debug, Default)]
+pub(crate) struct CursorRequest(Mutex<CursorState>);
+
+impl CursorRequest {
+    fn lock(&self) -> std::sync::MutexGuard<'_, CursorState> {
+        self.0
+            .lock()
+            .expect("aftgraphs::render::cursor::CursorRequest: poisoned lock")
+    }
+
+    pub fn set(&self, style: CursorStyle) {
+        let mut state = self.lock();
+        if state.style != style {
+            state.style = style;
+            state.dirty = true;
+        }
+    }
+
+    pub fn style(&self) -> CursorStyle {
+        self.lock().style.clone()
+    }
+
+    /// Track the mouse entering and leaving the ui, whose cursors replace the simulation's
+    pub fn set_over_ui(&self, over_ui: bool) {
+        let mut state = self.lock();
+        if state.over_ui && !over_ui {
+            state.dirty = true;
+        }
+        state.over_ui = over_ui;
+    }
+
+    /// The style to apply to the window, if it changed and the ui does not own the cursor
+    pub fn take_change(&self) -> Option<CursorStyle> {
+        let mut state = self.lock();
+        if !state.dirty || state.over_ui {
+            return None;
+        }
+        state.dirty = false;
+        Some(state.style.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reapplied_after_leaving_ui() {
+        let request = CursorRequest::default();
+        assert_eq!(None, request.take_change());
+
+        request.set(CursorStyle::Hidden);
+        request.set_over_ui(true);
+        assert_eq!(None, request.take_change());
+
+        request.set_over_ui(false);
+        assert_eq!(Some(CursorStyle::Hidden), request.take_change());
+        assert_eq!(None, request.take_change());
+
+        request.set_over_ui(true);
+        request.set_over_ui(false);
+        assert_eq!(Some(CursorStyle::Hidden), request.take_change());
+    }
+}
@@ -0,0 +1,62 @@
+use super::{ComputePass, FrameCounters, RenderPass};
+use crate::texture::RenderTarget;
+use std::sync::Arc;
+
+/// The encoder of the frame, handed to Simulation::encode before the frame's own render passes
+/// Compute and render passes begun on it run in the order they were begun, so that
+/// Simulation::render can draw what they computed and sample what they drew.
+pub struct Frame<'a> {
+    encoder: &'a mut wgpu::CommandEncoder,
+    counters: Arc<FrameCounters>,
+}
+
+impl<'a> Frame<'a> {
+    pub(crate) fn new(encoder: &'a mut wgpu::CommandEncoder, counters: Arc<FrameCounters>) -> Self {
+        Self { encoder, counters }
+    }
+
+    pub fn begin_compute_pass(&mut self, label: Option<&str>) -> ComputePass<'_> {
+        let pass = self
+            .encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label,
+                timestamp_writes: None,
+            });
+        ComputePass::new(pass, self.counters.clone())
+    }
+
+    /// Begin a pass drawing into target
+    /// Pipelines drawn in the pass must match target, see RenderTarget::color_target.
+    pub fn begin_pass(
+        &mut self,
+        label: Option<&str>,
+        target: &RenderTarget,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> RenderPass<'_> {
+        self.begin_pass_with(&wgpu::RenderPassDescriptor {
+            label,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Begin a pass with any attachments, such as several targets or a depth buffer
+    pub fn begin_pass_with(&mut self, desc: &wgpu::RenderPassDescriptor<'_>) -> RenderPass<'_> {
+        let pass = self.encoder.begin_render_pass(desc);
+        RenderPass::new(pass, self.counters.clone())
+    }
+
+    /// The wrapped encoder, for copies between the passes
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        self.encoder
+    }
+}
@@ -0,0 +1,461 @@
+use super::post::{draw_fullscreen, fullscreen_pipeline, post_module, PostPipeline, PostResources};
+use super::{Allocation, BindGroupLayout, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use half::f16;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Largest table a .cube file may hold along each axis
+const MAX_LUT_SIZE: u32 = 256;
+
+#[derive(Error, Debug)]
+pub enum CubeError {
+    #[error("failed to read .cube file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {line} of .cube file: {message}")]
+    Parse { line: usize, message: String },
+    #[error("1D LUTs are not supported, expected LUT_3D_SIZE")]
+    Unsupported1d,
+    #[error(".cube file is missing LUT_3D_SIZE")]
+    MissingSize,
+    #[error(".cube file should have {expected} table entries, found {found}")]
+    EntryCount { expected: usize, found: usize },
+}
+
+/// A 3D color lookup table, as stored in Adobe and Resolve .cube files
+/// The table is indexed with red changing fastest, then green, then blue.
+/// Colors are looked up by their display (sRGB encoded) values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    pub title: Option<String>,
+    pub size: u32,
+    /// Input color mapped to the first entry along each axis
+    pub domain_min: [f32; 3],
+    /// Input color mapped to the last entry along each axis
+    pub domain_max: [f32; 3],
+    pub table: Vec<[f32; 3]>,
+}
+
+fn parse_floats<const N: usize>(line: usize, tokens: &[&str]) -> Result<[f32; N], CubeError> {
+    let error = |message: String| CubeError::Parse { line, message };
+    if tokens.len() != N {
+        return Err(error(format!(
+            "expected {N} numbers, found {}",
+            tokens.len()
+        )));
+    }
+
+    let mut values = [0.0; N];
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token
+            .parse()
+            .map_err(|_| error(format!("{token} is not a number")))?;
+    }
+    Ok(values)
+}
+
+impl Lut3d {
+    /// The table that leaves every color as it is
+    pub fn identity(size: u32) -> Self {
+        let size = size.clamp(2, MAX_LUT_SIZE);
+        let scale = (size - 1) as f32;
+        let table = (0..size.pow(3))
+            .map(|index| {
+                [index % size, index / size % size, index / size / size]
+                    .map(|coordinate| coordinate as f32 / scale)
+            })
+            .collect();
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table,
+        }
+    }
+
+    pub fn from_cube(src: &str) -> Result<Self, CubeError> {
+        let mut lut = Self {
+            title: None,
+            size: 0,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table: vec![],
+        };
+
+        for (index, text) in src.lines().enumerate() {
+            let line = index + 1;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            match tokens[0] {
+                "TITLE" => {
+                    lut.title = Some(text["TITLE".len()..].trim().trim_matches('"').to_owned());
+                }
+                "LUT_1D_SIZE" => return Err(CubeError::Unsupported1d),
+                "LUT_3D_SIZE" => {
+                    let [size] = parse_floats::<1>(line, &tokens[1..])?;
+                    if size.fract() != 0.0 || !(2.0..=MAX_LUT_SIZE as f32).contains(&size) {
+                        return Err(CubeError::Parse {
+                            line,
+                            message: format!("LUT_3D_SIZE must be in 2..={MAX_LUT_SIZE}"),
+                        });
+                    }
+                    lut.size = size as u32;
+                }
+                "DOMAIN_MIN" => lut.domain_min = parse_floats(line, &tokens[1..])?,
+                "DOMAIN_MAX" => lut.domain_max = parse_floats(line, &tokens[1..])?,
+                "LUT_3D_INPUT_RANGE" => {
+                    let [min, max] = parse_floats(line, &tokens[1..])?;
+                    lut.domain_min = [min; 3];
+                    lut.domain_max = [max; 3];
+                }
+                keyword if keyword.parse::<f32>().is_err() => {
+                    log::debug!(
+                        "aftgraphs::render::grade::Lut3d::from_cube: ignoring keyword {keyword} on line {line}"
+                    );
+                }
+                _ => lut.table.push(parse_floats(line, &tokens)?),
+            }
+        }
+
+        if lut.size == 0 {
+            return Err(CubeError::MissingSize);
+        }
+        let expected = lut.size.pow(3) as usize;
+        if lut.table.len() != expected {
+            return Err(CubeError::EntryCount {
+                expected,
+                found: lut.table.len(),
+            });
+        }
+        Ok(lut)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, CubeError> {
+        let src = std::fs::read_to_string(path)?;
+        Self::from_cube(src.as_str())
+    }
+}
+
+/// Texels of the LUT texture, the table as half precision RGBA
+fn lut_texels(lut: &Lut3d) -> Vec<f16> {
+    lut.table
+        .iter()
+        .flat_map(|&[r, g, b]| [r, g, b, 1.0].map(f16::from_f32))
+        .collect()
+}
+
+/// Uniforms of res/grade.wgsl
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GradeUniform {
+    domain_min: [f32; 4],
+    domain_max: [f32; 4],
+    srgb: u32,
+    lut_size: f32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for GradeUniform {}
+unsafe impl bytemuck::Pod for GradeUniform {}
+
+struct LutResources {
+    size: u32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    uniform: wgpu::Buffer,
+    /// Binds the LUT and the uniform at group 1
+    bind_group: wgpu::BindGroup,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+/// The grading step of one frame
+pub(crate) struct GradePass {
+    lut: Arc<LutResources>,
+    pipeline: Arc<PostPipeline>,
+}
+
+impl GradePass {
+    /// Grade the frame source binds into view
+    pub(crate) fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::BindGroup,
+        view: &wgpu::TextureView,
+    ) {
+        draw_fullscreen(
+            encoder,
+            "aftgraphs::render::grade::ColorGrade",
+            view,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            &self.pipeline.pipeline,
+            &[source, &self.lut.bind_group],
+        );
+    }
+}
+
+/// The color grading LUT of a PostChain and the resources to apply it
+#[derive(Default)]
+pub(crate) struct ColorGrade {
+    lut: Option<Arc<LutResources>>,
+    /// File the LUT was loaded from, kept in RenderConfig presets
+    pub(crate) source: Option<PathBuf>,
+    /// Format of the frames the uniform was last written for
+    written: Option<wgpu::TextureFormat>,
+    layout: Option<BindGroupLayout>,
+    pipelines: HashMap<wgpu::TextureFormat, Arc<PostPipeline>>,
+}
+
+impl ColorGrade {
+    pub(crate) fn set_lut<P: UiPlatform>(&mut self, renderer: &Renderer<P>, lut: Option<&Lut3d>) {
+        self.source = None;
+        self.written = None;
+        let layout = &*self
+            .layout
+            .get_or_insert_with(|| ColorGrade::create_layout(renderer));
+        self.lut = lut.map(|lut| Arc::new(ColorGrade::create_lut(renderer, lut, layout)));
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.lut.is_some()
+    }
+
+    /// The grading step of this frame, None if no LUT is set
+    pub(crate) fn prepare<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+    ) -> Option<GradePass> {
+        let lut = self.lut.clone()?;
+        let format = resources.format;
+
+        if self.written != Some(format) {
+            let [min_r, min_g, min_b] = lut.domain_min;
+            let [max_r, max_g, max_b] = lut.domain_max;
+            let uniform = GradeUniform {
+                domain_min: [min_r, min_g, min_b, 0.0],
+                domain_max: [max_r, max_g, max_b, 1.0],
+                srgb: format.is_srgb() as u32,
+                lut_size: lut.size as f32,
+                _padding: [0; 2],
+            };
+            renderer
+                .queue
+                .write_buffer(&lut.uniform, 0, bytemuck::bytes_of(&uniform));
+            renderer.record_upload(std::mem::size_of::<GradeUniform>());
+            self.written = Some(format);
+        }
+
+        let layout = &*self
+            .layout
+            .get_or_insert_with(|| ColorGrade::create_layout(renderer));
+        let pipeline = self
+            .pipelines
+            .entry(format)
+            .or_insert_with(|| Arc::new(ColorGrade::create_pipeline(renderer, resources, layout)))
+            .clone();
+
+        Some(GradePass { lut, pipeline })
+    }
+
+    fn create_lut<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        lut: &Lut3d,
+        layout: &wgpu::BindGroupLayout,
+    ) -> LutResources {
+        let size = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::render::grade::ColorGrade::lut"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let texels = lut_texels(lut);
+        let data: &[u8] = bytemuck::cast_slice(&texels);
+        renderer.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(lut.size * 8),
+                rows_per_image: Some(lut.size),
+            },
+            size,
+        );
+        renderer.record_upload(data.len());
+
+        let uniform = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("aftgraphs::render::grade::ColorGrade::uniform"),
+            size: std::mem::size_of::<GradeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::grade::ColorGrade::lut"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            });
+
+        LutResources {
+            size: lut.size,
+            domain_min: lut.domain_min,
+            domain_max: lut.domain_max,
+            uniform,
+            bind_group,
+            _texture: texture,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::grade::ColorGrade::lut"),
+                data.len() as u64,
+            ),
+        }
+    }
+
+    fn create_layout<P: UiPlatform>(renderer: &Renderer<P>) -> BindGroupLayout {
+        super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::grade::ColorGrade::layout"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer)
+    }
+
+    fn create_pipeline<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+        layout: &wgpu::BindGroupLayout,
+    ) -> PostPipeline {
+        let label = "aftgraphs::render::grade::ColorGrade::pipeline";
+        let module = post_module(
+            renderer,
+            label,
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/grade.wgsl")),
+        );
+        let pipeline = fullscreen_pipeline(
+            renderer,
+            label,
+            &module,
+            "fs_main",
+            &[resources.layout, layout],
+            wgpu::ColorTargetState {
+                format: resources.format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+        );
+
+        PostPipeline::new(renderer, label, pipeline)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_cube_files() {
+        let lut = Lut3d::from_cube(
+            r#"
+            # Swaps red and blue
+            TITLE "swap"
+            LUT_3D_SIZE 2
+            DOMAIN_MIN 0 0 0
+            DOMAIN_MAX 1 1 1
+
+            0 0 0
+            0 0 1
+            0 1 0
+            0 1 1
+            1 0 0
+            1 0 1
+            1 1 0
+            1 1 1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some("swap"), lut.title.as_deref());
+        assert_eq!(2, lut.size);
+        assert_eq!([0.0, 0.0, 1.0], lut.table[1]);
+        assert_eq!(Lut3d::identity(2).table.len(), lut.table.len());
+
+        assert!(matches!(
+            Lut3d::from_cube("LUT_3D_SIZE 2\n0 0 0\n"),
+            Err(CubeError::EntryCount {
+                expected: 8,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            Lut3d::from_cube("LUT_1D_SIZE 2\n"),
+            Err(CubeError::Unsupported1d)
+        ));
+        assert!(matches!(
+            Lut3d::from_cube("LUT_3D_SIZE 2\n0 0\n"),
+            Err(CubeError::Parse { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn converts_to_half_floats() {
+        let lut = Lut3d {
+            table: vec![[0.0, 1.0, 0.5], [-2.0, 65504.0, 1e6]],
+            ..Lut3d::identity(2)
+        };
+        let bits: Vec<u16> = lut_texels(&lut)
+            .iter()
+            .map(|texel| texel.to_bits())
+            .collect();
+        assert_eq!(
+            vec![0x0000, 0x3c00, 0x3800, 0x3c00, 0xc000, 0x7bff, 0x7c00, 0x3c00],
+            bits
+        );
+    }
+}
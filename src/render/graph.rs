@@ -0,0 +1,450 @@
+use super::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RenderGraphError {
+    #[error("render graph pass {pass} uses undeclared resource {resource}")]
+    UnknownResource { pass: String, resource: String },
+    #[error("render graph pass {pass} reads {resource}, which no other pass writes")]
+    UnwrittenResource { pass: String, resource: String },
+    #[error("render graph passes depend on each other in a cycle: {0:?}")]
+    Cycle(Vec<String>),
+    #[error("imported render graph resource {0} was not bound when executing")]
+    UnboundImport(String),
+}
+
+/// Size of a transient texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    /// The size of the render target, see Renderer::viewport_size
+    Target,
+    /// The size of the render target scaled by a factor, at least one pixel
+    Scale(f32),
+    Fixed([u32; 2]),
+}
+
+impl TextureSize {
+    fn resolve(self, target: [u32; 2]) -> [u32; 2] {
+        match self {
+            TextureSize::Target => target,
+            TextureSize::Scale(scale) => {
+                target.map(|side| ((side as f32 * scale).round() as u32).max(1))
+            }
+            TextureSize::Fixed(size) => size,
+        }
+    }
+}
+
+/// A texture created and kept by a RenderGraph for its passes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransientTexture {
+    pub size: TextureSize,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TransientTexture {
+    /// A texture of the render target's size, rendered to and sampled
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            size: TextureSize::Target,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    pub fn with_size(mut self, size: TextureSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Add usages, such as STORAGE_BINDING for compute passes
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage |= usage;
+        self
+    }
+}
+
+struct GraphTarget {
+    desc: TransientTexture,
+    size: [u32; 2],
+    view: wgpu::TextureView,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+enum GraphResource {
+    Transient(TransientTexture, Option<GraphTarget>),
+    /// Bound to a view owned elsewhere each time the graph is executed
+    Imported,
+}
+
+/// Views of the resources of a RenderGraph while its passes are recorded
+pub struct GraphResources<'a> {
+    views: HashMap<&'a str, (&'a wgpu::TextureView, [u32; 2])>,
+}
+
+impl GraphResources<'_> {
+    pub fn view(&self, name: &str) -> Option<&wgpu::TextureView> {
+        self.views.get(name).map(|(view, _)| *view)
+    }
+
+    /// Size in pixels of the resource
+    pub fn size(&self, name: &str) -> Option<[u32; 2]> {
+        self.views.get(name).map(|(_, size)| *size)
+    }
+}
+
+type RecordFn = Box<dyn FnMut(&mut wgpu::CommandEncoder, &GraphResources<'_>)>;
+
+struct GraphPass {
+    name: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+    record: RecordFn,
+}
+
+/// Passes declaring the named textures they read and write
+/// The graph orders the passes so every read follows the writes of the other passes,
+/// creates the transient textures and records every pass into one command encoder.
+/// wgpu inserts the barriers between passes from their usage of the textures.
+/// Declare the graph once, for example in Simulation::new, and execute it every frame
+/// before drawing, the results can then be sampled through RenderGraph::view.
+#[derive(Default)]
+pub struct RenderGraph {
+    resources: HashMap<String, GraphResource>,
+    passes: Vec<GraphPass>,
+    generation: u64,
+}
+
+/// Declares the resources of a pass, see RenderGraph::add_pass
+pub struct GraphPassBuilder<'g> {
+    graph: &'g mut RenderGraph,
+    name: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+}
+
+impl GraphPassBuilder<'_> {
+    pub fn reads(mut self, resource: &str) -> Self {
+        self.reads.push(resource.to_owned());
+        self
+    }
+
+    pub fn writes(mut self, resource: &str) -> Self {
+        self.writes.push(resource.to_owned());
+        self
+    }
+
+    /// Add the pass, record is called with the graph's encoder every time it is executed
+    pub fn record(
+        self,
+        record: impl FnMut(&mut wgpu::CommandEncoder, &GraphResources<'_>) + 'static,
+    ) {
+        self.graph.passes.push(GraphPass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            record: Box::new(record),
+        });
+    }
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a texture created by the graph
+    pub fn add_texture(&mut self, name: &str, desc: TransientTexture) -> &mut Self {
+        self.resources
+            .insert(name.to_owned(), GraphResource::Transient(desc, None));
+        self
+    }
+
+    /// Declare a texture bound with RenderGraph::execute, such as a history buffer
+    pub fn import(&mut self, name: &str) -> &mut Self {
+        self.resources
+            .insert(name.to_owned(), GraphResource::Imported);
+        self
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> GraphPassBuilder<'_> {
+        GraphPassBuilder {
+            graph: self,
+            name: name.to_owned(),
+            reads: vec![],
+            writes: vec![],
+        }
+    }
+
+    /// View of a transient texture, created by the first RenderGraph::execute
+    pub fn view(&self, name: &str) -> Option<&wgpu::TextureView> {
+        match self.resources.get(name)? {
+            GraphResource::Transient(_, target) => target.as_ref().map(|target| &target.view),
+            GraphResource::Imported => None,
+        }
+    }
+
+    /// Changes whenever transient textures are recreated, so bind groups
+    /// of their views can be cached until then
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Indices of the passes in execution order
+    /// Passes run in the order they were added unless a read needs a later write.
+    /// A pass reading and writing the same resource follows its earlier writers only.
+    pub fn schedule(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let mut writers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, pass) in self.passes.iter().enumerate() {
+            for resource in pass.reads.iter().chain(&pass.writes) {
+                if !self.resources.contains_key(resource) {
+                    return Err(RenderGraphError::UnknownResource {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+            for resource in &pass.writes {
+                writers.entry(resource.as_str()).or_default().push(idx);
+            }
+        }
+
+        // dependencies[idx] are the passes that have to run before pass idx
+        let mut dependencies = vec![BTreeSet::new(); self.passes.len()];
+        for resource_writers in writers.values() {
+            for pair in resource_writers.windows(2) {
+                dependencies[pair[1]].insert(pair[0]);
+            }
+        }
+        for (idx, pass) in self.passes.iter().enumerate() {
+            for resource in &pass.reads {
+                let resource_writers = writers
+                    .get(resource.as_str())
+                    .map_or(&[][..], Vec::as_slice);
+                let rewrites = pass.writes.contains(resource);
+                let before: Vec<_> = resource_writers
+                    .iter()
+                    .copied()
+                    .filter(|&writer| writer != idx && (!rewrites || writer < idx))
+                    .collect();
+                let imported =
+                    matches!(self.resources.get(resource), Some(GraphResource::Imported));
+                if before.is_empty() && !imported {
+                    return Err(RenderGraphError::UnwrittenResource {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+                dependencies[idx].extend(before);
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut scheduled = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len()).find(|&idx| {
+                !scheduled[idx] && dependencies[idx].iter().all(|&dep| scheduled[dep])
+            });
+            let Some(next) = next else {
+                return Err(RenderGraphError::Cycle(
+                    (0..self.passes.len())
+                        .filter(|&idx| !scheduled[idx])
+                        .map(|idx| self.passes[idx].name.clone())
+                        .collect(),
+                ));
+            };
+            scheduled[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    /// Record every pass in order and submit them, before the frame recorded by Renderer::render.
+    /// imports binds the views of the imported resources for this execution
+    pub fn execute<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        imports: &[(&str, &wgpu::TextureView, [u32; 2])],
+    ) -> Result<(), RenderGraphError> {
+        profiling::scope!("render graph");
+        let order = self.schedule()?;
+
+        let target = renderer.viewport_size();
+        for (name, resource) in &mut self.resources {
+            match resource {
+                GraphResource::Transient(desc, target_texture) => {
+                    let size = desc.size.resolve(target);
+                    let current = target_texture
+                        .as_ref()
+                        .is_some_and(|target| target.size == size && target.desc == *desc);
+                    if !current {
+                        *target_texture = Some(Self::create_target(renderer, name, *desc, size));
+                        self.generation += 1;
+                    }
+                }
+                GraphResource::Imported => {
+                    if !imports.iter().any(|(import, ..)| import == name) {
+                        return Err(RenderGraphError::UnboundImport(name.clone()));
+                    }
+                }
+            }
+        }
+
+        let RenderGraph {
+            resources, passes, ..
+        } = self;
+        let mut views: HashMap<&str, (&wgpu::TextureView, [u32; 2])> = resources
+            .iter()
+            .filter_map(|(name, resource)| match resource {
+                GraphResource::Transient(_, Some(target)) => {
+                    Some((name.as_str(), (&target.view, target.size)))
+                }
+                _ => None,
+            })
+            .collect();
+        views.extend(
+            imports
+                .iter()
+                .map(|&(name, view, size)| (name, (view, size))),
+        );
+        let graph_resources = GraphResources { views };
+
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("aftgraphs::render::graph::RenderGraph::execute"),
+            });
+        for idx in order {
+            let pass = &mut passes[idx];
+            encoder.push_debug_group(&format!("aftgraphs: graph pass {}", pass.name));
+            (pass.record)(&mut encoder, &graph_resources);
+            encoder.pop_debug_group();
+        }
+        renderer.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        name: &str,
+        desc: TransientTexture,
+        size: [u32; 2],
+    ) -> GraphTarget {
+        log::debug!(
+            "aftgraphs::render::graph::RenderGraph::create_target: Creating {}x{} texture {name}",
+            size[0],
+            size[1]
+        );
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bytes = desc.format.block_copy_size(None).unwrap_or(4) as u64;
+
+        GraphTarget {
+            desc,
+            size,
+            view,
+            _texture: texture,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some(name),
+                size[0] as u64 * size[1] as u64 * bytes,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn graph() -> RenderGraph {
+        let mut graph = RenderGraph::new();
+        let format = wgpu::TextureFormat::Rgba16Float;
+        graph
+            .add_texture("scene", TransientTexture::new(format))
+            .add_texture("bright", TransientTexture::new(format))
+            .import("history");
+        graph
+    }
+
+    #[test]
+    fn reads_follow_writes() {
+        let mut graph = graph();
+        graph
+            .add_pass("composite")
+            .reads("scene")
+            .reads("bright")
+            .record(|_, _| {});
+        graph
+            .add_pass("bright")
+            .reads("scene")
+            .writes("bright")
+            .record(|_, _| {});
+        graph
+            .add_pass("scene")
+            .reads("history")
+            .writes("scene")
+            .record(|_, _| {});
+        graph
+            .add_pass("tonemap")
+            .reads("scene")
+            .writes("scene")
+            .record(|_, _| {});
+
+        assert_eq!(Ok(vec![2, 3, 1, 0]), graph.schedule());
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let mut graph = graph();
+        graph
+            .add_pass("a")
+            .reads("scene")
+            .writes("bright")
+            .record(|_, _| {});
+        graph
+            .add_pass("b")
+            .reads("bright")
+            .writes("scene")
+            .record(|_, _| {});
+        assert_eq!(
+            Err(RenderGraphError::Cycle(vec![
+                "a".to_owned(),
+                "b".to_owned()
+            ])),
+            graph.schedule()
+        );
+
+        let mut graph = RenderGraph::new();
+        graph.add_pass("a").writes("scene").record(|_, _| {});
+        assert!(matches!(
+            graph.schedule(),
+            Err(RenderGraphError::UnknownResource { .. })
+        ));
+
+        let mut graph = self::graph();
+        graph.add_pass("a").reads("scene").record(|_, _| {});
+        assert!(matches!(
+            graph.schedule(),
+            Err(RenderGraphError::UnwrittenResource { .. })
+        ));
+    }
+}
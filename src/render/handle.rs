@@ -0,0 +1,258 @@
+use super::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use std::{
+    collections::BTreeMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+use wgpu::util::DeviceExt;
+
+/// A resource created through a handle that is still alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveHandle {
+    pub kind: ResourceKind,
+    pub label: Option<String>,
+}
+
+/// Tracks the textures and buffers behind TextureHandles and BufferHandles
+/// Handles destroy their resource as soon as the last clone drops, and in debug
+/// builds the handles still alive when a ResourceScope or the Renderer ends are
+/// reported as leaks.
+#[derive(Debug, Default)]
+pub(crate) struct ResourceManager {
+    next_id: AtomicU64,
+    live: Mutex<BTreeMap<u64, LiveHandle>>,
+}
+
+impl ResourceManager {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, LiveHandle>> {
+        self.live
+            .lock()
+            .expect("aftgraphs::render::handle::ResourceManager: poisoned lock")
+    }
+
+    fn register(&self, kind: ResourceKind, label: Option<&str>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(
+            id,
+            LiveHandle {
+                kind,
+                label: label.map(str::to_owned),
+            },
+        );
+        id
+    }
+
+    fn release(&self, id: u64) {
+        self.lock().remove(&id);
+    }
+
+    /// Live handles created at or after the id start
+    pub(crate) fn live_since(&self, start: u64) -> Vec<LiveHandle> {
+        self.lock()
+            .range(start..)
+            .map(|(_, live)| live.clone())
+            .collect()
+    }
+
+    fn report_leaks(&self, scope: &str, start: u64) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        for leak in self.live_since(start) {
+            log::warn!(
+                "aftgraphs::render::handle: {:?} {} outlived {scope}",
+                leak.kind,
+                leak.label.as_deref().unwrap_or("<unlabeled>")
+            );
+        }
+    }
+}
+
+impl Drop for ResourceManager {
+    fn drop(&mut self) {
+        self.report_leaks("the Renderer", 0);
+    }
+}
+
+/// Reports the handles created while it was alive that outlive it, in debug builds
+/// Hold one for a frame or a scenario to check that its resources are released.
+#[must_use = "the scope ends as soon as the ResourceScope is dropped"]
+pub struct ResourceScope {
+    name: String,
+    start: u64,
+    manager: Arc<ResourceManager>,
+}
+
+impl ResourceScope {
+    /// Handles created in the scope that are still alive
+    pub fn live(&self) -> Vec<LiveHandle> {
+        self.manager.live_since(self.start)
+    }
+}
+
+impl Drop for ResourceScope {
+    fn drop(&mut self) {
+        self.manager
+            .report_leaks(&format!("resource scope {}", self.name), self.start);
+    }
+}
+
+trait Destroy {
+    fn destroy(&self);
+}
+
+impl Destroy for wgpu::Texture {
+    fn destroy(&self) {
+        wgpu::Texture::destroy(self);
+    }
+}
+
+impl Destroy for wgpu::Buffer {
+    fn destroy(&self) {
+        wgpu::Buffer::destroy(self);
+    }
+}
+
+struct Tracked<T: Destroy> {
+    resource: T,
+    id: u64,
+    manager: Weak<ResourceManager>,
+    _allocation: Allocation,
+}
+
+impl<T: Destroy> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.resource.destroy();
+        if let Some(manager) = self.manager.upgrade() {
+            manager.release(self.id);
+        }
+    }
+}
+
+impl<T: Destroy> Tracked<T> {
+    fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        resource: T,
+        kind: ResourceKind,
+        label: Option<&str>,
+        bytes: u64,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            resource,
+            id: renderer.resources.register(kind, label),
+            manager: Arc::downgrade(&renderer.resources),
+            _allocation: renderer.track_memory(kind, label, bytes),
+        })
+    }
+}
+
+/// Reference-counted texture, destroyed when the last clone drops
+#[derive(Clone)]
+pub struct TextureHandle(Arc<Tracked<wgpu::Texture>>);
+
+impl Deref for TextureHandle {
+    type Target = wgpu::Texture;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.resource
+    }
+}
+
+/// Reference-counted buffer, destroyed when the last clone drops
+#[derive(Clone)]
+pub struct BufferHandle(Arc<Tracked<wgpu::Buffer>>);
+
+impl Deref for BufferHandle {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.resource
+    }
+}
+
+impl<P: UiPlatform> Renderer<'_, P> {
+    pub fn create_texture(&self, desc: &wgpu::TextureDescriptor<'_>) -> TextureHandle {
+        let texture = self.device.create_texture(desc);
+        let bytes = desc.format.block_copy_size(None).unwrap_or(4) as u64
+            * desc.size.width as u64
+            * desc.size.height as u64
+            * desc.size.depth_or_array_layers as u64
+            * desc.sample_count as u64;
+        TextureHandle(Tracked::new(
+            self,
+            texture,
+            ResourceKind::Texture,
+            desc.label,
+            bytes,
+        ))
+    }
+
+    pub fn create_buffer(&self, desc: &wgpu::BufferDescriptor<'_>) -> BufferHandle {
+        let buffer = self.device.create_buffer(desc);
+        BufferHandle(Tracked::new(
+            self,
+            buffer,
+            ResourceKind::Buffer,
+            desc.label,
+            desc.size,
+        ))
+    }
+
+    pub fn create_buffer_init(&self, desc: &wgpu::util::BufferInitDescriptor<'_>) -> BufferHandle {
+        let buffer = self.device.create_buffer_init(desc);
+        self.record_upload(desc.contents.len());
+        BufferHandle(Tracked::new(
+            self,
+            buffer,
+            ResourceKind::Buffer,
+            desc.label,
+            desc.contents.len() as u64,
+        ))
+    }
+
+    /// Start checking that the handles created from now on are dropped before the returned scope
+    pub fn resource_scope(&self, name: &str) -> ResourceScope {
+        ResourceScope {
+            name: name.to_owned(),
+            start: self.resources.next_id.load(Ordering::Relaxed),
+            manager: self.resources.clone(),
+        }
+    }
+
+    /// Handles that are still alive
+    pub fn live_handles(&self) -> Vec<LiveHandle> {
+        self.resources.live_since(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scopes_see_later_handles() {
+        let manager = ResourceManager::default();
+        let before = manager.register(ResourceKind::Buffer, Some("before"));
+        let start = manager.next_id.load(Ordering::Relaxed);
+        let inside = manager.register(ResourceKind::Texture, Some("inside"));
+        let leaked = manager.register(ResourceKind::Texture, None);
+
+        manager.release(inside);
+        assert_eq!(
+            vec![LiveHandle {
+                kind: ResourceKind::Texture,
+                label: None
+            }],
+            manager.live_since(start)
+        );
+
+        manager.release(before);
+        manager.release(leaked);
+        assert!(manager.live_since(0).is_empty());
+    }
+}
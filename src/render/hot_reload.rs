@@ -0,0 +1,296 @@
+use super::{Renderer, Shader};
+use crate::ui::UiPlatform;
+use crate::watch::Watch;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+
+/// A shader module, and the file it is recompiled from
+struct Stage {
+    /// None keeps the module the pipeline was built with
+    path: Option<PathBuf>,
+    module: wgpu::ShaderModule,
+    /// Group of the views header prepended to the source
+    views_group: Option<u32>,
+}
+
+impl Stage {
+    /// The module recompiled from the file, None if it has no file
+    fn compile<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        label: Option<&str>,
+    ) -> std::io::Result<Option<wgpu::ShaderModule>> {
+        let Some(ref path) = self.path else {
+            return Ok(None);
+        };
+        let mut source = std::fs::read_to_string(path)?;
+        if let Some(group) = self.views_group {
+            source = format!("{}\n{source}", super::views::views_header(group));
+        }
+        Ok(Some(renderer.device.create_shader_module(
+            wgpu::ShaderModuleDescriptor {
+                label,
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            },
+        )))
+    }
+}
+
+struct VertexBuffer {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+struct Fragment {
+    /// None uses the vertex stage's module
+    stage: Option<Stage>,
+    entry: Option<String>,
+    targets: Vec<Option<wgpu::ColorTargetState>>,
+}
+
+/// Everything needed to build a RenderPipeline again from its shader files
+pub(crate) struct HotReload {
+    label: Option<String>,
+    vertex: Stage,
+    vertex_entry: String,
+    buffers: Vec<VertexBuffer>,
+    fragment: Option<Fragment>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+    multiview: Option<NonZeroU32>,
+    /// Changes to the shader files
+    watch: Watch,
+}
+
+impl HotReload {
+    /// The description of a pipeline being built, None if none of its shaders has a file
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        label: Option<&str>,
+        vertex: Shader<'_>,
+        fragment: Option<Shader<'_>>,
+        fragment_uses_vertex: bool,
+        primitive: wgpu::PrimitiveState,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        multisample: wgpu::MultisampleState,
+        multiview: Option<NonZeroU32>,
+    ) -> Option<Self> {
+        let paths: Vec<PathBuf> = std::iter::once(&vertex)
+            .chain(fragment.as_ref())
+            .filter_map(|shader| shader.path.clone())
+            .collect();
+        if paths.is_empty() {
+            return None;
+        }
+
+        let fragment = if fragment_uses_vertex {
+            Some(Fragment {
+                stage: None,
+                entry: vertex.fs_entry.map(str::to_owned),
+                targets: vertex.targets,
+            })
+        } else {
+            fragment.map(|shader| Fragment {
+                entry: shader.fs_entry.map(str::to_owned),
+                targets: shader.targets,
+                stage: Some(Stage {
+                    path: shader.path,
+                    module: shader.shader,
+                    views_group: shader.views_group,
+                }),
+            })
+        };
+        let buffers = vertex
+            .buffers
+            .iter()
+            .map(|buffer| VertexBuffer {
+                array_stride: buffer.array_stride,
+                step_mode: buffer.step_mode,
+                attributes: buffer.attributes.to_vec(),
+            })
+            .collect();
+
+        Some(Self {
+            label: label.map(str::to_owned),
+            vertex: Stage {
+                path: vertex.path,
+                module: vertex.shader,
+                views_group: vertex.views_group,
+            },
+            vertex_entry: vertex.vs_entry.to_owned(),
+            buffers,
+            fragment,
+            primitive,
+            depth_stencil,
+            multisample,
+            multiview,
+            watch: Watch::new(paths, []),
+        })
+    }
+
+    /// A new pipeline if a shader file changed and it compiled
+    fn rebuild<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        layout: &wgpu::PipelineLayout,
+    ) -> Option<wgpu::RenderPipeline> {
+        if !self.watch.changed() {
+            return None;
+        }
+        let name = self.label.as_deref().unwrap_or("<unlabeled>");
+        log::info!("aftgraphs::render::hot_reload::HotReload::rebuild: recompiling {name}");
+
+        // Compilation errors are logged instead of reaching the device's error handler
+        renderer
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let modules = self
+            .vertex
+            .compile(renderer, self.label.as_deref())
+            .and_then(|vertex| {
+                let fragment = match self
+                    .fragment
+                    .as_ref()
+                    .and_then(|fragment| fragment.stage.as_ref())
+                {
+                    Some(stage) => stage.compile(renderer, self.label.as_deref())?,
+                    None => None,
+                };
+                Ok((vertex, fragment))
+            });
+        let (vertex, fragment) = match modules {
+            Ok(modules) => modules,
+            Err(e) => {
+                // Popped so the scope does not swallow later errors
+                let _ = pollster::block_on(renderer.device.pop_error_scope());
+                log::error!("aftgraphs::render::hot_reload::HotReload::rebuild: {name}: {e}");
+                return None;
+            }
+        };
+
+        let buffers: Vec<wgpu::VertexBufferLayout<'_>> = self
+            .buffers
+            .iter()
+            .map(|buffer| wgpu::VertexBufferLayout {
+                array_stride: buffer.array_stride,
+                step_mode: buffer.step_mode,
+                attributes: &buffer.attributes,
+            })
+            .collect();
+        let vertex_module = vertex.as_ref().unwrap_or(&self.vertex.module);
+        let fragment_state = self.fragment.as_ref().map(|state| wgpu::FragmentState {
+            module: match state.stage {
+                Some(ref stage) => fragment.as_ref().unwrap_or(&stage.module),
+                None => vertex_module,
+            },
+            entry_point: state.entry.as_deref(),
+            targets: &state.targets,
+            compilation_options: Default::default(),
+        });
+
+        let pipeline = renderer
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: self.label.as_deref(),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: vertex_module,
+                    entry_point: Some(&self.vertex_entry),
+                    buffers: &buffers,
+                    compilation_options: Default::default(),
+                },
+                fragment: fragment_state,
+                primitive: self.primitive,
+                depth_stencil: self.depth_stencil.clone(),
+                multisample: self.multisample,
+                multiview: self.multiview,
+                cache: renderer.pipeline_cache(),
+            });
+
+        if let Some(e) = pollster::block_on(renderer.device.pop_error_scope()) {
+            log::error!(
+                "aftgraphs::render::hot_reload::HotReload::rebuild: {name}: keeping the previous pipeline: {e}"
+            );
+            return None;
+        }
+
+        if let Some(vertex) = vertex {
+            self.vertex.module = vertex;
+        }
+        if let (Some(module), Some(stage)) = (
+            fragment,
+            self.fragment
+                .as_mut()
+                .and_then(|fragment| fragment.stage.as_mut()),
+        ) {
+            stage.module = module;
+        }
+        Some(pipeline)
+    }
+}
+
+/// A RenderPipeline built from shader files, rebuilt by the renderer when they change
+pub(crate) struct Reloadable {
+    hot_reload: Mutex<HotReload>,
+    /// Layout of the rebuilt pipelines, made from the same bind group layouts as the
+    /// pipeline's own, so its bind groups stay valid
+    layout: wgpu::PipelineLayout,
+    /// The last pipeline rebuilt, bound instead of the original by RenderPass::set_pipeline
+    pub(crate) pipeline: Mutex<Option<wgpu::RenderPipeline>>,
+}
+
+impl Reloadable {
+    pub(crate) fn new(hot_reload: HotReload, layout: wgpu::PipelineLayout) -> Self {
+        Self {
+            hot_reload: Mutex::new(hot_reload),
+            layout,
+            pipeline: Mutex::new(None),
+        }
+    }
+
+    fn poll<P: UiPlatform>(&self, renderer: &Renderer<P>) {
+        let pipeline = self
+            .hot_reload
+            .lock()
+            .expect("aftgraphs::render::hot_reload::Reloadable::poll: poisoned lock")
+            .rebuild(renderer, &self.layout);
+        if pipeline.is_some() {
+            *self
+                .pipeline
+                .lock()
+                .expect("aftgraphs::render::hot_reload::Reloadable::poll: poisoned lock") =
+                pipeline;
+        }
+    }
+}
+
+/// The pipelines of a Renderer built with ShaderBuilder::with_path
+#[derive(Default)]
+pub(crate) struct HotReloads(Mutex<Vec<Weak<Reloadable>>>);
+
+impl HotReloads {
+    pub(crate) fn register(&self, reloadable: &Arc<Reloadable>) {
+        self.0
+            .lock()
+            .expect("aftgraphs::render::hot_reload::HotReloads::register: poisoned lock")
+            .push(Arc::downgrade(reloadable));
+    }
+
+    /// Rebuild the pipelines whose shader files changed, forgetting dropped pipelines
+    pub(crate) fn poll<P: UiPlatform>(&self, renderer: &Renderer<P>) {
+        let reloadables: Vec<Arc<Reloadable>> = {
+            let mut pipelines = self
+                .0
+                .lock()
+                .expect("aftgraphs::render::hot_reload::HotReloads::poll: poisoned lock");
+            pipelines.retain(|pipeline| pipeline.strong_count() > 0);
+            pipelines.iter().filter_map(Weak::upgrade).collect()
+        };
+        for reloadable in reloadables {
+            reloadable.poll(renderer);
+        }
+    }
+}
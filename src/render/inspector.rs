@@ -0,0 +1,119 @@
+#[cfg(not(target_arch = "wasm32"))]
+use super::{MemoryUsage, ResourceInfo};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Pausing and single stepping of the simulation, see Renderer::set_paused
+#[derive(Default)]
+pub(crate) struct FrameStep {
+    paused: AtomicBool,
+    steps: AtomicU32,
+}
+
+impl FrameStep {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        if !paused {
+            self.steps.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn step(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+        self.steps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// If the next frame advances the simulation, using up a requested step when paused
+    pub(crate) fn advance(&self) -> bool {
+        !self.is_paused()
+            || self
+                .steps
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |steps| {
+                    steps.checked_sub(1)
+                })
+                .is_ok()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+/// Draw the inspector window listing the live crate-managed resources
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn draw_inspector(
+    ui: &imgui::Ui,
+    frame_step: &FrameStep,
+    usage: MemoryUsage,
+    resources: &[ResourceInfo],
+) {
+    ui.window("Inspector")
+        .size([480.0, 360.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            let paused = frame_step.is_paused();
+            if ui.button(if paused { "Resume" } else { "Pause" }) {
+                frame_step.set_paused(!paused);
+            }
+            ui.same_line();
+            if ui.button("Step") {
+                frame_step.step();
+            }
+
+            ui.text(match usage.budget {
+                Some(budget) => format!(
+                    "GPU memory: {} of {}",
+                    format_bytes(usage.used),
+                    format_bytes(budget)
+                ),
+                None => format!("GPU memory: {}", format_bytes(usage.used)),
+            });
+            ui.separator();
+
+            ui.columns(4, "resources", true);
+            for header in ["Kind", "Label", "Size", "Value"] {
+                ui.text(header);
+                ui.next_column();
+            }
+            ui.separator();
+
+            for resource in resources {
+                ui.text(format!("{:?}", resource.kind));
+                ui.next_column();
+                ui.text(resource.label.as_deref().unwrap_or("<unlabeled>"));
+                ui.next_column();
+                ui.text(format_bytes(resource.bytes));
+                ui.next_column();
+                ui.text(resource.value.as_deref().unwrap_or(""));
+                ui.next_column();
+            }
+            ui.columns(1, "resources", false);
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn steps_only_advance_paused_frames_once() {
+        let frame_step = FrameStep::default();
+        assert!(frame_step.advance());
+
+        frame_step.set_paused(true);
+        assert!(!frame_step.advance());
+
+        frame_step.step();
+        frame_step.step();
+        assert!(frame_step.advance());
+        assert!(frame_step.advance());
+        assert!(!frame_step.advance());
+    }
+}
@@ -0,0 +1,350 @@
+use super::{Allocation, BindGroupLayout, LayerConfig, Renderer, ResourceKind};
+use crate::input::InputValue;
+use crate::ui::UiPlatform;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Render layers, composited from Background up to Overlay
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Background,
+    Simulation,
+    Annotations,
+    Overlay,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 4] = [
+        Layer::Background,
+        Layer::Simulation,
+        Layer::Annotations,
+        Layer::Overlay,
+    ];
+
+    /// Name of the layer in the "layers.<name>" checkbox inputs
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::Simulation => "simulation",
+            Layer::Annotations => "annotations",
+            Layer::Overlay => "overlay",
+        }
+    }
+}
+
+/// How a layer is composited onto the layers below it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Cover the layers below, transparent pixels included
+    Replace,
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    /// Blend state for layer textures holding premultiplied alpha
+    fn blend_state(self) -> wgpu::BlendState {
+        let color = match self {
+            BlendMode::Replace => return wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => return wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Multiply => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        wgpu::BlendState {
+            color,
+            alpha: wgpu::BlendComponent::OVER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LayerState {
+    blend: BlendMode,
+    visible: bool,
+}
+
+/// Texture a layer is drawn into before compositing
+pub(crate) struct LayerTarget {
+    size: [u32; 2],
+    pub(crate) view: wgpu::TextureView,
+    pub(crate) bind_group: wgpu::BindGroup,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+/// A layer to draw this frame, pipeline is None if the layer is hidden
+pub(crate) struct LayerPass {
+    pub(crate) layer: Layer,
+    pub(crate) target: Arc<LayerTarget>,
+    pub(crate) pipeline: Option<Arc<wgpu::RenderPipeline>>,
+}
+
+/// The added layers of a Renderer and the resources to composite them
+#[derive(Default)]
+pub(crate) struct LayerStack {
+    layers: [Option<LayerState>; 4],
+    targets: [Option<Arc<LayerTarget>>; 4],
+    layout: Option<BindGroupLayout>,
+    pipelines: HashMap<BlendMode, Arc<wgpu::RenderPipeline>>,
+}
+
+impl LayerStack {
+    pub(crate) fn add(&mut self, layer: Layer, blend: BlendMode) {
+        self.layers[layer as usize] = Some(LayerState {
+            blend,
+            visible: true,
+        });
+    }
+
+    pub(crate) fn remove(&mut self, layer: Layer) {
+        self.layers[layer as usize] = None;
+        self.targets[layer as usize] = None;
+    }
+
+    pub(crate) fn set_visible(&mut self, layer: Layer, visible: bool) {
+        if let Some(ref mut state) = self.layers[layer as usize] {
+            state.visible = visible;
+        }
+    }
+
+    /// The added layers in compositing order
+    pub(crate) fn configs(&self) -> Vec<LayerConfig> {
+        Layer::ALL
+            .into_iter()
+            .filter_map(|layer| {
+                let state = self.layers[layer as usize]?;
+                Some(LayerConfig {
+                    layer,
+                    blend: state.blend,
+                    visible: state.visible,
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the added layers
+    pub(crate) fn set_configs(&mut self, configs: &[LayerConfig]) {
+        for layer in Layer::ALL {
+            self.remove(layer);
+        }
+        for config in configs {
+            self.add(config.layer, config.blend);
+            self.set_visible(config.layer, config.visible);
+        }
+    }
+
+    /// Layers to draw in compositing order, with their blend mode if visible
+    /// Empty if no layer was added, otherwise the Simulation layer is always drawn.
+    fn active(&self, inputs: &HashMap<String, InputValue>) -> Vec<(Layer, Option<BlendMode>)> {
+        if self.layers.iter().all(Option::is_none) {
+            return vec![];
+        }
+
+        Layer::ALL
+            .into_iter()
+            .filter_map(|layer| {
+                let state = self.layers[layer as usize].or_else(|| {
+                    (layer == Layer::Simulation).then_some(LayerState {
+                        blend: BlendMode::Alpha,
+                        visible: true,
+                    })
+                })?;
+
+                let visible = match inputs.get(&format!("layers.{}", layer.name())) {
+                    Some(&InputValue::CHECKBOX(visible)) => visible,
+                    _ => state.visible,
+                };
+                Some((layer, visible.then_some(state.blend)))
+            })
+            .collect()
+    }
+
+    /// Layers to draw this frame, (re)creating their targets if the render target resized
+    pub(crate) fn prepare<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        inputs: &HashMap<String, InputValue>,
+    ) -> Vec<LayerPass> {
+        let active = self.active(inputs);
+        if active.is_empty() {
+            return vec![];
+        }
+
+        let size = renderer.viewport_size();
+        let format = renderer
+            .config
+            .as_ref()
+            .map_or(wgpu::TextureFormat::Rgba8UnormSrgb, |config| config.format);
+        let layout = &*self
+            .layout
+            .get_or_insert_with(|| LayerStack::create_layout(renderer));
+
+        active
+            .into_iter()
+            .map(|(layer, blend)| {
+                let target = match self.targets[layer as usize] {
+                    Some(ref target) if target.size == size => target.clone(),
+                    _ => {
+                        let target = Arc::new(LayerStack::create_target(
+                            renderer, layer, size, format, layout,
+                        ));
+                        self.targets[layer as usize] = Some(target.clone());
+                        target
+                    }
+                };
+
+                let pipeline = blend.map(|blend| {
+                    self.pipelines
+                        .entry(blend)
+                        .or_insert_with(|| {
+                            Arc::new(LayerStack::create_pipeline(renderer, blend, format, layout))
+                        })
+                        .clone()
+                });
+
+                LayerPass {
+                    layer,
+                    target,
+                    pipeline,
+                }
+            })
+            .collect()
+    }
+
+    fn create_layout<P: UiPlatform>(renderer: &Renderer<P>) -> BindGroupLayout {
+        super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::layer::LayerStack::layout"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .build(renderer)
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        layer: Layer,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+    ) -> LayerTarget {
+        log::debug!(
+            "aftgraphs::render::layer::LayerStack::create_target: Creating {}x{} target for layer {}",
+            size[0],
+            size[1],
+            layer.name()
+        );
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::render::layer::LayerTarget"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::layer::LayerTarget"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            });
+        let bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+
+        LayerTarget {
+            size,
+            view,
+            bind_group,
+            _texture: texture,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::layer::LayerTarget"),
+                size[0] as u64 * size[1] as u64 * bytes,
+            ),
+        }
+    }
+
+    fn create_pipeline<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        blend: BlendMode,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader = super::ShaderBuilder::new()
+            .with_module(wgpu::include_wgsl!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/res/layer.wgsl"
+            )))
+            .with_default_fs_entrypoint()
+            .with_target(Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend.blend_state()),
+                write_mask: wgpu::ColorWrites::ALL,
+            }))
+            .build(renderer);
+
+        super::RenderPipelineBuilder::new()
+            .with_vertex_shader(shader)
+            .with_bind_group_layout(layout)
+            .with_multisample(wgpu::MultisampleState::default())
+            .with_layout_label(Some("aftgraphs::render::layer::LayerStack::pipeline"))
+            .with_pipeline_label(Some("aftgraphs::render::layer::LayerStack::pipeline"))
+            .build(renderer)
+            .expect("aftgraphs::render::layer::LayerStack::create_pipeline: pipeline without push constants failed to build")
+            .pipeline
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inputs_hide_layers() {
+        let mut stack = LayerStack::default();
+        assert!(stack.active(&HashMap::new()).is_empty());
+
+        stack.add(Layer::Overlay, BlendMode::Additive);
+        stack.add(Layer::Background, BlendMode::Replace);
+        let inputs = HashMap::from([("layers.overlay".to_owned(), InputValue::CHECKBOX(false))]);
+        assert_eq!(
+            vec![
+                (Layer::Background, Some(BlendMode::Replace)),
+                (Layer::Simulation, Some(BlendMode::Alpha)),
+                (Layer::Overlay, None),
+            ],
+            stack.active(&inputs)
+        );
+    }
+}
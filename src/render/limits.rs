@@ -0,0 +1,29 @@
+/// The limits renderers request their wgpu::Device with
+/// WebGL2 limits, raised to the downlevel defaults when the adapter runs compute shaders
+/// so that storage buffers and textures can be bound.
+pub(crate) fn required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    let limits = if adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+    {
+        wgpu::Limits::downlevel_defaults()
+    } else {
+        wgpu::Limits::downlevel_webgl2_defaults()
+    };
+
+    let mut limits = limits.using_resolution(adapter.limits());
+    if adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+        limits.max_push_constant_size = adapter.limits().max_push_constant_size;
+    }
+    limits
+}
+
+/// The optional features renderers request their wgpu::Device with, when the adapter has them
+pub(crate) fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    adapter.features()
+        & (wgpu::Features::MULTIVIEW
+            | wgpu::Features::PIPELINE_CACHE
+            | wgpu::Features::PUSH_CONSTANTS
+            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+}
@@ -8,12 +8,18 @@ impl<'a, P: UiPlatform> Renderer<'a, P> {
         self.platform.handle_event(&mut self.ui, window, event);
     }
 
-    pub async fn prepare_ui(&mut self, window: &Window) {
+    pub fn prepare_ui(&mut self, window: &Window) {
         self.platform.prepare_frame(&mut self.ui, window);
     }
 
-    pub fn update_delta_time(&mut self, duration: Duration) {
-        self.delta_time = duration.as_secs_f64();
-        self.ui.context_mut().io_mut().update_delta_time(duration);
+    /// Start a frame at wall_time, delta after the previous frame
+    pub fn update_clock(&mut self, wall_time: f64, delta: Duration) {
+        self.clock.tick(wall_time, delta);
+        self.ui.context_mut().io_mut().update_delta_time(delta);
+    }
+
+    /// If the user asked to minimize non-essential motion, only known on WASM
+    pub fn prefers_reduced_motion(&self) -> bool {
+        false
     }
 }
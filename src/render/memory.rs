@@ -0,0 +1,277 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Approximate GPU memory used by crate-managed resources, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub used: u64,
+    pub budget: Option<u64>,
+}
+
+/// What a crate-managed resource is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResourceKind {
+    Pipeline,
+    Buffer,
+    Uniform,
+    Texture,
+}
+
+/// A live crate-managed resource, as listed by the inspector
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceInfo {
+    pub kind: ResourceKind,
+    pub label: Option<String>,
+    pub bytes: u64,
+    /// Debug formatted value of an inspected Uniform
+    pub value: Option<String>,
+}
+
+type BudgetCallback = Arc<dyn Fn(MemoryUsage) + Send + Sync>;
+
+/// Tracks the memory of crate-managed buffers and textures against a budget
+/// Resources hold an Allocation which gives its memory back when dropped.
+#[derive(Default)]
+pub(crate) struct MemoryBudget {
+    used: AtomicU64,
+    /// Zero means no budget
+    budget: AtomicU64,
+    over_budget: AtomicBool,
+    /// Lower the sample count when the simulation does not fit, see Renderer::downscale_for_memory
+    downscale: AtomicBool,
+    callback: Mutex<Option<BudgetCallback>>,
+    next_id: AtomicU64,
+    resources: Mutex<BTreeMap<u64, ResourceInfo>>,
+}
+
+/// Memory of a single crate-managed resource
+pub(crate) struct Allocation {
+    id: u64,
+    bytes: u64,
+    budget: Arc<MemoryBudget>,
+}
+
+impl MemoryBudget {
+    /// Start with memory that is never freed, such as a headless render target
+    pub(crate) fn with_reserved(bytes: u64) -> Self {
+        Self {
+            used: AtomicU64::new(bytes),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn allocate(
+        self: &Arc<Self>,
+        kind: ResourceKind,
+        label: Option<&str>,
+        bytes: u64,
+    ) -> Allocation {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.resources
+            .lock()
+            .expect("aftgraphs::render::memory::MemoryBudget::allocate: poisoned lock")
+            .insert(
+                id,
+                ResourceInfo {
+                    kind,
+                    label: label.map(str::to_owned),
+                    bytes,
+                    value: None,
+                },
+            );
+
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+        self.check();
+
+        Allocation {
+            id,
+            bytes,
+            budget: self.clone(),
+        }
+    }
+
+    /// Live resources, largest first
+    pub(crate) fn resources(&self) -> Vec<ResourceInfo> {
+        let mut resources: Vec<_> = self
+            .resources
+            .lock()
+            .expect("aftgraphs::render::memory::MemoryBudget::resources: poisoned lock")
+            .values()
+            .cloned()
+            .collect();
+        resources.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.kind.cmp(&b.kind)));
+        resources
+    }
+
+    pub(crate) fn usage(&self) -> MemoryUsage {
+        let budget = self.budget.load(Ordering::Relaxed);
+        MemoryUsage {
+            used: self.used.load(Ordering::Relaxed),
+            budget: (budget != 0).then_some(budget),
+        }
+    }
+
+    pub(crate) fn set_budget(&self, budget: Option<u64>) {
+        self.budget.store(budget.unwrap_or(0), Ordering::Relaxed);
+        self.check();
+    }
+
+    pub(crate) fn set_callback(&self, callback: BudgetCallback) {
+        *self
+            .callback
+            .lock()
+            .expect("aftgraphs::render::memory::MemoryBudget::set_callback: poisoned lock") =
+            Some(callback);
+    }
+
+    pub(crate) fn set_downscale(&self, downscale: bool) {
+        self.downscale.store(downscale, Ordering::Relaxed);
+    }
+
+    pub(crate) fn downscales(&self) -> bool {
+        self.downscale.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_over_budget(&self) -> bool {
+        self.over_budget.load(Ordering::Relaxed)
+    }
+
+    /// If allocating bytes more would stay within the budget
+    pub(crate) fn fits(&self, bytes: u64) -> bool {
+        let usage = self.usage();
+        usage
+            .budget
+            .is_none_or(|budget| usage.used + bytes <= budget)
+    }
+
+    /// Warn and run the callback when the usage crosses over the budget
+    fn check(&self) {
+        let usage = self.usage();
+        let over = usage.budget.is_some_and(|budget| usage.used > budget);
+
+        if self.over_budget.swap(over, Ordering::Relaxed) || !over {
+            return;
+        }
+
+        log::warn!(
+            "aftgraphs::render::memory::MemoryBudget: GPU memory budget exceeded: {} of {} bytes used",
+            usage.used,
+            usage.budget.unwrap_or_default()
+        );
+        // Not called under the lock, the callback may allocate or replace itself
+        let callback = self
+            .callback
+            .lock()
+            .expect("aftgraphs::render::memory::MemoryBudget::check: poisoned lock")
+            .clone();
+        if let Some(callback) = callback {
+            callback(usage);
+        }
+    }
+}
+
+impl Allocation {
+    /// Show value next to the resource in the inspector
+    pub(crate) fn set_value(&self, value: String) {
+        if let Some(info) = self
+            .budget
+            .resources
+            .lock()
+            .expect("aftgraphs::render::memory::Allocation::set_value: poisoned lock")
+            .get_mut(&self.id)
+        {
+            info.value = Some(value);
+        }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.budget
+            .resources
+            .lock()
+            .expect("aftgraphs::render::memory::Allocation::drop: poisoned lock")
+            .remove(&self.id);
+        self.budget.used.fetch_sub(self.bytes, Ordering::Relaxed);
+        self.budget.check();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn allocations_freed_on_drop() {
+        let budget = Arc::new(MemoryBudget::with_reserved(16));
+        let allocation = budget.allocate(ResourceKind::Buffer, Some("vertices"), 48);
+        assert_eq!(64, budget.usage().used);
+        assert_eq!(Some("vertices"), budget.resources()[0].label.as_deref());
+
+        drop(allocation);
+        assert_eq!(16, budget.usage().used);
+        assert!(budget.resources().is_empty());
+        assert_eq!(None, budget.usage().budget);
+    }
+
+    #[test]
+    fn callback_on_crossing() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let budget = Arc::new(MemoryBudget::default());
+        budget.set_budget(Some(100));
+        budget.set_callback(Arc::new({
+            let calls = calls.clone();
+            move |_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+
+        let first = budget.allocate(ResourceKind::Buffer, None, 80);
+        assert!(budget.fits(20));
+        assert!(!budget.fits(21));
+
+        let second = budget.allocate(ResourceKind::Buffer, None, 40);
+        let third = budget.allocate(ResourceKind::Buffer, None, 10);
+        assert!(budget.is_over_budget());
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+
+        drop(second);
+        drop(third);
+        assert!(!budget.is_over_budget());
+
+        let _again = budget.allocate(ResourceKind::Buffer, None, 40);
+        assert_eq!(2, calls.load(Ordering::Relaxed));
+        drop(first);
+    }
+
+    #[test]
+    fn callback_can_use_the_budget() {
+        let budget = Arc::new(MemoryBudget::default());
+        budget.set_budget(Some(100));
+        let weak = Arc::downgrade(&budget);
+        let freed = Arc::new(Mutex::new(vec![]));
+        budget.set_callback(Arc::new({
+            let freed = freed.clone();
+            move |usage| {
+                let budget = weak.upgrade().unwrap();
+                // A smaller replacement, allocated and listed from inside the callback
+                let smaller = budget.allocate(ResourceKind::Texture, None, 1);
+                assert_eq!(usage.used + 1, budget.usage().used);
+                assert_eq!(3, budget.resources().len());
+                budget.set_callback(Arc::new(|_| ()));
+                freed.lock().unwrap().push(smaller);
+            }
+        }));
+
+        let _first = budget.allocate(ResourceKind::Buffer, None, 80);
+        let _second = budget.allocate(ResourceKind::Buffer, None, 40);
+        assert_eq!(1, freed.lock().unwrap().len());
+        assert_eq!(121, budget.usage().used);
+    }
+}
@@ -0,0 +1,222 @@
+use super::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use std::sync::Arc;
+
+/// Highest sample count up to requested that supported accepts, 1 always is
+fn supported_sample_count(requested: u32, supported: impl Fn(u32) -> bool) -> u32 {
+    let mut count = requested.clamp(1, 16).next_power_of_two();
+    if count > requested {
+        count /= 2;
+    }
+    while count > 1 && !supported(count) {
+        count /= 2;
+    }
+    count.max(1)
+}
+
+/// Multisampled color texture the simulation is drawn into, resolved onto the render target
+pub(crate) struct MsaaTarget {
+    size: [u32; 2],
+    format: wgpu::TextureFormat,
+    pub(crate) sample_count: u32,
+    pub(crate) view: wgpu::TextureView,
+    _texture: wgpu::Texture,
+    _allocation: Allocation,
+}
+
+/// The multisampling of a Renderer and its target
+pub(crate) struct Msaa {
+    sample_count: u32,
+    target: Option<Arc<MsaaTarget>>,
+}
+
+impl Default for Msaa {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+            target: None,
+        }
+    }
+}
+
+impl Msaa {
+    pub(crate) fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Use the highest sample count up to count the render target format supports
+    pub(crate) fn set_sample_count<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        count: u32,
+    ) -> u32 {
+        let format = target_format(renderer);
+        let features = if renderer
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            renderer.adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(renderer.device.features())
+        };
+        let supported =
+            supported_sample_count(count, |count| features.flags.sample_count_supported(count));
+        if supported != count {
+            log::warn!(
+                "aftgraphs::render::msaa::Msaa::set_sample_count: {count} samples are not supported, using {supported}"
+            );
+        }
+
+        self.sample_count = supported;
+        self.target = None;
+        supported
+    }
+
+    /// Memory the target still needs until Msaa::prepare creates it, 0 without multisampling
+    pub(crate) fn missing_bytes<P: UiPlatform>(&self, renderer: &Renderer<P>) -> u64 {
+        if self.sample_count == 1 || self.target.is_some() {
+            return 0;
+        }
+        target_bytes(
+            renderer.viewport_size(),
+            target_format(renderer),
+            self.sample_count,
+        )
+    }
+
+    /// Drop the target, to be recreated at the new size of the render target
+    pub(crate) fn resized(&mut self) {
+        self.target = None;
+    }
+
+    /// The target to draw into this frame, None without multisampling
+    /// (Re)creates the target if the render target resized.
+    pub(crate) fn prepare<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+    ) -> Option<Arc<MsaaTarget>> {
+        if self.sample_count == 1 {
+            return None;
+        }
+
+        let size = renderer.viewport_size();
+        let format = target_format(renderer);
+        match self.target {
+            Some(ref target)
+                if target.size == size
+                    && target.format == format
+                    && target.sample_count == self.sample_count =>
+            {
+                Some(target.clone())
+            }
+            _ => {
+                let target = Arc::new(Msaa::create_target(
+                    renderer,
+                    size,
+                    format,
+                    self.sample_count,
+                ));
+                self.target = Some(target.clone());
+                Some(target)
+            }
+        }
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> MsaaTarget {
+        log::debug!(
+            "aftgraphs::render::msaa::Msaa::create_target: Creating {}x{} target with {sample_count} samples",
+            size[0],
+            size[1]
+        );
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::render::msaa::MsaaTarget"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        MsaaTarget {
+            size,
+            format,
+            sample_count,
+            view,
+            _texture: texture,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::msaa::MsaaTarget"),
+                target_bytes(size, format, sample_count),
+            ),
+        }
+    }
+}
+
+/// Approximate memory of a target of size with sample_count samples per pixel
+fn target_bytes(size: [u32; 2], format: wgpu::TextureFormat, sample_count: u32) -> u64 {
+    let bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+    size[0] as u64 * size[1] as u64 * bytes * sample_count as u64
+}
+
+fn target_format<P: UiPlatform>(renderer: &Renderer<P>) -> wgpu::TextureFormat {
+    renderer
+        .config
+        .as_ref()
+        .map_or(wgpu::TextureFormat::Rgba8UnormSrgb, |config| config.format)
+}
+
+/// Attachment drawing to view, through msaa if multisampling
+/// The multisampled texture is resolved onto view at the end of the pass.
+pub(crate) fn color_attachment<'a>(
+    msaa: Option<&'a MsaaTarget>,
+    view: &'a wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    match msaa {
+        Some(msaa) => wgpu::RenderPassColorAttachment {
+            view: &msaa.view,
+            resolve_target: Some(view),
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Discard,
+            },
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_supported_counts() {
+        let supported = |count| [1, 2, 4].contains(&count);
+        assert_eq!(4, supported_sample_count(4, supported));
+        assert_eq!(4, supported_sample_count(8, supported));
+        assert_eq!(2, supported_sample_count(3, supported));
+        assert_eq!(1, supported_sample_count(0, supported));
+        assert_eq!(1, supported_sample_count(1, |_| false));
+    }
+}
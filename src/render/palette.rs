@@ -0,0 +1,157 @@
+use crate::field::Colormap;
+
+/// Okabe-Ito colors, distinguishable with every common form of color blindness
+/// White takes the place of black to stand out on dark backgrounds.
+const OKABE_ITO: [[f32; 3]; 8] = [
+    [0.902, 0.624, 0.0],
+    [0.337, 0.706, 0.914],
+    [0.0, 0.620, 0.451],
+    [0.941, 0.894, 0.259],
+    [0.0, 0.447, 0.698],
+    [0.835, 0.369, 0.0],
+    [0.800, 0.475, 0.655],
+    [1.0, 1.0, 1.0],
+];
+
+/// Matplotlib's tab10 colors
+const TAB10: [[f32; 3]; 8] = [
+    [0.122, 0.467, 0.706],
+    [1.0, 0.498, 0.055],
+    [0.173, 0.627, 0.173],
+    [0.839, 0.153, 0.157],
+    [0.580, 0.404, 0.741],
+    [0.549, 0.337, 0.294],
+    [0.890, 0.467, 0.761],
+    [0.498, 0.498, 0.498],
+];
+
+/// Colors the crate picks by default, and simulations can follow with Renderer::palette
+/// Toggled with F2 in display mode and --high-contrast on the command line, on WASM
+/// it follows the prefers-contrast media query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    #[default]
+    Standard,
+    /// High-contrast ui and colorblind-safe colors
+    HighContrast,
+}
+
+impl Palette {
+    /// Default Colormap of new ScalarFields
+    pub fn colormap(self) -> Colormap {
+        match self {
+            Palette::Standard => Colormap::Viridis,
+            // Spans the full lightness range, unlike Viridis
+            Palette::HighContrast => Colormap::Inferno,
+        }
+    }
+
+    /// Categorical color idx, for telling apart series, particles or agents
+    /// Wraps around after 8 colors.
+    pub fn color(self, idx: usize) -> [f32; 3] {
+        match self {
+            Palette::Standard => TAB10[idx % TAB10.len()],
+            Palette::HighContrast => OKABE_ITO[idx % OKABE_ITO.len()],
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Palette::Standard => Palette::HighContrast,
+            Palette::HighContrast => Palette::Standard,
+        }
+    }
+
+    /// The palette requested with --high-contrast or by the browser
+    pub(crate) async fn startup() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let high_contrast = crate::cli::ARGUMENTS.read().await.high_contrast;
+        #[cfg(target_arch = "wasm32")]
+        let high_contrast = web_sys::window()
+            .and_then(|window| {
+                window
+                    .match_media("(prefers-contrast: more)")
+                    .ok()
+                    .flatten()
+            })
+            .is_some_and(|query| query.matches());
+
+        if high_contrast {
+            Palette::HighContrast
+        } else {
+            Palette::Standard
+        }
+    }
+
+    /// Restyle the ui for the palette
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn apply_style(self, style: &mut imgui::Style) {
+        use imgui::StyleColor;
+
+        match self {
+            Palette::Standard => {
+                style.use_dark_colors();
+                style.frame_border_size = 0.0;
+            }
+            Palette::HighContrast => {
+                let black = [0.0, 0.0, 0.0, 1.0];
+                let white = [1.0, 1.0, 1.0, 1.0];
+                let yellow = [1.0, 0.85, 0.0, 1.0];
+                let dark = [0.2, 0.2, 0.2, 1.0];
+
+                style.use_dark_colors();
+                style.frame_border_size = 1.0;
+                for color in [
+                    StyleColor::WindowBg,
+                    StyleColor::ChildBg,
+                    StyleColor::PopupBg,
+                    StyleColor::FrameBg,
+                    StyleColor::TitleBg,
+                    StyleColor::TitleBgCollapsed,
+                ] {
+                    style[color] = black;
+                }
+                for color in [StyleColor::Text, StyleColor::Border] {
+                    style[color] = white;
+                }
+                for color in [
+                    StyleColor::FrameBgHovered,
+                    StyleColor::TitleBgActive,
+                    StyleColor::Button,
+                    StyleColor::Header,
+                ] {
+                    style[color] = dark;
+                }
+                for color in [
+                    StyleColor::FrameBgActive,
+                    StyleColor::ButtonHovered,
+                    StyleColor::ButtonActive,
+                    StyleColor::HeaderHovered,
+                    StyleColor::HeaderActive,
+                    StyleColor::CheckMark,
+                    StyleColor::SliderGrab,
+                    StyleColor::SliderGrabActive,
+                    StyleColor::PlotHistogram,
+                    StyleColor::NavHighlight,
+                ] {
+                    style[color] = yellow;
+                }
+                style[StyleColor::TextDisabled] = [0.75, 0.75, 0.75, 1.0];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colors_wrap_around() {
+        for palette in [Palette::Standard, Palette::HighContrast] {
+            assert_eq!(palette.color(1), palette.color(9));
+            assert_ne!(palette.color(0), palette.color(1));
+            assert_eq!(palette, palette.toggled().toggled());
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use super::Renderer;
+use crate::ui::UiPlatform;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Keep compiled pipelines in dir for renderers created after this call, None stops caching.
+/// Repeated runs then skip recompiling pipelines the driver has seen before.
+/// Only adapters with wgpu::Features::PIPELINE_CACHE, currently Vulkan ones, use it.
+pub fn set_pipeline_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR
+        .write()
+        .expect("aftgraphs::render::set_pipeline_cache_dir: poisoned lock") = dir;
+}
+
+/// Replace the file at path with data, so an interrupted write never leaves half a cache
+fn store(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, data)?;
+    std::fs::rename(partial, path)
+}
+
+/// A wgpu::PipelineCache loaded from and saved to the cache directory
+pub(crate) struct PipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+    /// Pipelines were built since the cache was last saved
+    dirty: AtomicBool,
+}
+
+impl PipelineCache {
+    /// The cache of adapter in the cache directory, None without a directory or support
+    pub(crate) fn open(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Option<Self> {
+        let dir = CACHE_DIR
+            .read()
+            .expect("aftgraphs::render::PipelineCache::open: poisoned lock")
+            .clone()?;
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            log::info!(
+                "aftgraphs::render::PipelineCache::open: the adapter does not support pipeline caches"
+            );
+            return None;
+        }
+        // Caches are only valid for the adapter and driver that wrote them
+        let key = wgpu::util::pipeline_cache_key(&adapter.get_info())?;
+        let path = dir.join(key);
+
+        let data = match std::fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!(
+                    "aftgraphs::render::PipelineCache::open: failed to read {}: {e}",
+                    path.display()
+                );
+                None
+            }
+        };
+        log::debug!(
+            "aftgraphs::render::PipelineCache::open: {} bytes cached in {}",
+            data.as_ref().map_or(0, Vec::len),
+            path.display()
+        );
+
+        // Safety: the data was written by PipelineCache::save for this adapter's key,
+        // fallback replaces it with an empty cache if the driver rejects it anyway
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("aftgraphs::render::PipelineCache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(Self {
+            cache,
+            path,
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Write the cache to its file if pipelines were built since it was last saved
+    pub(crate) fn save(&self) {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        match store(&self.path, &data) {
+            Ok(()) => log::debug!(
+                "aftgraphs::render::PipelineCache::save: wrote {} bytes to {}",
+                data.len(),
+                self.path.display()
+            ),
+            Err(e) => log::warn!(
+                "aftgraphs::render::PipelineCache::save: failed to write {}: {e}",
+                self.path.display()
+            ),
+        }
+    }
+}
+
+impl Drop for PipelineCache {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+impl<P: UiPlatform> Renderer<'_, P> {
+    /// The cache for a pipeline about to be built, marking it to be saved
+    pub(crate) fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.pipeline_cache.as_ref().map(|cache| {
+            cache.dirty.store(true, Ordering::Relaxed);
+            &cache.cache
+        })
+    }
+
+    /// Write the pipelines built so far to the cache directory, see set_pipeline_cache_dir
+    /// Done after Simulation::new and when the renderer is dropped; call it after building
+    /// pipelines later on to keep them even if the process is killed.
+    pub fn save_pipeline_cache(&self) {
+        if let Some(ref cache) = self.pipeline_cache {
+            cache.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stores_replace_the_whole_file() {
+        let dir =
+            std::env::temp_dir().join(format!("aftgraphs-pipeline-cache-{}", std::process::id()));
+        let path = dir.join("cache").join("adapter");
+
+        store(&path, b"first pipelines").unwrap();
+        store(&path, b"second").unwrap();
+        assert_eq!(b"second".as_slice(), std::fs::read(&path).unwrap());
+        assert!(!path.with_extension("partial").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
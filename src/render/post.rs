@@ -0,0 +1,583 @@
+use super::bloom::{Bloom, BloomPass};
+use super::grade::{ColorGrade, GradePass};
+use super::{Allocation, BindGroupLayout, Renderer, ResourceKind};
+use crate::{input::InputValue, ui::UiPlatform};
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+/// Fullscreen triangle vertex shader vs_main, prepended to every pass drawn over a whole target
+const FULLSCREEN: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/fullscreen.wgsl"));
+
+/// Declarations prepended to the source of every step of a PostChain
+const POST_PRELUDE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/post.wgsl"));
+
+/// A shader module of source after the fullscreen vertex shader of res/fullscreen.wgsl
+pub(crate) fn fullscreen_module<P: UiPlatform>(
+    renderer: &Renderer<P>,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    renderer
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(format!("{FULLSCREEN}\n{source}").into()),
+        })
+}
+
+/// A shader module of a post-processing step, source reading the frame through the
+/// declarations of res/post.wgsl
+pub(crate) fn post_module<P: UiPlatform>(
+    renderer: &Renderer<P>,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    fullscreen_module(renderer, label, &format!("{POST_PRELUDE}\n{source}"))
+}
+
+/// A pipeline drawing the fullscreen triangle of module with its fragment entry_point
+pub(crate) fn fullscreen_pipeline<P: UiPlatform>(
+    renderer: &Renderer<P>,
+    label: &str,
+    module: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    target: wgpu::ColorTargetState,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = renderer
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+    renderer
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some(entry_point),
+                targets: &[Some(target)],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: renderer.pipeline_cache(),
+        })
+}
+
+/// Draw the fullscreen triangle into view with pipeline, binding bind_groups from group 0
+pub(crate) fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(index as u32, *bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+/// A full-screen pass applied to finished frames, see Renderer::add_post_effect
+/// The source defines the fragment entry point fs_main, reading the frame through the
+/// declarations of res/post.wgsl, which are prepended to it with the fullscreen triangle of
+/// res/fullscreen.wgsl:
+///
+/// ```wgsl
+/// @fragment
+/// fn fs_main(in: PostVertex) -> @location(0) vec4<f32> {
+///     let color = textureSample(frame, frame_sampler, in.uv);
+///     return vec4<f32>(1.0 - color.rgb, color.a);
+/// }
+/// ```
+///
+/// Effects can bind their own resources at group 1 with PostEffect::with_bind_group.
+#[derive(Clone)]
+pub struct PostEffect {
+    name: String,
+    source: Cow<'static, str>,
+    bind_group: Option<Arc<(wgpu::BindGroupLayout, wgpu::BindGroup)>>,
+}
+
+impl PostEffect {
+    /// An effect from WGSL source, name is toggled by the "post.<name>" checkbox input
+    pub fn new(name: &str, source: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            name: name.to_owned(),
+            source: source.into(),
+            bind_group: None,
+        }
+    }
+
+    /// Fast approximate antialiasing, smoothing jagged edges
+    pub fn fxaa() -> Self {
+        Self::new(
+            "fxaa",
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/fxaa.wgsl")),
+        )
+    }
+
+    /// A small gaussian blur, about five pixels wide
+    pub fn blur() -> Self {
+        Self::new(
+            "blur",
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/blur.wgsl")),
+        )
+    }
+
+    /// Bind bind_group, laid out by layout, at group 1 of the effect
+    pub fn with_bind_group(
+        mut self,
+        layout: wgpu::BindGroupLayout,
+        bind_group: wgpu::BindGroup,
+    ) -> Self {
+        self.bind_group = Some(Arc::new((layout, bind_group)));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Uniforms of res/post.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+struct PostUniform {
+    size: [f32; 2],
+    texel: [f32; 2],
+    time: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for PostUniform {}
+unsafe impl bytemuck::Pod for PostUniform {}
+
+/// Resources of a PostChain the steps of a frame share
+pub(crate) struct PostResources<'a> {
+    pub(crate) size: [u32; 2],
+    pub(crate) format: wgpu::TextureFormat,
+    /// Layout of group 0 of every step, the declarations of res/post.wgsl
+    pub(crate) layout: &'a wgpu::BindGroupLayout,
+    sampler: &'a wgpu::Sampler,
+    uniform: &'a wgpu::Buffer,
+}
+
+impl PostResources<'_> {
+    /// A group 0 bind group reading view as the frame
+    pub(crate) fn bind_group<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        label: &str,
+        view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform.as_entire_binding(),
+                    },
+                ],
+            })
+    }
+}
+
+/// Two textures the steps read from and write to in turn
+struct PostTarget {
+    size: [u32; 2],
+    format: wgpu::TextureFormat,
+    views: [wgpu::TextureView; 2],
+    /// Samples the texture of the same index
+    bind_groups: [wgpu::BindGroup; 2],
+    _textures: [wgpu::Texture; 2],
+    _allocation: Allocation,
+}
+
+/// A compiled step of a PostChain
+pub(crate) struct PostPipeline {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    _allocation: Allocation,
+}
+
+impl PostPipeline {
+    pub(crate) fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        label: &str,
+        pipeline: wgpu::RenderPipeline,
+    ) -> Self {
+        Self {
+            pipeline,
+            _allocation: renderer.track_memory(ResourceKind::Pipeline, Some(label), 0),
+        }
+    }
+}
+
+enum PostStep {
+    Bloom(BloomPass),
+    Effect {
+        name: String,
+        pipeline: Arc<PostPipeline>,
+        bind_group: Option<Arc<(wgpu::BindGroupLayout, wgpu::BindGroup)>>,
+    },
+    Grade(GradePass),
+}
+
+/// The steps applied to one frame
+pub(crate) struct PostPass {
+    target: Arc<PostTarget>,
+    steps: Vec<PostStep>,
+}
+
+impl PostPass {
+    /// The view to draw the frame into instead of the render target
+    pub(crate) fn view(&self) -> &wgpu::TextureView {
+        &self.target.views[0]
+    }
+
+    /// Apply every step in order, the last one writing to view
+    pub(crate) fn record(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        for (idx, step) in self.steps.iter().enumerate() {
+            let destination = if idx + 1 == self.steps.len() {
+                view
+            } else {
+                &self.target.views[(idx + 1) % 2]
+            };
+            let source = &self.target.bind_groups[idx % 2];
+
+            match step {
+                PostStep::Bloom(bloom) => {
+                    encoder.push_debug_group("aftgraphs: bloom");
+                    bloom.record(encoder, source, destination);
+                    encoder.pop_debug_group();
+                }
+                PostStep::Effect {
+                    name,
+                    pipeline,
+                    bind_group,
+                } => {
+                    let mut bind_groups = vec![source];
+                    if let Some(ref bind_group) = bind_group {
+                        bind_groups.push(&bind_group.1);
+                    }
+                    draw_fullscreen(
+                        encoder,
+                        &format!("aftgraphs::render::post::{name}"),
+                        destination,
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        &pipeline.pipeline,
+                        &bind_groups,
+                    );
+                }
+                PostStep::Grade(grade) => grade.record(encoder, source, destination),
+            }
+        }
+    }
+}
+
+/// The post-processing of a Renderer and the resources to apply it
+/// Every frame runs bloom, then the effects in order, then color grading, each step
+/// drawing the fullscreen triangle of res/fullscreen.wgsl over the shared targets.
+#[derive(Default)]
+pub(crate) struct PostChain {
+    pub(crate) effects: Vec<PostEffect>,
+    pub(crate) bloom: Bloom,
+    pub(crate) grade: ColorGrade,
+    uniform: Option<wgpu::Buffer>,
+    /// Last values written to the uniform buffer
+    written: Option<PostUniform>,
+    sampler: Option<wgpu::Sampler>,
+    layout: Option<BindGroupLayout>,
+    target: Option<Arc<PostTarget>>,
+    pipelines: HashMap<(String, wgpu::TextureFormat), Arc<PostPipeline>>,
+}
+
+impl PostChain {
+    /// Register effect after the others, replacing an effect of the same name in place
+    pub(crate) fn add(&mut self, effect: PostEffect) {
+        self.pipelines.retain(|(name, _), _| *name != effect.name);
+        match self.effects.iter_mut().find(|e| e.name == effect.name) {
+            Some(existing) => *existing = effect,
+            None => self.effects.push(effect),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) {
+        self.effects.retain(|effect| effect.name != name);
+        self.pipelines.retain(|(effect, _), _| effect != name);
+    }
+
+    /// The steps of this frame, None if bloom and grading are off and no effect is registered
+    /// or enabled by the inputs. (Re)creates the targets if the render target resized.
+    pub(crate) fn prepare<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        inputs: &HashMap<String, InputValue>,
+    ) -> Option<PostPass> {
+        let bloom = self
+            .bloom
+            .settings
+            .and_then(|settings| settings.with_inputs(inputs));
+        let enabled: Vec<&PostEffect> = self
+            .effects
+            .iter()
+            .filter(|effect| {
+                !matches!(
+                    inputs.get(&format!("post.{}", effect.name)),
+                    Some(&InputValue::CHECKBOX(false))
+                )
+            })
+            .collect();
+        if bloom.is_none() && enabled.is_empty() && !self.grade.is_set() {
+            return None;
+        }
+
+        let size = renderer.viewport_size();
+        let format = renderer
+            .config
+            .as_ref()
+            .map_or(wgpu::TextureFormat::Rgba8UnormSrgb, |config| config.format);
+
+        let uniform = &*self.uniform.get_or_insert_with(|| {
+            renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("aftgraphs::render::post::PostChain::uniform"),
+                size: std::mem::size_of::<PostUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let values = PostUniform {
+            size: size.map(|side| side as f32),
+            texel: size.map(|side| 1.0 / side.max(1) as f32),
+            time: renderer.clock.time() as f32,
+            _padding: [0.0; 3],
+        };
+        if self.written != Some(values) {
+            renderer
+                .queue
+                .write_buffer(uniform, 0, bytemuck::bytes_of(&values));
+            renderer.record_upload(std::mem::size_of::<PostUniform>());
+            self.written = Some(values);
+        }
+
+        let layout = &*self
+            .layout
+            .get_or_insert_with(|| PostChain::create_layout(renderer));
+        let sampler = &*self.sampler.get_or_insert_with(|| {
+            renderer.device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("aftgraphs::render::post::PostChain::sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        });
+        let resources = PostResources {
+            size,
+            format,
+            layout,
+            sampler,
+            uniform,
+        };
+
+        let target = match self.target {
+            Some(ref target) if target.size == size && target.format == format => target.clone(),
+            _ => {
+                let target = Arc::new(PostChain::create_target(renderer, &resources));
+                self.target = Some(target.clone());
+                target
+            }
+        };
+
+        let mut steps = vec![];
+        if let Some(settings) = bloom {
+            steps.push(PostStep::Bloom(
+                self.bloom.prepare(renderer, &resources, settings),
+            ));
+        }
+        steps.extend(enabled.into_iter().map(|effect| {
+            PostStep::Effect {
+                name: effect.name.clone(),
+                pipeline: self
+                    .pipelines
+                    .entry((effect.name.clone(), format))
+                    .or_insert_with(|| {
+                        Arc::new(PostChain::create_pipeline(renderer, effect, &resources))
+                    })
+                    .clone(),
+                bind_group: effect.bind_group.clone(),
+            }
+        }));
+        if let Some(grade) = self.grade.prepare(renderer, &resources) {
+            steps.push(PostStep::Grade(grade));
+        }
+
+        Some(PostPass { target, steps })
+    }
+
+    fn create_layout<P: UiPlatform>(renderer: &Renderer<P>) -> BindGroupLayout {
+        super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::post::PostChain::layout"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer)
+    }
+
+    fn create_target<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        resources: &PostResources<'_>,
+    ) -> PostTarget {
+        let PostResources { size, format, .. } = *resources;
+        log::debug!(
+            "aftgraphs::render::post::PostChain::create_target: Creating {}x{} targets",
+            size[0],
+            size[1]
+        );
+
+        let textures = [0, 1].map(|_| {
+            renderer.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("aftgraphs::render::post::PostTarget"),
+                size: wgpu::Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        });
+        let views =
+            [0, 1].map(|idx| textures[idx].create_view(&wgpu::TextureViewDescriptor::default()));
+        let bind_groups = [0, 1].map(|idx| {
+            resources.bind_group(renderer, "aftgraphs::render::post::PostTarget", &views[idx])
+        });
+        let bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+
+        PostTarget {
+            size,
+            format,
+            views,
+            bind_groups,
+            _textures: textures,
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::render::post::PostTarget"),
+                2 * size[0] as u64 * size[1] as u64 * bytes,
+            ),
+        }
+    }
+
+    fn create_pipeline<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        effect: &PostEffect,
+        resources: &PostResources<'_>,
+    ) -> PostPipeline {
+        log::debug!(
+            "aftgraphs::render::post::PostChain::create_pipeline: Compiling effect {}",
+            effect.name
+        );
+
+        let label = format!("aftgraphs::render::post::{}", effect.name);
+        let module = post_module(renderer, &label, &effect.source);
+        let mut bind_group_layouts = vec![resources.layout];
+        if let Some(ref bind_group) = effect.bind_group {
+            bind_group_layouts.push(&bind_group.0);
+        }
+        let pipeline = fullscreen_pipeline(
+            renderer,
+            &label,
+            &module,
+            "fs_main",
+            &bind_group_layouts,
+            wgpu::ColorTargetState {
+                format: resources.format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+        );
+
+        PostPipeline::new(renderer, &label, pipeline)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn effects_replace_by_name() {
+        let mut chain = PostChain::default();
+        chain.add(PostEffect::blur());
+        chain.add(PostEffect::fxaa());
+        chain.add(PostEffect::new("blur", "// replaced"));
+        let names: Vec<&str> = chain.effects.iter().map(PostEffect::name).collect();
+        assert_eq!(vec!["blur", "fxaa"], names);
+        assert_eq!("// replaced", chain.effects[0].source);
+
+        chain.remove("blur");
+        assert_eq!(1, chain.effects.len());
+    }
+}
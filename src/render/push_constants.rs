@@ -0,0 +1,127 @@
+use super::Renderer;
+use crate::ui::UiPlatform;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PushConstantError {
+    #[error("push constants at offset {offset} of {size} bytes are not aligned to 4 bytes")]
+    Unaligned { offset: u32, size: u32 },
+    #[error("no push constant range of the pipeline holds bytes {offset}..{end} for {stages:?}")]
+    OutOfRange {
+        stages: wgpu::ShaderStages,
+        offset: u32,
+        end: u32,
+    },
+    #[error("bytes {offset}..{end} overlap a range for {range:?}, but were set for {stages:?}")]
+    StageMismatch {
+        stages: wgpu::ShaderStages,
+        range: wgpu::ShaderStages,
+        offset: u32,
+        end: u32,
+    },
+}
+
+/// Check setting size bytes at offset for stages against the ranges of a pipeline layout
+/// Every stage must have a range holding all of the bytes, and every range overlapping
+/// the bytes must be for exactly the stages given, as wgpu requires.
+pub(crate) fn check_push_constants(
+    ranges: &[wgpu::PushConstantRange],
+    stages: wgpu::ShaderStages,
+    offset: u32,
+    size: u32,
+) -> Result<(), PushConstantError> {
+    let alignment = wgpu::PUSH_CONSTANT_ALIGNMENT;
+    if offset % alignment != 0 || size % alignment != 0 {
+        return Err(PushConstantError::Unaligned { offset, size });
+    }
+
+    let end = offset + size;
+    for stage in stages.iter() {
+        if !ranges.iter().any(|range| {
+            range.stages.contains(stage) && range.range.start <= offset && end <= range.range.end
+        }) {
+            return Err(PushConstantError::OutOfRange {
+                stages: stage,
+                offset,
+                end,
+            });
+        }
+    }
+
+    let overlapping = ranges
+        .iter()
+        .filter(|range| range.range.start < end && offset < range.range.end);
+    for range in overlapping {
+        if !stages.contains(range.stages) {
+            return Err(PushConstantError::StageMismatch {
+                stages,
+                range: range.stages,
+                offset,
+                end,
+            });
+        }
+    }
+    Ok(())
+}
+
+impl<P: UiPlatform> Renderer<'_, P> {
+    /// If pipelines may declare push constant ranges
+    /// The feature is requested whenever the adapter has it, native Vulkan, Metal and DX12 do.
+    pub fn supports_push_constants(&self) -> bool {
+        self.device
+            .features()
+            .contains(wgpu::Features::PUSH_CONSTANTS)
+    }
+
+    /// Bytes of push constants a pipeline may declare, 0 without support
+    pub fn max_push_constant_size(&self) -> u32 {
+        self.device.limits().max_push_constant_size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_constants_fit_their_ranges() {
+        let ranges = [
+            wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX,
+                range: 0..16,
+            },
+            wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::FRAGMENT,
+                range: 16..32,
+            },
+        ];
+        let vertex = wgpu::ShaderStages::VERTEX;
+        assert_eq!(Ok(()), check_push_constants(&ranges, vertex, 0, 16));
+        assert_eq!(Ok(()), check_push_constants(&ranges, vertex, 4, 8));
+        assert_eq!(
+            Err(PushConstantError::Unaligned { offset: 2, size: 4 }),
+            check_push_constants(&ranges, vertex, 2, 4)
+        );
+        assert!(matches!(
+            check_push_constants(&ranges, vertex, 8, 16),
+            Err(PushConstantError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            check_push_constants(&ranges, wgpu::ShaderStages::VERTEX_FRAGMENT, 0, 16),
+            Err(PushConstantError::OutOfRange { .. })
+        ));
+
+        let shared = [wgpu::PushConstantRange {
+            stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            range: 0..16,
+        }];
+        assert!(matches!(
+            check_push_constants(&shared, vertex, 0, 16),
+            Err(PushConstantError::StageMismatch { .. })
+        ));
+        assert_eq!(
+            Ok(()),
+            check_push_constants(&shared, wgpu::ShaderStages::VERTEX_FRAGMENT, 0, 16)
+        );
+    }
+}
@@ -0,0 +1,681 @@
+use super::{Allocation, BindGroupLayout, ComputePipeline, Frame, Renderer, ResourceKind};
+use crate::storage::StorageBuffer;
+use crate::texture::Texture;
+use crate::ui::UiPlatform;
+use std::num::NonZeroU64;
+use std::sync::{Arc, Mutex};
+
+/// Invocations per workgroup of res/reduce.wgsl
+const WORKGROUP_SIZE: u32 = 256;
+/// Workgroups dispatched at most, each loops over the items past the first of every workgroup
+const MAX_WORKGROUPS: u32 = 256;
+/// Bins a Reduction's histogram may have
+pub const MAX_HISTOGRAM_BINS: u32 = 256;
+/// Results being read back before Reduction::record skips frames
+const READBACKS: usize = 3;
+/// Bytes of the Totals of res/reduce.wgsl
+const TOTALS_SIZE: u64 = 16 + 4 * MAX_HISTOGRAM_BINS as u64;
+
+/// The value of each texel a Reduction reduces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureValue {
+    /// One channel, 0 to 3 for red to alpha
+    Channel(u32),
+    /// Rec. 709 luminance of the linear color
+    Luminance,
+    /// log2 of the luminance, so the mean is the log of the geometric mean, as auto-exposure uses
+    LogLuminance,
+}
+
+impl TextureValue {
+    fn index(self) -> u32 {
+        match self {
+            Self::Channel(channel) => channel.min(3),
+            Self::Luminance => 4,
+            Self::LogLuminance => 5,
+        }
+    }
+}
+
+/// Counts of values in equal bins between range[0] and range[1]
+/// Values outside the range are counted in the first or last bin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub range: [f32; 2],
+    pub bins: Vec<u32>,
+}
+
+impl Histogram {
+    pub fn total(&self) -> u64 {
+        self.bins.iter().map(|&count| u64::from(count)).sum()
+    }
+
+    fn bin_width(&self) -> f32 {
+        (self.range[1] - self.range[0]) / self.bins.len().max(1) as f32
+    }
+
+    /// The value below which fraction of the values lie, interpolated within its bin
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        let target = fraction.clamp(0.0, 1.0) as f64 * self.total() as f64;
+        let mut below = 0.0;
+        for (index, &count) in self.bins.iter().enumerate() {
+            let count = f64::from(count);
+            if count > 0.0 && below + count >= target {
+                let within = ((target - below) / count) as f32;
+                return self.range[0] + (index as f32 + within) * self.bin_width();
+            }
+            below += count;
+        }
+        self.range[1]
+    }
+
+    /// The mean of the values between the low and high percentiles, at their bins' centers
+    /// Auto-exposure uses it to ignore the darkest and brightest parts of a frame.
+    pub fn mean_between(&self, low: f32, high: f32) -> Option<f32> {
+        let total = self.total() as f64;
+        let (low, high) = (
+            low.clamp(0.0, 1.0) as f64 * total,
+            high.clamp(0.0, 1.0) as f64 * total,
+        );
+
+        let (mut below, mut sum, mut counted) = (0.0, 0.0, 0.0);
+        for (index, &count) in self.bins.iter().enumerate() {
+            let count = f64::from(count);
+            // The part of this bin's values between the percentiles
+            let inside = ((below + count).min(high) - below.max(low)).max(0.0);
+            let center = self.range[0] + (index as f32 + 0.5) * self.bin_width();
+            sum += inside * f64::from(center);
+            counted += inside;
+            below += count;
+        }
+        (counted > 0.0).then(|| (sum / counted) as f32)
+    }
+}
+
+/// The minimum, maximum and mean of the values reduced, and their histogram if requested
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReductionResult {
+    pub count: u32,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub histogram: Option<Histogram>,
+}
+
+impl ReductionResult {
+    /// Decode the Totals of res/reduce.wgsl
+    fn decode(bytes: &[u8], count: u32, histogram: Option<(u32, [f32; 2])>) -> Self {
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let histogram = histogram.map(|(bins, range)| Histogram {
+            range,
+            bins: words[4..4 + bins as usize].to_vec(),
+        });
+
+        if count == 0 {
+            return Self {
+                count,
+                min: 0.0,
+                max: 0.0,
+                mean: 0.0,
+                histogram,
+            };
+        }
+        Self {
+            count,
+            min: f32::from_bits(words[0]),
+            max: f32::from_bits(words[1]),
+            mean: f32::from_bits(words[2]) / count as f32,
+            histogram,
+        }
+    }
+}
+
+/// Uniforms of res/reduce.wgsl
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ReduceParams {
+    count: u32,
+    width: u32,
+    bins: u32,
+    value: u32,
+    histogram_min: f32,
+    histogram_max: f32,
+    workgroups: u32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Zeroable for ReduceParams {}
+unsafe impl bytemuck::Pod for ReduceParams {}
+
+/// What a readback buffer holds, shared with its map_async callback
+#[derive(Clone, Copy, PartialEq)]
+enum ReadbackState {
+    Free,
+    /// Copied into by a frame, which may not be submitted yet
+    Copied,
+    Mapping,
+    Mapped,
+    Failed,
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    state: Arc<Mutex<ReadbackState>>,
+    /// Orders the results, the latest one wins
+    sequence: u64,
+    count: u32,
+    histogram: Option<(u32, [f32; 2])>,
+}
+
+/// GPU reduction of a storage buffer of floats or a texture to their minimum, maximum, mean,
+/// and optionally a histogram, so simulations don't write their own parallel reductions
+/// Record it in Simulation::encode at most once per frame, the result can be read with
+/// Reduction::latest a frame or two later, without waiting for the GPU.
+pub struct Reduction {
+    label: Option<String>,
+    histogram: Option<(u32, [f32; 2])>,
+    buffer_pipeline: ComputePipeline,
+    texture_pipeline: ComputePipeline,
+    combine_pipeline: ComputePipeline,
+    buffer_layout: BindGroupLayout,
+    texture_layout: BindGroupLayout,
+    params: wgpu::Buffer,
+    totals: wgpu::Buffer,
+    output: wgpu::BindGroup,
+    readbacks: Vec<Readback>,
+    sequence: u64,
+    latest: Option<ReductionResult>,
+    latest_sequence: u64,
+    _allocation: Allocation,
+}
+
+impl Reduction {
+    pub fn new<P: UiPlatform>(renderer: &Renderer<P>, label: Option<&str>) -> Self {
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let output_layout = super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::reduce::Reduction::output"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: compute,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: compute,
+                ty: super::BINDING_STORAGE_BUFFER,
+                count: None,
+            })
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: compute,
+                ty: super::BINDING_STORAGE_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let buffer_layout = super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::reduce::Reduction::buffer"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: compute,
+                ty: super::BINDING_READ_ONLY_STORAGE_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let texture_layout = super::BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::render::reduce::Reduction::texture"))
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: compute,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .build(renderer);
+
+        let pipeline = |entry_point, layouts: &[&wgpu::BindGroupLayout]| {
+            super::ComputePipelineBuilder::new()
+                .with_module(wgpu::include_wgsl!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/res/reduce.wgsl"
+                )))
+                .with_entry_point(entry_point)
+                .with_label(Some("aftgraphs::render::reduce::Reduction::pipeline"))
+                .with_bind_group_layouts_iter(layouts.iter().copied())
+                .build(renderer)
+        };
+        let buffer_pipeline = pipeline("reduce_buffer", &[&output_layout, &buffer_layout]);
+        let texture_pipeline = pipeline("reduce_texture", &[&output_layout, &texture_layout]);
+        let combine_pipeline = pipeline("combine", &[&output_layout]);
+
+        let buffer = |suffix: &str, size, usage| {
+            renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!(
+                    "{}::{suffix}",
+                    label.unwrap_or("aftgraphs::render::reduce::Reduction")
+                )),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let params = buffer(
+            "params",
+            std::mem::size_of::<ReduceParams>() as u64,
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+        let partials = buffer(
+            "partials",
+            16 * MAX_WORKGROUPS as u64,
+            wgpu::BufferUsages::STORAGE,
+        );
+        let totals = buffer(
+            "totals",
+            TOTALS_SIZE,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        );
+        let readbacks = (0..READBACKS)
+            .map(|_| Readback {
+                buffer: buffer(
+                    "readback",
+                    TOTALS_SIZE,
+                    wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                ),
+                state: Arc::new(Mutex::new(ReadbackState::Free)),
+                sequence: 0,
+                count: 0,
+                histogram: None,
+            })
+            .collect();
+
+        let output = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::reduce::Reduction::output"),
+                layout: &output_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: partials.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: totals.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let bytes = 16 * MAX_WORKGROUPS as u64 + TOTALS_SIZE * (1 + READBACKS as u64);
+        Self {
+            label: label.map(str::to_owned),
+            histogram: None,
+            buffer_pipeline,
+            texture_pipeline,
+            combine_pipeline,
+            buffer_layout,
+            texture_layout,
+            params,
+            totals,
+            output,
+            readbacks,
+            sequence: 0,
+            latest: None,
+            latest_sequence: 0,
+            _allocation: renderer.track_memory(ResourceKind::Buffer, label, bytes),
+        }
+    }
+
+    /// Also count the values into (bins, range) equal bins between range[0] and range[1],
+    /// at most MAX_HISTOGRAM_BINS of them, or stop counting with None
+    pub fn set_histogram(&mut self, histogram: Option<(u32, [f32; 2])>) {
+        self.histogram = histogram.map(|(bins, range)| (bins.clamp(1, MAX_HISTOGRAM_BINS), range));
+    }
+
+    /// Reduce the elements of a storage buffer
+    pub fn record_storage<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        frame: &mut Frame<'_>,
+        storage: &StorageBuffer<f32>,
+    ) {
+        let count = storage.len() as u32;
+        if count == 0 {
+            return;
+        }
+        let source = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::reduce::Reduction::buffer"),
+                layout: &self.buffer_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: storage.buffer(),
+                        offset: 0,
+                        size: NonZeroU64::new(count as u64 * 4),
+                    }),
+                }],
+            });
+        self.record(renderer, frame, &source, count, 0, 0);
+    }
+
+    /// Reduce the value of every texel of a texture with a float format
+    pub fn record_texture<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        frame: &mut Frame<'_>,
+        texture: &Texture,
+        value: TextureValue,
+    ) {
+        if !matches!(
+            texture.format().sample_type(None, None),
+            Some(wgpu::TextureSampleType::Float { .. })
+        ) {
+            log::error!(
+                "aftgraphs::render::reduce::Reduction::record_texture: {:?} textures can't be reduced",
+                texture.format()
+            );
+            return;
+        }
+
+        let [width, height] = texture.size();
+        let source = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::render::reduce::Reduction::texture"),
+                layout: &self.texture_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(texture.view()),
+                }],
+            });
+        self.record(
+            renderer,
+            frame,
+            &source,
+            width * height,
+            width,
+            value.index(),
+        );
+    }
+
+    fn record<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        frame: &mut Frame<'_>,
+        source: &wgpu::BindGroup,
+        count: u32,
+        width: u32,
+        value: u32,
+    ) {
+        self.collect(renderer, true);
+        let Some(readback) = self.readbacks.iter_mut().find(|readback| {
+            *readback
+                .state
+                .lock()
+                .expect("aftgraphs::render::reduce::Reduction::record: poisoned lock")
+                == ReadbackState::Free
+        }) else {
+            log::debug!(
+                "aftgraphs::render::reduce::Reduction::record: every readback is in flight, skipping"
+            );
+            return;
+        };
+
+        let workgroups = count.div_ceil(WORKGROUP_SIZE).clamp(1, MAX_WORKGROUPS);
+        let (bins, range) = self.histogram.unwrap_or((0, [0.0, 1.0]));
+        let params = ReduceParams {
+            count,
+            width,
+            bins,
+            value,
+            histogram_min: range[0],
+            histogram_max: range[1],
+            workgroups,
+            _padding: 0,
+        };
+        renderer
+            .queue
+            .write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+        renderer.record_upload(std::mem::size_of::<ReduceParams>());
+
+        frame.encoder().clear_buffer(&self.totals, 0, None);
+        {
+            let pipeline = if width == 0 {
+                &self.buffer_pipeline
+            } else {
+                &self.texture_pipeline
+            };
+            let mut pass = frame.begin_compute_pass(Some(
+                self.label
+                    .as_deref()
+                    .unwrap_or("aftgraphs::render::reduce::Reduction"),
+            ));
+            pass.set_pipeline(pipeline);
+            pass.bind(0, &self.output);
+            pass.bind(1, source);
+            pass.dispatch([workgroups, 1, 1]);
+            pass.set_pipeline(&self.combine_pipeline);
+            pass.dispatch([1, 1, 1]);
+        }
+        frame
+            .encoder()
+            .copy_buffer_to_buffer(&self.totals, 0, &readback.buffer, 0, TOTALS_SIZE);
+
+        self.sequence += 1;
+        readback.sequence = self.sequence;
+        readback.count = count;
+        readback.histogram = self.histogram;
+        *readback
+            .state
+            .lock()
+            .expect("aftgraphs::render::reduce::Reduction::record: poisoned lock") =
+            ReadbackState::Copied;
+    }
+
+    /// Keep the latest result of the mapped readbacks, freeing them
+    /// If submitted, the frames that copied into the other readbacks were submitted,
+    /// so they can be mapped.
+    fn collect<P: UiPlatform>(&mut self, renderer: &Renderer<P>, submitted: bool) {
+        renderer.device.poll(wgpu::Maintain::Poll);
+
+        let mut newest: Option<&Readback> = None;
+        for readback in &self.readbacks {
+            let mut state = readback
+                .state
+                .lock()
+                .expect("aftgraphs::render::reduce::Reduction::collect: poisoned lock");
+            let current = *state;
+            match current {
+                ReadbackState::Copied if submitted => {
+                    *state = ReadbackState::Mapping;
+                    // The callback may run at once and take the lock
+                    drop(state);
+                    let mapped = readback.state.clone();
+                    readback
+                        .buffer
+                        .slice(..)
+                        .map_async(wgpu::MapMode::Read, move |result| {
+                            let mut state = mapped.lock().expect(
+                                "aftgraphs::render::reduce::Reduction::collect: poisoned lock",
+                            );
+                            *state = match result {
+                                Ok(()) => ReadbackState::Mapped,
+                                Err(e) => {
+                                    log::error!(
+                                        "aftgraphs::render::reduce::Reduction::collect: failed to map readback: {e}"
+                                    );
+                                    ReadbackState::Failed
+                                }
+                            };
+                        });
+                }
+                ReadbackState::Mapped
+                    if newest.is_none_or(|newest| readback.sequence > newest.sequence) =>
+                {
+                    newest = Some(readback);
+                }
+                _ => (),
+            }
+        }
+
+        let newest = newest.map_or(0, |newest| newest.sequence);
+        for readback in &self.readbacks {
+            let mut state = readback
+                .state
+                .lock()
+                .expect("aftgraphs::render::reduce::Reduction::collect: poisoned lock");
+            match *state {
+                ReadbackState::Mapped if readback.sequence <= newest => {
+                    // Results older than the one kept are dropped
+                    if readback.sequence == newest && newest > self.latest_sequence {
+                        let bytes = readback.buffer.slice(..).get_mapped_range();
+                        self.latest = Some(ReductionResult::decode(
+                            &bytes,
+                            readback.count,
+                            readback.histogram,
+                        ));
+                        self.latest_sequence = newest;
+                    }
+                    readback.buffer.unmap();
+                    *state = ReadbackState::Free;
+                }
+                ReadbackState::Failed => *state = ReadbackState::Free,
+                _ => (),
+            }
+        }
+    }
+
+    /// The result of the latest recording read back from the GPU so far
+    /// Usually that of the frame before last, None until the first one arrives.
+    pub fn latest<P: UiPlatform>(&mut self, renderer: &Renderer<P>) -> Option<&ReductionResult> {
+        self.collect(renderer, false);
+        self.latest.as_ref()
+    }
+}
+
+/// Eases an exposure towards the one exposing the average luminance of frames at key
+/// Feed it the results of a Reduction of the HDR frame's TextureValue::LogLuminance, and
+/// multiply the colors by the exposure before tonemapping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposure {
+    /// Luminance the average of a frame is exposed to, 0.18 is a mid grey
+    pub key: f32,
+    /// How fast the exposure adapts, the fraction of the remaining stops it closes per second
+    /// approaches 1 - e^-speed
+    pub speed: f32,
+    /// Smallest and largest exposure in stops
+    pub range: [f32; 2],
+    /// Percentiles of the histogram averaged, ignoring the darkest and brightest parts
+    pub percentiles: [f32; 2],
+    /// The current exposure in stops
+    stops: Option<f32>,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            key: 0.18,
+            speed: 2.0,
+            range: [-8.0, 8.0],
+            percentiles: [0.5, 0.95],
+            stops: None,
+        }
+    }
+}
+
+impl AutoExposure {
+    /// Adapt to a reduction of log2 luminance over dt seconds, returning the exposure
+    /// The first result is adapted to at once. The histogram is used if it was counted.
+    pub fn update(&mut self, result: &ReductionResult, dt: f32) -> f32 {
+        let average = result
+            .histogram
+            .as_ref()
+            .and_then(|histogram| histogram.mean_between(self.percentiles[0], self.percentiles[1]))
+            .unwrap_or(result.mean);
+        let target = (self.key.max(1e-5).log2() - average).clamp(self.range[0], self.range[1]);
+
+        let stops = match self.stops {
+            Some(stops) => stops + (target - stops) * (1.0 - (-self.speed * dt.max(0.0)).exp()),
+            None => target,
+        };
+        self.stops = Some(stops);
+        self.exposure()
+    }
+
+    /// The factor to multiply colors by, 1 until the first update
+    pub fn exposure(&self) -> f32 {
+        self.stops.unwrap_or(0.0).exp2()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram_percentiles() {
+        let histogram = Histogram {
+            range: [0.0, 4.0],
+            bins: vec![2, 0, 1, 1],
+        };
+        assert_eq!(4, histogram.total());
+        assert_eq!(0.0, histogram.percentile(0.0));
+        assert_eq!(0.5, histogram.percentile(0.25));
+        assert_eq!(1.0, histogram.percentile(0.5));
+        assert_eq!(4.0, histogram.percentile(1.0));
+
+        assert_eq!(Some(1.75), histogram.mean_between(0.0, 1.0));
+        assert_eq!(Some(3.0), histogram.mean_between(0.5, 1.0));
+        assert_eq!(Some(0.5), histogram.mean_between(0.0, 0.25));
+        assert_eq!(None, histogram.mean_between(0.5, 0.5));
+    }
+
+    #[test]
+    fn decodes_totals() {
+        let mut words = vec![(-1.5f32).to_bits(), 3.0f32.to_bits(), 6.0f32.to_bits(), 0];
+        words.extend([1, 2, 0]);
+        words.resize(TOTALS_SIZE as usize / 4, 0);
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+
+        let result = ReductionResult::decode(&bytes, 3, Some((3, [-2.0, 4.0])));
+        assert_eq!((-1.5, 3.0, 2.0), (result.min, result.max, result.mean));
+        assert_eq!(
+            Some(vec![1, 2, 0]),
+            result.histogram.map(|histogram| histogram.bins)
+        );
+        assert_eq!(0.0, ReductionResult::decode(&bytes, 0, None).mean);
+    }
+
+    #[test]
+    fn exposure_adapts() {
+        let result = |mean| ReductionResult {
+            count: 1,
+            min: mean,
+            max: mean,
+            mean,
+            histogram: None,
+        };
+        let mut exposure = AutoExposure {
+            key: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(1.0, exposure.exposure());
+        assert_eq!(0.25, exposure.update(&result(2.0), 0.1));
+
+        let exposure = exposure.update(&result(0.0), 1.0);
+        assert!(0.25 < exposure && exposure < 1.0);
+    }
+}
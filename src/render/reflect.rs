@@ -0,0 +1,651 @@
+use super::{BindGroupLayout, BindGroupLayoutBuilder, Renderer};
+use crate::ui::UiPlatform;
+use naga::{
+    proc::Layouter, AddressSpace, Binding, Block, Handle, ImageClass, ImageDimension, Module,
+    ScalarKind, ShaderStage, Statement, StorageFormat, TypeInner,
+};
+use std::collections::BTreeSet;
+use std::num::NonZeroU64;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ReflectionError {
+    #[error("shader module is not WGSL")]
+    NotWgsl,
+    #[error("failed to parse WGSL: {0}")]
+    Parse(String),
+    #[error("failed to lay out shader types: {0}")]
+    Layout(String),
+    #[error("no entry point named {0}")]
+    MissingEntryPoint(String),
+    #[error("{0} at @group({1}) @binding({2}) has no bind group layout equivalent")]
+    Unsupported(String, u32, u32),
+}
+
+/// A difference between what a shader declares and what the Rust side provides
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LayoutMismatch {
+    #[error("@location({0}) is read by the shader but not provided by any vertex attribute")]
+    MissingLocation(u32),
+    #[error("@location({location}) is {shader:?} in the shader but {rust:?} in the vertex layout")]
+    Format {
+        location: u32,
+        shader: wgpu::VertexFormat,
+        rust: wgpu::VertexFormat,
+    },
+    #[error("@location({location}) at offset {offset} overflows the array stride of {stride}")]
+    Overflow {
+        location: u32,
+        offset: u64,
+        stride: u64,
+    },
+    #[error("@location({0}) overlaps @location({1}) in the vertex layout")]
+    Overlap(u32, u32),
+    #[error("@group({group}) @binding({binding}) needs at least {shader} bytes but the Rust type has {rust}")]
+    BindingSize {
+        group: u32,
+        binding: u32,
+        shader: u64,
+        rust: u64,
+    },
+    #[error("@group({0}) @binding({1}) is not declared in the shader")]
+    MissingBinding(u32, u32),
+}
+
+/// A vertex input read by an entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: wgpu::VertexFormat,
+}
+
+/// What a WGSL shader declares, parsed with naga: bind group layouts with the
+/// stages using each binding and its minimum size, and the vertex inputs of
+/// each entry point. Derive layouts from the shader instead of writing them by
+/// hand, or check hand written layouts and Rust types against it.
+pub struct ShaderReflection {
+    module: Module,
+    layouter: Layouter,
+}
+
+fn vertex_format(inner: &TypeInner) -> Option<wgpu::VertexFormat> {
+    use wgpu::VertexFormat as VF;
+
+    let (size, scalar) = match *inner {
+        TypeInner::Scalar(scalar) => (1, scalar),
+        TypeInner::Vector { size, scalar } => (size as u8, scalar),
+        _ => return None,
+    };
+    let format = match (scalar.kind, scalar.width, size) {
+        (ScalarKind::Float, 4, 1) => VF::Float32,
+        (ScalarKind::Float, 4, 2) => VF::Float32x2,
+        (ScalarKind::Float, 4, 3) => VF::Float32x3,
+        (ScalarKind::Float, 4, 4) => VF::Float32x4,
+        (ScalarKind::Float, 2, 2) => VF::Float16x2,
+        (ScalarKind::Float, 2, 4) => VF::Float16x4,
+        (ScalarKind::Float, 8, 1) => VF::Float64,
+        (ScalarKind::Float, 8, 2) => VF::Float64x2,
+        (ScalarKind::Float, 8, 3) => VF::Float64x3,
+        (ScalarKind::Float, 8, 4) => VF::Float64x4,
+        (ScalarKind::Uint, 4, 1) => VF::Uint32,
+        (ScalarKind::Uint, 4, 2) => VF::Uint32x2,
+        (ScalarKind::Uint, 4, 3) => VF::Uint32x3,
+        (ScalarKind::Uint, 4, 4) => VF::Uint32x4,
+        (ScalarKind::Sint, 4, 1) => VF::Sint32,
+        (ScalarKind::Sint, 4, 2) => VF::Sint32x2,
+        (ScalarKind::Sint, 4, 3) => VF::Sint32x3,
+        (ScalarKind::Sint, 4, 4) => VF::Sint32x4,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// If a shader reading format can be fed from a buffer laid out as rust, like
+/// a vec4<f32> read from Unorm8x4 or Float16x4 attributes
+fn compatible(shader: wgpu::VertexFormat, rust: wgpu::VertexFormat) -> bool {
+    use wgpu::VertexFormat as VF;
+
+    shader == rust
+        || matches!(
+            (shader, rust),
+            (
+                VF::Float32x2,
+                VF::Unorm8x2 | VF::Snorm8x2 | VF::Unorm16x2 | VF::Snorm16x2 | VF::Float16x2
+            ) | (
+                VF::Float32x4,
+                VF::Unorm8x4
+                    | VF::Snorm8x4
+                    | VF::Unorm16x4
+                    | VF::Snorm16x4
+                    | VF::Float16x4
+                    | VF::Unorm10_10_10_2
+            ) | (VF::Uint32x2, VF::Uint8x2 | VF::Uint16x2)
+                | (VF::Uint32x4, VF::Uint8x4 | VF::Uint16x4)
+                | (VF::Sint32x2, VF::Sint8x2 | VF::Sint16x2)
+                | (VF::Sint32x4, VF::Sint8x4 | VF::Sint16x4)
+        )
+}
+
+fn texture_format(format: StorageFormat) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as TF;
+
+    let format = match format {
+        StorageFormat::R32Float => TF::R32Float,
+        StorageFormat::R32Uint => TF::R32Uint,
+        StorageFormat::R32Sint => TF::R32Sint,
+        StorageFormat::Rg32Float => TF::Rg32Float,
+        StorageFormat::Rg32Uint => TF::Rg32Uint,
+        StorageFormat::Rg32Sint => TF::Rg32Sint,
+        StorageFormat::Rgba8Unorm => TF::Rgba8Unorm,
+        StorageFormat::Rgba8Snorm => TF::Rgba8Snorm,
+        StorageFormat::Rgba8Uint => TF::Rgba8Uint,
+        StorageFormat::Rgba8Sint => TF::Rgba8Sint,
+        StorageFormat::Bgra8Unorm => TF::Bgra8Unorm,
+        StorageFormat::Rgba16Float => TF::Rgba16Float,
+        StorageFormat::Rgba16Uint => TF::Rgba16Uint,
+        StorageFormat::Rgba16Sint => TF::Rgba16Sint,
+        StorageFormat::Rgba32Float => TF::Rgba32Float,
+        StorageFormat::Rgba32Uint => TF::Rgba32Uint,
+        StorageFormat::Rgba32Sint => TF::Rgba32Sint,
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    use wgpu::TextureViewDimension as TVD;
+
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => TVD::D1,
+        (ImageDimension::D2, false) => TVD::D2,
+        (ImageDimension::D2, true) => TVD::D2Array,
+        (ImageDimension::D3, _) => TVD::D3,
+        (ImageDimension::Cube, false) => TVD::Cube,
+        (ImageDimension::Cube, true) => TVD::CubeArray,
+    }
+}
+
+/// Add the functions block calls to calls, including calls nested in control flow
+fn collect_calls(block: &Block, calls: &mut Vec<Handle<naga::Function>>) {
+    for statement in block.iter() {
+        match statement {
+            Statement::Block(block) => collect_calls(block, calls),
+            Statement::If { accept, reject, .. } => {
+                collect_calls(accept, calls);
+                collect_calls(reject, calls);
+            }
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    collect_calls(&case.body, calls);
+                }
+            }
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                collect_calls(body, calls);
+                collect_calls(continuing, calls);
+            }
+            Statement::Call { function, .. } => calls.push(*function),
+            _ => (),
+        }
+    }
+}
+
+impl ShaderReflection {
+    pub fn from_wgsl(source: &str) -> Result<Self, ReflectionError> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|e| ReflectionError::Parse(e.emit_to_string(source)))?;
+        let mut layouter = Layouter::default();
+        layouter
+            .update(module.to_ctx())
+            .map_err(|e| ReflectionError::Layout(e.to_string()))?;
+        Ok(Self { module, layouter })
+    }
+
+    /// Reflect the module given to ShaderBuilder::with_module, e.g. from include_wgsl!
+    pub fn from_descriptor(module: &wgpu::ShaderModuleDescriptor) -> Result<Self, ReflectionError> {
+        match module.source {
+            wgpu::ShaderSource::Wgsl(ref source) => Self::from_wgsl(source),
+            _ => Err(ReflectionError::NotWgsl),
+        }
+    }
+
+    /// The stages whose entry points use the global, directly or through the functions they call
+    fn visibility(&self, global: Handle<naga::GlobalVariable>) -> wgpu::ShaderStages {
+        let uses = |function: &naga::Function| {
+            function
+                .expressions
+                .iter()
+                .any(|(_, expr)| matches!(*expr, naga::Expression::GlobalVariable(used) if used == global))
+        };
+
+        let mut stages = wgpu::ShaderStages::NONE;
+        for entry_point in &self.module.entry_points {
+            let mut calls = vec![];
+            collect_calls(&entry_point.function.body, &mut calls);
+            let mut seen = BTreeSet::new();
+            let mut used = uses(&entry_point.function);
+            while let Some(function) = calls.pop() {
+                if used || !seen.insert(function) {
+                    continue;
+                }
+                let function = &self.module.functions[function];
+                used = uses(function);
+                collect_calls(&function.body, &mut calls);
+            }
+
+            if used {
+                stages |= match entry_point.stage {
+                    ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                    ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                    ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                };
+            }
+        }
+        stages
+    }
+
+    /// The entries of the bind group layout for @group(group), ordered by binding
+    pub fn bind_group_entries(
+        &self,
+        group: u32,
+    ) -> Result<Vec<wgpu::BindGroupLayoutEntry>, ReflectionError> {
+        let mut entries = vec![];
+        for (handle, global) in self.module.global_variables.iter() {
+            let Some(ref binding) = global.binding else {
+                continue;
+            };
+            if binding.group != group {
+                continue;
+            }
+
+            let size = self.layouter[global.ty].size as u64;
+            let min_binding_size = NonZeroU64::new(size);
+            let inner = &self.module.types[global.ty].inner;
+            let unsupported = || {
+                let name = global.name.clone().unwrap_or_else(|| "binding".to_owned());
+                ReflectionError::Unsupported(name, binding.group, binding.binding)
+            };
+
+            let ty = match (global.space, inner) {
+                (AddressSpace::Uniform, _) => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size,
+                },
+                (AddressSpace::Storage { access }, _) => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage {
+                        read_only: !access.contains(naga::StorageAccess::STORE),
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size,
+                },
+                (AddressSpace::Handle, &TypeInner::Sampler { comparison }) => {
+                    wgpu::BindingType::Sampler(if comparison {
+                        wgpu::SamplerBindingType::Comparison
+                    } else {
+                        wgpu::SamplerBindingType::Filtering
+                    })
+                }
+                (
+                    AddressSpace::Handle,
+                    &TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    },
+                ) => {
+                    let view_dimension = view_dimension(dim, arrayed);
+                    match class {
+                        ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                            sample_type: match kind {
+                                ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                                ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                                _ => wgpu::TextureSampleType::Float { filterable: !multi },
+                            },
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Storage { format, access } => {
+                            wgpu::BindingType::StorageTexture {
+                                access: match (
+                                    access.contains(naga::StorageAccess::LOAD),
+                                    access.contains(naga::StorageAccess::STORE),
+                                ) {
+                                    (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                                    (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                                    _ => wgpu::StorageTextureAccess::WriteOnly,
+                                },
+                                format: texture_format(format).ok_or_else(unsupported)?,
+                                view_dimension,
+                            }
+                        }
+                    }
+                }
+                _ => return Err(unsupported()),
+            };
+
+            let mut visibility = self.visibility(handle);
+            if visibility.is_empty() {
+                // Declared but unused by every entry point, still give it a valid stage
+                visibility = wgpu::ShaderStages::VERTEX_FRAGMENT;
+            }
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty,
+                count: None,
+            });
+        }
+
+        entries.sort_by_key(|entry| entry.binding);
+        Ok(entries)
+    }
+
+    /// Create the bind group layout for @group(group)
+    pub fn bind_group_layout<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        group: u32,
+        label: Option<&str>,
+    ) -> Result<BindGroupLayout, ReflectionError> {
+        Ok(BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entries(self.bind_group_entries(group)?)
+            .build(renderer))
+    }
+
+    /// The size in bytes the buffer at @group(group) @binding(binding) needs at least
+    pub fn min_binding_size(&self, group: u32, binding: u32) -> Option<u64> {
+        self.module
+            .global_variables
+            .iter()
+            .find(|(_, global)| {
+                global
+                    .binding
+                    .as_ref()
+                    .is_some_and(|res| res.group == group && res.binding == binding)
+            })
+            .map(|(_, global)| self.layouter[global.ty].size as u64)
+    }
+
+    /// Check that T is large enough for the buffer at @group(group) @binding(binding)
+    pub fn check_binding<T>(&self, group: u32, binding: u32) -> Result<(), LayoutMismatch> {
+        let shader = self
+            .min_binding_size(group, binding)
+            .ok_or(LayoutMismatch::MissingBinding(group, binding))?;
+        let rust = std::mem::size_of::<T>() as u64;
+        if rust < shader {
+            return Err(LayoutMismatch::BindingSize {
+                group,
+                binding,
+                shader,
+                rust,
+            });
+        }
+        Ok(())
+    }
+
+    /// The vertex inputs of the vertex entry point, ordered by location.
+    /// Includes the @location members of struct arguments.
+    pub fn vertex_inputs(&self, entry_point: &str) -> Result<Vec<VertexInput>, ReflectionError> {
+        let entry_point = self
+            .module
+            .entry_points
+            .iter()
+            .find(|entry| entry.stage == ShaderStage::Vertex && entry.name == entry_point)
+            .ok_or_else(|| ReflectionError::MissingEntryPoint(entry_point.to_owned()))?;
+
+        let mut inputs = vec![];
+        let mut push = |binding: &Option<Binding>, ty: Handle<naga::Type>| {
+            if let Some(Binding::Location { location, .. }) = *binding {
+                if let Some(format) = vertex_format(&self.module.types[ty].inner) {
+                    inputs.push(VertexInput { location, format });
+                }
+            }
+        };
+        for argument in &entry_point.function.arguments {
+            match self.module.types[argument.ty].inner {
+                TypeInner::Struct { ref members, .. } => {
+                    for member in members {
+                        push(&member.binding, member.ty);
+                    }
+                }
+                _ => push(&argument.binding, argument.ty),
+            }
+        }
+
+        inputs.sort_by_key(|input| input.location);
+        Ok(inputs)
+    }
+
+    /// Vertex attributes for the inputs at locations, packed tightly in location order,
+    /// for a vertex buffer whose Rust type has one field per input without padding
+    pub fn vertex_attributes(
+        &self,
+        entry_point: &str,
+        locations: std::ops::Range<u32>,
+    ) -> Result<Vec<wgpu::VertexAttribute>, ReflectionError> {
+        let mut offset = 0;
+        Ok(self
+            .vertex_inputs(entry_point)?
+            .into_iter()
+            .filter(|input| locations.contains(&input.location))
+            .map(|input| {
+                let attribute = wgpu::VertexAttribute {
+                    format: input.format,
+                    offset,
+                    shader_location: input.location,
+                };
+                offset += input.format.size();
+                attribute
+            })
+            .collect())
+    }
+
+    /// Compare the vertex buffer layouts given to ShaderBuilder::with_buffer against the inputs
+    /// of the vertex entry point, returning every mismatch found
+    pub fn check_vertex_layouts(
+        &self,
+        entry_point: &str,
+        layouts: &[wgpu::VertexBufferLayout],
+    ) -> Result<Vec<LayoutMismatch>, ReflectionError> {
+        let mut mismatches = vec![];
+
+        for input in self.vertex_inputs(entry_point)? {
+            let provided = layouts.iter().find_map(|layout| {
+                layout
+                    .attributes
+                    .iter()
+                    .find(|attribute| attribute.shader_location == input.location)
+            });
+            match provided {
+                None => mismatches.push(LayoutMismatch::MissingLocation(input.location)),
+                Some(attribute) if !compatible(input.format, attribute.format) => {
+                    mismatches.push(LayoutMismatch::Format {
+                        location: input.location,
+                        shader: input.format,
+                        rust: attribute.format,
+                    });
+                }
+                Some(_) => (),
+            }
+        }
+
+        for layout in layouts {
+            for (idx, attribute) in layout.attributes.iter().enumerate() {
+                let end = attribute.offset + attribute.format.size();
+                if end > layout.array_stride {
+                    mismatches.push(LayoutMismatch::Overflow {
+                        location: attribute.shader_location,
+                        offset: attribute.offset,
+                        stride: layout.array_stride,
+                    });
+                }
+                for other in &layout.attributes[idx + 1..] {
+                    let other_end = other.offset + other.format.size();
+                    if attribute.offset < other_end && other.offset < end {
+                        mismatches.push(LayoutMismatch::Overlap(
+                            attribute.shader_location,
+                            other.shader_location,
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Log every mismatch of check_vertex_layouts as a warning, returning if there were none
+    pub fn validate_vertex_layouts(
+        &self,
+        entry_point: &str,
+        layouts: &[wgpu::VertexBufferLayout],
+    ) -> bool {
+        match self.check_vertex_layouts(entry_point, layouts) {
+            Ok(mismatches) => {
+                for mismatch in &mismatches {
+                    log::warn!("aftgraphs::render::ShaderReflection::validate_vertex_layouts: {entry_point}: {mismatch}");
+                }
+                mismatches.is_empty()
+            }
+            Err(e) => {
+                log::warn!("aftgraphs::render::ShaderReflection::validate_vertex_layouts: {e}");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHADER: &str = r#"
+struct Params {
+    color: vec4<f32>,
+    scale: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> positions: array<vec2<f32>>;
+@group(1) @binding(0) var image: texture_2d<f32>;
+@group(1) @binding(1) var image_sampler: sampler;
+
+struct Instance {
+    @location(1) offset: vec2<f32>,
+    @location(2) radius: f32,
+}
+
+fn scaled(position: vec2<f32>) -> vec2<f32> {
+    return position * params.scale;
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, instance: Instance) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(scaled(position) * instance.radius + instance.offset + positions[0], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return params.color * textureSample(image, image_sampler, vec2<f32>(0.5));
+}
+"#;
+
+    #[test]
+    fn reflects_bindings() {
+        let reflection = ShaderReflection::from_wgsl(SHADER).unwrap();
+
+        let entries = reflection.bind_group_entries(0).unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!(wgpu::ShaderStages::VERTEX_FRAGMENT, entries[0].visibility);
+        assert_eq!(
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(32),
+            },
+            entries[0].ty
+        );
+        assert_eq!(wgpu::ShaderStages::VERTEX, entries[1].visibility);
+
+        let entries = reflection.bind_group_entries(1).unwrap();
+        assert_eq!(wgpu::ShaderStages::FRAGMENT, entries[0].visibility);
+        assert_eq!(
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            entries[1].ty
+        );
+
+        assert_eq!(Ok(()), reflection.check_binding::<[f32; 8]>(0, 0));
+        assert_eq!(
+            Err(LayoutMismatch::BindingSize {
+                group: 0,
+                binding: 0,
+                shader: 32,
+                rust: 20,
+            }),
+            reflection.check_binding::<[f32; 5]>(0, 0)
+        );
+    }
+
+    #[test]
+    fn reflects_vertex_inputs() {
+        let reflection = ShaderReflection::from_wgsl(SHADER).unwrap();
+
+        let instance = reflection.vertex_attributes("vs_main", 1..3).unwrap();
+        assert_eq!(
+            wgpu::vertex_attr_array![1 => Float32x2, 2 => Float32].to_vec(),
+            instance
+        );
+
+        let vertices = wgpu::vertex_attr_array![0 => Float32x2];
+        let wrong = [
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: 0,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 4,
+                shader_location: 2,
+            },
+        ];
+        let layouts = [
+            wgpu::VertexBufferLayout {
+                array_stride: 8,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &vertices,
+            },
+            wgpu::VertexBufferLayout {
+                array_stride: 12,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wrong,
+            },
+        ];
+        assert_eq!(
+            vec![
+                LayoutMismatch::Format {
+                    location: 2,
+                    shader: wgpu::VertexFormat::Float32,
+                    rust: wgpu::VertexFormat::Float32x3,
+                },
+                LayoutMismatch::Overlap(1, 2),
+                LayoutMismatch::Overflow {
+                    location: 2,
+                    offset: 4,
+                    stride: 12,
+                },
+            ],
+            reflection
+                .check_vertex_layouts("vs_main", &layouts)
+                .unwrap()
+        );
+    }
+}
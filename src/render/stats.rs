@@ -0,0 +1,404 @@
+use super::push_constants::{check_push_constants, PushConstantError};
+use super::timing::{FrameTimes, FrameTiming, JankCallback, JANK_FACTOR};
+use super::BindPipeline;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+use web_time::Duration;
+
+/// Counters for the work submitted during one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RendererStats {
+    pub draw_calls: u64,
+    pub instances: u64,
+    pub vertices: u64,
+    pub pipeline_switches: u64,
+    /// Number of buffer or texture writes made by the crate's guards
+    pub buffer_uploads: u64,
+    pub bytes_uploaded: u64,
+    /// Time since the end of the previous frame, zero for the first frame
+    pub frame_time: Duration,
+    /// If the frame took longer than JANK_FACTOR target frame times
+    pub jank: bool,
+}
+
+impl RendererStats {
+    /// Triangles drawn, assuming every draw used a triangle list
+    pub fn triangles(&self) -> u64 {
+        self.vertices / 3
+    }
+}
+
+/// Per-frame counters shared between a Renderer and its RenderPasses
+/// Work recorded between two frames is attributed to the later frame.
+#[derive(Default)]
+pub(crate) struct FrameCounters {
+    draw_calls: AtomicU64,
+    instances: AtomicU64,
+    vertices: AtomicU64,
+    pipeline_switches: AtomicU64,
+    buffer_uploads: AtomicU64,
+    bytes_uploaded: AtomicU64,
+    last: Mutex<RendererStats>,
+    timing: Mutex<FrameTiming>,
+    on_jank: Mutex<Option<JankCallback>>,
+}
+
+impl FrameCounters {
+    pub(crate) fn record_draw(&self, vertices: u32, instances: u32) {
+        self.draw_calls.fetch_add(1, Ordering::Relaxed);
+        self.instances
+            .fetch_add(instances as u64, Ordering::Relaxed);
+        self.vertices
+            .fetch_add(vertices as u64 * instances as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_pipeline_switch(&self) {
+        self.pipeline_switches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_upload(&self, bytes: usize) {
+        self.buffer_uploads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_uploaded
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Counters of the frame in progress
+    pub(crate) fn current(&self) -> RendererStats {
+        RendererStats {
+            draw_calls: self.draw_calls.load(Ordering::Relaxed),
+            instances: self.instances.load(Ordering::Relaxed),
+            vertices: self.vertices.load(Ordering::Relaxed),
+            pipeline_switches: self.pipeline_switches.load(Ordering::Relaxed),
+            buffer_uploads: self.buffer_uploads.load(Ordering::Relaxed),
+            bytes_uploaded: self.bytes_uploaded.load(Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+
+    /// Counters of the last finished frame
+    pub(crate) fn last(&self) -> RendererStats {
+        *self
+            .last
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::last: poisoned lock")
+    }
+
+    /// Frame times of the most recent frames
+    pub(crate) fn frame_times(&self) -> FrameTimes {
+        self.timing
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::frame_times: poisoned lock")
+            .times
+            .clone()
+    }
+
+    pub(crate) fn target_frame_time(&self) -> Duration {
+        self.timing
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::target_frame_time: poisoned lock")
+            .target
+    }
+
+    pub(crate) fn set_target_frame_time(&self, target: Duration) {
+        self.timing
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::set_target_frame_time: poisoned lock")
+            .target = target;
+    }
+
+    pub(crate) fn set_jank_callback(&self, callback: JankCallback) {
+        *self
+            .on_jank
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::set_jank_callback: poisoned lock") =
+            Some(callback);
+    }
+
+    /// Store the counters of the frame in progress as the last frame and reset them
+    /// Runs the jank callback if the frame took too long.
+    pub(crate) fn end_frame(&self) {
+        let (frame_time, jank) = self
+            .timing
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::end_frame: poisoned lock")
+            .end_frame();
+
+        let stats = RendererStats {
+            draw_calls: self.draw_calls.swap(0, Ordering::Relaxed),
+            instances: self.instances.swap(0, Ordering::Relaxed),
+            vertices: self.vertices.swap(0, Ordering::Relaxed),
+            pipeline_switches: self.pipeline_switches.swap(0, Ordering::Relaxed),
+            buffer_uploads: self.buffer_uploads.swap(0, Ordering::Relaxed),
+            bytes_uploaded: self.bytes_uploaded.swap(0, Ordering::Relaxed),
+            frame_time,
+            jank,
+        };
+
+        *self
+            .last
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::end_frame: poisoned lock") = stats;
+
+        if !jank {
+            return;
+        }
+        log::debug!(
+            "aftgraphs::render::stats::FrameCounters::end_frame: Janky frame took {:.2} ms",
+            frame_time.as_secs_f64() * 1e3
+        );
+        if let Some(ref on_jank) = *self
+            .on_jank
+            .lock()
+            .expect("aftgraphs::render::stats::FrameCounters::end_frame: poisoned lock")
+        {
+            on_jank(frame_time);
+        }
+    }
+}
+
+/// The wrapped pass of a RenderPass, owned or reborrowed from another RenderPass
+/// The lifetime of the owned pass is forgotten so it can be reborrowed,
+/// 'a still ties it to the encoder it was begun on.
+enum WrappedPass<'a> {
+    Owned(wgpu::RenderPass<'static>, PhantomData<&'a mut ()>),
+    Borrowed(&'a mut wgpu::RenderPass<'static>),
+}
+
+/// A wgpu::RenderPass that counts the draws and pipeline switches recorded into it
+/// Everything else is forwarded to the wrapped pass through Deref.
+pub struct RenderPass<'a> {
+    pass: WrappedPass<'a>,
+    counters: Arc<FrameCounters>,
+    /// Push constant ranges of the bound pipeline, shared with reborrowed passes
+    push_constant_ranges: Arc<Mutex<Vec<wgpu::PushConstantRange>>>,
+}
+
+impl<'a> RenderPass<'a> {
+    pub(crate) fn new(pass: wgpu::RenderPass<'a>, counters: Arc<FrameCounters>) -> Self {
+        Self {
+            pass: WrappedPass::Owned(pass.forget_lifetime(), PhantomData),
+            counters,
+            push_constant_ranges: Arc::default(),
+        }
+    }
+
+    /// A RenderPass recording into the same pass, for handing to another Simulation
+    pub fn reborrow(&mut self) -> RenderPass<'_> {
+        let counters = self.counters.clone();
+        let push_constant_ranges = self.push_constant_ranges.clone();
+        RenderPass {
+            pass: WrappedPass::Borrowed(self),
+            counters,
+            push_constant_ranges,
+        }
+    }
+
+    /// Set the pipeline of the following draws
+    /// A RenderPipeline binds the pipeline last rebuilt from its shader files.
+    pub fn set_pipeline<T: BindPipeline + ?Sized>(&mut self, pipeline: &T) {
+        self.counters.record_pipeline_switch();
+        pipeline.bind_pipeline(&mut **self);
+        *self.push_constant_ranges() = pipeline.push_constant_ranges().to_vec();
+    }
+
+    fn push_constant_ranges(&self) -> MutexGuard<'_, Vec<wgpu::PushConstantRange>> {
+        self.push_constant_ranges
+            .lock()
+            .expect("aftgraphs::render::stats::RenderPass::push_constant_ranges: poisoned lock")
+    }
+
+    /// Set data as the push constants at offset for stages, for the following draws
+    /// Checked against the push constant ranges of the pipeline bound with
+    /// RenderPass::set_pipeline, which wgpu would otherwise only report as a validation error.
+    pub fn set_push_constants_checked<T: bytemuck::NoUninit>(
+        &mut self,
+        stages: wgpu::ShaderStages,
+        offset: u32,
+        data: &T,
+    ) -> Result<(), PushConstantError> {
+        let data = bytemuck::bytes_of(data);
+        check_push_constants(
+            &self.push_constant_ranges(),
+            stages,
+            offset,
+            data.len() as u32,
+        )?;
+        (**self).set_push_constants(stages, offset, data);
+        Ok(())
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.counters
+            .record_draw(vertices.len() as u32, instances.len() as u32);
+        (**self).draw(vertices, instances);
+    }
+
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.counters
+            .record_draw(indices.len() as u32, instances.len() as u32);
+        (**self).draw_indexed(indices, base_vertex, instances);
+    }
+
+    /// The counts of indirect draws are unknown on the CPU, only the call is counted
+    pub fn draw_indirect(&mut self, indirect_buffer: &wgpu::Buffer, indirect_offset: u64) {
+        self.counters.record_draw(0, 0);
+        (**self).draw_indirect(indirect_buffer, indirect_offset);
+    }
+
+    /// The counts of indirect draws are unknown on the CPU, only the call is counted
+    pub fn draw_indexed_indirect(&mut self, indirect_buffer: &wgpu::Buffer, indirect_offset: u64) {
+        self.counters.record_draw(0, 0);
+        (**self).draw_indexed_indirect(indirect_buffer, indirect_offset);
+    }
+}
+
+impl Deref for RenderPass<'_> {
+    type Target = wgpu::RenderPass<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        match self.pass {
+            WrappedPass::Owned(ref pass, _) => pass,
+            WrappedPass::Borrowed(ref pass) => pass,
+        }
+    }
+}
+
+/// Draws made directly on the wrapped pass are not counted
+impl DerefMut for RenderPass<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self.pass {
+            WrappedPass::Owned(ref mut pass, _) => pass,
+            WrappedPass::Borrowed(ref mut pass) => pass,
+        }
+    }
+}
+
+/// Number of buckets in the HUD frame time histogram
+#[cfg(not(target_arch = "wasm32"))]
+const HUD_BUCKETS: usize = 32;
+
+/// Name of a present mode in the HUD
+#[cfg(not(target_arch = "wasm32"))]
+fn present_mode_name(mode: &wgpu::PresentMode) -> std::borrow::Cow<'_, str> {
+    match mode {
+        wgpu::PresentMode::Fifo => "Fifo (vsync)".into(),
+        wgpu::PresentMode::FifoRelaxed => "Fifo relaxed (adaptive vsync)".into(),
+        wgpu::PresentMode::Mailbox => "Mailbox (no tearing)".into(),
+        wgpu::PresentMode::Immediate => "Immediate (tearing)".into(),
+        mode => format!("{mode:?}").into(),
+    }
+}
+
+/// Draw the renderer statistics HUD
+/// The frame time histogram spans zero to twice the jank threshold.
+/// Returns the present mode picked from present_modes, to be applied once the frame is presented.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn draw_hud(
+    ui: &imgui::Ui,
+    stats: &RendererStats,
+    times: &FrameTimes,
+    target: Duration,
+    present_mode: Option<wgpu::PresentMode>,
+    present_modes: &[wgpu::PresentMode],
+) -> Option<wgpu::PresentMode> {
+    let ms = |time: Duration| time.as_secs_f64() * 1e3;
+    let mut requested = None;
+
+    ui.window("Renderer statistics")
+        .always_auto_resize(true)
+        .build(|| {
+            if let Some(mode) = present_mode.filter(|_| !present_modes.is_empty()) {
+                let mut current = present_modes
+                    .iter()
+                    .position(|&supported| supported == mode)
+                    .unwrap_or(0);
+                if ui.combo(
+                    "Present mode",
+                    &mut current,
+                    present_modes,
+                    present_mode_name,
+                ) && present_modes[current] != mode
+                {
+                    requested = Some(present_modes[current]);
+                }
+                ui.separator();
+            }
+
+            ui.text(format!("Draw calls: {}", stats.draw_calls));
+            ui.text(format!("Instances: {}", stats.instances));
+            ui.text(format!(
+                "Vertices: {} (~{} triangles)",
+                stats.vertices,
+                stats.triangles()
+            ));
+            ui.text(format!("Pipeline switches: {}", stats.pipeline_switches));
+            ui.text(format!(
+                "Uploads: {} ({:.1} KiB)",
+                stats.buffer_uploads,
+                stats.bytes_uploaded as f64 / 1024.0
+            ));
+
+            ui.separator();
+            ui.text(format!(
+                "Frame time: {:.2} ms (mean {:.2} ms)",
+                ms(stats.frame_time),
+                ms(times.mean())
+            ));
+            ui.text(format!(
+                "p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+                ms(times.percentile(50.0)),
+                ms(times.percentile(95.0)),
+                ms(times.percentile(99.0)),
+                ms(times.max())
+            ));
+            ui.text(format!(
+                "Janky frames: {} of {}",
+                times.jank_count(target),
+                times.len()
+            ));
+
+            let histogram: Vec<f32> = times
+                .histogram(HUD_BUCKETS, target * JANK_FACTOR * 2)
+                .into_iter()
+                .map(|count| count as f32)
+                .collect();
+            ui.plot_histogram("##frame times", &histogram)
+                .overlay_text(format!("0 - {:.1} ms", ms(target * JANK_FACTOR * 2)))
+                .scale_min(0.0)
+                .graph_size([0.0, 60.0])
+                .build();
+        });
+
+    requested
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn end_frame_resets() {
+        let counters = FrameCounters::default();
+        counters.record_pipeline_switch();
+        counters.record_draw(3, 2);
+        counters.record_draw(6, 1);
+        counters.record_upload(64);
+
+        assert_eq!(RendererStats::default(), counters.last());
+        counters.end_frame();
+
+        let stats = counters.last();
+        assert_eq!(2, stats.draw_calls);
+        assert_eq!(3, stats.instances);
+        assert_eq!(12, stats.vertices);
+        assert_eq!(4, stats.triangles());
+        assert_eq!(1, stats.pipeline_switches);
+        assert_eq!(1, stats.buffer_uploads);
+        assert_eq!(64, stats.bytes_uploaded);
+        assert_eq!(RendererStats::default(), counters.current());
+    }
+}
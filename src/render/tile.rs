@@ -0,0 +1,106 @@
+use crate::uniform::Mat4;
+
+/// A part of an image too large to render at once, see Renderer::tile.
+/// Tiles along the right and bottom edges may reach past the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    /// Top left pixel of the tile in the image
+    pub offset: [u32; 2],
+    /// Size of the tile in pixels, the size of the render target
+    pub size: [u32; 2],
+    /// Size of the whole image in pixels
+    pub image: [u32; 2],
+}
+
+impl Tile {
+    /// Tiles of at most tile_size covering an image of size, row by row
+    pub fn split(image: [u32; 2], tile_size: [u32; 2]) -> Vec<Tile> {
+        let size = [tile_size[0].max(1), tile_size[1].max(1)];
+        (0..image[1].div_ceil(size[1]))
+            .flat_map(|row| {
+                (0..image[0].div_ceil(size[0])).map(move |column| Tile {
+                    offset: [column * size[0], row * size[1]],
+                    size,
+                    image,
+                })
+            })
+            .collect()
+    }
+
+    /// Width and height of the part of the tile inside the image
+    pub fn visible_size(&self) -> [u32; 2] {
+        [
+            self.size[0].min(self.image[0] - self.offset[0]),
+            self.size[1].min(self.image[1] - self.offset[1]),
+        ]
+    }
+
+    /// Map the NDC of the whole image to the NDC of the tile
+    pub fn crop(&self, projection: Mat4) -> Mat4 {
+        let ndc = |pixel: u32, size: u32| 2.0 * pixel as f32 / size as f32 - 1.0;
+        let left = ndc(self.offset[0], self.image[0]);
+        let right = ndc(self.offset[0] + self.size[0], self.image[0]);
+        // Pixel rows go down, NDC up
+        let top = -ndc(self.offset[1], self.image[1]);
+        let bottom = -ndc(self.offset[1] + self.size[1], self.image[1]);
+
+        let scale = [2.0 / (right - left), 2.0 / (top - bottom)];
+        let translate = [
+            -(left + right) / (right - left),
+            -(top + bottom) / (top - bottom),
+        ];
+        Mat4(projection.0.map(|column| {
+            [
+                scale[0] * column[0] + translate[0] * column[3],
+                scale[1] * column[1] + translate[1] * column[3],
+                column[2],
+                column[3],
+            ]
+        }))
+    }
+
+    /// A scissor rect x, y, width, height in pixels of the image as a scissor rect of the tile
+    pub fn scissor(&self, [x, y, width, height]: [u32; 4]) -> [u32; 4] {
+        let clip = |start: u32, len: u32, offset: u32, size: u32| {
+            let end = (start + len).clamp(offset, offset + size);
+            let start = start.clamp(offset, offset + size);
+            (start - offset, end - start)
+        };
+        let (x, width) = clip(x, width, self.offset[0], self.size[0]);
+        let (y, height) = clip(y, height, self.offset[1], self.size[1]);
+        [x, y, width, height]
+    }
+
+    /// Copy the visible part of a frame read back from a tile render into image.
+    /// Frame rows are frame_stride bytes apart, image rows tightly packed, 4 bytes per pixel
+    pub fn stitch(&self, frame: &[u8], frame_stride: usize, image: &mut [u8]) {
+        let [width, height] = self.visible_size();
+        let row_len = width as usize * 4;
+        let image_stride = self.image[0] as usize * 4;
+        for row in 0..height as usize {
+            let src = row * frame_stride;
+            let dst = (self.offset[1] as usize + row) * image_stride + self.offset[0] as usize * 4;
+            image[dst..dst + row_len].copy_from_slice(&frame[src..src + row_len]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image_and_crop_to_their_part() {
+        let tiles = Tile::split([300, 200], [128, 128]);
+        assert_eq!(6, tiles.len());
+        assert_eq!([44, 72], tiles[5].visible_size());
+
+        // The top left quarter of a 2x2 split shows NDC [-1, 0] x [0, 1]
+        let crop = Tile::split([100, 100], [50, 50])[0].crop(Mat4::IDENTITY);
+        let apply = |[x, y]: [f32; 2]| [crop[0][0] * x + crop[3][0], crop[1][1] * y + crop[3][1]];
+        assert_eq!([-1.0, -1.0], apply([-1.0, 0.0]));
+        assert_eq!([1.0, 1.0], apply([0.0, 1.0]));
+
+        assert_eq!([0, 10, 30, 100], tiles[1].scissor([100, 10, 58, 100]));
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use web_time::{Duration, Instant};
+
+/// Number of frames kept by FrameTimes
+pub const FRAME_TIME_WINDOW: usize = 240;
+
+/// A frame is janky if it takes longer than this many target frame times
+pub const JANK_FACTOR: u32 = 2;
+
+/// Rolling window of the most recent frame times
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FrameTimes {
+    times: VecDeque<Duration>,
+}
+
+impl FrameTimes {
+    fn push(&mut self, time: Duration) {
+        if self.times.len() == FRAME_TIME_WINDOW {
+            self.times.pop_front();
+        }
+        self.times.push_back(time);
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Frame times from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.times.iter().copied()
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.times.iter().sum::<Duration>() / self.times.len().max(1) as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.iter().max().unwrap_or_default()
+    }
+
+    /// Frame time at or below which percentile percent of the frames fall
+    /// percentile is clamped to [0, 100], zero if no frames were recorded
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.times.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut sorted: Vec<_> = self.iter().collect();
+        sorted.sort_unstable();
+
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted[rank.saturating_sub(1)]
+    }
+
+    /// Number of frames that took longer than JANK_FACTOR target frame times
+    pub fn jank_count(&self, target: Duration) -> usize {
+        self.iter()
+            .filter(|&time| time > target * JANK_FACTOR)
+            .count()
+    }
+
+    /// Count the frames into buckets of equal width between zero and max
+    /// Frames longer than max are counted in the last bucket.
+    pub fn histogram(&self, buckets: usize, max: Duration) -> Vec<u32> {
+        let mut counts = vec![0; buckets];
+        if buckets == 0 || max.is_zero() {
+            return counts;
+        }
+
+        for time in self.iter() {
+            let bucket = (time.as_secs_f64() / max.as_secs_f64() * buckets as f64) as usize;
+            counts[bucket.min(buckets - 1)] += 1;
+        }
+
+        counts
+    }
+}
+
+pub(crate) type JankCallback = Box<dyn Fn(Duration) + Send + Sync>;
+
+/// Frame time bookkeeping of a FrameCounters
+pub(crate) struct FrameTiming {
+    last_end: Option<Instant>,
+    pub(crate) times: FrameTimes,
+    pub(crate) target: Duration,
+}
+
+impl Default for FrameTiming {
+    fn default() -> Self {
+        Self {
+            last_end: None,
+            times: FrameTimes::default(),
+            target: Duration::from_secs_f64(1.0 / 60.0),
+        }
+    }
+}
+
+impl FrameTiming {
+    /// Record the end of a frame, returning its frame time and if it was janky
+    /// The first frame has no start to measure from and is not recorded.
+    pub(crate) fn end_frame(&mut self) -> (Duration, bool) {
+        let now = Instant::now();
+        let Some(last_end) = self.last_end.replace(now) else {
+            return (Duration::ZERO, false);
+        };
+
+        let time = now - last_end;
+        self.times.push(time);
+
+        (time, time > self.target * JANK_FACTOR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame_times(millis: impl IntoIterator<Item = u64>) -> FrameTimes {
+        let mut times = FrameTimes::default();
+        for millis in millis {
+            times.push(Duration::from_millis(millis));
+        }
+        times
+    }
+
+    #[test]
+    fn percentiles_of_window() {
+        let times = frame_times(1..=100);
+        assert_eq!(Duration::from_millis(50), times.percentile(50.0));
+        assert_eq!(Duration::from_millis(99), times.percentile(99.0));
+        assert_eq!(Duration::from_millis(100), times.percentile(100.0));
+        assert_eq!(Duration::from_millis(1), times.percentile(0.0));
+        assert_eq!(Duration::ZERO, FrameTimes::default().percentile(50.0));
+
+        let times = frame_times(0..FRAME_TIME_WINDOW as u64 + 10);
+        assert_eq!(FRAME_TIME_WINDOW, times.len());
+        assert_eq!(Some(Duration::from_millis(10)), times.iter().next());
+    }
+
+    #[test]
+    fn jank_and_histogram() {
+        let times = frame_times([16, 17, 40, 16, 100]);
+        assert_eq!(2, times.jank_count(Duration::from_millis(16)));
+        assert_eq!(
+            vec![0, 3, 1, 1],
+            times.histogram(4, Duration::from_millis(64))
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use super::Renderer;
+use crate::ui::UiPlatform;
+use std::sync::atomic::{AtomicU8, Ordering};
+use thiserror::Error;
+
+const VALIDATION_DEFAULT: u8 = 0;
+const VALIDATION_ON: u8 = 1;
+const VALIDATION_OFF: u8 = 2;
+
+static VALIDATION: AtomicU8 = AtomicU8::new(VALIDATION_DEFAULT);
+
+/// Error kinds captured by Renderer::scope, pushed in this order
+const FILTERS: [wgpu::ErrorFilter; 3] = [
+    wgpu::ErrorFilter::Validation,
+    wgpu::ErrorFilter::OutOfMemory,
+    wgpu::ErrorFilter::Internal,
+];
+
+/// wgpu errors captured by Renderer::scope or Renderer::pop_error_scope
+#[derive(Debug, Error)]
+#[error(
+    "captured {} wgpu error(s): {}",
+    .0.len(),
+    .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+)]
+pub struct CapturedErrors(pub Vec<wgpu::Error>);
+
+/// Force wgpu validation on or off for renderers created after this call
+/// None restores the default: validation follows the build configuration
+/// (on with debug assertions) and the WGPU_VALIDATION environment variable.
+pub fn set_validation(enabled: Option<bool>) {
+    let value = match enabled {
+        None => VALIDATION_DEFAULT,
+        Some(true) => VALIDATION_ON,
+        Some(false) => VALIDATION_OFF,
+    };
+    VALIDATION.store(value, Ordering::Relaxed);
+}
+
+/// The flags renderers create their wgpu::Instance with
+pub fn instance_flags() -> wgpu::InstanceFlags {
+    let mut flags = wgpu::InstanceFlags::from_build_config().with_env();
+
+    match VALIDATION.load(Ordering::Relaxed) {
+        VALIDATION_ON => flags |= wgpu::InstanceFlags::debugging(),
+        VALIDATION_OFF => flags.remove(wgpu::InstanceFlags::VALIDATION),
+        _ => (),
+    }
+
+    flags
+}
+
+impl<P: UiPlatform> Renderer<'_, P> {
+    /// Start capturing wgpu errors instead of sending them to the uncaptured error handler
+    /// Every call must be paired with a call to Renderer::pop_error_scope.
+    pub fn push_error_scope(&self) {
+        for filter in FILTERS {
+            self.device.push_error_scope(filter);
+        }
+    }
+
+    /// Stop capturing wgpu errors, returning the errors captured since the matching push
+    pub async fn pop_error_scope(&self) -> Result<(), CapturedErrors> {
+        let mut errors = vec![];
+        for _ in FILTERS {
+            if let Some(error) = self.device.pop_error_scope().await {
+                errors.push(error);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            log::debug!(
+                "aftgraphs::render::Renderer::pop_error_scope: captured {} error(s)",
+                errors.len()
+            );
+            Err(CapturedErrors(errors))
+        }
+    }
+
+    /// Run f while capturing wgpu errors
+    /// Lets tests and debug builds assert that no validation errors occurred.
+    /// For async work, use Renderer::push_error_scope and Renderer::pop_error_scope.
+    pub async fn scope<R>(&self, f: impl FnOnce(&Self) -> R) -> Result<R, CapturedErrors> {
+        self.push_error_scope();
+        let result = f(self);
+        self.pop_error_scope().await.map(|()| result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validation_override() {
+        set_validation(Some(true));
+        assert!(instance_flags().contains(wgpu::InstanceFlags::VALIDATION));
+
+        set_validation(Some(false));
+        assert!(!instance_flags().contains(wgpu::InstanceFlags::VALIDATION));
+
+        set_validation(None);
+        assert_eq!(
+            wgpu::InstanceFlags::from_build_config().with_env(),
+            instance_flags()
+        );
+    }
+}
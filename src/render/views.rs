@@ -0,0 +1,201 @@
+use super::{Allocation, BindGroupLayout, RenderPipeline, Renderer, ResourceKind};
+use crate::stereo::mul;
+use crate::ui::UiPlatform;
+use crate::uniform::Mat4;
+use std::sync::Arc;
+use wgpu::RenderPass;
+
+/// Views Renderer::set_views holds, enough for the faces of a cubemap
+pub const MAX_VIEWS: usize = 6;
+
+/// Declarations prepended by ShaderBuilder::with_views, {group} is replaced by its group
+const VIEWS_HEADER: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/views.wgsl"));
+
+/// The WGSL declarations of the views uniform at group
+pub(crate) fn views_header(group: u32) -> String {
+    VIEWS_HEADER.replace("{group}", &group.to_string())
+}
+
+/// One view of a multiview pipeline, the View struct of res/views.wgsl
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct ViewParams {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    /// xyz is the camera's world position, w is 1
+    pub position: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for ViewParams {}
+unsafe impl bytemuck::Pod for ViewParams {}
+
+impl Default for ViewParams {
+    fn default() -> Self {
+        Self {
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+            view_projection: Mat4::IDENTITY,
+            position: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl ViewParams {
+    /// A view from its matrices, view_projection is their product
+    pub fn new(view: Mat4, projection: Mat4, position: [f32; 3]) -> Self {
+        let [x, y, z] = position;
+        Self {
+            view,
+            projection,
+            view_projection: mul(projection, view),
+            position: [x, y, z, 1.0],
+        }
+    }
+}
+
+/// The Views struct of res/views.wgsl
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct ViewsUniform {
+    views: [ViewParams; MAX_VIEWS],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for ViewsUniform {}
+unsafe impl bytemuck::Pod for ViewsUniform {}
+
+impl ViewsUniform {
+    /// The first MAX_VIEWS of views
+    fn new(views: &[ViewParams]) -> Self {
+        let mut uniform = Self {
+            views: [ViewParams::default(); MAX_VIEWS],
+            count: views.len().min(MAX_VIEWS) as u32,
+            _padding: [0; 3],
+        };
+        for (slot, view) in uniform.views.iter_mut().zip(views) {
+            *slot = *view;
+        }
+        uniform
+    }
+}
+
+pub(crate) struct ViewResources {
+    pub(crate) layout: BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    _allocation: Allocation,
+}
+
+/// The views uniform of a Renderer, created when first used
+#[derive(Default)]
+pub(crate) struct Views {
+    resources: Option<Arc<ViewResources>>,
+}
+
+impl Views {
+    pub(crate) fn resources<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+    ) -> Arc<ViewResources> {
+        self.resources
+            .get_or_insert_with(|| Arc::new(Views::create(renderer)))
+            .clone()
+    }
+
+    fn create<P: UiPlatform>(renderer: &Renderer<P>) -> ViewResources {
+        let label = Some("aftgraphs::render::views::Views");
+        let layout = super::BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: super::BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        let size = std::mem::size_of::<ViewsUniform>() as u64;
+        let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        renderer.queue.write_buffer(
+            &buffer,
+            0,
+            bytemuck::bytes_of(&ViewsUniform::new(&[ViewParams::default()])),
+        );
+        let bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+        ViewResources {
+            layout,
+            buffer,
+            bind_group,
+            _allocation: renderer.track_memory(ResourceKind::Uniform, label, size),
+        }
+    }
+}
+
+impl<P: UiPlatform> Renderer<'_, P> {
+    pub(crate) fn view_resources(&self) -> Arc<ViewResources> {
+        self.views
+            .lock()
+            .expect("aftgraphs::render::Renderer::view_resources: poisoned lock")
+            .resources(self)
+    }
+
+    /// Set the views of pipelines with shaders built with ShaderBuilder::with_views,
+    /// one per layer of a multiview render pass, at most MAX_VIEWS
+    pub fn set_views(&self, views: &[ViewParams]) {
+        if views.len() > MAX_VIEWS {
+            log::warn!(
+                "aftgraphs::render::Renderer::set_views: {} views given, only the first {MAX_VIEWS} are used",
+                views.len()
+            );
+        }
+        let resources = self.view_resources();
+        self.queue.write_buffer(
+            &resources.buffer,
+            0,
+            bytemuck::bytes_of(&ViewsUniform::new(views)),
+        );
+        self.record_upload(std::mem::size_of::<ViewsUniform>());
+    }
+
+    /// Bind the views at the group pipeline's shaders declared them at, if they did
+    pub fn bind_views(&self, render_pass: &mut RenderPass<'_>, pipeline: &RenderPipeline) {
+        if let Some(group) = pipeline.views_group() {
+            render_pass.set_bind_group(group, &self.view_resources().bind_group, &[]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uniform_matches_wgsl_layout() {
+        // Three mat4x4<f32> and a vec4<f32> per view, then the count padded to 16 bytes
+        assert_eq!(208, std::mem::size_of::<ViewParams>());
+        assert_eq!(208 * MAX_VIEWS + 16, std::mem::size_of::<ViewsUniform>());
+        assert!(views_header(2).contains("@group(2) @binding(0)"));
+
+        let views =
+            [ViewParams::new(Mat4::IDENTITY, Mat4::IDENTITY, [1.0, 2.0, 3.0]); MAX_VIEWS + 1];
+        let uniform = ViewsUniform::new(&views);
+        assert_eq!(MAX_VIEWS as u32, uniform.count);
+        assert_eq!([1.0, 2.0, 3.0, 1.0], uniform.views[MAX_VIEWS - 1].position);
+    }
+}
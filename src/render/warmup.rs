@@ -0,0 +1,139 @@
+use super::{PipelineError, RenderPipeline, Renderer};
+use crate::ui::UiPlatform;
+use winit::window::Window;
+
+type PipelineTask<P> = Box<dyn FnOnce(&Renderer<'_, P>) -> Result<RenderPipeline, PipelineError>>;
+
+/// Pipelines declared in Simulation::prepare.
+/// They are compiled behind a loading screen before Simulation::new,
+/// which takes them back with Renderer::take_pipeline
+pub struct Warmup<P: UiPlatform> {
+    tasks: Vec<(String, PipelineTask<P>)>,
+}
+
+impl<P: UiPlatform> Default for Warmup<P> {
+    fn default() -> Self {
+        Self { tasks: vec![] }
+    }
+}
+
+impl<P: UiPlatform> Warmup<P> {
+    /// Compile the pipeline returned by build before the first frame, stored under label
+    /// A pipeline declared again under the same label replaces the earlier one.
+    pub fn pipeline(
+        &mut self,
+        label: impl Into<String>,
+        build: impl FnOnce(&Renderer<'_, P>) -> Result<RenderPipeline, PipelineError> + 'static,
+    ) {
+        let label = label.into();
+        match self
+            .tasks
+            .iter_mut()
+            .find(|(declared, _)| *declared == label)
+        {
+            Some(task) => {
+                log::warn!("aftgraphs::render::Warmup::pipeline: {label} was already declared");
+                task.1 = Box::new(build);
+            }
+            None => self.tasks.push((label, Box::new(build))),
+        }
+    }
+
+    /// The labels of the declared pipelines, in the order they are compiled
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.tasks.iter().map(|(label, _)| label.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Compile every pipeline, drawing the progress to window in between
+    /// Pipelines that fail to build are logged, Renderer::take_pipeline returns None for them.
+    pub(crate) async fn run(self, renderer: &mut Renderer<'_, P>, window: Option<&Window>) {
+        let total = self.tasks.len();
+        for (done, (label, build)) in self.tasks.into_iter().enumerate() {
+            log::debug!("aftgraphs::render::Warmup::run: compiling {label} ({done}/{total})");
+            Self::draw(renderer, window, &label, progress(done, total)).await;
+
+            match build(renderer) {
+                Ok(pipeline) => {
+                    renderer
+                        .prepared
+                        .lock()
+                        .expect("aftgraphs::render::Warmup::run: poisoned lock")
+                        .insert(label, pipeline);
+                }
+                Err(e) => log::error!("aftgraphs::render::Warmup::run: {e}"),
+            }
+        }
+        Self::draw(renderer, window, "pipelines", progress(total, total)).await;
+    }
+
+    async fn draw(
+        renderer: &mut Renderer<'_, P>,
+        window: Option<&Window>,
+        label: &str,
+        progress: f32,
+    ) {
+        if let Err(e) = renderer.draw_loading(window, label, progress).await {
+            log::warn!("aftgraphs::render::Warmup::draw: failed to draw loading screen: {e}");
+        }
+    }
+}
+
+/// Fraction of the pipelines compiled, done / total
+fn progress(done: usize, total: usize) -> f32 {
+    if total == 0 {
+        1.0
+    } else {
+        done as f32 / total as f32
+    }
+}
+
+/// Centered progress bar of the pipeline being compiled
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn draw_progress(frame: &imgui::Ui, label: &str, progress: f32) {
+    let [width, height] = frame.io().display_size;
+    frame
+        .window("Loading")
+        .position([width / 2.0, height / 2.0], imgui::Condition::Always)
+        .position_pivot([0.5, 0.5])
+        .size([width / 2.0, 0.0], imgui::Condition::Always)
+        .no_decoration()
+        .movable(false)
+        .build(|| {
+            frame.text(format!("Compiling {label}"));
+            imgui::ProgressBar::new(progress)
+                .size([-1.0, 0.0])
+                .build(frame);
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warmup_keys_pipelines_by_owned_labels() {
+        let mut warmup = Warmup::<()>::default();
+        for tile in 0..3 {
+            warmup.pipeline(format!("tile {tile}"), |_| unreachable!());
+        }
+        warmup.pipeline("tile 1", |_| unreachable!());
+        assert_eq!(
+            vec!["tile 0", "tile 1", "tile 2"],
+            warmup.labels().collect::<Vec<_>>()
+        );
+        assert_eq!(3, warmup.len());
+
+        assert_eq!(0.0, progress(0, 4));
+        assert_eq!(0.5, progress(2, 4));
+        assert_eq!(1.0, progress(4, 4));
+        assert_eq!(1.0, progress(0, 0));
+    }
+}
@@ -6,9 +6,23 @@ use winit::{event::Event, window::Window};
 impl<'a, P: UiPlatform> Renderer<'a, P> {
     pub fn handle_event<T>(&mut self, _window: &Window, _event: &Event<T>) {}
 
-    pub async fn prepare_ui(&mut self, _window: &Window) {}
+    pub fn prepare_ui(&mut self, _window: &Window) {}
 
-    pub fn update_delta_time(&mut self, duration: Duration) {
-        self.delta_time = duration.as_secs_f64();
+    /// Start a frame at wall_time, delta after the previous frame
+    pub fn update_clock(&mut self, wall_time: f64, delta: Duration) {
+        self.clock.tick(wall_time, delta);
+    }
+
+    /// If the user asked to minimize non-essential motion, with the prefers-reduced-motion media query
+    /// Simulations should slow down or skip animations that are not the point of the simulation.
+    pub fn prefers_reduced_motion(&self) -> bool {
+        web_sys::window()
+            .and_then(|window| {
+                window
+                    .match_media("(prefers-reduced-motion: reduce)")
+                    .ok()
+                    .flatten()
+            })
+            .is_some_and(|query| query.matches())
     }
 }
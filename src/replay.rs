@@ -0,0 +1,164 @@
+use crate::simulation::SerializableSimulation;
+use std::collections::VecDeque;
+
+/// Seconds of simulation state kept for replays
+pub const REPLAY_LENGTH: f64 = 10.0;
+/// Playback speed of replays
+pub const REPLAY_SPEED: f64 = 0.25;
+
+struct Playback {
+    /// Time of the recording being shown
+    cursor: f64,
+    /// State of the simulation when the replay started, restored at the end
+    resume: Vec<u8>,
+}
+
+/// Ring buffer of the last length seconds of simulation snapshots, replayed at speed
+pub(crate) struct ReplayBuffer {
+    length: f64,
+    speed: f64,
+    snapshots: VecDeque<(f64, Vec<u8>)>,
+    playback: Option<Playback>,
+    requested: bool,
+}
+
+impl Default for ReplayBuffer {
+    fn default() -> Self {
+        Self::new(REPLAY_LENGTH, REPLAY_SPEED)
+    }
+}
+
+impl ReplayBuffer {
+    pub(crate) fn new(length: f64, speed: f64) -> Self {
+        Self {
+            length,
+            speed,
+            snapshots: VecDeque::new(),
+            playback: None,
+            requested: false,
+        }
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Start a replay on the next frame, or stop the running one
+    pub(crate) fn toggle(&mut self) {
+        self.requested = true;
+    }
+
+    /// Load the snapshot to show this frame into simulation while replaying
+    /// Returns if a replay is running.
+    pub(crate) fn before_render(
+        &mut self,
+        simulation: &mut dyn SerializableSimulation,
+        delta_time: f64,
+    ) -> bool {
+        if std::mem::take(&mut self.requested) {
+            if self.is_playing() {
+                self.finish(simulation);
+                return false;
+            }
+
+            let Some(&(start, _)) = self.snapshots.front() else {
+                log::info!("aftgraphs::replay::ReplayBuffer::before_render: Nothing to replay");
+                return false;
+            };
+            log::info!(
+                "aftgraphs::replay::ReplayBuffer::before_render: Replaying {:.1} s at {}x speed",
+                self.recorded(),
+                self.speed
+            );
+            self.playback = Some(Playback {
+                cursor: start,
+                resume: simulation.save_state(),
+            });
+        }
+
+        let Some(ref mut playback) = self.playback else {
+            return false;
+        };
+
+        let end = self.snapshots.back().map_or(0.0, |&(time, _)| time);
+        if playback.cursor > end {
+            self.finish(simulation);
+            return false;
+        }
+
+        let idx = self
+            .snapshots
+            .partition_point(|&(time, _)| time <= playback.cursor);
+        playback.cursor += delta_time * self.speed;
+        simulation.load_state(&self.snapshots[idx.saturating_sub(1)].1);
+        true
+    }
+
+    /// Record the state of simulation at time, unless replaying
+    pub(crate) fn after_render(&mut self, simulation: &mut dyn SerializableSimulation, time: f64) {
+        if self.is_playing() {
+            return;
+        }
+
+        self.snapshots.push_back((time, simulation.save_state()));
+        while self
+            .snapshots
+            .front()
+            .is_some_and(|&(start, _)| time - start > self.length)
+        {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Seconds of state in the buffer
+    fn recorded(&self) -> f64 {
+        match (self.snapshots.front(), self.snapshots.back()) {
+            (Some((start, _)), Some((end, _))) => end - start,
+            _ => 0.0,
+        }
+    }
+
+    fn finish(&mut self, simulation: &mut dyn SerializableSimulation) {
+        if let Some(playback) = self.playback.take() {
+            log::info!("aftgraphs::replay::ReplayBuffer::finish: Replay finished");
+            simulation.load_state(&playback.resume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Counter(u8);
+
+    impl SerializableSimulation for Counter {
+        fn save_state(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn load_state(&mut self, state: &[u8]) {
+            self.0 = state[0];
+        }
+    }
+
+    #[test]
+    fn replay_resumes_live_state() {
+        let mut replay = ReplayBuffer::new(2.0, 0.5);
+        let mut counter = Counter(0);
+        for frame in 0..=4u8 {
+            counter.0 = frame;
+            replay.after_render(&mut counter, frame as f64);
+        }
+        // Only the last two seconds are kept
+        assert_eq!(2.0, replay.recorded());
+
+        replay.toggle();
+        let mut shown = vec![];
+        while replay.before_render(&mut counter, 1.0) {
+            shown.push(counter.0);
+        }
+        assert_eq!(vec![2, 2, 3, 3, 4], shown);
+        assert_eq!(4, counter.0);
+    }
+}
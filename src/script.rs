@@ -0,0 +1,344 @@
+//! Simulation logic scripted in [Rhai](https://rhai.rs), prototyped without recompiling.
+//!
+//! The script runs before every frame is rendered:
+//!
+//! ```text
+//! let speed = input("controls.speed") * 2.0;
+//! uniform("rotation", (time * speed * 90.0).to_radians() % (2.0 * PI()));
+//! if input("controls.trail") {
+//!     draw("trail", 0..4, 0..frame % 100);
+//! }
+//! ```
+//!
+//! `time`, `dt`, `wall_time` and `frame` come from the renderer's Clock, `input(name)` reads
+//! a slider as a float or a checkbox as a bool, and is `()` for unknown inputs.
+//! `uniform(name, value)` writes the uniform registered under name with
+//! Renderer::register_uniform as an f32. `draw(name, vertices, instances)` spawns a draw
+//! the simulation issues with the pipeline it calls name, see Renderer::script_draws.
+//! Scripts loaded from a file are reloaded when the file changes.
+//! Scripting needs the `rhai` feature, without it scripts fail to load.
+
+use crate::input::InputValue;
+use crate::render::{Clock, RenderPass};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("failed to read script: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "rhai")]
+    #[error("{0}")]
+    Parse(#[from] rhai::ParseError),
+    #[cfg(feature = "rhai")]
+    #[error("{0}")]
+    Run(#[from] Box<rhai::EvalAltResult>),
+    #[error("scripting needs the rhai feature")]
+    Unsupported,
+}
+
+/// A draw spawned by a script, issued by the simulation with the pipeline named name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptDraw {
+    pub name: String,
+    pub vertices: Range<u32>,
+    pub instances: Range<u32>,
+}
+
+impl ScriptDraw {
+    /// Record the draw into render_pass, with the pipeline and bindings already set
+    pub fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        render_pass.draw(self.vertices.clone(), self.instances.clone());
+    }
+}
+
+/// The uniform writes and draws of one run of a script
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptOutput {
+    pub uniforms: Vec<(String, f32)>,
+    pub draws: Vec<ScriptDraw>,
+}
+
+/// What the functions registered with the engine read and write during a run
+#[cfg(feature = "rhai")]
+#[derive(Default)]
+struct Calls {
+    inputs: HashMap<String, InputValue>,
+    output: ScriptOutput,
+}
+
+#[cfg(feature = "rhai")]
+type SharedCalls = std::sync::Arc<std::sync::Mutex<Calls>>;
+
+#[cfg(feature = "rhai")]
+fn lock(calls: &SharedCalls) -> std::sync::MutexGuard<'_, Calls> {
+    calls
+        .lock()
+        .expect("aftgraphs::script::Script: poisoned lock")
+}
+
+/// A Rhai range as vertex or instance indices, negative ends clamped to 0
+#[cfg(feature = "rhai")]
+fn indices(range: Range<rhai::INT>) -> Range<u32> {
+    let index = |end: rhai::INT| end.clamp(0, u32::MAX as rhai::INT) as u32;
+    index(range.start)..index(range.end)
+}
+
+/// An engine with the functions of the module documentation, recording into calls
+#[cfg(feature = "rhai")]
+fn engine(calls: &SharedCalls) -> rhai::Engine {
+    use rhai::{Dynamic, FLOAT, INT};
+
+    let mut engine = rhai::Engine::new();
+
+    let shared = calls.clone();
+    engine.register_fn("input", move |name: &str| -> Dynamic {
+        match lock(&shared).inputs.get(name) {
+            Some(&InputValue::SLIDER(value)) => Dynamic::from_float(value as FLOAT),
+            Some(&InputValue::CHECKBOX(checked)) => Dynamic::from_bool(checked),
+            None => Dynamic::UNIT,
+        }
+    });
+
+    let shared = calls.clone();
+    engine.register_fn("uniform", move |name: &str, value: FLOAT| {
+        let uniform = (name.to_owned(), value as f32);
+        lock(&shared).output.uniforms.push(uniform);
+    });
+    let shared = calls.clone();
+    engine.register_fn("uniform", move |name: &str, value: INT| {
+        let uniform = (name.to_owned(), value as f32);
+        lock(&shared).output.uniforms.push(uniform);
+    });
+
+    let shared = calls.clone();
+    engine.register_fn(
+        "draw",
+        move |name: &str, vertices: Range<INT>, instances: Range<INT>| {
+            let draw = ScriptDraw {
+                name: name.to_owned(),
+                vertices: indices(vertices),
+                instances: indices(instances),
+            };
+            lock(&shared).output.draws.push(draw);
+        },
+    );
+    let shared = calls.clone();
+    engine.register_fn("draw", move |name: &str, vertices: Range<INT>| {
+        let draw = ScriptDraw {
+            name: name.to_owned(),
+            vertices: indices(vertices),
+            instances: 0..1,
+        };
+        lock(&shared).output.draws.push(draw);
+    });
+
+    engine
+}
+
+/// A compiled script, see the module documentation
+pub struct Script {
+    #[cfg(feature = "rhai")]
+    engine: rhai::Engine,
+    #[cfg(feature = "rhai")]
+    ast: rhai::AST,
+    #[cfg(feature = "rhai")]
+    calls: SharedCalls,
+    /// Draws spawned by the last run
+    draws: Vec<ScriptDraw>,
+    /// The last run failed, so the same error is not logged every frame
+    failing: bool,
+    /// File the script was loaded from and the changes to it, for hot reload
+    #[cfg(not(target_arch = "wasm32"))]
+    source: Option<(PathBuf, crate::watch::Watch)>,
+}
+
+impl Script {
+    #[cfg_attr(not(feature = "rhai"), allow(unused_variables))]
+    pub fn parse(src: &str) -> Result<Self, ScriptError> {
+        #[cfg(feature = "rhai")]
+        {
+            let calls = SharedCalls::default();
+            let engine = engine(&calls);
+            let ast = engine.compile(src)?;
+            Ok(Self {
+                engine,
+                ast,
+                calls,
+                draws: vec![],
+                failing: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                source: None,
+            })
+        }
+        #[cfg(not(feature = "rhai"))]
+        Err(ScriptError::Unsupported)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self, ScriptError> {
+        let path = path.into();
+        let mut script = Self::parse(&std::fs::read_to_string(&path)?)?;
+        let watch = crate::watch::Watch::new([path.clone()], []);
+        script.source = Some((path, watch));
+        Ok(script)
+    }
+
+    /// Recompile the script if its file changed, keeping the old script if the new one fails
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_if_changed(&mut self) {
+        let Some((ref path, ref watch)) = self.source else {
+            return;
+        };
+        if !watch.changed() {
+            return;
+        }
+
+        let script = std::fs::read_to_string(path)
+            .map_err(ScriptError::from)
+            .and_then(|src| Self::parse(&src));
+        match script {
+            Ok(script) => {
+                log::info!(
+                    "aftgraphs::script::Script::reload_if_changed: reloaded {}",
+                    path.display()
+                );
+                let source = self.source.take();
+                *self = Self { source, ..script };
+            }
+            Err(e) => log::error!(
+                "aftgraphs::script::Script::reload_if_changed: {}: {e}",
+                path.display()
+            ),
+        }
+    }
+
+    /// The script given on the command line, if any
+    pub(crate) async fn startup() -> Option<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = crate::cli::ARGUMENTS.read().await.script.clone()?;
+            match Self::from_file(&path) {
+                Ok(script) => return Some(script),
+                Err(e) => log::error!(
+                    "aftgraphs::script::Script::startup: failed to load {}: {e}",
+                    path.display()
+                ),
+            }
+        }
+        None
+    }
+
+    /// Run the script, returning its uniform writes in order and the draws it spawned
+    #[cfg_attr(not(feature = "rhai"), allow(unused_variables))]
+    pub fn run(
+        &mut self,
+        inputs: &HashMap<String, InputValue>,
+        clock: &Clock,
+    ) -> Result<ScriptOutput, ScriptError> {
+        #[cfg(feature = "rhai")]
+        {
+            use rhai::{FLOAT, INT};
+
+            lock(&self.calls).inputs.clone_from(inputs);
+            let mut scope = rhai::Scope::new();
+            scope.push_constant("time", clock.time() as FLOAT);
+            scope.push_constant("dt", clock.delta_time() as FLOAT);
+            scope.push_constant("wall_time", clock.wall_time() as FLOAT);
+            scope.push_constant("frame", clock.frame() as INT);
+
+            let result = self.engine.run_ast_with_scope(&mut scope, &self.ast);
+            // Writes made before an error are dropped with it
+            let output = std::mem::take(&mut lock(&self.calls).output);
+            result?;
+            Ok(output)
+        }
+        #[cfg(not(feature = "rhai"))]
+        Err(ScriptError::Unsupported)
+    }
+
+    /// Run the script for a frame, keeping its draws for Script::draws
+    /// Errors are logged when the script starts failing, and the frame gets no draws.
+    pub(crate) fn run_frame(
+        &mut self,
+        inputs: &HashMap<String, InputValue>,
+        clock: &Clock,
+    ) -> Vec<(String, f32)> {
+        match self.run(inputs, clock) {
+            Ok(output) => {
+                self.failing = false;
+                self.draws = output.draws;
+                output.uniforms
+            }
+            Err(e) => {
+                if !self.failing {
+                    log::error!("aftgraphs::script::Script::run_frame: {e}");
+                }
+                self.failing = true;
+                self.draws.clear();
+                vec![]
+            }
+        }
+    }
+
+    /// The draws spawned by the last frame's run
+    pub fn draws(&self) -> &[ScriptDraw] {
+        &self.draws
+    }
+}
+
+#[cfg(all(test, feature = "rhai"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn script_writes_uniforms_and_spawns_draws() {
+        let mut script = Script::parse(
+            r#"
+            // Double the slider
+            let speed = input("controls.speed") * 2.0;
+            uniform("rotation", -speed * speed);
+            uniform("count", 3);
+            if input("controls.trail") {
+                draw("trail", 0..4, 0..frame + 2);
+            }
+            draw("quad", -1..6);
+            "#,
+        )
+        .unwrap();
+
+        let inputs = [
+            ("controls.speed".to_owned(), InputValue::SLIDER(1.5)),
+            ("controls.trail".to_owned(), InputValue::CHECKBOX(true)),
+        ]
+        .into_iter()
+        .collect();
+        let output = script.run(&inputs, &Clock::default()).unwrap();
+        assert_eq!(
+            vec![("rotation".to_owned(), -9.0), ("count".to_owned(), 3.0)],
+            output.uniforms
+        );
+        assert_eq!(
+            vec![
+                ScriptDraw {
+                    name: "trail".to_owned(),
+                    vertices: 0..4,
+                    instances: 0..2,
+                },
+                ScriptDraw {
+                    name: "quad".to_owned(),
+                    vertices: 0..6,
+                    instances: 0..1,
+                },
+            ],
+            output.draws
+        );
+
+        let mut failing = Script::parse(r#"uniform("x", 1.0); undefined()"#).unwrap();
+        assert!(failing.run(&inputs, &Clock::default()).is_err());
+        assert!(failing.run_frame(&inputs, &Clock::default()).is_empty());
+        assert!(Script::parse("let x = ;").is_err());
+    }
+}
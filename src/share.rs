@@ -0,0 +1,179 @@
+use memmap2::MmapMut;
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use thiserror::Error;
+
+/// Identifies a shared frame file
+pub const SHARED_FRAME_MAGIC: [u8; 8] = *b"AFTFRAME";
+pub const SHARED_FRAME_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ShareError {
+    #[error("failed to share frames: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("frame sharing through {0} is not supported")]
+    Unsupported(&'static str),
+}
+
+/// Header at the start of a shared frame file, followed by height rows of width RGBA8 pixels
+/// frame is odd while a frame is being written, readers retry until it is even and unchanged.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SharedFrameHeader {
+    pub magic: [u8; 8],
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub _padding: u32,
+    pub frame: u64,
+}
+
+unsafe impl bytemuck::Zeroable for SharedFrameHeader {}
+unsafe impl bytemuck::Pod for SharedFrameHeader {}
+
+const HEADER_SIZE: usize = std::mem::size_of::<SharedFrameHeader>();
+const FRAME_OFFSET: usize = std::mem::offset_of!(SharedFrameHeader, frame);
+
+/// Publishes rendered frames to other applications
+/// On Linux frames go to a memory mapped file in /dev/shm, Spout and Syphon are not supported yet.
+pub struct FrameShare {
+    path: PathBuf,
+    map: MmapMut,
+    width: u32,
+    height: u32,
+    frame: u64,
+}
+
+impl FrameShare {
+    /// Share frames of size under name, e.g. /dev/shm/aftgraphs-name on Linux
+    pub fn open(name: &str, size: (u32, u32)) -> Result<Self, ShareError> {
+        let share = if cfg!(target_os = "linux") {
+            FrameShare::at(
+                Path::new("/dev/shm").join(format!("aftgraphs-{name}")),
+                size,
+            )
+        } else if cfg!(target_os = "macos") {
+            Err(ShareError::Unsupported("Syphon"))
+        } else if cfg!(target_os = "windows") {
+            Err(ShareError::Unsupported("Spout"))
+        } else {
+            Err(ShareError::Unsupported("shared memory"))
+        };
+
+        share.inspect_err(|err| log::error!("aftgraphs::share::FrameShare::open: {name}: {err}"))
+    }
+
+    /// Share frames through the file at path
+    pub fn at(path: PathBuf, (width, height): (u32, u32)) -> Result<Self, ShareError> {
+        let len = HEADER_SIZE + width as usize * height as usize * 4;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(len as u64)?;
+
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..HEADER_SIZE].copy_from_slice(bytemuck::bytes_of(&SharedFrameHeader {
+            magic: SHARED_FRAME_MAGIC,
+            version: SHARED_FRAME_VERSION,
+            width,
+            height,
+            _padding: 0,
+            frame: 0,
+        }));
+
+        log::info!(
+            "aftgraphs::share::FrameShare::at: Sharing {width}x{height} frames at {}",
+            path.display()
+        );
+        Ok(Self {
+            path,
+            map,
+            width,
+            height,
+            frame: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Publish a frame of RGBA8 rows bytes_per_row apart, as read back from the render target
+    pub fn publish(&mut self, rgba: &[u8], bytes_per_row: usize) {
+        let row_len = self.width as usize * 4;
+        let needed = bytes_per_row * (self.height as usize).saturating_sub(1) + row_len;
+        if bytes_per_row < row_len || rgba.len() < needed {
+            log::error!(
+                "aftgraphs::share::FrameShare::publish: frame of {} bytes does not fit {}x{}",
+                rgba.len(),
+                self.width,
+                self.height
+            );
+            return;
+        }
+
+        // Safety: the map is page aligned, so the frame counter is 8 byte aligned
+        let frame = unsafe { AtomicU64::from_ptr(self.map.as_mut_ptr().add(FRAME_OFFSET).cast()) };
+        frame.store(self.frame * 2 + 1, Ordering::Release);
+
+        let pixels = &mut self.map[HEADER_SIZE..];
+        for (dst, src) in pixels
+            .chunks_exact_mut(row_len)
+            .zip(rgba.chunks(bytes_per_row))
+        {
+            dst.copy_from_slice(&src[..row_len]);
+        }
+
+        self.frame += 1;
+        frame.store(self.frame * 2, Ordering::Release);
+    }
+}
+
+impl Drop for FrameShare {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!("aftgraphs::share::FrameShare::drop: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn publish_strips_row_padding() {
+        let path = std::env::temp_dir().join(format!("aftgraphs-share-{}", std::process::id()));
+        let mut share = FrameShare::at(path.clone(), (1, 2)).unwrap();
+        share.publish(&[1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8], 8);
+
+        let contents = std::fs::read(&path).unwrap();
+        let header: SharedFrameHeader = bytemuck::pod_read_unaligned(&contents[..HEADER_SIZE]);
+        assert_eq!(SHARED_FRAME_MAGIC, header.magic);
+        assert_eq!(2, header.frame);
+        assert_eq!(&[1, 2, 3, 4, 5, 6, 7, 8], &contents[HEADER_SIZE..]);
+    }
+
+    #[test]
+    fn publish_takes_aligned_rows_as_read_back() {
+        // 64 pixels fill a row exactly, so the readback has no padding
+        let path =
+            std::env::temp_dir().join(format!("aftgraphs-share-aligned-{}", std::process::id()));
+        let mut share = FrameShare::at(path.clone(), (64, 2)).unwrap();
+        let bytes_per_row = crate::render::padded_bytes_per_row(64) as usize;
+        let frame: Vec<u8> = (0..2 * bytes_per_row)
+            .map(|byte| (byte / 256) as u8)
+            .collect();
+        share.publish(&frame, frame.len() / 2);
+
+        let contents = std::fs::read(&path).unwrap();
+        assert_eq!(frame.as_slice(), &contents[HEADER_SIZE..]);
+        assert_eq!(1, contents[HEADER_SIZE + 64 * 4]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     input::{InputValue, Inputs},
-    render::{RenderError, Renderer},
+    render::{Frame, Layer, RenderError, RenderPass, Renderer, Warmup},
     ui::{UiPlatform, UiWinitPlatform},
     GraphicsInitError,
 };
@@ -8,9 +8,11 @@ use async_std::sync::Mutex;
 use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 use thiserror::Error;
 pub use winit::event::{ElementState, MouseButton, RawKeyEvent};
+pub use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::{
     error::EventLoopError,
     event_loop::{ControlFlow, EventLoop},
+    window::Window,
 };
 
 #[derive(Clone)]
@@ -18,22 +20,213 @@ pub enum InputEvent {
     Keyboard(RawKeyEvent),
     /// f64 pair is (x, y) coordinates in [-1, 1] space
     Mouse(ElementState, MouseButton, (f64, f64)),
+    /// A message of the DataStream with the given name
+    Data(String, serde_json::Value),
+}
+
+/// An input and the time it happened, in seconds like Renderer::time
+#[derive(Clone)]
+pub struct TimedInput {
+    pub event: InputEvent,
+    pub time: f64,
 }
 
 pub trait Simulation: 'static {
+    /// Queue inputs for Renderer::drain_inputs instead of calling on_input_at for each
+    /// The simulation is then only locked once per frame, even under event storms.
+    /// Inputs render does not drain are passed to on_input_at after it, in order.
+    const QUEUE_INPUTS: bool = false;
+
+    /// Frames depend only on Clock::time, the input values and the input events, not on
+    /// earlier frames. Lets headless renders split frames across GPUs with --gpus, every
+    /// GPU's copy of the simulation gets all of the inputs.
+    const STATELESS: bool = false;
+
     #[allow(async_fn_in_trait)]
     async fn render<P: UiPlatform>(
         &mut self,
         renderer: &Renderer<P>,
-        render_pass: wgpu::RenderPass<'_>,
+        render_pass: RenderPass<'_>,
         inputs: &mut HashMap<String, InputValue>,
     );
 
+    /// Draw a layer other than Layer::Simulation, only called for layers added
+    /// with Renderer::add_layer
+    #[allow(async_fn_in_trait)]
+    async fn render_layer<P: UiPlatform>(
+        &mut self,
+        _renderer: &Renderer<'_, P>,
+        _layer: Layer,
+        _render_pass: RenderPass<'_>,
+        _inputs: &mut HashMap<String, InputValue>,
+    ) {
+    }
+
+    /// Record compute passes with Frame::begin_compute_pass and render passes into offscreen
+    /// targets with Frame::begin_pass, called every frame before the frame's own render passes
+    /// begin so that they can draw what the passes computed and sample what they drew
+    #[allow(async_fn_in_trait)]
+    async fn encode<P: UiPlatform>(
+        &mut self,
+        _renderer: &Renderer<'_, P>,
+        _frame: &mut Frame<'_>,
+        _inputs: &mut HashMap<String, InputValue>,
+    ) {
+    }
+
     #[allow(async_fn_in_trait)]
     async fn on_input(&mut self, event: InputEvent);
 
+    /// Called for every input with the time it happened, in seconds like Clock::wall_time
+    /// Inputs arrive between renders, Renderer::step_offset places them within the step
+    /// of the next render. Calls Simulation::on_input by default.
+    #[allow(async_fn_in_trait)]
+    async fn on_input_at(&mut self, event: InputEvent, _time: f64) {
+        self.on_input(event).await;
+    }
+
+    /// Declare pipelines to compile behind a loading screen before Simulation::new,
+    /// to avoid hitching on the first frame. Take them with Renderer::take_pipeline
+    fn prepare<P: UiPlatform>(_warmup: &mut Warmup<P>) {}
+
     #[allow(async_fn_in_trait)]
     async fn new<P: UiPlatform>(renderer: &Renderer<P>) -> Self;
+
+    /// Extra command line arguments, merged into the generated command
+    /// Their values are passed to Simulation::new_with. Ids must not clash with
+    /// the arguments of the crate, like --seed or --render.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn args() -> Vec<clap::Arg> {
+        vec![]
+    }
+
+    /// Create the simulation with the values of the arguments of Simulation::args
+    /// Calls Simulation::new by default.
+    #[allow(async_fn_in_trait)]
+    async fn new_with<P: UiPlatform>(renderer: &Renderer<P>, _config: &Config) -> Self {
+        Self::new(renderer).await
+    }
+
+    /// Return Some(self) if the simulation implements SerializableSimulation,
+    /// which enables the replay buffer in display mode
+    fn serializable(&mut self) -> Option<&mut dyn SerializableSimulation> {
+        None
+    }
+}
+
+/// A Simulation whose state can be saved and restored
+/// The state should hold everything Simulation::render steps, so loading a state
+/// and rendering with a zero delta_time shows the moment the state was saved.
+pub trait SerializableSimulation {
+    fn save_state(&self) -> Vec<u8>;
+
+    fn load_state(&mut self, state: &[u8]);
+}
+
+/// Compile the pipelines of Simulation::prepare, showing progress on window, then create T
+/// T is created again with fewer samples while it does not fit the memory budget,
+/// see Renderer::set_memory_downscale
+pub(crate) async fn create<T: Simulation, P: UiPlatform>(
+    renderer: &mut Renderer<'_, P>,
+    window: Option<&Window>,
+) -> T {
+    loop {
+        let mut warmup = Warmup::default();
+        T::prepare(&mut warmup);
+        if !warmup.is_empty() {
+            warmup.run(renderer, window).await;
+        }
+        let simulation = T::new_with(renderer, &Config::startup().await).await;
+        if !renderer.downscale_for_memory() {
+            renderer.save_pipeline_cache();
+            return simulation;
+        }
+
+        // Its pipelines and the prepared ones are built for the old sample count
+        drop(simulation);
+        renderer
+            .prepared
+            .lock()
+            .expect("aftgraphs::simulation::create: poisoned lock")
+            .clear();
+    }
+}
+
+/// Queue event for Renderer::drain_inputs or pass it on right away, see Simulation::QUEUE_INPUTS
+pub(crate) async fn send_input<T: Simulation, P: UiPlatform>(
+    renderer: &Renderer<'_, P>,
+    simulation: &Mutex<T>,
+    event: InputEvent,
+    time: f64,
+) {
+    if T::QUEUE_INPUTS {
+        renderer.queue_inputs([TimedInput { event, time }]);
+    } else {
+        simulation.lock().await.on_input_at(event, time).await;
+    }
+}
+
+/// Frames, the end signal and the thread writing them, see encoder::encoder
+#[cfg(not(target_arch = "wasm32"))]
+type FrameWriter = (
+    crossbeam::channel::Sender<Vec<u8>>,
+    crossbeam::channel::Sender<()>,
+    std::thread::JoinHandle<()>,
+);
+
+/// Starts writing the frames of a --transparent render with their alpha:
+/// as ProRes 4444 into a .mov out_file when built with the 'ffmpeg' feature,
+/// otherwise as an RGBA PNG sequence next to out_file. Only ProRes keeps the chapters.
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(feature = "ffmpeg"), allow(unused_variables))]
+fn transparent_writer(
+    size: (u32, u32),
+    delta_t: f64,
+    out_file: std::path::PathBuf,
+    chapters: Vec<crate::headless::Chapter>,
+) -> FrameWriter {
+    #[cfg(feature = "ffmpeg")]
+    if is_prores(&out_file) {
+        return prores::prores(size, delta_t, out_file, chapters);
+    }
+    sequence::sequence(size, out_file)
+}
+
+/// Whether transparent_writer encodes ProRes into out_file
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "ffmpeg")]
+fn is_prores(out_file: &std::path::Path) -> bool {
+    out_file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mov"))
+}
+
+/// Starts writing the frames of a headless render to out_file, as video or transparent_writer
+#[cfg(not(target_arch = "wasm32"))]
+fn frame_writer(
+    size: (u32, u32),
+    delta_t: f64,
+    out_file: std::path::PathBuf,
+    transparent: bool,
+    #[cfg_attr(not(feature = "x264"), allow(unused_variables))] bitrate: Option<u32>,
+) -> Result<FrameWriter, SimulationRunError> {
+    if transparent {
+        return Ok(transparent_writer(size, delta_t, out_file, vec![]));
+    }
+    #[cfg(feature = "x264")]
+    {
+        Ok(encoder::encoder(
+            size, delta_t, out_file, None, bitrate, None,
+        ))
+    }
+    #[cfg(not(feature = "x264"))]
+    {
+        log::error!(
+            "aftgraphs::simulation::frame_writer: {}",
+            SimulationRunError::HeadlessWithoutx264
+        );
+        Err(SimulationRunError::HeadlessWithoutx264)
+    }
 }
 
 pub struct SimulationContext<T: Simulation, P: UiPlatform> {
@@ -43,9 +236,30 @@ pub struct SimulationContext<T: Simulation, P: UiPlatform> {
     _platform: PhantomData<P>,
 }
 
+mod composite;
+mod config;
+pub use composite::{CompositeSimulation, SimulationSet, Viewport};
+pub use config::{Config, ConfigValue};
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(feature = "x264")]
 mod encoder;
+#[cfg(not(target_arch = "wasm32"))]
+mod ensemble;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "ffmpeg")]
+mod mux;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "ffmpeg")]
+mod prores;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "x264")]
+pub mod rtmp;
+#[cfg(not(target_arch = "wasm32"))]
+mod sequence;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "x264")]
+pub mod timings;
 
 #[derive(Error, Debug)]
 pub enum SimulationRunError {
@@ -59,6 +273,12 @@ pub enum SimulationRunError {
     HeadlessWithoutSize,
     #[error("headless video encoding failed: {0}")]
     HeadlessEncodingError(String),
+    #[error("headless crop failed: {0}")]
+    HeadlessCropError(#[from] crate::headless::CropError),
+    #[error("{0} renders can not be accumulated")]
+    HeadlessAccumulateUnsupported(&'static str),
+    #[error("writing headless output failed: {0}")]
+    HeadlessOutputError(#[from] std::io::Error),
     #[error("display rendering used without a winit::event::EventLoop")]
     DisplayWithoutEventLoop,
     #[error("display rendering used without a winit::window::Window")]
@@ -118,46 +338,64 @@ impl<T: Simulation> SimulationContext<T, ()> {
         out_img: Arc<Mutex<Vec<u8>>>,
     ) -> Result<(), SimulationRunError> {
         use crate::{cli::ARGUMENTS, headless::HeadlessMetadata, input::InputState};
-        use web_time::Duration;
+        use timings::TimingsRecorder;
+        use web_time::{Duration, Instant};
         use SimulationRunError as SRE;
 
         log::debug!("aftgraphs::simulation::SimulationContext::run_headless entered");
 
         let size = self.size.ok_or(SRE::HeadlessWithoutSize)?;
-        let mut renderer = crate::headless::init(size)
-            .await
-            .map_err(Into::<SRE>::into)?;
-
-        let input_values = if let Some(ref initial) = headless_inputs.initial_inputs {
-            let input_values = InputState::default();
-
-            {
-                let mut state = input_values.lock().await;
-                let state = state.as_mut();
-                for (name, val) in &initial.inputs {
-                    let name = name.replace('_', " ").replace('-', ".");
-                    state.insert(name, val.clone());
-                }
+        let renderers = match ARGUMENTS.read().await.gpus {
+            Some(count) if T::STATELESS => crate::headless::init_all(size, count).await,
+            Some(_) => {
+                log::warn!(
+                    "aftgraphs::simulation::SimulationContext::run_headless: {} is not Simulation::STATELESS, rendering on one GPU",
+                    std::any::type_name::<T>()
+                );
+                crate::headless::init(size).await.map(|renderer| vec![renderer])
             }
-            input_values
-        } else {
-            InputState::default()
-        };
+            None => crate::headless::init(size).await.map(|renderer| vec![renderer]),
+        }
+        .map_err(Into::<SRE>::into)?;
 
         let HeadlessMetadata {
             duration,
             size: _,
             delta_t,
+            crop,
         } = headless_inputs.simulation;
 
-        let mut events = headless_inputs.blocks;
-        events.sort_by(|lhs, rhs| lhs.time.total_cmp(&rhs.time));
-        let mut events = events.into_iter();
-        let mut current_event = events.next();
+        let chapters = headless_inputs.chapters();
+        let timeline = crate::timeline::Timeline::new(headless_inputs);
+        let render_config = crate::render::RenderConfig::startup().await;
+        let transparent = ARGUMENTS.read().await.transparent;
+        let sub_frames = ARGUMENTS.read().await.accumulate.unwrap_or(1).max(1);
+
+        // Each renderer runs its own copy of the simulation, taking every n-th frame
+        let mut lanes = Vec::with_capacity(renderers.len());
+        for mut renderer in renderers {
+            if let Some(ref config) = render_config {
+                renderer.apply_render_config(config);
+            }
+            if transparent {
+                renderer.clear_color.a = 0.0;
+            }
+            renderer.set_palette(crate::render::Palette::startup().await);
+            renderer.configure_inputs(&inputs);
+            renderer.set_script(crate::script::Script::startup().await);
+            renderer.set_audio_input(crate::audio::AudioInput::startup(true).await);
+            renderer.set_timeline(Some(timeline.clone()));
+
+            // Apply the initial inputs
+            let input_values = InputState::default();
+            renderer.advance_timeline(input_values.lock().await.as_mut());
 
-        let simulation = Arc::new(Mutex::new(T::new(&renderer).await));
+            let simulation = Arc::new(Mutex::new(create::<T, _>(&mut renderer, None).await));
+            lanes.push((renderer, simulation, input_values));
+        }
 
-        let size = renderer
+        let size = lanes[0]
+            .0
             .texture
             .as_ref()
             .ok_or_else(|| {
@@ -172,7 +410,7 @@ impl<T: Simulation> SimulationContext<T, ()> {
 
         let mut out_img = out_img.lock().await;
 
-        let (render_imgui, out_file) = {
+        let (render_imgui, out_file, timings, share) = {
             let args = ARGUMENTS.read().await;
             let headless = args.headless.clone().ok_or_else(|| {
                 log::error!(
@@ -181,75 +419,462 @@ impl<T: Simulation> SimulationContext<T, ()> {
                 );
                 SRE::HeadlessWithoutOutputFile
             })?;
-            (args.render_imgui, headless.out_file)
+            let timings = args.timings.then(|| Arc::new(TimingsRecorder::default()));
+            (
+                args.render_imgui,
+                headless.out_file,
+                timings,
+                args.share.clone(),
+            )
+        };
+        let mut share = share.and_then(|name| crate::share::FrameShare::open(&name, size).ok());
+
+        // Frames are cropped after being shared, the video is the size of the crop
+        let crop = ARGUMENTS
+            .read()
+            .await
+            .crop
+            .as_deref()
+            .and_then(|crop| crop.parse().ok())
+            .or(crop);
+        // Only PNG sequences and ProRes take odd sizes
+        let crop = crop
+            .map(|crop| {
+                let rect =
+                    crop.pixels([size.0, size.1], lanes[0].0.projection_params().projection)?;
+                match transparent {
+                    true => Ok(rect),
+                    false => crate::headless::even(rect),
+                }
+            })
+            .transpose()
+            .map_err(|e| {
+                log::error!("aftgraphs::simulation::SimulationContext::run_headless: {e}");
+                SRE::HeadlessCropError(e)
+            })?;
+        let video_size = crop.map_or(size, |[_, _, width, height]| (width, height));
+        let video_stride = crate::render::padded_bytes_per_row(video_size.0) as usize;
+
+        // H.264 is muxed into a container out_file once it is encoded, with the chapters
+        #[cfg(feature = "ffmpeg")]
+        let (out_file, container) = match !transparent && mux::is_container(&out_file) {
+            true => (out_file.with_extension("h264"), Some(out_file)),
+            false => (out_file, None),
+        };
+        #[cfg(feature = "ffmpeg")]
+        let chapters_muxed = container.is_some() || (transparent && is_prores(&out_file));
+        #[cfg(not(feature = "ffmpeg"))]
+        let chapters_muxed = false;
+
+        // Chapters that can not be muxed are written next to the video instead
+        if !chapters.is_empty() && !chapters_muxed {
+            let metadata_file = out_file.with_extension("ffmetadata");
+            log::info!(
+                "aftgraphs::simulation::SimulationContext::run_headless: Writing {} chapters to {}",
+                chapters.len(),
+                metadata_file.display()
+            );
+            if let Err(e) = std::fs::write(&metadata_file, crate::headless::ffmetadata(&chapters)) {
+                log::error!("aftgraphs::simulation::SimulationContext::run_headless: Failed to write chapters: {e}");
+            }
+        }
+
+        let (bitrate, rtmp) = {
+            let args = ARGUMENTS.read().await;
+            let metadata = rtmp::StreamMetadata {
+                size: video_size,
+                fps: 1.0 / delta_t,
+                bitrate: args.bitrate,
+            };
+            let rtmp = args
+                .rtmp
+                .as_ref()
+                .and_then(|url| rtmp::RtmpSink::new(url, metadata).ok());
+            (args.bitrate, rtmp)
         };
 
-        let (send_frame, finished, handle) = encoder::encoder(size, delta_t, out_file);
+        let (send_frame, finished, handle) = if transparent {
+            if rtmp.is_some() {
+                log::warn!("aftgraphs::simulation::SimulationContext::run_headless: --rtmp streams video, not sent with --transparent");
+            }
+            transparent_writer(video_size, delta_t, out_file.clone(), chapters.clone())
+        } else {
+            encoder::encoder(
+                video_size,
+                delta_t,
+                &out_file,
+                timings.clone(),
+                bitrate,
+                rtmp,
+            )
+        };
 
+        let mut frame = 0;
         let mut time = 0.0;
         let delta_duration = Duration::from_secs_f64(delta_t);
         while time <= duration {
-            renderer.update_delta_time(delta_duration);
-            renderer.time = time;
+            // Every renderer records and submits a frame before any is read back,
+            // so the GPUs work at once while the frames still arrive in order
+            let mut pending = Vec::with_capacity(lanes.len());
+            for (renderer, simulation, input_values) in &mut lanes {
+                if time > duration {
+                    break;
+                }
 
-            if let Some(ref event) = current_event {
-                if time > event.time {
-                    log::debug!("aftgraphs::simulation::SimulationContext::run_headless: Handling headless event at time {time}");
+                let render_start = Instant::now();
+                // Renderers take turns, so the frame index and time are the render's,
+                // not the count of frames this renderer drew
+                renderer.clock.seek_frame((frame + pending.len()) as u64);
+                renderer.update_clock(time, delta_duration);
+                // Sub-frames step through the interval of the frame, averaged into it
+                // Stepping sets the time, which renderers taking turns did not reach by ticking
+                for sub_frame in 0..sub_frames {
+                    renderer
+                        .clock
+                        .sub_frame(time, delta_t, sub_frame, sub_frames);
+
+                    {
+                        let mut input_values = input_values.lock().await;
+                        for TimedInput { event, time } in
+                            renderer.advance_timeline(input_values.as_mut())
+                        {
+                            log::debug!("aftgraphs::simulation::SimulationContext::run_headless: Handling headless event at time {time}");
+                            send_input(renderer, simulation, event, time).await;
+                        }
+                    }
 
-                    let mut state = input_values.lock().await;
-                    let state = state.as_mut();
+                    {
+                        log::debug!(
+                            "aftgraphs::simulation::SimulationContext::run_headless: Rendering simulation"
+                        );
 
-                    for (name, val) in &event.inputs {
-                        let name = name.replace('_', " ").replace('-', ".");
-                        state.insert(name, val.clone());
+                        let mut input_values = input_values.lock().await;
+                        renderer
+                            .render(simulation.clone(), input_values.as_mut())
+                            .await;
                     }
 
-                    for event in &event.events {
-                        let mut simulation = simulation.lock().await;
-                        simulation.on_input(event.clone().into()).await;
+                    if sub_frames > 1 {
+                        renderer.accumulate_headless(sub_frame, sub_frames).await;
                     }
+                }
 
-                    current_event = events.next();
+                if render_imgui {
+                    log::debug!(
+                        "aftgraphs::simulation::SimulationContext::run_headless: Drawing ui"
+                    );
+
+                    renderer
+                        .draw_ui(None, &inputs, input_values.clone())
+                        .await?;
                 }
+
+                let render = render_start.elapsed();
+                pending.push((render, renderer.render_headless_submit().await?));
+                time += delta_t;
             }
 
-            {
-                log::debug!(
-                    "aftgraphs::simulation::SimulationContext::run_headless: Rendering simulation"
+            for ((renderer, ..), (render, readback)) in lanes.iter().zip(pending) {
+                let (gpu_wait, readback) = renderer
+                    .render_headless_read(readback, out_img.as_mut())
+                    .await?;
+                if let Some(ref timings) = timings {
+                    timings.record(frame, |timings| {
+                        timings.render = render;
+                        timings.gpu_wait = gpu_wait;
+                        timings.readback = readback;
+                    });
+                }
+
+                // The readback buffer holds exactly the padded rows of the frame
+                let frame_stride = out_img.len() / size.1 as usize;
+                if let Some(ref mut share) = share {
+                    share.publish(out_img.as_slice(), frame_stride);
+                }
+
+                let video_frame = match crop {
+                    Some(rect) => {
+                        let mut video_frame = vec![0; video_stride * video_size.1 as usize];
+                        crate::headless::copy_rect(
+                            rect,
+                            out_img.as_slice(),
+                            frame_stride,
+                            &mut video_frame,
+                            video_stride,
+                        );
+                        video_frame
+                    }
+                    None => out_img.to_owned(),
+                };
+                send_frame.send(video_frame).map_err(|e| {
+                    log::error!("aftgraphs::simulation::SimulationContext::run_headless: Failed to send frame on channel: {e}");
+                    SRE::HeadlessEncodingError(format!("{e:?}"))
+                })?;
+                frame += 1;
+
+                profiling::finish_frame!();
+            }
+        }
+
+        if let Err(e) = finished.send(()) {
+            log::warn!("aftgraphs::simulation::SimulationContext::run_headless: error signaling end of frames to encoding thread: {e}");
+        }
+
+        if let Err(e) = handle.join() {
+            log::error!("aftgraphs::simulation::SimulationContext::run_headless: encoding thread panicked: {e:?}");
+            return Err(SRE::HeadlessEncodingError(format!("{e:?}")));
+        }
+
+        #[cfg(feature = "ffmpeg")]
+        if let Some(container) = container {
+            mux::mux(&out_file, delta_t, &chapters, &container).map_err(|e| {
+                log::error!(
+                    "aftgraphs::simulation::SimulationContext::run_headless: Failed to mux {} into {}: {e}",
+                    out_file.display(),
+                    container.display()
                 );
+                SRE::HeadlessEncodingError(e.to_string())
+            })?;
+            if let Err(e) = std::fs::remove_file(&out_file) {
+                log::warn!(
+                    "aftgraphs::simulation::SimulationContext::run_headless: Failed to remove {}: {e}",
+                    out_file.display()
+                );
+            }
+        }
+
+        if let Some(timings) = timings {
+            eprintln!("{}", timings.report());
+        }
+        Ok(())
+    }
+
+    /// Render the last frame of headless_inputs in tiles of at most tile_size pixels
+    /// and write the stitched image as a PNG to the output file.
+    /// Only simulations drawing with Renderer::projection_params, or cropping their own
+    /// projection to Renderer::tile, are split into tiles correctly
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn run_tiled(
+        self,
+        inputs: Inputs,
+        headless_inputs: crate::headless::HeadlessInput,
+        tile_size: u32,
+    ) -> Result<(), SimulationRunError> {
+        use crate::{cli::ARGUMENTS, input::InputState, render::Tile};
+        use web_time::Duration;
+        use SimulationRunError as SRE;
 
+        log::debug!("aftgraphs::simulation::SimulationContext::run_tiled entered");
+
+        let size = self.size.ok_or(SRE::HeadlessWithoutSize)?;
+        if headless_inputs.simulation.crop.is_some() {
+            let e = crate::headless::CropError::Unsupported("--tile");
+            log::error!("aftgraphs::simulation::SimulationContext::run_tiled: {e}");
+            return Err(SRE::HeadlessCropError(e));
+        }
+        let tile_size = [tile_size.min(size.0), tile_size.min(size.1)];
+        let tiles = Tile::split([size.0, size.1], tile_size);
+        let (out_file, transparent) = {
+            let args = ARGUMENTS.read().await;
+            let headless = args
+                .headless
+                .clone()
+                .ok_or(SRE::HeadlessWithoutOutputFile)?;
+            (headless.out_file, args.transparent)
+        };
+
+        let mut renderer = crate::headless::init((tile_size[0], tile_size[1]))
+            .await
+            .map_err(Into::<SRE>::into)?;
+        renderer.aspect_ratio = size.0 as f64 / size.1 as f64;
+        if let Some(config) = crate::render::RenderConfig::startup().await {
+            renderer.apply_render_config(&config);
+        }
+        if transparent {
+            renderer.clear_color.a = 0.0;
+        }
+        renderer.set_palette(crate::render::Palette::startup().await);
+        renderer.configure_inputs(&inputs);
+        renderer.set_script(crate::script::Script::startup().await);
+        renderer.set_audio_input(crate::audio::AudioInput::startup(true).await);
+
+        let duration = headless_inputs.simulation.duration;
+        let delta_t = headless_inputs.simulation.delta_t;
+        renderer.set_timeline(Some(crate::timeline::Timeline::new(headless_inputs)));
+        let input_values = InputState::default();
+        renderer.advance_timeline(input_values.lock().await.as_mut());
+
+        let simulation = Arc::new(Mutex::new(create::<T, _>(&mut renderer, None).await));
+
+        // Step through the timeline drawing the first tile, the last frame is the image
+        renderer.set_tile(tiles.first().copied());
+        let mut frame = vec![];
+        let mut time = 0.0;
+        let delta_duration = Duration::from_secs_f64(delta_t);
+        while time <= duration {
+            renderer.update_clock(time, delta_duration);
+            let mut input_values = input_values.lock().await;
+            for TimedInput { event, time } in renderer.advance_timeline(input_values.as_mut()) {
+                send_input(&renderer, &simulation, event, time).await;
+            }
+            renderer
+                .render(simulation.clone(), input_values.as_mut())
+                .await;
+            renderer.render_headless_finish(&mut frame).await?;
+            time += delta_t;
+        }
+
+        // The readback buffer holds exactly the padded rows of the tile
+        let frame_stride = frame.len() / tile_size[1] as usize;
+        let mut image = vec![0; size.0 as usize * size.1 as usize * 4];
+        for (idx, &tile) in tiles.iter().enumerate() {
+            if idx > 0 {
+                log::info!(
+                    "aftgraphs::simulation::SimulationContext::run_tiled: Rendering tile {}/{}",
+                    idx + 1,
+                    tiles.len()
+                );
+                renderer.set_tile(Some(tile));
+                // Draw the same moment again, in the same frame
+                let time = renderer.clock.time();
+                renderer.clock.step_to(time, 0.0);
                 let mut input_values = input_values.lock().await;
                 renderer
                     .render(simulation.clone(), input_values.as_mut())
                     .await;
+                renderer.render_headless_finish(&mut frame).await?;
             }
+            tile.stitch(&frame, frame_stride, &mut image);
+        }
+
+        log::info!(
+            "aftgraphs::simulation::SimulationContext::run_tiled: Writing {}x{} image to {}",
+            size.0,
+            size.1,
+            out_file.display()
+        );
+        let row_len = size.0 as usize * 4;
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&out_file)?);
+        crate::png::write(&mut out, size.0, size.1, transparent, |y| {
+            &image[y as usize * row_len..]
+        })?;
+        std::io::Write::flush(&mut out)?;
+        Ok(())
+    }
+
+    /// Render every frame of headless_inputs as the six faces of a cubemap, each face_size
+    /// pixels square, and encode them converted to an equirectangular 360° video.
+    /// Only simulations drawing each face with Renderer::cube_face are captured correctly
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn run_cubemap(
+        self,
+        inputs: Inputs,
+        headless_inputs: crate::headless::HeadlessInput,
+        face_size: u32,
+    ) -> Result<(), SimulationRunError> {
+        use crate::{
+            cli::ARGUMENTS,
+            input::InputState,
+            render::{CubeFace, Equirectangular},
+        };
+        use web_time::Duration;
+        use SimulationRunError as SRE;
+
+        log::debug!("aftgraphs::simulation::SimulationContext::run_cubemap entered");
+
+        let size = self.size.ok_or(SRE::HeadlessWithoutSize)?;
+        if headless_inputs.simulation.crop.is_some() {
+            let e = crate::headless::CropError::Unsupported("--cubemap");
+            log::error!("aftgraphs::simulation::SimulationContext::run_cubemap: {e}");
+            return Err(SRE::HeadlessCropError(e));
+        }
+        if size.0 != 2 * size.1 {
+            log::warn!(
+                "aftgraphs::simulation::SimulationContext::run_cubemap: {}x{} is not twice as wide as high, 360° video players will stretch it",
+                size.0,
+                size.1
+            );
+        }
+        let (out_file, transparent, bitrate) = {
+            let args = ARGUMENTS.read().await;
+            let headless = args
+                .headless
+                .clone()
+                .ok_or(SRE::HeadlessWithoutOutputFile)?;
+            (headless.out_file, args.transparent, args.bitrate)
+        };
 
-            if render_imgui {
-                log::debug!("aftgraphs::simulation::SimulationContext::run_headless: Drawing ui");
+        let mut renderer = crate::headless::init((face_size, face_size))
+            .await
+            .map_err(Into::<SRE>::into)?;
+        if let Some(config) = crate::render::RenderConfig::startup().await {
+            renderer.apply_render_config(&config);
+        }
+        if transparent {
+            renderer.clear_color.a = 0.0;
+        }
+        renderer.set_palette(crate::render::Palette::startup().await);
+        renderer.configure_inputs(&inputs);
+        renderer.set_script(crate::script::Script::startup().await);
+        renderer.set_audio_input(crate::audio::AudioInput::startup(true).await);
+
+        let duration = headless_inputs.simulation.duration;
+        let delta_t = headless_inputs.simulation.delta_t;
+        renderer.set_timeline(Some(crate::timeline::Timeline::new(headless_inputs)));
+        let input_values = InputState::default();
+        renderer.advance_timeline(input_values.lock().await.as_mut());
+
+        let simulation = Arc::new(Mutex::new(create::<T, _>(&mut renderer, None).await));
+
+        let (send_frame, finished, handle) =
+            frame_writer(size, delta_t, out_file, transparent, bitrate)?;
+
+        // Frames sent on are laid out like a read back texture of the video size
+        let equirect = Equirectangular::new(face_size, [size.0, size.1]);
+        let stride = crate::render::padded_bytes_per_row(size.0) as usize;
+        let mut faces = vec![vec![]; CubeFace::ALL.len()];
+        let mut time = 0.0;
+        let delta_duration = Duration::from_secs_f64(delta_t);
+        while time <= duration {
+            renderer.update_clock(time, delta_duration);
+            let mut input_values = input_values.lock().await;
+            for TimedInput { event, time } in renderer.advance_timeline(input_values.as_mut()) {
+                send_input(&renderer, &simulation, event, time).await;
+            }
 
+            for (face, pixels) in CubeFace::ALL.into_iter().zip(&mut faces) {
+                renderer.set_cube_face(Some(face));
+                if face != CubeFace::PosX {
+                    // Draw the same moment again, in the same frame
+                    let time = renderer.clock.time();
+                    renderer.clock.step_to(time, 0.0);
+                }
                 renderer
-                    .draw_ui(None, &inputs, input_values.clone())
-                    .await?;
+                    .render(simulation.clone(), input_values.as_mut())
+                    .await;
+                renderer.render_headless_finish(pixels).await?;
             }
 
-            renderer.render_headless_finish(out_img.as_mut()).await?;
-            send_frame.send(out_img.to_owned()).map_err(|e| {
-                log::error!("aftgraphs::simulation::SimulationContext::run_headless: Failed to send frame on channel: {e}");
+            // The readback buffer holds exactly the padded rows of a face
+            let face_stride = faces[0].len() / face_size as usize;
+            let mut frame = vec![0; stride * size.1 as usize];
+            equirect.convert(&faces, face_stride, &mut frame, stride);
+            send_frame.send(frame).map_err(|e| {
+                log::error!("aftgraphs::simulation::SimulationContext::run_cubemap: Failed to send frame on channel: {e}");
                 SRE::HeadlessEncodingError(format!("{e:?}"))
             })?;
             time += delta_t;
-        }
 
-        if let Err(e) = finished.send(()) {
-            log::warn!("aftgraphs::simulation::SimulationContext::run_headless: error signaling end of frames to encoding thread: {e}");
+            profiling::finish_frame!();
         }
 
-        if let Err(e) = handle.join() {
-            log::error!("aftgraphs::simulation::SimulationContext::run_headless: encoding thread panicked: {e:?}");
-            Err(SRE::HeadlessEncodingError(format!("{e:?}")))
-        } else {
-            Ok(())
+        if let Err(e) = finished.send(()) {
+            log::warn!("aftgraphs::simulation::SimulationContext::run_cubemap: error signaling end of frames to encoding thread: {e}");
         }
+        handle.join().map_err(|e| {
+            log::error!("aftgraphs::simulation::SimulationContext::run_cubemap: encoding thread panicked: {e:?}");
+            SRE::HeadlessEncodingError(format!("{e:?}"))
+        })
     }
 }
 
@@ -284,7 +909,8 @@ impl<T: Simulation> SimulationContext<T, UiWinitPlatform> {
         })?;
 
         event_loop.set_control_flow(ControlFlow::Poll);
-        event_loop
+        crate::stream::set_event_proxy(Some(event_loop.create_proxy()));
+        let result = event_loop
             .run_app(&mut crate::App::<T>::new(inputs))
             .map_err(|err| {
                 log::error!(
@@ -292,6 +918,8 @@ impl<T: Simulation> SimulationContext<T, UiWinitPlatform> {
                     err,
                 );
                 err.into()
-            })
+            });
+        crate::stream::set_event_proxy(None);
+        result
     }
 }
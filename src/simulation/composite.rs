@@ -0,0 +1,433 @@
+use super::{Config, InputEvent, Simulation};
+use crate::{
+    input::InputValue,
+    render::{Frame, Layer, RenderPass, Renderer, Warmup},
+    ui::UiPlatform,
+};
+use std::collections::HashMap;
+use winit::event::ElementState;
+
+/// A rectangle of the render target in fractions of its size, from the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self::new(0.0, 0.0, 1.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Split the render target into count columns of equal width
+    pub fn columns(count: usize) -> Vec<Self> {
+        let width = 1.0 / count.max(1) as f32;
+        (0..count)
+            .map(|idx| Self::new(idx as f32 * width, 0.0, width, 1.0))
+            .collect()
+    }
+
+    /// Split the render target into count rows of equal height
+    pub fn rows(count: usize) -> Vec<Self> {
+        let height = 1.0 / count.max(1) as f32;
+        (0..count)
+            .map(|idx| Self::new(0.0, idx as f32 * height, 1.0, height))
+            .collect()
+    }
+
+    /// The rectangle as pixel x, y, width and height in a render target of size
+    /// Width and height are at least one pixel.
+    pub fn pixels(&self, size: [u32; 2]) -> [u32; 4] {
+        let [width, height] = size.map(|len| len as f32);
+        let x = (self.x * width).round() as u32;
+        let y = (self.y * height).round() as u32;
+        [
+            x.min(size[0].saturating_sub(1)),
+            y.min(size[1].saturating_sub(1)),
+            ((self.width * width).round() as u32).clamp(1, size[0].saturating_sub(x).max(1)),
+            ((self.height * height).round() as u32).clamp(1, size[1].saturating_sub(y).max(1)),
+        ]
+    }
+
+    /// If a point in [-1, 1] space of the whole render target is inside the viewport
+    pub fn contains(&self, position: (f64, f64)) -> bool {
+        let (x, y) = self.to_local(position);
+        (-1.0..=1.0).contains(&x) && (-1.0..=1.0).contains(&y)
+    }
+
+    /// Map a point in [-1, 1] space of the whole render target to [-1, 1] space of the viewport
+    pub fn to_local(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        // Fractions of the render target from the top left
+        let fx = (x + 1.0) / 2.0;
+        let fy = (1.0 - y) / 2.0;
+
+        let local_x = (fx - self.x as f64) / self.width as f64;
+        let local_y = (fy - self.y as f64) / self.height as f64;
+        (local_x * 2.0 - 1.0, 1.0 - local_y * 2.0)
+    }
+}
+
+/// A tuple of Simulations hosted by a CompositeSimulation
+/// Implemented for tuples of two to four Simulations.
+pub trait SimulationSet: 'static {
+    const LEN: usize;
+
+    /// Whether every Simulation is Simulation::STATELESS
+    const STATELESS: bool;
+
+    /// Simulation::prepare of every Simulation
+    fn prepare<P: UiPlatform>(warmup: &mut Warmup<P>);
+
+    /// Simulation::args of every Simulation
+    #[cfg(not(target_arch = "wasm32"))]
+    fn args() -> Vec<clap::Arg> {
+        vec![]
+    }
+
+    #[allow(async_fn_in_trait)]
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self;
+
+    /// Simulation::new_with of every Simulation
+    /// Calls SimulationSet::new by default.
+    #[allow(async_fn_in_trait)]
+    async fn new_with<P: UiPlatform>(renderer: &Renderer<'_, P>, _config: &Config) -> Self {
+        Self::new(renderer).await
+    }
+
+    /// Draw layer of the Simulation at idx
+    #[allow(async_fn_in_trait)]
+    async fn render<P: UiPlatform>(
+        &mut self,
+        idx: usize,
+        layer: Layer,
+        renderer: &Renderer<'_, P>,
+        render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    );
+
+    /// Simulation::encode of every Simulation
+    #[allow(async_fn_in_trait)]
+    async fn encode<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        frame: &mut Frame<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    );
+
+    #[allow(async_fn_in_trait)]
+    async fn on_input_at(&mut self, idx: usize, event: InputEvent, time: f64);
+
+    /// Type name of the Simulation at idx, used for debug groups
+    fn name(idx: usize) -> &'static str;
+}
+
+macro_rules! impl_simulation_set {
+    ($len:literal; $($idx:tt $sim:ident),+) => {
+        impl<$($sim: Simulation),+> SimulationSet for ($($sim,)+) {
+            const LEN: usize = $len;
+            const STATELESS: bool = $($sim::STATELESS)&&+;
+
+            fn prepare<P: UiPlatform>(warmup: &mut Warmup<P>) {
+                $($sim::prepare(warmup);)+
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            fn args() -> Vec<clap::Arg> {
+                let mut args = vec![];
+                $(args.extend($sim::args());)+
+                args
+            }
+
+            async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+                ($($sim::new(renderer).await,)+)
+            }
+
+            async fn new_with<P: UiPlatform>(renderer: &Renderer<'_, P>, config: &Config) -> Self {
+                ($($sim::new_with(renderer, config).await,)+)
+            }
+
+            async fn render<P: UiPlatform>(
+                &mut self,
+                idx: usize,
+                layer: Layer,
+                renderer: &Renderer<'_, P>,
+                render_pass: RenderPass<'_>,
+                inputs: &mut HashMap<String, InputValue>,
+            ) {
+                match (idx, layer) {
+                    $(($idx, Layer::Simulation) => {
+                        self.$idx.render(renderer, render_pass, inputs).await
+                    })+
+                    $(($idx, layer) => {
+                        self.$idx.render_layer(renderer, layer, render_pass, inputs).await
+                    })+
+                    _ => (),
+                }
+            }
+
+            async fn encode<P: UiPlatform>(
+                &mut self,
+                renderer: &Renderer<'_, P>,
+                frame: &mut Frame<'_>,
+                inputs: &mut HashMap<String, InputValue>,
+            ) {
+                $(self.$idx.encode(renderer, frame, inputs).await;)+
+            }
+
+            async fn on_input_at(&mut self, idx: usize, event: InputEvent, time: f64) {
+                match idx {
+                    $($idx => self.$idx.on_input_at(event, time).await,)+
+                    _ => (),
+                }
+            }
+
+            fn name(idx: usize) -> &'static str {
+                match idx {
+                    $($idx => std::any::type_name::<$sim>(),)+
+                    _ => "",
+                }
+            }
+        }
+    };
+}
+
+impl_simulation_set!(2; 0 A, 1 B);
+impl_simulation_set!(3; 0 A, 1 B, 2 C);
+impl_simulation_set!(4; 0 A, 1 B, 2 C, 3 D);
+
+/// Hosts several Simulations, each drawn into its own viewport of the render target
+/// The Simulations share the inputs, so their UI blocks should have distinct names.
+/// Mouse presses focus the Simulation under the cursor, mouse and keyboard events
+/// go to the focused Simulation with mouse positions in [-1, 1] space of its viewport.
+/// Inside a viewport Renderer::viewport_size and Renderer::viewport_aspect_ratio
+/// describe the viewport rather than the whole render target, so CompositeSimulations do not nest.
+///
+/// Used directly the viewports are equal columns, to lay them out differently wrap
+/// it in a Simulation that calls CompositeSimulation::set_viewports.
+pub struct CompositeSimulation<S: SimulationSet> {
+    pub simulations: S,
+    viewports: Vec<Viewport>,
+    focus: Option<usize>,
+    /// Renderer::time of the last render, for inputs without a time
+    time: f64,
+}
+
+impl<S: SimulationSet> CompositeSimulation<S> {
+    /// Viewports of the Simulations, in tuple order
+    pub fn viewports(&self) -> &[Viewport] {
+        &self.viewports
+    }
+
+    /// Set the viewports of the Simulations, in tuple order
+    /// Simulations without a viewport are not drawn and get no inputs.
+    pub fn set_viewports(&mut self, viewports: Vec<Viewport>) {
+        if viewports.len() != S::LEN {
+            log::warn!(
+                "aftgraphs::simulation::CompositeSimulation::set_viewports: {} viewports for {} simulations",
+                viewports.len(),
+                S::LEN
+            );
+        }
+        self.viewports = viewports;
+        self.focus = self.focus.filter(|&idx| idx < self.viewports.len());
+    }
+
+    fn with_simulations<P: UiPlatform>(renderer: &Renderer<'_, P>, simulations: S) -> Self {
+        Self {
+            simulations,
+            viewports: Viewport::columns(S::LEN),
+            focus: None,
+            time: renderer.clock.wall_time(),
+        }
+    }
+
+    /// Index of the Simulation receiving inputs
+    pub fn focus(&self) -> Option<usize> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Option<usize>) {
+        self.focus = focus.filter(|&idx| idx < self.viewports.len());
+    }
+
+    /// Draw layer of every Simulation into its viewport
+    async fn render_viewports<P: UiPlatform>(
+        &mut self,
+        layer: Layer,
+        renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        let size = renderer.viewport_size();
+        for (idx, viewport) in self.viewports.iter().enumerate() {
+            let [x, y, width, height] = viewport.pixels(size);
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            renderer.set_viewport(Some([width, height]));
+
+            render_pass.push_debug_group(&format!("aftgraphs: viewport {idx} {}", S::name(idx)));
+            self.simulations
+                .render(idx, layer, renderer, render_pass.reborrow(), inputs)
+                .await;
+            render_pass.pop_debug_group();
+        }
+        renderer.set_viewport(None);
+    }
+
+    /// Index of the topmost viewport containing a point in [-1, 1] space
+    fn viewport_at(&self, position: (f64, f64)) -> Option<usize> {
+        self.viewports
+            .iter()
+            .rposition(|viewport| viewport.contains(position))
+    }
+}
+
+impl<S: SimulationSet> Simulation for CompositeSimulation<S> {
+    const STATELESS: bool = S::STATELESS;
+
+    fn prepare<P: UiPlatform>(warmup: &mut Warmup<P>) {
+        S::prepare(warmup);
+    }
+
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        Self::with_simulations(renderer, S::new(renderer).await)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn args() -> Vec<clap::Arg> {
+        S::args()
+    }
+
+    async fn new_with<P: UiPlatform>(renderer: &Renderer<'_, P>, config: &Config) -> Self {
+        Self::with_simulations(renderer, S::new_with(renderer, config).await)
+    }
+
+    async fn on_input(&mut self, event: InputEvent) {
+        self.on_input_at(event, self.time).await;
+    }
+
+    async fn on_input_at(&mut self, event: InputEvent, time: f64) {
+        let event = match event {
+            InputEvent::Mouse(state, button, position) => {
+                if state == ElementState::Pressed {
+                    self.focus = self.viewport_at(position);
+                }
+                let Some(focus) = self.focus else {
+                    return;
+                };
+                InputEvent::Mouse(state, button, self.viewports[focus].to_local(position))
+            }
+            // Data is for every simulation, not only the focused one
+            InputEvent::Data(..) => {
+                for idx in 0..S::LEN {
+                    self.simulations.on_input_at(idx, event.clone(), time).await;
+                }
+                return;
+            }
+            event => event,
+        };
+
+        if let Some(focus) = self.focus {
+            self.simulations.on_input_at(focus, event, time).await;
+        }
+    }
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        self.time = renderer.clock.wall_time();
+        self.render_viewports(Layer::Simulation, renderer, render_pass, inputs)
+            .await;
+    }
+
+    async fn encode<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        frame: &mut Frame<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        self.simulations.encode(renderer, frame, inputs).await;
+    }
+
+    async fn render_layer<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        layer: Layer,
+        render_pass: RenderPass<'_>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        self.render_viewports(layer, renderer, render_pass, inputs)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn viewport_local_coordinates() {
+        let right = Viewport::columns(2)[1];
+        assert!(right.contains((0.5, 0.0)));
+        assert!(!right.contains((-0.5, 0.0)));
+        assert_eq!((0.0, 0.0), right.to_local((0.5, 0.0)));
+        assert_eq!((-1.0, 1.0), right.to_local((0.0, 1.0)));
+        assert_eq!([400, 0, 400, 600], right.pixels([800, 600]));
+    }
+
+    struct Stateless;
+    struct Stateful;
+
+    impl Simulation for Stateless {
+        const STATELESS: bool = true;
+
+        async fn render<P: UiPlatform>(
+            &mut self,
+            _renderer: &Renderer<'_, P>,
+            _render_pass: RenderPass<'_>,
+            _inputs: &mut HashMap<String, InputValue>,
+        ) {
+        }
+
+        async fn on_input(&mut self, _event: InputEvent) {}
+
+        async fn new<P: UiPlatform>(_renderer: &Renderer<'_, P>) -> Self {
+            Self
+        }
+    }
+
+    impl Simulation for Stateful {
+        async fn render<P: UiPlatform>(
+            &mut self,
+            _renderer: &Renderer<'_, P>,
+            _render_pass: RenderPass<'_>,
+            _inputs: &mut HashMap<String, InputValue>,
+        ) {
+        }
+
+        async fn on_input(&mut self, _event: InputEvent) {}
+
+        async fn new<P: UiPlatform>(_renderer: &Renderer<'_, P>) -> Self {
+            Self
+        }
+    }
+
+    #[test]
+    fn composites_are_stateless_when_every_simulation_is() {
+        assert!(CompositeSimulation::<(Stateless, Stateless)>::STATELESS);
+        assert!(!CompositeSimulation::<(Stateless, Stateful)>::STATELESS);
+        assert!(!CompositeSimulation::<(Stateful, Stateless, Stateless)>::STATELESS);
+    }
+}
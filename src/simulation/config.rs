@@ -0,0 +1,225 @@
+use percent_encoding::percent_decode_str;
+use std::collections::BTreeMap;
+
+/// A value of an argument of Simulation::args, parsed when the Config is loaded
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConfigValue {
+    /// true or false, or a name without a value
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl ConfigValue {
+    /// The most specific value value reads as
+    pub fn parse(value: &str) -> Self {
+        if value.is_empty() {
+            Self::Bool(true)
+        } else if let Ok(value) = value.parse() {
+            Self::Bool(value)
+        } else if let Ok(value) = value.parse() {
+            Self::Int(value)
+        } else if let Ok(value) = value.parse() {
+            Self::Float(value)
+        } else {
+            Self::Text(value.to_owned())
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl TryFrom<&ConfigValue> for bool {
+    type Error = ();
+
+    fn try_from(value: &ConfigValue) -> Result<Self, Self::Error> {
+        match *value {
+            ConfigValue::Bool(value) => Ok(value),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TryFrom<&ConfigValue> for String {
+    type Error = ();
+
+    /// Any value, as it was given for text
+    fn try_from(value: &ConfigValue) -> Result<Self, Self::Error> {
+        Ok(value.to_string())
+    }
+}
+
+macro_rules! impl_try_from_config_value {
+    (int: $($int:ty),+; float: $($float:ty),+) => {
+        $(impl TryFrom<&ConfigValue> for $int {
+            type Error = ();
+
+            fn try_from(value: &ConfigValue) -> Result<Self, Self::Error> {
+                match *value {
+                    ConfigValue::Int(value) => value.try_into().map_err(|_| ()),
+                    _ => Err(()),
+                }
+            }
+        })+
+
+        $(impl TryFrom<&ConfigValue> for $float {
+            type Error = ();
+
+            fn try_from(value: &ConfigValue) -> Result<Self, Self::Error> {
+                match *value {
+                    ConfigValue::Int(value) => Ok(value as $float),
+                    ConfigValue::Float(value) => Ok(value as $float),
+                    _ => Err(()),
+                }
+            }
+        })+
+    };
+}
+
+impl_try_from_config_value!(int: i8, i16, i32, i64, u8, u16, u32, u64, usize; float: f32, f64);
+
+/// Values of the arguments a Simulation adds with Simulation::args, passed to
+/// Simulation::new_with. Values are parsed into ConfigValues when the Config is loaded,
+/// with the defaults of the arguments filled in. On WASM they come from the query string
+/// of the page instead, e.g. ?count=100&paused
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Config {
+    values: BTreeMap<String, Vec<ConfigValue>>,
+}
+
+/// Decode a component of a query string, + is a space
+fn decode_component(component: &str) -> String {
+    percent_decode_str(&component.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+impl Config {
+    /// Parse a url query string, with or without the leading ?
+    /// Names without a value, like ?paused, are flags set to true.
+    pub fn from_query(query: &str) -> Self {
+        let mut config = Self::default();
+        for pair in query.trim_start_matches('?').split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            config = config.with_value(&decode_component(name), decode_component(value));
+        }
+        config
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn from_matches<'a>(
+        matches: &clap::ArgMatches,
+        ids: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let values = ids
+            .into_iter()
+            .filter_map(|id| {
+                let values = matches.get_raw(id)?;
+                let values = values
+                    .map(|value| ConfigValue::parse(&value.to_string_lossy()))
+                    .collect();
+                Some((id.to_owned(), values))
+            })
+            .collect();
+        Self { values }
+    }
+
+    /// The arguments given on the command line or the query string of the page
+    pub(crate) async fn startup() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        return crate::cli::ARGUMENTS.read().await.config.clone();
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let query = web_sys::window()
+                .and_then(|window| window.location().search().ok())
+                .unwrap_or_default();
+            Self::from_query(&query)
+        }
+    }
+
+    /// Add a value of the argument name, for creating simulations in tests
+    pub fn with_value(mut self, name: &str, value: impl ToString) -> Self {
+        self.values
+            .entry(name.to_owned())
+            .or_default()
+            .push(ConfigValue::parse(&value.to_string()));
+        self
+    }
+
+    /// If the argument name has a value, including a default
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// The last value of the argument name
+    pub fn value(&self, name: &str) -> Option<&ConfigValue> {
+        self.values.get(name)?.last()
+    }
+
+    /// The last value of the argument name, None if it is missing or not a T
+    pub fn get<T: for<'a> TryFrom<&'a ConfigValue>>(&self, name: &str) -> Option<T> {
+        let value = self.value(name)?;
+        let converted = T::try_from(value).ok();
+        if converted.is_none() {
+            log::warn!(
+                "aftgraphs::simulation::Config::get: invalid value {value} for {name}, expected {}",
+                std::any::type_name::<T>()
+            );
+        }
+        converted
+    }
+
+    /// Every value of the argument name that is a T
+    pub fn get_all<T: for<'a> TryFrom<&'a ConfigValue>>(&self, name: &str) -> Vec<T> {
+        self.values
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| T::try_from(value).ok())
+            .collect()
+    }
+
+    /// If the flag name was set, clap's ArgAction::SetTrue flags or names without a value on WASM
+    pub fn flag(&self, name: &str) -> bool {
+        self.value(name) == Some(&ConfigValue::Bool(true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_query() {
+        let config =
+            Config::from_query("?count=100&name=two+words%21&paused&tag=a&tag=b&verbose=false");
+        assert_eq!(Some(100u32), config.get("count"));
+        assert_eq!(Some("two words!".to_owned()), config.get("name"));
+        assert!(config.flag("paused"));
+        assert!(!config.flag("verbose"));
+        assert!(!config.flag("missing"));
+        assert_eq!(
+            vec!["a".to_owned(), "b".to_owned()],
+            config.get_all::<String>("tag")
+        );
+        assert_eq!(None::<u32>, config.get("name"));
+        assert_eq!(Some(&ConfigValue::Int(100)), config.value("count"));
+        assert_eq!(Some(100.0f32), config.get("count"));
+        assert_eq!(None::<u8>, config.with_value("small", "-1").get("small"));
+        assert_eq!(Config::default(), Config::from_query(""));
+    }
+}
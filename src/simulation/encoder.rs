@@ -1,10 +1,13 @@
+use super::{rtmp::RtmpSink, timings::TimingsRecorder};
 use crossbeam::{channel, select};
 use dcv_color_primitives::{convert_image, get_buffers_size, ColorSpace, ImageFormat, PixelFormat};
 use std::{
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    sync::Arc,
     thread::{self, JoinHandle},
+    time::Instant,
 };
 use x264::{Encoder, Param, Picture};
 
@@ -12,10 +15,16 @@ use x264::{Encoder, Param, Picture};
 /// Returns a sending channel to send frames to
 /// The frames shouldn't be changed from the GPU buffer.
 /// Close the channel to signal the end.
+/// Color conversion and encoding times are recorded into timings, if given.
+/// bitrate in kilobits per second switches x264 to average bitrate with a matching VBV,
+/// encoded frames are also published to rtmp, if given.
 pub fn encoder(
     size: (u32, u32),
     delta_t: f64,
     out_file: impl AsRef<Path>,
+    timings: Option<Arc<TimingsRecorder>>,
+    bitrate: Option<u32>,
+    rtmp: Option<RtmpSink>,
 ) -> (
     channel::Sender<Vec<u8>>,
     channel::Sender<()>,
@@ -26,11 +35,27 @@ pub fn encoder(
     let out_file = out_file.as_ref().to_owned();
 
     let handle = thread::spawn(move || {
+        profiling::register_thread!("encoder");
+
         let mut params = Param::new()
             .set_dimension(size.0 as usize, size.1 as usize)
             .param_parse("repeat_headers", "1")
             .and_then(|params| params.param_parse("annexb", "1"))
             .and_then(|params| params.param_parse("fps", &(1.0 / delta_t).to_string()))
+            .and_then(|params| match bitrate {
+                Some(bitrate) => params
+                    .param_parse("bitrate", &bitrate.to_string())
+                    .and_then(|params| params.param_parse("vbv-maxrate", &bitrate.to_string()))
+                    .and_then(|params| {
+                        params.param_parse("vbv-bufsize", &(2 * bitrate).to_string())
+                    }),
+                None => Ok(params),
+            })
+            // Keyframes every 2 seconds, so viewers and reconnections can start quickly
+            .and_then(|params| match rtmp {
+                Some(_) => params.param_parse("keyint", &(2.0 / delta_t).round().to_string()),
+                None => Ok(params),
+            })
             .and_then(|params| params.apply_profile("high"))
             .unwrap();
 
@@ -44,6 +69,9 @@ pub fn encoder(
             finished: recv_finished,
             picture,
             encoder,
+            timings,
+            delta_t,
+            rtmp,
         };
 
         handler.encoding_loop();
@@ -59,17 +87,25 @@ struct EncoderHandler {
     finished: channel::Receiver<()>,
     picture: Picture,
     encoder: Encoder,
+    timings: Option<Arc<TimingsRecorder>>,
+    delta_t: f64,
+    rtmp: Option<RtmpSink>,
 }
 
 impl EncoderHandler {
     fn encoding_loop(mut self) {
-        let mut out_file = File::create(self.out_file.clone())
-            .expect("aftgraphs::simulation::encoder::EncoderHandler: Failed to create output file");
+        let mut out_file = match File::create(&self.out_file) {
+            Ok(out_file) => out_file,
+            Err(e) => {
+                crate::error::report(
+                    "aftgraphs::simulation::encoder::EncoderHandler",
+                    format!("failed to create output file: {e}"),
+                );
+                return;
+            }
+        };
 
-        let bytes_per_row = std::mem::size_of::<u32>() * self.size.0;
-        let missing_bytes = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize
-            - (bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
-        let bytes_per_row = bytes_per_row + missing_bytes;
+        let bytes_per_row = crate::render::padded_bytes_per_row(self.size.0 as u32) as usize;
 
         let mut frame_idx = 0;
         'outer: loop {
@@ -83,8 +119,12 @@ impl EncoderHandler {
                         },
                     };
 
+                    profiling::scope!("encode");
+                    let convert_start = Instant::now();
                     let encoded_frame = Self::encode_frame(self.size, bytes_per_row, frame);
+                    let color_convert = convert_start.elapsed();
 
+                    let encode_start = Instant::now();
                     self.picture = self.picture.set_timestamp(frame_idx as i64);
                     self.picture
                         .as_mut_slice(0)
@@ -99,8 +139,20 @@ impl EncoderHandler {
                         .unwrap()
                         .copy_from_slice(encoded_frame.2.as_slice());
 
-                    if let Some((nal, _, _)) = self.encoder.encode(&self.picture).unwrap() {
-                        out_file.write_all(nal.as_bytes()).expect("aftgraphs::simulation::encoder::EncoderHandler: Failed to write frame to output file");
+                    if let Some((nal, pts, dts)) = self.encoder.encode(&self.picture).unwrap() {
+                        if let Err(e) = out_file.write_all(nal.as_bytes()) {
+                            Self::write_failed(e);
+                            return;
+                        }
+                        self.publish(nal.as_bytes(), pts, dts);
+                    }
+
+                    if let Some(ref timings) = self.timings {
+                        let encode = encode_start.elapsed();
+                        timings.record(frame_idx, |timings| {
+                            timings.color_convert = color_convert;
+                            timings.encode = encode;
+                        });
                     }
 
                     frame_idx += 1;
@@ -113,13 +165,36 @@ impl EncoderHandler {
 
         while self.encoder.delayed_frames() {
             match self.encoder.encode(None) {
-                Ok(Some((nal, _, _))) => out_file.write_all(nal.as_bytes()).expect("aftgraphs::simulation::encoder::EncoderHandler: Failed to write frame to output file"),
+                Ok(Some((nal, pts, dts))) => {
+                    if let Err(e) = out_file.write_all(nal.as_bytes()) {
+                        Self::write_failed(e);
+                        return;
+                    }
+                    self.publish(nal.as_bytes(), pts, dts);
+                }
                 Ok(None) => log::info!("aftgraphs::simulation::encoder::EncoderHandler: delayed frame encoding resulted in None"),
                 Err(e) => log::warn!("aftgraphs::simulation::encoder::EncoderHandler: delayed frame encoding resulted in Err: {e}"),
             }
         }
     }
 
+    /// Frames sent after the encoder thread stopped fail to send, ending the render
+    fn write_failed(e: std::io::Error) {
+        crate::error::report(
+            "aftgraphs::simulation::encoder::EncoderHandler",
+            format!("failed to write frame to output file: {e}"),
+        );
+    }
+
+    /// Send an encoded frame to the RTMP sink, timestamps in frames
+    fn publish(&mut self, data: &[u8], pts: i64, dts: i64) {
+        let Some(ref mut rtmp) = self.rtmp else {
+            return;
+        };
+        let ms = |frames: i64| (frames as f64 * self.delta_t * 1000.0).round() as i64;
+        rtmp.send(data, ms(pts), ms(dts));
+    }
+
     fn encode_frame(
         (width, height): (usize, usize),
         bytes_per_row: usize,
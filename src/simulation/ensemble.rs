@@ -0,0 +1,203 @@
+use super::{Simulation, SimulationContext, SimulationRunError};
+use crate::{headless::HeadlessInput, input::Inputs};
+
+/// Pad tightly packed rows of 4 bytes per pixel to the row stride of headless frames
+fn pad_rows(image: &[u8], width: u32) -> Vec<u8> {
+    let row_len = width as usize * 4;
+    let stride = crate::render::padded_bytes_per_row(width) as usize;
+    let mut padded = vec![0; stride * (image.len() / row_len)];
+    for (src, dst) in image
+        .chunks_exact(row_len)
+        .zip(padded.chunks_exact_mut(stride))
+    {
+        dst[..row_len].copy_from_slice(src);
+    }
+    padded
+}
+
+impl<T: Simulation> SimulationContext<T, ()> {
+    /// Render headless_inputs once for every seed of its HeadlessEnsemble, each run
+    /// at the size of the render. Runs are written next to the output file as
+    /// <stem>-seed<seed>, or with HeadlessEnsemble::grid tiled row by row into the output file.
+    /// The runs share one renderer, drawing their frames in turn.
+    pub async fn run_ensemble(
+        self,
+        inputs: Inputs,
+        headless_inputs: HeadlessInput,
+    ) -> Result<(), SimulationRunError> {
+        use super::{create, frame_writer, send_input, TimedInput};
+        use crate::{cli::ARGUMENTS, headless::HeadlessEnsemble, input::InputState, render::Tile};
+        use async_std::sync::Mutex;
+        use std::sync::Arc;
+        use web_time::Duration;
+        use SimulationRunError as SRE;
+
+        log::debug!("aftgraphs::simulation::SimulationContext::run_ensemble entered");
+
+        let size = self.size.ok_or(SRE::HeadlessWithoutSize)?;
+        if headless_inputs.simulation.crop.is_some() || ARGUMENTS.read().await.crop.is_some() {
+            let e = crate::headless::CropError::Unsupported("ensemble");
+            log::error!("aftgraphs::simulation::SimulationContext::run_ensemble: {e}");
+            return Err(SRE::HeadlessCropError(e));
+        }
+        // The runs share the renderer's accumulation target
+        if ARGUMENTS.read().await.accumulate.is_some() {
+            let e = SRE::HeadlessAccumulateUnsupported("ensemble");
+            log::error!("aftgraphs::simulation::SimulationContext::run_ensemble: {e}");
+            return Err(e);
+        }
+        let ensemble = headless_inputs.ensemble.clone().unwrap_or_default();
+        let seeds = ensemble.seeds(crate::rand::startup_seed().await);
+        if seeds.is_empty() {
+            log::warn!(
+                "aftgraphs::simulation::SimulationContext::run_ensemble: the ensemble has no runs"
+            );
+            return Ok(());
+        }
+
+        let (render_imgui, out_file, transparent) = {
+            let args = ARGUMENTS.read().await;
+            let headless = args
+                .headless
+                .clone()
+                .ok_or(SRE::HeadlessWithoutOutputFile)?;
+            (args.render_imgui, headless.out_file, args.transparent)
+        };
+
+        let duration = headless_inputs.simulation.duration;
+        let delta_t = headless_inputs.simulation.delta_t;
+        let render_config = crate::render::RenderConfig::startup().await;
+        let timeline = crate::timeline::Timeline::new(headless_inputs);
+
+        let mut renderer = crate::headless::init(size)
+            .await
+            .map_err(Into::<SRE>::into)?;
+        if let Some(ref config) = render_config {
+            renderer.apply_render_config(config);
+        }
+        if transparent {
+            renderer.clear_color.a = 0.0;
+        }
+        renderer.set_palette(crate::render::Palette::startup().await);
+        renderer.configure_inputs(&inputs);
+        renderer.set_script(crate::script::Script::startup().await);
+        renderer.set_timeline(Some(timeline));
+
+        // Every run sees the same timeline, so they share the input values
+        let input_values = InputState::default();
+        renderer.advance_timeline(input_values.lock().await.as_mut());
+
+        let mut runs = Vec::with_capacity(seeds.len());
+        for &seed in &seeds {
+            log::info!(
+                "aftgraphs::simulation::SimulationContext::run_ensemble: Starting run with seed {seed}"
+            );
+            renderer.seed = seed;
+            let simulation = Arc::new(Mutex::new(create::<T, _>(&mut renderer, None).await));
+            runs.push((seed, simulation));
+        }
+
+        let sink = |size: (u32, u32), out_file: std::path::PathBuf| {
+            frame_writer(size, delta_t, out_file, transparent, None)
+        };
+        let (columns, rows) = HeadlessEnsemble::grid_size(runs.len());
+        let grid = [size.0 * columns as u32, size.1 * rows as u32];
+        let sinks = if ensemble.grid {
+            vec![sink((grid[0], grid[1]), out_file)?]
+        } else {
+            seeds
+                .iter()
+                .map(|&seed| sink(size, HeadlessEnsemble::run_file(&out_file, seed)))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let send_frame = |idx: usize, frame: Vec<u8>| {
+            sinks[idx].0.send(frame).map_err(|e| {
+                log::error!("aftgraphs::simulation::SimulationContext::run_ensemble: Failed to send frame on channel: {e}");
+                SRE::HeadlessEncodingError(format!("{e:?}"))
+            })
+        };
+
+        let mut frame = vec![];
+        let mut image = vec![];
+        if ensemble.grid {
+            image.resize(grid[0] as usize * grid[1] as usize * 4, 0);
+        }
+        let mut time = 0.0;
+        let delta_duration = Duration::from_secs_f64(delta_t);
+        while time <= duration {
+            renderer.update_clock(time, delta_duration);
+            let events = renderer.advance_timeline(input_values.lock().await.as_mut());
+            // Each run steps from the same clock
+            let clock = renderer.clock;
+            for (idx, (seed, simulation)) in runs.iter().enumerate() {
+                renderer.clock = clock;
+                renderer.seed = *seed;
+                for TimedInput { event, time } in events.iter().cloned() {
+                    send_input(&renderer, simulation, event, time).await;
+                }
+                renderer
+                    .render(simulation.clone(), input_values.lock().await.as_mut())
+                    .await;
+
+                if render_imgui {
+                    renderer
+                        .draw_ui(None, &inputs, input_values.clone())
+                        .await?;
+                }
+                renderer.render_headless_finish(&mut frame).await?;
+
+                if ensemble.grid {
+                    let cell = Tile {
+                        offset: [
+                            (idx % columns) as u32 * size.0,
+                            (idx / columns) as u32 * size.1,
+                        ],
+                        size: [size.0, size.1],
+                        image: grid,
+                    };
+                    cell.stitch(&frame, frame.len() / size.1 as usize, &mut image);
+                } else {
+                    send_frame(idx, frame.clone())?;
+                }
+            }
+
+            if ensemble.grid {
+                send_frame(0, pad_rows(&image, grid[0]))?;
+            }
+            time += delta_t;
+            profiling::finish_frame!();
+        }
+
+        let mut result = Ok(());
+        for (_, finished, handle) in sinks {
+            if let Err(e) = finished.send(()) {
+                log::warn!("aftgraphs::simulation::SimulationContext::run_ensemble: error signaling end of frames to encoding thread: {e}");
+            }
+            if let Err(e) = handle.join() {
+                log::error!("aftgraphs::simulation::SimulationContext::run_ensemble: encoding thread panicked: {e:?}");
+                result = Err(SRE::HeadlessEncodingError(format!("{e:?}")));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pads_rows() {
+        let padded = pad_rows(&[1; 2 * 3 * 4], 2);
+        assert_eq!(
+            3 * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize,
+            padded.len()
+        );
+        assert_eq!([1; 8], padded[256..264]);
+        assert_eq!(0, padded[264]);
+
+        // Rows already a multiple of the alignment are not padded
+        let padded = pad_rows(&[1; 64 * 2 * 4], 64);
+        assert_eq!(vec![1; 64 * 2 * 4], padded);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::headless::Chapter;
+use ffmpeg_next::{codec, encoder, format, media, Dictionary, Rational};
+use std::path::Path;
+
+/// Whether H.264 written to out_file is muxed into a container, rather than kept as a raw stream
+pub fn is_container(out_file: &Path) -> bool {
+    out_file
+        .extension()
+        .is_some_and(|ext| !ext.eq_ignore_ascii_case("h264") && !ext.eq_ignore_ascii_case("264"))
+}
+
+/// Add chapters to output, before its header is written
+pub fn add_chapters(
+    output: &mut format::context::Output,
+    chapters: &[Chapter],
+) -> Result<(), ffmpeg_next::Error> {
+    let ms = |time: f64| (time * 1e3).round() as i64;
+    for (id, chapter) in chapters.iter().enumerate() {
+        output.add_chapter(
+            id as i64,
+            Rational(1, 1000),
+            ms(chapter.start),
+            ms(chapter.end),
+            &chapter.title,
+        )?;
+    }
+    Ok(())
+}
+
+/// Copy the raw H.264 stream in video, with frames delta_t apart, into the container
+/// out_file's extension names, with chapters
+pub fn mux(
+    video: &Path,
+    delta_t: f64,
+    chapters: &[Chapter],
+    out_file: &Path,
+) -> Result<(), ffmpeg_next::Error> {
+    ffmpeg_next::init()?;
+
+    // A raw stream has no timestamps, they are generated from the frame rate
+    let mut options = Dictionary::new();
+    options.set("framerate", &(1.0 / delta_t).to_string());
+    options.set("fflags", "+genpts");
+    let mut input = format::input_with_dictionary(video, options)?;
+    let mut output = format::output(out_file)?;
+
+    let (index, time_base) = {
+        let stream = input
+            .streams()
+            .best(media::Type::Video)
+            .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+        let mut out_stream = output.add_stream(encoder::find(codec::Id::None))?;
+        out_stream.set_parameters(stream.parameters());
+        // The codec tag of the raw stream may not be valid in the container
+        unsafe {
+            (*out_stream.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+        (stream.index(), stream.time_base())
+    };
+    add_chapters(&mut output, chapters)?;
+    output.write_header()?;
+    // The muxer may pick its own time base in write_header
+    let stream_time_base = output
+        .stream(0)
+        .ok_or(ffmpeg_next::Error::StreamNotFound)?
+        .time_base();
+
+    for (stream, mut packet) in input.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        packet.rescale_ts(time_base, stream_time_base);
+        packet.set_position(-1);
+        packet.set_stream(0);
+        packet.write_interleaved(&mut output)?;
+    }
+    output.write_trailer()
+}
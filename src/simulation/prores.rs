@@ -0,0 +1,162 @@
+use super::mux;
+use crate::headless::Chapter;
+use crossbeam::{channel, select};
+use ffmpeg_next::{codec, encoder, format, frame, software::scaling, Dictionary, Packet, Rational};
+use std::{
+    path::Path,
+    thread::{self, JoinHandle},
+};
+
+/// Starts encoding frames as ProRes 4444 with alpha in the background, into out_file
+/// The container comes from the extension of out_file, ProRes is usually kept in .mov.
+/// Takes frames and the end signal like encoder::encoder, chapters are muxed into out_file.
+pub fn prores(
+    size: (u32, u32),
+    delta_t: f64,
+    out_file: impl AsRef<Path>,
+    chapters: Vec<Chapter>,
+) -> (
+    channel::Sender<Vec<u8>>,
+    channel::Sender<()>,
+    JoinHandle<()>,
+) {
+    let (send, recv) = channel::bounded(8);
+    let (send_finished, recv_finished) = channel::bounded(1);
+    let out_file = out_file.as_ref().to_owned();
+
+    let handle = thread::spawn(move || {
+        profiling::register_thread!("prores");
+
+        // Dropping the receivers on failure fails the next frame sent
+        if let Err(e) = encode(size, delta_t, &out_file, &chapters, &recv, &recv_finished) {
+            log::error!(
+                "aftgraphs::simulation::prores: Failed to encode {}: {e}",
+                out_file.display()
+            );
+        }
+    });
+
+    (send, send_finished, handle)
+}
+
+fn encode(
+    size: (u32, u32),
+    delta_t: f64,
+    out_file: &Path,
+    chapters: &[Chapter],
+    frames: &channel::Receiver<Vec<u8>>,
+    finished: &channel::Receiver<()>,
+) -> Result<(), ffmpeg_next::Error> {
+    use format::Pixel;
+
+    ffmpeg_next::init()?;
+    let mut output = format::output(out_file)?;
+    let codec = encoder::find_by_name("prores_ks").ok_or(ffmpeg_next::Error::EncoderNotFound)?;
+    let global_header = output
+        .format()
+        .flags()
+        .contains(format::Flags::GLOBAL_HEADER);
+
+    // Frame n is shown at n * delta_t
+    let time_base = Rational::from(delta_t);
+    let mut video = codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()?;
+    video.set_width(size.0);
+    video.set_height(size.1);
+    video.set_format(Pixel::YUVA444P10LE);
+    video.set_time_base(time_base);
+    video.set_frame_rate(Some(time_base.invert()));
+    if global_header {
+        video.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+    let mut options = Dictionary::new();
+    options.set("profile", "4444");
+    let mut video = video.open_with(options)?;
+
+    let index = {
+        let mut stream = output.add_stream(codec)?;
+        stream.set_parameters(&video);
+        stream.index()
+    };
+    mux::add_chapters(&mut output, chapters)?;
+    output.write_header()?;
+    // The muxer may pick its own time base in write_header
+    let stream_time_base = output
+        .stream(index)
+        .ok_or(ffmpeg_next::Error::StreamNotFound)?
+        .time_base();
+
+    let mut scaler = scaling::Context::get(
+        Pixel::RGBA,
+        size.0,
+        size.1,
+        Pixel::YUVA444P10LE,
+        size.0,
+        size.1,
+        scaling::Flags::BILINEAR,
+    )?;
+    let mut rgba = frame::Video::new(Pixel::RGBA, size.0, size.1);
+    let mut yuva = frame::Video::empty();
+    let bytes_per_row = crate::render::padded_bytes_per_row(size.0) as usize;
+    let row_len = size.0 as usize * 4;
+
+    let mut frame_idx = 0;
+    loop {
+        select! {
+            recv(frames) -> frame => {
+                let frame = match frame {
+                    Ok(f) => f,
+                    Err(e) => {
+                        log::warn!("aftgraphs::simulation::prores: Error recieving frame: {e:?}");
+                        continue;
+                    },
+                };
+
+                profiling::scope!("encode prores");
+                let stride = rgba.stride(0);
+                for (row, line) in rgba
+                    .data_mut(0)
+                    .chunks_mut(stride)
+                    .take(size.1 as usize)
+                    .enumerate()
+                {
+                    let start = row * bytes_per_row;
+                    line[..row_len].copy_from_slice(&frame[start..start + row_len]);
+                }
+                scaler.run(&rgba, &mut yuva)?;
+                yuva.set_pts(Some(frame_idx));
+                video.send_frame(&yuva)?;
+                write_packets(&mut video, &mut output, index, [time_base, stream_time_base])?;
+                frame_idx += 1;
+            }
+            recv(finished) -> _ => break,
+        }
+    }
+
+    video.send_eof()?;
+    write_packets(
+        &mut video,
+        &mut output,
+        index,
+        [time_base, stream_time_base],
+    )?;
+    output.write_trailer()
+}
+
+/// Write the packets video has ready to stream index of output
+/// time_bases are those of the encoder and of the stream
+fn write_packets(
+    video: &mut encoder::Video,
+    output: &mut format::context::Output,
+    index: usize,
+    [time_base, stream_time_base]: [Rational; 2],
+) -> Result<(), ffmpeg_next::Error> {
+    let mut packet = Packet::empty();
+    while video.receive_packet(&mut packet).is_ok() {
+        packet.set_stream(index);
+        packet.rescale_ts(time_base, stream_time_base);
+        packet.write_interleaved(output)?;
+    }
+    Ok(())
+}
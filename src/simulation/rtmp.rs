@@ -0,0 +1,743 @@
+use amf::Value;
+use crossbeam::channel::{self, TrySendError};
+use flv::AccessUnit;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+mod amf;
+mod flv;
+
+const DEFAULT_PORT: u16 = 1935;
+const HANDSHAKE_SIZE: usize = 1536;
+const CHUNK_SIZE: usize = 4096;
+/// Frames queued for the publishing thread, a few seconds of video
+const QUEUE_FRAMES: usize = 256;
+/// Timeout of connecting, the handshake and every write
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const CSID_CONTROL: u32 = 2;
+const CSID_COMMAND: u32 = 3;
+const CSID_DATA: u32 = 4;
+const CSID_VIDEO: u32 = 6;
+
+const TYPE_SET_CHUNK_SIZE: u8 = 1;
+const TYPE_VIDEO: u8 = 9;
+const TYPE_DATA: u8 = 18;
+const TYPE_COMMAND: u8 = 20;
+
+const TXN_CREATE_STREAM: f64 = 4.0;
+
+#[derive(Error, Debug)]
+pub enum RtmpError {
+    #[error("RTMP connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid RTMP url {0}, expected rtmp://host[:port]/app/stream-key")]
+    InvalidUrl(String),
+    #[error("RTMP server rejected the stream: {0}")]
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RtmpUrl {
+    host: String,
+    port: u16,
+    app: String,
+    key: String,
+}
+
+impl RtmpUrl {
+    fn parse(url: &str) -> Result<Self, RtmpError> {
+        let invalid = || RtmpError::InvalidUrl(url.to_owned());
+        let rest = url.strip_prefix("rtmp://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let (app, key) = path.rsplit_once('/').ok_or_else(invalid)?;
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, DEFAULT_PORT),
+        };
+
+        if host.is_empty() || app.is_empty() || key.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            app: app.to_owned(),
+            key: key.to_owned(),
+        })
+    }
+
+    /// The url without the stream key, safe to log
+    fn tc_url(&self) -> String {
+        format!("rtmp://{}:{}/{}", self.host, self.port, self.app)
+    }
+}
+
+/// Settings of the encoded video announced to the server
+#[derive(Debug, Clone, Copy)]
+pub struct StreamMetadata {
+    pub size: (u32, u32),
+    pub fps: f64,
+    /// Kilobits per second
+    pub bitrate: Option<u32>,
+}
+
+/// Publishes encoded H.264 frames to an RTMP server in real time
+/// Frames are paced to their timestamps on a thread of their own, so the encoder is not held
+/// up. While that thread falls behind, frames are dropped until the next keyframe.
+/// Dropped connections are retried with exponential backoff, resuming at the next keyframe.
+pub struct RtmpSink {
+    frames: Option<channel::Sender<Frame>>,
+    /// A frame was dropped, so the ones depending on it are too
+    dropping: bool,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RtmpSink {
+    pub fn new(url: &str, metadata: StreamMetadata) -> Result<Self, RtmpError> {
+        let url = RtmpUrl::parse(url).inspect_err(|err| {
+            log::error!("aftgraphs::simulation::rtmp::RtmpSink::new: {err}");
+        })?;
+        log::info!(
+            "aftgraphs::simulation::rtmp::RtmpSink::new: Streaming to {}",
+            url.tc_url()
+        );
+
+        let (send, recv) = channel::bounded(QUEUE_FRAMES);
+        let handle = thread::spawn(move || {
+            profiling::register_thread!("rtmp");
+            let mut publisher = Publisher {
+                url,
+                metadata,
+                connection: None,
+                sequence_header: None,
+                retry_at: Instant::now(),
+                backoff: MIN_BACKOFF,
+                pace: None,
+            };
+            for frame in recv {
+                publisher.publish(frame);
+            }
+        });
+
+        Ok(Self {
+            frames: Some(send),
+            dropping: false,
+            handle: Some(handle),
+        })
+    }
+
+    /// Queue an Annex B encoded frame, timestamps in milliseconds
+    pub fn send(&mut self, data: &[u8], pts: i64, dts: i64) {
+        let unit = AccessUnit::from_annexb(data);
+        let keyframe = unit.is_keyframe();
+        if self.dropping && !keyframe {
+            return;
+        }
+
+        let frame = Frame {
+            body: unit.frame((pts - dts) as i32),
+            sequence_header: unit.sequence_header(),
+            keyframe,
+            dts,
+        };
+        let Some(ref frames) = self.frames else {
+            return;
+        };
+        match frames.try_send(frame) {
+            Ok(()) => self.dropping = false,
+            Err(TrySendError::Full(_)) => {
+                if !self.dropping {
+                    log::warn!("aftgraphs::simulation::rtmp::RtmpSink::send: The stream is behind, dropping frames until the next keyframe");
+                }
+                self.dropping = true;
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::error!(
+                    "aftgraphs::simulation::rtmp::RtmpSink::send: The publishing thread stopped"
+                );
+                self.frames = None;
+            }
+        }
+    }
+}
+
+impl Drop for RtmpSink {
+    /// Publish the queued frames before returning
+    fn drop(&mut self) {
+        drop(self.frames.take());
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!(
+                    "aftgraphs::simulation::rtmp::RtmpSink::drop: The publishing thread panicked"
+                );
+            }
+        }
+    }
+}
+
+/// An encoded frame as an FLV video tag body, timestamps in milliseconds
+struct Frame {
+    body: Vec<u8>,
+    sequence_header: Option<Vec<u8>>,
+    keyframe: bool,
+    dts: i64,
+}
+
+/// The connection of an RtmpSink, owned by its publishing thread
+struct Publisher {
+    url: RtmpUrl,
+    metadata: StreamMetadata,
+    connection: Option<Connection>,
+    sequence_header: Option<Vec<u8>>,
+    retry_at: Instant,
+    backoff: Duration,
+    /// Wall clock time of the first frame and its decode timestamp
+    pace: Option<(Instant, i64)>,
+}
+
+impl Publisher {
+    /// Send frame once its timestamp is due
+    fn publish(&mut self, frame: Frame) {
+        if let Some(header) = frame.sequence_header {
+            self.sequence_header = Some(header);
+        }
+
+        let (start, first_dts) = *self.pace.get_or_insert((Instant::now(), frame.dts));
+        let target = start + Duration::from_millis((frame.dts - first_dts).max(0) as u64);
+        if let Some(wait) = target.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+
+        if self.connection.is_none() {
+            // Only a keyframe can start the stream
+            if !frame.keyframe || Instant::now() < self.retry_at {
+                return;
+            }
+            self.reconnect(frame.dts);
+        }
+
+        let Some(ref mut connection) = self.connection else {
+            return;
+        };
+        let timestamp = (frame.dts - connection.base_dts).max(0) as u32;
+        if let Err(err) = connection.write_message(
+            CSID_VIDEO,
+            TYPE_VIDEO,
+            connection.stream_id,
+            timestamp,
+            &frame.body,
+        ) {
+            log::warn!("aftgraphs::simulation::rtmp::Publisher::publish: Connection lost: {err}");
+            self.connection = None;
+            self.retry_at = Instant::now() + self.backoff;
+        }
+    }
+
+    fn reconnect(&mut self, dts: i64) {
+        let Some(header) = self.sequence_header.clone() else {
+            log::warn!(
+                "aftgraphs::simulation::rtmp::Publisher::reconnect: No H.264 parameter sets yet"
+            );
+            return;
+        };
+
+        let connection = Connection::open(&self.url, &self.metadata, dts).and_then(|mut conn| {
+            conn.write_message(CSID_VIDEO, TYPE_VIDEO, conn.stream_id, 0, &header)?;
+            Ok(conn)
+        });
+        match connection {
+            Ok(connection) => {
+                log::info!("aftgraphs::simulation::rtmp::Publisher::reconnect: Publishing");
+                self.connection = Some(connection);
+                self.backoff = MIN_BACKOFF;
+            }
+            Err(err) => {
+                log::warn!(
+                    "aftgraphs::simulation::rtmp::Publisher::reconnect: {err}, retrying in {:?}",
+                    self.backoff
+                );
+                self.retry_at = Instant::now() + self.backoff;
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Previous header of a chunk stream, which later chunks of the stream may leave out
+#[derive(Debug, Clone, Copy, Default)]
+struct ChunkHeader {
+    timestamp: u32,
+    /// Added to the timestamp by a fmt 3 chunk starting a message
+    delta: u32,
+    len: usize,
+    type_id: u8,
+    stream_id: u32,
+    /// The timestamp field was extended, so fmt 3 chunks carry the extended field too
+    extended: bool,
+}
+
+/// Reassembles the messages of the chunk streams a server sends
+struct ChunkReader {
+    chunk_size: usize,
+    headers: HashMap<u32, ChunkHeader>,
+    partial: HashMap<u32, Vec<u8>>,
+}
+
+impl Default for ChunkReader {
+    fn default() -> Self {
+        Self {
+            chunk_size: 128,
+            headers: HashMap::new(),
+            partial: HashMap::new(),
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl ChunkReader {
+    /// Read chunks from stream until a message is complete, returning its type and payload
+    /// Chunk size changes are applied and returned like any other message.
+    fn read_message(&mut self, stream: &mut impl Read) -> std::io::Result<(u8, Vec<u8>)> {
+        loop {
+            let [basic] = read_array(stream)?;
+            let fmt = basic >> 6;
+            let csid = match basic & 0x3F {
+                0 => 64 + read_array::<1>(stream)?[0] as u32,
+                1 => {
+                    let [low, high] = read_array(stream)?;
+                    64 + low as u32 + high as u32 * 256
+                }
+                csid => csid as u32,
+            };
+
+            let mut header = self.headers.get(&csid).copied().unwrap_or_default();
+            let starts_message = self.partial.get(&csid).is_none_or(Vec::is_empty);
+            let field = match fmt {
+                0 => {
+                    let bytes: [u8; 11] = read_array(stream)?;
+                    header.len = u32::from_be_bytes([0, bytes[3], bytes[4], bytes[5]]) as usize;
+                    header.type_id = bytes[6];
+                    header.stream_id =
+                        u32::from_le_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]);
+                    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+                }
+                1 => {
+                    let bytes: [u8; 7] = read_array(stream)?;
+                    header.len = u32::from_be_bytes([0, bytes[3], bytes[4], bytes[5]]) as usize;
+                    header.type_id = bytes[6];
+                    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+                }
+                2 => {
+                    let bytes: [u8; 3] = read_array(stream)?;
+                    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+                }
+                // Repeats the extended timestamp, if the stream's last header had one
+                _ => {
+                    if header.extended {
+                        read_array::<4>(stream)?;
+                    }
+                    None
+                }
+            };
+
+            match field {
+                Some(field) => {
+                    header.extended = field == 0xFF_FFFF;
+                    let field = if header.extended {
+                        u32::from_be_bytes(read_array(stream)?)
+                    } else {
+                        field
+                    };
+                    if fmt == 0 {
+                        header.timestamp = field;
+                        header.delta = 0;
+                    } else {
+                        header.timestamp = header.timestamp.wrapping_add(field);
+                        header.delta = field;
+                    }
+                }
+                None if starts_message => {
+                    header.timestamp = header.timestamp.wrapping_add(header.delta);
+                }
+                None => (),
+            }
+            self.headers.insert(csid, header);
+
+            let partial = self.partial.entry(csid).or_default();
+            let start = partial.len();
+            let Some(left) = header.len.checked_sub(start) else {
+                return Err(invalid_data(
+                    "RTMP message is shorter than the chunks already received",
+                ));
+            };
+            let read = left.min(self.chunk_size);
+            partial.resize(start + read, 0);
+            stream.read_exact(&mut partial[start..])?;
+
+            if partial.len() == header.len {
+                let payload = self.partial.remove(&csid).unwrap_or_default();
+                if header.type_id == TYPE_SET_CHUNK_SIZE {
+                    self.set_chunk_size(&payload)?;
+                }
+                return Ok((header.type_id, payload));
+            }
+        }
+    }
+
+    fn set_chunk_size(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let [a, b, c, d, ..] = *payload else {
+            return Err(invalid_data("RTMP chunk size message is too short"));
+        };
+        let size = u32::from_be_bytes([a, b, c, d]) & 0x7FFF_FFFF;
+        if size == 0 {
+            return Err(invalid_data("RTMP chunk size must be at least 1"));
+        }
+        self.chunk_size = size as usize;
+        Ok(())
+    }
+}
+
+fn read_array<const N: usize>(stream: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+struct Connection {
+    stream: TcpStream,
+    stream_id: u32,
+    /// Decode timestamp sent as 0
+    base_dts: i64,
+    reader: ChunkReader,
+}
+
+impl Connection {
+    fn open(url: &RtmpUrl, metadata: &StreamMetadata, base_dts: i64) -> Result<Self, RtmpError> {
+        let address = (url.host.as_str(), url.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| RtmpError::InvalidUrl(url.tc_url()))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        let mut connection = Self {
+            stream,
+            stream_id: 0,
+            base_dts,
+            reader: ChunkReader::default(),
+        };
+        connection.handshake()?;
+        connection.write_message(
+            CSID_CONTROL,
+            TYPE_SET_CHUNK_SIZE,
+            0,
+            0,
+            &(CHUNK_SIZE as u32).to_be_bytes(),
+        )?;
+
+        let string = |s: &str| Value::String(s.to_owned());
+        connection.command(&[
+            string("connect"),
+            Value::Number(1.0),
+            Value::object(&[
+                ("app", string(&url.app)),
+                ("type", string("nonprivate")),
+                ("flashVer", string("FMLE/3.0 (compatible; aftgraphs)")),
+                ("tcUrl", string(&url.tc_url())),
+            ]),
+        ])?;
+        connection.command(&[
+            string("releaseStream"),
+            Value::Number(2.0),
+            Value::Null,
+            string(&url.key),
+        ])?;
+        connection.command(&[
+            string("FCPublish"),
+            Value::Number(3.0),
+            Value::Null,
+            string(&url.key),
+        ])?;
+        connection.command(&[
+            string("createStream"),
+            Value::Number(TXN_CREATE_STREAM),
+            Value::Null,
+        ])?;
+
+        connection.stream_id = loop {
+            let values = connection.read_command()?;
+            match (&values[..], values.get(3)) {
+                ([Value::String(name), Value::Number(txn), ..], Some(&Value::Number(id)))
+                    if name == "_result" && *txn == TXN_CREATE_STREAM =>
+                {
+                    break id as u32
+                }
+                ([Value::String(name), ..], _) if name == "_error" => {
+                    return Err(RtmpError::Rejected(format!("{values:?}")))
+                }
+                _ => (),
+            }
+        };
+
+        let payload = amf::encode(&[
+            string("publish"),
+            Value::Number(0.0),
+            Value::Null,
+            string(&url.key),
+            string("live"),
+        ]);
+        connection.write_message(
+            CSID_COMMAND,
+            TYPE_COMMAND,
+            connection.stream_id,
+            0,
+            &payload,
+        )?;
+        loop {
+            let values = connection.read_command()?;
+            let [Value::String(name), _, _, status, ..] = &values[..] else {
+                continue;
+            };
+            if name != "onStatus" {
+                continue;
+            }
+
+            let code = match status.get("code") {
+                Some(Value::String(code)) => code.clone(),
+                _ => String::new(),
+            };
+            if code == "NetStream.Publish.Start" {
+                break;
+            }
+            if status.get("level") == Some(&string("error")) {
+                return Err(RtmpError::Rejected(code));
+            }
+        }
+
+        let mut properties = vec![
+            ("width", Value::Number(metadata.size.0 as f64)),
+            ("height", Value::Number(metadata.size.1 as f64)),
+            ("framerate", Value::Number(metadata.fps)),
+            ("videocodecid", Value::Number(7.0)),
+        ];
+        if let Some(bitrate) = metadata.bitrate {
+            properties.push(("videodatarate", Value::Number(bitrate as f64)));
+        }
+        let payload = amf::encode(&[
+            string("@setDataFrame"),
+            string("onMetaData"),
+            Value::object(&properties),
+        ]);
+        connection.write_message(CSID_DATA, TYPE_DATA, connection.stream_id, 0, &payload)?;
+
+        Ok(connection)
+    }
+
+    fn handshake(&mut self) -> Result<(), RtmpError> {
+        // C0 and C1: version 3, time, zeros and filler
+        let mut c0c1 = vec![3];
+        c0c1.extend_from_slice(&[0; 8]);
+        c0c1.extend((0..HANDSHAKE_SIZE - 8).map(|idx| (idx * 31 % 251) as u8));
+        self.stream.write_all(&c0c1)?;
+
+        let mut s0s1 = vec![0; 1 + HANDSHAKE_SIZE];
+        self.stream.read_exact(&mut s0s1)?;
+        if s0s1[0] != 3 {
+            return Err(RtmpError::Rejected(format!(
+                "unsupported RTMP version {}",
+                s0s1[0]
+            )));
+        }
+
+        // C2 echoes S1
+        self.stream.write_all(&s0s1[1..])?;
+        let mut s2 = vec![0; HANDSHAKE_SIZE];
+        self.stream.read_exact(&mut s2)?;
+        Ok(())
+    }
+
+    fn command(&mut self, values: &[Value]) -> Result<(), RtmpError> {
+        let payload = amf::encode(values);
+        self.write_message(CSID_COMMAND, TYPE_COMMAND, 0, 0, &payload)?;
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        csid: u32,
+        type_id: u8,
+        stream_id: u32,
+        timestamp: u32,
+        payload: &[u8],
+    ) -> std::io::Result<()> {
+        let extended = timestamp >= 0xFF_FFFF;
+        let mut buf = Vec::with_capacity(payload.len() + payload.len() / CHUNK_SIZE + 16);
+        buf.push(csid as u8);
+        buf.extend_from_slice(&timestamp.min(0xFF_FFFF).to_be_bytes()[1..]);
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        buf.push(type_id);
+        buf.extend_from_slice(&stream_id.to_le_bytes());
+        if extended {
+            buf.extend_from_slice(&timestamp.to_be_bytes());
+        }
+
+        for (idx, chunk) in payload.chunks(CHUNK_SIZE).enumerate() {
+            if idx > 0 {
+                buf.push(0xC0 | csid as u8);
+                if extended {
+                    buf.extend_from_slice(&timestamp.to_be_bytes());
+                }
+            }
+            buf.extend_from_slice(chunk);
+        }
+
+        self.stream.write_all(&buf)
+    }
+
+    /// Read messages until an AMF0 command arrives
+    fn read_command(&mut self) -> Result<Vec<Value>, RtmpError> {
+        loop {
+            let (type_id, payload) = self.reader.read_message(&mut self.stream)?;
+            if type_id == TYPE_COMMAND {
+                return Ok(amf::decode(&payload));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_url() {
+        assert_eq!(
+            RtmpUrl {
+                host: "live.example.com".to_owned(),
+                port: 1935,
+                app: "app/live".to_owned(),
+                key: "key".to_owned(),
+            },
+            RtmpUrl::parse("rtmp://live.example.com/app/live/key").unwrap()
+        );
+        assert_eq!(
+            8080,
+            RtmpUrl::parse("rtmp://localhost:8080/live/key")
+                .unwrap()
+                .port
+        );
+        assert!(RtmpUrl::parse("rtmp://localhost/key").is_err());
+    }
+
+    #[test]
+    fn read_chunks() {
+        let mut reader = ChunkReader::default();
+        let mut stream: &[u8] = &[
+            // fmt 0 on chunk stream 3, extended timestamp, 3 bytes of a command
+            0x03,
+            0xFF,
+            0xFF,
+            0xFF,
+            0,
+            0,
+            3,
+            TYPE_COMMAND,
+            1,
+            0,
+            0,
+            0,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            1,
+            2,
+            3,
+            // fmt 3 starting the next message repeats the extended timestamp
+            0xC3,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            4,
+            5,
+            6,
+        ];
+        assert_eq!(
+            (TYPE_COMMAND, vec![1, 2, 3]),
+            reader.read_message(&mut stream).unwrap()
+        );
+        assert_eq!(
+            (TYPE_COMMAND, vec![4, 5, 6]),
+            reader.read_message(&mut stream).unwrap()
+        );
+        assert_eq!(0x0100_0000, reader.headers[&3].timestamp);
+        assert!(stream.is_empty());
+
+        // A chunk size of 0 would never finish a message
+        let mut stream: &[u8] = &[
+            0x02,
+            0,
+            0,
+            0,
+            0,
+            0,
+            4,
+            TYPE_SET_CHUNK_SIZE,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ];
+        assert!(reader.read_message(&mut stream).is_err());
+
+        // A message shrinking below the part already received
+        let mut reader = ChunkReader {
+            chunk_size: 2,
+            ..Default::default()
+        };
+        let mut stream: &[u8] = &[
+            0x03,
+            0,
+            0,
+            0,
+            0,
+            0,
+            4,
+            TYPE_COMMAND,
+            0,
+            0,
+            0,
+            0,
+            1,
+            2,
+            0x43,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            TYPE_COMMAND,
+        ];
+        assert!(reader.read_message(&mut stream).is_err());
+    }
+}
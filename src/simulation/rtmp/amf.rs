@@ -0,0 +1,151 @@
+//! The subset of AMF0 used by RTMP commands
+
+const NUMBER: u8 = 0x00;
+const BOOLEAN: u8 = 0x01;
+const STRING: u8 = 0x02;
+const OBJECT: u8 = 0x03;
+const NULL: u8 = 0x05;
+const ECMA_ARRAY: u8 = 0x08;
+const OBJECT_END: [u8; 3] = [0x00, 0x00, 0x09];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Object(Vec<(String, Value)>),
+    Null,
+}
+
+impl Value {
+    pub(super) fn object(properties: &[(&str, Value)]) -> Self {
+        Value::Object(
+            properties
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    /// Property key of an Object
+    pub(super) fn get(&self, key: &str) -> Option<&Value> {
+        let Value::Object(properties) = self else {
+            return None;
+        };
+        properties
+            .iter()
+            .find_map(|(name, value)| (name == key).then_some(value))
+    }
+}
+
+pub(super) fn encode(values: &[Value]) -> Vec<u8> {
+    let mut buf = vec![];
+    for value in values {
+        encode_value(&mut buf, value);
+    }
+    buf
+}
+
+fn encode_string(buf: &mut Vec<u8>, string: &str) {
+    buf.extend_from_slice(&(string.len() as u16).to_be_bytes());
+    buf.extend_from_slice(string.as_bytes());
+}
+
+fn encode_value(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Number(number) => {
+            buf.push(NUMBER);
+            buf.extend_from_slice(&number.to_be_bytes());
+        }
+        Value::Boolean(boolean) => buf.extend_from_slice(&[BOOLEAN, *boolean as u8]),
+        Value::String(string) => {
+            buf.push(STRING);
+            encode_string(buf, string);
+        }
+        Value::Object(properties) => {
+            buf.push(OBJECT);
+            for (key, value) in properties {
+                encode_string(buf, key);
+                encode_value(buf, value);
+            }
+            buf.extend_from_slice(&OBJECT_END);
+        }
+        Value::Null => buf.push(NULL),
+    }
+}
+
+/// Decode values until buf ends or an unsupported type is found
+pub(super) fn decode(mut buf: &[u8]) -> Vec<Value> {
+    let mut values = vec![];
+    while let Some((value, rest)) = decode_value(buf) {
+        values.push(value);
+        buf = rest;
+    }
+    values
+}
+
+fn decode_string(buf: &[u8]) -> Option<(String, &[u8])> {
+    let len = u16::from_be_bytes(buf.get(..2)?.try_into().ok()?) as usize;
+    let string = buf.get(2..2 + len)?;
+    Some((
+        String::from_utf8_lossy(string).into_owned(),
+        &buf[2 + len..],
+    ))
+}
+
+fn decode_properties(mut buf: &[u8]) -> Option<(Value, &[u8])> {
+    let mut properties = vec![];
+    loop {
+        if buf.starts_with(&OBJECT_END) {
+            return Some((Value::Object(properties), &buf[OBJECT_END.len()..]));
+        }
+        let (key, rest) = decode_string(buf)?;
+        let (value, rest) = decode_value(rest)?;
+        properties.push((key, value));
+        buf = rest;
+    }
+}
+
+fn decode_value(buf: &[u8]) -> Option<(Value, &[u8])> {
+    let (&marker, rest) = buf.split_first()?;
+    match marker {
+        NUMBER => {
+            let number = f64::from_be_bytes(rest.get(..8)?.try_into().ok()?);
+            Some((Value::Number(number), &rest[8..]))
+        }
+        BOOLEAN => Some((Value::Boolean(*rest.first()? != 0), &rest[1..])),
+        STRING => {
+            let (string, rest) = decode_string(rest)?;
+            Some((Value::String(string), rest))
+        }
+        OBJECT => decode_properties(rest),
+        // The count is only a hint, the properties end like an object's
+        ECMA_ARRAY => decode_properties(rest.get(4..)?),
+        NULL => Some((Value::Null, rest)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let values = vec![
+            Value::String("_result".to_owned()),
+            Value::Number(2.0),
+            Value::Null,
+            Value::object(&[
+                ("code", Value::String("NetStream.Publish.Start".to_owned())),
+                ("live", Value::Boolean(true)),
+            ]),
+        ];
+        let decoded = decode(&encode(&values));
+        assert_eq!(values, decoded);
+        assert_eq!(
+            Some(&Value::String("NetStream.Publish.Start".to_owned())),
+            decoded[3].get("code")
+        );
+    }
+}
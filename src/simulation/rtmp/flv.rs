@@ -0,0 +1,125 @@
+//! FLV video tag bodies of H.264 encoded frames
+
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+const CODEC_AVC: u8 = 7;
+const FRAME_KEY: u8 = 1 << 4;
+const FRAME_INTER: u8 = 2 << 4;
+
+/// An encoded frame split into its NAL units
+pub(super) struct AccessUnit<'a> {
+    pub nals: Vec<&'a [u8]>,
+}
+
+impl<'a> AccessUnit<'a> {
+    /// Split an Annex B byte stream on its start codes
+    pub(super) fn from_annexb(data: &'a [u8]) -> Self {
+        let mut nals = vec![];
+        let mut start = None;
+        let mut idx = 0;
+        while idx + 3 <= data.len() {
+            if data[idx..idx + 3] == [0, 0, 1] {
+                if let Some(start) = start {
+                    nals.push(trim_zeros(&data[start..idx]));
+                }
+                idx += 3;
+                start = Some(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        if let Some(start) = start {
+            nals.push(&data[start..]);
+        }
+
+        nals.retain(|nal| !nal.is_empty());
+        Self { nals }
+    }
+
+    fn find(&self, nal_type: u8) -> Option<&'a [u8]> {
+        self.nals
+            .iter()
+            .copied()
+            .find(|nal| nal[0] & 0x1F == nal_type)
+    }
+
+    pub(super) fn is_keyframe(&self) -> bool {
+        self.find(NAL_IDR).is_some()
+    }
+
+    /// Tag body of the AVCDecoderConfigurationRecord, if the unit holds an SPS and a PPS
+    pub(super) fn sequence_header(&self) -> Option<Vec<u8>> {
+        let sps = self.find(NAL_SPS).filter(|sps| sps.len() >= 4)?;
+        let pps = self.find(NAL_PPS)?;
+
+        let mut body = vec![FRAME_KEY | CODEC_AVC, 0, 0, 0, 0];
+        body.extend_from_slice(&[1, sps[1], sps[2], sps[3], 0xFF, 0xE1]);
+        body.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        body.extend_from_slice(sps);
+        body.push(1);
+        body.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+        body.extend_from_slice(pps);
+        Some(body)
+    }
+
+    /// Tag body of the frame's NAL units, length prefixed, composition_time in ms
+    pub(super) fn frame(&self, composition_time: i32) -> Vec<u8> {
+        let frame_type = if self.is_keyframe() {
+            FRAME_KEY
+        } else {
+            FRAME_INTER
+        };
+        let mut body = vec![frame_type | CODEC_AVC, 1];
+        body.extend_from_slice(&composition_time.to_be_bytes()[1..]);
+
+        let nals = self.nals.iter().filter(|nal| {
+            let nal_type = nal[0] & 0x1F;
+            nal_type != NAL_SPS && nal_type != NAL_PPS && nal_type != NAL_AUD
+        });
+        for nal in nals {
+            body.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            body.extend_from_slice(nal);
+        }
+        body
+    }
+}
+
+/// Drop the leading zero of a 4 byte start code ending the previous NAL unit
+fn trim_zeros(nal: &[u8]) -> &[u8] {
+    let end = nal
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |idx| idx + 1);
+    &nal[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_annexb() {
+        let data = [
+            0, 0, 0, 1, 0x67, 0x64, 0x00, 0x1F, 0, 0, 1, 0x68, 0xEE, 0, 0, 0, 1, 0x65, 0x88,
+        ];
+        let unit = AccessUnit::from_annexb(&data);
+        assert_eq!(
+            vec![&[0x67, 0x64, 0x00, 0x1F][..], &[0x68, 0xEE], &[0x65, 0x88]],
+            unit.nals
+        );
+        assert!(unit.is_keyframe());
+        assert_eq!(
+            vec![0x17, 1, 0, 0, 0, 0, 0, 0, 2, 0x65, 0x88],
+            unit.frame(0)
+        );
+        assert_eq!(
+            Some(&[0x17, 0, 0, 0, 0, 1, 0x64, 0x00, 0x1F, 0xFF, 0xE1][..]),
+            unit.sequence_header()
+                .as_deref()
+                .map(|header| &header[..11])
+        );
+    }
+}
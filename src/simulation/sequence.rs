@@ -0,0 +1,91 @@
+use crossbeam::{channel, select};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+/// Starts writing frames as an RGBA PNG sequence in the background, next to out_file
+/// Frame n is written to <out_file stem>-<n>.png, numbered from 0 with 5 digits.
+/// Used for --transparent unless prores::prores encodes a .mov output.
+/// Takes frames and the end signal like encoder::encoder.
+pub fn sequence(
+    size: (u32, u32),
+    out_file: impl AsRef<Path>,
+) -> (
+    channel::Sender<Vec<u8>>,
+    channel::Sender<()>,
+    JoinHandle<()>,
+) {
+    let (send, recv) = channel::bounded(8);
+    let (send_finished, recv_finished) = channel::bounded(1);
+    let out_file = out_file.as_ref().to_owned();
+
+    let handle = thread::spawn(move || {
+        profiling::register_thread!("sequence");
+
+        let bytes_per_row = crate::render::padded_bytes_per_row(size.0) as usize;
+        let mut frame_idx = 0;
+        loop {
+            select! {
+                recv(recv) -> frame => {
+                    let frame = match frame {
+                        Ok(f) => f,
+                        Err(e) => {
+                            log::warn!("aftgraphs::simulation::sequence: Error recieving frame: {e:?}");
+                            continue;
+                        },
+                    };
+
+                    profiling::scope!("write png");
+                    let path = frame_path(&out_file, frame_idx);
+                    if let Err(e) = write_frame(&path, size, bytes_per_row, &frame) {
+                        log::error!(
+                            "aftgraphs::simulation::sequence: Failed to write {}: {e}",
+                            path.display()
+                        );
+                    }
+                    frame_idx += 1;
+                }
+                recv(recv_finished) -> _ => break,
+            }
+        }
+    });
+
+    (send, send_finished, handle)
+}
+
+fn frame_path(out_file: &Path, frame: u64) -> PathBuf {
+    let stem = out_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    out_file.with_file_name(format!("{stem}-{frame:05}.png"))
+}
+
+fn write_frame(
+    path: &Path,
+    size: (u32, u32),
+    bytes_per_row: usize,
+    frame: &[u8],
+) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    crate::png::write(&mut out, size.0, size.1, true, |y| {
+        &frame[y as usize * bytes_per_row..]
+    })?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames_are_numbered_next_to_output() {
+        assert_eq!(
+            PathBuf::from("out/render-00042.png"),
+            frame_path(Path::new("out/render.h264"), 42)
+        );
+    }
+}
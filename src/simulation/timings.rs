@@ -0,0 +1,132 @@
+use std::{fmt::Write, sync::Mutex, time::Duration};
+
+/// Time spent on one frame by each stage of the headless pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameTimings {
+    /// Simulation::render and the ui, on the CPU
+    pub render: Duration,
+    /// Submitting the frame and waiting for the GPU to finish it
+    pub gpu_wait: Duration,
+    /// Copying the mapped frame off the GPU buffer
+    pub readback: Duration,
+    /// Removing the row padding and converting RGBA to YUV420
+    pub color_convert: Duration,
+    /// x264 encoding and writing the output file
+    pub encode: Duration,
+}
+
+impl FrameTimings {
+    fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("render", self.render),
+            ("gpu wait", self.gpu_wait),
+            ("readback", self.readback),
+            ("color convert", self.color_convert),
+            ("encode", self.encode),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages().iter().map(|(_, time)| *time).sum()
+    }
+}
+
+/// Collects FrameTimings from the render loop and the encoding thread
+#[derive(Debug, Default)]
+pub struct TimingsRecorder {
+    frames: Mutex<Vec<FrameTimings>>,
+}
+
+fn ms(time: Duration) -> f64 {
+    time.as_secs_f64() * 1e3
+}
+
+impl TimingsRecorder {
+    /// Update the timings of a frame
+    pub fn record(&self, frame: usize, update: impl FnOnce(&mut FrameTimings)) {
+        let mut frames = self
+            .frames
+            .lock()
+            .expect("aftgraphs::simulation::timings::TimingsRecorder::record: poisoned lock");
+        if frames.len() <= frame {
+            frames.resize(frame + 1, FrameTimings::default());
+        }
+        update(&mut frames[frame]);
+    }
+
+    pub fn frames(&self) -> Vec<FrameTimings> {
+        self.frames
+            .lock()
+            .expect("aftgraphs::simulation::timings::TimingsRecorder::frames: poisoned lock")
+            .clone()
+    }
+
+    /// Per-frame breakdown followed by a summary of each stage
+    pub fn report(&self) -> String {
+        let frames = self.frames();
+        let mut report = String::new();
+
+        let _ = write!(report, "{:>6}", "frame");
+        for (stage, _) in FrameTimings::default().stages() {
+            let _ = write!(report, " {stage:>14}");
+        }
+        let _ = writeln!(report, " {:>14}", "total");
+
+        for (idx, frame) in frames.iter().enumerate() {
+            let _ = write!(report, "{idx:>6}");
+            for (_, time) in frame.stages() {
+                let _ = write!(report, " {:>11.3} ms", ms(time));
+            }
+            let _ = writeln!(report, " {:>11.3} ms", ms(frame.total()));
+        }
+
+        let count = frames.len().max(1) as u32;
+        let total: Duration = frames.iter().map(FrameTimings::total).sum();
+        let _ = writeln!(report, "\nSummary of {} frames:", frames.len());
+        for (idx, (stage, _)) in FrameTimings::default().stages().into_iter().enumerate() {
+            let times = frames.iter().map(|frame| frame.stages()[idx].1);
+            let sum: Duration = times.clone().sum();
+            let max = times.max().unwrap_or_default();
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                sum.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+
+            let _ = writeln!(
+                report,
+                "{stage:>14}: mean {:>9.3} ms, max {:>9.3} ms, {share:>5.1}% of the pipeline",
+                ms(sum / count),
+                ms(max),
+            );
+        }
+        let _ = write!(
+            report,
+            "{:>14}: mean {:>9.3} ms, {:.3} s overall",
+            "total",
+            ms(total / count),
+            total.as_secs_f64()
+        );
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_out_of_order() {
+        let recorder = TimingsRecorder::default();
+        recorder.record(1, |frame| frame.encode = Duration::from_millis(3));
+        recorder.record(0, |frame| frame.render = Duration::from_millis(1));
+        recorder.record(1, |frame| frame.render = Duration::from_millis(2));
+
+        let frames = recorder.frames();
+        assert_eq!(2, frames.len());
+        assert_eq!(Duration::from_millis(1), frames[0].total());
+        assert_eq!(Duration::from_millis(5), frames[1].total());
+        assert!(recorder.report().contains("Summary of 2 frames"));
+    }
+}
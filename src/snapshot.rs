@@ -0,0 +1,143 @@
+//! Named snapshots of the input values, camera, seed and time of a simulation,
+//! encoded as a compact url-safe string to share exact views.
+//! On WASM the string goes into the url fragment as #snapshot=<string> and is
+//! applied when the page loads, natively it is copied to the clipboard and
+//! applied at startup with --snapshot <string>. Pressing F8 shares the current view.
+
+use crate::input::InputValue;
+use crate::render::{AspectPolicy, Renderer};
+use crate::ui::UiPlatform;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod linux;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+#[cfg(not(target_arch = "wasm32"))]
+use linux::{share, startup_string};
+#[cfg(target_arch = "wasm32")]
+use wasm::{share, startup_string};
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("invalid snapshot encoding: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("failed to parse snapshot: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The state needed to show the same view of a simulation again
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, InputValue>,
+    #[serde(default)]
+    pub camera: AspectPolicy,
+    pub seed: u64,
+    /// Simulation time in seconds, see Clock::time
+    #[serde(default)]
+    pub time: f64,
+}
+
+impl Snapshot {
+    /// The current view of renderer with the input values inputs
+    pub fn capture<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        inputs: &HashMap<String, InputValue>,
+    ) -> Self {
+        Self {
+            name: None,
+            inputs: inputs
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            camera: renderer.aspect_policy(),
+            seed: renderer.seed(),
+            time: renderer.clock.time(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Show the snapshot on renderer, overwriting the saved input values in inputs
+    /// Random streams created before the seed changes keep the old seed,
+    /// snapshots given at startup are applied before Simulation::new.
+    pub fn apply<P: UiPlatform>(
+        &self,
+        renderer: &mut Renderer<P>,
+        inputs: &mut HashMap<String, InputValue>,
+    ) {
+        renderer.seed = self.seed;
+        renderer.clock.seek(self.time);
+        renderer.set_aspect_policy(self.camera);
+        inputs.extend(
+            self.inputs
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+    }
+
+    /// The snapshot as an url-safe string
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self)
+            .expect("aftgraphs::snapshot::Snapshot::encode: snapshots always serialize");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, SnapshotError> {
+        let json = URL_SAFE_NO_PAD.decode(encoded.trim())?;
+        serde_json::from_slice(&json).map_err(Into::into)
+    }
+
+    /// Put the snapshot into the url fragment on WASM, or copy it to the clipboard natively
+    pub fn share(&self) {
+        share(&self.encode());
+    }
+
+    /// The snapshot given in the url fragment or with --snapshot
+    pub(crate) async fn startup() -> Option<Snapshot> {
+        let encoded = startup_string().await?;
+        match Snapshot::decode(&encoded) {
+            Ok(snapshot) => Some(snapshot),
+            Err(err) => {
+                log::error!("aftgraphs::snapshot::Snapshot::startup: {err}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::WorldRect;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = Snapshot {
+            name: Some("spiral".to_owned()),
+            inputs: BTreeMap::from([
+                ("speed".to_owned(), InputValue::SLIDER(0.25)),
+                ("trails".to_owned(), InputValue::CHECKBOX(true)),
+            ]),
+            camera: AspectPolicy::Letterbox(WorldRect::new([-2.0, -1.0], [2.0, 1.0])),
+            seed: 42,
+            time: 12.5,
+        };
+        let encoded = snapshot.encode();
+        assert!(encoded
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+        assert_eq!(snapshot, Snapshot::decode(&encoded).unwrap());
+        assert!(Snapshot::decode("TW=u").is_err());
+    }
+}
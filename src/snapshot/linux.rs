@@ -0,0 +1,16 @@
+use copypasta::{ClipboardContext, ClipboardProvider};
+
+/// The snapshot given with --snapshot
+pub(super) async fn startup_string() -> Option<String> {
+    crate::cli::ARGUMENTS.read().await.snapshot.clone()
+}
+
+pub(super) fn share(encoded: &str) {
+    log::info!("aftgraphs::snapshot::share: run with --snapshot {encoded} to restore this view");
+
+    let copied = ClipboardContext::new()
+        .and_then(|mut clipboard| clipboard.set_contents(encoded.to_owned()));
+    if let Err(e) = copied {
+        log::warn!("aftgraphs::snapshot::share: failed to copy the snapshot to the clipboard: {e}");
+    }
+}
@@ -0,0 +1,23 @@
+const FRAGMENT_KEY: &str = "snapshot=";
+
+/// The snapshot in the url fragment, #snapshot=<string>
+pub(super) async fn startup_string() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    hash.trim_start_matches('#')
+        .split('&')
+        .find_map(|part| part.strip_prefix(FRAGMENT_KEY))
+        .map(str::to_owned)
+}
+
+pub(super) fn share(encoded: &str) {
+    let Some(window) = web_sys::window() else {
+        log::warn!("aftgraphs::snapshot::share: no global `window` exists");
+        return;
+    };
+    if let Err(e) = window
+        .location()
+        .set_hash(&format!("{FRAGMENT_KEY}{encoded}"))
+    {
+        log::warn!("aftgraphs::snapshot::share: failed to set the url fragment: {e:?}");
+    }
+}
@@ -0,0 +1,239 @@
+mod gpu;
+pub use gpu::GpuSpatialHash;
+
+/// Spatial hash for neighbor queries on 2D points
+///
+/// Points are bucketed into square cells of side cell_size, and cells are hashed
+/// into a table sized to the number of points, so the structure works on unbounded
+/// domains. Rebuild it every frame from the current positions with SpatialHash::build.
+/// For best results cell_size should be about the largest query radius.
+/// Points kept on the GPU are hashed there with GpuSpatialHash.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialHash {
+    cell_size: f32,
+    positions: Vec<[f32; 2]>,
+    // Bucket i holds entries[cell_start[i]..cell_start[i + 1]]
+    cell_start: Vec<usize>,
+    entries: Vec<usize>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0,
+            "aftgraphs::spatial::SpatialHash::new: cell size must be positive"
+        );
+
+        Self {
+            cell_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Change the cell size, takes effect on the next build
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        assert!(
+            cell_size > 0.0,
+            "aftgraphs::spatial::SpatialHash::set_cell_size: cell size must be positive"
+        );
+        self.cell_size = cell_size;
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The positions the hash was last built from
+    pub fn positions(&self) -> &[[f32; 2]] {
+        &self.positions
+    }
+
+    /// Rebuild the hash from a set of positions
+    /// Indices returned by queries are indices into positions
+    pub fn build(&mut self, positions: impl IntoIterator<Item = [f32; 2]>) {
+        self.positions.clear();
+        self.positions.extend(positions);
+
+        let table_size = self.positions.len().next_power_of_two();
+        self.cell_start.clear();
+        self.cell_start.resize(table_size + 1, 0);
+
+        // Counting sort of the points by bucket
+        for &position in &self.positions {
+            let bucket = self.bucket(self.cell(position));
+            self.cell_start[bucket] += 1;
+        }
+
+        let mut start = 0;
+        for count in self.cell_start.iter_mut() {
+            start += *count;
+            *count = start;
+        }
+
+        self.entries.clear();
+        self.entries.resize(self.positions.len(), 0);
+        for (idx, &position) in self.positions.iter().enumerate() {
+            let bucket = self.bucket(self.cell(position));
+            self.cell_start[bucket] -= 1;
+            self.entries[self.cell_start[bucket]] = idx;
+        }
+    }
+
+    /// Cells saturate at the i32 range, NaN positions fall in cell (0, 0)
+    fn cell(&self, position: [f32; 2]) -> (i32, i32) {
+        (
+            (position[0] / self.cell_size).floor() as i32,
+            (position[1] / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// The same hash as spatial_bucket of res/spatial_hash.wgsl, the table size is a power of two
+    fn bucket(&self, cell: (i32, i32)) -> usize {
+        let table_size = self.cell_start.len() - 1;
+        let hash =
+            (cell.0 as u32).wrapping_mul(73_856_093) ^ (cell.1 as u32).wrapping_mul(19_349_663);
+        hash as usize & (table_size - 1)
+    }
+
+    /// Append the indices of all points within radius of point to out
+    pub fn query_into(&self, point: [f32; 2], radius: f32, out: &mut Vec<usize>) {
+        if self.positions.is_empty() {
+            return;
+        }
+
+        let min = self.cell([point[0] - radius, point[1] - radius]);
+        let max = self.cell([point[0] + radius, point[1] + radius]);
+        let radius_sq = radius * radius;
+
+        // Distinct cells can hash to the same bucket, only scan each bucket once
+        let table_size = self.cell_start.len() - 1;
+        let span = |min: i32, max: i32| (i64::from(max) - i64::from(min) + 1).max(0) as u64;
+        let cells = span(min.0, max.0).saturating_mul(span(min.1, max.1));
+        let buckets: Vec<usize> = if cells >= table_size as u64 {
+            (0..table_size).collect()
+        } else {
+            let mut buckets: Vec<usize> = (min.0..=max.0)
+                .flat_map(|x| (min.1..=max.1).map(move |y| (x, y)))
+                .map(|cell| self.bucket(cell))
+                .collect();
+            buckets.sort_unstable();
+            buckets.dedup();
+            buckets
+        };
+
+        for bucket in buckets {
+            let entries = &self.entries[self.cell_start[bucket]..self.cell_start[bucket + 1]];
+            out.extend(entries.iter().copied().filter(|&idx| {
+                let position = self.positions[idx];
+                (position[0] - point[0]).powi(2) + (position[1] - point[1]).powi(2) <= radius_sq
+            }));
+        }
+    }
+
+    /// Indices of all points within radius of point
+    pub fn query(&self, point: [f32; 2], radius: f32) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.query_into(point, radius, &mut out);
+        out
+    }
+
+    /// Indices of all points within radius of the point at index, excluding itself
+    pub fn neighbors(&self, index: usize, radius: f32) -> Vec<usize> {
+        let mut out = self.query(self.positions[index], radius);
+        out.retain(|&idx| idx != index);
+        out
+    }
+
+    /// All pairs (i, j) with i < j of points within radius of each other
+    pub fn pairs(&self, radius: f32) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        let mut neighbors = Vec::new();
+
+        for (i, &position) in self.positions.iter().enumerate() {
+            neighbors.clear();
+            self.query_into(position, radius, &mut neighbors);
+            pairs.extend(neighbors.iter().filter(|&&j| j > i).map(|&j| (i, j)));
+        }
+
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn brute_force(positions: &[[f32; 2]], point: [f32; 2], radius: f32) -> Vec<usize> {
+        positions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                (p[0] - point[0]).powi(2) + (p[1] - point[1]).powi(2) <= radius.powi(2)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    #[test]
+    fn empty() {
+        let mut hash = SpatialHash::new(0.1);
+        hash.build([]);
+
+        assert!(hash.query([0.0, 0.0], 1.0).is_empty());
+        assert!(hash.pairs(1.0).is_empty());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let positions: Vec<_> = (0..200)
+            .map(|i| {
+                let t = i as f32 * 0.37;
+                [t.sin() * 1.5, (t * 1.7).cos() - 0.25]
+            })
+            .collect();
+
+        let mut hash = SpatialHash::new(0.1);
+        hash.build(positions.iter().copied());
+
+        for &(point, radius) in &[([0.0, 0.0], 0.1), ([1.0, -0.5], 0.35), ([-1.4, 0.7], 0.05)] {
+            let mut result = hash.query(point, radius);
+            result.sort();
+            assert_eq!(brute_force(&positions, point, radius), result);
+        }
+    }
+
+    #[test]
+    fn pairs() {
+        let mut hash = SpatialHash::new(0.5);
+        hash.build([[0.0, 0.0], [0.3, 0.0], [5.0, 5.0], [5.0, 5.2], [-3.0, 1.0]]);
+
+        let mut pairs = hash.pairs(0.4);
+        pairs.sort();
+        assert_eq!(vec![(0, 1), (2, 3)], pairs);
+        assert_eq!(vec![0], hash.neighbors(1, 0.4));
+    }
+
+    #[test]
+    fn unbounded_queries_scan_each_bucket_once() {
+        let mut hash = SpatialHash::new(0.5);
+        hash.build([[0.0, 0.0], [0.3, 0.0], [5.0, 5.0], [-3.0e30, 1.0]]);
+
+        let mut result = hash.query([1.0, 1.0], f32::INFINITY);
+        result.sort();
+        assert_eq!(vec![0, 1, 2, 3], result);
+
+        result = hash.query([f32::NAN, 0.0], 1.0);
+        assert!(result.is_empty());
+        result = hash.query([0.0, 0.0], 1.0e18);
+        result.sort();
+        assert_eq!(vec![0, 1, 2], result);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::render::{
+    Allocation, BindGroupLayout, BindGroupLayoutBuilder, ComputePipeline, ComputePipelineBuilder,
+    Frame, Renderer, ResourceKind, BINDING_READ_ONLY_STORAGE_BUFFER, BINDING_STORAGE_BUFFER,
+    BINDING_UNIFORM_BUFFER,
+};
+use crate::storage::StorageBuffer;
+use crate::ui::UiPlatform;
+use std::num::NonZeroU64;
+
+/// Invocations per workgroup of res/spatial.wgsl
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Functions of the build and of the query header
+const HASH: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/res/spatial_hash.wgsl"
+));
+const BUILD: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/spatial.wgsl"));
+/// Declarations prepended by GpuSpatialHash::query_header, {group} is replaced by its group
+const QUERY_HEADER: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/res/spatial_query.wgsl"
+));
+
+/// Uniforms of res/spatial.wgsl and res/spatial_query.wgsl
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpatialParams {
+    cell_size: f32,
+    count: u32,
+    table_size: u32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Zeroable for SpatialParams {}
+unsafe impl bytemuck::Pod for SpatialParams {}
+
+/// The buffers of the hash, sized for a table of capacity buckets and as many points
+/// The bind groups keep the entries and offsets alive.
+struct Table {
+    capacity: u32,
+    cell_start: wgpu::Buffer,
+    build: wgpu::BindGroup,
+    query: wgpu::BindGroup,
+    _allocation: Allocation,
+}
+
+/// A SpatialHash built on the GPU, for points that never leave it
+///
+/// Record GpuSpatialHash::build in Simulation::encode with a storage buffer of positions,
+/// then bind GpuSpatialHash::bind_group to shaders declaring the hash with
+/// GpuSpatialHash::query_header. The buckets and cells are those of SpatialHash,
+/// only the order of the points within a bucket differs.
+pub struct GpuSpatialHash {
+    cell_size: f32,
+    label: Option<String>,
+    count_pipeline: ComputePipeline,
+    scan_pipeline: ComputePipeline,
+    scatter_pipeline: ComputePipeline,
+    build_layout: BindGroupLayout,
+    positions_layout: BindGroupLayout,
+    query_layout: BindGroupLayout,
+    params: wgpu::Buffer,
+    table: Table,
+    _allocation: Allocation,
+}
+
+impl GpuSpatialHash {
+    pub fn new<P: UiPlatform>(renderer: &Renderer<P>, cell_size: f32, label: Option<&str>) -> Self {
+        assert!(
+            cell_size > 0.0,
+            "aftgraphs::spatial::GpuSpatialHash::new: cell size must be positive"
+        );
+
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let entry = |binding, visibility, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty,
+            count: None,
+        };
+        let build_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::spatial::GpuSpatialHash::build"))
+            .with_entry(entry(0, compute, BINDING_UNIFORM_BUFFER))
+            .with_entry(entry(1, compute, BINDING_STORAGE_BUFFER))
+            .with_entry(entry(2, compute, BINDING_STORAGE_BUFFER))
+            .with_entry(entry(3, compute, BINDING_STORAGE_BUFFER))
+            .build(renderer);
+        let positions_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::spatial::GpuSpatialHash::positions"))
+            .with_entry(entry(0, compute, BINDING_READ_ONLY_STORAGE_BUFFER))
+            .build(renderer);
+        let stages = wgpu::ShaderStages::VERTEX_FRAGMENT | compute;
+        let query_layout = BindGroupLayoutBuilder::new()
+            .with_label(Some("aftgraphs::spatial::GpuSpatialHash::query"))
+            .with_entry(entry(0, stages, BINDING_UNIFORM_BUFFER))
+            .with_entry(entry(1, stages, BINDING_READ_ONLY_STORAGE_BUFFER))
+            .with_entry(entry(2, stages, BINDING_READ_ONLY_STORAGE_BUFFER))
+            .build(renderer);
+
+        let pipeline = |entry_point| {
+            ComputePipelineBuilder::new()
+                .with_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("aftgraphs::spatial::GpuSpatialHash"),
+                    source: wgpu::ShaderSource::Wgsl(format!("{HASH}\n{BUILD}").into()),
+                })
+                .with_entry_point(entry_point)
+                .with_label(Some("aftgraphs::spatial::GpuSpatialHash::pipeline"))
+                .with_bind_group_layouts_iter([&*build_layout, &*positions_layout])
+                .build(renderer)
+        };
+        let count_pipeline = pipeline("count");
+        let scan_pipeline = pipeline("scan");
+        let scatter_pipeline = pipeline("scatter");
+
+        let size = std::mem::size_of::<SpatialParams>() as u64;
+        let params = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!(
+                "{}::params",
+                label.unwrap_or("aftgraphs::spatial::GpuSpatialHash")
+            )),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let table = Table::new(renderer, label, 1, &params, &build_layout, &query_layout);
+
+        Self {
+            cell_size,
+            label: label.map(str::to_owned),
+            count_pipeline,
+            scan_pipeline,
+            scatter_pipeline,
+            build_layout,
+            positions_layout,
+            query_layout,
+            params,
+            table,
+            _allocation: renderer.track_memory(ResourceKind::Buffer, label, size),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Change the cell size, takes effect on the next build
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        assert!(
+            cell_size > 0.0,
+            "aftgraphs::spatial::GpuSpatialHash::set_cell_size: cell size must be positive"
+        );
+        self.cell_size = cell_size;
+    }
+
+    /// The WGSL declarations of the hash bound at group, with the functions
+    /// of res/spatial_hash.wgsl. See res/spatial_query.wgsl for how to query it
+    pub fn query_header(group: u32) -> String {
+        format!(
+            "{HASH}\n{}",
+            QUERY_HEADER.replace("{group}", &group.to_string())
+        )
+    }
+
+    /// Get the bind group of the hash for the shaders querying it
+    /// Changes when the hash outgrows its buffers, so fetch it again after building
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.table.query
+    }
+
+    /// Get the bind group layout (useful for setting up pipelines)
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.query_layout
+    }
+
+    /// Rebuild the hash from the positions in a storage buffer
+    /// Indices of the entries are indices into positions
+    pub fn build<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        frame: &mut Frame<'_>,
+        positions: &StorageBuffer<[f32; 2]>,
+    ) {
+        let count = positions.len() as u32;
+        let table_size = count.next_power_of_two();
+        if table_size > self.table.capacity {
+            self.table = Table::new(
+                renderer,
+                self.label.as_deref(),
+                table_size,
+                &self.params,
+                &self.build_layout,
+                &self.query_layout,
+            );
+        }
+
+        let params = SpatialParams {
+            cell_size: self.cell_size,
+            count,
+            table_size,
+            _padding: 0,
+        };
+        renderer
+            .queue
+            .write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+        renderer.record_upload(std::mem::size_of::<SpatialParams>());
+
+        frame
+            .encoder()
+            .clear_buffer(&self.table.cell_start, 0, None);
+        if count == 0 {
+            return;
+        }
+
+        let source = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("aftgraphs::spatial::GpuSpatialHash::positions"),
+                layout: &self.positions_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: positions.buffer(),
+                        offset: 0,
+                        size: NonZeroU64::new(count as u64 * 8),
+                    }),
+                }],
+            });
+
+        let mut pass = frame.begin_compute_pass(Some(
+            self.label
+                .as_deref()
+                .unwrap_or("aftgraphs::spatial::GpuSpatialHash"),
+        ));
+        pass.set_pipeline(&self.count_pipeline);
+        pass.bind(0, &self.table.build);
+        pass.bind(1, &source);
+        pass.dispatch_items([count, 1, 1], [WORKGROUP_SIZE, 1, 1]);
+        pass.set_pipeline(&self.scan_pipeline);
+        pass.dispatch([1, 1, 1]);
+        pass.set_pipeline(&self.scatter_pipeline);
+        pass.dispatch_items([count, 1, 1], [WORKGROUP_SIZE, 1, 1]);
+    }
+}
+
+impl Table {
+    fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        label: Option<&str>,
+        capacity: u32,
+        params: &wgpu::Buffer,
+        build_layout: &wgpu::BindGroupLayout,
+        query_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let buffer = |suffix: &str, len: u32, usage| {
+            renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!(
+                    "{}::{suffix}",
+                    label.unwrap_or("aftgraphs::spatial::GpuSpatialHash")
+                )),
+                size: len as u64 * 4,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let storage = wgpu::BufferUsages::STORAGE;
+        let cell_start = buffer(
+            "cell_start",
+            capacity + 1,
+            storage | wgpu::BufferUsages::COPY_DST,
+        );
+        let entries = buffer("entries", capacity, storage);
+        let offsets = buffer("offsets", capacity, storage);
+
+        let bind_group = |label, layout, buffers: &[&wgpu::Buffer]| {
+            let entries: Vec<_> = std::iter::once(params)
+                .chain(buffers.iter().copied())
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            renderer
+                .device
+                .create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout,
+                    entries: &entries,
+                })
+        };
+        let build = bind_group(
+            "aftgraphs::spatial::GpuSpatialHash::build",
+            build_layout,
+            &[&cell_start, &entries, &offsets],
+        );
+        let query = bind_group(
+            "aftgraphs::spatial::GpuSpatialHash::query",
+            query_layout,
+            &[&cell_start, &entries],
+        );
+
+        let bytes = (3 * capacity as u64 + 1) * 4;
+        Self {
+            capacity,
+            cell_start,
+            build,
+            query,
+            _allocation: renderer.track_memory(ResourceKind::Buffer, label, bytes),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{
+    render::{Allocation, Renderer, ResourceKind, ViewParams},
+    ui::UiPlatform,
+    uniform::Mat4,
+};
+use std::num::NonZeroU32;
+
+/// Eyes of a stereo view, as array layers and @builtin(view_index)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    Left = 0,
+    Right = 1,
+}
+
+impl Eye {
+    pub const ALL: [Eye; 2] = [Eye::Left, Eye::Right];
+}
+
+/// A head with two eyes ipd apart, looking down -Z at zero yaw and pitch
+/// Distances are in world units, angles in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoCamera {
+    /// Point between the eyes
+    pub position: [f32; 3],
+    /// Rotation about +Y, positive turns right
+    pub yaw: f32,
+    /// Rotation about the right axis, positive looks up
+    pub pitch: f32,
+    /// Interpupillary distance
+    pub ipd: f32,
+    /// Vertical field of view of each eye
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for StereoCamera {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+            ipd: 0.064,
+            fov_y: std::f32::consts::FRAC_PI_2,
+            near: 0.01,
+            far: 100.0,
+        }
+    }
+}
+
+/// Per-eye matrices for a uniform buffer, indexed by @builtin(view_index)
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct StereoParams {
+    pub view_projection: [Mat4; 2],
+    /// xyz is the eye's world position, w is 1
+    pub eye_position: [[f32; 4]; 2],
+}
+
+unsafe impl bytemuck::Zeroable for StereoParams {}
+unsafe impl bytemuck::Pod for StereoParams {}
+
+impl StereoCamera {
+    fn forward(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [cos_pitch * sin_yaw, sin_pitch, -cos_pitch * cos_yaw]
+    }
+
+    fn right(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        [cos_yaw, 0.0, sin_yaw]
+    }
+
+    pub fn eye_position(&self, eye: Eye) -> [f32; 3] {
+        let offset = match eye {
+            Eye::Left => -self.ipd / 2.0,
+            Eye::Right => self.ipd / 2.0,
+        };
+        let right = self.right();
+        std::array::from_fn(|idx| self.position[idx] + right[idx] * offset)
+    }
+
+    /// World to eye space of eye
+    pub fn view(&self, eye: Eye) -> Mat4 {
+        let forward = self.forward();
+        let right = self.right();
+        let up = cross(right, forward);
+        let eye = self.eye_position(eye);
+
+        Mat4([
+            [right[0], up[0], -forward[0], 0.0],
+            [right[1], up[1], -forward[1], 0.0],
+            [right[2], up[2], -forward[2], 0.0],
+            [-dot(right, eye), -dot(up, eye), dot(forward, eye), 1.0],
+        ])
+    }
+
+    /// Perspective projection of each eye onto a target of aspect ratio width / height, depth in [0, 1]
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let focal = 1.0 / (self.fov_y / 2.0).tan();
+        let depth = self.near - self.far;
+        Mat4([
+            [focal / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, focal, 0.0, 0.0],
+            [0.0, 0.0, self.far / depth, -1.0],
+            [0.0, 0.0, self.near * self.far / depth, 0.0],
+        ])
+    }
+
+    pub fn view_projection(&self, eye: Eye, aspect_ratio: f32) -> Mat4 {
+        mul(self.projection(aspect_ratio), self.view(eye))
+    }
+
+    /// Both eyes for Renderer::set_views, indexed by @builtin(view_index)
+    pub fn views(&self, aspect_ratio: f32) -> [ViewParams; 2] {
+        let projection = self.projection(aspect_ratio);
+        Eye::ALL.map(|eye| ViewParams::new(self.view(eye), projection, self.eye_position(eye)))
+    }
+
+    pub fn params(&self, aspect_ratio: f32) -> StereoParams {
+        StereoParams {
+            view_projection: Eye::ALL.map(|eye| self.view_projection(eye, aspect_ratio)),
+            eye_position: Eye::ALL.map(|eye| {
+                let [x, y, z] = self.eye_position(eye);
+                [x, y, z, 1.0]
+            }),
+        }
+    }
+}
+
+pub(crate) fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn mul(a: Mat4, b: Mat4) -> Mat4 {
+    Mat4(std::array::from_fn(|col| {
+        std::array::from_fn(|row| (0..4).map(|k| a[k][row] * b[col][k]).sum())
+    }))
+}
+
+/// A two layer array texture both eyes are drawn to in one multiview render pass
+/// Build pipelines drawing to it with RenderPipelineBuilder::with_multiview(StereoTarget::multiview()),
+/// their shaders can read each eye's matrices from StereoCamera::views through ShaderBuilder::with_views.
+/// Each eye's layer can then be sampled, copied or handed to a headset compositor.
+/// Presenting to a headset, e.g. through OpenXR, is left to the application: it needs the
+/// compositor's swapchain images shared with wgpu's device, which wgpu does not expose.
+pub struct StereoTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    eye_views: [wgpu::TextureView; 2],
+    size: (u32, u32),
+    _allocation: Allocation,
+}
+
+impl StereoTarget {
+    /// Create eye layers of size, None if the device does not support multiview
+    pub fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> Option<Self> {
+        if !renderer.supports_multiview() {
+            log::warn!("aftgraphs::stereo::StereoTarget::new: Device does not support multiview");
+            return None;
+        }
+
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("aftgraphs::stereo::StereoTarget"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: Eye::ALL.len() as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let bytes = format
+            .block_copy_size(None)
+            .map_or(0, |block| block as u64 * width as u64 * height as u64 * 2);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("aftgraphs::stereo::StereoTarget"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let eye_views = Eye::ALL.map(|eye| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("aftgraphs::stereo::StereoTarget eye"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: eye as u32,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        });
+
+        Some(Self {
+            texture,
+            view,
+            eye_views,
+            size: (width, height),
+            _allocation: renderer.track_memory(
+                ResourceKind::Texture,
+                Some("aftgraphs::stereo::StereoTarget"),
+                bytes,
+            ),
+        })
+    }
+
+    /// The multiview option of pipelines drawing to the target
+    pub fn multiview() -> Option<NonZeroU32> {
+        NonZeroU32::new(Eye::ALL.len() as u32)
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// View of both layers, the color attachment of a multiview render pass
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// View of the layer of one eye
+    pub fn eye_view(&self, eye: Eye) -> &wgpu::TextureView {
+        &self.eye_views[eye as usize]
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.size.0 as f32 / self.size.1 as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transform(matrix: Mat4, point: [f32; 3]) -> [f32; 4] {
+        std::array::from_fn(|row| {
+            matrix[0][row] * point[0]
+                + matrix[1][row] * point[1]
+                + matrix[2][row] * point[2]
+                + matrix[3][row]
+        })
+    }
+
+    #[test]
+    fn eyes_center_points_straight_ahead() {
+        let camera = StereoCamera::default();
+        for eye in Eye::ALL {
+            let [x, y, _] = camera.eye_position(eye);
+            let clip = transform(camera.view_projection(eye, 1.0), [x, y, -1.0]);
+            assert!((clip[0] / clip[3]).abs() < 1e-6);
+            assert!((clip[1] / clip[3]).abs() < 1e-6);
+            assert!((0.0..=1.0).contains(&(clip[2] / clip[3])));
+        }
+
+        let left = camera.eye_position(Eye::Left);
+        let right = camera.eye_position(Eye::Right);
+        assert!((right[0] - left[0] - camera.ipd).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::render::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use bytemuck::NoUninit;
+use std::num::NonZeroU64;
+use std::ops::{Deref, DerefMut};
+use wgpu::RenderPass;
+
+mod builder;
+pub use builder::StorageBufferBuilder;
+
+/// An array of T bound as a storage buffer, for data too large for a Uniform
+/// The binding covers exactly the elements, so arrayLength in a shader is the length.
+/// Bindings can not be empty, so empty data still binds one element and arrayLength is 1,
+/// pass the length separately when the buffer may be empty.
+pub struct StorageBuffer<T: NoUninit> {
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    data: Vec<T>,
+    /// Elements the buffer has room for, it only grows
+    capacity: usize,
+    /// Elements the bind group covers
+    bound_length: usize,
+    usage: wgpu::BufferUsages,
+    /// Shaders may write to the buffer, so its contents are copied when it grows
+    read_write: bool,
+    label: Option<String>,
+    allocation: Allocation,
+}
+
+pub struct StorageBufferGuard<'a, 'b, T: NoUninit, P: UiPlatform> {
+    storage: &'a mut StorageBuffer<T>,
+    renderer: &'a Renderer<'b, P>,
+    changed: bool,
+}
+
+/// Bytes of len elements, padded to the copy alignment
+/// At least one element, as a binding of empty data would be zero sized.
+fn binding_size<T>(len: usize) -> u64 {
+    let bytes = (len.max(1) * std::mem::size_of::<T>()).max(4) as u64;
+    bytes.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+}
+
+/// Bind group covering the first len elements of buffer
+fn create_bind_group<T>(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    len: usize,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label,
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset: 0,
+                size: NonZeroU64::new(binding_size::<T>(len)),
+            }),
+        }],
+    })
+}
+
+impl<T: NoUninit> StorageBuffer<T> {
+    /// Create a guard to modify the elements
+    /// When the guard drops, it will buffer the data to the GPU, growing the buffer if needed
+    pub fn modify<'a, 'b, P: UiPlatform>(
+        &'a mut self,
+        renderer: &'a Renderer<'b, P>,
+    ) -> StorageBufferGuard<'a, 'b, T, P> {
+        StorageBufferGuard {
+            storage: self,
+            renderer,
+            changed: false,
+        }
+    }
+
+    /// Get the bind group (used for set_bind_group on a render or compute pass)
+    /// Changes whenever the length changes, so fetch it again after modifying the buffer
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Get the bind group layout (useful for setting up pipelines)
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, slot: u32) {
+        render_pass.set_bind_group(slot, self.bind_group(), &[]);
+    }
+
+    /// Write the elements to the GPU, reallocating the buffer if they outgrew it
+    /// A grown read-write buffer keeps what shaders wrote: the bound elements are copied
+    /// from the old buffer and only the new elements are written.
+    fn upload<P: UiPlatform>(&mut self, renderer: &Renderer<P>) {
+        let mut from = 0;
+        if self.data.len() > self.capacity {
+            self.capacity = self.data.len().next_power_of_two();
+            let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: self.label.as_deref(),
+                size: binding_size::<T>(self.capacity),
+                usage: self.usage,
+                mapped_at_creation: false,
+            });
+            if self.read_write && self.usage.contains(wgpu::BufferUsages::COPY_SRC) {
+                let mut encoder =
+                    renderer
+                        .device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("aftgraphs::storage::StorageBuffer::upload"),
+                        });
+                encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+                renderer.queue.submit(Some(encoder.finish()));
+                from = self.bound_length;
+            }
+            self.buffer = buffer;
+            self.allocation = renderer.track_memory(
+                ResourceKind::Buffer,
+                self.label.as_deref(),
+                self.buffer.size(),
+            );
+        } else if self.data.len() == self.bound_length {
+            self.write(renderer, 0);
+            return;
+        }
+
+        self.bind_group = create_bind_group::<T>(
+            &renderer.device,
+            self.label.as_deref(),
+            &self.bind_group_layout,
+            &self.buffer,
+            self.data.len(),
+        );
+        self.bound_length = self.data.len();
+        self.write(renderer, from);
+    }
+
+    /// Write the elements from index on, starting at the copy alignment before it
+    fn write<P: UiPlatform>(&self, renderer: &Renderer<P>, from: usize) {
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+        let start = (from * std::mem::size_of::<T>()).min(bytes.len());
+        let start = start - start % alignment;
+        let aligned = bytes.len() - bytes.len() % alignment;
+        if start < aligned {
+            renderer
+                .queue
+                .write_buffer(&self.buffer, start as u64, &bytes[start..aligned]);
+        }
+        if aligned < bytes.len() {
+            let mut tail = [0; wgpu::COPY_BUFFER_ALIGNMENT as usize];
+            tail[..bytes.len() - aligned].copy_from_slice(&bytes[aligned..]);
+            renderer
+                .queue
+                .write_buffer(&self.buffer, aligned as u64, &tail);
+        }
+        renderer.record_upload(bytes.len() - start);
+    }
+}
+
+impl<T: NoUninit> AsRef<[T]> for StorageBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T: NoUninit> Deref for StorageBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl<T: NoUninit, P: UiPlatform> AsRef<[T]> for StorageBufferGuard<'_, '_, T, P> {
+    fn as_ref(&self) -> &[T] {
+        self.storage.as_ref()
+    }
+}
+
+impl<T: NoUninit, P: UiPlatform> Deref for StorageBufferGuard<'_, '_, T, P> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage.data
+    }
+}
+
+/// Using this will make the data be sent to the GPU on drop
+impl<T: NoUninit, P: UiPlatform> AsMut<[T]> for StorageBufferGuard<'_, '_, T, P> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.changed = true;
+        self.storage.data.as_mut_slice()
+    }
+}
+
+/// Using this will make the data be sent to the GPU on drop
+impl<T: NoUninit, P: UiPlatform> DerefMut for StorageBufferGuard<'_, '_, T, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.changed = true;
+        &mut self.storage.data
+    }
+}
+
+/// Buffers data to GPU if changed
+impl<T: NoUninit, P: UiPlatform> Drop for StorageBufferGuard<'_, '_, T, P> {
+    fn drop(&mut self) {
+        if self.changed {
+            self.storage.upload(self.renderer);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use super::{binding_size, create_bind_group, StorageBuffer};
+use crate::{
+    render::{
+        BindGroupLayoutBuilder, Renderer, ResourceKind, BINDING_READ_ONLY_STORAGE_BUFFER,
+        BINDING_STORAGE_BUFFER,
+    },
+    ui::UiPlatform,
+};
+use bytemuck::NoUninit;
+
+pub struct StorageBufferBuilder<'a, T: NoUninit> {
+    bind_group_layout: Option<wgpu::BindGroupLayout>,
+    usage: wgpu::BufferUsages,
+    visibility: Option<wgpu::ShaderStages>,
+    read_only: bool,
+    capacity: usize,
+    label: Option<&'a str>,
+    data: Vec<T>,
+}
+
+impl<T: NoUninit> Default for StorageBufferBuilder<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: NoUninit> StorageBufferBuilder<'a, T> {
+    pub fn new() -> Self {
+        Self {
+            bind_group_layout: None,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            visibility: None,
+            read_only: true,
+            capacity: 0,
+            label: None,
+            data: vec![],
+        }
+    }
+
+    /// Creates the StorageBuffer
+    /// This includes calls to the GPU
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> StorageBuffer<T> {
+        let Self {
+            bind_group_layout,
+            usage,
+            visibility,
+            read_only,
+            capacity,
+            label,
+            data,
+        } = self;
+
+        let bind_group_layout = bind_group_layout.unwrap_or_else(|| {
+            // Vertex shaders can not write to storage buffers
+            let (ty, stages) = if read_only {
+                (
+                    BINDING_READ_ONLY_STORAGE_BUFFER,
+                    wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                )
+            } else {
+                (
+                    BINDING_STORAGE_BUFFER,
+                    wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                )
+            };
+            BindGroupLayoutBuilder::new()
+                .with_label(label)
+                .with_entry(wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: visibility.unwrap_or(stages),
+                    ty,
+                    count: None,
+                })
+                .build(renderer)
+                .into()
+        });
+
+        let capacity = capacity.max(data.len()).max(1);
+        let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: binding_size::<T>(capacity),
+            usage,
+            mapped_at_creation: false,
+        });
+
+        let storage = StorageBuffer {
+            allocation: renderer.track_memory(ResourceKind::Buffer, label, buffer.size()),
+            bind_group: create_bind_group::<T>(
+                &renderer.device,
+                label,
+                &bind_group_layout,
+                &buffer,
+                data.len(),
+            ),
+            bound_length: data.len(),
+            buffer,
+            bind_group_layout,
+            data,
+            capacity,
+            usage,
+            read_write: !read_only,
+            label: label.map(String::from),
+        };
+        storage.write(renderer, 0);
+        storage
+    }
+
+    /// Add a label to the storage buffer
+    /// The label will be applied to the bind group layout, the buffer, and the bind group
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Sets the initial elements of the buffer.
+    /// Will override any previously set elements.
+    pub fn with_data(mut self, data: Vec<T>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Sets the initial elements of the buffer.
+    /// Will override any previously set elements.
+    pub fn with_initial_data(mut self, data: &[T]) -> Self {
+        self.data.clear();
+        self.data.extend_from_slice(data);
+        self
+    }
+
+    /// Reserve room for this many elements, so the buffer does not grow until it is exceeded
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Bind the buffer read-write instead of read-only, for compute or fragment shaders
+    /// writing to it. Read-write storage is not visible to vertex shaders.
+    /// When a read-write buffer grows, the elements shaders wrote are kept and only the
+    /// new elements are written. Set this with a read-write bind group layout as well.
+    pub fn with_read_write(mut self) -> Self {
+        self.read_only = false;
+        self
+    }
+
+    /// Sets the stages the buffer is visible to
+    /// Defaults to every stage that may access the buffer, ignored if a bind group layout is given
+    pub fn with_visibility(mut self, visibility: wgpu::ShaderStages) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    /// Bind the buffer with layout instead of one made from the access and visibility
+    /// see aftgraphs::Renderer::BindGroupLayoutBuilder
+    pub fn with_bind_group_layout(mut self, layout: impl Into<wgpu::BindGroupLayout>) -> Self {
+        self.bind_group_layout = Some(layout.into());
+        self
+    }
+
+    /// Sets the usage for the storage buffer, which must include STORAGE and COPY_DST
+    /// Without COPY_SRC, a read-write buffer is rewritten from the elements when it grows
+    /// Defaults to wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC
+    pub fn with_buffer_usage(mut self, usage: wgpu::BufferUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+}
@@ -0,0 +1,149 @@
+use crate::simulation::InputEvent;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+use thiserror::Error;
+use winit::event_loop::EventLoopProxy;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod linux;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+mod websocket;
+
+/// Where display mode delivers data messages, set by SimulationContext::run_display
+static PROXY: Mutex<Option<EventLoopProxy<InputEvent>>> = Mutex::new(None);
+
+/// Messages received without an event loop, drained by Embedded::frame
+/// None until collect_pending, headless renders drop live messages to stay reproducible.
+static PENDING: Mutex<Option<Vec<InputEvent>>> = Mutex::new(None);
+
+/// A live message was dropped, so the warning is only logged once
+static DROPPED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Error, Debug)]
+pub enum DataStreamError {
+    #[error("failed to connect data stream: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("websocket handshake failed: {0}")]
+    Handshake(String),
+    #[error("unsupported data stream url {0}, expected ws://")]
+    UnsupportedUrl(String),
+}
+
+/// Where a DataStream receives messages from
+/// Every message is one JSON value: a WebSocket text or binary message,
+/// a line of a TCP stream or a UDP datagram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataSource {
+    /// A ws:// url, wss:// is only supported on WASM
+    WebSocket(String),
+    /// host:port to connect to
+    #[cfg(not(target_arch = "wasm32"))]
+    Tcp(String),
+    /// host:port to listen on
+    #[cfg(not(target_arch = "wasm32"))]
+    Udp(String),
+}
+
+/// A live connection delivering each message to Simulation::on_input as InputEvent::Data
+/// The connection closes when the DataStream drops.
+/// Headless renders drop live messages, record them as DATA events of the headless input
+/// to render them reproducibly.
+pub struct DataStream {
+    name: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    connection: linux::Connection,
+    #[cfg(target_arch = "wasm32")]
+    connection: wasm::Connection,
+}
+
+impl DataStream {
+    /// Connect to source, name is passed along with every message
+    pub fn connect(name: &str, source: DataSource) -> Result<Self, DataStreamError> {
+        log::info!("aftgraphs::stream::DataStream::connect: Connecting {name} to {source:?}");
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let connection = linux::Connection::open(name.to_owned(), source);
+        #[cfg(target_arch = "wasm32")]
+        let connection = wasm::Connection::open(name.to_owned(), source);
+
+        let connection = connection.inspect_err(|err| {
+            log::error!("aftgraphs::stream::DataStream::connect: {name}: {err}");
+        })?;
+        Ok(Self {
+            name: name.to_owned(),
+            connection,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// If the connection is still open
+    pub fn is_open(&self) -> bool {
+        self.connection.is_open()
+    }
+}
+
+pub(crate) fn set_event_proxy(proxy: Option<EventLoopProxy<InputEvent>>) {
+    *PROXY
+        .lock()
+        .expect("aftgraphs::stream::set_event_proxy: poisoned lock") = proxy;
+}
+
+/// Keep messages received without an event loop for take_pending
+pub(crate) fn collect_pending() {
+    PENDING
+        .lock()
+        .expect("aftgraphs::stream::collect_pending: poisoned lock")
+        .get_or_insert_with(Vec::new);
+}
+
+/// Messages received since the last call, after collect_pending
+pub(crate) fn take_pending() -> Vec<InputEvent> {
+    PENDING
+        .lock()
+        .expect("aftgraphs::stream::take_pending: poisoned lock")
+        .as_mut()
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
+/// Parse message as JSON and deliver it to the simulation
+fn deliver(name: &str, message: &[u8]) {
+    let value = match serde_json::from_slice(message) {
+        Ok(value) => value,
+        Err(err) => {
+            log::warn!("aftgraphs::stream::deliver: {name}: skipping invalid message: {err}");
+            return;
+        }
+    };
+
+    let event = InputEvent::Data(name.to_owned(), value);
+    let proxy = PROXY
+        .lock()
+        .expect("aftgraphs::stream::deliver: poisoned lock");
+    match proxy.as_ref() {
+        Some(proxy) => {
+            if proxy.send_event(event).is_err() {
+                log::debug!("aftgraphs::stream::deliver: {name}: event loop closed");
+            }
+        }
+        None => match PENDING
+            .lock()
+            .expect("aftgraphs::stream::deliver: poisoned lock")
+            .as_mut()
+        {
+            Some(pending) => pending.push(event),
+            None => {
+                if !DROPPED.swap(true, Ordering::Relaxed) {
+                    log::warn!("aftgraphs::stream::deliver: {name}: Dropping live messages while rendering headless, replay them as DATA events");
+                }
+            }
+        },
+    }
+}
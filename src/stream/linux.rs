@@ -0,0 +1,154 @@
+use super::{deliver, websocket, DataSource, DataStreamError};
+use std::{
+    io::{BufRead, BufReader, ErrorKind},
+    net::{TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+use tungstenite::{Message, WebSocket};
+use web_time::Duration;
+
+/// How often a blocked reader checks if its DataStream was dropped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Largest UDP datagram
+const DATAGRAM_SIZE: usize = 65536;
+
+pub(super) struct Connection {
+    closed: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Connection {
+    pub(super) fn open(name: String, source: DataSource) -> Result<Self, DataStreamError> {
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader: Box<dyn FnOnce(&AtomicBool) + Send> = match source {
+            DataSource::WebSocket(url) => {
+                let socket = websocket::connect(&url)?;
+                socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+                Box::new(move |closed| read_websocket(&name, socket, closed))
+            }
+            DataSource::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                Box::new(move |closed| read_lines(&name, stream, closed))
+            }
+            DataSource::Udp(address) => {
+                let socket = UdpSocket::bind(address)?;
+                socket.set_read_timeout(Some(POLL_INTERVAL))?;
+                Box::new(move |closed| read_datagrams(&name, socket, closed))
+            }
+        };
+
+        let handle = {
+            let closed = closed.clone();
+            std::thread::spawn(move || {
+                reader(&closed);
+                closed.store(true, Ordering::Release);
+            })
+        };
+        Ok(Self {
+            closed,
+            handle: Some(handle),
+        })
+    }
+
+    pub(super) fn is_open(&self) -> bool {
+        !self.closed.load(Ordering::Acquire)
+            && self
+                .handle
+                .as_ref()
+                .is_some_and(|handle| !handle.is_finished())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // The reader notices within POLL_INTERVAL
+        self.closed.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!("aftgraphs::stream::linux::Connection::drop: The reader panicked");
+            }
+        }
+    }
+}
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+fn read_lines(name: &str, stream: TcpStream, closed: &AtomicBool) {
+    let mut reader = BufReader::new(stream);
+    let mut line = vec![];
+    while !closed.load(Ordering::Acquire) {
+        // Bytes read before a timeout stay in line
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.ends_with(b"\n") => {
+                if !line.trim_ascii().is_empty() {
+                    deliver(name, &line);
+                }
+                line.clear();
+            }
+            Ok(_) if line.len() > websocket::MAX_MESSAGE_SIZE => {
+                log::error!("aftgraphs::stream::linux::read_lines: {name}: line too long");
+                line.clear();
+                break;
+            }
+            Ok(_) => (),
+            Err(err) if is_timeout(&err) => (),
+            Err(err) => {
+                log::error!("aftgraphs::stream::linux::read_lines: {name}: {err}");
+                break;
+            }
+        }
+    }
+
+    if !line.trim_ascii().is_empty() {
+        deliver(name, &line);
+    }
+    log::info!("aftgraphs::stream::linux::read_lines: {name}: Connection closed");
+}
+
+fn read_datagrams(name: &str, socket: UdpSocket, closed: &AtomicBool) {
+    let mut buf = vec![0; DATAGRAM_SIZE];
+    while !closed.load(Ordering::Acquire) {
+        match socket.recv(&mut buf) {
+            Ok(len) => deliver(name, &buf[..len]),
+            Err(err) if is_timeout(&err) => (),
+            Err(err) => {
+                log::error!("aftgraphs::stream::linux::read_datagrams: {name}: {err}");
+                break;
+            }
+        }
+    }
+}
+
+fn read_websocket(name: &str, mut socket: WebSocket<TcpStream>, closed: &AtomicBool) {
+    while !closed.load(Ordering::Acquire) {
+        match socket.read() {
+            Ok(Message::Text(text)) => deliver(name, text.as_bytes()),
+            Ok(Message::Binary(data)) => deliver(name, &data),
+            // Pings are answered and closes acknowledged by the socket
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(err)) if is_timeout(&err) => (),
+            Err(tungstenite::Error::ConnectionClosed) => {
+                log::info!("aftgraphs::stream::linux::read_websocket: {name}: Connection closed");
+                return;
+            }
+            Err(err) => {
+                log::error!("aftgraphs::stream::linux::read_websocket: {name}: {err}");
+                return;
+            }
+        }
+    }
+
+    // Start the closing handshake, without waiting for the server's reply
+    if let Err(err) = socket.close(None).and_then(|()| socket.flush()) {
+        log::warn!("aftgraphs::stream::linux::read_websocket: {name}: {err}");
+    }
+}
@@ -0,0 +1,48 @@
+use super::{deliver, DataSource, DataStreamError};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+pub(super) struct Connection {
+    socket: WebSocket,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Connection {
+    pub(super) fn open(name: String, source: DataSource) -> Result<Self, DataStreamError> {
+        let DataSource::WebSocket(url) = source;
+        let socket = WebSocket::new(&url)
+            .map_err(|err| DataStreamError::Io(std::io::Error::other(format!("{err:?}"))))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let data = event.data();
+            if let Some(text) = data.as_string() {
+                deliver(&name, text.as_bytes());
+            } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+                deliver(&name, &js_sys::Uint8Array::new(&buffer).to_vec());
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            _on_message: on_message,
+        })
+    }
+
+    pub(super) fn is_open(&self) -> bool {
+        matches!(
+            self.socket.ready_state(),
+            WebSocket::CONNECTING | WebSocket::OPEN
+        )
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        if let Err(err) = self.socket.close() {
+            log::warn!("aftgraphs::stream::wasm::Connection::drop: {err:?}");
+        }
+    }
+}
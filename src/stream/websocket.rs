@@ -0,0 +1,67 @@
+use super::DataStreamError;
+use std::net::TcpStream;
+use tungstenite::{protocol::WebSocketConfig, WebSocket};
+
+/// Largest message accepted, larger ones close the connection
+pub(super) const MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+/// The address to connect to for a ws:// url
+fn address(url: &str) -> Result<String, DataStreamError> {
+    let rest = url
+        .strip_prefix("ws://")
+        .ok_or_else(|| DataStreamError::UnsupportedUrl(url.to_owned()))?;
+    let host = rest.split('/').next().unwrap_or_default();
+    if host.contains(':') {
+        Ok(host.to_owned())
+    } else {
+        Ok(format!("{host}:80"))
+    }
+}
+
+/// Connect to url and perform the opening handshake
+pub(super) fn connect(url: &str) -> Result<WebSocket<TcpStream>, DataStreamError> {
+    let stream = TcpStream::connect(address(url)?)?;
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..Default::default()
+    };
+    let (socket, _) = tungstenite::client::client_with_config(url, stream, Some(config))
+        .map_err(|err| DataStreamError::Handshake(err.to_string()))?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use tungstenite::Message;
+
+    #[test]
+    fn connect_and_read() {
+        assert_eq!("localhost:80", address("ws://localhost/data").unwrap());
+        assert_eq!("127.0.0.1:9001", address("ws://127.0.0.1:9001").unwrap());
+        assert!(address("wss://localhost/data").is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/data", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            socket.send(Message::text("{\"x\": 1}")).unwrap();
+            // Over the limit, fails once the client hangs up partway through
+            socket
+                .send(Message::binary(vec![b' '; MAX_MESSAGE_SIZE + 1]))
+                .ok();
+        });
+
+        let mut socket = connect(&url).unwrap();
+        assert_eq!(Message::text("{\"x\": 1}"), socket.read().unwrap());
+        assert!(matches!(
+            socket.read(),
+            Err(tungstenite::Error::Capacity(_))
+        ));
+        drop(socket);
+        server.join().unwrap();
+    }
+}
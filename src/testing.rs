@@ -0,0 +1,400 @@
+use crate::render::{Clock, RendererStats};
+use bytemuck::NoUninit;
+use std::{ops::Range, sync::Mutex};
+use web_time::Duration;
+
+/// Time step run_n_frames advances the simulation by
+pub const SMOKE_TEST_DELTA_T: f64 = 1.0 / 60.0;
+
+/// A call recorded by a MockRenderer
+/// GPU resources are identified by their labels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCommand {
+    WriteBuffer {
+        buffer: String,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    SetPipeline(String),
+    SetBindGroup {
+        index: u32,
+        bind_group: String,
+    },
+    SetVertexBuffer {
+        slot: u32,
+        buffer: String,
+    },
+    SetIndexBuffer(String),
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    DrawIndexed {
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>,
+    },
+}
+
+/// A stand-in for Renderer that records buffer writes, binds and draws without a GPU
+///
+/// Meant for unit testing Simulation logic in plain cargo test: move the logic
+/// that decides what to upload and draw into functions taking the values they need,
+/// feed their output to a MockRenderer, then assert on the recorded commands.
+#[derive(Debug)]
+pub struct MockRenderer {
+    pub aspect_ratio: f64,
+    pub clock: Clock,
+    size: [u32; 2],
+    commands: Mutex<Vec<MockCommand>>,
+}
+
+/// A stand-in for RenderPass that records into its MockRenderer
+pub struct MockRenderPass<'a> {
+    renderer: &'a MockRenderer,
+}
+
+impl MockRenderer {
+    pub fn new(size: (u32, u32)) -> Self {
+        Self {
+            aspect_ratio: size.0 as f64 / size.1.max(1) as f64,
+            clock: Clock::default(),
+            size: [size.0, size.1],
+            commands: Mutex::new(vec![]),
+        }
+    }
+
+    fn record(&self, command: MockCommand) {
+        self.commands
+            .lock()
+            .expect("aftgraphs::testing::MockRenderer::record: poisoned lock")
+            .push(command);
+    }
+
+    /// Size of the render target in pixels
+    pub fn viewport_size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Advance the time like the event loop does between frames
+    pub fn advance(&mut self, delta_time: f64) {
+        let wall_time = self.clock.wall_time() + delta_time;
+        self.clock
+            .tick(wall_time, Duration::from_secs_f64(delta_time));
+    }
+
+    /// Record a write of data to the buffer with the given label
+    pub fn write_buffer<T: NoUninit>(&self, buffer: &str, offset: u64, data: &[T]) {
+        self.record(MockCommand::WriteBuffer {
+            buffer: buffer.to_owned(),
+            offset,
+            data: bytemuck::cast_slice(data).to_vec(),
+        });
+    }
+
+    /// Start recording a render pass
+    pub fn render_pass(&self) -> MockRenderPass<'_> {
+        MockRenderPass { renderer: self }
+    }
+
+    /// All commands recorded so far, in order
+    pub fn commands(&self) -> Vec<MockCommand> {
+        self.commands
+            .lock()
+            .expect("aftgraphs::testing::MockRenderer::commands: poisoned lock")
+            .clone()
+    }
+
+    /// Remove and return the commands recorded so far, such as at the end of a frame
+    pub fn take_commands(&self) -> Vec<MockCommand> {
+        std::mem::take(
+            &mut *self
+                .commands
+                .lock()
+                .expect("aftgraphs::testing::MockRenderer::take_commands: poisoned lock"),
+        )
+    }
+
+    /// The Draw and DrawIndexed commands recorded so far
+    pub fn draws(&self) -> Vec<MockCommand> {
+        self.commands()
+            .into_iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    MockCommand::Draw { .. } | MockCommand::DrawIndexed { .. }
+                )
+            })
+            .collect()
+    }
+
+    /// Contents of the buffer with the given label after applying every recorded write
+    /// None if the buffer was never written
+    pub fn buffer_contents(&self, buffer: &str) -> Option<Vec<u8>> {
+        let mut contents: Option<Vec<u8>> = None;
+
+        for command in self.commands() {
+            let MockCommand::WriteBuffer {
+                buffer: ref written,
+                offset,
+                ref data,
+            } = command
+            else {
+                continue;
+            };
+            if written != buffer {
+                continue;
+            }
+
+            let contents = contents.get_or_insert_with(Vec::new);
+            let start = offset as usize;
+            let end = start + data.len();
+            if contents.len() < end {
+                contents.resize(end, 0);
+            }
+            contents[start..end].copy_from_slice(data);
+        }
+
+        contents
+    }
+
+    /// Statistics of the commands recorded so far
+    pub fn stats(&self) -> RendererStats {
+        let mut stats = RendererStats::default();
+
+        for command in self.commands() {
+            match command {
+                MockCommand::WriteBuffer { data, .. } => {
+                    stats.buffer_uploads += 1;
+                    stats.bytes_uploaded += data.len() as u64;
+                }
+                MockCommand::SetPipeline(_) => stats.pipeline_switches += 1,
+                MockCommand::Draw {
+                    vertices,
+                    instances,
+                } => {
+                    stats.draw_calls += 1;
+                    stats.instances += instances.len() as u64;
+                    stats.vertices += (vertices.len() * instances.len()) as u64;
+                }
+                MockCommand::DrawIndexed {
+                    indices, instances, ..
+                } => {
+                    stats.draw_calls += 1;
+                    stats.instances += instances.len() as u64;
+                    stats.vertices += (indices.len() * instances.len()) as u64;
+                }
+                _ => (),
+            }
+        }
+
+        stats
+    }
+}
+
+impl MockRenderPass<'_> {
+    pub fn set_pipeline(&mut self, pipeline: &str) {
+        self.renderer
+            .record(MockCommand::SetPipeline(pipeline.to_owned()));
+    }
+
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &str) {
+        self.renderer.record(MockCommand::SetBindGroup {
+            index,
+            bind_group: bind_group.to_owned(),
+        });
+    }
+
+    pub fn set_vertex_buffer(&mut self, slot: u32, buffer: &str) {
+        self.renderer.record(MockCommand::SetVertexBuffer {
+            slot,
+            buffer: buffer.to_owned(),
+        });
+    }
+
+    pub fn set_index_buffer(&mut self, buffer: &str) {
+        self.renderer
+            .record(MockCommand::SetIndexBuffer(buffer.to_owned()));
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.renderer.record(MockCommand::Draw {
+            vertices,
+            instances,
+        });
+    }
+
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.renderer.record(MockCommand::DrawIndexed {
+            indices,
+            base_vertex,
+            instances,
+        });
+    }
+}
+
+/// Result of run_n_frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameRun {
+    pub width: u32,
+    pub height: u32,
+    /// Wall clock time of each frame, from render to readback
+    pub frame_times: Vec<Duration>,
+    /// Tightly packed RGBA8 pixels of the last frame, empty if no frames ran
+    pub last_frame: Vec<u8>,
+}
+
+impl FrameRun {
+    pub fn total_time(&self) -> Duration {
+        self.frame_times.iter().sum()
+    }
+
+    pub fn mean_frame_time(&self) -> Duration {
+        self.total_time() / self.frame_times.len().max(1) as u32
+    }
+
+    pub fn max_frame_time(&self) -> Duration {
+        self.frame_times.iter().copied().max().unwrap_or_default()
+    }
+
+    /// If every pixel of the last frame has the same color
+    /// A simulation that drew nothing leaves only the clear color.
+    pub fn is_blank(&self) -> bool {
+        let mut pixels = self.last_frame.chunks_exact(4);
+        let first = pixels.next();
+        pixels.all(|pixel| Some(pixel) == first)
+    }
+}
+
+/// Run a simulation headlessly for n frames and return the timings and the last frame
+///
+/// Meant for "it renders" tests in downstream simulation crates.
+/// The fallback adapter is preferred so the result does not depend on the GPU,
+/// any other adapter is used if the platform has no fallback adapter.
+/// Frames are rendered at times 0, SMOKE_TEST_DELTA_T, ... without inputs.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn run_n_frames<T: crate::simulation::Simulation>(
+    n: usize,
+    size: (u32, u32),
+) -> Result<FrameRun, crate::simulation::SimulationRunError> {
+    use crate::{headless, GraphicsInitError};
+    use async_std::sync::Mutex;
+    use std::{collections::HashMap, sync::Arc};
+    use web_time::Instant;
+
+    let mut renderer = match headless::init_fallback(size).await {
+        Ok(renderer) => renderer,
+        Err(GraphicsInitError::NoAdapter) => {
+            log::warn!(
+                "aftgraphs::testing::run_n_frames: No fallback adapter, using the default adapter"
+            );
+            headless::init(size).await?
+        }
+        Err(e) => return Err(e.into()),
+    };
+    log::debug!(
+        "aftgraphs::testing::run_n_frames: Running {n} frames on {:?}",
+        renderer.adapter.get_info().name
+    );
+
+    let simulation = Arc::new(Mutex::new(
+        crate::simulation::create::<T, _>(&mut renderer, None).await,
+    ));
+    let (width, height) = (size.0.max(1), size.1.max(1));
+    let mut inputs = HashMap::new();
+    let mut img = vec![];
+    let mut frame_times = Vec::with_capacity(n);
+
+    for frame in 0..n {
+        renderer.update_clock(
+            frame as f64 * SMOKE_TEST_DELTA_T,
+            Duration::from_secs_f64(SMOKE_TEST_DELTA_T),
+        );
+
+        let start = Instant::now();
+        renderer.render(simulation.clone(), &mut inputs).await;
+        renderer.render_headless_finish(&mut img).await?;
+        frame_times.push(start.elapsed());
+    }
+
+    let last_frame = if n == 0 {
+        vec![]
+    } else {
+        crate::compare::unpad(width, height, &img)
+    };
+
+    Ok(FrameRun {
+        width,
+        height,
+        frame_times,
+        last_frame,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_pass() {
+        let renderer = MockRenderer::new((800, 600));
+        renderer.write_buffer("instances", 0, &[1.0f32, 2.0]);
+
+        {
+            let mut pass = renderer.render_pass();
+            pass.set_pipeline("pipeline");
+            pass.set_bind_group(0, "uniforms");
+            pass.set_vertex_buffer(0, "instances");
+            pass.draw(0..3, 0..2);
+        }
+
+        assert_eq!(
+            vec![MockCommand::Draw {
+                vertices: 0..3,
+                instances: 0..2
+            }],
+            renderer.draws()
+        );
+
+        let stats = renderer.stats();
+        assert_eq!(1, stats.draw_calls);
+        assert_eq!(6, stats.vertices);
+        assert_eq!(1, stats.pipeline_switches);
+        assert_eq!(8, stats.bytes_uploaded);
+
+        assert_eq!(5, renderer.take_commands().len());
+        assert!(renderer.commands().is_empty());
+    }
+
+    #[test]
+    fn buffer_contents() {
+        let renderer = MockRenderer::new((1, 1));
+        assert_eq!(None, renderer.buffer_contents("buffer"));
+
+        renderer.write_buffer("buffer", 0, &[1u8, 2, 3, 4]);
+        renderer.write_buffer("other", 0, &[9u8]);
+        renderer.write_buffer("buffer", 2, &[5u8, 6, 7]);
+
+        assert_eq!(
+            Some(vec![1, 2, 5, 6, 7]),
+            renderer.buffer_contents("buffer")
+        );
+    }
+
+    #[test]
+    fn frame_run() {
+        let mut run = FrameRun {
+            width: 2,
+            height: 1,
+            frame_times: vec![Duration::from_millis(2), Duration::from_millis(4)],
+            last_frame: vec![0, 0, 0, 255, 0, 0, 0, 255],
+        };
+        assert_eq!(Duration::from_millis(6), run.total_time());
+        assert_eq!(Duration::from_millis(3), run.mean_frame_time());
+        assert_eq!(Duration::from_millis(4), run.max_frame_time());
+        assert!(run.is_blank());
+
+        run.last_frame[4] = 255;
+        assert!(!run.is_blank());
+    }
+}
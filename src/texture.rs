@@ -0,0 +1,269 @@
+use crate::asset::UploadContext;
+use crate::render::{Allocation, Renderer};
+use crate::ui::UiPlatform;
+use std::ops::Deref;
+use wgpu::RenderPass;
+
+mod builder;
+mod target;
+#[cfg(any(feature = "ffmpeg", target_arch = "wasm32"))]
+mod video;
+pub use builder::TextureBuilder;
+pub use target::RenderTarget;
+#[cfg(any(feature = "ffmpeg", target_arch = "wasm32"))]
+pub use video::{VideoError, VideoTexture};
+
+/// A wgpu::Sampler along with the binding type it needs in a bind group layout
+pub struct Sampler {
+    sampler: wgpu::Sampler,
+    binding_type: wgpu::SamplerBindingType,
+}
+
+/// A 2D texture and its sampler, bound together in a bind group.
+/// The texture is at binding 0 and the sampler at binding 1, in WGSL:
+///
+/// ```wgsl
+/// @group(0) @binding(0) var image: texture_2d<f32>;
+/// @group(0) @binding(1) var image_sampler: sampler;
+/// ```
+pub struct Texture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    _allocation: Allocation,
+}
+
+/// The binding type a sampler created from desc needs
+fn sampler_binding_type(desc: &wgpu::SamplerDescriptor) -> wgpu::SamplerBindingType {
+    if desc.compare.is_some() {
+        wgpu::SamplerBindingType::Comparison
+    } else if [desc.mag_filter, desc.min_filter, desc.mipmap_filter]
+        .contains(&wgpu::FilterMode::Linear)
+    {
+        wgpu::SamplerBindingType::Filtering
+    } else {
+        wgpu::SamplerBindingType::NonFiltering
+    }
+}
+
+/// Bytes in a size[0] x size[1] region of a texture of format, and the bytes in each of its rows
+fn region_bytes(format: wgpu::TextureFormat, size: [u32; 2]) -> (usize, u32) {
+    let bytes_per_row = format.block_copy_size(None).unwrap_or(4) * size[0];
+    (bytes_per_row as usize * size[1] as usize, bytes_per_row)
+}
+
+impl Sampler {
+    pub fn new<P: UiPlatform>(renderer: &Renderer<P>, desc: &wgpu::SamplerDescriptor) -> Self {
+        Self::with_device(&renderer.device, desc)
+    }
+
+    fn with_device(device: &wgpu::Device, desc: &wgpu::SamplerDescriptor) -> Self {
+        Self {
+            sampler: device.create_sampler(desc),
+            binding_type: sampler_binding_type(desc),
+        }
+    }
+
+    /// Bilinear filtering, for smooth images
+    pub fn linear<P: UiPlatform>(renderer: &Renderer<P>, address_mode: wgpu::AddressMode) -> Self {
+        Self::with_filter(&renderer.device, wgpu::FilterMode::Linear, address_mode)
+    }
+
+    /// Nearest neighbor filtering, for pixel art and textures that can not be filtered
+    pub fn nearest<P: UiPlatform>(renderer: &Renderer<P>, address_mode: wgpu::AddressMode) -> Self {
+        Self::with_filter(&renderer.device, wgpu::FilterMode::Nearest, address_mode)
+    }
+
+    pub(crate) fn with_filter(
+        device: &wgpu::Device,
+        filter: wgpu::FilterMode,
+        address_mode: wgpu::AddressMode,
+    ) -> Self {
+        Self::with_device(
+            device,
+            &wgpu::SamplerDescriptor {
+                label: Some("aftgraphs::texture::Sampler"),
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn binding_type(&self) -> wgpu::SamplerBindingType {
+        self.binding_type
+    }
+}
+
+impl Deref for Sampler {
+    type Target = wgpu::Sampler;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sampler
+    }
+}
+
+impl Texture {
+    /// The bind group layout entries of a texture at binding 0 and its sampler at binding 1,
+    /// for combining a Texture with other bindings in one layout
+    pub fn layout_entries(
+        visibility: wgpu::ShaderStages,
+        sample_type: wgpu::TextureSampleType,
+        sampler: wgpu::SamplerBindingType,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    sample_type,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility,
+                ty: wgpu::BindingType::Sampler(sampler),
+                count: None,
+            },
+        ]
+    }
+
+    /// Replace the contents of the whole texture, rows tightly packed
+    pub fn write<P: UiPlatform>(&self, renderer: &Renderer<P>, data: &[u8]) {
+        self.write_region(renderer, [0, 0], self.size(), data);
+    }
+
+    /// Replace the size[0] x size[1] region of the texture starting at origin, rows tightly packed
+    pub fn write_region<P: UiPlatform>(
+        &self,
+        renderer: &Renderer<P>,
+        origin: [u32; 2],
+        size: [u32; 2],
+        data: &[u8],
+    ) {
+        self.upload_region(&UploadContext::new(renderer), origin, size, data);
+    }
+
+    fn upload_region(
+        &self,
+        context: &UploadContext<'_>,
+        origin: [u32; 2],
+        size: [u32; 2],
+        data: &[u8],
+    ) {
+        let (len, bytes_per_row) = region_bytes(self.format(), size);
+        if data.len() != len {
+            log::warn!(
+                "aftgraphs::texture::Texture::write_region: {} bytes given for a {}x{} region, expected {len}",
+                data.len(),
+                size[0],
+                size[1]
+            );
+            return;
+        }
+
+        context.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(size[1]),
+            },
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+        context.record_upload(len);
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        [self.texture.width(), self.texture.height()]
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.texture.format()
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    /// Get the bind group (used for set_bind_group on a render pass)
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Get the bind group layout (useful for setting up render pipelines)
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, slot: u32) {
+        render_pass.set_bind_group(slot, self.bind_group(), &[]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn binding_types() {
+        let mut desc = wgpu::SamplerDescriptor::default();
+        assert_eq!(
+            wgpu::SamplerBindingType::NonFiltering,
+            sampler_binding_type(&desc)
+        );
+        desc.mag_filter = wgpu::FilterMode::Linear;
+        assert_eq!(
+            wgpu::SamplerBindingType::Filtering,
+            sampler_binding_type(&desc)
+        );
+        desc.compare = Some(wgpu::CompareFunction::Less);
+        assert_eq!(
+            wgpu::SamplerBindingType::Comparison,
+            sampler_binding_type(&desc)
+        );
+    }
+
+    #[test]
+    fn region_sizes() {
+        assert_eq!(
+            (4 * 3 * 2, 4 * 3),
+            region_bytes(wgpu::TextureFormat::Rgba8UnormSrgb, [3, 2])
+        );
+        assert_eq!(
+            (16 * 5, 16 * 5),
+            region_bytes(wgpu::TextureFormat::Rgba32Float, [5, 1])
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use super::{region_bytes, RenderTarget, Sampler, Texture};
+use crate::{
+    asset::UploadContext,
+    render::{
+        builder::{BuilderComplete, BuilderInit, BuilderState},
+        Renderer, ResourceKind,
+    },
+    ui::UiPlatform,
+};
+use std::{borrow::Cow, marker::PhantomData};
+
+/// Builder struct for a Texture
+/// Must be given a size, or initial data along with its size, to build.
+/// The default format is Rgba8UnormSrgb, sampled in the fragment stage with
+/// linear filtering and clamped to its edges. Formats that can not be filtered,
+/// like R32Float, default to nearest filtering.
+pub struct TextureBuilder<'a, S: BuilderState> {
+    label: Option<&'a str>,
+    size: [u32; 2],
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    data: Option<Cow<'a, [u8]>>,
+    filter: Option<wgpu::FilterMode>,
+    address_mode: wgpu::AddressMode,
+    sampler: Option<Sampler>,
+    visibility: wgpu::ShaderStages,
+    bind_group_layout: Option<wgpu::BindGroupLayout>,
+    state: PhantomData<S>,
+}
+
+impl Default for TextureBuilder<'_, BuilderInit> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextureBuilder<'_, BuilderInit> {
+    pub fn new() -> Self {
+        Self {
+            label: None,
+            size: [0, 0],
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            data: None,
+            filter: None,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            sampler: None,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            bind_group_layout: None,
+            state: PhantomData,
+        }
+    }
+}
+
+impl TextureBuilder<'_, BuilderComplete> {
+    /// Creates the Texture
+    /// This includes calls to the GPU
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> Texture {
+        self.upload(&UploadContext::new(renderer))
+    }
+
+    /// TextureBuilder::build with the device and queue of an asset upload
+    pub(crate) fn upload(self, context: &UploadContext<'_>) -> Texture {
+        let Self {
+            label,
+            size,
+            format,
+            usage,
+            data,
+            filter,
+            address_mode,
+            sampler,
+            visibility,
+            bind_group_layout,
+            state: _,
+        } = self;
+
+        let sample_type = format
+            .sample_type(None, Some(context.device.features()))
+            .unwrap_or(wgpu::TextureSampleType::Float { filterable: false });
+        let filterable = matches!(
+            sample_type,
+            wgpu::TextureSampleType::Float { filterable: true }
+        );
+        let sampler = sampler.unwrap_or_else(|| {
+            let filter = filter.unwrap_or(if filterable {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            });
+            Sampler::with_filter(context.device, filter, address_mode)
+        });
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: usage | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group_layout = bind_group_layout.unwrap_or_else(|| {
+            context
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label,
+                    entries: &Texture::layout_entries(
+                        visibility,
+                        sample_type,
+                        sampler.binding_type(),
+                    ),
+                })
+        });
+        let bind_group = context
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+        let (bytes, _) = region_bytes(format, size);
+        let texture = Texture {
+            _allocation: context.track_memory(ResourceKind::Texture, label, bytes as u64),
+            texture,
+            view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        };
+        if let Some(data) = data {
+            texture.upload_region(context, [0, 0], size, &data);
+        }
+        texture
+    }
+
+    /// Creates a RenderTarget, adding RENDER_ATTACHMENT to the usage
+    /// This includes calls to the GPU
+    pub fn build_target<P: UiPlatform>(mut self, renderer: &Renderer<P>) -> RenderTarget {
+        self.usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        RenderTarget {
+            texture: self.build(renderer),
+        }
+    }
+}
+
+impl<'a, S: BuilderState> TextureBuilder<'a, S> {
+    fn complete(self) -> TextureBuilder<'a, BuilderComplete> {
+        TextureBuilder {
+            label: self.label,
+            size: self.size,
+            format: self.format,
+            usage: self.usage,
+            data: self.data,
+            filter: self.filter,
+            address_mode: self.address_mode,
+            sampler: self.sampler,
+            visibility: self.visibility,
+            bind_group_layout: self.bind_group_layout,
+            state: PhantomData,
+        }
+    }
+
+    /// Add a label to the texture
+    /// The label will be applied to the texture, the bind group layout, and the bind group
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Sets the size of the texture, its contents start zeroed
+    /// This will reset any previous data
+    pub fn with_size(mut self, width: u32, height: u32) -> TextureBuilder<'a, BuilderComplete> {
+        self.size = [width, height];
+        self.data = None;
+        self.complete()
+    }
+
+    /// Sets the size and initial contents of the texture, rows tightly packed in its format
+    /// The data is not sent to the GPU until TextureBuilder::build is called
+    pub fn with_data(
+        mut self,
+        width: u32,
+        height: u32,
+        data: impl Into<Cow<'a, [u8]>>,
+    ) -> TextureBuilder<'a, BuilderComplete> {
+        self.size = [width, height];
+        self.data = Some(data.into());
+        self.complete()
+    }
+
+    /// Sets the size and initial contents of the texture from a PNG, as Rgba8UnormSrgb
+    pub fn with_png(mut self, png: &[u8]) -> std::io::Result<TextureBuilder<'a, BuilderComplete>> {
+        let (width, height, rgba) = crate::png::read(png)?;
+        self.format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        Ok(self.with_data(width, height, rgba))
+    }
+
+    /// Sets the format of the texture
+    /// Defaults to wgpu::TextureFormat::Rgba8UnormSrgb
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the usage of the texture, TEXTURE_BINDING is always added
+    /// Defaults to wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Sets the filtering of the default sampler
+    pub fn with_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets the address mode of the default sampler
+    /// Defaults to wgpu::AddressMode::ClampToEdge
+    pub fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    /// Use sampler instead of creating one from the filter and address mode
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Sets the shader stages of the default bind group layout
+    /// Defaults to wgpu::ShaderStages::FRAGMENT
+    pub fn with_visibility(mut self, visibility: wgpu::ShaderStages) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Use layout instead of the default bind group layout
+    /// It must have the texture at binding 0 and the sampler at binding 1, see Texture::layout_entries
+    pub fn with_bind_group_layout(mut self, layout: wgpu::BindGroupLayout) -> Self {
+        self.bind_group_layout = Some(layout);
+        self
+    }
+}
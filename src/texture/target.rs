@@ -0,0 +1,38 @@
+use super::Texture;
+use std::ops::Deref;
+
+/// A Texture that render passes draw into, for sampling the result in later passes
+/// Built with TextureBuilder::build_target and drawn into with Renderer::render_to.
+/// Derefs to the Texture, whose bind group samples what was drawn.
+pub struct RenderTarget {
+    pub(super) texture: Texture,
+}
+
+impl RenderTarget {
+    /// The color target of pipelines drawing into this target, replacing what is under them
+    /// Such pipelines must also use a sample count of 1, see RenderPipelineBuilder::with_multisample
+    pub fn color_target(&self) -> wgpu::ColorTargetState {
+        self.color_target_blended(None)
+    }
+
+    /// The color target of pipelines drawing into this target with blend
+    pub fn color_target_blended(&self, blend: Option<wgpu::BlendState>) -> wgpu::ColorTargetState {
+        wgpu::ColorTargetState {
+            format: self.format(),
+            blend,
+            write_mask: wgpu::ColorWrites::ALL,
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+}
+
+impl Deref for RenderTarget {
+    type Target = Texture;
+
+    fn deref(&self) -> &Self::Target {
+        &self.texture
+    }
+}
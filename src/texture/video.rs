@@ -0,0 +1,183 @@
+use super::{Texture, TextureBuilder};
+use crate::render::Renderer;
+use crate::ui::UiPlatform;
+use thiserror::Error;
+use wgpu::RenderPass;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod ffmpeg;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+#[derive(Error, Debug)]
+pub enum VideoError {
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("failed to decode video: {0}")]
+    Ffmpeg(#[from] ffmpeg_next::Error),
+    #[error("the file has no video stream")]
+    NoVideoStream,
+    #[cfg(target_arch = "wasm32")]
+    #[error("failed to create the video element: {0}")]
+    Element(String),
+}
+
+enum Source {
+    #[cfg(not(target_arch = "wasm32"))]
+    File(ffmpeg::Decoder),
+    #[cfg(target_arch = "wasm32")]
+    Element(wasm::Element),
+}
+
+/// A video played into a Texture, bound like any other Texture
+/// Natively a file is decoded with ffmpeg following the time passed to VideoTexture::update,
+/// so headless renders show the same frames as the display. On WASM an HTML video element
+/// plays in real time, seeking whenever it drifts from the time passed to VideoTexture::update.
+pub struct VideoTexture {
+    texture: Texture,
+    source: Source,
+    looping: bool,
+    /// Label of the texture, recreated at the size of the video element
+    #[cfg(target_arch = "wasm32")]
+    label: Option<String>,
+}
+
+fn create_texture<P: UiPlatform>(
+    renderer: &Renderer<P>,
+    label: Option<&str>,
+    size: [u32; 2],
+) -> Texture {
+    TextureBuilder::new()
+        .with_label(label)
+        .with_size(size[0].max(1), size[1].max(1))
+        // Copying from video elements needs RENDER_ATTACHMENT
+        .with_usage(wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT)
+        .build(renderer)
+}
+
+impl VideoTexture {
+    fn new<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        label: Option<&str>,
+        size: [u32; 2],
+        source: Source,
+    ) -> Self {
+        Self {
+            texture: create_texture(renderer, label, size),
+            source,
+            looping: true,
+            #[cfg(target_arch = "wasm32")]
+            label: label.map(String::from),
+        }
+    }
+
+    /// Decode the video file at path
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        path: impl AsRef<std::path::Path>,
+        label: Option<&str>,
+    ) -> Result<Self, VideoError> {
+        let decoder = ffmpeg::Decoder::open(path.as_ref())?;
+        let size = decoder.size();
+        Ok(Self::new(renderer, label, size, Source::File(decoder)))
+    }
+
+    /// Play the video at url in a new, muted video element
+    #[cfg(target_arch = "wasm32")]
+    pub fn open<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        url: &str,
+        label: Option<&str>,
+    ) -> Result<Self, VideoError> {
+        let element = wasm::Element::create(url)?;
+        let size = element.size();
+        Ok(Self::new(renderer, label, size, Source::Element(element)))
+    }
+
+    /// Play an existing video element, which should be muted to be allowed to autoplay
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_element<P: UiPlatform>(
+        renderer: &Renderer<P>,
+        video: web_sys::HtmlVideoElement,
+        label: Option<&str>,
+    ) -> Self {
+        let element = wasm::Element::new(video);
+        let size = element.size();
+        Self::new(renderer, label, size, Source::Element(element))
+    }
+
+    /// Show the frame of the video at time seconds, usually Clock::time
+    /// Returns if the texture changed
+    pub fn update<P: UiPlatform>(&mut self, renderer: &Renderer<P>, time: f64) -> bool {
+        let time = match self.duration() {
+            Some(duration) if self.looping && duration > 0.0 => time.rem_euclid(duration),
+            _ => time.max(0.0),
+        };
+
+        match self.source {
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(ref mut decoder) => {
+                let Some(pixels) = decoder.frame_at(time) else {
+                    return false;
+                };
+                self.texture.write(renderer, &pixels);
+                true
+            }
+            #[cfg(target_arch = "wasm32")]
+            Source::Element(ref element) => {
+                element.sync(time);
+                if !element.is_ready() {
+                    return false;
+                }
+                // The size is only known once the video loaded
+                let size = element.size();
+                if size != self.texture.size() && size[0] > 0 && size[1] > 0 {
+                    self.texture = create_texture(renderer, self.label.as_deref(), size);
+                }
+                element.copy_to(renderer, &self.texture);
+                true
+            }
+        }
+    }
+
+    /// Length of the video in seconds, if known
+    pub fn duration(&self) -> Option<f64> {
+        match self.source {
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(ref decoder) => decoder.duration(),
+            #[cfg(target_arch = "wasm32")]
+            Source::Element(ref element) => element.duration(),
+        }
+    }
+
+    /// Start over from the beginning after the end, the default, or hold the last frame
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.texture.size()
+    }
+
+    /// The texture the video plays into
+    /// Replaced when a video element's size becomes known, so fetch it again after updating
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        self.texture.bind_group()
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        self.texture.bind_group_layout()
+    }
+
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, slot: u32) {
+        self.texture.bind(render_pass, slot);
+    }
+}
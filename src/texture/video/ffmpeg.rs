@@ -0,0 +1,211 @@
+use super::VideoError;
+use crossbeam::channel::{Receiver, Sender};
+use ffmpeg_next::{format, media, software::scaling, util::frame};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+/// Frames decoded ahead of the one shown
+const DECODE_AHEAD: usize = 4;
+/// Jumps forward further than this seek instead of decoding every frame in between
+const SEEK_AFTER: f64 = 2.0;
+
+/// An RGBA frame, rows tightly packed
+struct DecodedFrame {
+    time: f64,
+    pixels: Vec<u8>,
+}
+
+/// Decodes a video file on its own thread, from a start time onwards
+pub(super) struct Decoder {
+    path: PathBuf,
+    size: [u32; 2],
+    duration: Option<f64>,
+    frames: Option<Receiver<DecodedFrame>>,
+    /// The first frame after the time last shown
+    pending: Option<DecodedFrame>,
+    /// Time of the frame last shown
+    shown: Option<f64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The video stream of input, and a decoder for it
+fn open_video(
+    path: &Path,
+) -> Result<(format::context::Input, usize, ffmpeg_next::decoder::Video), VideoError> {
+    let input = format::input(path)?;
+    let stream = input
+        .streams()
+        .best(media::Type::Video)
+        .ok_or(VideoError::NoVideoStream)?;
+    let index = stream.index();
+    let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+    Ok((input, index, decoder))
+}
+
+/// Decode path from the keyframe before start until the end or the receiver is dropped
+fn decode(path: &Path, start: f64, frames: &Sender<DecodedFrame>) -> Result<(), VideoError> {
+    let (mut input, index, mut decoder) = open_video(path)?;
+    let time_base = f64::from(
+        input
+            .stream(index)
+            .ok_or(VideoError::NoVideoStream)?
+            .time_base(),
+    );
+    if start > 0.0 {
+        let timestamp = (start * f64::from(ffmpeg_next::ffi::AV_TIME_BASE)) as i64;
+        input.seek(timestamp, ..timestamp)?;
+    }
+
+    let (width, height) = (decoder.width(), decoder.height());
+    let mut scaler = scaling::Context::get(
+        decoder.format(),
+        width,
+        height,
+        format::Pixel::RGBA,
+        width,
+        height,
+        scaling::Flags::BILINEAR,
+    )?;
+    let mut decoded = frame::Video::empty();
+    let mut rgba = frame::Video::empty();
+
+    // Returns false once the receiver is gone
+    let mut receive = |decoder: &mut ffmpeg_next::decoder::Video| -> Result<bool, VideoError> {
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            scaler.run(&decoded, &mut rgba)?;
+            let row = width as usize * 4;
+            let stride = rgba.stride(0);
+            let pixels = rgba
+                .data(0)
+                .chunks(stride)
+                .take(height as usize)
+                .flat_map(|line| &line[..row])
+                .copied()
+                .collect();
+            let time = decoded.timestamp().unwrap_or(0) as f64 * time_base;
+            if frames.send(DecodedFrame { time, pixels }).is_err() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+
+    for (stream, packet) in input.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        if !receive(&mut decoder)? {
+            return Ok(());
+        }
+    }
+    decoder.send_eof()?;
+    receive(&mut decoder)?;
+    Ok(())
+}
+
+impl Decoder {
+    pub(super) fn open(path: &Path) -> Result<Self, VideoError> {
+        ffmpeg_next::init()?;
+        let (input, _, decoder) = open_video(path)?;
+        let duration = input.duration() as f64 / f64::from(ffmpeg_next::ffi::AV_TIME_BASE);
+
+        let mut decoder = Self {
+            path: path.to_owned(),
+            size: [decoder.width(), decoder.height()],
+            duration: (duration > 0.0).then_some(duration),
+            frames: None,
+            pending: None,
+            shown: None,
+            thread: None,
+        };
+        decoder.restart(0.0);
+        Ok(decoder)
+    }
+
+    pub(super) fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub(super) fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    /// Decode from the keyframe before start, stopping the previous decode
+    fn restart(&mut self, start: f64) {
+        self.stop();
+        let (sender, receiver) = crossbeam::channel::bounded(DECODE_AHEAD);
+        let path = self.path.clone();
+        let thread = std::thread::Builder::new()
+            .name("aftgraphs::texture::VideoTexture".to_owned())
+            .spawn(move || {
+                if let Err(e) = decode(&path, start, &sender) {
+                    log::error!(
+                        "aftgraphs::texture::VideoTexture::decode: {}: {e}",
+                        path.display()
+                    );
+                }
+            });
+        match thread {
+            Ok(thread) => {
+                self.frames = Some(receiver);
+                self.thread = Some(thread);
+            }
+            Err(e) => log::error!("aftgraphs::texture::VideoTexture::restart: {e}"),
+        }
+    }
+
+    fn stop(&mut self) {
+        // Dropping the receiver fails the next send, ending the thread
+        self.frames = None;
+        self.pending = None;
+        self.shown = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("aftgraphs::texture::VideoTexture::stop: decode thread panicked");
+            }
+        }
+    }
+
+    /// The last frame at or before time, if it is not the frame shown already
+    /// Waits for the decoder, so every time sees the same frame however fast it decodes.
+    pub(super) fn frame_at(&mut self, time: f64) -> Option<Vec<u8>> {
+        let rewound = self.shown.is_some_and(|shown| time < shown);
+        let skipped = self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| time > pending.time + SEEK_AFTER);
+        if rewound || skipped {
+            self.restart(time);
+        }
+
+        let mut latest = None;
+        loop {
+            let frame = match self.pending.take() {
+                Some(frame) => frame,
+                None => match self.frames.as_ref().map(Receiver::recv) {
+                    Some(Ok(frame)) => frame,
+                    // The end of the video, keep showing the last frame
+                    _ => break,
+                },
+            };
+            if frame.time > time && (latest.is_some() || self.shown.is_some()) {
+                self.pending = Some(frame);
+                break;
+            }
+            latest = Some(frame);
+        }
+
+        let latest = latest?;
+        self.shown = Some(latest.time);
+        Some(latest.pixels)
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,88 @@
+use super::VideoError;
+use crate::render::Renderer;
+use crate::texture::Texture;
+use crate::ui::UiPlatform;
+use wasm_bindgen::JsCast;
+
+/// Seconds the element may drift from the requested time before it seeks
+const MAX_DRIFT: f64 = 0.25;
+
+/// An HTML video element playing on its own clock
+pub(super) struct Element {
+    video: web_sys::HtmlVideoElement,
+}
+
+impl Element {
+    pub(super) fn new(video: web_sys::HtmlVideoElement) -> Self {
+        Self { video }
+    }
+
+    /// A muted, looping video element playing url
+    pub(super) fn create(url: &str) -> Result<Self, VideoError> {
+        let error = |e: wasm_bindgen::JsValue| VideoError::Element(format!("{e:?}"));
+        let video = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| VideoError::Element("no document".to_owned()))?
+            .create_element("video")
+            .map_err(error)?
+            .dyn_into::<web_sys::HtmlVideoElement>()
+            .map_err(|_| VideoError::Element("not a video element".to_owned()))?;
+        video.set_cross_origin(Some("anonymous"));
+        video.set_muted(true);
+        video.set_loop(true);
+        video.set_attribute("playsinline", "").map_err(error)?;
+        video.set_src(url);
+        if let Err(e) = video.play() {
+            log::warn!("aftgraphs::texture::VideoTexture::open: failed to play {url}: {e:?}");
+        }
+        Ok(Self::new(video))
+    }
+
+    /// If the element has a frame to show
+    pub(super) fn is_ready(&self) -> bool {
+        self.video.ready_state() >= web_sys::HtmlMediaElement::HAVE_CURRENT_DATA
+    }
+
+    /// The size of the video, 0 until its metadata loads
+    pub(super) fn size(&self) -> [u32; 2] {
+        [self.video.video_width(), self.video.video_height()]
+    }
+
+    pub(super) fn duration(&self) -> Option<f64> {
+        let duration = self.video.duration();
+        (duration.is_finite() && duration > 0.0).then_some(duration)
+    }
+
+    /// Seek to time if the element drifted too far from it
+    pub(super) fn sync(&self, time: f64) {
+        if (self.video.current_time() - time).abs() > MAX_DRIFT && !self.video.seeking() {
+            self.video.set_current_time(time);
+        }
+    }
+
+    /// Copy the current frame into texture
+    pub(super) fn copy_to<P: UiPlatform>(&self, renderer: &Renderer<P>, texture: &Texture) {
+        let size = texture.size();
+        renderer.queue.copy_external_image_to_texture(
+            &wgpu::ImageCopyExternalImage {
+                source: wgpu::ExternalImageSource::HTMLVideoElement(self.video.clone()),
+                origin: wgpu::Origin2d::ZERO,
+                flip_y: false,
+            },
+            wgpu::ImageCopyTextureTagged {
+                texture: texture.texture(),
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+                color_space: wgpu::PredefinedColorSpace::Srgb,
+                premultiplied_alpha: false,
+            },
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+        renderer.record_upload(size[0] as usize * size[1] as usize * 4);
+    }
+}
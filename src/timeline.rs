@@ -0,0 +1,226 @@
+//! Cues of a headless input file played back in display mode,
+//! to rehearse a presentation live exactly as it renders headless.
+//! Load one with --timeline, or Renderer::set_timeline.
+
+use crate::headless::{input_name, Chapter, HeadlessInput, HeadlessInputBlock};
+use crate::input::InputValue;
+use crate::simulation::TimedInput;
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TimelineError {
+    #[error("failed to read timeline: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse timeline TOML: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// Input changes and events at simulated times, in the HeadlessInput format
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    initial: HashMap<String, InputValue>,
+    /// Sorted by time
+    cues: Vec<HeadlessInputBlock>,
+    chapters: Vec<Chapter>,
+    duration: f64,
+    /// Index of the next cue to fire
+    next: usize,
+    /// Time to jump to before the next frame, set when scrubbing
+    seek: Option<f64>,
+}
+
+impl Timeline {
+    pub fn new(input: HeadlessInput) -> Self {
+        let chapters = input.chapters();
+        let initial = input
+            .initial_inputs
+            .map(|initial| {
+                initial
+                    .inputs
+                    .into_iter()
+                    .map(|(name, value)| (input_name(&name), value))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut cues = input.blocks;
+        cues.sort_by(|lhs, rhs| lhs.time.total_cmp(&rhs.time));
+
+        Self {
+            initial,
+            cues,
+            chapters,
+            duration: input.simulation.duration,
+            next: 0,
+            seek: Some(0.0),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, TimelineError> {
+        let input = toml::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(Self::new(input))
+    }
+
+    /// The timeline passed with --timeline, if any
+    pub(crate) async fn startup() -> Option<Self> {
+        let path = crate::cli::ARGUMENTS.read().await.timeline.clone()?;
+        match Self::from_file(&path) {
+            Ok(timeline) => Some(timeline),
+            Err(e) => {
+                log::error!(
+                    "aftgraphs::timeline::Timeline::startup: failed to load {}: {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    pub fn cues(&self) -> &[HeadlessInputBlock] {
+        &self.cues
+    }
+
+    /// Jump to time before the next frame
+    pub fn request_seek(&mut self, time: f64) {
+        self.seek = Some(time.clamp(0.0, self.duration));
+    }
+
+    pub(crate) fn take_seek(&mut self) -> Option<f64> {
+        self.seek.take()
+    }
+
+    /// Restart from the initial inputs, then apply the inputs of every cue before time.
+    /// Events of skipped cues are not sent
+    pub fn seek(&mut self, time: f64, values: &mut HashMap<String, InputValue>) {
+        values.extend(self.initial.clone());
+        self.next = 0;
+        while let Some(cue) = self.cues.get(self.next).filter(|cue| time > cue.time) {
+            values.extend(
+                cue.inputs
+                    .iter()
+                    .map(|(name, value)| (input_name(name), value.clone())),
+            );
+            self.next += 1;
+        }
+    }
+
+    /// Fire the cues passed at time, returning their events
+    pub fn advance(
+        &mut self,
+        time: f64,
+        values: &mut HashMap<String, InputValue>,
+    ) -> Vec<TimedInput> {
+        let mut events = vec![];
+        while let Some(cue) = self.cues.get(self.next).filter(|cue| time > cue.time) {
+            log::debug!(
+                "aftgraphs::timeline::Timeline::advance: firing cue at {}",
+                cue.time
+            );
+            values.extend(
+                cue.inputs
+                    .iter()
+                    .map(|(name, value)| (input_name(name), value.clone())),
+            );
+            events.extend(cue.events.iter().map(|event| TimedInput {
+                event: event.clone().into(),
+                time: cue.time,
+            }));
+            self.next += 1;
+        }
+        events
+    }
+}
+
+/// Play/pause button, scrubber and chapter list of timeline
+pub(crate) fn draw_timeline(
+    ui: &imgui::Ui,
+    timeline: &mut Timeline,
+    frame_step: &crate::render::FrameStep,
+    time: f64,
+) {
+    ui.window("Timeline")
+        .size([480.0, 200.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            let paused = frame_step.is_paused();
+            if ui.button(if paused { "Play" } else { "Pause" }) {
+                frame_step.set_paused(!paused);
+            }
+            ui.same_line();
+
+            let mut scrub = time as f32;
+            ui.set_next_item_width(-1.0);
+            if ui
+                .slider_config("##time", 0.0, timeline.duration as f32)
+                .display_format("%.2f s")
+                .build(&mut scrub)
+            {
+                timeline.request_seek(scrub as f64);
+            }
+
+            ui.separator();
+            let mut jump = None;
+            for chapter in &timeline.chapters {
+                let current = (chapter.start..chapter.end).contains(&time);
+                if ui
+                    .selectable_config(format!("{:>8.2} s  {}", chapter.start, chapter.title))
+                    .selected(current)
+                    .build()
+                {
+                    jump = Some(chapter.start);
+                }
+            }
+            if let Some(start) = jump {
+                timeline.request_seek(start);
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeking_replays_inputs_of_earlier_cues() {
+        let input: HeadlessInput = toml::from_str(
+            r#"
+            [simulation]
+            duration = 10.0
+            delta_t = 0.1
+
+            [initial-inputs]
+            block-speed = { SLIDER = 0.0 }
+
+            [[block]]
+            time = 1.0
+            block-speed = { SLIDER = 1.0 }
+
+            [[block]]
+            time = 5.0
+            block-speed = { SLIDER = 5.0 }
+            "#,
+        )
+        .unwrap();
+        let mut timeline = Timeline::new(input);
+        let mut values = HashMap::new();
+        let speed = |values: &HashMap<String, InputValue>| values.get("block.speed").cloned();
+
+        timeline.seek(0.0, &mut values);
+        assert_eq!(Some(InputValue::SLIDER(0.0)), speed(&values));
+        timeline.advance(1.5, &mut values);
+        assert_eq!(Some(InputValue::SLIDER(1.0)), speed(&values));
+
+        timeline.seek(6.0, &mut values);
+        assert_eq!(Some(InputValue::SLIDER(5.0)), speed(&values));
+        timeline.seek(0.5, &mut values);
+        assert_eq!(Some(InputValue::SLIDER(0.0)), speed(&values));
+    }
+}
@@ -23,8 +23,10 @@ pub struct UiWinitPlatform(WinitPlatform);
 impl UiPlatform for UiWinitPlatform {
     fn prepare_frame(&mut self, ui: &mut Ui, window: &Window) {
         if let Err(e) = self.0.prepare_frame(ui.0.io_mut(), window) {
-            log::error!("aftgraphs::ui::UiWinitPlatform::prepare_frame: imgui context error: {e}");
-            panic!("aftgraphs::ui::UiWinitPlatform::prepare_frame: imgui context error: {e}");
+            crate::error::report(
+                "aftgraphs::ui::UiWinitPlatform::prepare_frame",
+                format!("imgui context error: {e}"),
+            );
         }
     }
 
@@ -101,8 +103,11 @@ impl Ui {
                 match factor.parse::<f64>() {
                     Ok(f) => HiDpiMode::Locked(f),
                     Err(e) => {
-                        log::error!("aftgraphs::ui::new: Invalid winit scaling factor: {e}");
-                        panic!("aftgraphs::ui::new: Invalid winit scaling factor: {e}")
+                        crate::error::report(
+                            "aftgraphs::ui::new",
+                            format!("invalid IMGUI_FORCE_DPI_FACTOR: {e}"),
+                        );
+                        HiDpiMode::Default
                     }
                 }
             } else {
@@ -32,6 +32,10 @@ impl Ui {
         self
     }
 
+    pub fn frame(&mut self) -> UiFrame<'_> {
+        self
+    }
+
     pub fn new(
         _window: &Window,
         _device: &wgpu::Device,
@@ -1,17 +1,31 @@
-use crate::render::Renderer;
+use crate::input::BoundInputs;
+use crate::render::{Allocation, Renderer};
 use crate::ui::UiPlatform;
 use bytemuck::NoUninit;
+use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
 use wgpu::RenderPass;
 
 mod builder;
+mod set;
+mod types;
 pub use builder::UniformBuilder;
+pub use set::{UniformField, UniformSet, UniformSetBuilder, UniformSetGuard};
+pub use types::{Color, Float, Mat4, Vec2, Vec3, Vec4};
 
 pub struct Uniform<T: NoUninit> {
-    buffer: wgpu::Buffer,
+    buffer: Arc<wgpu::Buffer>,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     data: T,
+    /// Set by Uniform::inspect, formats data for the inspector
+    format: Option<fn(&T) -> String>,
+    allocation: Allocation,
+    /// Values of the inputs bound with Renderer::register_uniform
+    bound: BoundInputs,
+    /// Copies bound into the data, set when the uniform is registered as that needs T: Pod
+    patch: OnceLock<fn(&mut T, &BoundInputs)>,
 }
 
 pub struct UniformGuard<'a, 'b, T: NoUninit, P: UiPlatform> {
@@ -43,13 +57,56 @@ impl<T: NoUninit> Uniform<T> {
     pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.bind_group_layout
     }
+
+    pub(crate) fn buffer(&self) -> &Arc<wgpu::Buffer> {
+        &self.buffer
+    }
+
+    /// The values of inputs bound to the uniform, kept in its data from now on
+    pub(crate) fn bound_inputs(&self) -> &BoundInputs
+    where
+        T: bytemuck::Pod,
+    {
+        self.patch.get_or_init(|| {
+            let patch: fn(&mut T, &BoundInputs) =
+                |data, bound| bound.copy_into(bytemuck::bytes_of_mut(data));
+            patch
+        });
+        &self.bound
+    }
+
+    /// Copy the values of bound inputs into data
+    fn patch_bound(&self, data: &mut T) {
+        if let Some(patch) = self.patch.get() {
+            patch(data, &self.bound);
+        }
+    }
+
+    /// Show the latest value in the renderer's inspector
+    fn publish(&self) {
+        if let Some(format) = self.format {
+            self.allocation.set_value(format(&self.data));
+        }
+    }
+}
+
+impl<T: NoUninit + Debug> Uniform<T> {
+    /// List the value of the uniform in the inspector, updated whenever it changes
+    pub fn inspect(&mut self) {
+        self.format = Some(|data| format!("{data:?}"));
+        self.publish();
+    }
 }
 
 impl<T: NoUninit + PartialEq> Uniform<T> {
     /// Update the uniform value
     /// Will immediately buffer data to the GPU, but only if the
     /// new value is not equal to the old value
-    pub fn update<P: UiPlatform>(&mut self, renderer: &Renderer<P>, value: T) {
+    /// Accepts anything convertible to T, e.g. a [f32; 4] or glam::Vec4 for a Vec4 uniform
+    /// Inputs bound to the uniform keep their values.
+    pub fn update<P: UiPlatform>(&mut self, renderer: &Renderer<P>, value: impl Into<T>) {
+        let mut value = value.into();
+        self.patch_bound(&mut value);
         if value == self.data {
             self.data = value;
             return;
@@ -59,9 +116,15 @@ impl<T: NoUninit + PartialEq> Uniform<T> {
         renderer
             .queue
             .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.data));
+        renderer.record_upload(std::mem::size_of::<T>());
+        self.publish();
     }
 
-    pub fn bind<'a, 'b: 'a>(&'b mut self, render_pass: &mut RenderPass<'a>, slot: u32) {
+    pub fn bind(&mut self, render_pass: &mut RenderPass<'_>, slot: u32) {
+        // The renderer already wrote the bound inputs to the buffer
+        let mut data = self.data;
+        self.patch_bound(&mut data);
+        self.data = data;
         render_pass.set_bind_group(slot, self.bind_group(), &[]);
     }
 }
@@ -113,11 +176,16 @@ impl<T: NoUninit, P: UiPlatform> DerefMut for UniformGuard<'_, '_, T, P> {
 impl<T: NoUninit, P: UiPlatform> Drop for UniformGuard<'_, '_, T, P> {
     fn drop(&mut self) {
         if self.changed {
+            let mut data = self.uniform.data;
+            self.uniform.patch_bound(&mut data);
+            self.uniform.data = data;
             self.renderer.queue.write_buffer(
                 &self.uniform.buffer,
                 0,
                 bytemuck::bytes_of(&self.uniform.data),
             );
+            self.renderer.record_upload(std::mem::size_of::<T>());
+            self.uniform.publish();
         }
     }
 }
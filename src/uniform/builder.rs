@@ -1,5 +1,8 @@
 use super::Uniform;
-use crate::{render::Renderer, ui::UiPlatform};
+use crate::{
+    render::{Renderer, ResourceKind},
+    ui::UiPlatform,
+};
 use bytemuck::{NoUninit, Zeroable};
 use std::marker::PhantomData;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
@@ -100,10 +103,14 @@ impl<T: NoUninit> UniformBuilder<'_, T, BuilderComplete> {
             });
 
         Uniform {
-            buffer,
+            allocation: renderer.track_memory(ResourceKind::Uniform, label, buffer.size()),
+            format: None,
+            buffer: buffer.into(),
             bind_group_layout,
             bind_group,
             data,
+            bound: Default::default(),
+            patch: Default::default(),
         }
     }
 }
@@ -121,10 +128,10 @@ impl<'a, T: NoUninit, S: BuilderState> UniformBuilder<'a, T, S> {
     /// see aftgraphs::Renderer::BindGroupLayoutBuilder
     pub fn with_bind_group_layout(
         self,
-        layout: wgpu::BindGroupLayout,
+        layout: impl Into<wgpu::BindGroupLayout>,
     ) -> UniformBuilder<'a, T, <S as sealed::Sealed>::AddBindGroupLayout> {
         UniformBuilder {
-            bind_group_layout: Some(layout),
+            bind_group_layout: Some(layout.into()),
             usage: self.usage,
             label: self.label,
             data: self.data,
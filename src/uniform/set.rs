@@ -0,0 +1,290 @@
+use crate::input::BoundInputs;
+use crate::render::{Allocation, Renderer, ResourceKind};
+use crate::ui::UiPlatform;
+use bytemuck::NoUninit;
+use std::{marker::PhantomData, num::NonZeroU64, ops::Range};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::RenderPass;
+
+/// Handle to one uniform of a UniformSet, only valid for the set it was added to
+#[derive(Debug)]
+pub struct UniformField<T: NoUninit> {
+    binding: u32,
+    _data: PhantomData<T>,
+}
+
+impl<T: NoUninit> Clone for UniformField<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: NoUninit> Copy for UniformField<T> {}
+
+impl<T: NoUninit> UniformField<T> {
+    /// Binding of the uniform in the bind group of its UniformSet
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+}
+
+struct FieldLayout {
+    visibility: wgpu::ShaderStages,
+    size: usize,
+    data: Vec<u8>,
+}
+
+/// Related uniforms sharing one buffer and one bind group
+/// Each uniform gets its own binding in the bind group, in the order they were added,
+/// at an offset in the buffer aligned to the device's min_uniform_buffer_offset_alignment.
+/// Binding the set once replaces a bind group switch per uniform.
+pub struct UniformSet {
+    buffer: std::sync::Arc<wgpu::Buffer>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+    _allocation: Allocation,
+    /// Values of the inputs bound with Renderer::register_uniform_field
+    bound: BoundInputs,
+}
+
+/// Batches writes to a UniformSet
+/// When the guard drops, the changed span of the buffer is sent to the GPU in one write
+pub struct UniformSetGuard<'a, 'b, P: UiPlatform> {
+    set: &'a mut UniformSet,
+    renderer: &'a Renderer<'b, P>,
+    dirty: Option<Range<usize>>,
+}
+
+#[derive(Default)]
+pub struct UniformSetBuilder<'a> {
+    label: Option<&'a str>,
+    fields: Vec<FieldLayout>,
+}
+
+/// Offsets of fields of the given sizes packed at the given alignment, and the total size
+fn field_offsets(sizes: impl IntoIterator<Item = usize>, alignment: usize) -> (Vec<usize>, usize) {
+    let mut offsets = vec![];
+    let mut end: usize = 0;
+
+    for size in sizes {
+        let offset = end.next_multiple_of(alignment);
+        offsets.push(offset);
+        end = offset + size;
+    }
+
+    (offsets, end)
+}
+
+impl<'a> UniformSetBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a label to the set
+    /// The label will be applied to the bind group layout, the buffer, and the bind group
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Add a uniform visible to the given shader stages, returning its handle
+    /// The uniform is bound at the next binding, starting from 0.
+    /// Handles are only valid for the UniformSet built by this builder.
+    pub fn add_field<T: NoUninit>(
+        &mut self,
+        visibility: wgpu::ShaderStages,
+        data: T,
+    ) -> UniformField<T> {
+        let binding = self.fields.len() as u32;
+        self.fields.push(FieldLayout {
+            visibility,
+            size: std::mem::size_of::<T>(),
+            data: bytemuck::bytes_of(&data).to_vec(),
+        });
+
+        UniformField {
+            binding,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn build<P: UiPlatform>(self, renderer: &Renderer<P>) -> UniformSet {
+        let alignment = renderer.device.limits().min_uniform_buffer_offset_alignment as usize;
+        let (offsets, size) = field_offsets(self.fields.iter().map(|field| field.size), alignment);
+
+        let mut data = vec![0; size.max(1)];
+        for (field, &offset) in self.fields.iter().zip(&offsets) {
+            data[offset..offset + field.size].copy_from_slice(&field.data);
+        }
+
+        let layout_entries: Vec<_> = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(binding, field)| wgpu::BindGroupLayoutEntry {
+                binding: binding as u32,
+                visibility: field.visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(field.size as u64),
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout =
+            renderer
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: self.label,
+                    entries: &layout_entries,
+                });
+
+        let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: self.label,
+            contents: &data,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let entries: Vec<_> = self
+            .fields
+            .iter()
+            .zip(&offsets)
+            .enumerate()
+            .map(|(binding, (field, &offset))| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: offset as u64,
+                    size: NonZeroU64::new(field.size as u64),
+                }),
+            })
+            .collect();
+        let bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: self.label,
+                layout: &bind_group_layout,
+                entries: &entries,
+            });
+
+        UniformSet {
+            _allocation: renderer.track_memory(ResourceKind::Uniform, self.label, buffer.size()),
+            buffer: buffer.into(),
+            bind_group_layout,
+            bind_group,
+            data,
+            offsets,
+            bound: Default::default(),
+        }
+    }
+}
+
+impl UniformSet {
+    /// Create a guard to modify several uniforms of the set
+    /// When the guard drops, it will buffer the changed data to the GPU
+    pub fn modify<'a, 'b, P: UiPlatform>(
+        &'a mut self,
+        renderer: &'a Renderer<'b, P>,
+    ) -> UniformSetGuard<'a, 'b, P> {
+        UniformSetGuard {
+            set: self,
+            renderer,
+            dirty: None,
+        }
+    }
+
+    /// Update a single uniform
+    /// Will immediately buffer data to the GPU, but only if the
+    /// new value is not equal to the old value. Bound inputs keep their values.
+    pub fn update<T: NoUninit, P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<P>,
+        field: UniformField<T>,
+        value: impl Into<T>,
+    ) {
+        self.modify(renderer).set(field, value);
+    }
+
+    /// Bytes of the buffer holding the uniform
+    fn range<T: NoUninit>(&self, field: UniformField<T>) -> Range<usize> {
+        let offset = self.offsets[field.binding as usize];
+        offset..offset + std::mem::size_of::<T>()
+    }
+
+    pub(crate) fn buffer(&self) -> &std::sync::Arc<wgpu::Buffer> {
+        &self.buffer
+    }
+
+    /// The values of inputs bound to uniforms of the set, kept in its data
+    pub(crate) fn bound_inputs(&self) -> &BoundInputs {
+        &self.bound
+    }
+
+    /// Byte offset of the uniform in the buffer
+    pub(crate) fn offset<T: NoUninit>(&self, field: UniformField<T>) -> u64 {
+        self.range(field).start as u64
+    }
+
+    /// Get the bind group (used for set_bind_group on a render pass)
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Get the bind group layout (useful for setting up render pipelines)
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, slot: u32) {
+        render_pass.set_bind_group(slot, self.bind_group(), &[]);
+    }
+}
+
+impl<P: UiPlatform> UniformSetGuard<'_, '_, P> {
+    /// Set the value of a uniform, marking it for upload if it changed
+    pub fn set<T: NoUninit>(&mut self, field: UniformField<T>, value: impl Into<T>) {
+        let range = self.set.range(field);
+        let value = value.into();
+        let bytes = bytemuck::bytes_of(&value);
+        if self.set.data[range.clone()] == *bytes {
+            return;
+        }
+
+        self.set.data[range.clone()].copy_from_slice(bytes);
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+}
+
+/// Buffers the changed span to the GPU
+impl<P: UiPlatform> Drop for UniformSetGuard<'_, '_, P> {
+    fn drop(&mut self) {
+        if let Some(dirty) = self.dirty.take() {
+            // The renderer writes bound inputs itself, the span only needs to keep them
+            self.set.bound.copy_into(&mut self.set.data);
+            self.renderer.queue.write_buffer(
+                &self.set.buffer,
+                dirty.start as wgpu::BufferAddress,
+                &self.set.data[dirty.clone()],
+            );
+            self.renderer.record_upload(dirty.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offsets_aligned() {
+        assert_eq!((vec![0, 256, 512], 528), field_offsets([16, 64, 16], 256));
+        assert_eq!((vec![0, 256], 768), field_offsets([256, 512], 256));
+        assert_eq!((vec![], 0), field_offsets([], 256));
+    }
+}
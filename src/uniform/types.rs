@@ -0,0 +1,340 @@
+//! Uniform values laid out the way WGSL expects them in a uniform buffer.
+//! Every type is padded to a multiple of 16 bytes where WGSL needs it, so it
+//! can be bound on its own or used as a field of a UniformSet without a
+//! hand written #[repr(C, align(16))] wrapper.
+
+use std::ops::{Deref, DerefMut};
+
+/// A f32 uniform, bound as f32 in WGSL.
+/// Padded to 16 bytes, the smallest uniform binding WebGL accepts.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Float {
+    pub value: f32,
+    _padding: [f32; 3],
+}
+
+/// A vec2<f32> uniform
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Vec2 {
+    pub value: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// A vec3<f32> uniform, padded to the 16 byte alignment of vec3 in WGSL
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Vec3 {
+    pub value: [f32; 3],
+    _padding: f32,
+}
+
+/// A vec4<f32> uniform
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C, align(16))]
+pub struct Vec4(pub [f32; 4]);
+
+/// A linear RGBA color, bound as vec4<f32> in WGSL
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C, align(16))]
+pub struct Color(pub [f32; 4]);
+
+/// A column major mat4x4<f32> uniform
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C, align(16))]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+unsafe impl bytemuck::Zeroable for Float {}
+unsafe impl bytemuck::Pod for Float {}
+
+unsafe impl bytemuck::Zeroable for Vec2 {}
+unsafe impl bytemuck::Pod for Vec2 {}
+
+unsafe impl bytemuck::Zeroable for Vec3 {}
+unsafe impl bytemuck::Pod for Vec3 {}
+
+unsafe impl bytemuck::Zeroable for Vec4 {}
+unsafe impl bytemuck::Pod for Vec4 {}
+
+unsafe impl bytemuck::Zeroable for Color {}
+unsafe impl bytemuck::Pod for Color {}
+
+unsafe impl bytemuck::Zeroable for Mat4 {}
+unsafe impl bytemuck::Pod for Mat4 {}
+
+impl Float {
+    pub const fn new(value: f32) -> Self {
+        Self {
+            value,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+impl Vec2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self {
+            value: [x, y],
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self {
+            value: [x, y, z],
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Vec4 {
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self([x, y, z, w])
+    }
+}
+
+impl Color {
+    pub const BLACK: Self = Self::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Self = Self::rgb(1.0, 1.0, 1.0);
+    pub const TRANSPARENT: Self = Self::rgba(0.0, 0.0, 0.0, 0.0);
+
+    /// An opaque color
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self([r, g, b, 1.0])
+    }
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self([r, g, b, a])
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::BLACK
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    /// Build from the columns of the matrix
+    pub const fn from_cols(cols: [[f32; 4]; 4]) -> Self {
+        Self(cols)
+    }
+
+    /// Build from the rows of the matrix, transposing to WGSL's column major layout
+    pub fn from_rows(rows: [[f32; 4]; 4]) -> Self {
+        let mut cols = [[0.0; 4]; 4];
+        for (row, values) in rows.iter().enumerate() {
+            for (col, &value) in values.iter().enumerate() {
+                cols[col][row] = value;
+            }
+        }
+        Self(cols)
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<f32> for Float {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<[f32; 4]> for Vec4 {
+    fn from(value: [f32; 4]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self::rgb(r, g, b)
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from(value: [f32; 4]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<wgpu::Color> for Color {
+    fn from(color: wgpu::Color) -> Self {
+        Self::rgba(
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        )
+    }
+}
+
+/// Columns of the matrix
+impl From<[[f32; 4]; 4]> for Mat4 {
+    fn from(cols: [[f32; 4]; 4]) -> Self {
+        Self(cols)
+    }
+}
+
+macro_rules! deref_value {
+    ($($ty:ty => $target:ty, $($field:tt).+;)*) => {
+        $(
+            impl Deref for $ty {
+                type Target = $target;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.$($field).+
+                }
+            }
+
+            impl DerefMut for $ty {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    &mut self.$($field).+
+                }
+            }
+        )*
+    };
+}
+
+deref_value! {
+    Float => f32, value;
+    Vec2 => [f32; 2], value;
+    Vec3 => [f32; 3], value;
+    Vec4 => [f32; 4], 0;
+    Color => [f32; 4], 0;
+    Mat4 => [[f32; 4]; 4], 0;
+}
+
+#[cfg(feature = "glam")]
+mod glam_impls {
+    use super::*;
+
+    impl From<glam::Vec2> for Vec2 {
+        fn from(value: glam::Vec2) -> Self {
+            value.to_array().into()
+        }
+    }
+
+    impl From<glam::Vec3> for Vec3 {
+        fn from(value: glam::Vec3) -> Self {
+            value.to_array().into()
+        }
+    }
+
+    impl From<glam::Vec4> for Vec4 {
+        fn from(value: glam::Vec4) -> Self {
+            value.to_array().into()
+        }
+    }
+
+    impl From<glam::Vec4> for Color {
+        fn from(value: glam::Vec4) -> Self {
+            value.to_array().into()
+        }
+    }
+
+    impl From<glam::Mat4> for Mat4 {
+        fn from(value: glam::Mat4) -> Self {
+            Self(value.to_cols_array_2d())
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra_impls {
+    use super::*;
+
+    impl From<nalgebra::Vector2<f32>> for Vec2 {
+        fn from(value: nalgebra::Vector2<f32>) -> Self {
+            Self::new(value.x, value.y)
+        }
+    }
+
+    impl From<nalgebra::Vector3<f32>> for Vec3 {
+        fn from(value: nalgebra::Vector3<f32>) -> Self {
+            Self::new(value.x, value.y, value.z)
+        }
+    }
+
+    impl From<nalgebra::Vector4<f32>> for Vec4 {
+        fn from(value: nalgebra::Vector4<f32>) -> Self {
+            Self::new(value.x, value.y, value.z, value.w)
+        }
+    }
+
+    impl From<nalgebra::Vector4<f32>> for Color {
+        fn from(value: nalgebra::Vector4<f32>) -> Self {
+            Self::rgba(value.x, value.y, value.z, value.w)
+        }
+    }
+
+    /// nalgebra stores matrices column major like WGSL
+    impl From<nalgebra::Matrix4<f32>> for Mat4 {
+        fn from(value: nalgebra::Matrix4<f32>) -> Self {
+            Self(value.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem::{align_of, size_of};
+
+    #[test]
+    fn wgsl_layout() {
+        assert_eq!((16, 16), (size_of::<Float>(), align_of::<Float>()));
+        assert_eq!((16, 16), (size_of::<Vec2>(), align_of::<Vec2>()));
+        assert_eq!((16, 16), (size_of::<Vec3>(), align_of::<Vec3>()));
+        assert_eq!((16, 16), (size_of::<Vec4>(), align_of::<Vec4>()));
+        assert_eq!((16, 16), (size_of::<Color>(), align_of::<Color>()));
+        assert_eq!((64, 16), (size_of::<Mat4>(), align_of::<Mat4>()));
+
+        assert_eq!(
+            &[1.5f32, 0.0, 0.0, 0.0],
+            bytemuck::cast_slice::<_, f32>(bytemuck::bytes_of(&Float::from(1.5)))
+        );
+        assert_eq!([1.0, 2.0, 3.0], *Vec3::from([1.0, 2.0, 3.0]));
+        assert_eq!(Color([0.5, 0.5, 0.5, 1.0]), [0.5; 3].into());
+    }
+
+    #[test]
+    fn transposes_rows() {
+        let rows = [
+            [1.0, 2.0, 3.0, 4.0],
+            [5.0, 6.0, 7.0, 8.0],
+            [9.0, 10.0, 11.0, 12.0],
+            [13.0, 14.0, 15.0, 16.0],
+        ];
+        let mat = Mat4::from_rows(rows);
+        assert_eq!([1.0, 5.0, 9.0, 13.0], mat[0]);
+        assert_eq!([4.0, 8.0, 12.0, 16.0], mat[3]);
+        assert_eq!(Mat4::IDENTITY, Mat4::from_rows(Mat4::IDENTITY.0));
+    }
+}
@@ -0,0 +1,229 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::AspectPolicy;
+use crate::{
+    render::{BindGroupLayoutBuilder, Renderer, BINDING_UNIFORM_BUFFER},
+    ui::UiPlatform,
+    uniform::{Uniform, UniformBuilder},
+};
+
+/// SI prefixes used to label lengths, largest first
+const PREFIXES: [(f64, &str); 5] = [
+    (1e3, "km"),
+    (1.0, "m"),
+    (1e-2, "cm"),
+    (1e-3, "mm"),
+    (1e-6, "µm"),
+];
+
+/// Scale between world units and physical units
+/// Lets simulations express parameters in meters and seconds while drawing in world units.
+/// Renderer::set_world_scale labels the window edges in its units.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WorldScale {
+    /// World units per meter
+    pub units_per_meter: f64,
+    /// Simulated seconds per real second, below 1 for slow motion
+    pub time_scale: f64,
+}
+
+impl Default for WorldScale {
+    fn default() -> Self {
+        Self {
+            units_per_meter: 1.0,
+            time_scale: 1.0,
+        }
+    }
+}
+
+/// WorldScale as bound to shaders
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct WorldScaleParams {
+    pub units_per_meter: f32,
+    pub meters_per_unit: f32,
+    pub time_scale: f32,
+    pub _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for WorldScaleParams {}
+unsafe impl bytemuck::NoUninit for WorldScaleParams {}
+
+impl WorldScale {
+    pub fn new(units_per_meter: f64, time_scale: f64) -> Self {
+        Self {
+            units_per_meter,
+            time_scale,
+        }
+    }
+
+    /// World units spanning meters
+    pub fn meters_to_units(&self, meters: f64) -> f64 {
+        meters * self.units_per_meter
+    }
+
+    /// Meters spanned by world units
+    pub fn units_to_meters(&self, units: f64) -> f64 {
+        units / self.units_per_meter
+    }
+
+    /// Simulated seconds passing in real seconds, e.g. Clock::delta_time
+    pub fn simulated(&self, seconds: f64) -> f64 {
+        seconds * self.time_scale
+    }
+
+    /// World units per real second of a velocity in meters per simulated second
+    pub fn velocity(&self, meters_per_second: f64) -> f64 {
+        self.meters_to_units(meters_per_second) * self.time_scale
+    }
+
+    /// World units per real second squared of an acceleration in meters per simulated second squared
+    pub fn acceleration(&self, meters_per_second_squared: f64) -> f64 {
+        self.meters_to_units(meters_per_second_squared) * self.time_scale * self.time_scale
+    }
+
+    /// Label a length in world units with the SI prefix that fits it best, for axis ticks
+    pub fn format_length(&self, units: f64) -> String {
+        let meters = self.units_to_meters(units);
+        if meters == 0.0 {
+            return "0 m".to_owned();
+        }
+        let (scale, unit) = PREFIXES
+            .iter()
+            .copied()
+            .find(|&(scale, _)| meters.abs() >= scale)
+            .unwrap_or(PREFIXES[PREFIXES.len() - 1]);
+        // Round away floating point noise like 5.000000000000001
+        let value = (meters / scale * 1e6).round() / 1e6;
+        format!("{value} {unit}")
+    }
+
+    /// Ticks between min and max world units with their labels, a round number of meters
+    /// apart: 1, 2 or 5 times a power of ten. There are at most about max_ticks of them.
+    pub fn axis_ticks(&self, min: f64, max: f64, max_ticks: usize) -> Vec<(f64, String)> {
+        let [min, max] = [self.units_to_meters(min), self.units_to_meters(max)];
+        let [min, max] = [min.min(max), min.max(max)];
+        let span = max - min;
+        if max_ticks == 0 || !span.is_finite() || span <= 0.0 {
+            return vec![];
+        }
+
+        let rough = span / max_ticks as f64;
+        let power = 10f64.powf(rough.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|multiple| multiple * power)
+            .find(|&step| step >= rough)
+            .unwrap_or(10.0 * power);
+
+        let [first, last] = [(min / step).ceil() as i64, (max / step).floor() as i64];
+        (first..=last)
+            .map(|idx| {
+                let units = self.meters_to_units(idx as f64 * step);
+                (units, self.format_length(units))
+            })
+            .collect()
+    }
+
+    pub fn params(&self) -> WorldScaleParams {
+        WorldScaleParams {
+            units_per_meter: self.units_per_meter as f32,
+            meters_per_unit: (1.0 / self.units_per_meter) as f32,
+            time_scale: self.time_scale as f32,
+            _padding: 0.0,
+        }
+    }
+
+    /// A uniform holding WorldScaleParams, visible to every shader stage
+    /// Keep it current with Uniform::update(renderer, scale.params()).
+    pub fn uniform<P: UiPlatform>(&self, renderer: &Renderer<P>) -> Uniform<WorldScaleParams> {
+        let label = Some("aftgraphs::units::WorldScale");
+        let layout = BindGroupLayoutBuilder::new()
+            .with_label(label)
+            .with_entry(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: BINDING_UNIFORM_BUFFER,
+                count: None,
+            })
+            .build(renderer);
+        UniformBuilder::new()
+            .with_label(label)
+            .with_bind_group_layout(layout)
+            .with_data(self.params())
+            .build(renderer)
+    }
+}
+
+/// Label the bottom and left edges of the window with ticks in physical units along the
+/// world space policy fits to it, see Renderer::set_world_scale
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn draw_axes(ui: &imgui::Ui, scale: &WorldScale, policy: AspectPolicy) {
+    const TICK_LENGTH: f32 = 6.0;
+    /// Logical pixels per tick, so labels do not overlap
+    const TICK_SPACING: f32 = 120.0;
+    const COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+
+    let [width, height] = ui.io().display_size;
+    if width < 1.0 || height < 1.0 {
+        return;
+    }
+    let aspect_ratio = (width / height) as f64;
+    let [min_x, min_y] = policy.ndc_to_world((-1.0, -1.0), aspect_ratio);
+    let [max_x, max_y] = policy.ndc_to_world((1.0, 1.0), aspect_ratio);
+    let line_height = ui.text_line_height();
+    let draw_list = ui.get_foreground_draw_list();
+
+    let ticks = (width / TICK_SPACING) as usize;
+    for (x, label) in scale.axis_ticks(min_x as f64, max_x as f64, ticks) {
+        let x = (x as f32 - min_x) / (max_x - min_x) * width;
+        draw_list
+            .add_line([x, height], [x, height - TICK_LENGTH], COLOR)
+            .build();
+        draw_list.add_text([x + 2.0, height - TICK_LENGTH - line_height], COLOR, &label);
+    }
+
+    let ticks = (height / TICK_SPACING) as usize;
+    for (y, label) in scale.axis_ticks(min_y as f64, max_y as f64, ticks) {
+        // Pixel rows go down, world space up
+        let y = (max_y - y as f32) / (max_y - min_y) * height;
+        draw_list
+            .add_line([0.0, y], [TICK_LENGTH, y], COLOR)
+            .build();
+        draw_list.add_text([TICK_LENGTH + 2.0, y - line_height / 2.0], COLOR, &label);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        // 100 world units per meter, at half speed
+        let scale = WorldScale::new(100.0, 0.5);
+        assert_eq!(250.0, scale.meters_to_units(2.5));
+        assert_eq!(0.5, scale.units_to_meters(50.0));
+        assert_eq!(-100.0, scale.acceleration(-4.0));
+        assert_eq!("2.5 m", scale.format_length(250.0));
+        assert_eq!("5 cm", scale.format_length(5.0));
+        assert_eq!("1.5 km", scale.format_length(150_000.0));
+        assert_eq!("0 m", scale.format_length(0.0));
+    }
+
+    #[test]
+    fn axis_ticks() {
+        let scale = WorldScale::new(100.0, 1.0);
+        let ticks = scale.axis_ticks(-30.0, 260.0, 5);
+        let units: Vec<f64> = ticks.iter().map(|&(units, _)| units).collect();
+        assert_eq!(vec![0.0, 100.0, 200.0], units);
+        assert_eq!("2 m", ticks[2].1);
+
+        let labels: Vec<String> = scale
+            .axis_ticks(0.0, 10.0, 4)
+            .into_iter()
+            .map(|(_, label)| label)
+            .collect();
+        assert_eq!(vec!["0 m", "5 cm", "10 cm"], labels);
+        assert!(scale.axis_ticks(1.0, 1.0, 4).is_empty());
+    }
+}
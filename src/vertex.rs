@@ -1,4 +1,4 @@
-use crate::render::Renderer;
+use crate::render::{Allocation, Renderer, ResourceKind};
 use crate::ui::UiPlatform;
 use bytemuck::NoUninit;
 use std::ops::Range;
@@ -28,6 +28,7 @@ pub struct VertexBuffer<T: NoUninit> {
     attributes: Vec<wgpu::VertexAttribute>,
     vertices: Vec<T>,
     label: Option<String>,
+    allocation: Allocation,
 }
 
 pub struct VertexBufferGuard<'a, 'b, T: NoUninit, P: UiPlatform> {
@@ -42,6 +43,7 @@ pub struct IndexBuffer<T: num_traits::PrimInt + NoUninit> {
     indices: Vec<T>,
     format: wgpu::IndexFormat,
     label: Option<String>,
+    allocation: Allocation,
 }
 
 pub struct IndexBufferGuard<'a, 'b, T: num_traits::PrimInt + NoUninit, P: UiPlatform> {
@@ -65,13 +67,25 @@ pub struct InstanceBuffer<V: NoUninit, I: NoUninit> {
     instances: Vec<I>,
     instance_label: Option<String>,
     vertex_label: Option<String>,
+    vertex_allocation: Allocation,
+    instance_allocation: Allocation,
+    /// Set by InstanceBufferBuilder::with_depth_sort, sorts instances before they are buffered
+    depth_key: Option<fn(&I) -> f32>,
+    /// Indices of the instances in the order they are buffered, None if every instance is
+    /// buffered in its own order
+    order: Option<Vec<u32>>,
+    /// The instances in order, reused between writes
+    staging: Vec<I>,
+    /// Instances hidden with InstanceBufferGuard::set_visible, instances past its end are visible
+    hidden: Vec<bool>,
 }
 
 pub struct InstanceBufferGuard<'a, 'b, V: NoUninit, I: NoUninit, P: UiPlatform> {
     instance_buffer: &'a mut InstanceBuffer<V, I>,
     renderer: &'a Renderer<'b, P>,
     changed: bool,
-    old_length: usize,
+    /// Set when the visibility of instances changed, so only the draw order is buffered again
+    reordered: bool,
     old_vertices_length: usize,
 }
 
@@ -100,6 +114,7 @@ impl<T: num_traits::PrimInt + NoUninit> IndexBuffer<T> {
             });
 
         Self {
+            allocation: renderer.track_memory(ResourceKind::Buffer, label, buffer.size()),
             buffer,
             indices,
             format,
@@ -144,7 +159,7 @@ impl<T: num_traits::PrimInt + NoUninit> IndexBuffer<T> {
         0..self.indices.len() as u32
     }
 
-    pub fn bind<'a, 'b: 'a>(&'b self, render_pass: &mut RenderPass<'a>) {
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>) {
         render_pass.set_index_buffer(self.as_index_buffer(), self.format);
     }
 }
@@ -198,6 +213,8 @@ impl<T: NoUninit + num_traits::PrimInt, P: UiPlatform> Drop
 {
     fn drop(&mut self) {
         if self.changed {
+            self.renderer
+                .record_upload(std::mem::size_of_val(self.index_buffer.indices.as_slice()));
             if self.old_length != self.len() {
                 self.index_buffer.buffer =
                     self.renderer
@@ -207,6 +224,13 @@ impl<T: NoUninit + num_traits::PrimInt, P: UiPlatform> Drop
                             contents: bytemuck::cast_slice(self.as_slice()),
                             usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
                         });
+                self.index_buffer.allocation = self
+                    .renderer
+                    .track_memory(
+                    ResourceKind::Buffer,
+                    self.index_buffer.label.as_deref(),
+                    self.index_buffer.buffer.size(),
+                );
             } else {
                 self.renderer.queue.write_buffer(
                     &self.index_buffer.buffer,
@@ -262,7 +286,7 @@ impl<T: NoUninit> VertexBuffer<T> {
         0..self.vertices.len() as u32
     }
 
-    pub fn bind<'a, 'b: 'a>(&'b self, render_pass: &mut RenderPass<'a>, slot: u32) {
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, slot: u32) {
         render_pass.set_vertex_buffer(slot, self.as_vertex_buffer());
     }
 }
@@ -306,6 +330,8 @@ impl<T: NoUninit, P: UiPlatform> DerefMut for VertexBufferGuard<'_, '_, T, P> {
 impl<T: NoUninit, P: UiPlatform> Drop for VertexBufferGuard<'_, '_, T, P> {
     fn drop(&mut self) {
         if self.changed {
+            self.renderer
+                .record_upload(std::mem::size_of_val(self.vertex_buffer.vertices.as_slice()));
             if self.old_length != self.len() {
                 self.vertex_buffer.buffer =
                     self.renderer
@@ -315,6 +341,13 @@ impl<T: NoUninit, P: UiPlatform> Drop for VertexBufferGuard<'_, '_, T, P> {
                             contents: bytemuck::cast_slice(self.as_slice()),
                             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
                         });
+                self.vertex_buffer.allocation = self
+                    .renderer
+                    .track_memory(
+                    ResourceKind::Buffer,
+                    self.vertex_buffer.label.as_deref(),
+                    self.vertex_buffer.buffer.size(),
+                );
             } else {
                 self.renderer.queue.write_buffer(
                     &self.vertex_buffer.buffer,
@@ -326,6 +359,37 @@ impl<T: NoUninit, P: UiPlatform> Drop for VertexBufferGuard<'_, '_, T, P> {
     }
 }
 
+/// Indices of instances sorted back to front, largest key first, keeping the order of equal
+/// keys. None when they are already sorted.
+fn back_to_front<I>(instances: &[I], key: impl Fn(&I) -> f32) -> Option<Vec<u32>> {
+    let keys: Vec<f32> = instances.iter().map(key).collect();
+    if keys.windows(2).all(|pair| pair[0] >= pair[1]) {
+        return None;
+    }
+
+    let mut order: Vec<u32> = (0..instances.len() as u32).collect();
+    order.sort_by(|&a, &b| keys[b as usize].total_cmp(&keys[a as usize]));
+    Some(order)
+}
+
+/// Indices of the instances left visible by hidden, back to front by key if given, in the
+/// order they are buffered. None when that is every instance in its own order.
+fn draw_order<I>(
+    instances: &[I],
+    hidden: &[bool],
+    key: Option<impl Fn(&I) -> f32>,
+) -> Option<Vec<u32>> {
+    let sorted = key.and_then(|key| back_to_front(instances, key));
+    let hidden = &hidden[..hidden.len().min(instances.len())];
+    if sorted.is_none() && !hidden.contains(&true) {
+        return None;
+    }
+
+    let order = sorted.unwrap_or_else(|| (0..instances.len() as u32).collect());
+    let is_hidden = |idx: u32| hidden.get(idx as usize).copied().unwrap_or(false);
+    Some(order.into_iter().filter(|&idx| !is_hidden(idx)).collect())
+}
+
 impl<V: NoUninit, I: NoUninit> InstanceBuffer<V, I> {
     /// Create a guard to modify the InstanceBuffer
     /// When the guard drops, it wil buffer the data to the GPU
@@ -333,18 +397,102 @@ impl<V: NoUninit, I: NoUninit> InstanceBuffer<V, I> {
         &'a mut self,
         renderer: &'a Renderer<'b, P>,
     ) -> InstanceBufferGuard<'a, 'b, V, I, P> {
-        let old_length = self.instances.len();
         let old_vertices_length = self.vertices.len();
 
         InstanceBufferGuard {
             instance_buffer: self,
             renderer,
             changed: false,
-            old_length,
+            reordered: false,
             old_vertices_length,
         }
     }
 
+    /// Replace the instances with f(0), f(1), ..., f(count - 1) computed in parallel,
+    /// then buffer them to the GPU once
+    /// Uses rayon natively and a pool of web workers on WASM, for simulations
+    /// that compute hundreds of thousands of instances per frame on the CPU.
+    /// f is owned so that it can be sent to the workers, which the browser's main
+    /// thread awaits without blocking.
+    pub async fn par_write<P: UiPlatform, F>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        count: usize,
+        f: F,
+    ) where
+        I: Send + 'static,
+        F: Fn(usize) -> I + Send + Sync + 'static,
+    {
+        profiling::scope!("instance par_write");
+        let mut guard = self.modify(renderer);
+        crate::parallel::fill(guard.instances_vec(), count, f).await;
+    }
+
+    /// Buffer the visible instances to the GPU, back to front by key if given, reallocating
+    /// the buffer if their count changed. The instances themselves keep their order.
+    fn write_instances<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        key: Option<impl Fn(&I) -> f32>,
+    ) {
+        self.order = draw_order(&self.instances, &self.hidden, key);
+        let data = match self.order {
+            Some(ref order) => {
+                self.staging.clear();
+                self.staging
+                    .extend(order.iter().map(|&idx| self.instances[idx as usize]));
+                self.staging.as_slice()
+            }
+            None => self.instances.as_slice(),
+        };
+        let contents: &[u8] = bytemuck::cast_slice(data);
+        renderer.record_upload(contents.len());
+
+        // The buffer fits every instance, so hiding some does not reallocate it
+        let size = std::mem::size_of_val(self.instances.as_slice()) as u64;
+        let size = wgpu::util::align_to(size, wgpu::COPY_BUFFER_ALIGNMENT)
+            .max(wgpu::COPY_BUFFER_ALIGNMENT);
+        if self.instance_buffer.size() != size {
+            self.instance_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                label: self.instance_label.as_deref(),
+                size,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.instance_allocation =
+                renderer.track_memory(ResourceKind::Buffer, self.instance_label.as_deref(), size);
+        }
+        renderer
+            .queue
+            .write_buffer(&self.instance_buffer, 0, contents);
+    }
+
+    /// Buffer the instances to the GPU back to front by key, larger keys being farther
+    /// away, for keys that depend on the camera, like the distance to the eye, so
+    /// overlapping alpha blended instances are drawn in the right order. Call it every
+    /// frame after modifying the instances. Only the buffered copy is sorted, indices into
+    /// the instances stay valid, but @builtin(instance_index) counts in the sorted order.
+    pub fn sort_by_depth<P: UiPlatform>(
+        &mut self,
+        renderer: &Renderer<'_, P>,
+        key: impl Fn(&I) -> f32,
+    ) {
+        profiling::scope!("instance sort_by_depth");
+        let order = draw_order(&self.instances, &self.hidden, Some(&key));
+        if order != self.order {
+            self.write_instances(renderer, Some(key));
+        }
+    }
+
+    /// Buffer instances back to front by key every time they are modified, or stop with None
+    pub fn set_depth_sort(&mut self, key: Option<fn(&I) -> f32>) {
+        self.depth_key = key;
+    }
+
+    pub fn is_visible(&self, idx: usize) -> bool {
+        !self.hidden.get(idx).copied().unwrap_or(false)
+    }
+
     pub fn vertex_layout(&self) -> wgpu::VertexBufferLayout<'_> {
         wgpu::VertexBufferLayout {
             array_stride: self.vertex_array_stride,
@@ -395,11 +543,13 @@ impl<V: NoUninit, I: NoUninit> InstanceBuffer<V, I> {
         self.instance_buffer.slice(bounds)
     }
 
+    /// The buffered instances, every visible instance in the order they are drawn
     pub fn range_instance(&self) -> Range<u32> {
-        0..self.instances.len() as u32
+        let len = self.order.as_ref().map_or(self.instances.len(), Vec::len);
+        0..len as u32
     }
 
-    pub fn bind<'a, 'b: 'a>(&'b self, render_pass: &mut RenderPass<'a>, v_slot: u32, i_slot: u32) {
+    pub fn bind(&self, render_pass: &mut RenderPass<'_>, v_slot: u32, i_slot: u32) {
         render_pass.set_vertex_buffer(v_slot, self.as_vertex_buffer());
         render_pass.set_vertex_buffer(i_slot, self.as_instance_buffer());
     }
@@ -455,6 +605,48 @@ impl<V: NoUninit, I: NoUninit, P: UiPlatform> InstanceBufferGuard<'_, '_, V, I,
         self.changed = true;
         &mut self.instance_buffer.instances
     }
+
+    /// Show or hide the instances in range, without touching the instance data.
+    /// Hidden instances are left out of the buffer, so drawing range_instance skips them in
+    /// one draw call. The mask follows instance indices, which a depth sort leaves in place.
+    pub fn set_visible<R: RangeBounds<usize>>(&mut self, range: R, visible: bool) {
+        use std::ops::Bound;
+
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.instance_buffer.instances.len(),
+        };
+        if start >= end {
+            return;
+        }
+
+        self.reordered = true;
+        let hidden = &mut self.instance_buffer.hidden;
+        if hidden.len() < end {
+            hidden.resize(end, false);
+        }
+        hidden[start..end].fill(!visible);
+    }
+
+    /// Set the visibility of every instance from a mask, instances past its end are visible
+    pub fn set_visibility_mask(&mut self, mask: &[bool]) {
+        self.reordered = true;
+        let hidden = &mut self.instance_buffer.hidden;
+        hidden.clear();
+        hidden.extend(mask.iter().map(|&visible| !visible));
+    }
+
+    /// Show every instance again
+    pub fn show_all(&mut self) {
+        self.reordered = true;
+        self.instance_buffer.hidden.clear();
+    }
 }
 
 impl<V: NoUninit, I: NoUninit, P: UiPlatform> Drop
@@ -462,6 +654,9 @@ impl<V: NoUninit, I: NoUninit, P: UiPlatform> Drop
 {
     fn drop(&mut self) {
         if self.changed {
+            self.renderer.record_upload(std::mem::size_of_val(
+                self.instance_buffer.vertices.as_slice(),
+            ));
             if self.old_vertices_length != self.instance_buffer.vertices.len() {
                 self.instance_buffer.vertex_buffer =
                     self.renderer
@@ -471,6 +666,13 @@ impl<V: NoUninit, I: NoUninit, P: UiPlatform> Drop
                             contents: bytemuck::cast_slice(self.vertices.as_slice()),
                             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
                         });
+                self.instance_buffer.vertex_allocation = self
+                    .renderer
+                    .track_memory(
+                    ResourceKind::Buffer,
+                    self.vertex_label.as_deref(),
+                    self.instance_buffer.vertex_buffer.size(),
+                );
             } else {
                 self.renderer.queue.write_buffer(
                     &self.instance_buffer.vertex_buffer,
@@ -478,23 +680,49 @@ impl<V: NoUninit, I: NoUninit, P: UiPlatform> Drop
                     bytemuck::cast_slice(&self.instance_buffer.vertices),
                 );
             }
+        }
 
-            if self.old_length != self.instance_buffer.instances.len() {
-                self.instance_buffer.instance_buffer =
-                    self.renderer
-                        .device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: self.instance_label.as_deref(),
-                            contents: bytemuck::cast_slice(self.instances.as_slice()),
-                            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                        });
-            } else {
-                self.renderer.queue.write_buffer(
-                    &self.instance_buffer.instance_buffer,
-                    0,
-                    bytemuck::cast_slice(&self.instance_buffer.instances),
-                );
-            }
+        if self.changed || self.reordered {
+            let key = self.instance_buffer.depth_key;
+            self.instance_buffer.write_instances(self.renderer, key);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sorts_back_to_front() {
+        let instances = [(1.0f32, 'a'), (3.0, 'b'), (2.0, 'c'), (3.0, 'd')];
+        let order = back_to_front(&instances, |instance| instance.0);
+        assert_eq!(Some(vec![1, 3, 2, 0]), order);
+
+        let sorted: Vec<_> = order
+            .unwrap()
+            .iter()
+            .map(|&idx| instances[idx as usize])
+            .collect();
+        assert_eq!(None, back_to_front(&sorted, |instance| instance.0));
+    }
+
+    #[test]
+    fn leaves_hidden_instances_out() {
+        let instances = [(1.0f32, 'a'), (3.0, 'b'), (2.0, 'c'), (3.0, 'd')];
+        let key = |instance: &(f32, char)| instance.0;
+        let unsorted = None::<fn(&(f32, char)) -> f32>;
+        assert_eq!(None, draw_order(&instances, &[], unsorted));
+        assert_eq!(None, draw_order(&instances, &[false; 8], unsorted));
+        assert_eq!(
+            Some(vec![0, 2]),
+            draw_order(&instances, &[false, true, false, true], unsorted)
+        );
+        // Hidden instances follow their index through the sort
+        assert_eq!(
+            Some(vec![3, 2, 0]),
+            draw_order(&instances, &[false, true], Some(key))
+        );
+        assert_eq!(Some(vec![]), draw_order(&instances, &[true; 4], Some(key)));
+    }
+}
@@ -1,5 +1,8 @@
 use super::{InstanceBuffer, VertexBuffer};
-use crate::{render::Renderer, ui::UiPlatform};
+use crate::{
+    render::{Renderer, ResourceKind},
+    ui::UiPlatform,
+};
 use bytemuck::NoUninit;
 use wgpu::util::DeviceExt;
 
@@ -52,6 +55,7 @@ impl<'a, T: NoUninit> VertexBufferBuilder<'a, T> {
             });
 
         VertexBuffer {
+            allocation: renderer.track_memory(ResourceKind::Buffer, label, buffer.size()),
             buffer,
             array_stride,
             step_mode,
@@ -146,10 +150,12 @@ pub struct InstanceBufferBuilder<'a, V: NoUninit, I: NoUninit> {
     instance_array_stride: wgpu::BufferAddress,
     vertex_step_mode: wgpu::VertexStepMode,
     instance_step_mode: wgpu::VertexStepMode,
+    label: Option<&'a str>,
     v_label: Option<&'a str>,
     i_label: Option<&'a str>,
     v_data: Vec<V>,
     i_data: Vec<I>,
+    depth_key: Option<fn(&I) -> f32>,
 }
 
 impl<V: NoUninit, I: NoUninit> Default for InstanceBufferBuilder<'_, V, I> {
@@ -167,10 +173,12 @@ impl<'a, V: NoUninit, I: NoUninit> InstanceBufferBuilder<'a, V, I> {
             instance_array_stride: std::mem::size_of::<I>() as wgpu::BufferAddress,
             vertex_step_mode: wgpu::VertexStepMode::Vertex,
             instance_step_mode: wgpu::VertexStepMode::Instance,
+            label: None,
             v_label: None,
             i_label: None,
             v_data: vec![],
             i_data: vec![],
+            depth_key: None,
         }
     }
 
@@ -184,12 +192,23 @@ impl<'a, V: NoUninit, I: NoUninit> InstanceBufferBuilder<'a, V, I> {
             instance_array_stride,
             vertex_step_mode,
             instance_step_mode,
+            label,
             v_label,
             i_label,
             v_data,
             i_data,
+            depth_key,
         } = self;
 
+        let v_label = v_label
+            .map(str::to_owned)
+            .or_else(|| label.map(|label| format!("{label}::vertices")));
+        let i_label = i_label
+            .map(str::to_owned)
+            .or_else(|| label.map(|label| format!("{label}::instances")));
+        let v_label = v_label.as_deref();
+        let i_label = i_label.as_deref();
+
         let vertex_buffer = renderer
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -206,7 +225,17 @@ impl<'a, V: NoUninit, I: NoUninit> InstanceBufferBuilder<'a, V, I> {
                     usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
                 });
 
-        InstanceBuffer {
+        let mut buffer = InstanceBuffer {
+            vertex_allocation: renderer.track_memory(
+                ResourceKind::Buffer,
+                v_label,
+                vertex_buffer.size(),
+            ),
+            instance_allocation: renderer.track_memory(
+                ResourceKind::Buffer,
+                i_label,
+                instance_buffer.size(),
+            ),
             vertex_buffer,
             instance_buffer,
             vertex_array_stride,
@@ -219,7 +248,24 @@ impl<'a, V: NoUninit, I: NoUninit> InstanceBufferBuilder<'a, V, I> {
             instances: i_data,
             instance_label: i_label.map(String::from),
             vertex_label: v_label.map(String::from),
+            depth_key,
+            order: None,
+            staging: vec![],
+            hidden: vec![],
+        };
+        if depth_key.is_some() {
+            buffer.write_instances(renderer, depth_key);
         }
+        buffer
+    }
+
+    /// Buffer the instances back to front by key, larger keys being farther away.
+    /// They are sorted whenever they are modified, for alpha blended instances that overlap,
+    /// while the instances themselves keep their order.
+    /// Use InstanceBuffer::sort_by_depth for keys that change every frame, like the distance to a camera.
+    pub fn with_depth_sort(mut self, key: fn(&I) -> f32) -> Self {
+        self.depth_key = Some(key);
+        self
     }
 
     /// Sets the initial vertices of the buffer.
@@ -303,6 +349,13 @@ impl<'a, V: NoUninit, I: NoUninit> InstanceBufferBuilder<'a, V, I> {
         self
     }
 
+    /// Sets a label for both buffers, suffixed with ::vertices and ::instances.
+    /// Explicit vertex or instance labels take precedence
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
     /// Sets the label of the vertices' VertexBufferLayout and the VertexBuffer, overriding any previous value
     pub fn with_vertex_label(mut self, label: Option<&'a str>) -> Self {
         self.v_label = label;
@@ -31,6 +31,12 @@ extern "C" {
 
     #[wasm_bindgen(catch, js_name = createWorker)]
     async fn create_worker(memory: &JsValue, ptr: &JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_name = fetchText)]
+    async fn fetch_text_js(url: &str) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_name = fetchBytes)]
+    async fn fetch_bytes_js(url: &str) -> Result<JsValue, JsValue>;
 }
 
 pub async fn wait(time: f64) {
@@ -38,6 +44,21 @@ pub async fn wait(time: f64) {
     promise_wait(duration.as_millis() as i32).await.unwrap();
 }
 
+/// Fetch url as text, the error is the message of the failed request
+pub(crate) async fn fetch_text(url: &str) -> Result<String, String> {
+    let text = fetch_text_js(url).await.map_err(|err| format!("{err:?}"))?;
+    text.as_string()
+        .ok_or_else(|| "response was not text".to_owned())
+}
+
+/// Fetch url as bytes, the error is the message of the failed request
+pub(crate) async fn fetch_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let bytes = fetch_bytes_js(url)
+        .await
+        .map_err(|err| format!("{err:?}"))?;
+    Ok(js_sys::Uint8Array::new(&bytes).to_vec())
+}
+
 pub type Handle = web_sys::Worker;
 pub type SpawnError = JsValue;
 
@@ -61,17 +82,28 @@ pub fn sim_main<T: Simulation>(inputs: Inputs) {
 
     log::debug!("aftgraphs::sim_main entered");
 
-    let html_window = web_sys::window().expect("aftgraphs::sim_main: no global `window` exists");
-    let document = html_window
-        .document()
-        .expect("aftgraphs::sim_main: should have a document on window");
-    let _body = document
-        .body()
-        .expect("aftgraphs::sim_main: document should have a body");
-
-    let event_loop = EventLoop::<InputEvent>::with_user_event()
-        .build()
-        .expect("aftgraphs::sim_main: failed to build event loop");
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        crate::error::report(
+            "aftgraphs::sim_main",
+            "no global `window` with a document exists",
+        );
+        return;
+    };
+    if document.body().is_none() {
+        crate::error::report("aftgraphs::sim_main", "the document has no body");
+        return;
+    }
+
+    let event_loop = match EventLoop::<InputEvent>::with_user_event().build() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            crate::error::report(
+                "aftgraphs::sim_main",
+                format!("failed to build event loop: {e}"),
+            );
+            return;
+        }
+    };
     event_loop.set_control_flow(ControlFlow::Poll);
 
     document.set_title(inputs.simulation.name.as_str());
@@ -82,8 +114,7 @@ pub fn sim_main<T: Simulation>(inputs: Inputs) {
             .run_display(inputs)
             .await
         {
-            log::error!("aftgraphs::sim_main: simulation failed: {e}");
-            panic!("aftgraphs::sim_main: simulation failed: {e}");
+            crate::error::report("aftgraphs::sim_main", format!("simulation failed: {e}"));
         }
     });
 }
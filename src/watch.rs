@@ -0,0 +1,428 @@
+use crossbeam::channel;
+use notify::{RecursiveMode, Watcher as _};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// A Watch, and where to send the changes it asked for
+struct Subscriber {
+    id: u64,
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+    changes: channel::Sender<PathBuf>,
+}
+
+impl Subscriber {
+    fn wants(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file == path)
+            || self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+}
+
+/// The notify watcher of the process, and how many Watches need each watched directory
+/// Kept apart from SUBSCRIBERS, which notify's thread locks while watch waits on it.
+struct Shared {
+    watcher: Option<notify::RecommendedWatcher>,
+    watched: BTreeMap<(PathBuf, bool), usize>,
+}
+
+static SUBSCRIBERS: RwLock<Vec<Subscriber>> = RwLock::new(Vec::new());
+static SHARED: Mutex<Shared> = Mutex::new(Shared {
+    watcher: None,
+    watched: BTreeMap::new(),
+});
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Send the paths of event to the Watches that asked for them
+fn dispatch(event: notify::Result<notify::Event>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            log::warn!("aftgraphs::watch::dispatch: {e}");
+            return;
+        }
+    };
+    // Reading a file opens and closes it, writing one also reports a modification
+    if event.kind.is_access() || event.kind.is_other() {
+        return;
+    }
+
+    let subscribers = SUBSCRIBERS
+        .read()
+        .expect("aftgraphs::watch::dispatch: poisoned lock");
+    for path in &event.paths {
+        for subscriber in subscribers
+            .iter()
+            .filter(|subscriber| subscriber.wants(path))
+        {
+            let _ = subscriber.changes.send(path.clone());
+        }
+    }
+}
+
+/// The absolute path of a file that may not exist yet, as notify reports it
+fn absolute(path: &Path) -> PathBuf {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_owned();
+    };
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    std::fs::canonicalize(dir)
+        .map(|dir| dir.join(name))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
+/// Changes to a set of files and directories, from the shared file watcher
+/// Everything in the crate reacting to files, shaders, scripts and --watch, subscribes
+/// to one notify watcher instead of polling modification times.
+#[derive(Debug)]
+pub(crate) struct Watch {
+    id: u64,
+    changes: channel::Receiver<PathBuf>,
+    /// Directories given to notify, and if they are watched recursively
+    watched: Vec<(PathBuf, bool)>,
+}
+
+impl Watch {
+    /// Watch files, which need not exist yet, and everything under dirs
+    pub(crate) fn new(
+        files: impl IntoIterator<Item = PathBuf>,
+        dirs: impl IntoIterator<Item = PathBuf>,
+    ) -> Self {
+        let files: Vec<PathBuf> = files.into_iter().map(|file| absolute(&file)).collect();
+        let dirs: Vec<PathBuf> = dirs
+            .into_iter()
+            .map(|dir| std::fs::canonicalize(&dir).unwrap_or(dir))
+            .collect();
+        // Editors often replace files instead of writing to them, so files are seen
+        // through their directory
+        let mut watched: Vec<(PathBuf, bool)> = files
+            .iter()
+            .filter_map(|file| Some((file.parent()?.to_owned(), false)))
+            .chain(dirs.iter().map(|dir| (dir.clone(), true)))
+            .collect();
+        watched.sort();
+        watched.dedup();
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (sender, changes) = channel::unbounded();
+        SUBSCRIBERS
+            .write()
+            .expect("aftgraphs::watch::Watch::new: poisoned lock")
+            .push(Subscriber {
+                id,
+                files,
+                dirs,
+                changes: sender,
+            });
+
+        let mut shared = SHARED
+            .lock()
+            .expect("aftgraphs::watch::Watch::new: poisoned lock");
+        if shared.watcher.is_none() {
+            match notify::recommended_watcher(dispatch) {
+                Ok(watcher) => shared.watcher = Some(watcher),
+                Err(e) => log::error!("aftgraphs::watch::Watch::new: {e}"),
+            }
+        }
+        for (dir, recursive) in &watched {
+            let count = shared.watched.entry((dir.clone(), *recursive)).or_default();
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+            let mode = match recursive {
+                true => RecursiveMode::Recursive,
+                false => RecursiveMode::NonRecursive,
+            };
+            if let Some(Err(e)) = shared
+                .watcher
+                .as_mut()
+                .map(|watcher| watcher.watch(dir, mode))
+            {
+                log::warn!(
+                    "aftgraphs::watch::Watch::new: failed to watch {}: {e}",
+                    dir.display()
+                );
+            }
+        }
+
+        Self {
+            id,
+            changes,
+            watched,
+        }
+    }
+
+    /// Paths changed since the last call, each once
+    pub(crate) fn changes(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.changes.try_iter().collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// If a watched path changed since the last call
+    pub(crate) fn changed(&self) -> bool {
+        !self.changes().is_empty()
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        SUBSCRIBERS
+            .write()
+            .expect("aftgraphs::watch::Watch::drop: poisoned lock")
+            .retain(|subscriber| subscriber.id != self.id);
+
+        let mut shared = SHARED
+            .lock()
+            .expect("aftgraphs::watch::Watch::drop: poisoned lock");
+        for key in &self.watched {
+            let Some(count) = shared.watched.get_mut(key) else {
+                continue;
+            };
+            *count -= 1;
+            if *count > 0 {
+                continue;
+            }
+            shared.watched.remove(key);
+            // Another Watch may still need the directory in the other mode
+            let other = (key.0.clone(), !key.1);
+            if shared.watched.contains_key(&other) {
+                continue;
+            }
+            if let Some(watcher) = shared.watcher.as_mut() {
+                let _ = watcher.unwatch(&key.0);
+            }
+        }
+    }
+}
+
+/// Time without changes before Watcher reports them, editors often write a file in steps
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Files that changed since the last poll of a Watcher
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct Changes {
+    /// The inputs TOML changed
+    pub inputs: bool,
+    /// Every added, removed or modified file
+    pub paths: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+/// The files --watch restarts the simulation for: the inputs TOML, the shaders
+/// next to it and every file under the asset directory given to --watch.
+/// Shaders embedded with include_wgsl! still need a rebuild, restarting picks up
+/// the ones loaded at runtime, e.g. through an AssetLoader.
+pub(crate) struct Watcher {
+    inputs: Option<PathBuf>,
+    /// Directory of the inputs TOML, its shaders are watched
+    shaders: Option<PathBuf>,
+    assets: Option<PathBuf>,
+    watch: Watch,
+    pending: Vec<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+/// If path is under root outside of hidden directories and build output
+fn visible(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|relative| {
+        relative.parent().is_none_or(|dirs| {
+            dirs.components().all(|dir| {
+                let dir = dir.as_os_str().to_string_lossy();
+                !dir.starts_with('.') && dir != "target"
+            })
+        })
+    })
+}
+
+impl Watcher {
+    pub(crate) fn new(inputs: Option<PathBuf>, assets: Option<PathBuf>) -> Self {
+        let shaders = inputs
+            .as_ref()
+            .and_then(|inputs| inputs.parent())
+            .map(|dir| match dir.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => dir,
+            })
+            .and_then(|dir| std::fs::canonicalize(dir).ok());
+        let assets = assets.map(|assets| std::fs::canonicalize(&assets).unwrap_or(assets));
+        let watch = Watch::new(
+            inputs.clone(),
+            shaders.iter().chain(assets.as_ref()).cloned(),
+        );
+        Self {
+            inputs: inputs.map(|inputs| absolute(&inputs)),
+            shaders,
+            assets,
+            watch,
+            pending: vec![],
+            last_change: None,
+        }
+    }
+
+    /// The watcher requested with --watch, if any
+    pub(crate) async fn startup(inputs: &crate::input::Inputs) -> Option<Self> {
+        let assets = crate::cli::ARGUMENTS.read().await.watch.clone()?;
+        if inputs.source.is_none() {
+            log::warn!(
+                "aftgraphs::watch::Watcher::startup: inputs without a source file are not watched, sim_main only records it in debug builds"
+            );
+        }
+
+        let watcher = Self::new(inputs.source.clone(), assets.clone());
+        log::info!(
+            "aftgraphs::watch::Watcher::startup: watching {}",
+            inputs
+                .source
+                .iter()
+                .chain(assets.as_ref())
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Some(watcher)
+    }
+
+    /// If a change to path restarts the simulation
+    fn wanted(&self, path: &Path) -> bool {
+        let is_shader = path.extension().is_some_and(|ext| ext == "wgsl");
+        self.inputs.as_deref() == Some(path)
+            || self
+                .assets
+                .as_ref()
+                .is_some_and(|assets| visible(assets, path))
+            || (is_shader && self.shaders.as_ref().is_some_and(|dir| visible(dir, path)))
+    }
+
+    /// The files changed since the last poll, once they stopped changing for SETTLE_TIME
+    pub(crate) fn poll(&mut self) -> Changes {
+        let changes: Vec<PathBuf> = self
+            .watch
+            .changes()
+            .into_iter()
+            .filter(|path| self.wanted(path))
+            .collect();
+        if !changes.is_empty() {
+            self.pending.extend(changes);
+            self.last_change = Some(Instant::now());
+        }
+        if self
+            .last_change
+            .is_none_or(|last_change| last_change.elapsed() < SETTLE_TIME)
+        {
+            return Changes::default();
+        }
+
+        self.last_change = None;
+        let mut paths = std::mem::take(&mut self.pending);
+        paths.sort();
+        paths.dedup();
+        Changes {
+            inputs: self
+                .inputs
+                .as_ref()
+                .is_some_and(|inputs| paths.contains(inputs)),
+            paths,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    /// Changes of watch, waiting a little for notify to see them
+    fn wait(watch: &Watch, expected: usize) -> Vec<PathBuf> {
+        let start = Instant::now();
+        let mut changes = vec![];
+        while start.elapsed() < Duration::from_secs(5) {
+            changes.extend(watch.changes());
+            changes.sort();
+            changes.dedup();
+            if changes.len() >= expected {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        changes
+    }
+
+    #[test]
+    fn watches_share_one_watcher() {
+        let dir = std::env::temp_dir().join(format!("aftgraphs-watch-{}", std::process::id()));
+        let assets = dir.join("assets");
+        fs::create_dir_all(&assets).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+        let assets = dir.join("assets");
+        let shader = dir.join("shader.wgsl");
+        fs::write(&shader, "").unwrap();
+
+        let shaders = Watch::new([shader.clone()], []);
+        let everything = Watch::new([shader.clone()], [assets.clone()]);
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        fs::write(&shader, "changed").unwrap();
+        assert_eq!(vec![shader.clone()], wait(&shaders, 1));
+
+        let mesh = assets.join("mesh.obj");
+        fs::write(&mesh, "").unwrap();
+        assert_eq!(vec![mesh.clone(), shader.clone()], wait(&everything, 2));
+        assert!(!shaders.changes().contains(&mesh));
+
+        drop(everything);
+        fs::remove_file(&shader).unwrap();
+        assert_eq!(vec![shader], wait(&shaders, 1));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn watcher_sees_inputs_shaders_and_assets() {
+        let dir = std::env::temp_dir().join(format!("aftgraphs-watcher-{}", std::process::id()));
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+        let inputs = dir.join("inputs.toml");
+        fs::write(&inputs, "").unwrap();
+
+        let mut watcher = Watcher::new(Some(inputs.clone()), Some(dir.join("assets")));
+        let shader = dir.join("shader.wgsl");
+        let mesh = dir.join("assets").join("mesh.obj");
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        fs::write(dir.join("target").join("built.wgsl"), "ignored").unwrap();
+        fs::write(&inputs, "changed").unwrap();
+        fs::write(&shader, "").unwrap();
+        fs::write(&mesh, "").unwrap();
+
+        let start = Instant::now();
+        let mut changes = Changes::default();
+        while changes.paths.len() < 3 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(SETTLE_TIME);
+            let polled = watcher.poll();
+            changes.inputs |= polled.inputs;
+            changes.paths.extend(polled.paths);
+        }
+        changes.paths.sort();
+        changes.paths.dedup();
+        assert!(changes.inputs);
+        assert_eq!(vec![mesh, inputs, shader], changes.paths);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
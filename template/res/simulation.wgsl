@@ -0,0 +1,20 @@
+struct Float {
+    @align(16)
+    f: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> brightness: Float;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let x = f32(i32(in_vertex_index) - 1);
+    let y = f32(i32(in_vertex_index & 1u) * 2 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    // Dim gray at 0, white at 1
+    return vec4(mix(vec3(0.2), vec3(1.0), brightness.f), 1.0);
+}
@@ -0,0 +1,51 @@
+use aftgraphs::prelude::*;
+use aftgraphs_macros::{sim_main, sim_plugin};
+use std::collections::HashMap;
+
+/// Brightness is written by the crate from the input bound to it
+struct {{project-name | upper_camel_case}} {
+    pipeline: RenderPipeline,
+    uniforms: UniformSet,
+}
+
+impl Simulation for {{project-name | upper_camel_case}} {
+    async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
+        let module = include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/simulation.wgsl"));
+
+        let mut uniforms = UniformSetBuilder::new()
+            .with_label(Some("{{project-name | upper_camel_case}}::uniforms"));
+        let brightness = uniforms.add_field(ShaderStages::FRAGMENT, Float::new(1.0));
+        let uniforms = uniforms.build(renderer);
+        renderer.register_uniform_field("brightness", &uniforms, brightness);
+
+        let shader = ShaderBuilder::new()
+            .with_module(module)
+            .with_default_fs_entrypoint()
+            .build(renderer);
+
+        let pipeline = RenderPipelineBuilder::new()
+            .with_pipeline_label(Some("{{project-name | upper_camel_case}}::pipeline"))
+            .with_vertex_shader(shader)
+            .with_bind_group_layout(uniforms.bind_group_layout())
+            .build(renderer)
+            .expect("{{project-name | upper_camel_case}}::pipeline failed to build");
+
+        Self { pipeline, uniforms }
+    }
+
+    async fn on_input(&mut self, _event: InputEvent) {}
+
+    async fn render<P: UiPlatform>(
+        &mut self,
+        _renderer: &Renderer<'_, P>,
+        mut render_pass: RenderPass<'_>,
+        _inputs: &mut HashMap<String, InputValue>,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        self.uniforms.bind(&mut render_pass, 0);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+sim_main! { "/res/simulation.toml", {{project-name | upper_camel_case}} }
+sim_plugin! { "/res/simulation.toml", {{project-name | upper_camel_case}} }
@@ -0,0 +1,5 @@
+use {{crate_name}}::sim_main;
+
+fn main() {
+    sim_main();
+}
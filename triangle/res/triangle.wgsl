@@ -20,7 +20,7 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) ve
     return vec4<f32>(new_x, new_y, 0.0, 1.0);
 }
 
-@group(1) @binding(0)
+@group(0) @binding(1)
 var<uniform> color: Float;
 
 @fragment
@@ -1,41 +1,15 @@
-use aftgraphs::prelude::*;
-use aftgraphs_macros::sim_main;
-use std::{collections::HashMap, num::NonZeroU64};
-
-#[derive(Clone, Copy, PartialEq, Debug)]
-#[repr(C, align(16))]
-struct Float(f32);
-
-unsafe impl bytemuck::Zeroable for Float {}
-unsafe impl bytemuck::NoUninit for Float {}
+use aftgraphs::{animate, coords, prelude::*};
+use aftgraphs_macros::{sim_main, sim_plugin};
+use std::collections::HashMap;
 
+/// Rotation and color are written by the crate from the inputs bound to them
 struct TriangleSimulation {
     pipeline: RenderPipeline,
-    rotation: Uniform<Float>,
-    color: Uniform<Float>,
+    uniforms: UniformSet,
     mouse_enabled: bool,
     snap_rotation: Option<f32>,
-}
-
-impl TriangleSimulation {
-    fn update_inputs<P: UiPlatform>(
-        &mut self,
-        renderer: &Renderer<P>,
-        inputs: &HashMap<String, InputValue>,
-    ) {
-        if let Some(&InputValue::SLIDER(val)) = inputs.get("triangle inputs.rotation") {
-            let val = (val as f32).to_radians();
-            self.rotation.update(renderer, Float(val));
-        }
-
-        if let Some(&InputValue::SLIDER(val)) = inputs.get("triangle inputs.color") {
-            self.color.update(renderer, Float(val as f32));
-        }
-
-        if let Some(&InputValue::CHECKBOX(val)) = inputs.get("triangle inputs.mouseInput") {
-            self.mouse_enabled = val;
-        }
-    }
+    /// Turns the triangle to the last snap
+    rotation_tween: Option<Tween>,
 }
 
 impl Simulation for TriangleSimulation {
@@ -45,42 +19,47 @@ impl Simulation for TriangleSimulation {
         mut render_pass: RenderPass<'_>,
         inputs: &mut HashMap<String, InputValue>,
     ) {
-        self.update_inputs(renderer, inputs);
+        if let Some(&InputValue::CHECKBOX(val)) = inputs.get("triangle inputs.mouseInput") {
+            self.mouse_enabled = val;
+        }
 
         if let Some(snap) = self.snap_rotation.take() {
-            self.rotation.update(renderer, Float(snap.to_radians()));
+            let rotation = match inputs.get("triangle inputs.rotation") {
+                Some(&InputValue::SLIDER(rotation)) => rotation,
+                _ => 0.0,
+            };
+            let snap = animate::nearest_angle_degrees(rotation, snap as f64);
+            self.rotation_tween = Some(Tween::new(rotation, snap, 0.4, Easing::EaseInOut));
+        }
+
+        if let Some(ref mut tween) = self.rotation_tween {
+            let rotation = tween.update(renderer.clock.delta_time());
             inputs.insert(
                 "triangle inputs.rotation".to_owned(),
-                InputValue::SLIDER(snap as f64),
+                InputValue::SLIDER(rotation.rem_euclid(360.0)),
             );
+            if tween.is_done() {
+                self.rotation_tween = None;
+            }
         }
 
         render_pass.set_pipeline(&self.pipeline);
-        self.rotation.bind(&mut render_pass, 0);
-        self.color.bind(&mut render_pass, 1);
+        self.uniforms.bind(&mut render_pass, 0);
         render_pass.draw(0..3, 0..1);
     }
 
     async fn on_input(&mut self, input: InputEvent) {
         if let InputEvent::Mouse(state, button, position) = input {
             if self.mouse_enabled && state.is_pressed() && matches!(button, MouseButton::Left) {
-                let click_angle = position.1.atan2(position.0).to_degrees() as f32;
-                let click_angle = if click_angle <= 0.0 {
-                    360.0 + click_angle
-                } else {
-                    click_angle
-                };
+                // The triangle is drawn in NDC, so the angle is taken there too
+                let click_angle = coords::angle_degrees(position) as f32;
 
                 // Click angle is now [0, 360) position of the click
                 // We want the second (initially upwards) vertex to point there
                 // tri_rotation + 90 deg = rotation of 2nd vertex
                 // => snap_angle + 90 = click_angle
 
-                let snap_angle = if click_angle - 90.0 < 0.0 {
-                    click_angle + 270.0
-                } else {
-                    click_angle - 90.0
-                };
+                let snap_angle = (click_angle - 90.0).rem_euclid(360.0);
                 self.snap_rotation = Some(snap_angle);
             }
         }
@@ -89,43 +68,13 @@ impl Simulation for TriangleSimulation {
     async fn new<P: UiPlatform>(renderer: &Renderer<'_, P>) -> Self {
         let module = include_wgsl!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/triangle.wgsl"));
 
-        let rotation_layout = BindGroupLayoutBuilder::new()
-            .with_label(Some("TriangleSimulation::rotation"))
-            .with_entry(BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(16u64),
-                },
-                count: None,
-            })
-            .build(renderer);
-        let color_layout = BindGroupLayoutBuilder::new()
-            .with_label(Some("TriangleSimulation::color"))
-            .with_entry(BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(16u64),
-                },
-                count: None,
-            })
-            .build(renderer);
-
-        let rotation: Uniform<Float> = UniformBuilder::new()
-            .with_label(Some("TriangleSimulation::rotation"))
-            .with_bind_group_layout(rotation_layout)
-            .with_zero_data()
-            .build(renderer);
-        let color: Uniform<Float> = UniformBuilder::new()
-            .with_label(Some("TriangleSimulation::color"))
-            .with_bind_group_layout(color_layout)
-            .with_zero_data()
-            .build(renderer);
+        let mut uniforms =
+            UniformSetBuilder::new().with_label(Some("TriangleSimulation::uniforms"));
+        let rotation = uniforms.add_field(ShaderStages::VERTEX, Float::new(0.0));
+        let color = uniforms.add_field(ShaderStages::FRAGMENT, Float::new(0.0));
+        let uniforms = uniforms.build(renderer);
+        renderer.register_uniform_field("rotation", &uniforms, rotation);
+        renderer.register_uniform_field("color", &uniforms, color);
 
         let shader = ShaderBuilder::new()
             .with_module(module)
@@ -136,18 +85,19 @@ impl Simulation for TriangleSimulation {
             .with_layout_label(Some("TriangleSimulation::pipeline_layout"))
             .with_pipeline_label(Some("TriangleSimulation::pipeline"))
             .with_vertex_shader(shader)
-            .with_bind_group_layout(rotation.bind_group_layout())
-            .with_bind_group_layout(color.bind_group_layout())
-            .build(renderer);
+            .with_bind_group_layout(uniforms.bind_group_layout())
+            .build(renderer)
+            .expect("TriangleSimulation::pipeline failed to build");
 
         Self {
             pipeline,
-            rotation,
-            color,
+            uniforms,
             mouse_enabled: false,
             snap_rotation: None,
+            rotation_tween: None,
         }
     }
 }
 
 sim_main! { "/res/triangle.toml", TriangleSimulation }
+sim_plugin! { "/res/triangle.toml", TriangleSimulation }
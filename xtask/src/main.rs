@@ -0,0 +1,163 @@
+use clap::{Parser, Subcommand};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+/// Flags the wasm build needs for the shared memory the worker threads use
+const WASM_RUSTFLAGS: &str = "-C target-feature=+atomics,+bulk-memory,+mutable-globals";
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+/// Placeholder for the bindings module name in res/index.html and res/worker.js
+const NAME_PLACEHOLDER: &str = "{{}}";
+
+#[derive(Parser)]
+#[command(about = "Development tasks for the aftgraphs workspace")]
+struct Cli {
+    #[command(subcommand)]
+    task: Task,
+}
+
+#[derive(Subcommand)]
+enum Task {
+    /// Build a simulation for the browser into a static site directory
+    Web {
+        /// The simulation crate to build, e.g. triangle
+        simulation: String,
+        /// Build without optimizations and keep debug info in the bindings
+        #[arg(long)]
+        debug: bool,
+        /// Directory to write the site to, defaults to target/web/<simulation>
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().task {
+        Task::Web {
+            simulation,
+            debug,
+            out,
+        } => web(&simulation, debug, out),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("xtask: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask should be inside the workspace")
+        .to_path_buf()
+}
+
+/// Name of the wasm artifact, and the bindings wasm-bindgen generates, for a crate
+fn artifact_name(simulation: &str) -> String {
+    simulation.replace('-', "_")
+}
+
+fn profile_dir(debug: bool) -> &'static str {
+    if debug {
+        "debug"
+    } else {
+        "web-release"
+    }
+}
+
+/// Point the glue script's imports at the bindings module
+fn fill_template(template: &str, name: &str) -> String {
+    template.replace(NAME_PLACEHOLDER, name)
+}
+
+fn run(command: &mut Command) -> io::Result<()> {
+    let status = command.status().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to run {:?}: {e}", command.get_program()),
+        )
+    })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{:?} exited with {status}",
+            command.get_program()
+        )))
+    }
+}
+
+fn web(simulation: &str, debug: bool, out: Option<PathBuf>) -> io::Result<()> {
+    let root = workspace_root();
+    if !root.join(simulation).join("Cargo.toml").is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{simulation} is not a crate in the workspace"),
+        ));
+    }
+
+    let name = artifact_name(simulation);
+    let out = out.unwrap_or_else(|| root.join("target").join("web").join(&name));
+
+    let mut build = Command::new(std::env::var_os("CARGO").unwrap_or("cargo".into()));
+    build
+        .current_dir(root.join(simulation))
+        .env("RUSTFLAGS", WASM_RUSTFLAGS)
+        .args(["build", "--lib", "--target", WASM_TARGET])
+        .args(["-Z", "build-std=panic_abort,std"]);
+    if !debug {
+        build.args(["--profile", "web-release"]);
+    }
+    run(&mut build)?;
+
+    let wasm = root
+        .join("target")
+        .join(WASM_TARGET)
+        .join(profile_dir(debug))
+        .join(format!("{name}.wasm"));
+    let mut bindgen = Command::new("wasm-bindgen");
+    bindgen.args(["--no-typescript", "--target", "web"]);
+    if debug {
+        bindgen.args(["--debug", "--keep-debug"]);
+    }
+    bindgen.arg("--out-dir").arg(&out).arg(&wasm);
+    run(&mut bindgen)?;
+
+    let res = root.join("res");
+    fs::copy(res.join("common.js"), out.join("common.js"))?;
+    for glue in ["index.html", "worker.js"] {
+        let template = fs::read_to_string(res.join(glue))?;
+        fs::write(out.join(glue), fill_template(&template, &name))?;
+    }
+    // SharedArrayBuffer needs the cross origin isolation headers listed here
+    fs::copy(root.join("serve.json"), out.join("serve.json"))?;
+
+    println!(
+        "Built {simulation} into {}, serve it with the headers in serve.json, e.g. `yarn exec serve {}`",
+        out.display(),
+        out.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fills_glue_templates() {
+        let name = artifact_name("my-sim");
+        assert_eq!("my_sim", name);
+        assert_eq!(
+            "import init, { simMain } from './my_sim.js'",
+            fill_template("import init, { simMain } from './{{}}.js'", &name)
+        );
+    }
+}